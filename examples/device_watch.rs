@@ -0,0 +1,50 @@
+//! Print storage devices as they are attached, mounted, unmounted or removed, until Ctrl-C.
+//!
+//! ```text
+//! cargo run --example device_watch
+//! ```
+
+use std::{collections::HashMap, thread, time::Duration};
+use lazysmg::format::{format_size, SizeUnits};
+use lazysmg::StorageDevice;
+
+/// How often the device list is read.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn describe(device: &StorageDevice) -> String {
+    if device.mounted {
+        format!(
+            "{} at {} ({} free of {})",
+            device.name,
+            device.mount_point,
+            format_size(device.available_space, SizeUnits::Binary),
+            format_size(device.total_space, SizeUnits::Binary)
+        )
+    } else {
+        format!("{} ({}, not mounted)", device.name, device.key())
+    }
+}
+
+fn main() {
+    let mut known: HashMap<String, StorageDevice> = HashMap::new();
+    let mut first = true;
+    loop {
+        let current: HashMap<String, StorageDevice> = lazysmg::devices()
+            .into_iter()
+            .map(|device| (device.key().to_string(), device))
+            .collect();
+        for (key, device) in &current {
+            if !known.contains_key(key) {
+                println!("{} {}", if first { "present:" } else { "added:  " }, describe(device));
+            }
+        }
+        for (key, device) in &known {
+            if !current.contains_key(key) {
+                println!("removed: {}", describe(device));
+            }
+        }
+        known = current;
+        first = false;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
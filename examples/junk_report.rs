@@ -0,0 +1,25 @@
+//! Scan the junk locations of this OS (caches, logs, trash, old downloads, ...) and print the
+//! totals per category and the folders holding the most junk. Nothing is deleted.
+//!
+//! ```text
+//! cargo run --example junk_report
+//! ```
+
+use std::error::Error;
+use lazysmg::format::{format_size, SizeUnits};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let report = lazysmg::junk_report()?;
+    let size = |bytes| format_size(bytes, SizeUnits::Binary);
+
+    println!("{} of junk in {} files\n", size(report.total_size), report.total_files);
+    println!("By category:");
+    for category in report.categories.iter().filter(|category| category.file_count > 0) {
+        println!("  {:<24} {:>12}  {:>7} files", category.name, size(category.total_size), category.file_count);
+    }
+    println!("\nLargest folders:");
+    for (path, total, files) in report.folders.iter().take(10) {
+        println!("  {:>12}  {:>7} files  {}", size(*total), files, path);
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+//! Print the largest files below a directory.
+//!
+//! ```text
+//! cargo run --example largest_files -- /Volumes/Backup 20
+//! ```
+
+use lazysmg::format::{format_size, SizeUnits};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| ".".to_string());
+    let count = args.next().and_then(|n| n.parse().ok()).unwrap_or(10);
+
+    match lazysmg::top_files(&path, count) {
+        Ok(files) => {
+            for file in files {
+                println!("{:>12}  {}", format_size(file.size, SizeUnits::Binary), file.path);
+            }
+        }
+        Err(e) => {
+            eprintln!("Scanning {} failed: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,1224 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use serde::{Deserialize, Serialize};
+use crate::clock::Clock;
+
+// Pure analysis of scan results: grouping, categorizing, finding duplicates and comparing
+// scans. Nothing here touches the disk or needs an async runtime, so this module builds
+// without the `platform` feature and can back other frontends, e.g. a report viewer in wasm.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    /// Where it points, for a symlink listed as one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+    /// Bytes it takes up on disk, less than `size` for sparse and compressed files; None where
+    /// the file system doesn't report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allocated_size: Option<u64>,
+}
+
+impl FileEntry {
+    /// Its size on disk if known, else its logical size.
+    pub fn disk_usage(&self) -> u64 {
+        self.allocated_size.unwrap_or(self.size)
+    }
+}
+
+/// Totals for one junk category, sent with the junk scan results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JunkCategorySummary {
+    pub id: String,
+    pub name: String,
+    /// Junk paths (with `~` expanded) that belong to this category
+    pub roots: Vec<String>,
+    pub total_size: u64,
+    pub file_count: usize,
+}
+
+/// The category whose junk path most specifically contains `path`.
+pub fn find_category<'a>(categories: &'a [JunkCategorySummary], path: &str) -> Option<&'a JunkCategorySummary> {
+    let path = Path::new(path);
+    categories
+        .iter()
+        .flat_map(|category| category.roots.iter().map(move |root| (category, root)))
+        .filter(|(_, root)| path.starts_with(root))
+        .max_by_key(|(_, root)| Path::new(root).components().count())
+        .map(|(category, _)| category)
+}
+
+/// Results of a junk scan, grouped by directory
+#[derive(Debug, Clone)]
+pub struct JunkScanResults {
+    pub folders: HashMap<String, FolderSummary>,
+    pub total_size: u64,
+    pub total_files: usize,
+}
+
+/// Summary information for a folder with junk files
+#[derive(Debug, Clone)]
+pub struct FolderSummary {
+    // pub path: String,
+    pub files: Vec<FileEntry>,
+    pub total_size: u64,
+}
+
+impl Default for JunkScanResults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JunkScanResults {
+    pub fn new() -> Self {
+        JunkScanResults {
+            folders: HashMap::new(),
+            total_size: 0,
+            total_files: 0,
+        }
+    }
+
+    /// Add a file to the results, grouping by its parent folder
+    pub fn add_file(&mut self, file: FileEntry) {
+        // Extract parent folder path
+        let path = PathBuf::from(&file.path);
+        let parent_path = if let Some(parent) = path.parent() {
+            parent.to_string_lossy().to_string()
+        } else {
+            // If no parent, use the path itself (unlikely)
+            file.path.clone()
+        };
+
+        // Add file size to total
+        self.total_size += file.size;
+        self.total_files += 1;
+
+        // Add or update folder summary
+        let folder_summary = self.folders.entry(parent_path.clone()).or_insert_with(|| FolderSummary {
+            //path: parent_path,
+            files: Vec::new(),
+            total_size: 0,
+        });
+
+        folder_summary.total_size += file.size;
+        folder_summary.files.push(file);
+    }
+
+    /// Sort folder summaries by size (largest first)
+    pub fn sort_by_size(&mut self) {
+        // Sort files within each folder
+        for folder_summary in self.folders.values_mut() {
+            folder_summary.files.sort_by_key(|f| std::cmp::Reverse(f.size));
+        }
+    }
+
+    /// Convert results to a flat list of file entries sorted by size
+    pub fn to_file_entries(&self) -> Vec<FileEntry> {
+        let mut result = Vec::new();
+
+        for folder in self.folders.values() {
+            for file in &folder.files {
+                result.push(file.clone());
+            }
+        }
+
+        result.sort_by_key(|f| std::cmp::Reverse(f.size));
+        result
+    }
+}
+
+/// A directory or file a scan couldn't read, which leaves what is in it out of the results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: String,
+    /// What went wrong, e.g. "Permission denied (os error 13)"
+    pub message: String,
+}
+
+/// How many of `errors` each root of a scan has, most first. An error belongs to the most
+/// specific of `roots` containing it, or else to the top-level directory of `mount_point` it
+/// is in.
+pub fn errors_by_root(errors: &[ScanError], roots: &[String], mount_point: &str) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for error in errors {
+        let path = Path::new(&error.path);
+        let root = roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| Path::new(root).components().count())
+            .cloned()
+            .unwrap_or_else(|| match path.strip_prefix(mount_point).ok().and_then(|rest| rest.components().next()) {
+                Some(top) => Path::new(mount_point).join(top).to_string_lossy().into_owned(),
+                None => mount_point.to_string(),
+            });
+        *counts.entry(root).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// Total size of the files at or below `dir`.
+pub fn size_under(entries: &[FileEntry], dir: &Path) -> u64 {
+    entries
+        .iter()
+        .filter(|entry| Path::new(&entry.path).starts_with(dir))
+        .map(|entry| entry.size)
+        .sum()
+}
+
+/// A file or directory directly inside the directory `dir_usage` sums up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirUsage {
+    pub name: String,
+    pub path: String,
+    /// Total size of the files at or below it
+    pub size: u64,
+    pub file_count: usize,
+    pub is_dir: bool,
+}
+
+/// What is directly inside `dir`, with the total size of the files below each directory, largest
+/// first. Scans list files only, so directories are known from the paths of their files.
+pub fn dir_usage(entries: &[FileEntry], dir: &Path) -> Vec<DirUsage> {
+    let mut children: HashMap<String, DirUsage> = HashMap::new();
+    for entry in entries {
+        let Ok(relative) = Path::new(&entry.path).strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(first) = components.next() else {
+            continue;
+        };
+        let name = first.as_os_str().to_string_lossy().into_owned();
+        let child = children.entry(name.clone()).or_insert_with(|| DirUsage {
+            path: dir.join(&name).to_string_lossy().into_owned(),
+            name,
+            size: 0,
+            file_count: 0,
+            is_dir: false,
+        });
+        child.size += entry.size;
+        child.file_count += 1;
+        child.is_dir |= components.next().is_some();
+    }
+    let mut children: Vec<DirUsage> = children.into_values().collect();
+    children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    children
+}
+
+/// Total size and file count of the files of one kind, such as a file type or extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeUsage<K> {
+    pub kind: K,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+/// Space taken up by each kind of file, where `kind_of` tells the kind of a file or leaves it
+/// out with `None`, largest first.
+pub fn usage_by<K: Eq + Hash + Ord>(entries: &[FileEntry], kind_of: impl Fn(&FileEntry) -> Option<K>) -> Vec<TypeUsage<K>> {
+    let mut kinds: HashMap<K, (u64, usize)> = HashMap::new();
+    for entry in entries {
+        if let Some(kind) = kind_of(entry) {
+            let total = kinds.entry(kind).or_default();
+            total.0 += entry.size;
+            total.1 += 1;
+        }
+    }
+    let mut usage: Vec<TypeUsage<K>> = kinds.into_iter().map(|(kind, (size, file_count))| TypeUsage { kind, size, file_count }).collect();
+    usage.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.kind.cmp(&b.kind)));
+    usage
+}
+
+/// Size and file count of the files at or below one directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirTotal {
+    pub size: u64,
+    pub file_count: usize,
+}
+
+/// Totals of every directory of a scan. Scans that only keep their largest files collect them
+/// as they go, so directory sizes stay right without holding each file in memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirTotals(HashMap<String, DirTotal>);
+
+impl DirTotals {
+    /// Sum up the directories at or below `root` from the files of a scan that kept each one.
+    pub fn of(root: &Path, entries: &[FileEntry]) -> DirTotals {
+        let mut totals = DirTotals::default();
+        for entry in entries {
+            totals.add_file(root, Path::new(&entry.path), entry.size);
+        }
+        totals
+    }
+
+    /// Count a file of `size` at `path` in each directory above it, up to and including `root`.
+    pub fn add_file(&mut self, root: &Path, path: &Path, size: u64) {
+        for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+            let dir = dir.to_string_lossy();
+            if let Some(total) = self.0.get_mut(dir.as_ref()) {
+                total.size += size;
+                total.file_count += 1;
+            } else {
+                self.0.insert(dir.into_owned(), DirTotal { size, file_count: 1 });
+            }
+        }
+    }
+
+    pub fn get(&self, dir: &str) -> Option<DirTotal> {
+        self.0.get(dir).copied()
+    }
+
+    /// Take `path` and what is below it out of the totals, after it was deleted. A file is
+    /// looked up in the kept `entries`.
+    pub fn remove(&mut self, path: &Path, entries: &[FileEntry]) {
+        let removed = self.get(&path.to_string_lossy()).unwrap_or_else(|| DirTotal {
+            size: size_under(entries, path),
+            file_count: entries.iter().filter(|entry| Path::new(&entry.path).starts_with(path)).count(),
+        });
+        self.0.retain(|dir, _| !Path::new(dir).starts_with(path));
+        for dir in path.ancestors().skip(1) {
+            if let Some(total) = self.0.get_mut(dir.to_string_lossy().as_ref()) {
+                total.size = total.size.saturating_sub(removed.size);
+                total.file_count = total.file_count.saturating_sub(removed.file_count);
+            }
+        }
+    }
+
+    /// Move the totals of `path` and the directories below it to `new_path`, after a rename.
+    pub fn rename(&mut self, path: &Path, new_path: &Path) {
+        let moved: Vec<String> = self.0.keys().filter(|dir| Path::new(dir).starts_with(path)).cloned().collect();
+        for dir in moved {
+            if let (Some(total), Ok(rest)) = (self.0.remove(&dir), Path::new(&dir).strip_prefix(path)) {
+                self.0.insert(new_path.join(rest).to_string_lossy().into_owned(), total);
+            }
+        }
+    }
+
+    /// The `limit` directories below `root` with the most below them, largest first.
+    pub fn largest(&self, root: &Path, limit: usize) -> Vec<(String, DirTotal)> {
+        let mut dirs: Vec<(String, DirTotal)> = self
+            .0
+            .iter()
+            .filter(|(dir, _)| Path::new(dir) != root && Path::new(dir).starts_with(root))
+            .map(|(dir, total)| (dir.clone(), *total))
+            .collect();
+        dirs.sort_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(&b.0)));
+        dirs.truncate(limit);
+        dirs
+    }
+
+    /// Like `dir_usage`, from the totals of the directories in `dir` and the kept `entries`
+    /// directly in it. The files that weren't kept are summed up in one more entry.
+    pub fn usage(&self, entries: &[FileEntry], dir: &Path) -> Vec<DirUsage> {
+        let mut children: Vec<DirUsage> = self
+            .0
+            .iter()
+            .filter(|(path, _)| Path::new(path).parent() == Some(dir))
+            .map(|(path, total)| DirUsage {
+                name: Path::new(path).file_name().map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned()),
+                path: path.clone(),
+                size: total.size,
+                file_count: total.file_count,
+                is_dir: true,
+            })
+            .collect();
+        children.extend(dir_usage(entries, dir).into_iter().filter(|child| !child.is_dir));
+        let listed = children.iter().fold(DirTotal::default(), |sum, child| DirTotal {
+            size: sum.size + child.size,
+            file_count: sum.file_count + child.file_count,
+        });
+        let total = self.get(&dir.to_string_lossy()).unwrap_or_default();
+        let rest = total.file_count.saturating_sub(listed.file_count);
+        if rest > 0 {
+            children.push(DirUsage {
+                name: format!("({} smaller {})", rest, if rest == 1 { "file" } else { "files" }),
+                path: dir.to_string_lossy().into_owned(),
+                size: total.size.saturating_sub(listed.size),
+                file_count: rest,
+                is_dir: false,
+            });
+        }
+        children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        children
+    }
+}
+
+/// What keeping data costs per month, e.g. backing it up to paid storage.
+pub trait CostModel {
+    fn monthly_cost(&self, bytes: u64) -> f64;
+}
+
+/// A flat price per GB and month. Storage providers bill decimal gigabytes (10^9 bytes).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatRate {
+    pub per_gb_month: f64,
+}
+
+impl CostModel for FlatRate {
+    fn monthly_cost(&self, bytes: u64) -> f64 {
+        bytes as f64 / 1e9 * self.per_gb_month
+    }
+}
+
+/// Smallest directory worth suggesting as a junk location.
+const MIN_SUGGESTION_SIZE: u64 = 50 * 1024 * 1024;
+/// Files a directory needs before its file sizes or churn say anything.
+const MIN_SUGGESTION_FILES: usize = 200;
+/// Average file size up to which a directory of many files looks like a cache.
+const SMALL_FILE_SIZE: u64 = 16 * 1024;
+/// Share of files new or changed since the previous scan that counts as high churn.
+const HIGH_CHURN: f64 = 0.5;
+/// Suggestions made at most, the largest.
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Why a directory looks like junk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JunkHint {
+    /// Its name contains "cache", or is "tmp", "temp", "log" or "logs"
+    CacheLikeName,
+    /// Many files, small on average
+    ManySmallFiles,
+    /// Most of its files are new or changed since the previous scan
+    HighChurn,
+}
+
+impl JunkHint {
+    pub fn label(self) -> &'static str {
+        match self {
+            JunkHint::CacheLikeName => "cache-like name",
+            JunkHint::ManySmallFiles => "many small files",
+            JunkHint::HighChurn => "high churn",
+        }
+    }
+}
+
+/// A directory that isn't a junk location yet but looks like one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JunkSuggestion {
+    pub path: String,
+    pub size: u64,
+    pub file_count: usize,
+    pub hints: Vec<JunkHint>,
+}
+
+/// Whether a directory name suggests disposable contents: caches, temporary files or logs.
+fn cache_like_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains("cache") || matches!(name.trim_start_matches('.'), "tmp" | "temp" | "log" | "logs")
+}
+
+/// Directories of a scan that look like junk but aren't at or around any of the `known` junk
+/// locations, largest first. A directory with a cache-like name counts everything below it
+/// (the outermost one if they nest); other directories only the files directly in them. With
+/// the `previous` scan of the same place, directories whose files mostly changed are found too.
+/// Directories near the root (fewer than four path components, e.g. `/home/user`) are never
+/// suggested.
+pub fn suggest_junk_dirs(entries: &[FileEntry], previous: Option<&[FileEntry]>, known: &[String]) -> Vec<JunkSuggestion> {
+    #[derive(Default)]
+    struct DirStats {
+        size: u64,
+        files: usize,
+        churned: usize,
+        cache_like: bool,
+    }
+    let previous: Option<HashMap<&str, u64>> = previous.map(|files| files.iter().map(|file| (file.path.as_str(), file.size)).collect());
+    let mut dirs: HashMap<&Path, DirStats> = HashMap::new();
+    for file in entries {
+        let path = Path::new(&file.path);
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        let cache_dir = path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| dir.file_name().is_some_and(|name| cache_like_name(&name.to_string_lossy())))
+            .last();
+        let stats = dirs.entry(cache_dir.unwrap_or(parent)).or_default();
+        stats.size += file.size;
+        stats.files += 1;
+        stats.cache_like = cache_dir.is_some();
+        if previous.as_ref().is_some_and(|previous| previous.get(file.path.as_str()) != Some(&file.size)) {
+            stats.churned += 1;
+        }
+    }
+
+    let mut suggestions: Vec<JunkSuggestion> = dirs
+        .into_iter()
+        .filter(|(dir, stats)| {
+            stats.size >= MIN_SUGGESTION_SIZE
+                && dir.components().count() >= 4
+                && !known.iter().any(|root| dir.starts_with(root) || Path::new(root).starts_with(dir))
+        })
+        .filter_map(|(dir, stats)| {
+            let many_files = stats.files >= MIN_SUGGESTION_FILES;
+            let hints: Vec<JunkHint> = [
+                (JunkHint::CacheLikeName, stats.cache_like),
+                (JunkHint::ManySmallFiles, many_files && stats.size / stats.files as u64 <= SMALL_FILE_SIZE),
+                (JunkHint::HighChurn, many_files && stats.churned as f64 >= stats.files as f64 * HIGH_CHURN),
+            ]
+            .into_iter()
+            .filter_map(|(hint, applies)| applies.then_some(hint))
+            .collect();
+            (!hints.is_empty()).then(|| JunkSuggestion {
+                path: dir.to_string_lossy().into_owned(),
+                size: stats.size,
+                file_count: stats.files,
+                hints,
+            })
+        })
+        .collect();
+    // A directory inside another suggested one is covered by it
+    let paths: Vec<String> = suggestions.iter().map(|suggestion| suggestion.path.clone()).collect();
+    suggestions.retain(|suggestion| !paths.iter().any(|other| *other != suggestion.path && Path::new(&suggestion.path).starts_with(other)));
+    suggestions.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// Files that are (or may be) copies of each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Size of each file in the group
+    pub size: u64,
+    /// At least two files, sorted by path
+    pub files: Vec<FileEntry>,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed by keeping only one of the files.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Split `files` into groups of at least two by `key`, largest waste first.
+fn groups_by<K: Eq + Hash>(files: Vec<&FileEntry>, mut key: impl FnMut(&FileEntry) -> Option<K>) -> Vec<DuplicateGroup> {
+    let mut by_key: HashMap<K, Vec<FileEntry>> = HashMap::new();
+    for file in files {
+        if let Some(key) = key(file) {
+            by_key.entry(key).or_default().push(file.clone());
+        }
+    }
+    let mut groups: Vec<DuplicateGroup> = by_key
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .map(|mut files| {
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            DuplicateGroup { size: files[0].size, files }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.files[0].path.cmp(&b.files[0].path)));
+    groups
+}
+
+/// Non-empty files that share their size with another file, the cheap first pass of finding
+/// duplicates: files of different sizes can't be copies, files of the same size only may be.
+pub fn duplicate_candidates(entries: &[FileEntry]) -> Vec<DuplicateGroup> {
+    groups_by(entries.iter().filter(|entry| entry.size > 0).collect(), |entry| Some(entry.size))
+}
+
+/// Duplicates by content: the candidates of equal size, split further by `content_key`, e.g. a
+/// hash of the file computed by the caller. Only candidates are passed to `content_key`;
+/// files it returns `None` for (unreadable ones) are left out.
+pub fn group_duplicates<K: Eq + Hash>(
+    entries: &[FileEntry],
+    mut content_key: impl FnMut(&FileEntry) -> Option<K>,
+) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = duplicate_candidates(entries)
+        .into_iter()
+        .flat_map(|candidates| groups_by(candidates.files.iter().collect(), &mut content_key))
+        .collect();
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.files[0].path.cmp(&b.files[0].path)));
+    groups
+}
+
+/// Which copy of a group of duplicates to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeepRule {
+    /// The one modified last
+    Newest,
+    /// One below this directory, the newest if there are several; groups without a copy there
+    /// are left alone
+    Under(PathBuf),
+}
+
+/// A copy to delete, and the one kept in its place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateRemoval {
+    pub keep: String,
+    pub remove: FileEntry,
+}
+
+/// What resolving groups of duplicates by a `KeepRule` would delete, before anything is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicatePlan {
+    pub removals: Vec<DuplicateRemoval>,
+    /// Bytes freed by the removals
+    pub freed: u64,
+    /// Groups the rule keeps no copy of, left as they are
+    pub skipped: usize,
+}
+
+/// Plan keeping one copy of each of `groups` by `rule` and deleting the rest. `modified` looks
+/// up when a file was last modified; ties go to the first file by path.
+pub fn plan_duplicate_removal(groups: &[DuplicateGroup], rule: &KeepRule, modified: impl Fn(&FileEntry) -> Option<SystemTime>) -> DuplicatePlan {
+    let mut plan = DuplicatePlan::default();
+    for group in groups {
+        let kept = group
+            .files
+            .iter()
+            .filter(|file| match rule {
+                KeepRule::Newest => true,
+                KeepRule::Under(dir) => Path::new(&file.path).starts_with(dir),
+            })
+            .fold(None, |newest: Option<&FileEntry>, file| match newest {
+                Some(newest) if modified(newest) >= modified(file) => Some(newest),
+                _ => Some(file),
+            });
+        let Some(kept) = kept else {
+            plan.skipped += 1;
+            continue;
+        };
+        for file in group.files.iter().filter(|file| file.path != kept.path) {
+            plan.freed += file.size;
+            plan.removals.push(DuplicateRemoval { keep: kept.path.clone(), remove: file.clone() });
+        }
+    }
+    plan
+}
+
+/// Where a candidate for freeing space comes from, in the order they are offered: the safest
+/// to delete first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GoalSource {
+    /// Files of a junk category, which apps recreate as needed
+    Junk,
+    /// A copy of a file kept elsewhere
+    Duplicate,
+    /// A download untouched for a while
+    OldDownload,
+    /// A large file nobody read or changed for a while
+    ColdFile,
+}
+
+impl GoalSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            GoalSource::Junk => "junk",
+            GoalSource::Duplicate => "duplicate",
+            GoalSource::OldDownload => "old download",
+            GoalSource::ColdFile => "cold file",
+        }
+    }
+}
+
+/// Files that can go together to free space, such as one junk category or one duplicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoalCandidate {
+    pub source: GoalSource,
+    /// What it is, e.g. the category name or the path
+    pub label: String,
+    pub files: Vec<FileEntry>,
+    /// For a duplicate, the path of the copy it leaves
+    pub keep: Option<String>,
+}
+
+impl GoalCandidate {
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+/// Candidates for freeing `needed` bytes, with those the user accepted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoalPlan {
+    pub needed: u64,
+    pub candidates: Vec<GoalCandidate>,
+    /// Whether each of `candidates` is accepted
+    pub accepted: Vec<bool>,
+}
+
+impl GoalPlan {
+    /// A plan offering `candidates` by source, then largest first, none accepted yet. A file
+    /// offered twice, say an old download that is also a duplicate, stays with the first
+    /// candidate; candidates left without files are dropped.
+    pub fn new(needed: u64, mut candidates: Vec<GoalCandidate>) -> GoalPlan {
+        candidates.sort_by(|a, b| a.source.cmp(&b.source).then_with(|| b.size().cmp(&a.size())).then_with(|| a.label.cmp(&b.label)));
+        let mut claimed = HashSet::new();
+        for candidate in &mut candidates {
+            candidate.files.retain(|file| claimed.insert(file.path.clone()));
+        }
+        candidates.retain(|candidate| !candidate.files.is_empty());
+        let accepted = vec![false; candidates.len()];
+        GoalPlan { needed, candidates, accepted }
+    }
+
+    /// Bytes the accepted candidates free.
+    pub fn accepted_size(&self) -> u64 {
+        self.candidates.iter().zip(&self.accepted).filter(|(_, accepted)| **accepted).map(|(candidate, _)| candidate.size()).sum()
+    }
+
+    pub fn is_met(&self) -> bool {
+        self.accepted_size() >= self.needed
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(accepted) = self.accepted.get_mut(index) {
+            *accepted = !*accepted;
+        }
+    }
+
+    /// Accept candidates in the order offered until the goal is met, or all of them if it
+    /// can't be.
+    pub fn accept_until_met(&mut self) {
+        for index in 0..self.candidates.len() {
+            if self.is_met() {
+                break;
+            }
+            self.accepted[index] = true;
+        }
+    }
+
+    /// The accepted candidates.
+    pub fn accepted(&self) -> impl Iterator<Item = &GoalCandidate> {
+        self.candidates.iter().zip(&self.accepted).filter(|(_, accepted)| **accepted).map(|(candidate, _)| candidate)
+    }
+}
+
+/// Differences between two scans of the same place, matched by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanDiff {
+    /// Files only in the new scan, largest first
+    pub added: Vec<FileEntry>,
+    /// Files only in the old scan, largest first
+    pub removed: Vec<FileEntry>,
+    /// Files in both scans whose size changed, as (old, new), largest change first
+    pub changed: Vec<(FileEntry, FileEntry)>,
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// How many bytes the new scan has more (or, if negative, less) than the old one.
+    pub fn size_delta(&self) -> i64 {
+        let added: i64 = self.added.iter().map(|file| file.size as i64).sum();
+        let removed: i64 = self.removed.iter().map(|file| file.size as i64).sum();
+        let changed: i64 = self.changed.iter().map(|(old, new)| new.size as i64 - old.size as i64).sum();
+        added - removed + changed
+    }
+}
+
+/// Compare two scans; neither needs to be sorted.
+pub fn diff_entries(old: &[FileEntry], new: &[FileEntry]) -> ScanDiff {
+    let old_by_path: HashMap<&str, &FileEntry> = old.iter().map(|file| (file.path.as_str(), file)).collect();
+    let new_by_path: HashMap<&str, &FileEntry> = new.iter().map(|file| (file.path.as_str(), file)).collect();
+    let mut diff = ScanDiff::default();
+    for file in new {
+        match old_by_path.get(file.path.as_str()) {
+            None => diff.added.push(file.clone()),
+            Some(before) if before.size != file.size => diff.changed.push(((*before).clone(), file.clone())),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old.iter().filter(|file| !new_by_path.contains_key(file.path.as_str())).cloned().collect();
+    diff.added.sort_by_key(|file| std::cmp::Reverse(file.size));
+    diff.removed.sort_by_key(|file| std::cmp::Reverse(file.size));
+    diff.changed.sort_by_key(|(old, new)| std::cmp::Reverse(old.size.abs_diff(new.size)));
+    diff
+}
+
+/// How much each file and directory grew or shrank since an earlier scan of the same place, to
+/// badge the entries of a listing with. Unchanged paths aren't in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeDeltas(HashMap<String, i64>);
+
+impl SizeDeltas {
+    /// Changes from `old` to `new` of the files and of the directories above them, up to and
+    /// including `root`.
+    pub fn between(root: &Path, old: &[FileEntry], new: &[FileEntry]) -> SizeDeltas {
+        let diff = diff_entries(old, new);
+        let mut deltas = SizeDeltas::default();
+        for file in &diff.added {
+            deltas.add(root, Path::new(&file.path), file.size as i64);
+        }
+        // A removed file isn't listed any more, only the directories it was in shrink
+        for file in &diff.removed {
+            if let Some(dir) = Path::new(&file.path).parent() {
+                deltas.add(root, dir, -(file.size as i64));
+            }
+        }
+        for (before, after) in &diff.changed {
+            deltas.add(root, Path::new(&after.path), after.size as i64 - before.size as i64);
+        }
+        deltas.0.retain(|_, delta| *delta != 0);
+        deltas
+    }
+
+    fn add(&mut self, root: &Path, path: &Path, delta: i64) {
+        for path in path.ancestors().take_while(|path| path.starts_with(root)) {
+            *self.0.entry(path.to_string_lossy().into_owned()).or_default() += delta;
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<i64> {
+        self.0.get(path).copied()
+    }
+
+    /// Order `items` by how much they grew, most first and the ones that shrank most last,
+    /// then by size. `path_and_size` tells where an item is and how large.
+    pub fn sort<T>(&self, items: &mut [T], path_and_size: impl Fn(&T) -> (&str, u64)) {
+        items.sort_by(|a, b| {
+            let (a_path, a_size) = path_and_size(a);
+            let (b_path, b_size) = path_and_size(b);
+            let delta = |path| self.get(path).unwrap_or(0);
+            delta(b_path).cmp(&delta(a_path)).then_with(|| b_size.cmp(&a_size))
+        });
+    }
+}
+
+/// What happened to a path between two scans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A file only in the new scan
+    Added,
+    /// A file only in the old scan
+    Removed,
+    Grown,
+    Shrunk,
+}
+
+/// A file or directory that changed between two scans of the same place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathChange {
+    pub path: String,
+    pub is_dir: bool,
+    pub kind: ChangeKind,
+    /// Bytes it has more, or if negative less, than before
+    pub delta: i64,
+}
+
+/// The files that appeared, disappeared or changed size between two scans of `root`, and the
+/// directories below it that grew or shrank, the largest changes first.
+pub fn changes_between(root: &Path, old: &[FileEntry], new: &[FileEntry]) -> Vec<PathChange> {
+    let diff = diff_entries(old, new);
+    let file = |file: &FileEntry, kind, delta| PathChange { path: file.path.clone(), is_dir: false, kind, delta };
+    let mut changes: Vec<PathChange> = diff
+        .added
+        .iter()
+        .map(|added| file(added, ChangeKind::Added, added.size as i64))
+        .chain(diff.removed.iter().map(|removed| file(removed, ChangeKind::Removed, -(removed.size as i64))))
+        .chain(diff.changed.iter().map(|(before, after)| {
+            let delta = after.size as i64 - before.size as i64;
+            file(after, if delta > 0 { ChangeKind::Grown } else { ChangeKind::Shrunk }, delta)
+        }))
+        .collect();
+    // Removed files only count toward their directories, the others are listed already
+    let files: HashSet<&str> = diff.added.iter().chain(diff.changed.iter().map(|(_, after)| after)).map(|file| file.path.as_str()).collect();
+    let root_path = root.to_string_lossy();
+    let deltas = SizeDeltas::between(root, old, new);
+    changes.extend(deltas.0.into_iter().filter(|(path, _)| *path != root_path && !files.contains(path.as_str())).map(|(path, delta)| {
+        PathChange { path, is_dir: true, kind: if delta > 0 { ChangeKind::Grown } else { ChangeKind::Shrunk }, delta }
+    }));
+    changes.sort_by(|a, b| b.delta.unsigned_abs().cmp(&a.delta.unsigned_abs()).then_with(|| a.path.cmp(&b.path)));
+    changes
+}
+
+/// Lets through files that weren't modified for some time, e.g. the `min_age_days` of a junk
+/// path. The cutoff is taken from a `Clock` once, so a scan judges all its files alike.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AgeFilter {
+    cutoff: Option<SystemTime>,
+}
+
+impl AgeFilter {
+    /// Files last modified at least `min_age` before the clock's time pass; without a
+    /// `min_age` every file does.
+    pub fn new(min_age: Option<Duration>, clock: &dyn Clock) -> AgeFilter {
+        AgeFilter { cutoff: min_age.and_then(|age| clock.now().checked_sub(age)) }
+    }
+
+    /// Whether a file last modified at `modified` passes. Files with an unknown modification
+    /// time are treated as fresh, so they're never suggested for deletion.
+    pub fn admits(&self, modified: Option<SystemTime>) -> bool {
+        match self.cutoff {
+            None => true,
+            Some(cutoff) => modified.is_some_and(|modified| modified < cutoff),
+        }
+    }
+}
+
+/// A large file nobody read or changed for a while, a candidate for archiving.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleFile {
+    pub path: String,
+    pub size: u64,
+    /// The later of its last access and last modification
+    pub last_used: SystemTime,
+}
+
+/// The files of at least `min_size` in `entries` whose last use `filter` lets through, largest
+/// first. `times` looks up when a file was last modified and last read; a file with neither
+/// known is left out, as `AgeFilter` treats it as fresh.
+pub fn stale_files(
+    entries: &[FileEntry],
+    min_size: u64,
+    filter: AgeFilter,
+    times: impl Fn(&FileEntry) -> (Option<SystemTime>, Option<SystemTime>),
+) -> Vec<StaleFile> {
+    let mut stale: Vec<StaleFile> = entries
+        .iter()
+        .filter(|entry| entry.size >= min_size)
+        .filter_map(|entry| {
+            let (modified, accessed) = times(entry);
+            let last_used = modified.max(accessed).filter(|last_used| filter.admits(Some(*last_used)))?;
+            Some(StaleFile { path: entry.path.clone(), size: entry.size, last_used })
+        })
+        .collect();
+    stale.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    stale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> FileEntry {
+        let name = Path::new(path).file_name().unwrap().to_string_lossy().into_owned();
+        FileEntry { name, path: path.to_string(), size, link_target: None, allocated_size: None }
+    }
+
+    #[test]
+    fn dir_usage_sums_up_children() {
+        let files = [
+            file("/mnt/dïsk/photos/2024/a.jpg", 300),
+            file("/mnt/dïsk/photos/2024/b.jpg", 200),
+            file("/mnt/dïsk/photos/c.jpg", 100),
+            file("/mnt/dïsk/notes.txt", 700),
+            file("/mnt/dïsk/empty/z", 0),
+            file("/mnt/other/x", 5000),
+        ];
+        let usage = |dir: &str| -> Vec<(String, u64, usize, bool)> {
+            dir_usage(&files, Path::new(dir)).into_iter().map(|child| (child.name, child.size, child.file_count, child.is_dir)).collect()
+        };
+        assert_eq!(
+            usage("/mnt/dïsk"),
+            vec![
+                ("notes.txt".to_string(), 700, 1, false),
+                ("photos".to_string(), 600, 3, true),
+                ("empty".to_string(), 0, 1, true),
+            ]
+        );
+        assert_eq!(usage("/mnt/dïsk/photos"), vec![("2024".to_string(), 500, 2, true), ("c.jpg".to_string(), 100, 1, false)]);
+        assert_eq!(dir_usage(&files, Path::new("/mnt/dïsk/photos"))[0].path, "/mnt/dïsk/photos/2024");
+        assert!(usage("/mnt/dïsk/notes.txt").is_empty());
+    }
+
+    #[test]
+    fn usage_by_sums_up_kinds() {
+        let files = [
+            file("/mnt/dïsk/a.JPG", 300),
+            file("/mnt/dïsk/b.jpg", 200),
+            file("/mnt/dïsk/c.png", 500),
+            file("/mnt/dïsk/Makefile", 50),
+            file("/mnt/dïsk/d.mov", 500),
+        ];
+        let extension = |entry: &FileEntry| Path::new(&entry.path).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        let usage: Vec<(String, u64, usize)> = usage_by(&files, extension).into_iter().map(|usage| (usage.kind, usage.size, usage.file_count)).collect();
+        assert_eq!(
+            usage,
+            vec![("jpg".to_string(), 500, 2), ("mov".to_string(), 500, 1), ("png".to_string(), 500, 1)]
+        );
+        assert!(usage_by(&files, |_| None::<String>).is_empty());
+    }
+
+    #[test]
+    fn errors_count_per_root() {
+        let error = |path: &str| ScanError { path: path.to_string(), message: "Permission denied (os error 13)".to_string() };
+        let errors = [
+            error("/mnt/dïsk/private"),
+            error("/mnt/dïsk/photos/2024/locked"),
+            error("/mnt/dïsk/photos/raw"),
+            error("/mnt/dïsk/cache/app/x"),
+            error("/mnt/dïsk"),
+        ];
+        let roots = ["/mnt/dïsk/cache".to_string(), "/mnt/dïsk/cache/app".to_string()];
+        assert_eq!(
+            errors_by_root(&errors, &roots, "/mnt/dïsk"),
+            vec![
+                ("/mnt/dïsk/photos".to_string(), 2),
+                ("/mnt/dïsk".to_string(), 1),
+                ("/mnt/dïsk/cache/app".to_string(), 1),
+                ("/mnt/dïsk/private".to_string(), 1),
+            ]
+        );
+        assert!(errors_by_root(&[], &roots, "/").is_empty());
+    }
+
+    #[test]
+    fn dir_totals_sum_up_files_not_kept() {
+        let root = Path::new("/mnt/dïsk");
+        let all = [
+            file("/mnt/dïsk/photos/2024/a.jpg", 300),
+            file("/mnt/dïsk/photos/c.jpg", 100),
+            file("/mnt/dïsk/notes.txt", 700),
+            file("/mnt/dïsk/small.txt", 5),
+            file("/mnt/dïsk/tiny.txt", 1),
+        ];
+        let mut totals = DirTotals::default();
+        for entry in &all {
+            totals.add_file(root, Path::new(&entry.path), entry.size);
+        }
+        // Only the two largest files were kept
+        let kept = &[all[0].clone(), all[2].clone()];
+        let usage = |totals: &DirTotals, dir: &str| -> Vec<(String, u64, usize, bool)> {
+            totals.usage(kept, Path::new(dir)).into_iter().map(|child| (child.name, child.size, child.file_count, child.is_dir)).collect()
+        };
+        assert_eq!(totals.get("/mnt/dïsk"), Some(DirTotal { size: 1106, file_count: 5 }));
+        assert_eq!(totals.get("/mnt"), None);
+        assert_eq!(
+            usage(&totals, "/mnt/dïsk"),
+            vec![
+                ("notes.txt".to_string(), 700, 1, false),
+                ("photos".to_string(), 400, 2, true),
+                ("(2 smaller files)".to_string(), 6, 2, false),
+            ]
+        );
+        assert_eq!(usage(&totals, "/mnt/dïsk/photos"), vec![("2024".to_string(), 300, 1, true), ("(1 smaller file)".to_string(), 100, 1, false)]);
+
+        totals.remove(Path::new("/mnt/dïsk/photos/2024"), kept);
+        assert_eq!(totals.get("/mnt/dïsk/photos/2024"), None);
+        assert_eq!(totals.get("/mnt/dïsk/photos"), Some(DirTotal { size: 100, file_count: 1 }));
+        totals.remove(Path::new("/mnt/dïsk/notes.txt"), kept);
+        assert_eq!(totals.get("/mnt/dïsk"), Some(DirTotal { size: 106, file_count: 3 }));
+    }
+
+    #[test]
+    fn largest_dirs_rank_below_root() {
+        let root = Path::new("/mnt/dïsk");
+        let files = [
+            file("/mnt/dïsk/photos/2024/a.jpg", 300),
+            file("/mnt/dïsk/photos/2024/b.jpg", 200),
+            file("/mnt/dïsk/photos/c.jpg", 100),
+            file("/mnt/dïsk/cache/x/1", 250),
+            file("/mnt/dïsk/cache/y/2", 250),
+            file("/mnt/dïsk/notes.txt", 700),
+        ];
+        let totals = DirTotals::of(root, &files);
+        assert_eq!(
+            totals.largest(root, 3),
+            vec![
+                ("/mnt/dïsk/photos".to_string(), DirTotal { size: 600, file_count: 3 }),
+                ("/mnt/dïsk/cache".to_string(), DirTotal { size: 500, file_count: 2 }),
+                ("/mnt/dïsk/photos/2024".to_string(), DirTotal { size: 500, file_count: 2 }),
+            ]
+        );
+        assert!(totals.largest(Path::new("/mnt/dïsk/photos/2024"), 50).is_empty());
+    }
+
+    #[test]
+    fn find_category_prefers_most_specific_root() {
+        let category = |id: &str, root: &str| JunkCategorySummary {
+            id: id.to_string(),
+            name: id.to_string(),
+            roots: vec![root.to_string()],
+            total_size: 0,
+            file_count: 0,
+        };
+        let categories = vec![category("caches", "/home/u/.cache"), category("browser", "/home/u/.cache/firefox")];
+        let find = |path| find_category(&categories, path).map(|category| category.id.as_str());
+        assert_eq!(find("/home/u/.cache/firefox/a"), Some("browser"));
+        assert_eq!(find("/home/u/.cache/pip/b"), Some("caches"));
+        assert_eq!(find("/home/u/.cachex/c"), None);
+    }
+
+    #[test]
+    fn junk_results_group_by_folder() {
+        let mut results = JunkScanResults::new();
+        results.add_file(file("/a/x", 1));
+        results.add_file(file("/a/y", 3));
+        results.add_file(file("/b/z", 2));
+        assert_eq!((results.total_size, results.total_files), (6, 3));
+        assert_eq!(results.folders["/a"].total_size, 4);
+        let sizes: Vec<u64> = results.to_file_entries().iter().map(|file| file.size).collect();
+        assert_eq!(sizes, vec![3, 2, 1]);
+        assert_eq!(size_under(&results.to_file_entries(), Path::new("/a")), 4);
+    }
+
+    #[test]
+    fn duplicates_need_equal_size_and_content() {
+        let entries = vec![file("/a", 10), file("/b", 10), file("/c", 10), file("/d", 5), file("/e", 0), file("/f", 0)];
+        let candidates = duplicate_candidates(&entries);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].files.len(), 3);
+        assert_eq!(candidates[0].wasted(), 20);
+
+        // "/c" differs in content, "/d" is never asked about
+        let groups = group_duplicates(&entries, |file| {
+            assert_ne!(file.path, "/d");
+            Some(file.path == "/c")
+        });
+        assert_eq!(groups, vec![DuplicateGroup { size: 10, files: vec![file("/a", 10), file("/b", 10)] }]);
+    }
+
+    #[test]
+    fn duplicate_plans_keep_one_copy_by_rule() {
+        let groups = vec![
+            DuplicateGroup { size: 10, files: vec![file("/a/1", 10), file("/b/1", 10), file("/c/1", 10)] },
+            DuplicateGroup { size: 5, files: vec![file("/a/2", 5), file("/c/2", 5)] },
+        ];
+        let modified = |file: &FileEntry| match file.path.as_str() {
+            "/b/1" => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(20)),
+            "/c/1" => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(10)),
+            _ => None,
+        };
+        fn removed(plan: &DuplicatePlan) -> Vec<(&str, &str)> {
+            plan.removals.iter().map(|removal| (removal.keep.as_str(), removal.remove.path.as_str())).collect()
+        }
+
+        let newest = plan_duplicate_removal(&groups, &KeepRule::Newest, modified);
+        assert_eq!(removed(&newest), [("/b/1", "/a/1"), ("/b/1", "/c/1"), ("/a/2", "/c/2")]);
+        assert_eq!((newest.freed, newest.skipped), (25, 0));
+
+        let under = plan_duplicate_removal(&groups, &KeepRule::Under(PathBuf::from("/c")), modified);
+        assert_eq!(removed(&under), [("/c/1", "/a/1"), ("/c/1", "/b/1"), ("/c/2", "/a/2")]);
+
+        let under = plan_duplicate_removal(&groups, &KeepRule::Under(PathBuf::from("/b")), modified);
+        assert_eq!(removed(&under), [("/b/1", "/a/1"), ("/b/1", "/c/1")]);
+        assert_eq!((under.freed, under.skipped), (20, 1));
+    }
+
+    #[test]
+    fn goal_plans_offer_safe_sources_first_and_each_file_once() {
+        let candidate = |source, label: &str, files: Vec<FileEntry>| GoalCandidate { source, label: label.to_string(), files, keep: None };
+        let mut plan = GoalPlan::new(
+            100,
+            vec![
+                candidate(GoalSource::ColdFile, "/d/movie.mkv", vec![file("/d/movie.mkv", 80)]),
+                candidate(GoalSource::OldDownload, "/d/movie.mkv", vec![file("/d/movie.mkv", 80)]),
+                candidate(GoalSource::Junk, "Caches", vec![file("/c/a", 30), file("/c/b", 20)]),
+                candidate(GoalSource::ColdFile, "/e/big.iso", vec![file("/e/big.iso", 200)]),
+            ],
+        );
+        let labels: Vec<(GoalSource, &str)> = plan.candidates.iter().map(|candidate| (candidate.source, candidate.label.as_str())).collect();
+        assert_eq!(labels, [(GoalSource::Junk, "Caches"), (GoalSource::OldDownload, "/d/movie.mkv"), (GoalSource::ColdFile, "/e/big.iso")]);
+        assert!(!plan.is_met());
+
+        plan.accept_until_met();
+        assert_eq!(plan.accepted, [true, true, false]);
+        assert_eq!(plan.accepted_size(), 130);
+        plan.toggle(1);
+        assert!(!plan.is_met());
+        assert_eq!(plan.accepted().count(), 1);
+    }
+
+    #[test]
+    fn diff_matches_files_by_path() {
+        let old = vec![file("/same", 1), file("/grown", 2), file("/gone", 4)];
+        let new = vec![file("/same", 1), file("/grown", 10), file("/new", 3)];
+        let diff = diff_entries(&old, &new);
+        assert_eq!(diff.added, vec![file("/new", 3)]);
+        assert_eq!(diff.removed, vec![file("/gone", 4)]);
+        assert_eq!(diff.changed, vec![(file("/grown", 2), file("/grown", 10))]);
+        assert_eq!(diff.size_delta(), 3 - 4 + 8);
+        assert!(diff_entries(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn size_deltas_reach_up_to_root() {
+        let root = Path::new("/mnt/dïsk");
+        let old = vec![file("/mnt/dïsk/a/same", 1), file("/mnt/dïsk/a/grown", 2), file("/mnt/dïsk/b/gone", 4)];
+        let new = vec![file("/mnt/dïsk/a/same", 1), file("/mnt/dïsk/a/grown", 10), file("/mnt/dïsk/b/new", 4)];
+        let deltas = SizeDeltas::between(root, &old, &new);
+        assert_eq!(deltas.get("/mnt/dïsk/a/grown"), Some(8));
+        assert_eq!(deltas.get("/mnt/dïsk/a"), Some(8));
+        assert_eq!(deltas.get("/mnt/dïsk/b/new"), Some(4));
+        // Gone and new files of the same size leave b where it was
+        assert_eq!(deltas.get("/mnt/dïsk/b"), None);
+        assert_eq!(deltas.get("/mnt/dïsk/b/gone"), None);
+        assert_eq!(deltas.get("/mnt/dïsk"), Some(8));
+        assert_eq!(deltas.get("/mnt"), None);
+
+        let mut entries = new.clone();
+        entries.push(file("/mnt/dïsk/b/shrunk", 50));
+        let deltas = SizeDeltas::between(root, &[file("/mnt/dïsk/b/shrunk", 80)], &entries);
+        deltas.sort(&mut entries, |entry| (entry.path.as_str(), entry.size));
+        let order: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(order, vec!["/mnt/dïsk/a/grown", "/mnt/dïsk/b/new", "/mnt/dïsk/a/same", "/mnt/dïsk/b/shrunk"]);
+    }
+
+    #[test]
+    fn changes_list_files_and_directories_by_size() {
+        let root = Path::new("/mnt/dïsk");
+        let old = vec![file("/mnt/dïsk/a/same", 1), file("/mnt/dïsk/a/grown", 2), file("/mnt/dïsk/b/gone", 40)];
+        let new = vec![file("/mnt/dïsk/a/same", 1), file("/mnt/dïsk/a/grown", 10), file("/mnt/dïsk/c/new", 30)];
+        let changes = changes_between(root, &old, &new);
+        let summary: Vec<(&str, bool, ChangeKind, i64)> = changes.iter().map(|change| (change.path.as_str(), change.is_dir, change.kind, change.delta)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/mnt/dïsk/b", true, ChangeKind::Shrunk, -40),
+                ("/mnt/dïsk/b/gone", false, ChangeKind::Removed, -40),
+                ("/mnt/dïsk/c", true, ChangeKind::Grown, 30),
+                ("/mnt/dïsk/c/new", false, ChangeKind::Added, 30),
+                ("/mnt/dïsk/a", true, ChangeKind::Grown, 8),
+                ("/mnt/dïsk/a/grown", false, ChangeKind::Grown, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn age_filter_uses_the_clock() {
+        use crate::clock::FixedClock;
+        use crate::filesystem::{FileSystem, MemoryFileSystem};
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::UNIX_EPOCH + 1000 * day;
+        let mut fs = MemoryFileSystem::default();
+        fs.add_file("/old", 1, Some(now - 40 * day));
+        fs.add_file("/new", 1, Some(now - 2 * day));
+        fs.add_file("/unknown", 1, None);
+        let admitted = |filter: AgeFilter| {
+            ["/old", "/new", "/unknown"].into_iter().filter(|path| filter.admits(fs.file_info(Path::new(path)).unwrap().modified)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(admitted(AgeFilter::new(Some(30 * day), &FixedClock(now))), vec!["/old"]);
+        // The same files a year later
+        assert_eq!(admitted(AgeFilter::new(Some(30 * day), &FixedClock(now + 365 * day))), vec!["/old", "/new"]);
+        assert_eq!(admitted(AgeFilter::new(None, &FixedClock(now))), vec!["/old", "/new", "/unknown"]);
+        assert!(fs.file_info(Path::new("/missing")).is_err());
+    }
+
+    #[test]
+    fn stale_files_were_neither_read_nor_changed() {
+        use crate::clock::FixedClock;
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::UNIX_EPOCH + 1000 * day;
+        let times: HashMap<&str, (Option<SystemTime>, Option<SystemTime>)> = HashMap::from([
+            ("/old.iso", (Some(now - 500 * day), Some(now - 400 * day))),
+            ("/read.mov", (Some(now - 500 * day), Some(now - 3 * day))),
+            ("/old-small.txt", (Some(now - 500 * day), None)),
+            ("/older.dmg", (Some(now - 800 * day), None)),
+            ("/unknown.bin", (None, None)),
+        ]);
+        let entries = [file("/old.iso", 200), file("/read.mov", 500), file("/old-small.txt", 10), file("/older.dmg", 300), file("/unknown.bin", 900)];
+        let filter = AgeFilter::new(Some(365 * day), &FixedClock(now));
+        let stale = stale_files(&entries, 100, filter, |entry| times[entry.path.as_str()]);
+        assert_eq!(
+            stale,
+            vec![
+                StaleFile { path: "/older.dmg".to_string(), size: 300, last_used: now - 800 * day },
+                StaleFile { path: "/old.iso".to_string(), size: 200, last_used: now - 400 * day },
+            ]
+        );
+    }
+}
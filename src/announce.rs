@@ -0,0 +1,177 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    sync::mpsc::{self, SyncSender},
+    thread,
+};
+use expanduser::expanduser;
+use crate::config::AccessibilityConfig;
+use crate::format::format_size;
+use crate::mode::FileOperation;
+use crate::{App, AppMode, PanelFocus};
+
+/// Announcements kept for a FIFO nobody reads yet; further ones are dropped rather than
+/// holding up the UI.
+const BACKLOG: usize = 64;
+
+/// Tells assistive tools what changes on screen, one line at a time: the selected device or
+/// file, the popup or task shown, notifications and finished scans. Lines go to a FIFO, written
+/// by a thread of its own since opening one waits for a reader, and/or to the terminal as OSC 9
+/// notifications.
+pub struct Announcer {
+    fifo: Option<SyncSender<String>>,
+    osc: bool,
+    selection: String,
+    status: String,
+    notification: Option<String>,
+}
+
+impl Announcer {
+    /// The announcer `config` asks for, or None if announcements are off.
+    pub fn new(config: &AccessibilityConfig) -> io::Result<Option<Announcer>> {
+        let fifo = match &config.announce_fifo {
+            Some(path) => {
+                let path = expanduser(path)?;
+                let (tx, rx) = mpsc::sync_channel::<String>(BACKLOG);
+                thread::spawn(move || {
+                    // Reopened when the reader goes away, so the next one picks up from there
+                    let mut out = None;
+                    for line in rx {
+                        if out.is_none() {
+                            out = OpenOptions::new().create(true).append(true).open(&path).ok();
+                        }
+                        if let Some(file) = out.as_mut()
+                            && writeln!(file, "{}", line).and_then(|_| file.flush()).is_err()
+                        {
+                            out = None;
+                        }
+                    }
+                });
+                Some(tx)
+            }
+            None => None,
+        };
+        if fifo.is_none() && !config.announce_osc {
+            return Ok(None);
+        }
+        Ok(Some(Announcer { fifo, osc: config.announce_osc, selection: String::new(), status: String::new(), notification: None }))
+    }
+
+    /// Announce `text` right away.
+    pub fn say(&mut self, text: &str) {
+        // Control characters would end the line or the escape sequence early
+        let text: String = text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+        if let Some(fifo) = &self.fifo {
+            let _ = fifo.try_send(text.clone());
+        }
+        if self.osc {
+            let mut stdout = io::stdout();
+            let _ = write!(stdout, "\x1b]9;{}\x07", text).and_then(|_| stdout.flush());
+        }
+    }
+
+    /// Announce what changed on screen since the last call.
+    pub fn observe(&mut self, app: &App, mode: &AppMode) {
+        let status = status(app, mode);
+        if status != self.status {
+            if !status.is_empty() {
+                self.say(&status);
+            }
+            self.status = status;
+        }
+        if app.notification != self.notification {
+            if let Some(notification) = &app.notification {
+                self.say(notification);
+            }
+            self.notification = app.notification.clone();
+        }
+        let selection = selection(app);
+        if selection != self.selection {
+            if !selection.is_empty() {
+                self.say(&selection);
+            }
+            self.selection = selection;
+        }
+    }
+}
+
+/// The device or file selected in the focused panel.
+fn selection(app: &App) -> String {
+    let units = app.config.display.size_units;
+    match app.focus {
+        PanelFocus::Left => app.devices.get(app.selected).map_or_else(String::new, |device| {
+            format!("selected device {}, {} free of {}", device.name, format_size(device.available_space, units), format_size(device.total_space, units))
+        }),
+        PanelFocus::Right => app
+            .get_selected_file_entry()
+            .map_or_else(String::new, |file| format!("selected file {}, {}", file.name, format_size(file.size, units))),
+    }
+}
+
+/// What the popup or the running task of `mode` says; empty for the modes that only change
+/// how the panels are navigated.
+fn status(app: &App, mode: &AppMode) -> String {
+    let device = |index: usize| app.devices.get(index).map_or("device", |device| device.name.as_str());
+    match mode {
+        AppMode::Scanning { device_index, .. } => format!("listing {}", device(*device_index)),
+        AppMode::FullScan { device_index, .. } => format!("scanning {}", device(*device_index)),
+        AppMode::Working(description) => description.clone(),
+        AppMode::Ejected(message) => message.clone(),
+        AppMode::EjectFailed { device_index, error, .. } => format!("ejecting {} failed: {}", device(*device_index), error),
+        AppMode::ConfirmEject(index) => match app.devices.get(*index).and_then(|device| device.trash_size).filter(|size| *size > 0) {
+            Some(trash) => format!("confirm eject of {}, which still holds {} of trashed files", device(*index), format_size(trash, app.config.display.size_units)),
+            None => format!("confirm eject of {}", device(*index)),
+        },
+        AppMode::ConfirmEjectAll => "confirm eject of all devices".to_string(),
+        AppMode::ConfirmEmptyTrash(index) => format!("confirm emptying the trash of {}", device(*index)),
+        AppMode::ConfirmFileOp { op_type, .. } => {
+            let op = match op_type {
+                FileOperation::Copy => "copy",
+                FileOperation::Move => "move",
+                FileOperation::Delete => "delete",
+                FileOperation::Archive => "archive",
+                FileOperation::Extract => "extract",
+                FileOperation::SecureDelete { .. } => "secure delete",
+            };
+            let name = app.get_selected_file_entry().map_or("file", |file| file.name.as_str());
+            format!("confirm {} of {}", op, name)
+        }
+        AppMode::ConfirmFolderDelete { .. } => "confirm delete of the folder".to_string(),
+        AppMode::ConfirmClean => "confirm cleaning of the selected build artifacts".to_string(),
+        AppMode::ConfirmUndo => "confirm undo".to_string(),
+        AppMode::ConfirmBrokenLinkDelete => format!("confirm delete of {} broken links", app.broken_links.len()),
+        AppMode::ConfirmDuplicateResolve { .. } => {
+            let removals = app.duplicate_plan.as_ref().map_or(0, |plan| plan.removals.len());
+            format!("confirm delete of {} duplicates", removals)
+        }
+        AppMode::ConfirmGoal { .. } => {
+            let accepted = app.goal.as_ref().map_or(0, |goal| goal.plan.accepted().count());
+            format!("confirm freeing space with {} accepted candidates", accepted)
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::FileEntry;
+
+    #[test]
+    fn announces_changes_only() {
+        let (tx, rx) = mpsc::sync_channel(BACKLOG);
+        let mut announcer = Announcer { fifo: Some(tx), osc: false, selection: String::new(), status: String::new(), notification: None };
+        let mut app = App::new(Vec::new(), Default::default());
+        app.focus = PanelFocus::Right;
+        app.file_entries = Some(vec![FileEntry { name: "a.mov".to_string(), path: "/a.mov".to_string(), size: 2048, link_target: None, allocated_size: None }]);
+
+        announcer.observe(&app, &AppMode::Normal);
+        announcer.observe(&app, &AppMode::Normal);
+        app.notification = Some("Copied\na.mov".to_string());
+        announcer.observe(&app, &AppMode::Working("Deleting a.mov".to_string()));
+        announcer.observe(&app, &AppMode::Normal);
+
+        let lines: Vec<String> = rx.try_iter().collect();
+        assert_eq!(lines, ["selected file a.mov, 2.00 KB", "Deleting a.mov", "Copied a.mov"]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{error::Error, path::Path};
+#[cfg(feature = "junk")]
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "junk")]
+use crate::filesystem::{FileSystem, LocalFileSystem};
+#[cfg(feature = "junk")]
+use crate::platform::junk_scanner::scan_system_junk_with;
+use crate::platform::macos::{detect_storage_devices, StorageDevice};
+use crate::scanner::{scan_files, FileEntry, ScanOptions};
+#[cfg(feature = "junk")]
+use crate::scanner::{JunkCategorySummary, ScanProgressMessage};
+
+/// Result of a junk scan of the system, as returned by `junk_report`.
+#[cfg(feature = "junk")]
+#[derive(Debug, Clone, Default)]
+pub struct JunkReport {
+    pub total_size: u64,
+    pub total_files: usize,
+    /// Totals per junk category (app caches, logs, ...), in their configured order
+    pub categories: Vec<JunkCategorySummary>,
+    /// Folders holding junk as (path, total size, file count), largest first
+    pub folders: Vec<(String, u64, usize)>,
+}
+
+/// The `n` largest files below `path`, largest first, scanned with one thread per CPU core.
+///
+/// ```no_run
+/// for file in lazysmg::top_files("/Volumes/Backup", 10)? {
+///     println!("{:>12}  {}", file.size, file.path);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error + Send>>(())
+/// ```
+pub fn top_files(path: impl AsRef<Path>, n: usize) -> Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>> {
+    let mut files = scan_files(&path.as_ref().to_string_lossy(), ScanOptions::default())?;
+    files.truncate(n);
+    Ok(files)
+}
+
+/// Storage devices currently attached, mounted or not, like the device list of the TUI.
+pub fn devices() -> Vec<StorageDevice> {
+    detect_storage_devices()
+}
+
+/// Scan the junk locations of this OS (plus the user's `junk_paths.toml`) and total them up
+/// per category and folder. Blocks until the scan is done; must not be called from within an
+/// async runtime.
+#[cfg(feature = "junk")]
+pub fn junk_report() -> Result<JunkReport, Box<dyn Error>> {
+    junk_report_with(&SystemClock, &LocalFileSystem)
+}
+
+/// `junk_report` with the age of files (for junk paths with `min_age_days`) judged by `clock`,
+/// and their size and modification time read through `fs`, e.g. to see what would be junk a
+/// month from now.
+#[cfg(feature = "junk")]
+pub fn junk_report_with(clock: &dyn Clock, fs: &dyn FileSystem) -> Result<JunkReport, Box<dyn Error>> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(1024);
+        let scan = scan_system_junk_with(progress_tx, ScanOptions::default(), clock, fs);
+        // The scan reports every file; only its final message carries the totals
+        let collect = async {
+            let mut report = JunkReport::default();
+            while let Some(message) = progress_rx.recv().await {
+                if let ScanProgressMessage::JunkScanComplete { results, files_processed, mut folder_summaries, categories, .. } = message {
+                    folder_summaries.sort_by_key(|(_, size, _)| std::cmp::Reverse(*size));
+                    report = JunkReport {
+                        total_size: results.iter().map(|file| file.size).sum(),
+                        total_files: files_processed,
+                        categories,
+                        folders: folder_summaries,
+                    };
+                }
+            }
+            report
+        };
+        let (scanned, report) = tokio::join!(scan, collect);
+        scanned?;
+        Ok(report)
+    })
+}
@@ -0,0 +1,265 @@
+use std::error::Error;
+use crate::platform::macos::{detect_storage_devices, distinct_space};
+use crate::platform::power;
+use crate::config;
+use crate::export::{self, DevicesExport, ScanExport};
+use crate::html_report::Snapshot;
+use crate::storage::profile;
+use crate::scanner::scan_files;
+#[cfg(feature = "junk")]
+use std::time::Instant;
+#[cfg(feature = "junk")]
+use crate::platform::junk_scanner::scan_system_junk;
+#[cfg(feature = "junk")]
+use crate::report::{self, JunkScanReport};
+#[cfg(feature = "junk")]
+use crate::scanner::ScanProgressMessage;
+#[cfg(feature = "junk")]
+use crate::storage::scan_history::{self, ScanRecord};
+#[cfg(feature = "junk")]
+use crate::format;
+
+const USAGE: &str = "\
+Usage:
+  lazysmg [--record <file> | --replay <file>] [--inline [<rows>]]
+                                                 Start the interactive TUI, with --inline in
+                                                 <rows> (default 20) below the prompt instead of
+                                                 the whole screen, leaving a summary on exit
+  lazysmg scan <path> [--json | --ncdu | --html] Scan <path> recursively and print files by size
+                                                 (--ncdu in the export format `ncdu -f` reads,
+                                                 --html as a standalone report page)
+  lazysmg devices [--json]                       List detected storage devices
+                                                 (--json writes a document with a schema_version)
+  lazysmg junk-scan [--report <file>] [--pipe <command>]
+                                                 Junk scan of the system disk for scheduled runs:
+                                                 records it and reports totals, changes and the
+                                                 largest junk (to [report] in the config)
+
+Options:
+  --profile <name>                               Use the config, junk paths, scan cache and scan
+                                                 history of profile <name>, kept apart from the
+                                                 default ones (in profiles/<name> below them)";
+
+/// Rows the TUI takes up with `--inline` if no number is given.
+const DEFAULT_INLINE_ROWS: u16 = 20;
+
+/// Fewer rows than this leave no room for the panels.
+const MIN_INLINE_ROWS: u16 = 10;
+
+/// What the binary was asked to do on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Interactive TUI, optionally recording or replaying a session. `inline` is the number of
+    /// rows to draw it in below the prompt, instead of taking over the whole screen.
+    Tui { record: Option<String>, replay: Option<String>, inline: Option<u16> },
+    /// Headless recursive scan of a path
+    Scan { path: String, output: ScanOutput },
+    /// Headless device listing
+    Devices { json: bool },
+    /// Headless junk scan with a report, written to `report` or piped to `pipe` if given,
+    /// instead of where the config says
+    JunkScan { report: Option<String>, pipe: Option<String> },
+    Help,
+}
+
+/// How the headless scan prints its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOutput {
+    /// One line per file, largest first
+    Text,
+    /// The versioned JSON document of `export.rs`
+    Json,
+    /// The export format of ncdu, for `ncdu -f`
+    Ncdu,
+    /// The standalone HTML snapshot of `html_report.rs`
+    Html,
+}
+
+/// Take `--profile <name>` out of the arguments (without the program name), wherever it is, and
+/// select that profile for the TUI or subcommand the rest asks for.
+pub fn select_profile(args: impl IntoIterator<Item = String>) -> Result<Vec<String>, String> {
+    let mut args = args.into_iter();
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile::select(&args.next().ok_or("--profile requires a name")?)?;
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok(rest)
+}
+
+/// Parse command-line arguments (without the program name).
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(String::as_str) {
+        Some("scan") => {
+            args.next();
+            let mut path = None;
+            let mut output = ScanOutput::Text;
+            for arg in args {
+                match arg.as_str() {
+                    "--json" => output = ScanOutput::Json,
+                    "--ncdu" => output = ScanOutput::Ncdu,
+                    "--html" => output = ScanOutput::Html,
+                    _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+                    other => return Err(format!("Unexpected argument for scan: {}", other)),
+                }
+            }
+            let path = path.ok_or("scan requires a path")?;
+            Ok(Command::Scan { path, output })
+        }
+        Some("devices") => {
+            args.next();
+            let mut json = false;
+            for arg in args {
+                match arg.as_str() {
+                    "--json" => json = true,
+                    other => return Err(format!("Unexpected argument for devices: {}", other)),
+                }
+            }
+            Ok(Command::Devices { json })
+        }
+        Some("junk-scan") => {
+            args.next();
+            let mut report = None;
+            let mut pipe = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--report" => report = Some(args.next().ok_or("--report requires a file")?),
+                    "--pipe" => pipe = Some(args.next().ok_or("--pipe requires a command")?),
+                    other => return Err(format!("Unexpected argument for junk-scan: {}", other)),
+                }
+            }
+            Ok(Command::JunkScan { report, pipe })
+        }
+        _ => {
+            let mut record = None;
+            let mut replay = None;
+            let mut inline = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--record" => record = Some(args.next().ok_or("--record requires a file")?),
+                    "--replay" => replay = Some(args.next().ok_or("--replay requires a file")?),
+                    "--inline" => {
+                        // The number of rows is optional
+                        let rows = args.next_if(|rows| rows.parse::<u16>().is_ok()).and_then(|rows| rows.parse().ok());
+                        inline = Some(rows.unwrap_or(DEFAULT_INLINE_ROWS).max(MIN_INLINE_ROWS));
+                    }
+                    "-h" | "--help" => return Ok(Command::Help),
+                    other => return Err(format!("Unknown argument: {}", other)),
+                }
+            }
+            Ok(Command::Tui { record, replay, inline })
+        }
+    }
+}
+
+pub fn print_usage() {
+    println!("{}", USAGE);
+}
+
+/// Run a headless command, printing its results to stdout.
+pub async fn run_headless(command: &Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Scan { path, output } => {
+            let config = config::load_config()?;
+            let options = config.scan.options(power::on_battery());
+            let files = scan_files(path, options).map_err(|e| e.to_string())?;
+            match output {
+                ScanOutput::Json => println!("{}", export::to_json(&ScanExport::new(path, None, &files))?),
+                ScanOutput::Ncdu => println!("{}", export::ncdu(path, &files)),
+                ScanOutput::Html => {
+                    let snapshot = Snapshot { title: path, root: path, files: &files, totals: None, units: config.display.size_units };
+                    print!("{}", snapshot.render());
+                }
+                ScanOutput::Text => {
+                    for file in &files {
+                        println!("{:>15}  {}", file.size, file.path);
+                    }
+                }
+            }
+        }
+        Command::Devices { json } => {
+            let devices = detect_storage_devices();
+            if *json {
+                println!("{}", export::to_json(&DevicesExport::new(&devices))?);
+            } else {
+                for device in &devices {
+                    let alias = device.alias_of.as_ref().map(|original| format!("  (alias of {})", original));
+                    println!(
+                        "{:<24} {:<32} {:>15} {:>15}{}{}",
+                        device.name,
+                        device.mount_point,
+                        device.total_space,
+                        device.available_space,
+                        if device.ejectable { "  (ejectable)" } else { "" },
+                        alias.unwrap_or_default()
+                    );
+                }
+                // Aliases and volumes sharing an APFS container are counted once
+                let (total, available) = distinct_space(&devices);
+                println!("{:<24} {:<32} {:>15} {:>15}", "Total", "", total, available);
+            }
+        }
+        Command::JunkScan { report, pipe } => {
+            #[cfg(feature = "junk")]
+            junk_scan(report.clone(), pipe.clone()).await?;
+            #[cfg(not(feature = "junk"))]
+            {
+                let _ = (report, pipe);
+                return Err("This build has no junk scanning (feature `junk`)".into());
+            }
+        }
+        Command::Help => print_usage(),
+        Command::Tui { .. } => {}
+    }
+    Ok(())
+}
+
+/// Junk scan the system disk, add it to the scan history and deliver its report. Flags given on
+/// the command line replace the `[report]` destinations of the config.
+#[cfg(feature = "junk")]
+async fn junk_scan(report_path: Option<String>, pipe: Option<String>) -> Result<(), Box<dyn Error>> {
+    let config = config::load_config()?;
+    format::set_number_format(config.display.number_format());
+    let mut destination = config.report.clone();
+    if report_path.is_some() || pipe.is_some() {
+        destination.path = report_path;
+        destination.command = pipe;
+    }
+    // Recorded under the disk the TUI junk scans, so both compare with each other
+    let devices = detect_storage_devices();
+    let (name, mount_point) = devices
+        .iter()
+        .find(|device| device.home_volume)
+        .or_else(|| devices.iter().find(|device| !device.ejectable))
+        .map_or(("System".to_string(), "/".to_string()), |device| (device.name.clone(), device.mount_point.clone()));
+
+    let started = Instant::now();
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(1024);
+    let scan = scan_system_junk(progress_tx, config.scan.options(power::on_battery()));
+    // The scan reports every file; only its final message carries the results
+    let collect = async {
+        let mut complete = None;
+        while let Some(message) = progress_rx.recv().await {
+            if let ScanProgressMessage::JunkScanComplete { results, folder_summaries, categories, .. } = message {
+                complete = Some((results, folder_summaries, categories));
+            }
+        }
+        complete
+    };
+    let (scanned, complete) = tokio::join!(scan, collect);
+    scanned?;
+    let (files, folders, categories) = complete.ok_or("The junk scan ended without results")?;
+
+    let record = ScanRecord::new(&name, &mount_point, true, started.elapsed().as_millis() as u64, &files);
+    let previous = scan_history::load().into_iter().rev().find(|previous| previous.same_target(&record));
+    // A failed write only loses this run from the next report's comparison
+    if let Err(err) = scan_history::append(&record) {
+        eprintln!("Couldn't add the scan to the history: {}", err);
+    }
+    let report = JunkScanReport { record, previous, categories, folders, files };
+    report::deliver(&report.render(config.report.top, config.display.size_units), &destination)
+}
@@ -0,0 +1,1031 @@
+use std::{collections::HashMap, error::Error, fmt, fs, path::PathBuf};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+use crate::analysis::FlatRate;
+use crate::format::{NumberFormat, SizeUnits};
+use crate::platform::macos::StorageDevice;
+use crate::scanner::{FileEntry, ScanOptions, SymlinkPolicy};
+use crate::storage::profile;
+use crate::theme::{ColorSupport, Theme, ThemeName};
+
+/// What the right panel shows when the app starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StartupView {
+    /// Quick non-recursive listing of the device root
+    #[default]
+    Listing,
+    /// Results of the last full scan, loaded from the scan cache
+    LastScan,
+    /// Junk scan of the system disk, grouped by folder
+    JunkSummary,
+}
+
+/// `[startup]` section of the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Device to select at startup, matched against its name, mount point or volume UUID
+    pub device: Option<String>,
+    pub view: StartupView,
+    /// Start a junk scan of the system disk right away
+    pub auto_junk_scan: bool,
+    /// Look for a newer release on GitHub at startup (at most once a day)
+    pub check_updates: bool,
+}
+
+/// A remappable user action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Help,
+    Down,
+    Up,
+    FocusLeft,
+    FocusRight,
+    SearchDevices,
+    Refresh,
+    Eject,
+    EjectAll,
+    Unmount,
+    Mount,
+    EmptyTrash,
+    QuickScan,
+    FullScan,
+    IncrementalScan,
+    ToggleGitignore,
+    Delete,
+    SecureDelete,
+    Copy,
+    Move,
+    Rename,
+    Archive,
+    Extract,
+    Reveal,
+    OpenFile,
+    QuickLook,
+    TogglePreview,
+    ToggleCost,
+    ToggleAllocated,
+    WidenDevicePanel,
+    NarrowDevicePanel,
+    GrowProgressPanel,
+    ShrinkProgressPanel,
+    Operations,
+    Undo,
+    ToggleFolderView,
+    OpenFolder,
+    ParentDir,
+    Back,
+    Forward,
+    Breadcrumbs,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    ToggleCategoryView,
+    ToggleCategory,
+    DevScan,
+    ToggleProject,
+    CleanArtifacts,
+    DockerPrune,
+    ClearBrowserCache,
+    JunkSuggestions,
+    AcceptSuggestion,
+    Pause,
+    Cancel,
+    Command,
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the help overlay.
+    pub const ALL: [Action; 58] = [
+        Action::Down,
+        Action::Up,
+        Action::FocusLeft,
+        Action::FocusRight,
+        Action::WidenDevicePanel,
+        Action::NarrowDevicePanel,
+        Action::GrowProgressPanel,
+        Action::ShrinkProgressPanel,
+        Action::Help,
+        Action::NewTab,
+        Action::CloseTab,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::SearchDevices,
+        Action::Refresh,
+        Action::Eject,
+        Action::EjectAll,
+        Action::Unmount,
+        Action::Mount,
+        Action::EmptyTrash,
+        Action::QuickScan,
+        Action::FullScan,
+        Action::IncrementalScan,
+        Action::ToggleGitignore,
+        Action::Delete,
+        Action::SecureDelete,
+        Action::Copy,
+        Action::Move,
+        Action::Rename,
+        Action::Archive,
+        Action::Extract,
+        Action::Reveal,
+        Action::OpenFile,
+        Action::QuickLook,
+        Action::TogglePreview,
+        Action::ToggleCost,
+        Action::ToggleAllocated,
+        Action::Operations,
+        Action::Undo,
+        Action::ToggleFolderView,
+        Action::OpenFolder,
+        Action::ParentDir,
+        Action::Back,
+        Action::Forward,
+        Action::Breadcrumbs,
+        Action::ToggleCategoryView,
+        Action::ToggleCategory,
+        Action::DevScan,
+        Action::ToggleProject,
+        Action::CleanArtifacts,
+        Action::DockerPrune,
+        Action::ClearBrowserCache,
+        Action::JunkSuggestions,
+        Action::AcceptSuggestion,
+        Action::Pause,
+        Action::Cancel,
+        Action::Command,
+        Action::Quit,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit application",
+            Action::Help => "Show/hide this help screen",
+            Action::Down => "Move down in current panel",
+            Action::Up => "Move up in current panel",
+            Action::FocusLeft => "Focus left panel (devices)",
+            Action::FocusRight => "Focus right panel (files)",
+            Action::SearchDevices => "Type to jump to a device (Enter = keep, Esc = cancel)",
+            Action::Refresh => "Refresh device list",
+            Action::Eject => "Eject selected device (if ejectable)",
+            Action::EjectAll => "Eject every ejectable device, one after another (requires confirmation)",
+            Action::Unmount => "Unmount the selected volume but keep its disk attached",
+            Action::Mount => "Mount the selected volume (listed dimmed when not mounted)",
+            Action::EmptyTrash => "Empty the trash of the selected device (requires confirmation)",
+            Action::QuickScan => "Scan current directory (non-recursive)",
+            Action::FullScan => "Full device scan with progress bar",
+            Action::IncrementalScan => "Incremental rescan (only changed directories)",
+            Action::ToggleGitignore => "Make scans respect .gitignore files, or stop them doing so",
+            Action::Delete => "Move selected file to the trash (requires confirmation)",
+            Action::SecureDelete => "Overwrite selected file, then delete it for good (needs delete.secure_erase)",
+            Action::Copy => "Copy selected file (requires confirmation)",
+            Action::Move => "Move selected file (requires confirmation)",
+            Action::Rename => "Rename selected file in place (right panel; Enter = rename, Esc = cancel)",
+            Action::Archive => "Pack the selected file or folder into a zip or tar.gz archive (Tab = switch format)",
+            Action::Extract => "Unpack the selected .zip, .tar.gz or .7z archive into a folder",
+            Action::Reveal => "Show the selected file in Finder (or the file manager)",
+            Action::OpenFile => "Open the selected file with its default app",
+            Action::QuickLook => "Preview the selected file with Quick Look (macOS)",
+            Action::TogglePreview => "Show/hide the preview pane next to the file list",
+            Action::ToggleCost => "Show/hide the monthly cost of sizes (rates per device in the config file)",
+            Action::ToggleAllocated => "Show the space files take up on disk instead of their size (sparse, compressed files)",
+            Action::WidenDevicePanel => "Widen the device panel (saved in the config file)",
+            Action::NarrowDevicePanel => "Narrow the device panel (saved in the config file)",
+            Action::GrowProgressPanel => "Enlarge the progress panel below the files (saved in the config file)",
+            Action::ShrinkProgressPanel => "Shrink the progress panel below the files (saved in the config file)",
+            Action::Operations => "Show background copies, moves and deletes (cancel with the cancel key)",
+            Action::Undo => "Undo the last delete or move (right panel; requires confirmation)",
+            Action::ToggleFolderView => "Toggle junk folder/file view, or directory sizes of a full scan",
+            Action::OpenFolder => "Open the selected directory, or show files of the selected junk folder",
+            Action::ParentDir => "List the parent of the directory being listed",
+            Action::Back => "Go back to the directory listed before, like in a browser",
+            Action::Forward => "Go forward again to the directory left by going back",
+            Action::Breadcrumbs => "Choose a directory of the breadcrumb path bar to jump up to",
+            Action::NewTab => "Open a tab on the device and directory shown",
+            Action::CloseTab => "Close the current tab",
+            Action::NextTab => "Switch to the next tab (1-9 switch to a tab by number)",
+            Action::PreviousTab => "Switch to the previous tab",
+            Action::ToggleCategoryView => "Show/hide junk categories with their totals, or the space by file type of a full scan",
+            Action::ToggleCategory => "Include/exclude the selected junk category",
+            Action::DevScan => "Find build artifacts (node_modules, target, ...) in projects",
+            Action::ToggleProject => "Select/deselect the highlighted project or Xcode item for cleaning",
+            Action::CleanArtifacts => "Remove the selected build artifacts or Xcode data (requires confirmation)",
+            Action::JunkSuggestions => "Show folders of the last full scan that look like junk",
+            Action::AcceptSuggestion => "Add the highlighted suggested folder to your junk paths (junk_paths.toml)",
+            Action::Pause => "Pause/resume a running junk folder deletion",
+            Action::Cancel => "Cancel a running scan",
+            Action::DockerPrune => "Prune Docker images, containers, volumes or build cache (after a junk scan)",
+            Action::ClearBrowserCache => "Clear the cache of browser profiles (after a junk scan)",
+            Action::Command => "Enter a command (:history = past scans, :xcode = Xcode cleanup, :eject-all)",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Help => &["?"],
+            Action::Down => &["j", "Down"],
+            Action::Up => &["k", "Up"],
+            Action::FocusLeft => &["Ctrl-h"],
+            Action::FocusRight => &["Ctrl-l"],
+            Action::SearchDevices => &["/"],
+            Action::Refresh => &["r"],
+            Action::Eject => &["e"],
+            Action::EjectAll => &["E"],
+            Action::Unmount => &["u"],
+            Action::Mount => &["M"],
+            Action::EmptyTrash => &["T"],
+            Action::QuickScan => &["s"],
+            Action::FullScan => &["S"],
+            Action::IncrementalScan => &["I"],
+            Action::ToggleGitignore => &["G"],
+            Action::Delete => &["d"],
+            Action::SecureDelete => &["W"],
+            Action::Copy => &["c"],
+            Action::Move => &["m"],
+            Action::Rename => &["r"],
+            Action::Archive => &["z"],
+            Action::Extract => &["x"],
+            Action::Reveal => &["f"],
+            Action::OpenFile => &["O"],
+            Action::QuickLook => &["v"],
+            Action::TogglePreview => &["i"],
+            Action::ToggleCost => &["$"],
+            Action::ToggleAllocated => &["A"],
+            Action::WidenDevicePanel => &[">"],
+            Action::NarrowDevicePanel => &["<"],
+            Action::GrowProgressPanel => &["+"],
+            Action::ShrinkProgressPanel => &["-"],
+            Action::Operations => &["o"],
+            Action::Undo => &["u"],
+            Action::ToggleFolderView => &["Tab"],
+            Action::OpenFolder => &["Enter"],
+            Action::ParentDir => &["Backspace"],
+            Action::Back => &["Ctrl-o", "Left"],
+            // Most terminals send Ctrl-i as Tab, so Right is the one that always works
+            Action::Forward => &["Ctrl-i", "Right"],
+            Action::Breadcrumbs => &["b"],
+            Action::NewTab => &["t"],
+            Action::CloseTab => &["w"],
+            Action::NextTab => &["]"],
+            Action::PreviousTab => &["["],
+            Action::ToggleCategoryView => &["C"],
+            Action::ToggleCategory => &["Space"],
+            Action::DevScan => &["D"],
+            Action::ToggleProject => &["Space"],
+            Action::CleanArtifacts => &["X"],
+            Action::DockerPrune => &["P"],
+            Action::ClearBrowserCache => &["B"],
+            Action::JunkSuggestions => &["g"],
+            Action::AcceptSuggestion => &["a"],
+            Action::Pause => &["p"],
+            Action::Cancel => &["c"],
+            Action::Command => &[":"],
+        }
+    }
+}
+
+/// A single key, optionally with Ctrl, as written in the config file ("q", "Ctrl-l", "Down",
+/// "F5").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl KeyBinding {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (ctrl, key) = match spec.strip_prefix("Ctrl-").or_else(|| spec.strip_prefix("Ctrl+")) {
+            Some(rest) => (true, rest),
+            None => (false, spec),
+        };
+        let code = match key {
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Enter" => KeyCode::Enter,
+            "Tab" => KeyCode::Tab,
+            "Esc" => KeyCode::Esc,
+            "Backspace" => KeyCode::Backspace,
+            "Delete" => KeyCode::Delete,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match key.strip_prefix('F').and_then(|number| number.parse::<u8>().ok()) {
+                        Some(number @ 1..=24) => KeyCode::F(number),
+                        _ => return Err(format!("Unknown key: {}", spec)),
+                    },
+                }
+            }
+        };
+        Ok(KeyBinding { code, ctrl })
+    }
+
+    /// Shift is ignored so that "S" matches however the terminal reports an uppercase letter.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        self.code == key.code && self.ctrl == key.modifiers.contains(KeyModifiers::CONTROL)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// One key or a list of keys for an action in the `[keys]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Keys bound to each action: the defaults, with any actions listed in `[keys]` replaced.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<Action, KeyList>")]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|spec| KeyBinding::parse(spec).expect("default key bindings are valid"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        KeyBindings { bindings }
+    }
+}
+
+impl TryFrom<HashMap<Action, KeyList>> for KeyBindings {
+    type Error = String;
+
+    fn try_from(overrides: HashMap<Action, KeyList>) -> Result<Self, Self::Error> {
+        let mut keys = KeyBindings::default();
+        for (action, list) in overrides {
+            let specs = match list {
+                KeyList::One(spec) => vec![spec],
+                KeyList::Many(specs) => specs,
+            };
+            let parsed = specs.iter().map(|spec| KeyBinding::parse(spec)).collect::<Result<_, _>>()?;
+            keys.bindings.insert(action, parsed);
+        }
+        Ok(keys)
+    }
+}
+
+impl KeyBindings {
+    /// All actions bound to this key press.
+    pub fn actions_for(&self, key: &KeyEvent) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(|action| self.keys(*action).iter().any(|binding| binding.matches(key)))
+            .collect()
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Human-readable keys for an action, e.g. "j, Down".
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(|binding| binding.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Parse a color name ("magenta", "dark-gray"), a 256-color index ("208") or a hex RGB ("#ff8800").
+pub fn parse_color(spec: &str) -> Result<Color, String> {
+    let name = spec.to_ascii_lowercase().replace(['-', '_', ' '], "");
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => {
+            if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+                match (channel(0), channel(2), channel(4)) {
+                    (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                    _ => return Err(format!("Invalid color: {}", spec)),
+                }
+            } else if let Ok(index) = name.parse::<u8>() {
+                Color::Indexed(index)
+            } else {
+                return Err(format!("Invalid color: {}", spec));
+            }
+        }
+    };
+    Ok(color)
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    let spec = String::deserialize(deserializer)?;
+    parse_color(&spec).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// `[colors]` section: a built-in theme, colors replacing some of its own, and how many colors
+/// the terminal can show.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ColorConfig {
+    pub theme: ThemeName,
+    /// Colors the terminal can't show are replaced by the closest ones it can
+    pub support: ColorSupport,
+    /// Border of the focused panel
+    #[serde(deserialize_with = "deserialize_color")]
+    pub focus: Option<Color>,
+    /// Selected row in lists and tables
+    #[serde(deserialize_with = "deserialize_color")]
+    pub highlight: Option<Color>,
+    /// Table header row
+    #[serde(deserialize_with = "deserialize_color")]
+    pub header: Option<Color>,
+    /// Device usage gauge
+    #[serde(deserialize_with = "deserialize_color")]
+    pub usage_gauge: Option<Color>,
+    /// Full scan progress gauge
+    #[serde(deserialize_with = "deserialize_color")]
+    pub scan_gauge: Option<Color>,
+    /// Unfilled part of the gauges
+    #[serde(deserialize_with = "deserialize_color")]
+    pub gauge_bg: Option<Color>,
+    /// Help overlay border, matched characters during a device search and keywords in previews
+    #[serde(deserialize_with = "deserialize_color")]
+    pub accent: Option<Color>,
+    /// Popup and help overlay background
+    #[serde(deserialize_with = "deserialize_color")]
+    pub popup_bg: Option<Color>,
+    /// Popup and help overlay text
+    #[serde(deserialize_with = "deserialize_color")]
+    pub popup_fg: Option<Color>,
+    /// Legend text
+    #[serde(deserialize_with = "deserialize_color")]
+    pub legend: Option<Color>,
+    /// Healthy drives
+    #[serde(deserialize_with = "deserialize_color")]
+    pub good: Option<Color>,
+    /// Failing drives
+    #[serde(deserialize_with = "deserialize_color")]
+    pub bad: Option<Color>,
+    /// Strings in previews
+    #[serde(deserialize_with = "deserialize_color")]
+    pub string: Option<Color>,
+    /// Numbers in previews
+    #[serde(deserialize_with = "deserialize_color")]
+    pub number: Option<Color>,
+    /// Directories in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub directory: Option<Color>,
+    /// Images in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub image: Option<Color>,
+    /// Videos in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub video: Option<Color>,
+    /// Audio files in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub audio: Option<Color>,
+    /// Archives and disk images in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub archive: Option<Color>,
+    /// Source code and config files in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub code: Option<Color>,
+    /// Text files and documents in file listings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub document: Option<Color>,
+    /// Paths of system files in scan results
+    #[serde(deserialize_with = "deserialize_color")]
+    pub system_data: Option<Color>,
+    /// Paths of data apps keep for themselves in scan results
+    #[serde(deserialize_with = "deserialize_color")]
+    pub app_data: Option<Color>,
+}
+
+impl ColorConfig {
+    /// The theme these settings describe.
+    pub fn theme(&self) -> Theme {
+        let base = Theme::builtin(self.theme);
+        Theme {
+            focus: self.focus.unwrap_or(base.focus),
+            highlight: self.highlight.unwrap_or(base.highlight),
+            header: self.header.unwrap_or(base.header),
+            usage_gauge: self.usage_gauge.unwrap_or(base.usage_gauge),
+            scan_gauge: self.scan_gauge.unwrap_or(base.scan_gauge),
+            gauge_bg: self.gauge_bg.unwrap_or(base.gauge_bg),
+            accent: self.accent.unwrap_or(base.accent),
+            popup_bg: self.popup_bg.unwrap_or(base.popup_bg),
+            popup_fg: self.popup_fg.unwrap_or(base.popup_fg),
+            legend: self.legend.unwrap_or(base.legend),
+            good: self.good.unwrap_or(base.good),
+            bad: self.bad.unwrap_or(base.bad),
+            string: self.string.unwrap_or(base.string),
+            number: self.number.unwrap_or(base.number),
+            directory: self.directory.unwrap_or(base.directory),
+            image: self.image.unwrap_or(base.image),
+            video: self.video.unwrap_or(base.video),
+            audio: self.audio.unwrap_or(base.audio),
+            archive: self.archive.unwrap_or(base.archive),
+            code: self.code.unwrap_or(base.code),
+            document: self.document.unwrap_or(base.document),
+            system_data: self.system_data.unwrap_or(base.system_data),
+            app_data: self.app_data.unwrap_or(base.app_data),
+        }
+        .limited_to(self.support)
+    }
+}
+
+/// Order in which file listings and scan results are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    SizeDesc,
+    SizeAsc,
+    Name,
+}
+
+impl SortOrder {
+    pub fn apply(self, entries: &mut [FileEntry]) {
+        match self {
+            SortOrder::SizeDesc => entries.sort_by_key(|e| std::cmp::Reverse(e.size)),
+            SortOrder::SizeAsc => entries.sort_by_key(|e| e.size),
+            SortOrder::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+}
+
+/// `[display]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Sort applied to all listings and scan results. When unset, directory listings are
+    /// sorted by name and scan results by descending size.
+    pub sort: Option<SortOrder>,
+    pub size_units: SizeUnits,
+    /// Show a Nerd Font icon before each name in file listings; off for terminals without a
+    /// patched font
+    pub icons: bool,
+    /// Locale numbers are written for, e.g. "de_DE" for 1.234.567 and 1,50 GB. Unset follows
+    /// `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    pub locale: Option<String>,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig { sort: None, size_units: SizeUnits::default(), icons: true, locale: None }
+    }
+}
+
+impl DisplayConfig {
+    /// How numbers are written: for the configured locale, or the one of the environment.
+    pub fn number_format(&self) -> NumberFormat {
+        self.locale.as_deref().map_or_else(NumberFormat::from_env, NumberFormat::for_locale)
+    }
+}
+
+/// How scans behave while the machine runs on battery.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatteryPolicy {
+    /// Scan with `battery_parallelism` threads
+    #[default]
+    Throttle,
+    /// Throttle, and ask before starting a full scan
+    Confirm,
+    /// Scan as on AC power
+    Ignore,
+}
+
+/// `[scan]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Number of threads used for directory traversal (0 = one per CPU core)
+    pub parallelism: usize,
+    pub on_battery: BatteryPolicy,
+    /// Traversal threads while on battery, unless `on_battery` is "ignore"
+    pub battery_parallelism: usize,
+    /// Leave out what `.gitignore` files exclude, for developer-focused scans
+    pub respect_gitignore: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Levels of directories full scans of a device walk below its root; unset walks them all
+    pub max_depth: Option<usize>,
+    /// Let full scans of the system volume walk all of `/` rather than the home directory and
+    /// the known data locations
+    pub whole_system: bool,
+    /// Files a full scan of a device holds in memory, the largest ones, for volumes with
+    /// millions of files; directory sizes still count every file. Unset keeps them all.
+    pub max_files: Option<usize>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            parallelism: 0,
+            on_battery: BatteryPolicy::Throttle,
+            battery_parallelism: 1,
+            respect_gitignore: false,
+            symlinks: SymlinkPolicy::Show,
+            max_depth: None,
+            whole_system: false,
+            max_files: None,
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Scan options for the current power source. On battery the thread count is capped at
+    /// `battery_parallelism`. The whole tree is walked; full scans of a device apply
+    /// `max_depth` themselves, since it can be changed for the session.
+    pub fn options(&self, on_battery: bool) -> ScanOptions {
+        let battery_parallelism = self.battery_parallelism.max(1);
+        let parallelism = match self.parallelism {
+            _ if !self.throttled(on_battery) => self.parallelism,
+            0 => battery_parallelism,
+            n => n.min(battery_parallelism),
+        };
+        ScanOptions { parallelism, respect_gitignore: self.respect_gitignore, symlinks: self.symlinks, max_depth: None, max_files: self.max_files }
+    }
+
+    /// Whether scans run with fewer threads right now.
+    pub fn throttled(&self, on_battery: bool) -> bool {
+        on_battery && self.on_battery != BatteryPolicy::Ignore
+    }
+}
+
+/// `[layout]` section: panel sizes in percent. Resizing the panels in the app writes them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// Width of the device panel, of the whole screen
+    pub device_panel: u16,
+    /// Height of the progress panel, of the right side below the file list
+    pub progress_panel: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig { device_panel: 30, progress_panel: 30 }
+    }
+}
+
+impl LayoutConfig {
+    /// Percent added or taken by one key press.
+    pub const STEP: u16 = 5;
+    const DEVICE_PANEL: (u16, u16) = (15, 60);
+    const PROGRESS_PANEL: (u16, u16) = (10, 60);
+
+    /// The layout with both panels kept within sizes that leave room for the others.
+    pub fn clamped(self) -> Self {
+        LayoutConfig {
+            device_panel: self.device_panel.clamp(Self::DEVICE_PANEL.0, Self::DEVICE_PANEL.1),
+            progress_panel: self.progress_panel.clamp(Self::PROGRESS_PANEL.0, Self::PROGRESS_PANEL.1),
+        }
+    }
+}
+
+/// `[cost]` section: what storing data costs, e.g. backing it up to paid storage.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CostConfig {
+    /// Shown before amounts
+    pub currency: String,
+    /// Price per GB and month of devices without a rate of their own
+    pub default_rate: Option<f64>,
+    /// Price per GB and month by device name, mount point or volume UUID
+    pub devices: HashMap<String, f64>,
+}
+
+impl Default for CostConfig {
+    fn default() -> Self {
+        CostConfig {
+            currency: "$".to_string(),
+            default_rate: None,
+            devices: HashMap::new(),
+        }
+    }
+}
+
+impl CostConfig {
+    /// What storing data on `device` costs, if it has a rate.
+    pub fn model_for(&self, device: &StorageDevice) -> Option<FlatRate> {
+        self.devices
+            .iter()
+            .find(|(spec, _)| device.matches(spec))
+            .map(|(_, &rate)| rate)
+            .or(self.default_rate)
+            .map(|per_gb_month| FlatRate { per_gb_month })
+    }
+}
+
+/// `[delete]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DeleteConfig {
+    /// Look up which processes have a file open before deleting it, and warn about them: the
+    /// space of a file still open isn't freed until they close it
+    pub check_open_files: bool,
+    /// Allow the secure delete, which overwrites a file before deleting it. Only worth it on
+    /// spinning disks and simple flash media: SSDs and copy-on-write file systems write the new
+    /// contents elsewhere and keep the old ones
+    pub secure_erase: bool,
+    /// Times the contents are overwritten by a secure delete
+    pub secure_erase_passes: u32,
+}
+
+impl Default for DeleteConfig {
+    fn default() -> Self {
+        DeleteConfig { check_open_files: true, secure_erase: false, secure_erase_passes: 3 }
+    }
+}
+
+/// Which deletes ask for confirmation first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmPolicy {
+    /// Every delete
+    #[default]
+    Always,
+    /// Deletes of at least `large_delete_mb`, and directories whose size isn't known
+    LargeOnly,
+    /// No delete, except of system paths
+    Never,
+}
+
+/// The `[confirm]` section. Deletes in system locations always ask, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    pub delete: ConfirmPolicy,
+    /// Size from which `large-only` asks, in MB
+    pub large_delete_mb: u64,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig { delete: ConfirmPolicy::Always, large_delete_mb: 100 }
+    }
+}
+
+/// The `[report]` section: where `lazysmg junk-scan`, run on a schedule, puts its report.
+/// With neither set, the report is printed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    /// File the report is written to, replacing the previous one (`~` is expanded)
+    pub path: Option<String>,
+    /// Shell command the report is piped to, e.g. a mailer: `mail -s "Disk health" me@example.com`
+    pub command: Option<String>,
+    /// Number of categories, folders and files listed as the largest
+    pub top: usize,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        ReportConfig { path: None, command: None, top: 10 }
+    }
+}
+
+/// The `[stale]` section: which files `:stale` reports as archive candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StaleConfig {
+    /// Size from which a file is reported, in MB
+    pub min_size_mb: u64,
+    /// Days since a file was last read or changed from which it is reported
+    pub unused_days: u64,
+}
+
+impl Default for StaleConfig {
+    fn default() -> Self {
+        StaleConfig { min_size_mb: 1024, unused_days: 365 }
+    }
+}
+
+/// Built-in way of previewing a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Renderer {
+    /// Image details, text or a hex dump, depending on what the file looks like
+    #[default]
+    Auto,
+    /// As text, even if it doesn't look like text
+    Text,
+    /// As a hex dump
+    Hex,
+}
+
+/// One `[[handlers]]` entry: how files with one of `extensions` (e.g. "db" or "tar.gz") or
+/// MIME types (e.g. "text/csv" or "image/*") are previewed and opened. Commands are split at
+/// spaces; `{path}` stands for the file, which is appended if it doesn't appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HandlerConfig {
+    pub extensions: Vec<String>,
+    pub mime: Vec<String>,
+    /// Built-in preview, if there is no `preview` command
+    pub renderer: Option<Renderer>,
+    /// Command whose output is shown in the preview pane
+    pub preview: Option<String>,
+    /// Command that opens the file instead of its default app
+    pub open: Option<String>,
+}
+
+/// The `[accessibility]` section: announcements of what changes on screen, one line each, for
+/// screen readers and other assistive tools that can't follow the panels.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// FIFO (made with `mkfifo`) or file the announcements are written to (`~` is expanded)
+    pub announce_fifo: Option<String>,
+    /// Also send them to the terminal as OSC 9 notifications
+    pub announce_osc: bool,
+}
+
+/// User configuration loaded from `~/.config/lazysmg/config.toml`.
+/// Every field is optional; a missing file or section falls back to the defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub startup: StartupConfig,
+    pub keys: KeyBindings,
+    pub colors: ColorConfig,
+    pub display: DisplayConfig,
+    pub scan: ScanConfig,
+    pub layout: LayoutConfig,
+    pub cost: CostConfig,
+    pub delete: DeleteConfig,
+    pub confirm: ConfirmConfig,
+    pub handlers: Vec<HandlerConfig>,
+    pub report: ReportConfig,
+    pub stale: StaleConfig,
+    pub accessibility: AccessibilityConfig,
+}
+
+/// Location of the user config file, per profile.
+pub fn config_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(profile::dir("~/.config/lazysmg")?.join("config.toml"))
+}
+
+/// Load the user config. A missing file yields the defaults; a malformed one is an error
+/// so typos don't get silently ignored.
+pub fn load_config() -> Result<Config, Box<dyn Error>> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content).map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+}
+
+/// Write the panel sizes into the `[layout]` section of the config file, creating the file if
+/// there is none. The rest of the file, comments included, is left as it is.
+pub fn save_layout(layout: LayoutConfig) -> Result<(), Box<dyn Error>> {
+    let path = config_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let content = with_layout(&content, layout).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+    crate::storage::persist::write_atomic(&path, content.as_bytes())?;
+    Ok(())
+}
+
+/// The config file `content` with the panel sizes of its `[layout]` section set to `layout`.
+fn with_layout(content: &str, layout: LayoutConfig) -> Result<String, toml_edit::TomlError> {
+    let mut document: toml_edit::Document = content.parse()?;
+    if !document.contains_table("layout") {
+        document["layout"] = toml_edit::table();
+    }
+    document["layout"]["device_panel"] = toml_edit::value(i64::from(layout.device_panel));
+    document["layout"]["progress_panel"] = toml_edit::value(i64::from(layout.progress_panel));
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys() {
+        let key = |spec: &str| KeyBinding::parse(spec).map(|binding| (binding.code, binding.ctrl));
+        assert_eq!(key("q"), Ok((KeyCode::Char('q'), false)));
+        assert_eq!(key("S"), Ok((KeyCode::Char('S'), false)));
+        assert_eq!(key("Ctrl-l"), Ok((KeyCode::Char('l'), true)));
+        assert_eq!(key("Ctrl+l"), Ok((KeyCode::Char('l'), true)));
+        assert_eq!(key("Ctrl-Down"), Ok((KeyCode::Down, true)));
+        assert_eq!(key("Space"), Ok((KeyCode::Char(' '), false)));
+        assert_eq!(key("F5"), Ok((KeyCode::F(5), false)));
+        assert_eq!(key("Ctrl-F12"), Ok((KeyCode::F(12), true)));
+        // A lone F is the letter
+        assert_eq!(key("F"), Ok((KeyCode::Char('F'), false)));
+        for invalid in ["", "Ctrl-", "F0", "F25", "Fx", "qq", "Alt-q", "down"] {
+            assert_eq!(key(invalid), Err(format!("Unknown key: {}", invalid)));
+        }
+        // What is shown parses back to the same key
+        for spec in ["Ctrl-l", "Space", "F5", "Down", "?"] {
+            assert_eq!(KeyBinding::parse(spec).unwrap().to_string(), spec);
+        }
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("magenta"), Ok(Color::Magenta));
+        assert_eq!(parse_color("Dark-Gray"), Ok(Color::DarkGray));
+        assert_eq!(parse_color("light_blue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color("grey"), Ok(Color::Gray));
+        assert_eq!(parse_color("default"), Ok(Color::Reset));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+        assert_eq!(parse_color("#FF8800"), Ok(Color::Rgb(255, 136, 0)));
+        for invalid in ["purple", "256", "#ff88", "#ff880g", "#ff880000", ""] {
+            assert_eq!(parse_color(invalid), Err(format!("Invalid color: {}", invalid)));
+        }
+    }
+
+    #[test]
+    fn keys_override_only_the_actions_listed() {
+        let config: Config = toml::from_str("[keys]\nquit = \"Ctrl-q\"\ndown = [\"n\", \"F2\"]\n").unwrap();
+        let keys = &config.keys;
+        assert_eq!(keys.label(Action::Quit), "Ctrl-q");
+        assert_eq!(keys.label(Action::Down), "n, F2");
+        assert_eq!(keys.label(Action::Up), "k, Up");
+
+        // A key bound to several actions matches all of them; which one applies depends on
+        // where the focus is
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let config: Config = toml::from_str("[keys]\ndelete = \"q\"\n").unwrap();
+        assert_eq!(config.keys.actions_for(&press(KeyCode::Char('q'))), vec![Action::Delete, Action::Quit]);
+        assert!(config.keys.actions_for(&press(KeyCode::Char('d'))).is_empty());
+        assert!(!config.keys.actions_for(&KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL)).contains(&Action::Quit));
+
+        // One invalid key rejects the whole section rather than leaving the action unbound
+        let error = toml::from_str::<Config>("[keys]\nquit = [\"q\", \"Hyper-q\"]\n").unwrap_err();
+        assert!(error.to_string().contains("Unknown key: Hyper-q"), "{}", error);
+        assert!(toml::from_str::<Config>("[keys]\nfly = \"f\"\n").is_err());
+    }
+
+    #[test]
+    fn layout_is_saved_keeping_the_rest_of_the_file() {
+        let layout = LayoutConfig { device_panel: 40, progress_panel: 20 };
+        let content = "# My settings\n[startup]\nview = \"last-scan\" # what I look at first\n\n[layout]\n# wide device panel\ndevice_panel = 35\n";
+        let saved = with_layout(content, layout).unwrap();
+        assert!(saved.contains("# My settings\n[startup]\nview = \"last-scan\" # what I look at first\n"), "{}", saved);
+        assert!(saved.contains("# wide device panel\ndevice_panel = 40\n"), "{}", saved);
+        let config: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(config.layout, layout);
+
+        // A file without the section, or no file at all, gets one
+        for content in ["[startup]\nview = \"listing\"\n", ""] {
+            let config: Config = toml::from_str(&with_layout(content, layout).unwrap()).unwrap();
+            assert_eq!(config.layout, layout);
+        }
+        assert!(with_layout("[layout\n", layout).is_err());
+    }
+}
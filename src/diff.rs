@@ -0,0 +1,219 @@
+use std::{fs, path::Path};
+use chrono::{DateTime, Local};
+
+/// Files larger than this aren't compared line by line.
+const MAX_DIFF_BYTES: u64 = 256 * 1024;
+/// Files with more lines than this aren't compared, which keeps the comparison table small.
+const MAX_DIFF_LINES: usize = 2000;
+/// Unchanged lines kept around each change.
+const CONTEXT_LINES: usize = 2;
+
+/// Size and modification time of one side of an overwrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersion {
+    pub size: u64,
+    pub modified: Option<DateTime<Local>>,
+    pub is_dir: bool,
+}
+
+impl FileVersion {
+    fn of(path: &Path) -> Option<FileVersion> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(FileVersion {
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(DateTime::<Local>::from),
+            is_dir: metadata.is_dir(),
+        })
+    }
+
+    /// When it was last modified, e.g. "2024-03-01 14:02".
+    pub fn modified_label(&self) -> String {
+        self.modified.map_or("unknown".to_string(), |modified| modified.format("%Y-%m-%d %H:%M").to_string())
+    }
+}
+
+/// One line of a unified diff from the existing file to the incoming one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    /// Only in the existing file
+    Removed(String),
+    /// Only in the incoming file
+    Added(String),
+    /// This many unchanged lines left out
+    Skipped(usize),
+}
+
+/// What a copy or move would replace, for its confirmation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverwritePreview {
+    pub target: String,
+    pub existing: FileVersion,
+    pub incoming: FileVersion,
+    /// The line diff, or why the files weren't compared
+    pub diff: Result<Vec<DiffLine>, String>,
+}
+
+impl OverwritePreview {
+    /// Whether the two files have the same contents.
+    pub fn identical(&self) -> bool {
+        self.diff.as_ref().is_ok_and(|lines| lines.iter().all(|line| matches!(line, DiffLine::Same(_) | DiffLine::Skipped(_))))
+    }
+}
+
+/// What copying or moving `source` to `target` would overwrite, or `None` if `target` doesn't
+/// exist. Text files up to `MAX_DIFF_BYTES` are compared line by line.
+pub fn overwrite_preview(source: &str, target: &str) -> Option<OverwritePreview> {
+    let existing = FileVersion::of(Path::new(target))?;
+    let incoming = FileVersion::of(Path::new(source))?;
+    let diff = if existing.is_dir || incoming.is_dir {
+        Err("Directories aren't compared; files with the same name are replaced".to_string())
+    } else if existing.size > MAX_DIFF_BYTES || incoming.size > MAX_DIFF_BYTES {
+        Err(format!("Too large to compare (over {} KB)", MAX_DIFF_BYTES / 1024))
+    } else {
+        match (read_text(target), read_text(source)) {
+            (Some(old), Some(new)) => line_diff(&old, &new),
+            _ => Err("Not text files".to_string()),
+        }
+    };
+    Some(OverwritePreview { target: target.to_string(), existing, incoming, diff })
+}
+
+/// Contents of a file if it is UTF-8 text without NUL bytes.
+fn read_text(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().filter(|text| !text.contains('\0'))
+}
+
+/// Unified diff of two texts, with unchanged runs cut down to `CONTEXT_LINES` around changes.
+fn line_diff(old: &str, new: &str) -> Result<Vec<DiffLine>, String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    if old.len() > MAX_DIFF_LINES || new.len() > MAX_DIFF_LINES {
+        return Err(format!("Too many lines to compare (over {})", MAX_DIFF_LINES));
+    }
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut common = vec![0u16; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[(i + 1) * width + j] >= common[i * width + j + 1]) {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    Ok(collapse_unchanged(lines))
+}
+
+/// Replace unchanged lines further than `CONTEXT_LINES` from any change by `Skipped`.
+fn collapse_unchanged(lines: Vec<DiffLine>) -> Vec<DiffLine> {
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, line)| !matches!(line, DiffLine::Same(_))).map(|(index, _)| index).collect();
+    let near_change = |index: usize| changed.iter().any(|&change| change.abs_diff(index) <= CONTEXT_LINES);
+    let mut collapsed = Vec::new();
+    for (index, line) in lines.into_iter().enumerate() {
+        if matches!(line, DiffLine::Same(_)) && !near_change(index) {
+            match collapsed.last_mut() {
+                Some(DiffLine::Skipped(count)) => *count += 1,
+                _ => collapsed.push(DiffLine::Skipped(1)),
+            }
+        } else {
+            collapsed.push(line);
+        }
+    }
+    collapsed
+}
+
+/// Whether `version` was modified after `other`, to point out the newer of the two files.
+pub fn is_newer(version: &FileVersion, other: &FileVersion) -> bool {
+    match (version.modified, other.modified) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffLine::{Added, Removed, Same, Skipped};
+
+    fn same(text: &str) -> DiffLine {
+        Same(text.to_string())
+    }
+
+    fn added(text: &str) -> DiffLine {
+        Added(text.to_string())
+    }
+
+    fn removed(text: &str) -> DiffLine {
+        Removed(text.to_string())
+    }
+
+    #[test]
+    fn identical_and_empty_texts() {
+        assert_eq!(line_diff("a\nb\nc\n", "a\nb\nc\n").unwrap(), [Skipped(3)]);
+        assert_eq!(line_diff("", "").unwrap(), []);
+        assert_eq!(line_diff("", "a\nb").unwrap(), [added("a"), added("b")]);
+        assert_eq!(line_diff("a\nb", "").unwrap(), [removed("a"), removed("b")]);
+    }
+
+    #[test]
+    fn insertions_and_deletions() {
+        assert_eq!(line_diff("a\nc", "a\nb\nc").unwrap(), [same("a"), added("b"), same("c")]);
+        assert_eq!(line_diff("a\nb\nc", "a\nc").unwrap(), [same("a"), removed("b"), same("c")]);
+        assert_eq!(line_diff("a\nold\nc", "a\nnew\nc").unwrap(), [same("a"), removed("old"), added("new"), same("c")]);
+    }
+
+    #[test]
+    fn context_is_kept_around_changes_only() {
+        let numbers = |range: std::ops::Range<u32>| range.map(|n| n.to_string()).collect::<Vec<_>>();
+        let text = |lines: &[String]| lines.join("\n");
+        let old = numbers(0..20);
+
+        // At the start: the context before it is cut short
+        let mut new = old.clone();
+        new[0] = "zero".to_string();
+        assert_eq!(line_diff(&text(&old), &text(&new)).unwrap(), [removed("0"), added("zero"), same("1"), same("2"), Skipped(17)]);
+
+        // In the middle
+        let mut new = old.clone();
+        new.remove(10);
+        assert_eq!(line_diff(&text(&old), &text(&new)).unwrap(), [Skipped(8), same("8"), same("9"), removed("10"), same("11"), same("12"), Skipped(7)]);
+
+        // At the end
+        let mut new = old.clone();
+        new.push("20".to_string());
+        assert_eq!(line_diff(&text(&old), &text(&new)).unwrap(), [Skipped(18), same("18"), same("19"), added("20")]);
+
+        // Changes close together share their context
+        let mut new = old.clone();
+        new[5] = "five".to_string();
+        new[9] = "nine".to_string();
+        assert_eq!(
+            line_diff(&text(&old), &text(&new)).unwrap(),
+            [Skipped(3), same("3"), same("4"), removed("5"), added("five"), same("6"), same("7"), same("8"), removed("9"), added("nine"), same("10"), same("11"), Skipped(8)]
+        );
+    }
+
+    #[test]
+    fn long_texts_arent_compared() {
+        let long = "line\n".repeat(MAX_DIFF_LINES + 1);
+        assert!(line_diff(&long, "line").is_err());
+    }
+}
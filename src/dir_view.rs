@@ -0,0 +1,162 @@
+use std::path::Path;
+use crate::analysis::{self, DirTotals, DirUsage, FileEntry, SizeDeltas};
+use crate::{App, ScanMode};
+
+/// Rows of the directory view kept in sight above and below the highlighted one.
+const VISIBLE_ROWS: usize = 20;
+
+/// Directories listed in the leaderboard of the largest ones.
+const LARGEST_DIRS: usize = 50;
+
+/// The directory view of a full scan, like ncdu: what is directly inside one directory with
+/// the total size below each entry, to drill down into what takes up the space.
+#[derive(Debug, Clone, Default)]
+pub struct DirView {
+    /// Directory shown, the scanned device's mount point or below it
+    pub dir: String,
+    pub children: Vec<DirUsage>,
+    /// Total size of the files below `dir`
+    pub total: u64,
+    pub selected: usize,
+    pub offset: usize,
+}
+
+impl DirView {
+    /// Sum up `dir` from the files of a scan, or from its directory totals if it only kept its
+    /// largest files. With `by_delta`, entries are ordered by how much they changed.
+    fn new(files: &[FileEntry], totals: Option<&DirTotals>, dir: String, by_delta: Option<&SizeDeltas>) -> DirView {
+        let mut children = match totals {
+            Some(totals) => totals.usage(files, Path::new(&dir)),
+            None => analysis::dir_usage(files, Path::new(&dir)),
+        };
+        if let Some(deltas) = by_delta {
+            deltas.sort(&mut children, |child| (child.path.as_str(), child.size));
+        }
+        let total = children.iter().map(|child| child.size).sum();
+        DirView { dir, children, total, selected: 0, offset: 0 }
+    }
+
+    /// Highlight the entry at `index`, scrolling to keep it in sight.
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.children.len().saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + VISIBLE_ROWS {
+            self.offset = self.selected + 1 - VISIBLE_ROWS;
+        }
+    }
+}
+
+impl App {
+    /// Whether the results of a finished full scan are shown, which the directory view sums up.
+    pub fn full_scan_shown(&self) -> bool {
+        self.full_scan_results.is_some() && !self.scan_progress.in_progress && self.scan_mode == ScanMode::FullScan
+    }
+
+    /// The size changes to order entries by, with `:sort delta`.
+    fn delta_order(&self) -> Option<&SizeDeltas> {
+        self.size_deltas.as_ref().filter(|_| self.sort_by_delta)
+    }
+
+    /// Whether the right panel shows the directory view instead of the files of the full scan.
+    pub fn dir_view_active(&self) -> bool {
+        self.dir_view.is_some() && self.full_scan_shown()
+    }
+
+    /// Switch between the files of the full scan and the directory view, which starts at the
+    /// root of the device. Returns false if there is no finished full scan to sum up.
+    pub fn toggle_dir_view(&mut self) -> bool {
+        if self.dir_view_active() {
+            self.dir_view = None;
+            return true;
+        }
+        let (Some(files), Some(device)) = (self.full_scan_results.as_deref().filter(|_| self.full_scan_shown()), self.devices.get(self.selected)) else {
+            return false;
+        };
+        self.dir_view = Some(DirView::new(files, self.full_scan_totals.as_ref(), device.mount_point.clone(), self.delta_order()));
+        true
+    }
+
+    /// Sum up the directory shown again, after the scan results changed. Falls back to the root
+    /// of the device once nothing is left below it.
+    pub fn refresh_dir_view(&mut self) {
+        let (Some(view), Some(files), Some(device)) = (self.dir_view.as_mut(), self.full_scan_results.as_deref(), self.devices.get(self.selected)) else {
+            return;
+        };
+        let highlighted = view.children.get(view.selected).map(|child| child.path.clone());
+        let totals = self.full_scan_totals.as_ref();
+        let by_delta = self.size_deltas.as_ref().filter(|_| self.sort_by_delta);
+        let mut refreshed = DirView::new(files, totals, view.dir.clone(), by_delta);
+        if refreshed.children.is_empty() {
+            refreshed = DirView::new(files, totals, device.mount_point.clone(), by_delta);
+        }
+        let index = refreshed.children.iter().position(|child| Some(&child.path) == highlighted.as_ref());
+        refreshed.select(index.unwrap_or(view.selected));
+        *view = refreshed;
+    }
+
+    pub fn next_dir_entry(&mut self) {
+        if let Some(view) = self.dir_view.as_mut() {
+            view.select(view.selected + 1);
+        }
+    }
+
+    pub fn previous_dir_entry(&mut self) {
+        if let Some(view) = self.dir_view.as_mut() {
+            view.select(view.selected.saturating_sub(1));
+        }
+    }
+
+    /// Show what is inside the highlighted directory. Returns false if a file is highlighted.
+    pub fn enter_dir_view_entry(&mut self) -> bool {
+        let by_delta = self.size_deltas.as_ref().filter(|_| self.sort_by_delta);
+        let (Some(view), Some(files)) = (self.dir_view.as_mut(), self.full_scan_results.as_deref()) else {
+            return false;
+        };
+        let Some(child) = view.children.get(view.selected).filter(|child| child.is_dir) else {
+            return false;
+        };
+        *view = DirView::new(files, self.full_scan_totals.as_ref(), child.path.clone(), by_delta);
+        true
+    }
+
+    /// Show the parent of the directory shown, with the directory it came from highlighted.
+    /// Returns false at the root of the device.
+    pub fn leave_dir_view_dir(&mut self) -> bool {
+        let by_delta = self.size_deltas.as_ref().filter(|_| self.sort_by_delta);
+        let (Some(view), Some(files), Some(device)) = (self.dir_view.as_mut(), self.full_scan_results.as_deref(), self.devices.get(self.selected)) else {
+            return false;
+        };
+        let Some(parent) = Path::new(&view.dir).parent().filter(|_| view.dir != device.mount_point) else {
+            return false;
+        };
+        let parent = parent.to_string_lossy().into_owned();
+        let left = std::mem::replace(view, DirView::new(files, self.full_scan_totals.as_ref(), parent, by_delta)).dir;
+        let index = view.children.iter().position(|child| child.path == left);
+        view.select(index.unwrap_or(0));
+        true
+    }
+
+    /// Rank the directories of the full scan by the size of everything below them, for the
+    /// leaderboard of the largest ones. Returns false if there is no finished full scan.
+    pub fn rank_largest_dirs(&mut self) -> bool {
+        let (Some(files), Some(device)) = (self.full_scan_results.as_deref().filter(|_| self.full_scan_shown()), self.devices.get(self.selected)) else {
+            return false;
+        };
+        let root = Path::new(&device.mount_point);
+        self.largest_dirs = match self.full_scan_totals.as_ref() {
+            Some(totals) => totals.largest(root, LARGEST_DIRS),
+            None => DirTotals::of(root, files).largest(root, LARGEST_DIRS),
+        };
+        true
+    }
+
+    /// Show `dir` in the directory view, picked from the leaderboard of the largest directories.
+    pub fn open_dir_view_at(&mut self, dir: &str) -> bool {
+        let Some(files) = self.full_scan_results.as_deref().filter(|_| self.full_scan_shown()) else {
+            return false;
+        };
+        self.dir_view = Some(DirView::new(files, self.full_scan_totals.as_ref(), dir.to_string(), self.delta_order()));
+        true
+    }
+}
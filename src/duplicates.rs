@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    io::{self, BufReader, Read},
+    path::Path,
+    time::SystemTime,
+};
+use lazysmg::analysis::{self, DuplicateGroup, FileEntry};
+
+// Duplicate finding for `:duplicates`: files of equal size are hashed to group them, and a copy
+// is only deleted after comparing it byte by byte with the one kept, so a hash collision can't
+// cost a file.
+
+/// Bytes read at a time while hashing or comparing.
+const CHUNK: usize = 64 * 1024;
+
+/// Groups of files with the same content among `entries`, largest waste first, with when
+/// each of their files was last modified, for picking the copy to keep.
+pub fn find(entries: &[FileEntry]) -> (Vec<DuplicateGroup>, HashMap<String, SystemTime>) {
+    let groups = analysis::group_duplicates(entries, |file| content_hash(Path::new(&file.path)).ok());
+    let modified = groups
+        .iter()
+        .flat_map(|group| &group.files)
+        .filter_map(|file| Some((file.path.clone(), Path::new(&file.path).metadata().ok()?.modified().ok()?)))
+        .collect();
+    (groups, modified)
+}
+
+/// Hash of the content of the file at `path`; symlinks are followed.
+fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::with_capacity(CHUNK, File::open(path)?);
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; CHUNK];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buffer[..read]);
+    }
+}
+
+/// Whether the files at `a` and `b` hold the same bytes.
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut chunk_a, mut chunk_b) = (vec![0; CHUNK], vec![0; CHUNK]);
+    loop {
+        let read = read_full(&mut a, &mut chunk_a)?;
+        if read != read_full(&mut b, &mut chunk_b)? || chunk_a[..read] != chunk_b[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buffer` from `reader` as far as it goes; less only at the end of the file.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn finds_copies_and_compares_them_byte_by_byte() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-duplicates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            FileEntry { name: name.to_string(), path: path.to_string_lossy().into_owned(), size: content.len() as u64, link_target: None, allocated_size: None }
+        };
+        let big: Vec<u8> = (0..CHUNK * 2 + 7).map(|i| (i % 251) as u8).collect();
+        let mut changed = big.clone();
+        changed[CHUNK + 3] ^= 1;
+        let entries = [write("a", &big), write("b", &big), write("c", &changed), write("d", b"short")];
+
+        let (groups, modified) = find(&entries);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, entries[..2]);
+        assert_eq!(modified.len(), 2);
+
+        assert!(same_content(&dir.join("a"), &dir.join("b")).unwrap());
+        assert!(!same_content(&dir.join("a"), &dir.join("c")).unwrap());
+        assert!(!same_content(&dir.join("a"), &dir.join("d")).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
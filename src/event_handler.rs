@@ -1,323 +1,2180 @@
-use std::{error::Error, sync::mpsc, thread, time::Duration};
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use crate::{App, AppMode, PanelFocus, ScanProgress, FileOperation, ScanMode};
-use crate::platform::{macos, junk_scanner};
-use crate::scanner::{scan_files, full_scan_with_progress, ScanProgressMessage};
-use crate::perform_file_operation;
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+    sync::{
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+use crossterm::event::{self, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Rect;
+use crate::{App, AppMode, ModeMachine, PanelFocus, ScanProgress, FileOperation, ScanMode};
+use crate::config::{self, Action, BatteryPolicy};
+use crate::export::{self, ScanExport};
+use crate::html_report::{self, Snapshot};
+use crate::format::{self, format_count, format_depth, format_size};
+use crate::{goal, tasks};
+use crate::{analysis, diff, operations};
+use crate::platform::{macos, holders, dev_artifacts, xcode_cleaner, docker, power, open, system_roots, trash, last_used};
+use crate::scanner::{list_directory, scan_files, full_scan_with_progress, incremental_scan_with_progress, system_scan_with_progress, ScanOptions, ScanPriority, ScanProgressMessage};
+use crate::storage::deletion_impact::{self, DeletionImpact};
+use crate::storage::space_check::SpaceCheck;
+use crate::storage::{device_guard, file_ops, scan_cache, secure_erase};
+use crate::storage::file_ops::ArchiveFormat;
+use crate::ui::{self, ScreenLayout};
 use tokio::sync::mpsc::Sender;
+use lazysmg::clock::SystemClock;
+use lazysmg::filesystem::{FileSystem, LocalFileSystem};
 
+/// How long an eject waits for running scans and file operations on the device to stop. It is
+/// called off if they haven't by then.
+const EJECT_CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the power listener checks whether the machine runs on battery.
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Changes between two scans listed by `:changes`, the largest ones.
+const CHANGES_SHOWN: usize = 500;
+
+/// Wait up to `poll_timeout` for a terminal event and handle it. Returns whether the app should quit.
 pub async fn process_event(
     app: &mut App,
-    mode: &mut AppMode,
+    mode: &mut ModeMachine,
+    poll_timeout: Duration,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    progress_tx: &Sender<ScanProgressMessage>,
+    impact_tx: &Sender<DeletionImpact>,
+) -> Result<bool, Box<dyn Error>> {
+    if !event::poll(poll_timeout)? {
+        return Ok(false);
+    }
+    let event = event::read()?;
+    handle_event(app, mode, event, async_tx, progress_tx, impact_tx)
+}
+
+/// Handle a terminal event. Whatever touches the file systems or devices is handed to the
+/// operation queue, a scan or `App::tasks`, so this returns right away however slow the disk.
+/// Returns whether the app should quit.
+pub fn handle_event(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    event: Event,
     async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
     progress_tx: &Sender<ScanProgressMessage>,
+    impact_tx: &Sender<DeletionImpact>,
 ) -> Result<bool, Box<dyn Error>> {
-    if event::poll(Duration::from_millis(200))? {
-        if let Event::Key(key) = event::read()? {
-            // Global key handlers
+    // Any input, including a resize of the terminal, changes what is on screen
+    app.dirty = true;
+    if let Event::Key(key) = event {
+        let actions = app.config.keys.actions_for(&key);
+
+        // Global key handlers
+        // Toggle help screen
+        if actions.contains(&Action::Help) {
+            app.show_help = !app.show_help;
+            app.help_query.clear();
+            return Ok(false);
+        }
+        // The help overlay takes what is typed as a search
+        if app.show_help {
             match key.code {
-                // Toggle help screen
-                KeyCode::Char('?') => {
-                    app.show_help = !app.show_help;
-                    return Ok(false);
-                },
+                KeyCode::Esc if app.help_query.is_empty() => app.show_help = false,
+                KeyCode::Esc => app.help_query.clear(),
+                KeyCode::Backspace => {
+                    app.help_query.pop();
+                }
+                KeyCode::Char(c) => app.help_query.push(c),
                 _ => {}
             }
+            return Ok(false);
+        }
 
-            // Handle panel switching with Ctrl-l and Ctrl-h.
-            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                match key.code {
-                    KeyCode::Char('l') => {
-                        app.focus = PanelFocus::Right;
+        // Handle panel switching (Ctrl-l and Ctrl-h by default).
+        if actions.contains(&Action::FocusRight) {
+            app.focus = PanelFocus::Right;
+        } else if actions.contains(&Action::FocusLeft) {
+            app.focus = PanelFocus::Left;
+        } else {
+            // Process keys in Normal mode.
+            match mode.mode().clone() {
+                AppMode::Normal => {
+                    // Unmounted volumes are listed, but only mounting applies to them
+                    let has_device = app.devices.get(app.selected).is_some_and(|device| device.mounted);
+                    if actions.contains(&Action::Quit) {
+                        return Ok(true);
+                    } else if actions.contains(&Action::SearchDevices) && app.focus == PanelFocus::Left {
+                        // Type-ahead device selection
+                        mode.start_device_search(app.selected);
+                    } else if actions.contains(&Action::Command) {
+                        mode.start_command();
+                    } else if actions.contains(&Action::Down) {
+                        match app.focus {
+                            PanelFocus::Left => app.next(),
+                            PanelFocus::Right if app.category_view_mode => app.next_category(),
+                            PanelFocus::Right if app.dev_view_active() => app.next_project(),
+                            PanelFocus::Right if app.xcode_view_active() => app.next_xcode_item(),
+                            PanelFocus::Right if app.dir_view_active() => app.next_dir_entry(),
+                            PanelFocus::Right => app.next_file(),
+                        }
+                    } else if actions.contains(&Action::Up) {
+                        match app.focus {
+                            PanelFocus::Left => app.previous(),
+                            PanelFocus::Right if app.category_view_mode => app.previous_category(),
+                            PanelFocus::Right if app.dev_view_active() => app.previous_project(),
+                            PanelFocus::Right if app.xcode_view_active() => app.previous_xcode_item(),
+                            PanelFocus::Right if app.dir_view_active() => app.previous_dir_entry(),
+                            PanelFocus::Right => app.previous_file(),
+                        }
+                    // `r` renames in the file panel and refreshes the devices elsewhere by default
+                    } else if actions.contains(&Action::Rename) && let Some(file) = app.selected_plain_file() {
+                        mode.start_rename(file.path.clone(), file.name.clone());
+                    } else if actions.contains(&Action::Archive) && let Some(file) = app.selected_plain_file() {
+                        if cfg!(feature = "archive") {
+                            mode.start_archive(file.path.clone(), ArchiveFormat::Zip.apply_to(&file.path));
+                        } else {
+                            mode.show_message("This build can't write archives (feature `archive`)");
+                        }
+                    } else if actions.contains(&Action::Extract) && let Some(file) = app.selected_plain_file() {
+                        if !cfg!(feature = "archive") {
+                            mode.show_message("This build can't extract archives (feature `archive`)");
+                        } else if ArchiveFormat::from_path(&file.path).is_some() {
+                            mode.start_extract(file.path.clone(), ArchiveFormat::strip_extension(&file.path).to_string());
+                        } else {
+                            mode.show_message("Not an archive (.zip, .tar.gz, .tgz or .7z)");
+                        }
+                    } else if (actions.contains(&Action::Reveal) || actions.contains(&Action::OpenFile) || actions.contains(&Action::QuickLook)) && let Some(file) = app.selected_plain_file() {
+                        let path = file.path.clone();
+                        let reveal = actions.contains(&Action::Reveal);
+                        let quick_look = actions.contains(&Action::QuickLook);
+                        let command = app.handlers.open_command(&path);
+                        // The opener may take a while to start, or wait for its window to close
+                        app.tasks.spawn(move || {
+                            let file = Path::new(&path);
+                            let (opened, done) = if reveal {
+                                (open::reveal(file), format!("Revealed {} in {}", path, open::file_manager_name()))
+                            } else if quick_look {
+                                (open::quick_look(file), format!("Previewing {} (close the preview window when done)", path))
+                            } else if let Some(command) = command {
+                                (open::open_with_command(file, &command), format!("Opened {} with {}", path, command[0]))
+                            } else {
+                                (open::open_with_default_app(file), format!("Opened {}", path))
+                            };
+                            let opened = opened.map_err(|err| err.to_string());
+                            Box::new(move |app: &mut App, mode: &mut ModeMachine| match opened {
+                                Ok(()) => app.notification = Some(done),
+                                Err(err) => tasks::report(app, mode, format!("Couldn't open {}: {}", path, err)),
+                            })
+                        });
+                    } else if let Some((device_steps, progress_steps)) = panel_resize(&actions) {
+                        if app.resize_panels(device_steps, progress_steps) {
+                            let layout = app.layout;
+                            app.tasks.spawn(move || {
+                                let saved = config::save_layout(layout).map_err(|err| err.to_string());
+                                Box::new(move |app: &mut App, _: &mut ModeMachine| {
+                                    if let Err(err) = saved {
+                                        app.notification = Some(format!("Couldn't save the panel sizes: {}", err));
+                                    }
+                                })
+                            });
+                        }
+                    } else if actions.contains(&Action::ToggleGitignore) {
+                        // Takes effect from the next scan
+                        app.config.scan.respect_gitignore = !app.config.scan.respect_gitignore;
+                        app.notification = Some(if app.config.scan.respect_gitignore {
+                            "Scans now leave out what .gitignore files exclude".to_string()
+                        } else {
+                            "Scans now count ignored files too".to_string()
+                        });
+                    } else if actions.contains(&Action::ToggleCost) {
+                        // Turning it off always works, even after switching to a device without a rate
+                        if app.show_cost || app.devices.get(app.selected).is_some_and(|device| app.config.cost.model_for(device).is_some()) {
+                            app.show_cost = !app.show_cost;
+                        } else {
+                            mode.show_message("This device has no storage cost; set a rate in the [cost] section of the config file");
+                        }
+                    } else if actions.contains(&Action::ToggleAllocated) {
+                        app.show_allocated = !app.show_allocated;
+                        app.notification = Some(if app.show_allocated {
+                            "Sizes now show the space taken up on disk".to_string()
+                        } else {
+                            "Sizes now show the size of the data".to_string()
+                        });
+                    } else if actions.contains(&Action::TogglePreview) {
+                        if cfg!(feature = "preview") {
+                            app.preview_pane = !app.preview_pane;
+                        } else {
+                            mode.show_message("This build has no preview pane (feature `preview`)");
+                        }
+                    } else if actions.contains(&Action::Refresh) {
+                        app.tasks.spawn(|| {
+                            let devices = macos::detect_storage_devices();
+                            Box::new(move |app: &mut App, _: &mut ModeMachine| app.set_devices(devices))
+                        });
+                    } else if actions.contains(&Action::Eject) && has_device && app.devices[app.selected].ejectable {
+                        mode.request_eject(app.selected);
+                    } else if actions.contains(&Action::EjectAll) {
+                        mode.request_eject_all();
+                    // `u` undoes in the file panel and unmounts in the device panel by default
+                    } else if actions.contains(&Action::Undo) && app.focus == PanelFocus::Right {
+                        if app.operations.last_revert().is_some() {
+                            mode.request_undo();
+                        } else {
+                            mode.show_message("Nothing to undo");
+                        }
+                    } else if actions.contains(&Action::Unmount) && has_device {
+                        unmount_selected(app, mode);
+                    } else if actions.contains(&Action::Mount) && app.devices.get(app.selected).is_some_and(|device| !device.mounted) {
+                        mount_selected(app, mode);
+                    } else if actions.contains(&Action::EmptyTrash) && has_device && app.devices[app.selected].trash_size.is_some() {
+                        mode.request_empty_trash(app.selected);
+                    // File operations when right panel is focused
+                    } else if actions.contains(&Action::Delete) && app.focus == PanelFocus::Right && app.folder_view_mode && app.scan_mode == ScanMode::JunkScan {
+                        // Delete all junk of the selected folder
+                        if let Some(folder) = app.folder_summaries.as_ref().and_then(|folders| folders.get(app.selected_folder_index))
+                            && mode.request_folder_delete(app.selected_folder_index)
+                            && !operations::delete_needs_confirmation(&app.config.confirm, &folder.path, Some(folder.total_size))
+                        {
+                            start_folder_delete(app, mode, progress_tx, app.selected_folder_index);
+                        }
+                    } else if actions.contains(&Action::Delete) && app.focus == PanelFocus::Right && !app.folder_view_mode && !app.dev_view_active() && !app.xcode_view_active() && let Some(file) = app.get_selected_file_entry() {
+                        // Only worth looking at the entry if the config may let it skip the confirmation
+                        if operations::delete_needs_confirmation(&app.config.confirm, &file.path, Some(0))
+                            || operations::strong_confirmation(&FileOperation::Delete, &file.path).is_some()
+                        {
+                            confirm_delete(app, mode, impact_tx);
+                        } else {
+                            delete_without_confirmation(app, impact_tx);
+                        }
+                    } else if actions.contains(&Action::SecureDelete) && app.focus == PanelFocus::Right && !app.folder_view_mode && !app.dev_view_active() && !app.xcode_view_active() && app.get_selected_file_entry().is_some() {
+                        confirm_secure_delete(app, mode);
+                    } else if actions.contains(&Action::Copy) && app.focus == PanelFocus::Right && has_device {
+                        if let Some(file) = app.get_selected_file_entry() {
+                            // For now, set a dummy target path
+                            let target_path = format!("{}/copied_{}", app.devices[app.selected].mount_point,
+                                std::path::Path::new(&file.path).file_name().unwrap_or_default().to_string_lossy());
+                            preview_overwrite(app, file.path.clone(), target_path.clone());
+                            mode.request_file_op(FileOperation::Copy, app.selected_file_index, Some(target_path));
+                        }
+                    } else if actions.contains(&Action::Move) && app.focus == PanelFocus::Right && has_device {
+                        if let Some(file) = app.get_selected_file_entry() {
+                            // For now, set a dummy target path
+                            let target_path = format!("{}/moved_{}", app.devices[app.selected].mount_point,
+                                std::path::Path::new(&file.path).file_name().unwrap_or_default().to_string_lossy());
+                            preview_overwrite(app, file.path.clone(), target_path.clone());
+                            app.confirm_input.clear();
+                            mode.request_file_op(FileOperation::Move, app.selected_file_index, Some(target_path));
+                        }
+                    } else if actions.contains(&Action::Operations) {
+                        mode.open_operations();
+                    } else if actions.contains(&Action::OpenFolder) && app.focus == PanelFocus::Right && app.listing_active()
+                        && let Some(file) = app.get_selected_file_entry()
+                    {
+                        open_folder(app, file.path.clone(), async_tx);
+                    } else if actions.contains(&Action::OpenFolder) && app.focus == PanelFocus::Right && app.dir_view_active() {
+                        if !app.enter_dir_view_entry() {
+                            mode.show_message("Only directories open in the directory view");
+                        }
+                    } else if actions.contains(&Action::ParentDir) && app.dir_view_active() {
+                        app.leave_dir_view_dir();
+                    } else if actions.contains(&Action::ParentDir) && app.listing_active() && app.breadcrumbs().len() > 1 {
+                        let parent = app.breadcrumbs().len() - 2;
+                        browse_to_breadcrumb(app, mode, async_tx, parent);
+                    } else if (actions.contains(&Action::Back) || actions.contains(&Action::Forward)) && app.listing_active() {
+                        browse_history(app, mode, async_tx, actions.contains(&Action::Forward));
+                    } else if actions.contains(&Action::Breadcrumbs) && app.listing_active() && app.breadcrumbs().len() > 1 {
+                        // The parent is the likeliest target
+                        mode.open_breadcrumbs(app.breadcrumbs().len() - 2);
+                    } else if actions.contains(&Action::NewTab) {
+                        if !app.open_tab() {
+                            mode.show_message(format!("At most {} tabs can be open", crate::tabs::MAX_TABS));
+                        }
+                    } else if (actions.contains(&Action::CloseTab) && app.close_tab())
+                        || (actions.contains(&Action::NextTab) && app.cycle_tab(1))
+                        || (actions.contains(&Action::PreviousTab) && app.cycle_tab(-1))
+                        // The number keys switch to a tab unless they are bound to something
+                        || matches!(key.code, KeyCode::Char(digit @ '1'..='9') if actions.is_empty() && app.switch_tab(digit as usize - '1' as usize))
+                    {
+                        list_switched_tab(app, mode, async_tx);
+                    } else if actions.contains(&Action::JunkSuggestions) {
+                        if !cfg!(feature = "junk") {
+                            mode.show_message("This build has no junk scanner (feature `junk`)");
+                        } else if app.junk_suggestions.is_empty() {
+                            mode.show_message("No folders look like junk; suggestions come from full scans");
+                        } else {
+                            mode.open_junk_suggestions();
+                        }
+                    } else if actions.contains(&Action::QuickScan) && has_device {
+                        // Regular scan (directory listing)
+                        let mount = app.devices[app.selected].mount_point.clone();
+                        let options = app.config.scan.options(app.on_battery);
+                        let sender = async_tx.clone();
+                        tokio::spawn(async move {
+                            let result = tokio::task::spawn_blocking(move || scan_files(&mount, options))
+                                .await
+                                .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
+                            let _ = sender.send(result).await;
+                        });
+                        mode.start_listing(app.selected);
+                    } else if actions.contains(&Action::ToggleCategoryView) && app.junk_categories.is_some() && app.scan_mode == ScanMode::JunkScan {
+                        // Category summary of the junk scan
+                        app.category_view_mode = !app.category_view_mode;
+                        app.selected_category_index = 0;
+                    } else if actions.contains(&Action::ToggleCategoryView) && app.scan_mode == ScanMode::FullScan {
+                        // Space by file type of the full scan in the bottom panel
+                        if !app.toggle_type_breakdown() {
+                            mode.show_message("The file type breakdown sums up a full scan; run one first");
+                        }
+                    } else if actions.contains(&Action::ToggleCategory) && app.category_view_mode {
+                        app.toggle_selected_category();
+                    } else if actions.contains(&Action::DockerPrune) && app.scan_mode == ScanMode::JunkScan && app.docker_usage.is_some() {
+                        mode.request_docker_prune(docker::DockerResource::DEFAULT_PRUNE.to_vec());
+                    } else if actions.contains(&Action::ClearBrowserCache) && app.scan_mode == ScanMode::JunkScan && !app.browser_profiles.is_empty() {
+                        mode.request_browser_cache_clear(app.suggested_browser_profiles());
+                    } else if actions.contains(&Action::DevScan) && has_device {
+                        // Search the device (or the home directory on system storage) for build artifacts
+                        start_dev_scan(app, mode, progress_tx);
+                    } else if actions.contains(&Action::ToggleProject) && app.dev_view_active() {
+                        app.toggle_selected_project();
+                    } else if actions.contains(&Action::CleanArtifacts) && app.dev_view_active() && !app.selected_projects().is_empty() {
+                        mode.request_clean();
+                    } else if actions.contains(&Action::ToggleProject) && app.xcode_view_active() {
+                        app.toggle_selected_xcode_item();
+                    } else if actions.contains(&Action::CleanArtifacts) && app.xcode_view_active() && !app.selected_xcode_items().is_empty() {
+                        mode.request_clean();
+                    } else if actions.contains(&Action::ToggleFolderView) && app.folder_summaries.is_some() && app.scan_mode == ScanMode::JunkScan {
+                        // Toggle folder view in junk scan mode
+                        app.category_view_mode = false;
+                        app.folder_view_mode = !app.folder_view_mode;
+                        // Reset indices when switching views
+                        if app.folder_view_mode {
+                            app.selected_file_index = 0;
+                        } else {
+                            app.selected_folder_index = 0;
+                        }
+                        app.file_list_offset = 0;
+                    } else if actions.contains(&Action::ToggleFolderView) && app.scan_mode == ScanMode::FullScan {
+                        // Directory sizes of the full scan instead of its files
+                        if !app.toggle_dir_view() {
+                            mode.show_message("The directory view sums up a full scan; run one first");
+                        }
+                    } else if actions.contains(&Action::OpenFolder) && app.folder_view_mode && app.folder_summaries.is_some() {
+                        // When in folder view, switch to file view showing files from selected folder
+                        app.folder_view_mode = false;
+                        // TODO: Filter files to show only those from selected folder
+                        app.selected_file_index = 0;
+                        app.file_list_offset = 0;
+                    } else if actions.contains(&Action::FullScan) && has_device {
+                        // Full device scan with progress tracking
+                        request_device_scan(app, mode, progress_tx, false);
+                    } else if actions.contains(&Action::IncrementalScan) && has_device && app.devices[app.selected].ejectable {
+                        // Incremental rescan: only re-walk directories changed since the cached scan
+                        request_device_scan(app, mode, progress_tx, true);
                     }
-                    KeyCode::Char('h') => {
-                        app.focus = PanelFocus::Left;
+                },
+                AppMode::ConfirmEject(index) => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            eject_device(app, mode, index, false);
+                        },
+                        // Volumes still holding trashed files can have it emptied first
+                        KeyCode::Char('t') | KeyCode::Char('T') if app.devices.get(index).and_then(|device| device.trash_size).is_some_and(|size| size > 0) => {
+                            eject_device(app, mode, index, true);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') => {
+                            mode.cancel();
+                        },
+                        _ => {}
                     }
-                    _ => {}
-                }
-            } else {
-                // Process keys in Normal mode.
-                match mode {
-                    AppMode::Normal => {
-                        match key.code {
-                            KeyCode::Char('q') => return Ok(true),
-                            KeyCode::Char('j') if app.focus == crate::PanelFocus::Left => {
-                                app.next();
-                            },
-                            KeyCode::Char('k') if app.focus == crate::PanelFocus::Left => {
-                                app.previous();
-                            },
-                            KeyCode::Char('j') | KeyCode::Down if app.focus == crate::PanelFocus::Right => {
-                                app.next_file();
-                            },
-                            KeyCode::Char('k') | KeyCode::Up if app.focus == crate::PanelFocus::Right => {
-                                app.previous_file();
-                            },
-                            KeyCode::Char('r') => {
-                                app.refresh();
-                            },
-                            KeyCode::Char('e') => {
-                                if !app.devices.is_empty() && app.devices[app.selected].ejectable {
-                                    *mode = AppMode::ConfirmEject(app.selected);
-                                }
-                            },
-                            // File operations when right panel is focused
-                            KeyCode::Char('d') if app.focus == crate::PanelFocus::Right => {
-                                if app.get_selected_file_entry().is_some() {
-                                    *mode = AppMode::ConfirmFileOp {
-                                        op_type: crate::FileOperation::Delete,
-                                        file_index: app.selected_file_index,
-                                        target_path: None,
-                                    };
-                                }
-                            },
-                            KeyCode::Char('c') if app.focus == crate::PanelFocus::Right => {
-                                if let Some(file) = app.get_selected_file_entry() {
-                                    // For now, set a dummy target path
-                                    let target_path = format!("{}/copied_{}", app.devices[app.selected].mount_point,
-                                        std::path::Path::new(&file.path).file_name().unwrap_or_default().to_string_lossy());
-                                    *mode = AppMode::ConfirmFileOp {
-                                        op_type: crate::FileOperation::Copy,
-                                        file_index: app.selected_file_index,
-                                        target_path: Some(target_path),
-                                    };
-                                }
-                            },
-                            KeyCode::Char('m') if app.focus == crate::PanelFocus::Right => {
-                                if let Some(file) = app.get_selected_file_entry() {
-                                    // For now, set a dummy target path
-                                    let target_path = format!("{}/moved_{}", app.devices[app.selected].mount_point,
-                                        std::path::Path::new(&file.path).file_name().unwrap_or_default().to_string_lossy());
-                                    *mode = AppMode::ConfirmFileOp {
-                                        op_type: crate::FileOperation::Move,
-                                        file_index: app.selected_file_index,
-                                        target_path: Some(target_path),
-                                    };
-                                }
-                            },
-                            KeyCode::Char('s') => {
-                                // Regular scan (directory listing)
-                                if !app.devices.is_empty() {
-                                    let mount = app.devices[app.selected].mount_point.clone();
-                                    let sender = async_tx.clone();
-                                    tokio::spawn(async move {
-                                        let result = tokio::task::spawn_blocking(move || scan_files(&mount))
-                                            .await
-                                            .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
-                                        let _ = sender.send(result).await;
-                                    });
-                                    *mode = AppMode::Scanning { device_index: app.selected, spinner_index: 0 };
-                                }
-                            },
-                            KeyCode::Tab => {
-                                // Toggle folder view in junk scan mode
-                                if app.folder_summaries.is_some() && app.scan_mode == crate::ScanMode::JunkScan {
-                                    app.folder_view_mode = !app.folder_view_mode;
-                                    // Reset indices when switching views
-                                    if app.folder_view_mode {
-                                        app.selected_file_index = 0;
-                                    } else {
-                                        app.selected_folder_index = 0;
-                                    }
-                                    app.file_list_offset = 0;
-                                }
-                            },
-                            KeyCode::Enter => {
-                                // When in folder view, switch to file view showing files from selected folder
-                                if app.folder_view_mode && app.folder_summaries.is_some() {
-                                    app.folder_view_mode = false;
-                                    // TODO: Filter files to show only those from selected folder
-                                    app.selected_file_index = 0;
-                                    app.file_list_offset = 0;
-                                }
-                            },
-                            KeyCode::Char('S') => {
-                                // Full device scan with progress tracking
-                                if !app.devices.is_empty() {
-                                    let device = &app.devices[app.selected];
-                                    let mount = device.mount_point.clone();
-                                    let total_size = device.total_space;
-                                    let is_system_storage = !device.ejectable;
-
-                                    // Reset folder view mode
-                                    app.folder_view_mode = false;
-                                    app.selected_folder_index = 0;
-
-                                    // Set up progress tracking
-                                    app.scan_progress = ScanProgress {
-                                        total_bytes: total_size,
-                                        scanned_bytes: 0,
-                                        files_processed: 0,
-                                        in_progress: true,
-                                        current_file: None,
-                                    };
-
-                                    // Create a clone of the progress channel
-                                    let progress_sender = progress_tx.clone();
-
-                                    // Different scan types based on device type
-                                    if is_system_storage {
-                                        // For system storage, scan for junk files
-                                        app.scan_mode = ScanMode::JunkScan;
-                                        
-                                        // Spawn the junk scan task
-                                        let progress_clone = progress_sender.clone();
-                                        tokio::spawn(async move {
-                                            let _ = junk_scanner::scan_system_junk(progress_clone).await;
-                                        });
-                                    } else {
-                                        // For external/ejectable devices, do a full scan
-                                        app.scan_mode = ScanMode::FullScan;
-                                        
-                                        // Spawn the full scan task
-                                        tokio::spawn(async move {
-                                            let _ = tokio::task::spawn_blocking(move || {
-                                                full_scan_with_progress(&mount, total_size, progress_sender)
-                                            }).await;
-                                        });
-                                    }
-
-                                    *mode = AppMode::FullScan {
-                                        device_index: app.selected,
-                                        spinner_index: 0
-                                    };
+                },
+                AppMode::ConfirmBatteryScan { incremental } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            mode.cancel();
+                            start_device_scan(app, mode, progress_tx, incremental);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmEjectAll => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') if app.devices.iter().any(|device| device.ejectable) => {
+                            eject_all(app, mode);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmEmptyTrash(index) => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            if let Some(device) = app.devices.get(index) {
+                                let working = format!("Emptying the trash of {}", device.name);
+                                match app.empty_trash(index) {
+                                    Ok(()) => {
+                                        mode.start_task(working);
+                                    },
+                                    Err(message) => mode.show_message(message),
                                 }
-                            },
-                            _ => {}
-                        }
-                    },
-                    AppMode::ConfirmEject(index) => {
-                        match key.code {
-                            KeyCode::Char('y') | KeyCode::Char('Y') => {
-                                if let Some(device) = app.devices.get(*index) {
-                                    // Clone the device info we need before borrowing
-                                    let device_name = device.name.clone();
-                                    // Unused variable - remove it
-                                    // let device_mount = device.mount_point.clone();
-
-                                    match macos::eject_device(device) {
+                            } else {
+                                mode.cancel();
+                            }
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::EjectFailed { device_index, .. } => {
+                    let escalation = macos::EjectEscalation::ALL
+                        .into_iter()
+                        .find(|escalation| key.code == KeyCode::Char(escalation.key()));
+                    match (escalation, app.devices.get(device_index).cloned()) {
+                        (Some(escalation), Some(device)) => {
+                            mode.start_task(format!("{} {}", escalation.label(), device.name));
+                            app.tasks.spawn(move || {
+                                let result = device_guard::eject_when_idle(&device.mount_point, EJECT_CANCEL_TIMEOUT, || {
+                                    macos::escalate_eject(&device, escalation).map_err(|err| err.to_string())
+                                });
+                                let holders = result.is_err().then(|| holders::volume_holders(&device.mount_point)).flatten();
+                                // Refreshed even if it failed, in case of partial ejection
+                                let devices = macos::detect_storage_devices();
+                                Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                                    app.set_devices(devices);
+                                    match result {
                                         Ok(()) => {
-                                            // Use refresh instead of manual removal to ensure consistency
-                                            app.refresh();
-                                            // Clear any file listings for the ejected device
                                             app.file_entries = None;
                                             app.full_scan_results = None;
-                                            *mode = AppMode::Ejected(format!("Ejected Device: {} successfully", device_name));
+                                            let done = if escalation == macos::EjectEscalation::UnmountOnly { "Unmounted" } else { "Ejected" };
+                                            tasks::report(app, mode, format!("{} Device: {} successfully", done, device.name));
                                         },
                                         Err(err) => {
-                                            // Still refresh in case of partial ejection
-                                            app.refresh();
-                                            *mode = AppMode::Ejected(format!("Failed to eject {}: {}", device_name, err));
-                                        },
-                                    }
-                                } else {
-                                    *mode = AppMode::Normal;
-                                }
-                            },
-                            KeyCode::Char('n') | KeyCode::Char('N') => {
-                                *mode = AppMode::Normal;
-                            },
-                            _ => {}
-                        }
-                    },
-                    AppMode::Ejected(_) => {
-                        *mode = AppMode::Normal;
-                    },
-                    AppMode::ConfirmFileOp { op_type, file_index, target_path } => {
-                        match key.code {
-                            KeyCode::Char('y') | KeyCode::Char('Y') => {
-                                // Clone needed values from the operation
-                                let op_type_clone = op_type.clone();
-                                let file_index_clone = *file_index;
-                                let target_path_clone = target_path.clone();
-
-                                // Get the source file path
-                                if let Some(file) = app.get_selected_file_entry() {
-                                    let source_path = file.path.clone();
-
-                                    // Perform the file operation
-                                    match perform_file_operation(
-                                        &op_type_clone,
-                                        &source_path,
-                                        target_path_clone.as_deref()
-                                    ) {
-                                        Ok(result) => {
-                                            // Refresh file list after the operation
-                                            app.selected_file_index = 0;
-
-                                            if let Some(ref mut entries) = app.full_scan_results {
-                                                // For deletion, remove from the list
-                                                if let FileOperation::Delete = op_type_clone {
-                                                    if file_index_clone < entries.len() {
-                                                        entries.remove(file_index_clone);
-                                                    }
-                                                }
+                                            let error = format!("{} failed: {}", escalation.label(), err);
+                                            if !mode.eject_failed(device_index, error.clone(), holders) {
+                                                tasks::report(app, mode, error);
                                             }
-
-                                            // Trigger a refresh of the regular file listing as well
-                                            app.file_entries = None;
-                                            app.scanning = true;
-                                            let mount = app.devices[app.selected].mount_point.clone();
-                                            let sender = async_tx.clone();
-                                            tokio::spawn(async move {
-                                                let result = tokio::task::spawn_blocking(move ||
-                                                    crate::scanner::list_directory(&mount)
-                                                ).await.unwrap_or_else(|e|
-                                                    Err(Box::new(e) as Box<dyn Error + Send + 'static>)
-                                                );
-                                                let _ = sender.send(result).await;
-                                            });
-
-                                            *mode = AppMode::Ejected(format!("File operation result: {}", result));
                                         },
-                                        Err(err) => {
-                                            *mode = AppMode::Ejected(format!("Operation failed: {}", err));
-                                        }
                                     }
-                                } else {
-                                    *mode = AppMode::Normal;
+                                })
+                            });
+                        },
+                        (None, _) if matches!(key.code, KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc) => {
+                            mode.cancel();
+                        },
+                        // The device went away in the meantime
+                        (Some(_), None) => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmDockerPrune { selected } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') if !selected.is_empty() => {
+                            mode.start_task("Pruning Docker data");
+                            app.tasks.spawn(move || {
+                                let mut results = Vec::new();
+                                for resource in docker::DockerResource::ALL.into_iter().filter(|r| selected.contains(r)) {
+                                    match docker::prune(resource) {
+                                        Ok(summary) => results.push(format!("{}: {}", resource.label(), summary)),
+                                        Err(err) => results.push(format!("{} failed: {}", resource.label(), err)),
+                                    }
                                 }
-                            },
-                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
-                                *mode = AppMode::Normal;
-                            },
-                            _ => {}
+                                // Show what is left
+                                let usage = docker::disk_usage();
+                                Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                                    app.docker_usage = usage;
+                                    tasks::report(app, mode, results.join("\n"));
+                                })
+                            });
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        KeyCode::Char(c) => {
+                            if let Some(resource) = docker::DockerResource::ALL.into_iter().find(|r| r.key() == c) {
+                                mode.toggle_docker_resource(resource);
+                            }
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmFolderDelete { folder_index } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            start_folder_delete(app, mode, progress_tx, folder_index);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmBrowserCacheClear { selected } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') if !selected.is_empty() => {
+                            mode.start_task("Clearing browser caches");
+                            app.clear_browser_caches(&selected);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        // Profiles are toggled by their number, 1-9
+                        KeyCode::Char(c) => {
+                            if let Some(number) = c.to_digit(10).filter(|n| *n >= 1)
+                                && (number as usize) <= app.browser_profiles.len()
+                            {
+                                mode.toggle_browser_profile(number as usize - 1);
+                            }
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::DeviceSearch { mut query, previous } => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            mode.finish_device_search();
+                        },
+                        KeyCode::Esc => {
+                            app.selected = previous.min(app.devices.len().saturating_sub(1));
+                            mode.cancel();
+                        },
+                        KeyCode::Backspace => {
+                            query.pop();
+                            app.select_device_matching(&query);
+                            mode.set_search_query(query);
+                        },
+                        KeyCode::Char(c) => {
+                            query.push(c);
+                            app.select_device_matching(&query);
+                            mode.set_search_query(query);
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Command { mut input } => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            return run_command(app, mode, progress_tx, input.trim());
+                        },
+                        KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        // Backspace on an empty command line closes it, as in vim
+                        KeyCode::Backspace if input.is_empty() => {
+                            mode.cancel();
+                        },
+                        KeyCode::Backspace => {
+                            input.pop();
+                            mode.set_command_input(input);
+                        },
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            mode.set_command_input(input);
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Rename { path, mut input } => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            mode.finish_rename();
+                            if let Err(e) = app.rename_entry(&path, &input) {
+                                mode.show_message(format!("Rename failed: {}", e));
+                            }
+                        },
+                        KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        KeyCode::Backspace => {
+                            input.pop();
+                            mode.set_rename_input(input);
+                        },
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            mode.set_rename_input(input);
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Archive { path, mut input } => {
+                    match key.code {
+                        KeyCode::Enter if !ArchiveFormat::from_path(&input).is_some_and(ArchiveFormat::writable) => {
+                            mode.show_message("The archive must end in .zip, .tar.gz or .tgz");
+                        },
+                        KeyCode::Enter => {
+                            mode.finish_archive();
+                            app.notification = Some(format!("Archiving {} into {}", path, input));
+                            app.operations.enqueue(FileOperation::Archive, path, Some(input));
+                        },
+                        KeyCode::Tab => {
+                            let format = ArchiveFormat::from_path(&input).map_or(ArchiveFormat::Zip, ArchiveFormat::toggled);
+                            mode.set_archive_input(format.apply_to(&input));
+                        },
+                        KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        KeyCode::Backspace => {
+                            input.pop();
+                            mode.set_archive_input(input);
+                        },
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            mode.set_archive_input(input);
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Extract { path, mut input } => {
+                    match key.code {
+                        KeyCode::Enter if input.trim().is_empty() => {
+                            mode.show_message("Enter the folder to extract into");
+                        },
+                        KeyCode::Enter => {
+                            mode.finish_extract();
+                            app.notification = Some(format!("Extracting {} into {}", path, input));
+                            app.operations.enqueue(FileOperation::Extract, path, Some(input));
+                        },
+                        KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        KeyCode::Backspace => {
+                            input.pop();
+                            mode.set_extract_input(input);
+                        },
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            mode.set_extract_input(input);
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::History { offset } => {
+                    if actions.contains(&Action::Down) {
+                        mode.scroll_history((offset + 1).min(app.scan_history.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.scroll_history(offset.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ScanErrors { offset } => {
+                    if actions.contains(&Action::Down) {
+                        mode.scroll_scan_errors((offset + 1).min(app.scan_errors.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.scroll_scan_errors(offset.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::StaleFiles { offset } => {
+                    if actions.contains(&Action::Down) {
+                        mode.scroll_stale_files((offset + 1).min(app.stale_files.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.scroll_stale_files(offset.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ScanChanges { offset } => {
+                    if actions.contains(&Action::Down) {
+                        mode.scroll_scan_changes((offset + 1).min(app.scan_changes.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.scroll_scan_changes(offset.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::BrokenLinks { offset } => {
+                    if actions.contains(&Action::Down) {
+                        mode.scroll_broken_links((offset + 1).min(app.broken_links.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.scroll_broken_links(offset.saturating_sub(1));
+                    } else if actions.contains(&Action::Delete) && !app.broken_links.is_empty() {
+                        mode.request_broken_link_delete();
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ConfirmBrokenLinkDelete => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            mode.start_task(format!("Deleting {} broken links", app.broken_links.len()));
+                            delete_broken_links(app);
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Duplicates { selected } => {
+                    let files = app.duplicate_groups.iter().map(|group| group.files.len()).sum::<usize>();
+                    if key.code == KeyCode::Char('n') && files > 0 {
+                        plan_duplicate_removal(app, mode, analysis::KeepRule::Newest);
+                    } else if key.code == KeyCode::Char('f')
+                        && let Some(dir) = app.duplicate_groups.iter().flat_map(|group| &group.files).nth(selected).and_then(|file| Path::new(&file.path).parent())
+                    {
+                        let rule = analysis::KeepRule::Under(dir.to_path_buf());
+                        plan_duplicate_removal(app, mode, rule);
+                    } else if actions.contains(&Action::Down) {
+                        mode.select_duplicate((selected + 1).min(files.saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.select_duplicate(selected.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ConfirmDuplicateResolve { .. } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            if let Some(plan) = app.duplicate_plan.take() {
+                                mode.start_task(format!("Checking {} duplicates before deleting them", plan.removals.len()));
+                                resolve_duplicates(app, plan);
+                            }
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            app.duplicate_plan = None;
+                            mode.review_duplicates();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Goal { selected } => {
+                    let count = app.goal.as_ref().map_or(0, |goal| goal.plan.candidates.len());
+                    if key.code == KeyCode::Char(' ') {
+                        if let Some(goal) = app.goal.as_mut() {
+                            goal.plan.toggle(selected);
+                        }
+                    } else if key.code == KeyCode::Char('a') {
+                        if let Some(goal) = app.goal.as_mut() {
+                            goal.plan.accept_until_met();
+                        }
+                    } else if key.code == KeyCode::Enter {
+                        if app.goal.as_ref().is_some_and(|goal| goal.plan.accepted().next().is_some()) {
+                            mode.request_goal_run();
+                        } else {
+                            app.notification = Some("Accept candidates with Space, or a to accept enough for the goal".to_string());
+                        }
+                    } else if actions.contains(&Action::Down) {
+                        mode.select_goal_candidate((selected + 1).min(count.saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.select_goal_candidate(selected.saturating_sub(1));
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ConfirmGoal { .. } => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            if let Some(goal) = app.goal.take() {
+                                mode.start_task(format!("Freeing space on {}", goal.device));
+                                run_goal(app, goal);
+                            }
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.review_goal();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::LargestDirs { selected } => {
+                    if actions.contains(&Action::Down) {
+                        mode.select_largest_dir((selected + 1).min(app.largest_dirs.len().saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.select_largest_dir(selected.saturating_sub(1));
+                    } else if actions.contains(&Action::OpenFolder) {
+                        // Show the highlighted directory in the directory view
+                        mode.cancel();
+                        let dir = app.largest_dirs.get(selected).map(|(dir, _)| dir.clone());
+                        if dir.is_some_and(|dir| app.open_dir_view_at(&dir)) {
+                            app.focus = PanelFocus::Right;
+                        }
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::Operations { selected } => {
+                    let count = app.operations.operations().len();
+                    if actions.contains(&Action::Down) {
+                        mode.select_operation((selected + 1).min(count.saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.select_operation(selected.saturating_sub(1));
+                    } else if actions.contains(&Action::Cancel) {
+                        app.operations.cancel(selected);
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Operations) || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::JunkSuggestions { selected } => {
+                    let count = app.junk_suggestions.len();
+                    if actions.contains(&Action::Down) {
+                        mode.select_junk_suggestion((selected + 1).min(count.saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) {
+                        mode.select_junk_suggestion(selected.saturating_sub(1));
+                    } else if actions.contains(&Action::AcceptSuggestion) && selected < count {
+                        accept_junk_suggestion(app, selected);
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::JunkSuggestions) || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::Breadcrumbs { selected } => {
+                    let count = app.breadcrumbs().len();
+                    if actions.contains(&Action::Down) || key.code == KeyCode::Right {
+                        mode.select_breadcrumb((selected + 1).min(count.saturating_sub(1)));
+                    } else if actions.contains(&Action::Up) || key.code == KeyCode::Left {
+                        mode.select_breadcrumb(selected.saturating_sub(1));
+                    } else if actions.contains(&Action::OpenFolder) {
+                        mode.cancel();
+                        browse_to_breadcrumb(app, mode, async_tx, selected);
+                    } else if key.code == KeyCode::Esc || actions.contains(&Action::Breadcrumbs) || actions.contains(&Action::Quit) {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ConfirmUndo => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            // Runs in the background like the operation it reverts
+                            app.operations.undo_last();
+                            mode.cancel();
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::Ejected(_) => {
+                    mode.dismiss();
+                },
+                AppMode::Working(_) => {
+                    // Only hides the popup; the result shows once the task is done
+                    if key.code == KeyCode::Esc {
+                        mode.cancel();
+                    }
+                },
+                AppMode::ConfirmClean => {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                            if app.xcode_view_active() {
+                                mode.start_task("Removing Xcode data");
+                                app.clean_selected_xcode_items();
+                            } else {
+                                mode.start_task("Removing build artifacts");
+                                app.clean_selected_projects();
+                            }
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            mode.cancel();
+                        },
+                        _ => {}
+                    }
+                },
+                AppMode::ConfirmFileOp { op_type, target_path, .. } => {
+                    // What isn't the user's own data is confirmed by typing its name
+                    let typed_name = app
+                        .get_selected_file_entry()
+                        .filter(|file| operations::strong_confirmation(&op_type, &file.path).is_some())
+                        .map(|file| operations::confirmation_name(&file.path));
+                    let confirmed = match (&typed_name, key.code) {
+                        (Some(name), KeyCode::Enter) => *name == app.confirm_input,
+                        (Some(_), KeyCode::Char(c)) => {
+                            app.confirm_input.push(c);
+                            false
                         }
-                    },
-                    AppMode::Scanning { .. } => {
-                        // Allow quitting or canceling during regular scan
-                        match key.code {
-                            KeyCode::Char('q') => {
-                                return Ok(true);
-                            },
-                            KeyCode::Char('c') => {
-                                app.scanning = false;
-                                *mode = AppMode::Normal;
-                            },
-                            _ => {}
+                        (Some(_), KeyCode::Backspace) => {
+                            app.confirm_input.pop();
+                            false
                         }
-                    },
-                    AppMode::FullScan { .. } => {
-                        match key.code {
-                            // Allow quitting during full scan
-                            KeyCode::Char('q') => {
-                                return Ok(true);
-                            },
-                            // Cancel the full scan
-                            KeyCode::Char('c') => {
-                                app.scan_progress.in_progress = false;
-                                *mode = AppMode::Normal;
-                            },
-                            _ => {}
+                        (None, KeyCode::Char('y') | KeyCode::Char('Y')) => true,
+                        (None, KeyCode::Char('n') | KeyCode::Char('N')) | (_, KeyCode::Esc) => {
+                            app.confirm_input.clear();
+                            mode.cancel();
+                            false
                         }
-                    },
+                        _ => false,
+                    };
+                    if confirmed {
+                        app.confirm_input.clear();
+                        // Queue it; it runs in the background next to other operations
+                        if let Some(file) = app.get_selected_file_entry() {
+                            let source_path = file.path.clone();
+                            if op_type == FileOperation::Delete {
+                                app.queue_confirmed_delete(source_path);
+                            } else {
+                                app.operations.enqueue(op_type, source_path, target_path);
+                            }
+                            mode.queue_file_op();
+                        } else {
+                            mode.cancel();
+                        }
+                    }
+                },
+                AppMode::Scanning { .. } => {
+                    // Allow quitting or canceling during regular scan
+                    if actions.contains(&Action::Quit) {
+                        return Ok(true);
+                    } else if actions.contains(&Action::Cancel) {
+                        app.scanning = false;
+                        mode.cancel();
+                    }
+                },
+                AppMode::FullScan { .. } => {
+                    // Allow quitting during full scan
+                    if actions.contains(&Action::Quit) {
+                        return Ok(true);
+                    // Stop a folder deletion; it reports what was deleted so far when it stops
+                    } else if actions.contains(&Action::Cancel) && let Some(job) = &app.folder_delete {
+                        job.stop();
+                    } else if actions.contains(&Action::Pause) && let Some(job) = &app.folder_delete {
+                        job.toggle_pause();
+                    // Switch between the largest files found so far and the listing
+                    } else if actions.contains(&Action::ToggleFolderView) && app.scan_mode == ScanMode::FullScan && app.largest_so_far.is_some() {
+                        app.show_largest_so_far = !app.show_largest_so_far;
+                    // Browse the listing while a full scan runs; the highlighted directory is scanned next
+                    } else if actions.contains(&Action::Down) && app.focus == PanelFocus::Right && app.scan_mode == ScanMode::FullScan && !app.largest_so_far_shown() {
+                        app.next_file();
+                        app.update_scan_priority();
+                    } else if actions.contains(&Action::Up) && app.focus == PanelFocus::Right && app.scan_mode == ScanMode::FullScan && !app.largest_so_far_shown() {
+                        app.previous_file();
+                        app.update_scan_priority();
+                    // Cancel the full scan
+                    } else if actions.contains(&Action::Cancel) {
+                        app.scan_progress.in_progress = false;
+                        mode.cancel();
+                    }
+                },
+            }
+        }
+    } else if let Event::Mouse(mouse) = event
+        && mode.is_normal()
+        && !app.show_help
+    {
+        // Popups cover the panels, so the mouse only applies to the main screen
+        let area = match app.viewport {
+            Some(area) => area,
+            None => {
+                let (width, height) = crossterm::terminal::size()?;
+                Rect::new(0, 0, width, height)
+            }
+        };
+        handle_mouse(app, mode, async_tx, mouse, &ui::screen_layout(area, app));
+    }
+    Ok(false)
+}
+
+/// Steps the device and progress panels grow by for a resize key.
+fn panel_resize(actions: &[Action]) -> Option<(i16, i16)> {
+    actions.iter().find_map(|action| match action {
+        Action::WidenDevicePanel => Some((1, 0)),
+        Action::NarrowDevicePanel => Some((-1, 0)),
+        Action::GrowProgressPanel => Some((0, 1)),
+        Action::ShrinkProgressPanel => Some((0, -1)),
+        _ => None,
+    })
+}
+
+/// Lines scrolled by one step of the mouse wheel.
+const WHEEL_LINES: usize = 3;
+
+/// Clicking a device or a listed file selects it, clicking anywhere else in a panel focuses that
+/// panel, and the wheel scrolls the file list.
+fn handle_mouse(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    mouse: MouseEvent,
+    layout: &ScreenLayout,
+) {
+    let hits = |area: Rect| (area.x..area.x + area.width).contains(&mouse.column) && (area.y..area.y + area.height).contains(&mouse.row);
+    // Entries the file list shows below its border and header
+    let visible_rows = (layout.file_list.height.saturating_sub(4) as usize).min(20);
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            if hits(layout.devices) {
+                app.focus = PanelFocus::Left;
+                // The list scrolls just far enough to show the selected device
+                let rows = layout.devices.height.saturating_sub(2) as usize;
+                let offset = (app.selected + 1).saturating_sub(rows);
+                let row = (mouse.row - layout.devices.y) as usize;
+                if let Some(row) = row.checked_sub(1).filter(|&row| row < rows)
+                    && offset + row < app.devices.len()
+                {
+                    app.selected = offset + row;
                 }
+            } else if hits(layout.details) || hits(layout.gauge) {
+                app.focus = PanelFocus::Left;
+            } else if hits(layout.file_list) && mouse.row == layout.file_list.y {
+                // A segment of the breadcrumb path bar in the title jumps to its directory
+                app.focus = PanelFocus::Right;
+                if app.listing_active() && let Some(index) = ui::breadcrumb_at(app, layout.file_list, mouse.column) {
+                    browse_to_breadcrumb(app, mode, async_tx, index);
+                }
+            } else if hits(layout.file_list) {
+                app.focus = PanelFocus::Right;
+                // Below the top border, the header and its margin
+                let row = (mouse.row - layout.file_list.y) as usize;
+                if let Some(row) = row.checked_sub(3).filter(|&row| row < visible_rows)
+                    && let Some(count) = listed_count(app)
+                    && app.file_list_offset + row < count
+                {
+                    if app.folder_view_mode {
+                        app.selected_folder_index = app.file_list_offset + row;
+                    } else {
+                        app.selected_file_index = app.file_list_offset + row;
+                    }
+                }
+            } else if layout.preview.is_some_and(hits) || hits(layout.progress) {
+                app.focus = PanelFocus::Right;
             }
         }
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp if hits(layout.file_list) => {
+            let Some(count) = listed_count(app) else {
+                return;
+            };
+            app.file_list_offset = if mouse.kind == MouseEventKind::ScrollDown {
+                (app.file_list_offset + WHEEL_LINES).min(count.saturating_sub(visible_rows))
+            } else {
+                app.file_list_offset.saturating_sub(WHEEL_LINES)
+            };
+            // Keep the highlighted entry on screen, as moving it with the keys does
+            let last_visible = app.file_list_offset + visible_rows.saturating_sub(1);
+            let selected = if app.folder_view_mode { &mut app.selected_folder_index } else { &mut app.selected_file_index };
+            *selected = (*selected).clamp(app.file_list_offset, last_visible.max(app.file_list_offset));
+        }
+        _ => {}
     }
+}
+
+/// Number of entries in the file list when the right panel shows the files of a listing or scan,
+/// or the folders of a junk scan, which scroll with `file_list_offset`. `None` for the other
+/// views, which scroll on their own.
+fn listed_count(app: &App) -> Option<usize> {
+    if app.xcode_view_active() || app.dev_view_active() || app.dir_view_active() || app.largest_so_far_shown() || (app.category_view_mode && app.junk_categories.is_some()) {
+        None
+    } else if app.folder_view_mode {
+        app.folder_summaries.as_ref().map(Vec::len)
+    } else if app.scanning {
+        None
+    } else {
+        app.full_scan_results.as_ref().or(app.file_entries.as_ref()).map(Vec::len)
+    }
+}
+
+/// List `dir` in the file panel instead of the root of the selected device, or the root again
+/// if `dir` is `None`. Going elsewhere is recorded in the device's navigation history.
+fn browse_to(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    dir: Option<String>,
+) {
+    let Some(device) = app.devices.get(app.selected) else {
+        return;
+    };
+    let dir = dir.filter(|dir| *dir != device.mount_point);
+    if dir != app.browse_dir {
+        app.nav_history.entry(device.mount_point.clone()).or_default().visit(app.browse_dir.clone());
+    }
+    list_dir(app, mode, async_tx, dir);
+}
+
+/// Go back to the directory of the selected device listed before the current one, or forward
+/// again with `forward`.
+fn browse_history(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    forward: bool,
+) {
+    let Some(device) = app.devices.get(app.selected) else {
+        return;
+    };
+    let history = app.nav_history.entry(device.mount_point.clone()).or_default();
+    let current = app.browse_dir.clone();
+    let dir = if forward { history.go_forward(current) } else { history.go_back(current) };
+    if let Some(dir) = dir {
+        list_dir(app, mode, async_tx, dir);
+    }
+}
+
+/// List `dir` of the selected device, or its root if `dir` is `None`, without recording it in
+/// the navigation history.
+fn list_dir(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    dir: Option<String>,
+) {
+    let Some(device) = app.devices.get(app.selected) else {
+        return;
+    };
+    let path = dir.clone().unwrap_or_else(|| device.mount_point.clone());
+    app.browse_dir = dir;
+    app.file_entries = None;
+    app.selected_file_index = 0;
+    app.file_list_offset = 0;
+    app.scanning = true;
+    let sender = async_tx.clone();
+    let symlinks = app.config.scan.symlinks;
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || list_directory(&path, symlinks))
+            .await
+            .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
+        let _ = sender.send(result).await;
+    });
+    mode.start_listing(app.selected);
+}
+
+/// List the directory of a tab that was switched to but has nothing listed, because its device
+/// was gone or only mounted since.
+fn list_switched_tab(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+) {
+    if app.file_entries.is_none() && app.devices.get(app.selected).is_some_and(|device| device.mounted) {
+        browse_to(app, mode, async_tx, app.browse_dir.clone());
+    }
+}
+
+/// List the directory of the segment at `index` of the breadcrumb path bar (0 is the device root).
+fn browse_to_breadcrumb(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+    index: usize,
+) {
+    let crumbs = app.breadcrumbs();
+    match crumbs.get(index) {
+        Some(_) if index == 0 => browse_to(app, mode, async_tx, None),
+        Some(dir) => browse_to(app, mode, async_tx, Some(dir.clone())),
+        None => {}
+    }
+}
+
+/// Add the suggestion at `index` to the user's junk paths and drop it from the list; the panel
+/// closes once none are left.
+fn accept_junk_suggestion(app: &mut App, index: usize) {
+    #[cfg(feature = "junk")]
+    {
+        let path = app.junk_suggestions[index].path.clone();
+        // Saved to the config file in the background
+        app.tasks.spawn(move || {
+            let added = crate::platform::junk_scanner::add_user_junk_path(&path).map_err(|err| err.to_string());
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                if let Err(err) = added {
+                    tasks::report(app, mode, format!("Couldn't add {} to your junk paths: {}", path, err));
+                    return;
+                }
+                app.junk_suggestions.retain(|suggestion| suggestion.path != path);
+                app.notification = Some(format!("Added {} to your junk paths; it shows up in the next junk scan", path));
+                if let AppMode::JunkSuggestions { selected } = *mode.mode() {
+                    if app.junk_suggestions.is_empty() {
+                        mode.cancel();
+                    } else {
+                        mode.select_junk_suggestion(selected.min(app.junk_suggestions.len() - 1));
+                    }
+                }
+            })
+        });
+    }
+    #[cfg(not(feature = "junk"))]
+    let _ = (app, index);
+}
+
+/// Work out which directories shrink if the selected entry is deleted. Full scan results answer
+/// this immediately; otherwise the target is measured on disk in the background and the result
+/// arrives through `impact_tx`.
+fn preview_deletion(app: &mut App, impact_tx: &Sender<DeletionImpact>) {
+    app.delete_preview = None;
+    let (Some(file), Some(device)) = (app.get_selected_file_entry(), app.devices.get(app.selected)) else {
+        return;
+    };
+    let target = file.path.clone();
+    let root = device.mount_point.clone();
+
+    match app.full_scan_results.as_deref() {
+        Some(results) if deletion_impact::covers(results, &target) => {
+            app.delete_preview = Some(deletion_impact::from_scan_results(&target, &root, results));
+        }
+        _ => {
+            let options = app.config.scan.options(app.on_battery);
+            let sender = impact_tx.clone();
+            tokio::spawn(async move {
+                if let Ok(impact) = tokio::task::spawn_blocking(move || deletion_impact::from_disk(&target, &root, options)).await {
+                    let _ = sender.send(impact).await;
+                }
+            });
+        }
+    }
+}
+
+/// Ask to confirm deleting the selected entry, showing what it frees and who has it open.
+fn confirm_delete(app: &mut App, mode: &mut ModeMachine, impact_tx: &Sender<DeletionImpact>) {
+    if mode.request_file_op(FileOperation::Delete, app.selected_file_index, None) {
+        app.confirm_input.clear();
+        preview_deletion(app, impact_tx);
+        check_open_files(app);
+    }
+}
+
+/// Ask to confirm securely deleting the selected entry, if `delete.secure_erase` allows it.
+/// Whether overwriting reaches its old contents on the disk it is on is looked up in the
+/// background, for the confirmation.
+fn confirm_secure_delete(app: &mut App, mode: &mut ModeMachine) {
+    if !app.config.delete.secure_erase {
+        mode.show_message("Secure delete is off; set secure_erase = true in the [delete] section of the config to allow it");
+        return;
+    }
+    let Some(path) = app.get_selected_file_entry().map(|file| file.path.clone()) else {
+        return;
+    };
+    let passes = app.config.delete.secure_erase_passes;
+    if mode.request_file_op(FileOperation::SecureDelete { passes }, app.selected_file_index, None) {
+        app.confirm_input.clear();
+        app.erase_warning = None;
+        app.tasks.spawn(move || {
+            let warning = secure_erase::warning(&path);
+            Box::new(move |app: &mut App, _: &mut ModeMachine| app.erase_warning = Some((path, warning)))
+        });
+    }
+}
+
+/// Delete the selected entry right away if `confirm.delete` lets it skip the confirmation,
+/// otherwise ask. Whether it is a directory, and the free space before, are looked up in the
+/// background first.
+fn delete_without_confirmation(app: &mut App, impact_tx: &Sender<DeletionImpact>) {
+    let Some(file) = app.get_selected_file_entry() else {
+        return;
+    };
+    let (path, file_size) = (file.path.clone(), file.size);
+    // A directory's size is only known from a full scan that covers it
+    let scanned = app.full_scan_results.as_deref().or(app.last_full_scan.as_ref().map(|(_, files)| files.as_slice()));
+    let dir_size = scanned.filter(|files| deletion_impact::covers(files, &path)).map(|files| analysis::size_under(files, Path::new(&path)));
+    let mount_point = app.devices.get(app.selected).map(|device| device.mount_point.clone());
+    let confirm = app.config.confirm;
+    let impact_tx = impact_tx.clone();
+    app.tasks.spawn(move || {
+        let size = if Path::new(&path).is_dir() { dir_size } else { Some(file_size) };
+        let space_check = match (mount_point, size) {
+            (Some(mount_point), Some(size)) if !operations::delete_needs_confirmation(&confirm, &path, Some(size)) => {
+                SpaceCheck::before_delete(&path, &mount_point, size, Vec::new())
+            }
+            _ => None,
+        };
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            if app.operations.delete_unconfirmed(&app.config.confirm, &path, size) {
+                app.space_checks.extend(space_check);
+                app.notification = Some(format!("Deleting {} ('{}' undoes it)", path, app.config.keys.label(Action::Undo)));
+            } else if app.get_selected_file_entry().is_some_and(|file| file.path == path) {
+                confirm_delete(app, mode, &impact_tx);
+            }
+        })
+    });
+}
+
+/// Look up in the background what copying or moving `source` to `target` would overwrite, for
+/// the confirmation.
+fn preview_overwrite(app: &mut App, source: String, target: String) {
+    app.overwrite_preview = None;
+    app.tasks.spawn(move || {
+        let preview = diff::overwrite_preview(&source, &target);
+        Box::new(move |app: &mut App, _: &mut ModeMachine| app.overwrite_preview = preview)
+    });
+}
+
+/// List the directory at `path` if it is one, which is looked up in the background.
+fn open_folder(
+    app: &mut App,
+    path: String,
+    async_tx: &Sender<Result<Vec<crate::scanner::FileEntry>, Box<dyn Error + Send + 'static>>>,
+) {
+    let async_tx = async_tx.clone();
+    app.tasks.spawn(move || {
+        let is_dir = Path::new(&path).is_dir();
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            // Unless the listing changed meanwhile
+            if is_dir && mode.is_normal() && app.listing_active() && app.get_selected_file_entry().is_some_and(|file| file.path == path) {
+                browse_to(app, mode, &async_tx, Some(path));
+            }
+        })
+    });
+}
+
+/// Look up which processes have the selected entry open, in the background, for the warning in
+/// its delete confirmation. Skipped if the config turns it off.
+fn check_open_files(app: &mut App) {
+    app.delete_holders = None;
+    app.delete_holders_rx = None;
+    if !app.config.delete.check_open_files {
+        return;
+    }
+    let Some(path) = app.get_selected_file_entry().map(|file| file.path.clone()) else {
+        return;
+    };
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let holders = holders::path_holders(&path);
+        // Ignore errors - the confirmation may have been closed
+        let _ = tx.send((path, holders));
+    });
+    app.delete_holders_rx = Some(rx);
+}
+
+/// Look up when the large files of the full scan were last read or changed, in the background,
+/// and list those unused for the configured time.
+fn find_stale_files(app: &mut App, mode: &mut ModeMachine) {
+    let Some(files) = app.full_scan_results.as_ref().filter(|_| app.full_scan_shown()) else {
+        mode.show_message("Stale files are looked for in the results of a full scan; run one first");
+        return;
+    };
+    let stale = app.config.stale;
+    let min_size = stale.min_size_mb * 1024 * 1024;
+    let candidates: Vec<crate::scanner::FileEntry> = files.iter().filter(|file| file.size >= min_size).cloned().collect();
+    mode.start_task(format!("Checking when {} files were last used", candidates.len()));
+    app.tasks.spawn(move || {
+        let filter = analysis::AgeFilter::new(Some(Duration::from_secs(stale.unused_days * 24 * 60 * 60)), &SystemClock);
+        // The access time only counts where reading a file updates it
+        let paths: Vec<String> = candidates.iter().map(|file| file.path.clone()).collect();
+        let opened = last_used::last_opened(&paths);
+        let files = analysis::stale_files(&candidates, min_size, filter, |file| {
+            let modified = LocalFileSystem.file_info(Path::new(&file.path)).ok().and_then(|info| info.modified);
+            (modified, opened.get(&file.path).copied())
+        });
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            app.last_opened.extend(opened);
+            app.stale_files = files;
+            if !mode.open_stale_files() {
+                app.notification = Some(format!("{} large files unused for {} days, see :stale", app.stale_files.len(), stale.unused_days));
+            }
+        })
+    });
+}
+
+/// Compare the cached full scan of the selected device with the one before it in the
+/// background, and list what changed.
+fn compare_with_previous_scan(app: &mut App, mode: &mut ModeMachine) {
+    let Some(device) = app.devices.get(app.selected).filter(|device| device.mounted) else {
+        mode.show_message("Select a mounted device to see what changed on it");
+        return;
+    };
+    let root = device.mount_point.clone();
+    mode.start_task(format!("Comparing the last two scans of {}", device.name));
+    app.tasks.spawn(move || {
+        let changes = scan_cache::load(&root).zip(scan_cache::load_previous(&root)).map(|(latest, previous)| {
+            let mut changes = analysis::changes_between(Path::new(&root), &previous.to_file_entries(), &latest.to_file_entries());
+            changes.truncate(CHANGES_SHOWN);
+            changes
+        });
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| match changes {
+            Some(changes) => {
+                app.scan_changes = changes;
+                if !mode.open_scan_changes() {
+                    app.notification = Some(format!("{} changes since the previous scan, see :changes", app.scan_changes.len()));
+                }
+            }
+            None => tasks::report(app, mode, "What changed is shown once the device was fully scanned twice".to_string()),
+        })
+    });
+}
+
+/// Look for symlinks whose targets are gone on the selected device in the background, and list
+/// them. The system volume is walked where full scans walk it.
+fn find_broken_links(app: &mut App, mode: &mut ModeMachine) {
+    let Some(device) = app.devices.get(app.selected).filter(|device| device.mounted) else {
+        mode.show_message("Select a mounted device to look for broken links on");
+        return;
+    };
+    let roots = if system_roots::is_system_root(&device.mount_point) && !app.config.scan.whole_system {
+        system_roots::system_scan_roots()
+    } else {
+        vec![device.mount_point.clone()]
+    };
+    let options = app.config.scan.options(app.on_battery);
+    mode.start_task(format!("Looking for broken links on {}", device.name));
+    app.tasks.spawn(move || {
+        let result = crate::scanner::find_broken_links(&roots, options).map_err(|e| e.to_string());
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| match result {
+            Ok(links) => {
+                app.broken_links = links;
+                if !mode.open_broken_links() {
+                    app.notification = Some(format!("{} broken links found, see :broken-links", app.broken_links.len()));
+                }
+            }
+            Err(e) => tasks::report(app, mode, format!("Looking for broken links failed: {}", e)),
+        })
+    });
+}
+
+/// Delete all the broken links found in the background. Links hold no data, so they are
+/// removed rather than moved to the trash.
+fn delete_broken_links(app: &mut App) {
+    let links = app.broken_links.clone();
+    app.tasks.spawn(move || {
+        let mut removed = HashSet::new();
+        let mut failures = Vec::new();
+        for link in &links {
+            match remove_broken_link(Path::new(&link.path)) {
+                Ok(()) => {
+                    removed.insert(link.path.clone());
+                }
+                Err(e) => failures.push(format!("{}: {}", link.path, e)),
+            }
+        }
+        let mut summary = format!("Deleted {} broken links", removed.len());
+        if let Some(first) = failures.first() {
+            summary.push_str(&format!("\n{} skipped: {}", failures.len(), first));
+        }
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            app.broken_links.retain(|link| !removed.contains(&link.path));
+            tasks::report(app, mode, summary);
+        })
+    });
+}
+
+/// Remove the broken link at `path`, unless its device is being ejected or it is no longer a
+/// broken link: something else may have taken its place since it was found.
+fn remove_broken_link(path: &Path) -> Result<(), Box<dyn Error>> {
+    device_guard::check(&path.to_string_lossy())?;
+    if !fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err("no longer a symlink".into());
+    }
+    if fs::metadata(path).is_ok() {
+        return Err("its target exists again".into());
+    }
+    Ok(fs::remove_file(path)?)
+}
+
+/// Look for files with the same content among the results of the full scan in the
+/// background, and list them. Only user data is looked at; copies apps and the system keep are
+/// theirs to manage.
+fn find_duplicates(app: &mut App, mode: &mut ModeMachine) {
+    let Some(files) = app.full_scan_results.as_ref().filter(|_| app.full_scan_shown()) else {
+        mode.show_message("Duplicates are looked for in the results of a full scan; run one first");
+        return;
+    };
+    #[cfg(feature = "duplicates")]
+    {
+        use crate::safety::Safety;
+        let candidates: Vec<crate::scanner::FileEntry> = files
+            .iter()
+            .filter(|file| file.size > 0 && file.link_target.is_none() && Safety::classify(&file.path) == Safety::UserData)
+            .cloned()
+            .collect();
+        mode.start_task(format!("Comparing the content of {} files", format_count(candidates.len() as u64)));
+        app.tasks.spawn(move || {
+            let (groups, modified) = crate::duplicates::find(&candidates);
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                app.duplicate_groups = groups;
+                app.duplicate_modified = modified;
+                if !mode.open_duplicates() {
+                    app.notification = Some(format!("{} groups of duplicates found, see :duplicates", app.duplicate_groups.len()));
+                }
+            })
+        });
+    }
+    #[cfg(not(feature = "duplicates"))]
+    {
+        let _ = files;
+        mode.show_message("This build can't find duplicates (feature `duplicates`)");
+    }
+}
+
+/// Plan resolving all the groups of duplicates by `rule` and show what it would delete, to
+/// confirm.
+fn plan_duplicate_removal(app: &mut App, mode: &mut ModeMachine, rule: analysis::KeepRule) {
+    let modified = &app.duplicate_modified;
+    let plan = analysis::plan_duplicate_removal(&app.duplicate_groups, &rule, |file| modified.get(&file.path).copied());
+    if plan.removals.is_empty() {
+        app.notification = Some("Nothing to delete: no group has another copy to keep".to_string());
+        return;
+    }
+    app.duplicate_plan = Some(plan);
+    mode.request_duplicate_resolve();
+}
+
+/// Carry out a confirmed plan: every copy still holding the same bytes as the one kept is
+/// moved to the trash, like other deletes, so it can be restored. Copies that changed since they
+/// were compared, or whose kept copy is gone, stay.
+#[cfg(feature = "duplicates")]
+fn resolve_duplicates(app: &mut App, plan: analysis::DuplicatePlan) {
+    app.tasks.spawn(move || {
+        let (verified, changed): (Vec<_>, Vec<_>) = plan
+            .removals
+            .into_iter()
+            .partition(|removal| crate::duplicates::same_content(Path::new(&removal.remove.path), Path::new(&removal.keep)).unwrap_or(false));
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            let freed: u64 = verified.iter().map(|removal| removal.remove.size).sum();
+            let removed: HashSet<&str> = verified.iter().map(|removal| removal.remove.path.as_str()).collect();
+            for group in &mut app.duplicate_groups {
+                group.files.retain(|file| !removed.contains(file.path.as_str()));
+            }
+            app.duplicate_groups.retain(|group| group.files.len() > 1);
+            for removal in &verified {
+                app.operations.enqueue(FileOperation::Delete, removal.remove.path.clone(), None);
+            }
+            let mut summary = format!("Moving {} duplicates to the trash, {} freed once it is emptied", verified.len(), format_size(freed, app.config.display.size_units));
+            if !changed.is_empty() {
+                summary.push_str(&format!("\n{} left alone: they no longer match the copy kept", changed.len()));
+            }
+            tasks::report(app, mode, summary);
+        })
+    });
+}
+
+#[cfg(not(feature = "duplicates"))]
+fn resolve_duplicates(_app: &mut App, _plan: analysis::DuplicatePlan) {}
+
+/// Set a goal of `target` (e.g. "50G") free on the selected device, and look for candidates to
+/// reach it in the background: the junk of the last junk scan, the duplicates found, and the old
+/// downloads and large cold files of the full scan.
+fn set_goal(app: &mut App, mode: &mut ModeMachine, target: &str) {
+    let units = app.config.display.size_units;
+    let Some(target) = format::parse_size(target, units) else {
+        mode.show_message("Usage: :goal <free space>, e.g. :goal 50G");
+        return;
+    };
+    let Some(device) = app.devices.get(app.selected).filter(|device| device.mounted) else {
+        mode.show_message("Select a mounted device to free space on");
+        return;
+    };
+    if device.available_space >= target {
+        mode.show_message(format!("{} already has {} free", device.name, format_size(device.available_space, units)));
+        return;
+    }
+    let mount_point = device.mount_point.clone();
+
+    let mut sources = goal::Sources::default();
+    if let (Some(files), Some(categories)) = (&app.junk_all_files, &app.junk_categories) {
+        let mut by_category: BTreeMap<String, Vec<crate::scanner::FileEntry>> = BTreeMap::new();
+        for file in files.iter().filter(|file| Path::new(&file.path).starts_with(&mount_point)) {
+            if let Some(category) = analysis::find_category(categories, &file.path).filter(|category| !app.excluded_categories.contains(&category.id)) {
+                by_category.entry(category.name.clone()).or_default().push(file.clone());
+            }
+        }
+        sources.junk = by_category.into_iter().collect();
+    }
+    let modified = &app.duplicate_modified;
+    let duplicates = analysis::plan_duplicate_removal(&app.duplicate_groups, &analysis::KeepRule::Newest, |file| modified.get(&file.path).copied());
+    sources.duplicates = duplicates.removals.into_iter().map(|removal| (removal.keep, removal.remove)).collect();
+    if let Some(files) = app.full_scan_results.as_ref().filter(|_| app.full_scan_shown()) {
+        sources.files = files.clone();
+    }
+    if sources.junk.is_empty() && sources.duplicates.is_empty() && sources.files.is_empty() {
+        mode.show_message("Candidates come from a junk scan, :duplicates and a full scan; run one first");
+        return;
+    }
+
+    let goal = goal::Goal { device: device.name.clone(), target, available: device.available_space, plan: Default::default() };
+    let stale = app.config.stale;
+    mode.start_task(format!("Looking for {} to free on {}", format_size(target - goal.available, units), goal.device));
+    app.tasks.spawn(move || {
+        let candidates = goal::candidates(sources, stale);
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            let plan = analysis::GoalPlan::new(goal.target - goal.available, candidates);
+            app.goal = Some(goal::Goal { plan, ..goal });
+            if !mode.open_goal() {
+                app.notification = Some("The plan for the goal is ready, see :goal".to_string());
+            }
+        })
+    });
+}
+
+/// Delete the accepted junk of `goal` in the background and queue moving the rest to the
+/// trash, then report how far it got.
+fn run_goal(app: &mut App, goal: goal::Goal) {
+    let units = app.config.display.size_units;
+    let empty_trash_key = app.config.keys.label(Action::EmptyTrash);
+    app.tasks.spawn(move || {
+        let outcome = goal::run(&goal.plan);
+        let report = outcome.report(&goal, units, &empty_trash_key);
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            for (path, _) in &outcome.deleted {
+                app.record_delete(path, false);
+                if let (Some(entries), Some(totals)) = (app.full_scan_results.as_ref(), app.full_scan_totals.as_mut()) {
+                    totals.remove(Path::new(path), entries);
+                }
+            }
+            // The queue updates the scan results as each of these finishes
+            for (path, _) in &outcome.to_trash {
+                app.operations.enqueue(FileOperation::Delete, path.clone(), None);
+            }
+            let deleted: HashSet<&str> = outcome.deleted.iter().map(|(path, _)| path.as_str()).collect();
+            for entries in [&mut app.full_scan_results, &mut app.junk_all_files].into_iter().flatten() {
+                entries.retain(|entry| !deleted.contains(entry.path.as_str()));
+            }
+            let removed = outcome.removed();
+            for group in &mut app.duplicate_groups {
+                group.files.retain(|file| !removed.contains(file.path.as_str()));
+            }
+            app.duplicate_groups.retain(|group| group.files.len() > 1);
+            app.refresh_dir_view();
+            app.refresh_type_breakdown();
+            tasks::report(app, mode, report);
+        })
+    });
+}
+
+/// Run a command entered on the ':' command line. Returns `Ok(true)` if the app should quit.
+fn run_command(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    progress_tx: &Sender<ScanProgressMessage>,
+    command: &str,
+) -> Result<bool, Box<dyn Error>> {
+    match command {
+        "" => {
+            mode.finish_command();
+        }
+        "history" | "hist" => {
+            mode.open_history();
+        }
+        "errors" => {
+            mode.open_scan_errors();
+        }
+        "dirs" => {
+            if app.rank_largest_dirs() {
+                mode.open_largest_dirs();
+            } else {
+                mode.show_message("The largest directories are ranked from a full scan; run one first");
+            }
+        }
+        "xcode" => {
+            mode.finish_command();
+            start_xcode_scan(app, mode, progress_tx);
+        }
+        "update" => {
+            mode.finish_command();
+            match app.start_update_check(true) {
+                Ok(()) => app.notification = Some("Checking for a newer release...".to_string()),
+                Err(message) => mode.show_message(message),
+            }
+        }
+        "depth" | "depth off" => {
+            mode.finish_command();
+            app.scan_depth = None;
+            app.notification = Some("Full scans walk the whole tree".to_string());
+        }
+        depth if depth.starts_with("depth ") => {
+            mode.finish_command();
+            match depth["depth ".len()..].trim().parse::<usize>() {
+                Ok(depth) => {
+                    app.scan_depth = Some(depth);
+                    app.notification = Some(format!("Full scans walk {} below the device root (:depth off for all)", format_depth(depth)));
+                }
+                Err(_) => mode.show_message("Usage: :depth <levels> or :depth off"),
+            }
+        }
+        "stale" => {
+            mode.finish_command();
+            find_stale_files(app, mode);
+        }
+        "changes" => {
+            mode.finish_command();
+            compare_with_previous_scan(app, mode);
+        }
+        "broken-links" | "broken" => {
+            mode.finish_command();
+            find_broken_links(app, mode);
+        }
+        "duplicates" | "dupes" => {
+            mode.finish_command();
+            find_duplicates(app, mode);
+        }
+        "goal" => {
+            mode.finish_command();
+            if app.goal.is_some() {
+                mode.open_goal();
+            } else {
+                mode.show_message("Usage: :goal <free space>, e.g. :goal 50G");
+            }
+        }
+        goal if goal.starts_with("goal ") => {
+            mode.finish_command();
+            set_goal(app, mode, goal["goal ".len()..].trim());
+        }
+        "export" => {
+            mode.show_message("Usage: :export <file>");
+        }
+        "export-ncdu" => {
+            mode.show_message("Usage: :export-ncdu <file>");
+        }
+        "export-html" => {
+            mode.show_message("Usage: :export-html <file>");
+        }
+        export if export.starts_with("export-html ") => {
+            mode.finish_command();
+            export_html(app, mode, export["export-html ".len()..].trim());
+        }
+        export if export.starts_with("export-ncdu ") => {
+            mode.finish_command();
+            export_ncdu(app, mode, export["export-ncdu ".len()..].trim());
+        }
+        export if export.starts_with("export ") => {
+            mode.finish_command();
+            export_scan(app, mode, export["export ".len()..].trim());
+        }
+        "sort delta" => {
+            mode.finish_command();
+            if app.size_deltas.is_some() {
+                app.set_sort_by_delta(true);
+                app.notification = Some("Scan results are ordered by how much they grew (:sort off for size)".to_string());
+            } else {
+                mode.show_message("Sizes are compared once a device with earlier scan results is scanned again");
+            }
+        }
+        "sort" | "sort off" | "sort size" => {
+            mode.finish_command();
+            app.set_sort_by_delta(false);
+        }
+        "eject-all" | "ejectall" => {
+            mode.request_eject_all();
+        }
+        "device refresh-info" => {
+            mode.finish_command();
+            match app.devices.get(app.selected).filter(|device| device.mounted) {
+                Some(device) => {
+                    // The device listener queries it again on its next pass
+                    macos::refresh_device_info(device);
+                    app.notification = Some(format!("The details of {} are read again with the next device update", device.name));
+                }
+                None => mode.show_message("Select a mounted device to refresh its details"),
+            }
+        }
+        "q" | "quit" => return Ok(true),
+        other => {
+            mode.show_message(format!("Unknown command: {}", other));
+        }
+    }
+    // Keep the help overlay from hiding the result
+    app.show_help = false;
     Ok(false)
 }
 
+/// Write the full scan results of the selected device to `file` as JSON, for other tools.
+fn export_scan(app: &mut App, mode: &mut ModeMachine, file: &str) {
+    let (Some(results), Some(device)) = (&app.full_scan_results, app.devices.get(app.selected)) else {
+        mode.show_message("Exports are written from a full scan; run one first");
+        return;
+    };
+    let path = match expanduser::expanduser(file) {
+        Ok(path) => path,
+        Err(err) => {
+            mode.show_message(format!("Couldn't export to {}: {}", file, err));
+            return;
+        }
+    };
+    let document = ScanExport::new(&device.mount_point, Some(device), results);
+    match export::write(&path, &document) {
+        Ok(()) => app.notification = Some(format!("Exported {} files to {}", format_count(document.file_count as u64), path.display())),
+        Err(err) => mode.show_message(format!("Couldn't export to {}: {}", path.display(), err)),
+    }
+}
+
+/// Write the full scan of the selected device to `file` in the export format of ncdu, to open
+/// with `ncdu -f`. Scans that only kept their largest files would come out incomplete.
+fn export_ncdu(app: &mut App, mode: &mut ModeMachine, file: &str) {
+    let (Some(results), Some(device)) = (&app.full_scan_results, app.devices.get(app.selected)) else {
+        mode.show_message("Exports are written from a full scan; run one first");
+        return;
+    };
+    if app.full_scan_totals.is_some() {
+        mode.show_message("The scan only kept its largest files, so ncdu would miss the rest; raise max_files in [scan] and scan again");
+        return;
+    }
+    let path = match expanduser::expanduser(file) {
+        Ok(path) => path,
+        Err(err) => {
+            mode.show_message(format!("Couldn't export to {}: {}", file, err));
+            return;
+        }
+    };
+    match export::write_ncdu(&path, &device.mount_point, results) {
+        Ok(()) => app.notification = Some(format!("Exported {} files to {}, open it with ncdu -f", format_count(results.len() as u64), path.display())),
+        Err(err) => mode.show_message(format!("Couldn't export to {}: {}", path.display(), err)),
+    }
+}
+
+/// Write a standalone HTML snapshot of the full scan of the selected device to `file`, to
+/// share it.
+fn export_html(app: &mut App, mode: &mut ModeMachine, file: &str) {
+    let (Some(results), Some(device)) = (&app.full_scan_results, app.devices.get(app.selected)) else {
+        mode.show_message("Exports are written from a full scan; run one first");
+        return;
+    };
+    let path = match expanduser::expanduser(file) {
+        Ok(path) => path,
+        Err(err) => {
+            mode.show_message(format!("Couldn't export to {}: {}", file, err));
+            return;
+        }
+    };
+    let snapshot = Snapshot {
+        title: &device.name,
+        root: &device.mount_point,
+        files: results,
+        totals: app.full_scan_totals.as_ref(),
+        units: app.config.display.size_units,
+    };
+    match html_report::write(&path, &snapshot) {
+        Ok(()) => app.notification = Some(format!("Wrote a snapshot of {} to {}", device.name, path.display())),
+        Err(err) => mode.show_message(format!("Couldn't export to {}: {}", path.display(), err)),
+    }
+}
+
+/// Eject the device at `index` in the background, emptying its trash first with
+/// `empty_trash_first`. A failed eject offers to retry, unmount or force it.
+fn eject_device(app: &mut App, mode: &mut ModeMachine, index: usize, empty_trash_first: bool) {
+    let Some(device) = app.devices.get(index).cloned() else {
+        mode.cancel();
+        return;
+    };
+    if empty_trash_first {
+        mode.start_task(format!("Emptying the trash of {}, then ejecting it", device.name));
+    } else {
+        mode.start_task(format!("Ejecting {}", device.name));
+    }
+    let units = app.config.display.size_units;
+    app.tasks.spawn(move || {
+        let emptied = empty_trash_first.then(|| trash::empty_trash(&device.mount_point, device.home_volume));
+        // Refuse new operations on the device and stop running ones first
+        let result = device_guard::eject_when_idle(&device.mount_point, EJECT_CANCEL_TIMEOUT, || macos::eject_device(&device).map_err(|err| err.to_string()));
+        let after = match result {
+            Ok(()) => Ok(macos::detect_storage_devices()),
+            Err(err) => Err((err, holders::volume_holders(&device.mount_point))),
+        };
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            let mut trash_summary = String::new();
+            if let Some((freed, failures)) = emptied {
+                app.session.add_freed(format!("Trash of {}", device.name), freed);
+                trash_summary = format!("Emptied the trash, freed {}", format_size(freed, units));
+                if let Some(first) = failures.first() {
+                    trash_summary.push_str(&format!(" ({} not deleted: {})", failures.len(), first));
+                }
+                trash_summary.push('\n');
+            }
+            match after {
+                Ok(devices) => {
+                    app.set_devices(devices);
+                    // Clear any file listings for the ejected device
+                    app.file_entries = None;
+                    app.full_scan_results = None;
+                    tasks::report(app, mode, format!("{}Ejected Device: {} successfully", trash_summary, device.name));
+                },
+                // Offer to retry, unmount or force eject
+                Err((err, holders)) => {
+                    if !mode.eject_failed(index, err.clone(), holders) {
+                        tasks::report(app, mode, format!("{}Couldn't eject {}: {}", trash_summary, device.name, err));
+                    } else if !trash_summary.is_empty() {
+                        app.notification = Some(trash_summary.trim_end().to_string());
+                    }
+                },
+            }
+        })
+    });
+}
+
+/// Eject every ejectable device in turn in the background, each guarded like a single eject.
+/// The result popup lists one line per device.
+fn eject_all(app: &mut App, mode: &mut ModeMachine) {
+    let devices: Vec<macos::StorageDevice> = app.devices.iter().filter(|device| device.ejectable).cloned().collect();
+    mode.start_task(format!("Ejecting {} devices", devices.len()));
+    app.tasks.spawn(move || {
+        let mut lines = Vec::new();
+        let mut ejected = 0;
+        for device in &devices {
+            let result = device_guard::eject_when_idle(&device.mount_point, EJECT_CANCEL_TIMEOUT, || macos::eject_device(device));
+            match result {
+                Ok(()) => {
+                    ejected += 1;
+                    lines.push(format!("✓ {}", device.name));
+                }
+                Err(err) => {
+                    let mut line = format!("✗ {}: {}", device.name, err);
+                    if let Some(holders) = holders::volume_holders(&device.mount_point).filter(|holders| !holders.is_empty()) {
+                        line.push_str(&format!(" (in use by {})", holders.join(", ")));
+                    }
+                    lines.push(line);
+                }
+            }
+        }
+        lines.insert(0, format!("Ejected {} of {} devices", ejected, devices.len()));
+        let devices = macos::detect_storage_devices();
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            app.set_devices(devices);
+            app.file_entries = None;
+            app.full_scan_results = None;
+            tasks::report(app, mode, lines.join("\n"));
+        })
+    });
+}
+
+/// After mounting or unmounting, show the `devices` detected since and select the same volume
+/// again, which has moved between the mounted and unmounted ones.
+fn reselect_after_mount_change(app: &mut App, devices: Vec<macos::StorageDevice>, name: &str, device_node: Option<&str>) {
+    app.set_devices(devices);
+    app.file_entries = None;
+    app.full_scan_results = None;
+    let same_volume = |device: &macos::StorageDevice| match (device_node, device.device_node.as_deref()) {
+        (Some(wanted), Some(node)) => wanted == node,
+        _ => device.name == name,
+    };
+    if let Some(index) = app.devices.iter().position(same_volume) {
+        app.selected = index;
+    }
+}
+
+/// Unmount the selected volume in the background, guarded like an eject, and report in the
+/// result popup.
+fn unmount_selected(app: &mut App, mode: &mut ModeMachine) {
+    let Some(device) = app.devices.get(app.selected).cloned() else {
+        return;
+    };
+    mode.start_task(format!("Unmounting {}", device.name));
+    app.tasks.spawn(move || {
+        let result = device_guard::eject_when_idle(&device.mount_point, EJECT_CANCEL_TIMEOUT, || macos::unmount_device(&device).map_err(|err| err.to_string()));
+        let devices = macos::detect_storage_devices();
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            reselect_after_mount_change(app, devices, &device.name, device.device_node.as_deref());
+            let summary = match result {
+                Ok(()) => format!("Unmounted {}. Its disk is still attached.", device.name),
+                Err(err) => format!("Failed to unmount {}: {}", device.name, err),
+            };
+            tasks::report(app, mode, summary);
+        })
+    });
+}
+
+/// Mount the selected unmounted volume in the background, and report in the result popup.
+fn mount_selected(app: &mut App, mode: &mut ModeMachine) {
+    let Some(device) = app.devices.get(app.selected).cloned() else {
+        return;
+    };
+    mode.start_task(format!("Mounting {}", device.name));
+    app.tasks.spawn(move || {
+        let result = macos::mount_device(&device).map_err(|err| err.to_string());
+        let devices = macos::detect_storage_devices();
+        Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+            reselect_after_mount_change(app, devices, &device.name, device.device_node.as_deref());
+            let summary = match result {
+                Ok(()) => {
+                    let mount = app.devices.get(app.selected).filter(|dev| dev.mounted).map(|dev| dev.mount_point.clone());
+                    if let Some(mount) = &mount {
+                        device_guard::remounted(mount);
+                    }
+                    format!("Mounted {} at {}", device.name, mount.as_deref().unwrap_or("?"))
+                }
+                Err(err) => format!("Failed to mount {}: {}", device.name, err),
+            };
+            tasks::report(app, mode, summary);
+        })
+    });
+}
+
+/// Start a full (or incremental) scan of the selected device, unless the machine runs on
+/// battery and the config asks to confirm first.
+fn request_device_scan(app: &mut App, mode: &mut ModeMachine, progress_tx: &Sender<ScanProgressMessage>, incremental: bool) {
+    if app.on_battery && app.config.scan.on_battery == BatteryPolicy::Confirm {
+        mode.request_battery_scan(incremental);
+    } else {
+        start_device_scan(app, mode, progress_tx, incremental);
+    }
+}
+
+/// Start a scan of the selected device with progress tracking: a junk scan for system storage,
+/// otherwise a full (or, if `incremental`, cache-assisted) scan of the device's mount point.
+pub fn start_device_scan(
+    app: &mut App,
+    mode: &mut ModeMachine,
+    progress_tx: &Sender<ScanProgressMessage>,
+    incremental: bool,
+) {
+    let Some(device) = app.devices.get(app.selected) else {
+        return;
+    };
+    let mount = device.mount_point.clone();
+    let total_size = device.total_space;
+    // Builds without junk scanning give system storage a full scan like any other device
+    let is_system_storage = !device.ejectable && cfg!(feature = "junk");
+    if let Err(busy) = device_guard::check(&mount) {
+        mode.show_message(format!("Can't scan: {}", busy));
+        return;
+    }
+
+    // Reset folder view mode
+    app.folder_view_mode = false;
+    app.selected_folder_index = 0;
+    app.category_view_mode = false;
+
+    // Set up progress tracking
+    app.scan_progress = ScanProgress::start(total_size);
+
+    app.scan_started_at = Some(std::time::Instant::now());
+
+    // Create a clone of the progress channel
+    let progress_sender = progress_tx.clone();
+    let options = app.config.scan.options(app.on_battery);
+
+    // Different scan types based on device type
+    if is_system_storage {
+        // For system storage, scan for junk files
+        app.scan_mode = ScanMode::JunkScan;
+
+        // Spawn the junk scan task
+        #[cfg(feature = "junk")]
+        tokio::spawn(async move {
+            let _ = crate::platform::junk_scanner::scan_system_junk(progress_sender, options).await;
+        });
+    } else {
+        // For external/ejectable devices, do a full scan
+        app.scan_mode = ScanMode::FullScan;
+        app.full_scan_depth = app.scan_depth;
+        let options = ScanOptions { max_depth: app.scan_depth, ..options };
+        // All of / holds virtual file systems and network mounts (left out by every scan) and
+        // the OS itself, so only the home directory and data locations are walked unless asked
+        app.full_scan_roots = None;
+        if system_roots::is_system_root(&mount) {
+            if app.config.scan.whole_system {
+                app.notification = Some("Scanning all of / (whole_system is set); virtual file systems and network mounts are skipped".to_string());
+            } else {
+                let roots = system_roots::system_scan_roots();
+                app.notification = Some(format!(
+                    "Scanning {} only; set whole_system in [scan] to walk all of /",
+                    roots.join(", ")
+                ));
+                app.full_scan_roots = Some(roots);
+            }
+        }
+        let roots = app.full_scan_roots.clone();
+
+        // The listing stays browsable during the scan; the highlighted directory is walked first.
+        // The results it replaces are kept to badge what changed.
+        app.rescan_baseline = app.full_scan_results.take().filter(|_| app.full_scan_totals.is_none());
+        app.size_deltas = None;
+        app.largest_so_far = None;
+        app.scanned_dir_sizes.clear();
+        app.scan_priority = ScanPriority::default();
+        app.update_scan_priority();
+        let priority = app.scan_priority.clone();
+
+        // Spawn the full scan task
+        tokio::spawn(async move {
+            let _ = tokio::task::spawn_blocking(move || {
+                if let Some(roots) = roots {
+                    system_scan_with_progress(&mount, roots, progress_sender, options, &priority)
+                } else if incremental {
+                    incremental_scan_with_progress(&mount, total_size, progress_sender, options, &priority)
+                } else {
+                    full_scan_with_progress(&mount, total_size, progress_sender, options, &priority)
+                }
+            }).await;
+        });
+    }
+
+    mode.start_full_scan(app.selected);
+}
+
+/// Delete the junk files of a folder in the background, streaming progress like a scan.
+fn start_folder_delete(app: &mut App, mode: &mut ModeMachine, progress_tx: &Sender<ScanProgressMessage>, folder_index: usize) {
+    let Some((folder, files)) = app.folder_junk_files(folder_index) else {
+        mode.cancel();
+        return;
+    };
+    let folder_path = folder.path.clone();
+
+    app.scan_progress = ScanProgress::start(files.iter().map(|file| file.size).sum());
+    let job = Arc::new(file_ops::FolderDeleteJob::new(folder_path, files.len()));
+    app.folder_delete = Some(job.clone());
+
+    let progress_sender = progress_tx.clone();
+    tokio::task::spawn_blocking(move || {
+        file_ops::delete_folder_junk(job, files, progress_sender);
+    });
+
+    mode.start_folder_delete(app.selected);
+}
+
+/// Start searching for developer build artifacts: below the mount point of an external device,
+/// or below the home directory on system storage, where projects normally live.
+fn start_dev_scan(app: &mut App, mode: &mut ModeMachine, progress_tx: &Sender<ScanProgressMessage>) {
+    let Some(device) = app.devices.get(app.selected) else {
+        return;
+    };
+    let root = if device.ejectable {
+        device.mount_point.clone()
+    } else {
+        match expanduser::expanduser("~") {
+            Ok(home) => home.to_string_lossy().into_owned(),
+            Err(_) => device.mount_point.clone(),
+        }
+    };
+    if let Err(busy) = device_guard::check(&root) {
+        mode.show_message(format!("Can't scan: {}", busy));
+        return;
+    }
+
+    app.folder_view_mode = false;
+    app.category_view_mode = false;
+
+    // The amount of artifacts isn't known up front, so the gauge only counts files
+    app.scan_progress = ScanProgress::start(0);
+    app.scan_started_at = Some(std::time::Instant::now());
+    app.scan_mode = ScanMode::DevArtifacts;
+
+    let progress_sender = progress_tx.clone();
+    let options = app.config.scan.options(app.on_battery);
+    tokio::spawn(async move {
+        let _ = tokio::task::spawn_blocking(move || {
+            dev_artifacts::scan_dev_artifacts_with_progress(&root, progress_sender, options)
+        }).await;
+    });
+
+    mode.start_full_scan(app.selected);
+}
+
+/// Start measuring Xcode's DerivedData, device support, simulator runtimes and archives.
+fn start_xcode_scan(app: &mut App, mode: &mut ModeMachine, progress_tx: &Sender<ScanProgressMessage>) {
+    app.folder_view_mode = false;
+    app.category_view_mode = false;
+    app.scan_progress = ScanProgress::start(0);
+    app.scan_started_at = Some(std::time::Instant::now());
+    app.scan_mode = ScanMode::Xcode;
+
+    let progress_sender = progress_tx.clone();
+    let options = app.config.scan.options(app.on_battery);
+    tokio::spawn(async move {
+        let _ = tokio::task::spawn_blocking(move || xcode_cleaner::scan_xcode_with_progress(progress_sender, options)).await;
+    });
+
+    mode.start_full_scan(app.selected);
+}
+
+/// Report the power source (true = on battery) at startup and whenever it changes.
+pub fn start_power_listener(tx: mpsc::Sender<bool>) {
+    thread::spawn(move || {
+        let mut last = None;
+        loop {
+            let on_battery = power::on_battery();
+            if last != Some(on_battery) {
+                if tx.send(on_battery).is_err() {
+                    break;
+                }
+                last = Some(on_battery);
+            }
+            thread::sleep(POWER_CHECK_INTERVAL);
+        }
+    });
+}
+
 pub fn start_device_listener(tx: mpsc::Sender<Vec<crate::platform::macos::StorageDevice>>) {
     thread::spawn(move || {
         let mut old_devices = crate::platform::macos::detect_storage_devices();
@@ -342,3 +2199,133 @@ pub fn start_device_listener(tx: mpsc::Sender<Vec<crate::platform::macos::Storag
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::{FinishedOperation, OperationQueue};
+    use crate::scanner::FileEntry;
+    use crossterm::event::{KeyEvent, KeyModifiers};
+    use lazysmg::filesystem::FileInfo;
+    use std::{io, time::Instant};
+
+    /// A network mount that takes `DELAY` to answer anything.
+    #[derive(Debug)]
+    struct SlowFileSystem;
+
+    const DELAY: Duration = Duration::from_millis(500);
+
+    impl FileSystem for SlowFileSystem {
+        fn file_info(&self, path: &Path) -> io::Result<FileInfo> {
+            thread::sleep(DELAY);
+            LocalFileSystem.file_info(path)
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            thread::sleep(DELAY);
+            LocalFileSystem.remove(path)
+        }
+    }
+
+    /// Wait for the next operation of the queue to finish.
+    async fn next_finished(app: &mut App, started: Instant) -> FinishedOperation {
+        loop {
+            if let Some(finished) = app.operations.update().into_iter().next() {
+                return finished;
+            }
+            assert!(started.elapsed() < 20 * DELAY, "the operation didn't finish");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_on_slow_file_system_doesnt_block_keys() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-slow-delete-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("big.bin");
+        fs::write(&file, vec![0; 4096]).unwrap();
+        let path = file.to_string_lossy().into_owned();
+
+        let mut app = App::new(Vec::new(), Default::default());
+        app.operations = OperationQueue::with_file_system(Arc::new(SlowFileSystem));
+        app.file_entries = Some(vec![FileEntry { name: "big.bin".to_string(), path: path.clone(), size: 4096, link_target: None, allocated_size: None }]);
+        app.focus = PanelFocus::Right;
+        let mut mode = ModeMachine::new();
+        assert!(mode.request_file_op(FileOperation::Delete, 0, None));
+
+        let (async_tx, _async_rx) = tokio::sync::mpsc::channel(1);
+        let (progress_tx, _progress_rx) = tokio::sync::mpsc::channel(1);
+        let (impact_tx, _impact_rx) = tokio::sync::mpsc::channel(1);
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        let started = Instant::now();
+        // Temporary files aren't user data, so the delete is confirmed by typing the name
+        for c in "big.bin".chars() {
+            handle_event(&mut app, &mut mode, key(KeyCode::Char(c)), &async_tx, &progress_tx, &impact_tx).unwrap();
+        }
+        handle_event(&mut app, &mut mode, key(KeyCode::Enter), &async_tx, &progress_tx, &impact_tx).unwrap();
+        // Keys keep working while the delete runs
+        handle_event(&mut app, &mut mode, key(KeyCode::Down), &async_tx, &progress_tx, &impact_tx).unwrap();
+        assert!(started.elapsed() < DELAY, "handling the keys took {:?}", started.elapsed());
+        assert!(mode.is_normal());
+
+        let finished = next_finished(&mut app, started).await;
+        assert!(finished.summary.ends_with(&format!("Moved file {} to the trash", path)), "{}", finished.summary);
+        assert_eq!(finished.removed.as_deref(), Some(path.as_str()));
+        assert!(!file.exists());
+
+        // Take it out of the trash again
+        assert!(app.operations.undo_last());
+        let restored = next_finished(&mut app, Instant::now()).await;
+        assert!(restored.summary.contains("Restored"), "{}", restored.summary);
+        assert!(file.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rename_acts_only_on_a_plain_file_listing() {
+        let mut app = App::new(Vec::new(), Default::default());
+        app.file_entries = Some(vec![FileEntry { name: "a.txt".to_string(), path: "/data/a.txt".to_string(), size: 1, link_target: None, allocated_size: None }]);
+        let (async_tx, _async_rx) = tokio::sync::mpsc::channel(1);
+        let (progress_tx, _progress_rx) = tokio::sync::mpsc::channel(1);
+        let (impact_tx, _impact_rx) = tokio::sync::mpsc::channel(1);
+        let rename = |app: &mut App| {
+            let mut mode = ModeMachine::new();
+            let key = Event::Key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE));
+            handle_event(app, &mut mode, key, &async_tx, &progress_tx, &impact_tx).unwrap();
+            matches!(mode.mode(), AppMode::Rename { path, .. } if path == "/data/a.txt")
+        };
+
+        // The device panel refreshes the devices instead
+        assert!(!rename(&mut app));
+        app.focus = PanelFocus::Right;
+        assert!(rename(&mut app));
+        app.folder_view_mode = true;
+        assert!(!rename(&mut app));
+        app.folder_view_mode = false;
+        app.category_view_mode = true;
+        assert!(!rename(&mut app));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_broken_links_are_removed() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("lazysmg-broken-links-{}", std::process::id()));
+        fs::create_dir_all(dir.join("now-a-dir")).unwrap();
+        fs::write(dir.join("now-a-dir/kept.txt"), "kept").unwrap();
+        fs::write(dir.join("target.txt"), "target").unwrap();
+        symlink(dir.join("gone"), dir.join("broken")).unwrap();
+        symlink(dir.join("target.txt"), dir.join("mended")).unwrap();
+
+        remove_broken_link(&dir.join("broken")).unwrap();
+        assert!(fs::symlink_metadata(dir.join("broken")).is_err());
+        assert!(remove_broken_link(&dir.join("now-a-dir")).unwrap_err().to_string().contains("no longer a symlink"));
+        assert!(dir.join("now-a-dir/kept.txt").exists());
+        assert!(remove_broken_link(&dir.join("mended")).is_err());
+        assert!(dir.join("mended").exists());
+        assert!(remove_broken_link(&dir.join("missing")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
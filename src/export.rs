@@ -0,0 +1,207 @@
+use std::{collections::BTreeMap, io, path::Path};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::platform::macos::{distinct_space, StorageDevice};
+use crate::scanner::FileEntry;
+use crate::storage::persist;
+
+// JSON documents for other tools and scripts: `scan --json`, `devices --json` and `:export`.
+// Every document starts with `schema_version` and `kind`, so a consumer can tell what it got
+// before reading the rest. Fields are only ever added within a version; consumers should ignore
+// the ones they don't know. Renaming, removing or changing the meaning of a field raises the
+// version.
+//
+// Scans can also be written in the export format of ncdu (`scan --ncdu`, `:export-ncdu`), to
+// open them with `ncdu -f`.
+
+/// Schema version of the exported documents.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Scan results: every file found below `root`, largest first.
+#[derive(Debug, Serialize)]
+pub struct ScanExport<'a> {
+    pub schema_version: u32,
+    /// Always "scan"
+    pub kind: &'static str,
+    /// lazysmg version that wrote the document
+    pub generator: String,
+    pub generated_at: DateTime<Local>,
+    pub root: &'a str,
+    /// The device scanned, if the scan was of one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<&'a StorageDevice>,
+    pub total_size: u64,
+    pub file_count: usize,
+    pub files: Vec<&'a FileEntry>,
+}
+
+/// Detected storage devices, with their space counted once per distinct volume.
+#[derive(Debug, Serialize)]
+pub struct DevicesExport<'a> {
+    pub schema_version: u32,
+    /// Always "devices"
+    pub kind: &'static str,
+    /// lazysmg version that wrote the document
+    pub generator: String,
+    pub generated_at: DateTime<Local>,
+    pub total_space: u64,
+    pub available_space: u64,
+    pub devices: &'a [StorageDevice],
+}
+
+fn generator() -> String {
+    format!("lazysmg {}", env!("CARGO_PKG_VERSION"))
+}
+
+impl<'a> ScanExport<'a> {
+    /// The document for `files` found below `root`, which need not be sorted.
+    pub fn new(root: &'a str, device: Option<&'a StorageDevice>, files: &'a [FileEntry]) -> Self {
+        let mut sorted: Vec<&FileEntry> = files.iter().collect();
+        sorted.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        ScanExport {
+            schema_version: SCHEMA_VERSION,
+            kind: "scan",
+            generator: generator(),
+            generated_at: Local::now(),
+            root,
+            device,
+            total_size: files.iter().map(|file| file.size).sum(),
+            file_count: files.len(),
+            files: sorted,
+        }
+    }
+}
+
+impl<'a> DevicesExport<'a> {
+    pub fn new(devices: &'a [StorageDevice]) -> Self {
+        let (total_space, available_space) = distinct_space(devices);
+        DevicesExport {
+            schema_version: SCHEMA_VERSION,
+            kind: "devices",
+            generator: generator(),
+            generated_at: Local::now(),
+            total_space,
+            available_space,
+            devices,
+        }
+    }
+}
+
+/// Version of the ncdu export format written, as `[major, minor]`.
+const NCDU_VERSION: [u32; 2] = [1, 2];
+
+/// A directory of the tree an ncdu export describes, with what is below it.
+#[derive(Default)]
+struct NcduDir<'a> {
+    files: Vec<(&'a str, &'a FileEntry)>,
+    dirs: BTreeMap<&'a str, NcduDir<'a>>,
+}
+
+impl NcduDir<'_> {
+    /// The directory as ncdu writes it: an array of its own entry, named `name`, followed by
+    /// its files and subdirectories.
+    fn to_value(&self, name: &str) -> Value {
+        let mut items = vec![json!({ "name": name })];
+        for (name, file) in &self.files {
+            let mut item = json!({ "name": name, "asize": file.size, "dsize": file.disk_usage() });
+            if file.link_target.is_some() {
+                item["notreg"] = Value::Bool(true);
+            }
+            items.push(item);
+        }
+        items.extend(self.dirs.iter().map(|(name, dir)| dir.to_value(name)));
+        Value::Array(items)
+    }
+}
+
+/// The files of a scan of `root` in the ncdu export format, which `ncdu -f` opens. Directories
+/// are rebuilt from the file paths; files outside `root` are left out.
+pub fn ncdu(root: &str, files: &[FileEntry]) -> Value {
+    let mut tree = NcduDir::default();
+    for file in files {
+        let Ok(relative) = Path::new(&file.path).strip_prefix(root) else {
+            continue;
+        };
+        let Some(relative) = relative.to_str() else {
+            continue;
+        };
+        let mut components: Vec<&str> = relative.split('/').filter(|component| !component.is_empty()).collect();
+        let Some(name) = components.pop() else {
+            continue;
+        };
+        let dir = components.into_iter().fold(&mut tree, |dir, component| dir.dirs.entry(component).or_default());
+        dir.files.push((name, file));
+    }
+    let timestamp = Local::now().timestamp();
+    json!([
+        NCDU_VERSION[0],
+        NCDU_VERSION[1],
+        { "progname": "lazysmg", "progver": env!("CARGO_PKG_VERSION"), "timestamp": timestamp },
+        tree.to_value(root),
+    ])
+}
+
+/// Write the ncdu export of the files of a scan of `root` to `path`, replacing it atomically.
+pub fn write_ncdu(path: &Path, root: &str, files: &[FileEntry]) -> io::Result<()> {
+    let mut json = ncdu(root, files).to_string();
+    json.push('\n');
+    persist::write_atomic(path, json.as_bytes())
+}
+
+/// Pretty-printed JSON of `document`.
+pub fn to_json(document: &impl Serialize) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(document)
+}
+
+/// Write `document` to `path`, replacing it atomically.
+pub fn write(path: &Path, document: &impl Serialize) -> io::Result<()> {
+    let mut json = to_json(document)?;
+    json.push('\n');
+    persist::write_atomic(path, json.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> FileEntry {
+        FileEntry { name: path.rsplit('/').next().unwrap().to_string(), path: path.to_string(), size, link_target: None, allocated_size: None }
+    }
+
+    #[test]
+    fn ncdu_export_nests_files_in_directories() {
+        let files = [file("/v/a/b/deep.bin", 30), file("/v/top.txt", 10), file("/v/a/mid.mov", 20), file("/elsewhere/x", 5)];
+        let json = ncdu("/v", &files);
+        assert_eq!(json[0], 1);
+        assert_eq!(json[1], 2);
+        assert_eq!(json[2]["progname"], "lazysmg");
+        assert_eq!(
+            json[3],
+            json!([
+                { "name": "/v" },
+                { "name": "top.txt", "asize": 10, "dsize": 10 },
+                [
+                    { "name": "a" },
+                    { "name": "mid.mov", "asize": 20, "dsize": 20 },
+                    [{ "name": "b" }, { "name": "deep.bin", "asize": 30, "dsize": 30 }],
+                ],
+            ])
+        );
+    }
+
+    #[test]
+    fn scan_export_is_versioned_and_sorted() {
+        let files = [file("/v/a.txt", 10), file("/v/b.mov", 300), file("/v/c.log", 10)];
+        let json: Value = serde_json::from_str(&to_json(&ScanExport::new("/v", None, &files)).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["kind"], "scan");
+        assert_eq!(json["root"], "/v");
+        assert!(json.get("device").is_none());
+        assert_eq!(json["total_size"], 320);
+        assert_eq!(json["file_count"], 3);
+        let paths: Vec<&str> = json["files"].as_array().unwrap().iter().map(|file| file["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, ["/v/b.mov", "/v/a.txt", "/v/c.log"]);
+        assert_eq!(json["files"][0]["size"], 300);
+    }
+}
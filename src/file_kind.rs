@@ -0,0 +1,73 @@
+use std::path::Path;
+
+/// What an entry of a file listing is, by its extension, for its icon and color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileKind {
+    Directory,
+    /// A symlink listed as one, rather than followed
+    Symlink,
+    Image,
+    Video,
+    Audio,
+    Archive,
+    Code,
+    Document,
+    Other,
+}
+
+impl FileKind {
+    pub fn of(path: &str, is_dir: bool) -> FileKind {
+        if is_dir {
+            return FileKind::Directory;
+        }
+        match extension(path).unwrap_or_default().as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif" | "svg" | "ico" | "raw" | "cr2" | "nef"
+            | "arw" | "dng" | "psd" => FileKind::Image,
+            "mp4" | "m4v" | "mov" | "mkv" | "avi" | "wmv" | "webm" | "flv" | "mpg" | "mpeg" | "3gp" => FileKind::Video,
+            "mp3" | "m4a" | "aac" | "flac" | "wav" | "ogg" | "opus" | "aiff" | "wma" => FileKind::Audio,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" | "dmg" | "iso" | "pkg" | "deb" | "rpm" | "jar" => {
+                FileKind::Archive
+            }
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "m" | "swift" | "go" | "java" | "kt" | "js" | "jsx" | "ts" | "tsx" | "py" | "rb"
+            | "sh" | "bash" | "zsh" | "fish" | "json" | "toml" | "yml" | "yaml" | "html" | "css" | "sql" | "lua" => FileKind::Code,
+            "txt" | "md" | "pdf" | "doc" | "docx" | "odt" | "rtf" | "xls" | "xlsx" | "ods" | "csv" | "ppt" | "pptx" | "odp" | "pages"
+            | "numbers" | "key" | "epub" => FileKind::Document,
+            _ => FileKind::Other,
+        }
+    }
+
+    /// Nerd Font glyph shown before the name, which needs a patched font.
+    pub fn icon(self) -> &'static str {
+        match self {
+            FileKind::Directory => "\u{f07b}",
+            FileKind::Symlink => "\u{f0c1}",
+            FileKind::Image => "\u{f1c5}",
+            FileKind::Video => "\u{f1c8}",
+            FileKind::Audio => "\u{f1c7}",
+            FileKind::Archive => "\u{f1c6}",
+            FileKind::Code => "\u{f1c9}",
+            FileKind::Document => "\u{f15c}",
+            FileKind::Other => "\u{f15b}",
+        }
+    }
+
+    /// Name of the kind in the file type breakdown.
+    pub fn label(self) -> &'static str {
+        match self {
+            FileKind::Directory => "Directories",
+            FileKind::Symlink => "Links",
+            FileKind::Image => "Images",
+            FileKind::Video => "Videos",
+            FileKind::Audio => "Audio",
+            FileKind::Archive => "Archives",
+            FileKind::Code => "Code",
+            FileKind::Document => "Documents",
+            FileKind::Other => "Other",
+        }
+    }
+}
+
+/// Extension of `path` in lowercase, so `.JPG` and `.jpg` count as one.
+pub fn extension(path: &str) -> Option<String> {
+    Path::new(path).extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::SystemTime,
+};
+
+// What code that judges files by their metadata reads it through, so it can run against files
+// made up in memory as well as the disk.
+
+/// The metadata of a file that lazysmg looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: u64,
+    /// None where the file system doesn't record it
+    pub modified: Option<SystemTime>,
+    /// When it was last read; None where the file system doesn't record it
+    pub accessed: Option<SystemTime>,
+    pub is_dir: bool,
+    /// Bytes the file takes up on disk, which differs from `size` for sparse and compressed
+    /// files; None where the file system doesn't report it
+    pub allocated_size: Option<u64>,
+}
+
+/// Source of file metadata, and a way to remove files. lazysmg's own deletes don't use
+/// `remove`: they move files to the trash, or overwrite them first for a secure delete.
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// Metadata of `path` itself; symlinks aren't followed.
+    fn file_info(&self, path: &Path) -> io::Result<FileInfo>;
+
+    /// Delete the file at `path`, or the directory and everything below it.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The real file systems of the machine.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn file_info(&self, path: &Path) -> io::Result<FileInfo> {
+        let metadata = fs::symlink_metadata(path)?;
+        Ok(FileInfo {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            is_dir: metadata.is_dir(),
+            allocated_size: allocated_size(&metadata),
+        })
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+}
+
+/// Bytes `metadata`'s file takes up on disk: its allocated blocks, which `st_blocks` counts in
+/// 512-byte units on every Unix. None elsewhere.
+pub fn allocated_size(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.blocks() * 512)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Files that only exist in memory, by path.
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    files: RwLock<HashMap<PathBuf, FileInfo>>,
+}
+
+impl MemoryFileSystem {
+    /// Add a file of `size` bytes last modified, and last read, at `modified`.
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, size: u64, modified: Option<SystemTime>) {
+        let files = self.files.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        files.insert(path.into(), FileInfo { size, modified, accessed: modified, is_dir: false, allocated_size: None });
+    }
+
+    /// Whether `path` is one of the files, or holds one.
+    pub fn contains(&self, path: &Path) -> bool {
+        let files = self.files.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        files.keys().any(|file| file.starts_with(path))
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't exist", path.display()))
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn file_info(&self, path: &Path) -> io::Result<FileInfo> {
+        let files = self.files.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        files.get(path).copied().ok_or_else(|| not_found(path))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut files = self.files.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = files.len();
+        files.retain(|file, _| !file.starts_with(path));
+        if files.len() == count {
+            return Err(not_found(path));
+        }
+        Ok(())
+    }
+}
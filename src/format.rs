@@ -0,0 +1,211 @@
+use std::sync::OnceLock;
+use serde::Deserialize;
+
+/// How byte sizes are displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizeUnits {
+    /// Powers of 1024 with the traditional KB/MB/GB labels
+    #[default]
+    Binary,
+    /// Powers of 1024 with IEC labels (KiB/MiB/GiB)
+    Iec,
+    /// Powers of 1000 (KB/MB/GB), as drive manufacturers and Finder count
+    Decimal,
+}
+
+/// How numbers are written in the user's locale: the separator between groups of three digits,
+/// if any, and the decimal separator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub grouping: Option<char>,
+    pub decimal: char,
+}
+
+impl NumberFormat {
+    /// Digits without separators and a decimal point, as in the "C" locale.
+    pub const PLAIN: NumberFormat = NumberFormat { grouping: None, decimal: '.' };
+
+    /// The separators of `locale`, a name such as "de_DE.UTF-8", "fr-CH" or "C". Languages
+    /// not known here write numbers like English.
+    pub fn for_locale(locale: &str) -> NumberFormat {
+        let name = locale.split(['.', '@']).next().unwrap_or_default().replace('-', "_");
+        let (language, region) = name.split_once('_').unwrap_or((name.as_str(), ""));
+        let (grouping, decimal) = match (language.to_ascii_lowercase().as_str(), region.to_ascii_uppercase().as_str()) {
+            ("" | "c" | "posix", _) => return NumberFormat::PLAIN,
+            ("de" | "it" | "fr", "CH") => ('\'', '.'),
+            ("de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl" | "sr", _) => ('.', ','),
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "no" | "fi" | "uk" | "hu" | "bg" | "et" | "lt" | "lv", _) => ('\u{a0}', ','),
+            _ => (',', '.'),
+        };
+        NumberFormat { grouping: Some(grouping), decimal }
+    }
+
+    /// The format of the locale the environment sets for numbers: `LC_ALL`, `LC_NUMERIC` or
+    /// `LANG`, the first one set.
+    pub fn from_env() -> NumberFormat {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map_or(NumberFormat::PLAIN, |locale| NumberFormat::for_locale(&locale))
+    }
+
+    /// `count` with its digits grouped, e.g. "1,234,567" or "1.234.567".
+    pub fn count(self, count: u64) -> String {
+        let digits = count.to_string();
+        let Some(separator) = self.grouping else {
+            return digits;
+        };
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// `value` with `decimals` decimal places and grouped digits, e.g. "1,234.50" or "1.234,50".
+    pub fn decimal(self, value: f64, decimals: usize) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (whole, fraction) = fixed.split_once('.').unwrap_or((fixed.as_str(), ""));
+        let mut text = if value < 0.0 { "-".to_string() } else { String::new() };
+        text.push_str(&self.count(whole.parse().unwrap_or(0)));
+        if !fraction.is_empty() {
+            text.push(self.decimal);
+            text.push_str(fraction);
+        }
+        text
+    }
+}
+
+static NUMBER_FORMAT: OnceLock<NumberFormat> = OnceLock::new();
+
+/// Write numbers the way `format` says for the rest of the run; called once at startup.
+pub fn set_number_format(format: NumberFormat) {
+    let _ = NUMBER_FORMAT.set(format);
+}
+
+/// The format numbers are written in, plain until one is set.
+pub fn number_format() -> NumberFormat {
+    NUMBER_FORMAT.get().copied().unwrap_or(NumberFormat::PLAIN)
+}
+
+/// Format a count of files, directories, ... with its digits grouped for the locale.
+pub fn format_count(count: u64) -> String {
+    number_format().count(count)
+}
+
+/// Format a duration coarsely for progress displays, e.g. "1h 05m", "3m 20s" or "42s".
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Format the depth of a depth-limited scan, e.g. "1 level" or "3 levels".
+pub fn format_depth(depth: usize) -> String {
+    format!("{} level{}", depth, if depth == 1 { "" } else { "s" })
+}
+
+/// Format a monthly cost, e.g. "~$1.20/month"; amounts under a cent show as "<$0.01/month".
+pub fn format_cost(amount: f64, currency: &str) -> String {
+    if amount < 0.01 {
+        format!("<{}{}/month", currency, number_format().decimal(0.01, 2))
+    } else {
+        format!("~{}{}/month", currency, number_format().decimal(amount, 2))
+    }
+}
+
+/// Format a byte count in a human-readable way, e.g. "1.50 GB".
+pub fn format_size(bytes: u64, units: SizeUnits) -> String {
+    let (base, labels): (f64, [&str; 5]) = match units {
+        SizeUnits::Binary => (1024.0, ["KB", "MB", "GB", "TB", "PB"]),
+        SizeUnits::Iec => (1024.0, ["KiB", "MiB", "GiB", "TiB", "PiB"]),
+        SizeUnits::Decimal => (1000.0, ["KB", "MB", "GB", "TB", "PB"]),
+    };
+
+    if (bytes as f64) < base {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / base;
+    let mut label = labels[0];
+    for next in &labels[1..] {
+        if value < base {
+            break;
+        }
+        value /= base;
+        label = next;
+    }
+    format!("{} {}", number_format().decimal(value, 2), label)
+}
+
+/// Parse a size typed by the user, e.g. "50G", "50 GB", "1.5TB" or "800 MiB". "KB", "MB" and so
+/// on count like `units` shows them; "KiB", "MiB" and so on are always powers of 1024. A number
+/// without a unit is in GB.
+pub fn parse_size(text: &str, units: SizeUnits) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let base: f64 = if units == SizeUnits::Decimal { 1000.0 } else { 1024.0 };
+    let unit = unit.trim().to_ascii_uppercase();
+    let (prefix, binary) = match unit.strip_suffix("IB") {
+        Some(prefix) => (prefix, true),
+        None => (unit.strip_suffix('B').unwrap_or(&unit), false),
+    };
+    let power = match prefix {
+        "" if binary => return None,
+        "" if unit.is_empty() => 3,
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    let base = if binary { 1024.0 } else { base };
+    (number >= 0.0).then(|| (number * base.powi(power)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_sizes() {
+        let gb = 1024 * 1024 * 1024;
+        assert_eq!(parse_size("50G", SizeUnits::Binary), Some(50 * gb));
+        assert_eq!(parse_size("50 gb", SizeUnits::Binary), Some(50 * gb));
+        assert_eq!(parse_size("50", SizeUnits::Binary), Some(50 * gb));
+        assert_eq!(parse_size("1.5T", SizeUnits::Decimal), Some(1_500_000_000_000));
+        assert_eq!(parse_size("800 MiB", SizeUnits::Decimal), Some(800 * 1024 * 1024));
+        assert_eq!(parse_size("512B", SizeUnits::Binary), Some(512));
+        assert_eq!(parse_size("lots", SizeUnits::Binary), None);
+        assert_eq!(parse_size("5 parsecs", SizeUnits::Binary), None);
+    }
+
+    #[test]
+    fn numbers_follow_the_locale() {
+        let english = NumberFormat::for_locale("en_US.UTF-8");
+        assert_eq!(english.count(1_234_567), "1,234,567");
+        assert_eq!(english.count(123), "123");
+        assert_eq!(english.decimal(1234.5, 2), "1,234.50");
+
+        let german = NumberFormat::for_locale("de-DE");
+        assert_eq!(german.count(1_234_567), "1.234.567");
+        assert_eq!(german.decimal(-1.5, 2), "-1,50");
+
+        assert_eq!(NumberFormat::for_locale("fr_FR@euro").count(1000), "1\u{a0}000");
+        assert_eq!(NumberFormat::for_locale("de_CH").decimal(1234.5, 1), "1'234.5");
+        assert_eq!(NumberFormat::for_locale("C").count(1_234_567), "1234567");
+        assert_eq!(NumberFormat::for_locale("POSIX").decimal(0.5, 2), "0.50");
+    }
+}
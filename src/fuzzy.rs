@@ -0,0 +1,46 @@
+/// Case-insensitive fuzzy subsequence match of `query` against `candidate`.
+///
+/// Returns a score (higher is better) and the char indices of `candidate` that matched,
+/// or `None` if the query characters do not all appear in order. Matches at the start of
+/// the candidate or of a word, and runs of consecutive characters, score higher, so
+/// "mac" prefers "Macintosh HD" over "Time Machine".
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0i64;
+    let mut next = 0;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let found = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(std::iter::once(q)))?;
+
+        score += 1;
+        if found == 0 {
+            score += 10;
+        } else if !chars[found - 1].is_alphanumeric() {
+            score += 5;
+        }
+        if positions.last().is_some_and(|&last| last + 1 == found) {
+            score += 3;
+        }
+        // Penalize characters skipped over to reach this match
+        score -= (found - next) as i64;
+
+        positions.push(found);
+        next = found + 1;
+    }
+
+    Some((score, positions))
+}
+
+/// Index of the candidate that best matches `query`, preferring the earliest on ties.
+pub fn best_match<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<usize> {
+    candidates
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, candidate)| fuzzy_match(query, candidate).map(|(score, _)| (i, score)))
+        .fold(None, |best: Option<(usize, i64)>, (i, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((i, score)),
+        })
+        .map(|(i, _)| i)
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use lazysmg::analysis::{self, FileEntry, GoalCandidate, GoalPlan, GoalSource};
+use lazysmg::clock::SystemClock;
+use lazysmg::filesystem::{FileSystem, LocalFileSystem};
+use crate::config::StaleConfig;
+use crate::format::{format_size, SizeUnits};
+use crate::platform::last_used;
+use crate::safety::Safety;
+use crate::storage::device_guard;
+
+// The "free up N GB" goal of `:goal`: candidates from the junk scan, the duplicates found and
+// the full scan are offered safest first, the user accepts enough of them to reach the goal, and
+// the accepted ones are deleted in one batch. Junk is deleted for good, like from the junk
+// view; everything else is user data and goes to the trash through the operation queue.
+
+/// Downloads smaller than this aren't worth offering.
+const MIN_DOWNLOAD_SIZE: u64 = 1024 * 1024;
+
+/// Old downloads and cold files offered at most, the largest ones.
+const MAX_PER_SOURCE: usize = 200;
+
+/// Free space wanted on a device, with the plan to get there.
+#[derive(Debug, Clone)]
+pub struct Goal {
+    pub device: String,
+    /// Free space wanted, in bytes
+    pub target: u64,
+    /// Free space when the goal was set
+    pub available: u64,
+    pub plan: GoalPlan,
+}
+
+/// What the candidates are picked from, taken from the app before looking for them in the
+/// background.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    /// Junk files of the last junk scan, by category name
+    pub junk: Vec<(String, Vec<FileEntry>)>,
+    /// Copies of the duplicates found, with the copy each one leaves
+    pub duplicates: Vec<(String, FileEntry)>,
+    /// Files of the full scan of the device
+    pub files: Vec<FileEntry>,
+}
+
+/// The candidates of `sources`: every junk category and duplicate, and among the scanned user
+/// data the downloads and large files unused for `stale.unused_days`. Looks up when files were
+/// last modified and opened.
+pub fn candidates(sources: Sources, stale: StaleConfig) -> Vec<GoalCandidate> {
+    let downloads = expanduser::expanduser("~/Downloads").ok();
+    let modified = |file: &FileEntry| LocalFileSystem.file_info(Path::new(&file.path)).ok().and_then(|info| info.modified);
+    pick(sources, stale, downloads.as_deref(), modified, last_used::last_opened)
+}
+
+/// `candidates`, with the downloads directory and the lookups of when files were last modified
+/// and (for a batch of paths) last opened given.
+fn pick(
+    sources: Sources,
+    stale: StaleConfig,
+    downloads: Option<&Path>,
+    modified: impl Fn(&FileEntry) -> Option<SystemTime>,
+    last_opened: impl FnOnce(&[String]) -> HashMap<String, SystemTime>,
+) -> Vec<GoalCandidate> {
+    let mut candidates: Vec<GoalCandidate> = sources
+        .junk
+        .into_iter()
+        .map(|(category, files)| GoalCandidate { source: GoalSource::Junk, label: category, files, keep: None })
+        .collect();
+    candidates.extend(sources.duplicates.into_iter().map(|(keep, file)| GoalCandidate {
+        source: GoalSource::Duplicate,
+        label: format!("{} (copy of {})", file.path, keep),
+        files: vec![file],
+        keep: Some(keep),
+    }));
+
+    let min_size = stale.min_size_mb * 1024 * 1024;
+    let user_data: Vec<FileEntry> = sources.files.into_iter().filter(|file| Safety::classify(&file.path) == Safety::UserData).collect();
+    let (downloads, others): (Vec<FileEntry>, Vec<FileEntry>) =
+        user_data.into_iter().partition(|file| downloads.is_some_and(|downloads| Path::new(&file.path).starts_with(downloads)));
+    let others: Vec<FileEntry> = others.into_iter().filter(|file| file.size >= min_size).collect();
+    let paths: Vec<String> = downloads.iter().chain(&others).filter(|file| file.size >= MIN_DOWNLOAD_SIZE).map(|file| file.path.clone()).collect();
+    let opened = last_opened(&paths);
+    let times = |file: &FileEntry| (modified(file), opened.get(&file.path).copied());
+    let unused = |entries: &[FileEntry], min_size, source| {
+        let filter = analysis::AgeFilter::new(Some(Duration::from_secs(stale.unused_days * 24 * 60 * 60)), &SystemClock);
+        let by_path: HashMap<&str, &FileEntry> = entries.iter().map(|file| (file.path.as_str(), file)).collect();
+        analysis::stale_files(entries, min_size, filter, times)
+            .into_iter()
+            .take(MAX_PER_SOURCE)
+            .filter_map(|stale| by_path.get(stale.path.as_str()).map(|file| (*file).clone()))
+            .map(|file| GoalCandidate { source, label: file.path.clone(), files: vec![file], keep: None })
+            .collect::<Vec<_>>()
+    };
+    candidates.extend(unused(&downloads, MIN_DOWNLOAD_SIZE, GoalSource::OldDownload));
+    candidates.extend(unused(&others, min_size, GoalSource::ColdFile));
+    candidates
+}
+
+/// What running the accepted plan did.
+#[derive(Debug, Clone, Default)]
+pub struct Outcome {
+    /// Junk files deleted for good, with their size
+    pub deleted: Vec<(String, u64)>,
+    /// Paths to move to the trash through the operation queue, with their size
+    pub to_trash: Vec<(String, u64)>,
+    /// Paths that couldn't be or weren't removed, with why
+    pub failed: Vec<(String, String)>,
+}
+
+/// Delete the accepted junk of `plan` for good and pick the rest for the trash, which goes
+/// through the operation queue so it can be undone. Junk must still be a regular file, and a
+/// duplicate must still match the copy it leaves. A file that an accepted duplicate leaves as
+/// its copy stays, even if it is accepted as a cold file or download too.
+pub fn run(plan: &GoalPlan) -> Outcome {
+    let mut outcome = Outcome::default();
+    let kept: HashSet<&str> = plan.accepted().filter_map(|candidate| candidate.keep.as_deref()).collect();
+    for candidate in plan.accepted() {
+        for file in &candidate.files {
+            let path = Path::new(&file.path);
+            let result = if kept.contains(file.path.as_str()) {
+                Err("it is the copy kept of a duplicate removed".to_string())
+            } else if candidate.source == GoalSource::Junk {
+                delete_junk(path).map(|_| false)
+            } else if let Some(keep) = &candidate.keep {
+                same_content(path, Path::new(keep)).then_some(true).ok_or_else(|| format!("it no longer matches {}", keep))
+            } else {
+                Ok(true)
+            };
+            match result {
+                Ok(true) => outcome.to_trash.push((file.path.clone(), file.size)),
+                Ok(false) => outcome.deleted.push((file.path.clone(), file.size)),
+                Err(e) => outcome.failed.push((file.path.clone(), e)),
+            }
+        }
+    }
+    outcome
+}
+
+/// Delete the junk file at `path`, unless its device is being ejected or it is no longer a
+/// regular file.
+fn delete_junk(path: &Path) -> Result<(), String> {
+    device_guard::check(&path.to_string_lossy()).map_err(|busy| busy.to_string())?;
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() => fs::remove_file(path).map_err(|e| e.to_string()),
+        Ok(_) => Err("no longer a regular file".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether the copy at `path` still has the content of `keep`.
+#[cfg(feature = "duplicates")]
+fn same_content(path: &Path, keep: &Path) -> bool {
+    crate::duplicates::same_content(path, keep).unwrap_or(false)
+}
+
+/// Without duplicate finding there are no duplicates to remove.
+#[cfg(not(feature = "duplicates"))]
+fn same_content(_path: &Path, _keep: &Path) -> bool {
+    false
+}
+
+impl Outcome {
+    /// Paths deleted or on their way to the trash.
+    pub fn removed(&self) -> HashSet<&str> {
+        self.deleted.iter().chain(&self.to_trash).map(|(path, _)| path.as_str()).collect()
+    }
+
+    /// The final report: how far the goal got, and what is left to do.
+    pub fn report(&self, goal: &Goal, units: SizeUnits, empty_trash_key: &str) -> String {
+        let freed: u64 = self.deleted.iter().map(|(_, size)| size).sum();
+        let trashed: u64 = self.to_trash.iter().map(|(_, size)| size).sum();
+        let mut lines = vec![format!("Goal: {} free on {}", format_size(goal.target, units), goal.device)];
+        lines.push(format!("Deleted {} junk files, freeing {}", self.deleted.len(), format_size(freed, units)));
+        if !self.to_trash.is_empty() {
+            lines.push(format!(
+                "Moving {} files to the trash; {} more is freed once it is emptied ({})",
+                self.to_trash.len(),
+                format_size(trashed, units),
+                empty_trash_key
+            ));
+        }
+        let free = goal.available + freed + trashed;
+        if free >= goal.target {
+            lines.push(format!("About {} free then: goal reached", format_size(free, units)));
+        } else {
+            lines.push(format!("About {} free then, {} short of the goal", format_size(free, units), format_size(goal.target - free, units)));
+        }
+        if let Some((path, error)) = self.failed.first() {
+            lines.push(format!("{} couldn't be removed, e.g. {}: {}", self.failed.len(), path, error));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_the_trash_towards_the_goal() {
+        let gb = 1024 * 1024 * 1024;
+        let goal = Goal { device: "Macintosh HD".to_string(), target: 50 * gb, available: 20 * gb, plan: GoalPlan::default() };
+        let outcome = Outcome {
+            deleted: vec![("/c/a".to_string(), 10 * gb)],
+            to_trash: vec![("/d/movie.mkv".to_string(), 25 * gb)],
+            failed: vec![("/d/locked.iso".to_string(), "Permission denied".to_string())],
+        };
+        let report = outcome.report(&goal, SizeUnits::Binary, "T");
+        let size = |gb_count| format_size(gb_count * gb, SizeUnits::Binary);
+        assert!(report.contains(&format!("Deleted 1 junk files, freeing {}", size(10))), "{}", report);
+        assert!(report.contains(&format!("{} more is freed once it is emptied (T)", size(25))), "{}", report);
+        assert!(report.contains(&format!("About {} free then: goal reached", size(55))), "{}", report);
+        assert!(report.contains("1 couldn't be removed, e.g. /d/locked.iso: Permission denied"), "{}", report);
+        assert_eq!(outcome.removed().len(), 2);
+    }
+
+    fn file(path: &str, size: u64) -> FileEntry {
+        let name = Path::new(path).file_name().unwrap().to_string_lossy().into_owned();
+        FileEntry { name, path: path.to_string(), size, link_target: None, allocated_size: None }
+    }
+
+    #[test]
+    fn candidates_are_junk_duplicates_and_unused_user_data() {
+        let gb = 1024 * 1024 * 1024;
+        let now = SystemTime::now();
+        let days_ago = |days: u64| now - Duration::from_secs(days * 24 * 60 * 60);
+        let sources = Sources {
+            junk: vec![("Caches".to_string(), vec![file("/media/ana/.cache/a", 10)])],
+            duplicates: vec![("/media/ana/Photos/a.jpg".to_string(), file("/media/ana/Old/a.jpg", 5))],
+            files: vec![
+                file("/media/ana/Downloads/old.zip", 2 * 1024 * 1024),
+                file("/media/ana/Downloads/tiny.txt", 10),
+                file("/media/ana/Downloads/new.zip", 2 * 1024 * 1024),
+                file("/media/ana/Movies/big.mkv", 3 * gb),
+                file("/media/ana/Movies/watched.mkv", 3 * gb),
+                file("/media/ana/Movies/small.mkv", 10 * 1024 * 1024),
+                file("/usr/lib/huge.so", 3 * gb),
+            ],
+        };
+        let modified = |file: &FileEntry| Some(if file.path.contains("new") { days_ago(1) } else { days_ago(400) });
+        let last_opened = |paths: &[String]| {
+            assert!(!paths.iter().any(|path| path.contains("tiny") || path.contains("small") || path.contains("huge")), "{:?}", paths);
+            HashMap::from([("/media/ana/Movies/watched.mkv".to_string(), days_ago(2))])
+        };
+        let stale = StaleConfig { min_size_mb: 1024, unused_days: 30 };
+        let candidates = pick(sources, stale, Some(Path::new("/media/ana/Downloads")), modified, last_opened);
+
+        let found: Vec<(GoalSource, &str, Option<&str>)> = candidates.iter().map(|candidate| (candidate.source, candidate.label.as_str(), candidate.keep.as_deref())).collect();
+        assert_eq!(
+            found,
+            [
+                (GoalSource::Junk, "Caches", None),
+                (GoalSource::Duplicate, "/media/ana/Old/a.jpg (copy of /media/ana/Photos/a.jpg)", Some("/media/ana/Photos/a.jpg")),
+                (GoalSource::OldDownload, "/media/ana/Downloads/old.zip", None),
+                (GoalSource::ColdFile, "/media/ana/Movies/big.mkv", None),
+            ]
+        );
+    }
+
+    #[cfg(feature = "duplicates")]
+    #[test]
+    fn run_rechecks_what_it_removes() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-goal-{}", std::process::id()));
+        fs::create_dir_all(dir.join("cache/now-a-dir")).unwrap();
+        let write = |name: &str, content: &str| {
+            fs::write(dir.join(name), content).unwrap();
+            file(&dir.join(name).to_string_lossy(), content.len() as u64)
+        };
+        let junk = write("cache/junk.bin", "junk");
+        fs::write(dir.join("cache/now-a-dir/keep.txt"), "mine").unwrap();
+        let became_dir = file(&dir.join("cache/now-a-dir").to_string_lossy(), 4);
+        let kept = write("photo.jpg", "photo");
+        let copy = write("photo copy.jpg", "photo");
+        let changed_keep = write("notes.txt", "notes");
+        let changed_copy = write("notes copy.txt", "edited");
+        let cold = write("movie.mkv", "movie");
+        let not_accepted = write("report.pdf", "report");
+
+        let candidate = |source, files: Vec<FileEntry>, keep: Option<&FileEntry>| GoalCandidate {
+            source,
+            label: files[0].path.clone(),
+            files,
+            keep: keep.map(|keep| keep.path.clone()),
+        };
+        let plan = GoalPlan {
+            needed: 0,
+            candidates: vec![
+                candidate(GoalSource::Junk, vec![junk.clone(), became_dir.clone()], None),
+                candidate(GoalSource::Duplicate, vec![copy.clone()], Some(&kept)),
+                candidate(GoalSource::Duplicate, vec![changed_copy.clone()], Some(&changed_keep)),
+                candidate(GoalSource::ColdFile, vec![kept.clone()], None),
+                candidate(GoalSource::ColdFile, vec![cold.clone()], None),
+                candidate(GoalSource::ColdFile, vec![not_accepted.clone()], None),
+            ],
+            accepted: vec![true, true, true, true, true, false],
+        };
+        let outcome = run(&plan);
+
+        assert_eq!(outcome.deleted, [(junk.path.clone(), junk.size)]);
+        assert!(!Path::new(&junk.path).exists());
+        // Only the queue moves files to the trash
+        assert_eq!(outcome.to_trash, [(copy.path.clone(), copy.size), (cold.path.clone(), cold.size)]);
+        assert!(Path::new(&copy.path).exists());
+        let failed: Vec<&str> = outcome.failed.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(failed, [became_dir.path.as_str(), changed_copy.path.as_str(), kept.path.as_str()]);
+        assert!(dir.join("cache/now-a-dir/keep.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
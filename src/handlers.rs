@@ -0,0 +1,128 @@
+use std::path::Path;
+#[cfg(feature = "preview")]
+use crate::config::Renderer;
+use crate::config::HandlerConfig;
+
+/// How the preview pane shows a file.
+#[cfg(feature = "preview")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewHandler {
+    Builtin(Renderer),
+    /// Program and arguments, the file's path filled in
+    Command(Vec<String>),
+}
+
+/// Handlers that come with lazysmg, tried after those of the config file. Their commands are
+/// skipped when the program isn't installed.
+fn builtin_handlers() -> Vec<HandlerConfig> {
+    let preview = |extensions: &[&str], command: &str| HandlerConfig {
+        extensions: extensions.iter().map(|extension| extension.to_string()).collect(),
+        preview: Some(command.to_string()),
+        ..HandlerConfig::default()
+    };
+    let mut handlers = vec![
+        preview(&["zip", "jar"], "unzip -l {path}"),
+        preview(&["tar", "tar.gz", "tgz", "tar.bz2", "tar.xz"], "tar -tvf {path}"),
+    ];
+    if cfg!(target_os = "macos") {
+        // Binary property lists are unreadable as a hex dump
+        handlers.push(preview(&["plist"], "plutil -p {path}"));
+    }
+    handlers
+}
+
+/// MIME type of a file by its extension, for the common types.
+pub fn mime_type(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+    Some(match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "xml" => "text/xml",
+        "json" => "application/json",
+        "js" => "text/javascript",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "db" | "sqlite" | "sqlite3" => "application/vnd.sqlite3",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "svg" => "image/svg+xml",
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        _ => return None,
+    })
+}
+
+/// Whether `handler` is meant for the file at `path`: by the end of its name, so extensions
+/// like "tar.gz" work, or by its MIME type, where "image/*" stands for all images.
+fn handles(handler: &HandlerConfig, path: &str) -> bool {
+    let name = Path::new(path).file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let by_extension = handler
+        .extensions
+        .iter()
+        .any(|extension| name.ends_with(&format!(".{}", extension.trim_start_matches('.').to_lowercase())));
+    by_extension
+        || mime_type(path).is_some_and(|mime| {
+            handler.mime.iter().any(|pattern| match pattern.strip_suffix("/*") {
+                Some(kind) => mime.split('/').next() == Some(kind),
+                None => pattern == mime,
+            })
+        })
+}
+
+/// A command of a handler as program and arguments: split at spaces, with `{path}` replaced by
+/// `path`, or `path` appended if it doesn't appear.
+fn command_line(command: &str, path: &str) -> Vec<String> {
+    let mut args: Vec<String> = command.split_whitespace().map(|arg| arg.replace("{path}", path)).collect();
+    if !command.contains("{path}") {
+        args.push(path.to_string());
+    }
+    args
+}
+
+/// Which preview renderer and open command a file gets: the first handler of the config file
+/// that covers it, then the built-in ones.
+#[derive(Debug, Clone, Default)]
+pub struct HandlerRegistry {
+    handlers: Vec<HandlerConfig>,
+}
+
+impl HandlerRegistry {
+    pub fn new(configured: &[HandlerConfig]) -> HandlerRegistry {
+        HandlerRegistry { handlers: configured.iter().cloned().chain(builtin_handlers()).collect() }
+    }
+
+    fn matching<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a HandlerConfig> {
+        self.handlers.iter().filter(move |handler| handles(handler, path))
+    }
+
+    /// How to preview the file at `path`; files no handler covers are shown by what they look like.
+    #[cfg(feature = "preview")]
+    pub fn preview_for(&self, path: &str) -> PreviewHandler {
+        self.matching(path)
+            .find_map(|handler| match (&handler.preview, handler.renderer) {
+                (Some(command), _) => Some(PreviewHandler::Command(command_line(command, path))),
+                (None, Some(renderer)) => Some(PreviewHandler::Builtin(renderer)),
+                (None, None) => None,
+            })
+            .unwrap_or(PreviewHandler::Builtin(Renderer::Auto))
+    }
+
+    /// Command that opens the file at `path`, if a handler has one; otherwise it opens in its
+    /// default app.
+    pub fn open_command(&self, path: &str) -> Option<Vec<String>> {
+        self.matching(path).find_map(|handler| handler.open.as_deref()).map(|command| command_line(command, path))
+    }
+}
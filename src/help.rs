@@ -0,0 +1,138 @@
+use crate::config::{Action, KeyBindings};
+
+/// `:` commands, with what they do.
+const COMMANDS: [(&str, &str); 19] = [
+    (":history", "Scans and operations of this and earlier runs"),
+    (":errors", "Paths the last scan couldn't read"),
+    (":dirs", "The 50 largest directories of the full scan"),
+    (":stale", "Large files nobody has read or changed for a long time"),
+    (":broken-links", "Symlinks whose targets are gone, to delete in bulk"),
+    (":duplicates", "Files of the full scan with the same content, to keep one copy of"),
+    (":goal <size>", "Plan freeing up space until the selected device has <size> free"),
+    (":changes", "What changed since the previous full scan of the device"),
+    (":export <file>", "Write the full scan results to a JSON file for other tools"),
+    (":export-ncdu <file>", "Write the full scan in the export format ncdu -f opens"),
+    (":export-html <file>", "Write a standalone HTML report of the full scan, to share"),
+    (":sort delta", "Order scan results by how much they changed since the previous scan"),
+    (":sort off", "Order scan results by size again"),
+    (":depth <levels>", "Levels full scans walk below the device root"),
+    (":depth off", "Let full scans walk the whole tree"),
+    (":xcode", "Xcode caches, simulators and archives"),
+    (":update", "Look for a newer release"),
+    (":eject-all", "Eject every ejectable device"),
+    (":device refresh-info", "Read the details of the selected device again"),
+];
+
+/// Options of the config file, with what they set.
+const CONFIG_OPTIONS: [(&str, &str); 42] = [
+    ("startup.device", "Device selected at startup"),
+    ("startup.view", "View at startup: listing, last-scan or junk-summary"),
+    ("startup.auto_junk_scan", "Junk scan of the system disk at startup"),
+    ("startup.check_updates", "Look for a newer release at startup"),
+    ("keys", "Remap any action to one key or a list of keys"),
+    ("colors.theme", "Built-in theme: dark, light or high-contrast"),
+    ("colors.support", "Colors the terminal shows"),
+    ("colors.<element>", "Replace one of the theme's colors"),
+    ("display.sort", "Sort of listings and scan results"),
+    ("display.size_units", "binary, iec or decimal sizes"),
+    ("display.icons", "Nerd Font icons before names"),
+    ("display.locale", "Locale numbers and sizes are written for"),
+    ("scan.parallelism", "Directory traversal threads"),
+    ("scan.on_battery", "Scans on battery: throttle, confirm or ignore"),
+    ("scan.battery_parallelism", "Traversal threads on battery"),
+    ("scan.respect_gitignore", "Leave out what .gitignore files exclude"),
+    ("scan.symlinks", "Symbolic links: skip, show or follow"),
+    ("scan.max_depth", "Levels full scans walk below the device root"),
+    ("scan.whole_system", "Full scans of the system volume walk all of /"),
+    ("scan.max_files", "Files full scans hold in memory"),
+    ("layout.device_panel", "Width of the device panel, in percent"),
+    ("layout.progress_panel", "Height of the progress panel, in percent"),
+    ("cost.currency", "Shown before amounts"),
+    ("cost.default_rate", "Price per GB and month of devices without a rate"),
+    ("cost.devices", "Price per GB and month by device"),
+    ("delete.check_open_files", "Warn about processes that have a file open"),
+    ("delete.secure_erase", "Allow overwriting files before deleting them"),
+    ("delete.secure_erase_passes", "Times a secure delete overwrites a file"),
+    ("confirm.delete", "Which deletes ask first: always, large-only or never"),
+    ("confirm.large_delete_mb", "Size from which large-only asks, in MB"),
+    ("report.path", "File the junk-scan report is written to"),
+    ("report.command", "Command the junk-scan report is piped to"),
+    ("report.top", "Largest categories, folders and files in the report"),
+    ("stale.min_size_mb", "Size from which :stale reports a file, in MB"),
+    ("stale.unused_days", "Days unused from which :stale reports a file"),
+    ("accessibility.announce_fifo", "FIFO screen reader announcements are written to"),
+    ("accessibility.announce_osc", "Send announcements as OSC 9 notifications"),
+    ("handlers.extensions", "Extensions a handler applies to"),
+    ("handlers.mime", "MIME types a handler applies to"),
+    ("handlers.renderer", "Built-in preview: auto, text or hex"),
+    ("handlers.preview", "Command whose output is the preview"),
+    ("handlers.open", "Command that opens the file"),
+];
+
+/// What a help entry describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpKind {
+    Key,
+    Command,
+    Config,
+}
+
+impl HelpKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            HelpKind::Key => "Key",
+            HelpKind::Command => "Command",
+            HelpKind::Config => "Config",
+        }
+    }
+}
+
+/// One line of help search results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpEntry {
+    pub kind: HelpKind,
+    /// The keys, command or option
+    pub name: String,
+    pub description: &'static str,
+}
+
+/// Keybindings, commands and config options matching `query`: each of its words, ignoring
+/// case, appears in the name or the description.
+pub fn search(keys: &KeyBindings, query: &str) -> Vec<HelpEntry> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let key_entries = Action::ALL
+        .into_iter()
+        .map(|action| HelpEntry { kind: HelpKind::Key, name: keys.label(action), description: action.description() });
+    let entries = |kind, list: &'static [(&str, &'static str)]| {
+        list.iter().map(move |(name, description)| HelpEntry { kind, name: name.to_string(), description })
+    };
+    key_entries
+        .chain(entries(HelpKind::Command, &COMMANDS))
+        .chain(entries(HelpKind::Config, &CONFIG_OPTIONS))
+        .filter(|entry| {
+            let text = format!("{} {}", entry.name, entry.description).to_lowercase();
+            words.iter().all(|word| text.contains(word.as_str()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_matches_every_word_across_kinds() {
+        let keys = KeyBindings::default();
+        let found = search(&keys, "STALE");
+        assert!(found.iter().any(|entry| entry.kind == HelpKind::Command && entry.name == ":stale"));
+        assert!(found.iter().any(|entry| entry.kind == HelpKind::Config && entry.name == "stale.unused_days"));
+
+        let found = search(&keys, "quit application");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, HelpKind::Key);
+        assert_eq!(found[0].name, keys.label(Action::Quit));
+
+        assert!(search(&keys, "stale nonsense").is_empty());
+        assert_eq!(search(&keys, "").len(), Action::ALL.len() + COMMANDS.len() + CONFIG_OPTIONS.len());
+    }
+}
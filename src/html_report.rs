@@ -0,0 +1,293 @@
+use std::{fmt::Write as _, io, path::Path};
+use chrono::Local;
+use crate::analysis::{self, DirTotals, DirUsage};
+use crate::file_kind::FileKind;
+use crate::format::{format_count, format_size, SizeUnits};
+use crate::scanner::FileEntry;
+use crate::storage::persist;
+use crate::type_view::TypeBreakdown;
+
+// Standalone HTML snapshot of a full scan (`scan --html`, `:export-html`), to share with
+// teammates or attach to a ticket: the largest files and directories, space by kind of file and
+// a treemap of what is directly below the root. It is built from the same aggregations as the
+// TUI views and needs nothing but a browser: the styles are inline and there are no scripts.
+
+/// Files and directories listed in each table.
+const TOP: usize = 25;
+
+/// Entries of the root drawn in the treemap; the rest share one more tile.
+const TREEMAP_TILES: usize = 40;
+
+/// Size of the treemap in SVG units; browsers scale it to the page width.
+const TREEMAP_WIDTH: f64 = 1000.0;
+const TREEMAP_HEIGHT: f64 = 500.0;
+
+/// What the snapshot describes.
+pub struct Snapshot<'a> {
+    /// What was scanned, e.g. the device name
+    pub title: &'a str,
+    pub root: &'a str,
+    pub files: &'a [FileEntry],
+    /// Directory totals of a scan that kept only its largest files, which `files` can't add up
+    pub totals: Option<&'a DirTotals>,
+    pub units: SizeUnits,
+}
+
+impl Snapshot<'_> {
+    /// The snapshot as a complete HTML page.
+    pub fn render(&self) -> String {
+        let root = Path::new(self.root);
+        let computed;
+        let totals = match self.totals {
+            Some(totals) => totals,
+            None => {
+                computed = DirTotals::of(root, self.files);
+                &computed
+            }
+        };
+        let total = totals.get(self.root).unwrap_or_else(|| analysis::DirTotal {
+            size: self.files.iter().map(|file| file.size).sum(),
+            file_count: self.files.len(),
+        });
+        let size = |bytes| escape(&format_size(bytes, self.units));
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title} – lazysmg scan</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">{root} · {size} in {count} files · scanned {date} by lazysmg {version}</p>\n",
+            title = escape(self.title),
+            root = escape(self.root),
+            size = size(total.size),
+            count = format_count(total.file_count as u64),
+            date = Local::now().format("%Y-%m-%d %H:%M"),
+            version = env!("CARGO_PKG_VERSION"),
+        );
+        if self.totals.is_some() {
+            html.push_str("<p class=\"meta\">The scan kept only its largest files; directory sizes count every file.</p>\n");
+        }
+
+        html.push_str("<h2>Treemap</h2>\n");
+        html.push_str(&self.treemap(&totals.usage(self.files, root)));
+
+        html.push_str("<h2>Largest directories</h2>\n<table>\n<tr><th>Directory</th><th class=\"num\">Size</th><th class=\"num\">Files</th></tr>\n");
+        for (dir, dir_total) in totals.largest(root, TOP) {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape(&dir),
+                size(dir_total.size),
+                format_count(dir_total.file_count as u64)
+            );
+        }
+        html.push_str("</table>\n");
+
+        let breakdown = TypeBreakdown::new(self.files);
+        html.push_str("<h2>By kind</h2>\n<table>\n<tr><th>Kind</th><th class=\"num\">Size</th><th class=\"num\">Files</th><th></th></tr>\n");
+        for kind in &breakdown.kinds {
+            let share = if breakdown.total == 0 { 0.0 } else { kind.size as f64 * 100.0 / breakdown.total as f64 };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"bar\"><span style=\"width:{:.1}%;background:{}\"></span></td></tr>",
+                kind.kind.label(),
+                size(kind.size),
+                format_count(kind.file_count as u64),
+                share,
+                color(kind.kind)
+            );
+        }
+        html.push_str("</table>\n");
+
+        let mut largest: Vec<&FileEntry> = self.files.iter().collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        html.push_str("<h2>Largest files</h2>\n<table>\n<tr><th>File</th><th class=\"num\">Size</th></tr>\n");
+        for file in largest.into_iter().take(TOP) {
+            let _ = writeln!(html, "<tr><td>{}</td><td class=\"num\">{}</td></tr>", escape(&file.path), size(file.size));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// SVG treemap of `children`, the entries directly below the root, with their size in the
+    /// tooltip of each tile.
+    fn treemap(&self, children: &[DirUsage]) -> String {
+        // Empty entries take no room, so they neither get a tile nor count as more
+        let shown: Vec<&DirUsage> = children.iter().filter(|child| child.size > 0).collect();
+        let mut tiles: Vec<(String, u64, FileKind)> = shown
+            .iter()
+            .take(TREEMAP_TILES)
+            .map(|child| (child.name.clone(), child.size, FileKind::of(&child.path, child.is_dir)))
+            .collect();
+        let rest: u64 = shown.iter().skip(TREEMAP_TILES).map(|child| child.size).sum();
+        if rest > 0 {
+            tiles.push((format!("{} more", shown.len() - TREEMAP_TILES), rest, FileKind::Other));
+        }
+        let sizes: Vec<u64> = tiles.iter().map(|(_, size, _)| *size).collect();
+        let mut rects = Vec::new();
+        layout(&sizes, Rect { x: 0.0, y: 0.0, width: TREEMAP_WIDTH, height: TREEMAP_HEIGHT }, &mut rects);
+
+        let mut svg = format!("<svg class=\"treemap\" viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">\n", TREEMAP_WIDTH, TREEMAP_HEIGHT);
+        for ((name, size, kind), rect) in tiles.iter().zip(rects) {
+            let _ = write!(
+                svg,
+                "<g><title>{name} – {size}</title><rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                color(*kind),
+                name = escape(name),
+                size = escape(&format_size(*size, self.units)),
+            );
+            // Only tiles wide and tall enough for a line of text get a label
+            if rect.width > 60.0 && rect.height > 18.0 {
+                let _ = write!(svg, "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>", rect.x + 4.0, rect.y + 14.0, escape(name));
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Write the snapshot to `path`, replacing it atomically.
+pub fn write(path: &Path, snapshot: &Snapshot) -> io::Result<()> {
+    persist::write_atomic(path, snapshot.render().as_bytes())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Lay out tiles of `sizes`, largest first, in `area`: split them in two halves of about the
+/// same total, side by side along the longer edge, and each half again. Tiles come out in the
+/// order of `sizes`.
+fn layout(sizes: &[u64], area: Rect, rects: &mut Vec<Rect>) {
+    let total: u64 = sizes.iter().sum();
+    if sizes.len() <= 1 || total == 0 {
+        rects.extend(sizes.iter().map(|_| area));
+        return;
+    }
+    let mut first = 0;
+    let mut split = 1;
+    for (i, size) in sizes.iter().enumerate().take(sizes.len() - 1) {
+        first += size;
+        split = i + 1;
+        if first * 2 >= total {
+            break;
+        }
+    }
+    let share = first as f64 / total as f64;
+    let (a, b) = if area.width >= area.height {
+        let width = area.width * share;
+        (Rect { width, ..area }, Rect { x: area.x + width, width: area.width - width, ..area })
+    } else {
+        let height = area.height * share;
+        (Rect { height, ..area }, Rect { y: area.y + height, height: area.height - height, ..area })
+    };
+    layout(&sizes[..split], a, rects);
+    layout(&sizes[split..], b, rects);
+}
+
+/// Fill color of a kind of file, in the treemap and the bars of the breakdown.
+fn color(kind: FileKind) -> &'static str {
+    match kind {
+        FileKind::Directory => "#5b8def",
+        FileKind::Symlink => "#9aa5b1",
+        FileKind::Image => "#e0a526",
+        FileKind::Video => "#d9534f",
+        FileKind::Audio => "#a66fd6",
+        FileKind::Archive => "#8d6e63",
+        FileKind::Code => "#3fae7a",
+        FileKind::Document => "#2bb3c0",
+        FileKind::Other => "#b0b7c0",
+    }
+}
+
+/// `text` with the characters HTML gives a meaning escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "\
+body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;margin:2em auto;max-width:1100px;padding:0 1em;color:#222}\
+.meta{color:#666}\
+table{border-collapse:collapse;width:100%}\
+th,td{text-align:left;padding:.25em .5em;border-bottom:1px solid #eee;word-break:break-all}\
+.num{text-align:right;white-space:nowrap;word-break:normal}\
+.bar{width:30%}\
+.bar span{display:block;height:.8em}\
+.treemap{width:100%;height:auto}\
+.treemap rect{stroke:#fff;stroke-width:1}\
+.treemap text{font-size:12px;fill:#fff}";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> FileEntry {
+        FileEntry { name: path.rsplit('/').next().unwrap().to_string(), path: path.to_string(), size, link_target: None, allocated_size: None }
+    }
+
+    #[test]
+    fn treemap_tiles_fill_the_area_in_proportion() {
+        let area = Rect { x: 0.0, y: 0.0, width: 100.0, height: 60.0 };
+        let mut rects = Vec::new();
+        layout(&[50, 30, 20], area, &mut rects);
+        // Side by side across the wide area, then stacked in the tall right half
+        assert_eq!(
+            rects,
+            [
+                Rect { x: 0.0, y: 0.0, width: 50.0, height: 60.0 },
+                Rect { x: 50.0, y: 0.0, width: 50.0, height: 36.0 },
+                Rect { x: 50.0, y: 36.0, width: 50.0, height: 24.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_lists_the_largest_and_escapes_names() {
+        let files = [file("/v/a/<big>.mov", 300), file("/v/a/b.txt", 10), file("/v/c.zip", 100)];
+        let snapshot = Snapshot { title: "Disk & Co", root: "/v", files: &files, totals: None, units: SizeUnits::default() };
+        let html = snapshot.render();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Disk &amp; Co</h1>"));
+        assert!(html.contains("/v/a/&lt;big&gt;.mov"));
+        assert!(!html.contains("<big>"));
+        assert!(!html.contains("<script"));
+        // Largest files first
+        assert!(html.find("&lt;big&gt;.mov</td>").unwrap() < html.find("/v/c.zip</td>").unwrap());
+        assert_eq!(html.matches("<rect").count(), 2);
+    }
+
+    #[test]
+    fn treemap_leaves_out_empty_entries() {
+        let usage = |i: usize, size: u64| DirUsage { name: format!("f{}", i), path: format!("/v/f{}", i), size, file_count: 1, is_dir: false };
+        // Empty files among the first ones don't push others into the rest
+        let mut children: Vec<DirUsage> = (0..5).map(|i| usage(i, 0)).collect();
+        children.extend((5..TREEMAP_TILES + 8).map(|i| usage(i, 10)));
+        let snapshot = Snapshot { title: "", root: "/v", files: &[], totals: None, units: SizeUnits::default() };
+        let svg = snapshot.treemap(&children);
+        assert_eq!(svg.matches("<rect").count(), TREEMAP_TILES + 1);
+        assert!(svg.contains("<title>3 more – "), "{}", svg);
+        assert!(!svg.contains("<title>f0 "));
+
+        let svg = snapshot.treemap(&children[..TREEMAP_TILES]);
+        assert_eq!(svg.matches("<rect").count(), TREEMAP_TILES - 5);
+        assert!(!svg.contains("more"));
+    }
+}
@@ -1,6 +1,31 @@
+pub mod analysis;
+pub mod clock;
+pub mod filesystem;
+pub mod fuzzy;
+pub mod format;
+#[cfg(feature = "platform")]
+pub mod api;
+#[cfg(feature = "platform")]
 pub mod platform;
+#[cfg(feature = "platform")]
 pub mod storage;
+#[cfg(feature = "platform")]
 pub mod scanner;
+#[cfg(feature = "tui")]
+pub mod config;
+#[cfg(feature = "tui")]
+pub mod theme;
 
+pub use analysis::{AgeFilter, FileEntry};
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use filesystem::{FileInfo, FileSystem, LocalFileSystem, MemoryFileSystem};
 // Re-export the scanner module for use in other modules
-pub use scanner::{FileEntry, ScanProgressMessage};
+#[cfg(feature = "platform")]
+pub use scanner::ScanProgressMessage;
+// The simple entry points for using lazysmg as a library, see `examples/`
+#[cfg(feature = "platform")]
+pub use api::{devices, top_files};
+#[cfg(feature = "junk")]
+pub use api::{junk_report, junk_report_with, JunkReport};
+#[cfg(feature = "platform")]
+pub use platform::macos::{distinct_space, StorageDevice};
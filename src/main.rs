@@ -1,60 +1,114 @@
 mod ui;
+mod announce;
+mod cli;
+mod config;
+mod diff;
+mod dir_view;
+#[cfg(feature = "duplicates")]
+mod duplicates;
 mod event_handler;
+mod export;
+mod file_kind;
+mod format;
+mod handlers;
+mod help;
+mod html_report;
+mod fuzzy;
+mod goal;
+mod mode;
+mod nav_history;
+mod operations;
+#[cfg(feature = "preview")]
+mod preview;
+mod replay;
+mod safety;
+#[cfg(feature = "junk")]
+mod report;
 mod platform;
 mod scanner;
+mod session;
 mod storage; // if needed
+mod tabs;
+mod tasks;
+mod type_view;
 
+// Shared with the library as is, rather than compiled again as a module of the binary
+use lazysmg::analysis::{self, DuplicateGroup, DuplicatePlan, FlatRate, JunkSuggestion, PathChange, SizeDeltas, StaleFile};
+use lazysmg::theme::{self, Theme};
+#[cfg(feature = "junk")]
+use lazysmg::clock;
+use lazysmg::filesystem::{self, FileSystem};
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
-    sync::mpsc,
-    time::Duration,
+    io::Write,
+    path::Path,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant, SystemTime},
 };
 use crossterm::{
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{DisableMouseCapture, EnableMouseCapture},
+    cursor::MoveTo,
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal, TerminalOptions, Viewport};
 use ui::draw_app;
 use event_handler::process_event;
+use platform::{dev_artifacts, last_used, trash};
+#[cfg(feature = "net")]
+use platform::update_check;
+use platform::dev_artifacts::DevProject;
+use platform::xcode_cleaner::{self, XcodeItem};
+use platform::docker::DockerUsage;
+use platform::browser_profiles::{self, Browser, BrowserProfile};
 use platform::macos::{detect_storage_devices, StorageDevice};
-use scanner::{FileEntry, list_directory, ScanProgressMessage};
+use scanner::{BrokenLink, DirTotals, FileEntry, JunkCategorySummary, ScanError, list_directory, ScanPriority, ScanProgressMessage};
+use replay::{RecordedEvent, Recorder};
+use config::{Config, LayoutConfig, SortOrder, StartupView};
+use operations::{FileOpOutcome, OperationQueue, Revert};
+use storage::{device_guard, file_ops, scan_cache};
+use storage::file_ops::{ArchiveFormat, FileOpJob, FolderDeleteJob};
+use storage::scan_history::{self, ScanRecord};
+use storage::deletion_impact::DeletionImpact;
+use storage::space_check::{SpaceCheck, SpaceNote};
+use diff::OverwritePreview;
+use dir_view::DirView;
+use nav_history::NavHistory;
+use type_view::TypeBreakdown;
+use session::SessionSummary;
+use tabs::Tab;
+use tasks::Tasks;
+use handlers::HandlerRegistry;
+pub use mode::{AppMode, FileOperation, ModeMachine};
+
+/// Least time between two redraws, so a busy scan redraws at most ten times a second.
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the spinner of a running listing or scan advances.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(200);
+/// Longest wait for input while nothing is running; device updates are picked up at this pace.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Which panel is focused.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum PanelFocus {
+    #[default]
     Left,
     Right,
 }
 
-/// Application mode. We keep only Normal (with a possible scanning flag).
-#[derive(Debug, Clone)]
-pub enum AppMode {
-    Normal,
-    ConfirmEject(usize),
-    Ejected(String),
-    Scanning { device_index: usize, spinner_index: usize },
-    FullScan { device_index: usize, spinner_index: usize },
-    ConfirmFileOp { 
-        op_type: FileOperation, 
-        file_index: usize,
-        target_path: Option<String> // For copy/move operations
-    },
-}
-
-#[derive(Debug, Clone)]
-pub enum FileOperation {
-    Copy,
-    Move,
-    Delete,
-}
-
 /// Different scanning modes for the application
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum ScanMode {
     /// Regular full scan of a device (external or ejectable)
+    #[default]
     FullScan,
     /// Junk scan mode (system storage only)
     JunkScan,
+    /// Search for developer build artifacts (node_modules, target, ...)
+    DevArtifacts,
+    /// Xcode data under ~/Library/Developer and simulator runtimes
+    Xcode,
 }
 
 /// Summary of a folder containing junk files
@@ -65,14 +119,63 @@ pub struct FolderSummary {
     pub file_count: usize,
 }
 
+/// Throughput is measured over this much of the most recent scanning.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+/// Least time between two throughput samples.
+const THROUGHPUT_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Largest files of a full scan whose last opening is looked up.
+const LAST_OPENED_LOOKUPS: usize = 1000;
+
 /// Tracks progress during a full storage scan
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ScanProgress {
     pub total_bytes: u64,         // Total size of the storage device
     pub scanned_bytes: u64,       // Total bytes scanned so far
     pub files_processed: u64,     // Number of files processed
     pub in_progress: bool,        // Whether a full scan is in progress
     pub current_file: Option<String>, // Currently being processed file
+    samples: VecDeque<(Instant, u64, u64)>, // (time, bytes, files) over the last THROUGHPUT_WINDOW, oldest first
+}
+
+impl ScanProgress {
+    /// Progress of a scan starting now, over `total_bytes` (0 when not known up front).
+    pub fn start(total_bytes: u64) -> ScanProgress {
+        ScanProgress {
+            total_bytes,
+            in_progress: true,
+            samples: VecDeque::from([(Instant::now(), 0, 0)]),
+            ..ScanProgress::default()
+        }
+    }
+
+    /// Note how far the scan got, for the throughput; called after taking in progress messages.
+    pub fn sample(&mut self) {
+        let now = Instant::now();
+        if self.samples.back().is_some_and(|(time, _, _)| now.duration_since(*time) < THROUGHPUT_SAMPLE_INTERVAL) {
+            return;
+        }
+        self.samples.push_back((now, self.scanned_bytes, self.files_processed));
+        // The oldest sample kept is the last one at least a full window old
+        while self.samples.get(1).is_some_and(|(time, _, _)| now.duration_since(*time) >= THROUGHPUT_WINDOW) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes and files per second over the last `THROUGHPUT_WINDOW`, once it has been measured
+    /// for a second.
+    pub fn throughput(&self) -> Option<(f64, f64)> {
+        let ((start, start_bytes, start_files), (end, end_bytes, end_files)) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = end.duration_since(*start).as_secs_f64();
+        (elapsed >= 1.0).then(|| (end_bytes.saturating_sub(*start_bytes) as f64 / elapsed, end_files.saturating_sub(*start_files) as f64 / elapsed))
+    }
+
+    /// Time the rest of the scan takes at the recent throughput, when the total is known.
+    pub fn remaining(&self) -> Option<Duration> {
+        let (bytes_per_sec, _) = self.throughput()?;
+        let left = self.total_bytes.checked_sub(self.scanned_bytes).filter(|left| *left > 0)?;
+        (bytes_per_sec > 0.0).then(|| Duration::from_secs_f64(left as f64 / bytes_per_sec))
+    }
 }
 
 /// Main application state.
@@ -81,46 +184,903 @@ pub struct App {
     pub devices: Vec<StorageDevice>,
     pub selected: usize,
     pub file_entries: Option<Vec<FileEntry>>, // current directory listing for the selected device
+    pub browse_dir: Option<String>,            // subdirectory of the device being listed; None for its root
+    pub nav_history: HashMap<String, NavHistory>, // directories listed before and after the current one, by device mount point
     pub scanning: bool,                        // whether a directory listing is in progress
     pub focus: PanelFocus,
     pub full_scan_results: Option<Vec<FileEntry>>, // results from a full device scan
+    pub full_scan_totals: Option<DirTotals>,       // directory totals of a full scan that kept only its largest files
+    pub scan_errors: Vec<ScanError>,               // what the last full or junk scan couldn't read, shown by :errors
     pub scan_progress: ScanProgress,               // tracks progress during full scan
     pub selected_file_index: usize,                // currently selected file in the list
     pub clipboard: Option<(String, FileOperation)>, // stores path and operation type for copy/move
     pub file_list_offset: usize,                   // scrolling offset for file list
-    pub device_results: std::collections::HashMap<String, Vec<FileEntry>>, // results per device
+    pub device_results: std::collections::HashMap<String, (Vec<FileEntry>, Option<DirTotals>)>, // scan results of the devices not selected, with their totals
     pub show_help: bool,                          // whether to show the help overlay
+    pub help_query: String,                       // search typed into the help overlay
+    pub confirm_input: String,                    // name typed to confirm deleting or moving what isn't user data
     pub scan_mode: ScanMode,                      // current scan mode
     pub folder_summaries: Option<Vec<FolderSummary>>, // folder summaries for junk scan
     pub selected_folder_index: usize,             // selected folder in junk scan view
     pub folder_view_mode: bool,                   // whether we're viewing folders or files
+    pub pending_folder_view: bool,                // switch to folder view when the running junk scan completes
+    pub config: Config,                           // user configuration (keys, colors, display, scan)
+    pub scan_started_at: Option<Instant>,         // when the running full/junk scan was started
+    pub scan_history: Vec<ScanRecord>,            // completed scans, oldest first
+    pub session: SessionSummary,                  // scans and cleanups of this session, printed on exit
+    pub notification: Option<String>,             // summary of the last completed scan
+    pub delete_preview: Option<DeletionImpact>,   // what a pending delete would free, shown in its confirmation
+    pub overwrite_preview: Option<OverwritePreview>, // the file a pending copy or move would replace, shown in its confirmation
+    pub delete_holders: Option<(String, Option<Vec<String>>)>, // processes that have a file about to be deleted open, if they could be found
+    pub delete_holders_rx: Option<mpsc::Receiver<(String, Option<Vec<String>>)>>, // open file lookup in progress
+    pub erase_warning: Option<(String, Option<String>)>, // why overwriting may not reach the old contents of a file about to be securely deleted
+    pub space_checks: Vec<SpaceCheck>,            // free space before queued deletes, to tell whether they freed it
+    pub space_note_tx: mpsc::Sender<SpaceNote>,   // why finished deletes didn't free their space
+    pub space_note_rx: mpsc::Receiver<SpaceNote>,
+    pub junk_categories: Option<Vec<JunkCategorySummary>>, // per-category totals of the last junk scan
+    pub excluded_categories: HashSet<String>,     // ids of junk categories left out of the results
+    pub junk_all_files: Option<Vec<FileEntry>>,   // junk scan results before excluding categories
+    pub junk_all_folders: Option<Vec<FolderSummary>>, // junk folder summaries before excluding categories
+    pub category_view_mode: bool,                 // whether we're viewing junk categories
+    pub selected_category_index: usize,           // selected category in the category view
+    pub dev_projects: Option<Vec<DevProject>>,    // projects with build artifacts from the last dev scan
+    pub dev_scan_root: Option<String>,            // directory the last dev scan searched
+    pub excluded_projects: HashSet<String>,       // paths of projects left out of a bulk clean
+    pub selected_project_index: usize,            // selected project in the build artifact view
+    pub xcode_items: Option<Vec<XcodeItem>>,      // Xcode data found by the last Xcode scan
+    pub xcode_selected: HashSet<String>,          // paths of Xcode items selected for cleaning
+    pub selected_xcode_index: usize,              // highlighted item in the Xcode view
+    pub docker_usage: Option<Vec<DockerUsage>>,   // Docker disk usage from the last junk scan, if Docker is running
+    pub browser_profiles: Vec<BrowserProfile>,    // browser profiles found by the last junk scan
+    pub running_browsers: Vec<Browser>,           // browsers running when the browser cache dialog was opened
+    pub folder_delete: Option<Arc<FolderDeleteJob>>, // running junk folder deletion, to pause or stop it
+    pub dirty: bool,                              // whether the screen is out of date and must be redrawn
+    pub on_battery: bool,                         // whether the machine runs on battery, which throttles scans
+    pub operations: OperationQueue,               // copies, moves and deletes queued, running or recently finished
+    pub tasks: Tasks,                             // ejects, cleans, renames and other actions running in the background
+    pub scan_priority: ScanPriority,              // directory the running full scan walks next, if not done yet
+    pub scan_depth: Option<usize>,                // levels of directories full scans walk, from the config or :depth
+    pub full_scan_depth: Option<usize>,           // levels the running or shown full scan walked; deeper files aren't counted
+    pub full_scan_roots: Option<Vec<String>>,     // directories the running or shown scan of the system volume was limited to
+    pub scanned_dir_sizes: HashMap<String, u64>,  // top-level directories the running full scan has finished, with their size
+    pub largest_so_far: Option<Vec<FileEntry>>,   // largest files the running full scan has met, as it reports them
+    pub show_largest_so_far: bool,                // whether they are shown during a full scan rather than the listing
+    pub update_check: Option<UpdateCheck>,        // update check running in the background
+    pub update_instructions: Option<String>,      // how to upgrade to a newer release, printed on exit
+    pub viewport: Option<Rect>,                   // rows the UI is drawn in below the prompt with --inline; the whole screen otherwise
+    pub preview_pane: bool,                       // whether the preview pane is shown next to the file list
+    pub layout: LayoutConfig,                     // panel sizes, resized with the keys and saved to the config file
+    pub show_cost: bool,                          // whether sizes come with what storing them costs per month
+    pub show_allocated: bool,                     // whether listings show the space files take up on disk rather than their size
+    pub dir_view: Option<DirView>,                // directory sizes of the full scan, shown instead of its files
+    pub type_breakdown: Option<TypeBreakdown>,    // space by file type of the full scan, shown in the bottom panel
+    pub largest_dirs: Vec<(String, analysis::DirTotal)>, // leaderboard of the largest directories of the full scan
+    pub size_deltas: Option<SizeDeltas>,          // how much entries changed since the results a rescan replaced
+    pub rescan_baseline: Option<Vec<FileEntry>>,  // results a running full scan replaces, compared with its own
+    pub sort_by_delta: bool,                      // whether full scan results are ordered by how much they changed
+    pub stale_files: Vec<StaleFile>,              // large files of the full scan unused for a while, from :stale
+    pub last_opened: HashMap<String, SystemTime>, // when the large files of the full scan were last opened, where known
+    pub broken_links: Vec<BrokenLink>,            // symlinks of the selected device whose targets are gone, from :broken-links
+    pub scan_changes: Vec<PathChange>,            // what changed between the last two cached scans of the device, from :changes
+    pub duplicate_groups: Vec<DuplicateGroup>,    // files of the full scan with the same content, from :duplicates
+    pub duplicate_modified: HashMap<String, SystemTime>, // when each of the duplicates was last modified
+    pub duplicate_plan: Option<DuplicatePlan>,    // copies a bulk resolution of the duplicates would delete, to confirm
+    pub goal: Option<goal::Goal>,                 // free space goal set with :goal, with the candidates to reach it
+    pub theme: Theme,                             // colors of the UI, resolved from the config file
+    pub handlers: HandlerRegistry,                // preview renderers and open commands by extension or MIME type
+    pub junk_suggestions: Vec<JunkSuggestion>,    // directories that look like junk but aren't junk locations yet
+    pub junk_suggestions_rx: Option<mpsc::Receiver<Vec<JunkSuggestion>>>, // suggestions being worked out in the background
+    pub last_full_scan: Option<(String, Vec<FileEntry>)>, // mount point and files of the last full scan, to find churn
+    pub tabs: Vec<Tab>,                           // tabs of the file panel; the fields above hold what the current one shows
+    pub active_tab: usize,                        // index of the current tab
+    pub tab_switched: bool,                       // another tab was shown, whose device isn't newly selected
+    #[cfg(feature = "preview")]
+    pub preview: Option<preview::Preview>,        // preview of the selected entry, once loaded
+    #[cfg(feature = "preview")]
+    pub preview_load: Option<(String, mpsc::Receiver<preview::Preview>)>, // entry whose preview is loading in the background
+}
+
+/// An update check running in the background.
+#[derive(Debug)]
+pub struct UpdateCheck {
+    /// Started with `:update`, which reports every outcome; the startup check only reports a
+    /// newer release
+    pub interactive: bool,
+    /// Upgrade instructions if there is a newer release, or why the check failed
+    pub result_rx: mpsc::Receiver<Result<Option<String>, String>>,
 }
 
 impl App {
-    pub fn new(devices: Vec<StorageDevice>) -> App {
-        App {
+    pub fn new(devices: Vec<StorageDevice>, config: Config) -> App {
+        let layout = config.layout.clamped();
+        let theme = config.colors.theme();
+        let handlers = HandlerRegistry::new(&config.handlers);
+        let scan_depth = config.scan.max_depth;
+        let (space_note_tx, space_note_rx) = mpsc::channel();
+        let mut app = App {
             devices,
             selected: 0,
             file_entries: None,
+            browse_dir: None,
+            nav_history: HashMap::new(),
             scanning: false,
             focus: PanelFocus::Left,
             full_scan_results: None,
-            scan_progress: ScanProgress {
-                total_bytes: 0,
-                scanned_bytes: 0,
-                files_processed: 0,
-                in_progress: false,
-                current_file: None,
-            },
+            full_scan_totals: None,
+            scan_errors: Vec::new(),
+            scan_progress: ScanProgress::default(),
             selected_file_index: 0,
             clipboard: None,
             file_list_offset: 0,
             device_results: std::collections::HashMap::new(),
             show_help: false,
+            help_query: String::new(),
+            confirm_input: String::new(),
             scan_mode: ScanMode::FullScan,
             folder_summaries: None,
             selected_folder_index: 0,
             folder_view_mode: false,
+            pending_folder_view: false,
+            config,
+            scan_started_at: None,
+            scan_history: Vec::new(),
+            session: SessionSummary::default(),
+            notification: None,
+            delete_preview: None,
+            overwrite_preview: None,
+            delete_holders: None,
+            erase_warning: None,
+            delete_holders_rx: None,
+            space_checks: Vec::new(),
+            space_note_tx,
+            space_note_rx,
+            junk_categories: None,
+            excluded_categories: HashSet::new(),
+            junk_all_files: None,
+            junk_all_folders: None,
+            category_view_mode: false,
+            selected_category_index: 0,
+            dev_projects: None,
+            dev_scan_root: None,
+            excluded_projects: HashSet::new(),
+            selected_project_index: 0,
+            xcode_items: None,
+            xcode_selected: HashSet::new(),
+            selected_xcode_index: 0,
+            docker_usage: None,
+            browser_profiles: Vec::new(),
+            running_browsers: Vec::new(),
+            folder_delete: None,
+            dirty: true,
+            on_battery: false,
+            operations: OperationQueue::default(),
+            tasks: Tasks::default(),
+            scan_priority: ScanPriority::default(),
+            scan_depth,
+            full_scan_depth: None,
+            full_scan_roots: None,
+            scanned_dir_sizes: HashMap::new(),
+            largest_so_far: None,
+            show_largest_so_far: true,
+            update_check: None,
+            update_instructions: None,
+            viewport: None,
+            preview_pane: false,
+            layout,
+            show_cost: false,
+            show_allocated: false,
+            dir_view: None,
+            type_breakdown: None,
+            largest_dirs: Vec::new(),
+            size_deltas: None,
+            rescan_baseline: None,
+            sort_by_delta: false,
+            stale_files: Vec::new(),
+            last_opened: HashMap::new(),
+            broken_links: Vec::new(),
+            scan_changes: Vec::new(),
+            duplicate_groups: Vec::new(),
+            duplicate_modified: HashMap::new(),
+            duplicate_plan: None,
+            goal: None,
+            theme,
+            handlers,
+            junk_suggestions: Vec::new(),
+            junk_suggestions_rx: None,
+            last_full_scan: None,
+            tabs: Vec::new(),
+            active_tab: 0,
+            tab_switched: false,
+            #[cfg(feature = "preview")]
+            preview: None,
+            #[cfg(feature = "preview")]
+            preview_load: None,
+        };
+        app.tabs.push(Tab::capture(&app));
+        app
+    }
+
+    /// Entry the preview pane shows: the selected file or directory of a listing or scan, not
+    /// the junk folders, categories, projects or Xcode data.
+    pub fn previewed_path(&self) -> Option<String> {
+        if !self.preview_pane || self.folder_view_mode || self.category_view_mode || self.dev_view_active() || self.xcode_view_active() {
+            return None;
+        }
+        self.get_selected_file_entry().map(|file| file.path.clone())
+    }
+
+    /// Keep the preview pane on the selected entry: start loading the preview of a newly
+    /// selected one in the background, and pick it up once loaded. Previews of entries that
+    /// were passed over while scrolling are dropped.
+    #[cfg(feature = "preview")]
+    pub fn update_preview(&mut self) {
+        let selected = self.previewed_path();
+        let shown = self.preview_load.as_ref().map(|(path, _)| path).or(self.preview.as_ref().map(|preview| &preview.path));
+        if selected.as_ref() != shown {
+            self.preview = None;
+            self.preview_load = selected.map(|path| {
+                let (preview_tx, preview_rx) = mpsc::channel();
+                let target = path.clone();
+                let handler = self.handlers.preview_for(&path);
+                tokio::task::spawn_blocking(move || {
+                    // Ignore errors - the selection may have moved on
+                    let _ = preview_tx.send(preview::load(&target, &handler));
+                });
+                (path, preview_rx)
+            });
+            self.dirty = true;
+        }
+        if let Some((_, preview_rx)) = &self.preview_load
+            && let Ok(preview) = preview_rx.try_recv()
+        {
+            self.preview = Some(preview);
+            self.preview_load = None;
+            self.dirty = true;
+        }
+    }
+
+    /// Whether a preview is loading, so the loop checks back soon.
+    pub fn preview_loading(&self) -> bool {
+        #[cfg(feature = "preview")]
+        return self.preview_load.is_some();
+        #[cfg(not(feature = "preview"))]
+        false
+    }
+
+    /// Look for directories of a finished full scan of the selected device that look like junk
+    /// but aren't junk locations yet, in the background. The device's previous full scan of this
+    /// session, if any, tells which directories churn.
+    #[cfg(feature = "junk")]
+    pub fn start_junk_suggestions(&mut self, results: &[FileEntry]) {
+        let Some(device) = self.devices.get(self.selected) else {
+            return;
+        };
+        let previous = self.last_full_scan.take().filter(|(root, _)| *root == device.mount_point).map(|(_, files)| files);
+        self.last_full_scan = Some((device.mount_point.clone(), results.to_vec()));
+        let results = results.to_vec();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let known = platform::junk_scanner::junk_roots();
+            // Ignore errors - the app may have been closed
+            let _ = tx.send(analysis::suggest_junk_dirs(&results, previous.as_deref(), &known));
+        });
+        self.junk_suggestions_rx = Some(rx);
+    }
+
+    /// Look up when the large files of a completed full scan were last opened, in the
+    /// background, for the column of the scan results.
+    pub fn start_last_opened_lookup(&mut self, results: &[FileEntry]) {
+        self.last_opened.clear();
+        let min_size = self.config.stale.min_size_mb * 1024 * 1024;
+        let paths: Vec<String> = results.iter().filter(|file| file.size >= min_size).take(LAST_OPENED_LOOKUPS).map(|file| file.path.clone()).collect();
+        if paths.is_empty() {
+            return;
+        }
+        self.tasks.spawn(move || {
+            let found = last_used::last_opened(&paths);
+            Box::new(move |app: &mut App, _: &mut ModeMachine| app.last_opened.extend(found))
+        });
+    }
+
+    /// Queue deleting the confirmed `path`. The free space of the selected device is measured
+    /// first, in the background, to explain afterwards if the delete doesn't free the space its
+    /// confirmation showed.
+    pub fn queue_confirmed_delete(&mut self, path: String) {
+        let (Some(device), Some(impact)) = (self.devices.get(self.selected), self.delete_preview.as_ref().filter(|impact| impact.target == path)) else {
+            self.operations.enqueue(FileOperation::Delete, path, None);
+            return;
+        };
+        let holders = match &self.delete_holders {
+            Some((target, Some(holders))) if *target == path => holders.clone(),
+            _ => Vec::new(),
+        };
+        let (mount_point, freed) = (device.mount_point.clone(), impact.freed);
+        self.tasks.spawn(move || {
+            let space_check = SpaceCheck::before_delete(&path, &mount_point, freed, holders);
+            Box::new(move |app: &mut App, _: &mut ModeMachine| {
+                app.space_checks.extend(space_check);
+                app.operations.enqueue(FileOperation::Delete, path, None);
+            })
+        });
+    }
+
+    /// Add a delete that went through to the session summary: to the trash if `trashed`, or
+    /// freed. Its size is known from the delete's confirmation or a full scan, if at all.
+    pub fn record_delete(&mut self, path: &str, trashed: bool) {
+        let size = match self.space_checks.iter().find(|check| check.path == path) {
+            Some(check) => check.expected,
+            None => self.full_scan_results.as_deref().map_or(0, |entries| analysis::size_under(entries, Path::new(path))),
+        };
+        if trashed {
+            self.session.trashed.0 += 1;
+            self.session.trashed.1 += size;
+        } else {
+            self.session.add_freed(format!("Deleted {}", path), size);
+        }
+    }
+
+    /// Check in the background whether the delete of `path` (into the trash if `trashed`)
+    /// freed its space; a note arrives through `space_note_rx` if it didn't.
+    pub fn start_space_check(&mut self, path: &str, trashed: bool) {
+        let Some(index) = self.space_checks.iter().position(|check| check.path == path) else {
+            return;
+        };
+        let check = self.space_checks.remove(index);
+        let units = self.config.display.size_units;
+        let empty_trash_key = self.config.keys.label(config::Action::EmptyTrash);
+        let tx = self.space_note_tx.clone();
+        std::thread::spawn(move || {
+            if let Some(note) = check.explain(trashed, units, &empty_trash_key) {
+                // Ignore errors - the app may have been closed
+                let _ = tx.send(note);
+            }
+        });
+    }
+
+    /// Look for a newer release in the background. The `:update` command (`interactive`)
+    /// skips the cached answer of the last day.
+    pub fn start_update_check(&mut self, interactive: bool) -> Result<(), &'static str> {
+        #[cfg(feature = "net")]
+        {
+            let (result_tx, result_rx) = mpsc::channel();
+            tokio::task::spawn_blocking(move || {
+                let result = update_check::latest_release(interactive)
+                    .map(|release| release.is_newer().then(|| release.upgrade_instructions()))
+                    .map_err(|e| e.to_string());
+                // Ignore errors - the app may have been closed
+                let _ = result_tx.send(result);
+            });
+            self.update_check = Some(UpdateCheck { interactive, result_rx });
+            Ok(())
+        }
+        #[cfg(not(feature = "net"))]
+        {
+            let _ = interactive;
+            Err("This build can't check for updates (feature `net`)")
+        }
+    }
+
+    /// Reorder entries according to the configured sort, if any.
+    pub fn sort_entries(&self, entries: &mut [FileEntry]) {
+        if let Some(order) = self.config.display.sort {
+            order.apply(entries);
+        }
+    }
+
+    /// Reorder the results of a full scan: by how much their size changed with `:sort delta`,
+    /// else by the configured sort or largest first.
+    pub fn sort_scan_results(&self, results: &mut [FileEntry]) {
+        match self.size_deltas.as_ref().filter(|_| self.sort_by_delta) {
+            Some(deltas) => deltas.sort(results, |entry| (entry.path.as_str(), entry.size)),
+            None => self.config.display.sort.unwrap_or(SortOrder::SizeDesc).apply(results),
+        }
+    }
+
+    /// Order the full scan results and the directory view by how much entries changed since
+    /// the previous scan, or back to the usual order.
+    pub fn set_sort_by_delta(&mut self, by_delta: bool) {
+        self.sort_by_delta = by_delta;
+        if let Some(mut results) = self.full_scan_results.take() {
+            self.sort_scan_results(&mut results);
+            self.full_scan_results = Some(results);
+        }
+        self.refresh_dir_view();
+    }
+
+    /// Keep what the scan that just completed couldn't read, and mention it in the notification
+    /// about the scan.
+    fn set_scan_errors(&mut self, errors: Vec<ScanError>) {
+        self.scan_errors = errors;
+        if self.scan_errors.is_empty() {
+            return;
+        }
+        let note = format!("{} paths couldn't be read, see :errors", self.scan_errors.len());
+        self.notification = Some(match self.notification.take() {
+            Some(summary) => format!("{}; {}", summary, note),
+            None => note,
+        });
+    }
+
+    /// Add the scan that just completed to the history (persisting it unless `persist` is false)
+    /// and show a summary, compared with the previous scan of the same target, as a notification.
+    pub fn record_completed_scan(&mut self, junk: bool, results: &[FileEntry], persist: bool) {
+        let (Some(started), Some(device)) = (self.scan_started_at.take(), self.devices.get(self.selected)) else {
+            return;
+        };
+        let duration = started.elapsed();
+        let mut record = ScanRecord::new(&device.name, &device.mount_point, junk, duration.as_millis() as u64, results);
+        // A scan that kept only its largest files knows its totals from the directory totals
+        if let Some(total) = self.full_scan_totals.as_ref().filter(|_| !junk).and_then(|totals| totals.get(&device.mount_point)) {
+            record.total_size = total.size;
+            record.file_count = total.file_count;
+        }
+        self.session.scans.push(record.clone());
+        if let Some(depth) = self.full_scan_depth.filter(|_| !junk) {
+            // Its totals leave out the deeper files, so it isn't compared with complete scans
+            self.notification = Some(format!(
+                "Scan of {} {} deep took {:.1}s: {} in {} files",
+                record.device,
+                format::format_depth(depth),
+                duration.as_secs_f64(),
+                format::format_size(record.total_size, self.config.display.size_units),
+                format::format_count(record.file_count as u64)
+            ));
+            return;
+        }
+        if let Some(roots) = self.full_scan_roots.as_ref().filter(|_| !junk) {
+            // Neither are the totals of a scan that left most of the system volume out
+            self.notification = Some(format!(
+                "Scan of {} ({} directories of the system volume) took {:.1}s: {} in {} files",
+                record.device,
+                roots.len(),
+                duration.as_secs_f64(),
+                format::format_size(record.total_size, self.config.display.size_units),
+                format::format_count(record.file_count as u64)
+            ));
+            return;
+        }
+
+        let units = self.config.display.size_units;
+        let mut summary = format!(
+            "{} scan of {} took {:.1}s: {} in {} files",
+            if junk { "Junk" } else { "Full" },
+            record.device,
+            duration.as_secs_f64(),
+            format::format_size(record.total_size, units),
+            format::format_count(record.file_count as u64)
+        );
+        if let Some(previous) = self.scan_history.iter().rev().find(|r| r.same_target(&record)) {
+            summary.push_str(&format!(
+                " (was {} on {})",
+                format::format_size(previous.total_size, units),
+                previous.finished_at.format("%Y-%m-%d")
+            ));
+        }
+
+        // A failed write only loses this entry from future sessions
+        if persist {
+            let _ = scan_history::append(&record);
+        }
+        self.scan_history.push(record);
+        self.notification = Some(summary);
+    }
+
+    /// Rebuild the junk file and folder views from the last junk scan, leaving out excluded categories.
+    pub fn apply_category_filter(&mut self) {
+        let (Some(files), Some(folders), Some(categories)) =
+            (&self.junk_all_files, &self.junk_all_folders, &self.junk_categories)
+        else {
+            return;
+        };
+        let included = |path: &str| {
+            !analysis::find_category(categories, path)
+                .is_some_and(|category| self.excluded_categories.contains(&category.id))
+        };
+        let files: Vec<FileEntry> = files.iter().filter(|file| included(&file.path)).cloned().collect();
+        let folders: Vec<FolderSummary> = folders.iter().filter(|folder| included(&folder.path)).cloned().collect();
+
+        self.full_scan_results = Some(files);
+        self.full_scan_totals = None;
+        self.folder_summaries = Some(folders);
+        self.selected_file_index = 0;
+        self.file_list_offset = 0;
+    }
+
+    /// Junk files of the folder at `folder_index` in the folder view (excluded categories left out).
+    pub fn folder_junk_files(&self, folder_index: usize) -> Option<(&FolderSummary, Vec<FileEntry>)> {
+        let folder = self.folder_summaries.as_ref()?.get(folder_index)?;
+        let files = self
+            .full_scan_results
+            .iter()
+            .flatten()
+            .filter(|file| Path::new(&file.path).parent() == Some(Path::new(&folder.path)))
+            .cloned()
+            .collect();
+        Some((folder, files))
+    }
+
+    /// Drop deleted junk files from the results, folder summaries and category totals.
+    pub fn remove_deleted_junk(&mut self, deleted: &[String]) {
+        let (Some(files), Some(folders), Some(categories)) =
+            (&mut self.junk_all_files, &mut self.junk_all_folders, &mut self.junk_categories)
+        else {
+            return;
+        };
+        let deleted: HashSet<&str> = deleted.iter().map(String::as_str).collect();
+        for file in files.iter().filter(|file| deleted.contains(file.path.as_str())) {
+            let parent = Path::new(&file.path).parent().map(|parent| parent.to_string_lossy().into_owned());
+            if let Some(folder) = folders.iter_mut().find(|folder| Some(&folder.path) == parent.as_ref()) {
+                folder.total_size = folder.total_size.saturating_sub(file.size);
+                folder.file_count = folder.file_count.saturating_sub(1);
+            }
+            if let Some(id) = analysis::find_category(categories, &file.path).map(|category| category.id.clone())
+                && let Some(category) = categories.iter_mut().find(|category| category.id == id)
+            {
+                category.total_size = category.total_size.saturating_sub(file.size);
+                category.file_count = category.file_count.saturating_sub(1);
+            }
+        }
+        files.retain(|file| !deleted.contains(file.path.as_str()));
+        folders.retain(|folder| folder.file_count > 0);
+        self.apply_category_filter();
+        let folder_count = self.folder_summaries.as_ref().map_or(0, Vec::len);
+        self.selected_folder_index = self.selected_folder_index.min(folder_count.saturating_sub(1));
+    }
+
+    /// Include or exclude the selected junk category.
+    pub fn toggle_selected_category(&mut self) {
+        let Some(category) = self.junk_categories.as_ref().and_then(|categories| categories.get(self.selected_category_index)) else {
+            return;
+        };
+        if !self.excluded_categories.remove(&category.id) {
+            self.excluded_categories.insert(category.id.clone());
+        }
+        self.apply_category_filter();
+    }
+
+    pub fn next_category(&mut self) {
+        let count = self.junk_categories.as_ref().map_or(0, Vec::len);
+        if self.selected_category_index + 1 < count {
+            self.selected_category_index += 1;
+        }
+    }
+
+    pub fn previous_category(&mut self) {
+        self.selected_category_index = self.selected_category_index.saturating_sub(1);
+    }
+
+    /// Whether the right panel shows the projects found by a dev scan.
+    pub fn dev_view_active(&self) -> bool {
+        self.scan_mode == ScanMode::DevArtifacts && self.dev_projects.is_some()
+    }
+
+    /// Projects whose artifacts a bulk clean would remove.
+    pub fn selected_projects(&self) -> Vec<&DevProject> {
+        self.dev_projects
+            .iter()
+            .flatten()
+            .filter(|project| !self.excluded_projects.contains(&project.path))
+            .collect()
+    }
+
+    /// Select or deselect the highlighted project for cleaning.
+    pub fn toggle_selected_project(&mut self) {
+        let Some(project) = self.dev_projects.as_ref().and_then(|projects| projects.get(self.selected_project_index)) else {
+            return;
+        };
+        if !self.excluded_projects.remove(&project.path) {
+            self.excluded_projects.insert(project.path.clone());
+        }
+    }
+
+    pub fn next_project(&mut self) {
+        let count = self.dev_projects.as_ref().map_or(0, Vec::len);
+        if self.selected_project_index + 1 < count {
+            self.selected_project_index += 1;
+        }
+    }
+
+    pub fn previous_project(&mut self) {
+        self.selected_project_index = self.selected_project_index.saturating_sub(1);
+    }
+
+    /// Remove the build artifacts of the selected projects in the background, then drop them
+    /// from the view and report in the result popup.
+    pub fn clean_selected_projects(&mut self) {
+        let artifacts: Vec<_> = self
+            .dev_projects
+            .iter()
+            .flatten()
+            .filter(|project| !self.excluded_projects.contains(&project.path))
+            .flat_map(|project| project.artifacts.clone())
+            .collect();
+        let units = self.config.display.size_units;
+        self.tasks.spawn(move || {
+            let mut freed = 0;
+            let mut cleaned = HashSet::new();
+            let mut failures = Vec::new();
+            for artifact in &artifacts {
+                match dev_artifacts::clean_artifact(artifact) {
+                    Ok(()) => {
+                        freed += artifact.size;
+                        cleaned.insert(artifact.path.clone());
+                    }
+                    Err(err) => failures.push(err.to_string()),
+                }
+            }
+            let mut summary = format!("Removed {} build artifact directories, freed {}", cleaned.len(), format::format_size(freed, units));
+            if let Some(first) = failures.first() {
+                summary.push_str(&format!("\n{} skipped: {}", failures.len(), first));
+            }
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                app.session.add_freed(format!("{} build artifact directories", cleaned.len()), freed);
+                if let Some(projects) = app.dev_projects.as_mut() {
+                    for project in projects.iter_mut() {
+                        project.artifacts.retain(|artifact| !cleaned.contains(&artifact.path));
+                    }
+                    projects.retain(|project| !project.artifacts.is_empty());
+                    app.selected_project_index = app.selected_project_index.min(projects.len().saturating_sub(1));
+                }
+                tasks::report(app, mode, summary);
+            })
+        });
+    }
+
+    /// Whether the right panel shows a directory listing, which can be browsed.
+    pub fn listing_active(&self) -> bool {
+        self.file_entries.is_some()
+            && self.full_scan_results.is_none()
+            && !self.scanning
+            && !self.folder_view_mode
+            && !self.category_view_mode
+            && !self.dev_view_active()
+            && !self.xcode_view_active()
+    }
+
+    /// Whether the right panel shows the largest files of the running full scan instead of the
+    /// listing, which stays browsable to pick the directory scanned next.
+    pub fn largest_so_far_shown(&self) -> bool {
+        self.show_largest_so_far && self.largest_so_far.is_some() && self.scan_progress.in_progress && self.scan_mode == ScanMode::FullScan
+    }
+
+    /// Paths of the segments of the breadcrumb path bar, from the device root down to the
+    /// directory being listed. Empty unless a subdirectory is listed.
+    pub fn breadcrumbs(&self) -> Vec<String> {
+        let (Some(dir), Some(device)) = (self.browse_dir.as_deref(), self.devices.get(self.selected)) else {
+            return Vec::new();
+        };
+        if self.full_scan_results.is_some() {
+            return Vec::new();
+        }
+        let root = Path::new(&device.mount_point);
+        let mut segments: Vec<String> = Path::new(dir)
+            .ancestors()
+            .take_while(|ancestor| ancestor.starts_with(root) && *ancestor != root)
+            .map(|ancestor| ancestor.to_string_lossy().into_owned())
+            .collect();
+        segments.push(device.mount_point.clone());
+        segments.reverse();
+        segments
+    }
+
+    /// Whether the right panel shows the items found by an Xcode scan.
+    pub fn xcode_view_active(&self) -> bool {
+        self.scan_mode == ScanMode::Xcode && self.xcode_items.is_some()
+    }
+
+    /// Xcode items a clean would remove.
+    pub fn selected_xcode_items(&self) -> Vec<&XcodeItem> {
+        self.xcode_items
+            .iter()
+            .flatten()
+            .filter(|item| self.xcode_selected.contains(&item.path))
+            .collect()
+    }
+
+    /// Select or deselect the highlighted Xcode item for cleaning.
+    pub fn toggle_selected_xcode_item(&mut self) {
+        let Some(item) = self.xcode_items.as_ref().and_then(|items| items.get(self.selected_xcode_index)) else {
+            return;
+        };
+        if !self.xcode_selected.remove(&item.path) {
+            self.xcode_selected.insert(item.path.clone());
+        }
+    }
+
+    pub fn next_xcode_item(&mut self) {
+        let count = self.xcode_items.as_ref().map_or(0, Vec::len);
+        if self.selected_xcode_index + 1 < count {
+            self.selected_xcode_index += 1;
+        }
+    }
+
+    pub fn previous_xcode_item(&mut self) {
+        self.selected_xcode_index = self.selected_xcode_index.saturating_sub(1);
+    }
+
+    /// Remove the selected Xcode items in the background, then drop them from the view and
+    /// report in the result popup.
+    pub fn clean_selected_xcode_items(&mut self) {
+        let items: Vec<XcodeItem> = self.selected_xcode_items().into_iter().cloned().collect();
+        let units = self.config.display.size_units;
+        self.tasks.spawn(move || {
+            let mut freed = 0;
+            let mut removed = HashSet::new();
+            let mut failures = Vec::new();
+            for item in &items {
+                match xcode_cleaner::remove_item(item) {
+                    Ok(()) => {
+                        freed += item.size;
+                        removed.insert(item.path.clone());
+                    }
+                    Err(err) => failures.push(err.to_string()),
+                }
+            }
+            let mut summary = format!("Removed {} Xcode items, freed {}", removed.len(), format::format_size(freed, units));
+            if let Some(first) = failures.first() {
+                summary.push_str(&format!("\n{} failed: {}", failures.len(), first));
+            }
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                app.session.add_freed(format!("{} Xcode items", removed.len()), freed);
+                if let Some(items) = app.xcode_items.as_mut() {
+                    items.retain(|item| !removed.contains(&item.path));
+                    app.xcode_selected.retain(|path| !removed.contains(path));
+                    app.selected_xcode_index = app.selected_xcode_index.min(items.len().saturating_sub(1));
+                }
+                tasks::report(app, mode, summary);
+            })
+        });
+    }
+
+    /// Look up the running browsers and return the profiles whose cache is worth clearing: those
+    /// with a cache, of browsers that aren't running.
+    pub fn suggested_browser_profiles(&mut self) -> Vec<usize> {
+        self.running_browsers = browser_profiles::running_browsers();
+        self.browser_profiles
+            .iter()
+            .enumerate()
+            .filter(|(_, profile)| profile.cache_size > 0 && !self.running_browsers.contains(&profile.browser))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Clear the cache of the profiles at `selected` in the background, then report in the
+    /// result popup.
+    pub fn clear_browser_caches(&mut self, selected: &[usize]) {
+        let units = self.config.display.size_units;
+        let profiles: Vec<(usize, BrowserProfile)> =
+            self.browser_profiles.iter().cloned().enumerate().filter(|(index, _)| selected.contains(index)).collect();
+        self.tasks.spawn(move || {
+            let mut lines = Vec::new();
+            let mut freed_total = 0;
+            // Profile directory and what clearing its cache freed
+            let mut cleared = Vec::new();
+            for (index, profile) in &profiles {
+                let label = format!("{} {}", profile.browser.label(), profile.name);
+                match browser_profiles::clear_cache(profile) {
+                    Ok(freed) => {
+                        freed_total += freed;
+                        cleared.push((profile.path.clone(), freed));
+                        lines.push(format!("{}: freed {}", label, format::format_size(freed, units)));
+                    }
+                    Err(err) => lines.push(format!("{} (#{}) failed: {}", label, index + 1, err)),
+                }
+            }
+            lines.insert(0, format!("Cleared browser caches, freed {}", format::format_size(freed_total, units)));
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                app.session.add_freed(format!("Browser caches of {} profiles", cleared.len()), freed_total);
+                for (path, freed) in cleared {
+                    if let Some(profile) = app.browser_profiles.iter_mut().find(|profile| profile.path == path) {
+                        profile.cache_size = profile.cache_size.saturating_sub(freed);
+                    }
+                }
+                tasks::report(app, mode, lines.join("\n"));
+            })
+        });
+    }
+
+    /// Point the running full scan at the highlighted entry of the listing, so that directory
+    /// is walked next.
+    pub fn update_scan_priority(&self) {
+        let path = self.file_entries.as_ref().and_then(|entries| entries.get(self.selected_file_index)).map(|entry| entry.path.clone());
+        self.scan_priority.set(path);
+    }
+
+    /// Empty the trash of the device at `index` in the background, then refresh the devices so
+    /// the details panel shows what is left, and report in the result popup.
+    pub fn empty_trash(&mut self, index: usize) -> Result<(), String> {
+        let Some(device) = self.devices.get(index) else {
+            return Err("The device is gone".to_string());
+        };
+        if let Err(busy) = device_guard::check(&device.mount_point) {
+            return Err(format!("Can't empty the trash: {}", busy));
+        }
+        let (name, mount_point, home_volume) = (device.name.clone(), device.mount_point.clone(), device.home_volume);
+        let units = self.config.display.size_units;
+        self.tasks.spawn(move || {
+            let (freed, failures) = trash::empty_trash(&mount_point, home_volume);
+            let devices = detect_storage_devices();
+            let mut summary = format!("Emptied the trash of {}, freed {}", name, format::format_size(freed, units));
+            if let Some(first) = failures.first() {
+                summary.push_str(&format!("\n{} not deleted: {}", failures.len(), first));
+            }
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| {
+                app.session.add_freed(format!("Trash of {}", name), freed);
+                app.set_devices(devices);
+                tasks::report(app, mode, summary);
+            })
+        });
+        Ok(())
+    }
+
+    /// Rename the file or directory at `path` to `new_name` within its directory in the
+    /// background, then update the listings and scan results in place instead of scanning
+    /// again. Returns an error for a name that can't be used, without renaming.
+    pub fn rename_entry(&mut self, path: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        let new_name = new_name.trim().to_string();
+        if new_name.is_empty() || new_name == "." || new_name == ".." || new_name.contains('/') {
+            return Err(format!("\"{}\" is not a valid name", new_name).into());
+        }
+        let path = path.to_string();
+        let new_path = Path::new(&path).with_file_name(&new_name);
+        if new_path == Path::new(&path) {
+            self.notification = Some(format!("{} keeps its name", path));
+            return Ok(());
+        }
+        device_guard::check(&path)?;
+        self.tasks.spawn(move || {
+            let result = if std::fs::symlink_metadata(&new_path).is_ok() {
+                Err(format!("{} already exists", new_path.display()))
+            } else {
+                std::fs::rename(&path, &new_path).map_err(|err| err.to_string())
+            };
+            Box::new(move |app: &mut App, mode: &mut ModeMachine| match result {
+                Ok(()) => {
+                    app.renamed(&path, &new_name);
+                    app.notification = Some(format!("Renamed {} to {}", path, new_name));
+                }
+                Err(err) => tasks::report(app, mode, format!("Rename failed: {}", err)),
+            })
+        });
+        Ok(())
+    }
+
+    /// Update the listings and scan results after the entry at `path` was renamed to `new_name`.
+    fn renamed(&mut self, path: &str, new_name: &str) {
+        let old_path = Path::new(path);
+        let new_path = old_path.with_file_name(new_name);
+        // The entry itself and, for a directory, everything below it moved
+        let rename = |entries: &mut Vec<FileEntry>| {
+            for entry in entries.iter_mut() {
+                let Ok(rest) = Path::new(&entry.path).strip_prefix(old_path) else {
+                    continue;
+                };
+                if rest.as_os_str().is_empty() {
+                    entry.name = new_name.to_string();
+                    entry.path = new_path.to_string_lossy().into_owned();
+                } else {
+                    entry.path = new_path.join(rest).to_string_lossy().into_owned();
+                }
+            }
+        };
+        let lists = [&mut self.file_entries, &mut self.full_scan_results, &mut self.junk_all_files];
+        for entries in lists.into_iter().flatten() {
+            rename(entries);
+        }
+        self.device_results.values_mut().for_each(|(entries, _)| rename(entries));
+        let totals = self.full_scan_totals.iter_mut().chain(self.device_results.values_mut().filter_map(|(_, totals)| totals.as_mut()));
+        totals.for_each(|totals| totals.rename(old_path, &new_path));
+        if let Some(size) = self.scanned_dir_sizes.remove(path) {
+            self.scanned_dir_sizes.insert(new_path.to_string_lossy().into_owned(), size);
+        }
+    }
+
+    /// Keep the full scan results shown for the device named `device`, to show them again when
+    /// it is selected rather than scanning it again.
+    fn stash_full_scan(&mut self, device: Option<&str>) {
+        let totals = self.full_scan_totals.take();
+        if let (Some(results), Some(device)) = (self.full_scan_results.take(), device) {
+            self.device_results.insert(device.to_string(), (results, totals));
         }
     }
 
@@ -140,8 +1100,42 @@ impl App {
         }
     }
 
+    /// Jump to the device whose name best fuzzy-matches `query` (no-op for an empty query or no match).
+    pub fn select_device_matching(&mut self, query: &str) {
+        if query.is_empty() {
+            return;
+        }
+        if let Some(index) = fuzzy::best_match(query, self.devices.iter().map(|dev| dev.name.as_str())) {
+            self.selected = index;
+        }
+    }
+
+    /// Grow (positive) or shrink the device and progress panels by `LayoutConfig::STEP` each,
+    /// within their limits. Returns whether anything changed.
+    pub fn resize_panels(&mut self, device_steps: i16, progress_steps: i16) -> bool {
+        let step = |percent: u16, steps: i16| percent.saturating_add_signed(steps * LayoutConfig::STEP as i16);
+        let resized = LayoutConfig {
+            device_panel: step(self.layout.device_panel, device_steps),
+            progress_panel: step(self.layout.progress_panel, progress_steps),
+        }
+        .clamped();
+        let changed = resized != self.layout;
+        self.layout = resized;
+        changed
+    }
+
+    /// Cost model of the selected device, when costs are shown and it has a rate.
+    pub fn cost_model(&self) -> Option<FlatRate> {
+        self.devices.get(self.selected).filter(|_| self.show_cost).and_then(|device| self.config.cost.model_for(device))
+    }
+
     pub fn refresh(&mut self) {
-        self.devices = detect_storage_devices();
+        self.set_devices(detect_storage_devices());
+    }
+
+    /// Show `devices`, freshly detected, keeping the selection within them.
+    pub fn set_devices(&mut self, devices: Vec<StorageDevice>) {
+        self.devices = devices;
         if self.devices.is_empty() {
             self.selected = 0;
         } else if self.selected >= self.devices.len() {
@@ -180,28 +1174,74 @@ impl App {
         }
     }
     
+    /// The selected device; None if the device list is empty or shrank past the selection.
+    pub fn selected_device(&self) -> Option<&StorageDevice> {
+        self.devices.get(self.selected)
+    }
+
+    /// Entries the file listing shows: the results of a finished full scan, or else the loaded
+    /// directory listing if it has any.
+    pub fn listed_entries(&self) -> Option<&[FileEntry]> {
+        if self.full_scan_results.is_some() && !self.scan_progress.in_progress {
+            return self.full_scan_results.as_deref();
+        }
+        self.file_entries.as_deref().filter(|entries| !self.scanning && !entries.is_empty())
+    }
+
+    /// Keep the highlighted entry within the (possibly shorter) listing.
+    pub fn clamp_file_selection(&mut self) {
+        let count = self.full_scan_results.as_ref().or(self.file_entries.as_ref()).map_or(0, |entries| entries.len());
+        self.selected_file_index = self.selected_file_index.min(count.saturating_sub(1));
+        self.file_list_offset = self.file_list_offset.min(self.selected_file_index);
+    }
+
     pub fn get_selected_file_entry(&self) -> Option<&FileEntry> {
+        // Directories have no entry of their own, and the highlighted file of the full scan
+        // isn't on screen
+        if self.dir_view_active() {
+            return None;
+        }
         if let Some(ref entries) = self.full_scan_results {
             if self.selected_file_index < entries.len() {
                 return Some(&entries[self.selected_file_index]);
             }
-        } else if let Some(ref entries) = self.file_entries {
-            if self.selected_file_index < entries.len() {
-                return Some(&entries[self.selected_file_index]);
-            }
+        } else if let Some(ref entries) = self.file_entries
+            && self.selected_file_index < entries.len()
+        {
+            return Some(&entries[self.selected_file_index]);
         }
         None
     }
+
+    /// The highlighted file when the file panel has the focus and shows a plain listing or
+    /// scan result, not folders, categories, projects, Xcode items or a directory's contents.
+    /// What renaming, archiving, extracting and opening act on.
+    pub fn selected_plain_file(&self) -> Option<&FileEntry> {
+        let plain = self.focus == PanelFocus::Right
+            && !self.folder_view_mode
+            && !self.category_view_mode
+            && !self.dev_view_active()
+            && !self.xcode_view_active()
+            && !self.dir_view_active();
+        self.get_selected_file_entry().filter(|_| plain)
+    }
 }
 
-/// Performs file operations
+/// Performs file operations. Deletes move the item to the trash, so they can be undone like
+/// moves; one that can't go there fails and stays where it is.
 pub fn perform_file_operation(
     op_type: &FileOperation, 
     source_path: &str, 
-    target_path: Option<&str>
-) -> Result<String, Box<dyn std::error::Error>> {
+    target_path: Option<&str>,
+    job: &FileOpJob,
+    file_system: &dyn FileSystem,
+) -> Result<FileOpOutcome, Box<dyn std::error::Error>> {
     use std::fs;
     use std::path::Path;
+
+    // Never touch a device the user is ejecting, and stop when it starts being ejected
+    let paths: Vec<&str> = std::iter::once(source_path).chain(target_path).collect();
+    let _on_devices = job.run_on(&paths)?;
     
     match op_type {
         FileOperation::Copy => {
@@ -214,9 +1254,12 @@ pub fn perform_file_operation(
                     fs::create_dir_all(parent)?;
                 }
                 
-                // Perform the copy
-                fs::copy(source_path, target_path)?;
-                Ok(format!("Copied {} to {}", source_path.display(), target_path.display()))
+                // Copy in chunks so the progress can be shown
+                file_ops::copy_with_progress(source_path, target_path, job)?;
+                Ok(FileOpOutcome {
+                    summary: format!("Copied {} to {}", source_path.display(), target_path.display()),
+                    revert: None,
+                })
             } else {
                 Err("Target path not provided for copy operation".into())
             }
@@ -231,38 +1274,138 @@ pub fn perform_file_operation(
                     fs::create_dir_all(parent)?;
                 }
                 
-                // Perform the move
-                fs::rename(source_path, target_path)?;
-                Ok(format!("Moved {} to {}", source_path.display(), target_path.display()))
+                // A rename, or a copy with progress and removing the source across volumes
+                file_ops::move_with_progress(source_path, target_path, job)?;
+                Ok(FileOpOutcome {
+                    summary: format!("Moved {} to {}", source_path.display(), target_path.display()),
+                    revert: Some(Revert::MoveBack { moved_to: target.to_string(), original: source_path.display().to_string() }),
+                })
             } else {
                 Err("Target path not provided for move operation".into())
             }
         },
         FileOperation::Delete => {
             let path = Path::new(source_path);
-            if path.is_dir() {
-                fs::remove_dir_all(path)?;
-                Ok(format!("Deleted directory: {}", path.display()))
-            } else {
-                fs::remove_file(path)?;
-                Ok(format!("Deleted file: {}", path.display()))
+            let kind = if file_system.file_info(path)?.is_dir { "directory" } else { "file" };
+            // The confirmation promised the trash and undo, so without a usable trash nothing
+            // is deleted
+            let trashed = trash::move_to_trash(path)
+                .map_err(|err| format!("couldn't move {} {} to the trash, so it wasn't deleted: {}", kind, path.display(), err))?;
+            Ok(FileOpOutcome {
+                summary: format!("Moved {} {} to the trash", kind, path.display()),
+                revert: Some(Revert::Restore {
+                    trashed: trashed.to_string_lossy().into_owned(),
+                    original: source_path.to_string(),
+                }),
+            })
+        },
+        FileOperation::Archive => {
+            let Some(target) = target_path else {
+                return Err("Target path not provided for archive operation".into());
+            };
+            let format = ArchiveFormat::from_path(target).ok_or("archives must end in .zip, .tar.gz or .tgz")?;
+            #[cfg(feature = "archive")]
+            {
+                if let Some(parent) = Path::new(target).parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                file_ops::archive_with_progress(Path::new(source_path), Path::new(target), format, job)?;
+                Ok(FileOpOutcome {
+                    summary: format!("Archived {} into {}", source_path, target),
+                    revert: None,
+                })
+            }
+            #[cfg(not(feature = "archive"))]
+            {
+                let _ = format;
+                Err("this build can't write archives (feature `archive`)".into())
+            }
+        },
+        FileOperation::Extract => {
+            let Some(target) = target_path else {
+                return Err("Target path not provided for extract operation".into());
+            };
+            #[cfg(feature = "archive")]
+            {
+                file_ops::extract_with_progress(Path::new(source_path), Path::new(target), job)?;
+                Ok(FileOpOutcome {
+                    summary: format!("Extracted {} into {}", source_path, target),
+                    revert: None,
+                })
+            }
+            #[cfg(not(feature = "archive"))]
+            {
+                let _ = (target, job);
+                Err("this build can't extract archives (feature `archive`)".into())
             }
         },
+        FileOperation::SecureDelete { passes } => {
+            let path = Path::new(source_path);
+            let kind = if fs::symlink_metadata(path)?.is_dir() { "directory" } else { "file" };
+            file_ops::secure_delete_with_progress(path, *passes, job)?;
+            Ok(FileOpOutcome {
+                summary: format!("Overwrote {} {} {} times and deleted it", kind, path.display(), passes.max(&1)),
+                revert: None,
+            })
+        },
     }
 }
 
+/// Make room for `rows` of the UI below the prompt, scrolling the screen up if it is too close
+/// to the bottom, and return the area to draw in.
+fn reserve_inline_rows(stdout: &mut std::io::Stdout, rows: u16) -> std::io::Result<Rect> {
+    let (width, height) = crossterm::terminal::size()?;
+    // One row is left for the cursor below the UI
+    let rows = rows.min(height.saturating_sub(1));
+    // In raw mode a newline only moves down, scrolling once it reaches the bottom
+    stdout.write_all("\n".repeat(rows as usize).as_bytes())?;
+    stdout.flush()?;
+    let (_, row) = crossterm::cursor::position()?;
+    Ok(Rect::new(0, row.saturating_sub(rows), width, rows))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    // Initialize terminal.
+    // Headless subcommands print their results and exit without starting the TUI. A profile
+    // is selected first, as it decides which config and data files are read.
+    let (record_path, replay_path, inline) = match cli::select_profile(std::env::args().skip(1)).and_then(cli::parse_args) {
+        Ok(cli::Command::Tui { record, replay, inline }) => (record, replay, inline),
+        Ok(command) => return cli::run_headless(&command).await,
+        Err(e) => {
+            cli::print_usage();
+            return Err(e.into());
+        }
+    };
+    let config = config::load_config()?;
+    format::set_number_format(config.display.number_format());
+    let mut announcer = announce::Announcer::new(&config.accessibility)?;
+    let mut recorder = match record_path {
+        Some(path) => Some(Recorder::create(&path)?),
+        None => None,
+    };
+    let replaying = replay_path.is_some();
+
+    // Initialize terminal: the whole screen, or with --inline some rows below the prompt.
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    let viewport = match inline {
+        Some(rows) => Some(reserve_inline_rows(&mut stdout, rows)?),
+        None => {
+            execute!(stdout, EnterAlternateScreen)?;
+            None
+        }
+    };
+    execute!(stdout, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = match viewport {
+        Some(area) => Terminal::with_options(backend, TerminalOptions { viewport: Viewport::fixed(area) })?,
+        None => Terminal::new(backend)?,
+    };
 
     // Create an mpsc channel for device updates.
     let (device_tx, device_rx) = mpsc::channel();
-    event_handler::start_device_listener(device_tx);
+    // Power source changes (true = on battery)
+    let (power_tx, power_rx) = mpsc::channel::<bool>();
 
     // Tokio mpsc channel for async directory listings.
     let (scan_tx, mut scan_rx) =
@@ -272,68 +1415,146 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let (progress_tx, mut progress_rx) = 
         tokio::sync::mpsc::channel::<scanner::ScanProgressMessage>(100);
 
-    let devices = detect_storage_devices();
-    let mut app = App::new(devices);
-    let mut mode = AppMode::Normal;
+    // Deletion previews measured on disk in the background
+    let (impact_tx, mut impact_rx) = tokio::sync::mpsc::channel::<DeletionImpact>(1);
+
+    // When replaying, recorded messages replace the real device listener and scanners.
+    let (control_tx, control_rx) = mpsc::channel::<RecordedEvent>();
+    let devices = if let Some(path) = replay_path {
+        replay::start_replay(&path, control_tx, device_tx, scan_tx.clone(), progress_tx.clone())?;
+        Vec::new()
+    } else {
+        event_handler::start_device_listener(device_tx);
+        event_handler::start_power_listener(power_tx);
+        detect_storage_devices()
+    };
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.record(RecordedEvent::Devices(devices.clone()));
+    }
+    let mut app = App::new(devices, config.clone());
+    app.viewport = viewport;
+    if !replaying {
+        app.scan_history = scan_history::load();
+        if config.startup.check_updates {
+            // Without the `net` feature there is nothing to check with
+            let _ = app.start_update_check(false);
+        }
+    }
+    let mut mode = ModeMachine::new();
     let spinner_chars = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
-    // When the app starts, if there is at least one device, trigger a directory listing for it.
+    // Apply the configured startup device. A startup junk scan always targets the system disk.
+    let startup = &config.startup;
+    let junk_at_startup = startup.auto_junk_scan || startup.view == StartupView::JunkSummary;
+    let startup_device = if junk_at_startup {
+        app.devices
+            .iter()
+            .position(|dev| dev.mount_point == "/")
+            .or_else(|| app.devices.iter().position(|dev| !dev.ejectable && dev.mounted))
+    } else {
+        startup.device.as_ref().and_then(|wanted| app.devices.iter().position(|dev| dev.matches(wanted)))
+    };
+    if let Some(index) = startup_device {
+        app.selected = index;
+    }
+
+    // When the app starts, if there is at least one device, show the configured startup view for it.
     let mut last_selected = app.selected;
-    if !app.devices.is_empty() {
+    let cached_scan = match startup.view {
+        StartupView::LastScan if !app.devices.is_empty() => scan_cache::load(&app.devices[app.selected].mount_point),
+        _ => None,
+    };
+    if junk_at_startup && !app.devices.is_empty() {
+        app.pending_folder_view = startup.view == StartupView::JunkSummary;
+        event_handler::start_device_scan(&mut app, &mut mode, &progress_tx, false);
+    } else if app.devices.get(app.selected).is_some_and(|dev| dev.mounted) {
+        // Show the last full scan of this device without rescanning, with its root listed for
+        // when it is scanned again
+        if let Some(cache) = cached_scan {
+            let mut entries = cache.to_file_entries();
+            app.sort_entries(&mut entries);
+            app.full_scan_results = Some(entries);
+        }
         let mount = app.devices[app.selected].mount_point.clone();
         let sender = scan_tx.clone();
+        let symlinks = app.config.scan.symlinks;
         tokio::spawn(async move {
-            let result = tokio::task::spawn_blocking(move || list_directory(&mount))
+            let result = tokio::task::spawn_blocking(move || list_directory(&mount, symlinks))
                 .await
                 .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
             let _ = sender.send(result).await;
         });
         app.scanning = true;
-        mode = AppMode::Scanning { device_index: app.selected, spinner_index: 0 };
+        mode.start_listing(app.selected);
     }
 
+    // The screen is only redrawn when something changed, and at most every MIN_FRAME_INTERVAL
+    let mut last_draw: Option<Instant> = None;
+    let mut last_spinner_tick = Instant::now();
+
     loop {
+        if let Ok(on_battery) = power_rx.try_recv() {
+            app.dirty |= on_battery != app.on_battery;
+            app.on_battery = on_battery;
+        }
+
         // Update device list from listener.
         if let Ok(new_devices) = device_rx.try_recv() {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(RecordedEvent::Devices(new_devices.clone()));
+            }
+            // The listener also resends unchanged lists now and then
+            app.dirty |= new_devices != app.devices;
+
             // Store previous selection info before updating device list
             let prev_selected = if !app.devices.is_empty() {
-                Some(app.devices[app.selected].mount_point.clone())
+                Some(app.devices[app.selected].key().to_string())
             } else {
                 None
             };
+            let prev_name = app.devices.get(app.selected).map(|dev| dev.name.clone());
+            let last_key = app.devices.get(last_selected).map(|dev| dev.key().to_string());
             
             // Update the device list
             app.devices = new_devices;
+            let mounts: Vec<String> = app.devices.iter().map(|dev| dev.mount_point.clone()).collect();
+            device_guard::forget_missing(&mounts);
+            // Follow the device last listed to its place in the new list
+            if let Some(index) = last_key.and_then(|key| app.devices.iter().position(|dev| dev.key() == key)) {
+                last_selected = index;
+            }
             
             // Update selection
             if app.devices.is_empty() {
                 app.selected = 0;
                 app.file_entries = None;
-                app.full_scan_results = None;
+                app.stash_full_scan(prev_name.as_deref());
             } else {
                 // Try to maintain the same device selection if possible
                 if let Some(prev_mount) = prev_selected {
-                    if let Some(index) = app.devices.iter().position(|dev| dev.mount_point == prev_mount) {
+                    if let Some(index) = app.devices.iter().position(|dev| dev.key() == prev_mount) {
                         app.selected = index;
                     } else {
                         // Previous device not found, reset selection and clear file entries
                         app.selected = 0;
                         app.file_entries = None;
-                        app.full_scan_results = None;
-                        // Trigger a directory listing for the new device
-                        mode = AppMode::Scanning { device_index: app.selected, spinner_index: 0 };
+                        app.stash_full_scan(prev_name.as_deref());
                         last_selected = app.selected;
-                        
-                        // Start scan for the new selection
-                        let mount = app.devices[app.selected].mount_point.clone();
-                        let sender = scan_tx.clone();
-                        tokio::spawn(async move {
-                            let result = tokio::task::spawn_blocking(move || list_directory(&mount))
-                                .await
-                                .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
-                            let _ = sender.send(result).await;
-                        });
-                        app.scanning = true;
+
+                        // Trigger a directory listing for the new device (a replay supplies its own)
+                        if !replaying && app.devices[app.selected].mounted {
+                            mode.start_listing(app.selected);
+                            let mount = app.devices[app.selected].mount_point.clone();
+                            let sender = scan_tx.clone();
+                            let symlinks = app.config.scan.symlinks;
+                            tokio::spawn(async move {
+                                let result = tokio::task::spawn_blocking(move || list_directory(&mount, symlinks))
+                                    .await
+                                    .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
+                                let _ = sender.send(result).await;
+                            });
+                            app.scanning = true;
+                        }
                     }
                 } else if app.selected >= app.devices.len() {
                     app.selected = app.devices.len() - 1;
@@ -343,107 +1564,211 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         }
 
+        // A tab that was switched to brings its own listing of its device
+        if std::mem::take(&mut app.tab_switched) {
+            last_selected = app.selected;
+        }
+
         // When in Normal mode, check if the selection changed.
-        if let AppMode::Normal = mode {
-            if !app.devices.is_empty() && app.selected != last_selected {
-                // A new device was selected
-                app.selected_file_index = 0;   // Reset selection
-                app.file_list_offset = 0;      // Reset scroll
-                
-                // Clear full scan results when switching devices
-                app.full_scan_results = None;
-                
-                // Get current device ID
-                let device_id = &app.devices[app.selected].name;
-                
-                // First check if we have full scan results for this device
-                let has_full_scan = app.device_results.contains_key(device_id);
-                
-                if has_full_scan {
-                    // Use the cached full scan results
-                    if let Some(entries) = app.device_results.get(device_id) {
-                        app.file_entries = Some(entries.clone());
-                        app.full_scan_results = Some(entries.clone());
-                    }
-                } else {
-                    // No full scan results, do a regular directory listing
+        if mode.is_normal() && !app.devices.is_empty() && app.selected != last_selected {
+            // A new device was selected
+            app.dirty = true;
+            app.selected_file_index = 0;   // Reset selection
+            app.file_list_offset = 0;      // Reset scroll
+            app.browse_dir = None;         // Listings start at the device root
+            
+            // Keep the full scan results of the device switched away from, moved rather than
+            // copied, and show those of the new device if it has some
+            let previous = app.devices.get(last_selected).map(|dev| dev.name.clone());
+            app.stash_full_scan(previous.as_deref());
+            app.dir_view = None;
+            app.type_breakdown = None;
+            app.size_deltas = None;
+            app.file_entries = None;
+            
+            if !app.devices[app.selected].mounted {
+                // Nothing to list until the volume is mounted
+            } else {
+                if let Some((entries, totals)) = app.device_results.remove(&app.devices[app.selected].name) {
+                    app.full_scan_results = Some(entries);
+                    app.full_scan_totals = totals;
+                }
+                if !replaying {
+                    // Its root is listed as well, shown when it is scanned again
                     app.scanning = true;
-                    app.file_entries = None;
                     
                     let mount = app.devices[app.selected].mount_point.clone();
                     let sender = scan_tx.clone();
+                    let symlinks = app.config.scan.symlinks;
                     tokio::spawn(async move {
-                        let result = tokio::task::spawn_blocking(move || list_directory(&mount))
+                        let result = tokio::task::spawn_blocking(move || list_directory(&mount, symlinks))
                             .await
                             .unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Error + Send + 'static>));
                         let _ = sender.send(result).await;
                     });
                     
                     // Update mode to scanning
-                    mode = AppMode::Scanning { device_index: app.selected, spinner_index: 0 };
+                    mode.start_listing(app.selected);
                 }
-                
-                // Update last_selected.
-                last_selected = app.selected;
             }
+            
+            // Update last_selected.
+            last_selected = app.selected;
         }
 
-        // In Scanning mode, update spinner and attempt to receive the file listing.
-        if let AppMode::Scanning { ref mut spinner_index, .. } = mode {
-            *spinner_index = (*spinner_index + 1) % spinner_chars.len();
-            if let Ok(result) = scan_rx.try_recv() {
-                match result {
-                    Ok(file_entries) => {
-                        // Store in device cache if we have a device selected
-                        if !app.devices.is_empty() {
-                            let device_id = app.devices[app.selected].name.clone();
-                            app.device_results.insert(device_id, file_entries.clone());
-                        }
-                        
-                        app.file_entries = Some(file_entries);
-                        app.scanning = false;
-                        mode = AppMode::Normal;
-                    }
-                    Err(e) => {
-                        mode = AppMode::Ejected(format!("Scan failed: {}", e));
-                        app.scanning = false;
-                    }
+        // Apply mode changes from a replayed session before consuming its messages.
+        while let Ok(event) = control_rx.try_recv() {
+            app.dirty = true;
+            match event {
+                RecordedEvent::ListingStarted { device_index } if device_index < app.devices.len() => {
+                    app.selected = device_index;
+                    last_selected = device_index;
+                    app.scanning = true;
+                    mode.start_listing(device_index);
+                }
+                RecordedEvent::FullScanStarted { device_index, total_bytes, junk, dev_artifacts, xcode } if device_index < app.devices.len() => {
+                    app.selected = device_index;
+                    last_selected = device_index;
+                    app.folder_view_mode = false;
+                    app.category_view_mode = false;
+                    app.scan_progress = ScanProgress::start(total_bytes);
+                    app.scan_mode = if xcode {
+                        ScanMode::Xcode
+                    } else if dev_artifacts {
+                        ScanMode::DevArtifacts
+                    } else if junk {
+                        ScanMode::JunkScan
+                    } else {
+                        ScanMode::FullScan
+                    };
+                    app.scan_started_at = Some(Instant::now());
+                    mode.start_full_scan(device_index);
+                }
+                RecordedEvent::MalformedLines(lines) => {
+                    app.notification = Some(format!("Replay skipped {} malformed line{}", lines, if lines == 1 { "" } else { "s" }));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.observe_mode(mode.mode(), &app);
+        }
+
+        // Advance the spinner of a running listing or scan
+        if mode.is_busy() && last_spinner_tick.elapsed() >= SPINNER_INTERVAL {
+            mode.tick_spinner(spinner_chars.len());
+            last_spinner_tick = Instant::now();
+            app.dirty = true;
+        }
+
+        // In Scanning mode, attempt to receive the file listing.
+        if let AppMode::Scanning { .. } = mode.mode() && let Ok(result) = scan_rx.try_recv() {
+            app.dirty = true;
+            if let Some(recorder) = recorder.as_mut() {
+                let recorded = result.as_ref().map(|entries| entries.clone()).map_err(|e| e.to_string());
+                recorder.record(RecordedEvent::Listing(recorded));
+            }
+            match result {
+                Ok(mut file_entries) => {
+                    app.sort_entries(&mut file_entries);
+                    app.file_entries = Some(file_entries);
+                    app.scanning = false;
+                    mode.finish_listing();
+                }
+                Err(e) => {
+                    mode.show_message(format!("Scan failed: {}", e));
+                    app.scanning = false;
                 }
             }
         }
         
-        // In FullScan mode, update spinner and check for progress updates
-        if let AppMode::FullScan { ref mut spinner_index, .. } = mode {
-            *spinner_index = (*spinner_index + 1) % spinner_chars.len();
-            
-            // Check for progress updates
+        // In FullScan mode, check for progress updates
+        if let AppMode::FullScan { .. } = mode.mode() {
             while let Ok(progress_msg) = progress_rx.try_recv() {
+                app.dirty = true;
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(RecordedEvent::Progress(progress_msg.clone()));
+                }
                 match progress_msg {
                     ScanProgressMessage::FileScanned { size, path } => {
                         app.scan_progress.scanned_bytes += size;
                         app.scan_progress.files_processed += 1;
                         app.scan_progress.current_file = Some(path);
                     },
-                    ScanProgressMessage::ScanComplete { results, files_processed } => {
-                        // Store full scan results in both places
-                        app.full_scan_results = Some(results.clone());
-                        
-                        // Also store in device cache if device is available
-                        if !app.devices.is_empty() {
-                            let device_id = app.devices[app.selected].name.clone();
-                            app.device_results.insert(device_id, results);
+                    ScanProgressMessage::FilesDeleted { files, size, path } => {
+                        app.scan_progress.scanned_bytes += size;
+                        app.scan_progress.files_processed += files;
+                        app.scan_progress.current_file = Some(path);
+                    },
+                    ScanProgressMessage::FolderDeleteComplete { folder, deleted, freed, failures, stopped } => {
+                        app.remove_deleted_junk(&deleted);
+                        app.session.add_freed(format!("{} junk files from {}", deleted.len(), folder), freed);
+                        app.folder_delete = None;
+                        app.scan_progress.in_progress = false;
+                        app.scan_progress.current_file = None;
+                        mode.finish_full_scan();
+
+                        let mut summary = format!(
+                            "{} {} junk files from {}, freed {}",
+                            if stopped { "Stopped after deleting" } else { "Deleted" },
+                            deleted.len(),
+                            folder,
+                            format::format_size(freed, app.config.display.size_units)
+                        );
+                        if let Some(first) = failures.first() {
+                            summary.push_str(&format!("\n{} not deleted: {}", failures.len(), first));
                         }
+                        mode.show_message(summary);
+                    },
+                    ScanProgressMessage::DirectoryScanned { path, size } => {
+                        app.scanned_dir_sizes.insert(path, size);
+                    },
+                    ScanProgressMessage::LargestFiles { files } => {
+                        app.largest_so_far = Some(files);
+                    },
+                    ScanProgressMessage::ScanComplete { mut results, files_processed, dir_totals, errors } => {
+                        app.largest_so_far = None;
+                        // Compared with the results the scan replaced, unless either only kept
+                        // its largest files
+                        let baseline = app.rescan_baseline.take().filter(|_| dir_totals.is_none());
+                        app.size_deltas = match (baseline, app.devices.get(app.selected)) {
+                            (Some(old), Some(device)) => Some(SizeDeltas::between(Path::new(&device.mount_point), &old, &results)),
+                            _ => None,
+                        };
+                        app.sort_scan_results(&mut results);
+                        if let Some(announcer) = announcer.as_mut() {
+                            let root = app.devices.get(app.selected).map_or("", |device| device.mount_point.as_str());
+                            let total = match dir_totals.as_ref() {
+                                Some(totals) => totals.get(root).map_or(0, |total| total.size),
+                                None => results.iter().map(|file| file.size).sum(),
+                            };
+                            announcer.say(&format!("scan complete: {} in {} files", format::format_size(total, app.config.display.size_units), format::format_count(files_processed as u64)));
+                        }
+                        app.full_scan_totals = dir_totals;
+                        app.record_completed_scan(false, &results, !replaying);
+                        app.set_scan_errors(errors);
+                        #[cfg(feature = "junk")]
+                        app.start_junk_suggestions(&results);
+                        app.start_last_opened_lookup(&results);
+                        // Kept for the device when another one is selected
+                        app.full_scan_results = Some(results);
+                        app.refresh_dir_view();
+                        app.refresh_type_breakdown();
                         
                         app.scan_progress.in_progress = false;
                         app.scan_progress.files_processed = files_processed as u64;
                         app.scan_progress.current_file = None;
                         app.folder_summaries = None; // No folder summaries for regular scans
-                        mode = AppMode::Normal;
+                        app.junk_categories = None;
+                        app.category_view_mode = false;
+                        mode.finish_full_scan();
                     },
-                    ScanProgressMessage::JunkScanComplete { results, files_processed, folder_summaries } => {
-                        // Store full scan results in both places
-                        app.full_scan_results = Some(results.clone());
-                        
+                    ScanProgressMessage::JunkScanComplete { mut results, files_processed, folder_summaries, categories, docker, browsers, errors } => {
+                        app.sort_entries(&mut results);
+                        app.record_completed_scan(true, &results, !replaying);
+                        app.set_scan_errors(errors);
+
                         // Convert folder summaries to a format we can store
                         let summaries = folder_summaries
                             .into_iter()
@@ -453,34 +1778,221 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 file_count: count,
                             })
                             .collect();
-                        
-                        app.folder_summaries = Some(summaries);
-                        
-                        // Also store in device cache if device is available
-                        if !app.devices.is_empty() {
-                            let device_id = app.devices[app.selected].name.clone();
-                            app.device_results.insert(device_id, results);
+
+                        // Keep the unfiltered results so categories can be toggled, then show
+                        // them without the excluded categories (in both places, as for full scans)
+                        app.junk_all_files = Some(results);
+                        app.junk_all_folders = Some(summaries);
+                        app.junk_categories = Some(categories);
+                        app.docker_usage = docker;
+                        app.browser_profiles = browsers;
+                        app.selected_category_index = 0;
+                        app.apply_category_filter();
+                        if app.pending_folder_view {
+                            app.folder_view_mode = true;
+                            app.pending_folder_view = false;
                         }
                         
                         app.scan_progress.in_progress = false;
                         app.scan_progress.files_processed = files_processed as u64;
                         app.scan_progress.current_file = None;
                         app.scan_mode = ScanMode::JunkScan;
-                        mode = AppMode::Normal;
+                        mode.finish_full_scan();
+                    }
+                    ScanProgressMessage::DevArtifactsComplete { root, projects, files_processed } => {
+                        // Dev scans aren't device scans, so they stay out of the scan history
+                        app.scan_started_at = None;
+                        let total: u64 = projects.iter().map(DevProject::total_size).sum();
+                        app.notification = Some(format!(
+                            "Found {} of build artifacts in {} projects under {}",
+                            format::format_size(total, app.config.display.size_units),
+                            projects.len(),
+                            root
+                        ));
+                        app.dev_projects = Some(projects);
+                        app.dev_scan_root = Some(root);
+                        app.excluded_projects.clear();
+                        app.selected_project_index = 0;
+
+                        app.scan_progress.in_progress = false;
+                        app.scan_progress.files_processed = files_processed as u64;
+                        app.scan_progress.current_file = None;
+                        app.scan_mode = ScanMode::DevArtifacts;
+                        mode.finish_full_scan();
+                    }
+                    ScanProgressMessage::XcodeScanComplete { items, files_processed } => {
+                        app.scan_started_at = None;
+                        let total: u64 = items.iter().map(|item| item.size).sum();
+                        app.notification = Some(format!(
+                            "Found {} of Xcode data in {} items",
+                            format::format_size(total, app.config.display.size_units),
+                            items.len()
+                        ));
+                        app.xcode_selected = items.iter().filter(|item| item.suggested).map(|item| item.path.clone()).collect();
+                        app.xcode_items = Some(items);
+                        app.selected_xcode_index = 0;
+
+                        app.scan_progress.in_progress = false;
+                        app.scan_progress.files_processed = files_processed as u64;
+                        app.scan_progress.current_file = None;
+                        app.scan_mode = ScanMode::Xcode;
+                        mode.finish_full_scan();
                     }
                 }
             }
+            if app.scan_progress.in_progress {
+                app.scan_progress.sample();
+            }
+        }
+
+        // Start queued file operations and pick up the finished ones. While any run, redraw
+        // regularly so their progress shows.
+        let finished = app.operations.update();
+        if app.operations.is_active() {
+            app.dirty = true;
+        }
+        if !finished.is_empty() {
+            app.dirty = true;
+            for operation in &finished {
+                if let (Some(removed), Some(trashed)) = (&operation.removed, operation.trashed) {
+                    app.record_delete(removed, trashed);
+                }
+                if let (Some(removed), Some(entries), Some(totals)) = (&operation.removed, app.full_scan_results.as_ref(), app.full_scan_totals.as_mut()) {
+                    totals.remove(Path::new(removed), entries);
+                }
+                if let (Some(removed), Some(entries)) = (&operation.removed, app.full_scan_results.as_mut()) {
+                    entries.retain(|entry| !Path::new(&entry.path).starts_with(removed));
+                }
+                if operation.removed.is_some() {
+                    app.refresh_dir_view();
+                    app.refresh_type_breakdown();
+                }
+                if let (Some(removed), Some(trashed)) = (&operation.removed, operation.trashed) {
+                    app.start_space_check(removed, trashed);
+                }
+            }
+            // List the directory again, which shows a copied or moved file there
+            if *mode.mode() == AppMode::Normal && let Some(device) = app.devices.get(app.selected).filter(|device| device.mounted && !replaying) {
+                let listed = app.browse_dir.clone().unwrap_or_else(|| device.mount_point.clone());
+                let symlinks = app.config.scan.symlinks;
+                app.tasks.spawn(move || {
+                    let entries = list_directory(&listed, symlinks).ok();
+                    Box::new(move |app: &mut App, _: &mut ModeMachine| {
+                        let still_listed = app.devices.get(app.selected).is_some_and(|device| app.browse_dir.as_deref().unwrap_or(&device.mount_point) == listed);
+                        if !still_listed || app.scanning {
+                            return;
+                        }
+                        let mut entries = entries;
+                        if let Some(entries) = entries.as_mut() {
+                            app.sort_entries(entries);
+                        }
+                        app.file_entries = entries;
+                        app.clamp_file_selection();
+                    })
+                });
+            }
+            app.clamp_file_selection();
+            app.notification = finished.into_iter().last().map(|operation| operation.summary);
+        }
+
+        // Apply what background tasks did, such as an eject or a rename
+        if Tasks::apply_finished(&mut app, &mut mode) {
+            app.dirty = true;
+        }
+
+        #[cfg(feature = "preview")]
+        app.update_preview();
+
+        // A newer release shows as a notice; `:update` also reports no update or a failed check
+        if let Some(result) = app.update_check.as_ref().and_then(|check| check.result_rx.try_recv().ok()) {
+            let interactive = app.update_check.take().is_some_and(|check| check.interactive);
+            let message = match result {
+                Ok(Some(instructions)) => {
+                    app.notification = instructions.lines().next().map(|notice| format!("{} (:update = how to upgrade)", notice));
+                    app.update_instructions = Some(instructions.clone());
+                    Some(instructions)
+                }
+                Ok(None) => Some(format!("lazysmg {} is the newest version", env!("CARGO_PKG_VERSION"))),
+                Err(err) => Some(format!("Update check failed: {}", err)),
+            };
+            // Only in a popup if that doesn't interrupt a dialog opened in the meantime
+            if let Some(message) = message.filter(|_| interactive && *mode.mode() == AppMode::Normal) {
+                mode.show_message(message);
+            }
+            app.dirty = true;
+        }
+
+        if let Some(holders) = app.delete_holders_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            app.delete_holders_rx = None;
+            app.delete_holders = Some(holders);
+            app.dirty = true;
+        }
+
+        // A delete that didn't free its space explains why, unless that is just the trash
+        while let Ok(note) = app.space_note_rx.try_recv() {
+            app.dirty = true;
+            match note {
+                SpaceNote::InTrash(notice) => app.notification = Some(notice),
+                SpaceNote::Explained(text) if mode.is_normal() => mode.show_message(text),
+                SpaceNote::Explained(text) => app.notification = text.lines().next().map(str::to_string),
+            }
+        }
+
+        // Suggested junk locations are announced next to the scan summary
+        if let Some(suggestions) = app.junk_suggestions_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            app.junk_suggestions_rx = None;
+            if !suggestions.is_empty() {
+                let notice = format!(
+                    "{} folders look like junk ({} = review)",
+                    suggestions.len(),
+                    app.config.keys.label(config::Action::JunkSuggestions)
+                );
+                app.notification = Some(match app.notification.take() {
+                    Some(summary) => format!("{} | {}", summary, notice),
+                    None => notice,
+                });
+            }
+            app.junk_suggestions = suggestions;
+            app.dirty = true;
+        }
+
+        // Keep a background deletion preview only if its delete is still awaiting confirmation.
+        if let Ok(impact) = impact_rx.try_recv()
+            && matches!(mode.mode(), AppMode::ConfirmFileOp { op_type: FileOperation::Delete, .. })
+            && app.get_selected_file_entry().is_some_and(|file| file.path == impact.target)
+        {
+            app.delete_preview = Some(impact);
+            app.dirty = true;
+        }
+
+        // Draw UI, unless nothing changed or the last frame was just drawn.
+        let frame_due = last_draw.is_none_or(|drawn| drawn.elapsed() >= MIN_FRAME_INTERVAL);
+        if app.dirty && frame_due {
+            if let Some(announcer) = announcer.as_mut() {
+                announcer.observe(&app, mode.mode());
+            }
+            draw_app(&mut terminal, &app, mode.mode(), &spinner_chars)?;
+            app.dirty = false;
+            last_draw = Some(Instant::now());
         }
 
-        // Draw UI.
-        draw_app(&mut terminal, &app, &mode, &spinner_chars)?;
+        // Wait for input: until the next frame is due if one is pending, briefly while a
+        // listing or scan reports progress, and longer while idle.
+        let poll_timeout = if app.dirty {
+            last_draw.map_or(Duration::ZERO, |drawn| MIN_FRAME_INTERVAL.saturating_sub(drawn.elapsed()))
+        } else if mode.is_busy() || app.operations.is_active() || app.tasks.running() > 0 || app.preview_loading() {
+            MIN_FRAME_INTERVAL
+        } else {
+            IDLE_POLL_INTERVAL
+        };
 
         // Process key events.
-        if process_event(&mut app, &mut mode, &scan_tx, &progress_tx).await? {
+        if process_event(&mut app, &mut mode, poll_timeout, &scan_tx, &progress_tx, &impact_tx).await? {
             break;
         }
-
-        tokio::time::sleep(Duration::from_millis(200)).await;
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.observe_mode(mode.mode(), &app);
+        }
     }
 
     // Create a short delay to allow any in-progress tasks to complete gracefully
@@ -492,8 +2004,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
     
     // Clean up terminal state
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    match app.viewport {
+        Some(area) => {
+            // The summary takes the place of the UI, and stays in the scrollback
+            execute!(terminal.backend_mut(), DisableMouseCapture, MoveTo(0, area.y), Clear(ClearType::FromCursorDown))?;
+            println!("{}", ui::exit_summary(&app));
+        }
+        None => {
+            execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+            // Evidence of what the session did, unless it only looked around
+            if !app.session.is_empty() {
+                println!("{}", ui::exit_summary(&app));
+            }
+        }
+    }
     terminal.show_cursor()?;
+
+    if let Some(instructions) = &app.update_instructions {
+        println!("{}", instructions);
+    }
     
     // Return success
     Ok(())
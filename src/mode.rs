@@ -0,0 +1,1640 @@
+use crate::platform::docker::DockerResource;
+
+/// Application mode. `Normal` is the idle browsing state; every other mode is
+/// either a background task in progress or a popup waiting for a key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppMode {
+    Normal,
+    ConfirmEject(usize),
+    /// Confirm ejecting every ejectable device
+    ConfirmEjectAll,
+    /// Confirm starting a full (or incremental) scan while on battery
+    ConfirmBatteryScan { incremental: bool },
+    /// Confirm emptying the trash of the device at this index
+    ConfirmEmptyTrash(usize),
+    /// A normal eject failed; offers retry, unmount-only or force eject. `holders` lists the
+    /// processes using the volume, or is None when they couldn't be determined.
+    EjectFailed { device_index: usize, error: String, holders: Option<Vec<String>> },
+    Ejected(String),
+    /// An eject, clean or other action the user waits for runs in the background; the popup
+    /// says what it is doing until the result replaces it
+    Working(String),
+    Scanning { device_index: usize, spinner_index: usize },
+    FullScan { device_index: usize, spinner_index: usize },
+    ConfirmFileOp {
+        op_type: FileOperation,
+        file_index: usize,
+        target_path: Option<String> // For copy/move operations
+    },
+    /// Type-ahead device selection; `previous` is restored if the search is cancelled
+    DeviceSearch { query: String, previous: usize },
+    /// Command line opened with ':'
+    Command { input: String },
+    /// Prompt for a new name of the file or directory at `path`, prefilled with its name
+    Rename { path: String, input: String },
+    /// Prompt for the archive to pack the file or directory at `path` into; the extension of
+    /// `input` picks the format
+    Archive { path: String, input: String },
+    /// Prompt for the directory to unpack the archive at `path` into
+    Extract { path: String, input: String },
+    /// Scan history overlay, scrolled down by `offset` rows
+    History { offset: usize },
+    /// What the last scan couldn't read, scrolled down by `offset` rows
+    ScanErrors { offset: usize },
+    /// Large files unused for a while, scrolled down by `offset` rows
+    StaleFiles { offset: usize },
+    /// What changed between the last two scans of a device, scrolled down by `offset` rows
+    ScanChanges { offset: usize },
+    /// Symlinks whose targets are gone, scrolled down by `offset` rows
+    BrokenLinks { offset: usize },
+    /// Confirm deleting all the broken symlinks found
+    ConfirmBrokenLinkDelete,
+    /// Groups of files with the same content, with the file at `selected`, counted across the
+    /// groups, highlighted
+    Duplicates { selected: usize },
+    /// Dry run of the planned removal of duplicates, to confirm; `selected` is the file
+    /// highlighted in the view it came from
+    ConfirmDuplicateResolve { selected: usize },
+    /// Candidates for reaching the free space goal, with the one at `selected` highlighted
+    Goal { selected: usize },
+    /// Confirm deleting the accepted candidates of the goal; `selected` is the candidate
+    /// highlighted in the view it came from
+    ConfirmGoal { selected: usize },
+    /// Leaderboard of the largest directories of the full scan, with the one at `selected`
+    /// highlighted
+    LargestDirs { selected: usize },
+    /// Background copies, moves and deletes, with the one at `selected` highlighted
+    Operations { selected: usize },
+    /// Directories suggested as junk locations, with the one at `selected` highlighted
+    JunkSuggestions { selected: usize },
+    /// Choosing a segment of the breadcrumb path bar to jump to, with the one at `selected`
+    /// highlighted (0 is the device root)
+    Breadcrumbs { selected: usize },
+    /// Confirm undoing the most recent delete or move
+    ConfirmUndo,
+    /// Confirm removing the build artifacts of the selected projects
+    ConfirmClean,
+    /// Confirm deleting all junk files of a folder in the junk folder view
+    ConfirmFolderDelete { folder_index: usize },
+    /// Choose which kinds of Docker data to prune, then confirm
+    ConfirmDockerPrune { selected: Vec<DockerResource> },
+    /// Choose which browser profiles (indices into the found profiles) to clear the cache of, then confirm
+    ConfirmBrowserCacheClear { selected: Vec<usize> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOperation {
+    Copy,
+    Move,
+    Delete,
+    /// Pack into a zip or tar.gz archive at the target path
+    Archive,
+    /// Unpack a zip, tar.gz or 7z archive into the target directory
+    Extract,
+    /// Overwrite the contents `passes` times, then delete for good
+    SecureDelete { passes: u32 },
+}
+
+/// Owns the current `AppMode` and is the only place allowed to change it.
+///
+/// Each transition method checks the mode it starts from and returns `false`
+/// (leaving the mode untouched) when the transition is not valid there, so
+/// stray key presses or late background messages can never push the app into
+/// an inconsistent state.
+#[derive(Debug, Clone)]
+pub struct ModeMachine {
+    mode: AppMode,
+}
+
+impl Default for ModeMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModeMachine {
+    pub fn new() -> Self {
+        ModeMachine { mode: AppMode::Normal }
+    }
+
+    pub fn mode(&self) -> &AppMode {
+        &self.mode
+    }
+
+    pub fn is_normal(&self) -> bool {
+        self.mode == AppMode::Normal
+    }
+
+    /// Whether a background listing or scan is running.
+    pub fn is_busy(&self) -> bool {
+        matches!(self.mode, AppMode::Scanning { .. } | AppMode::FullScan { .. })
+    }
+
+    /// Advance the spinner of a running listing or scan.
+    pub fn tick_spinner(&mut self, spinner_len: usize) {
+        if let AppMode::Scanning { spinner_index, .. } | AppMode::FullScan { spinner_index, .. } = &mut self.mode {
+            *spinner_index = (*spinner_index + 1) % spinner_len.max(1);
+        }
+    }
+
+    /// Normal/Scanning -> Scanning. A listing may restart itself when the selected device changes.
+    pub fn start_listing(&mut self, device_index: usize) -> bool {
+        match self.mode {
+            AppMode::Normal | AppMode::Scanning { .. } => {
+                self.mode = AppMode::Scanning { device_index, spinner_index: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scanning -> Normal once the listing has arrived.
+    pub fn finish_listing(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::Scanning { .. }))
+    }
+
+    /// Normal -> FullScan.
+    pub fn start_full_scan(&mut self, device_index: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::FullScan { device_index, spinner_index: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// FullScan -> Normal once the scan results have arrived.
+    pub fn finish_full_scan(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::FullScan { .. }))
+    }
+
+    /// Normal -> ConfirmEject.
+    pub fn request_eject(&mut self, device_index: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmEject(device_index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal/Command -> ConfirmEjectAll.
+    pub fn request_eject_all(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal | AppMode::Command { .. } => {
+                self.mode = AppMode::ConfirmEjectAll;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmBatteryScan.
+    pub fn request_battery_scan(&mut self, incremental: bool) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmBatteryScan { incremental };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmEmptyTrash.
+    pub fn request_empty_trash(&mut self, device_index: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmEmptyTrash(device_index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ConfirmEject/EjectFailed/Working -> EjectFailed after an eject attempt failed.
+    pub fn eject_failed(&mut self, device_index: usize, error: String, holders: Option<Vec<String>>) -> bool {
+        match self.mode {
+            AppMode::ConfirmEject(_) | AppMode::EjectFailed { .. } | AppMode::Working(_) => {
+                self.mode = AppMode::EjectFailed { device_index, error, holders };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmFileOp.
+    pub fn request_file_op(&mut self, op_type: FileOperation, file_index: usize, target_path: Option<String>) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmFileOp { op_type, file_index, target_path };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmFolderDelete.
+    pub fn request_folder_delete(&mut self, folder_index: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmFolderDelete { folder_index };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ConfirmFolderDelete -> FullScan: the deletion runs in the background and reports its
+    /// progress like a scan.
+    pub fn start_folder_delete(&mut self, device_index: usize) -> bool {
+        match self.mode {
+            AppMode::ConfirmFolderDelete { .. } => {
+                self.mode = AppMode::FullScan { device_index, spinner_index: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ConfirmFileOp -> Normal: the copy, move or delete was queued and runs in the background.
+    pub fn queue_file_op(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::ConfirmFileOp { .. }))
+    }
+
+    /// Normal -> ConfirmClean.
+    pub fn request_clean(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmClean;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmDockerPrune with `selected` checked.
+    pub fn request_docker_prune(&mut self, selected: Vec<DockerResource>) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmDockerPrune { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Check or uncheck a resource in the Docker prune dialog.
+    pub fn toggle_docker_resource(&mut self, resource: DockerResource) -> bool {
+        match &mut self.mode {
+            AppMode::ConfirmDockerPrune { selected } => {
+                if let Some(index) = selected.iter().position(|r| *r == resource) {
+                    selected.remove(index);
+                } else {
+                    selected.push(resource);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmBrowserCacheClear with the profiles in `selected` checked.
+    pub fn request_browser_cache_clear(&mut self, selected: Vec<usize>) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmBrowserCacheClear { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Check or uncheck a profile in the browser cache dialog.
+    pub fn toggle_browser_profile(&mut self, profile: usize) -> bool {
+        match &mut self.mode {
+            AppMode::ConfirmBrowserCacheClear { selected } => {
+                if let Some(index) = selected.iter().position(|p| *p == profile) {
+                    selected.remove(index);
+                } else {
+                    selected.push(profile);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> DeviceSearch with an empty query.
+    pub fn start_device_search(&mut self, current_selection: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::DeviceSearch { query: String::new(), previous: current_selection };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the query of a running device search.
+    pub fn set_search_query(&mut self, new_query: String) -> bool {
+        match &mut self.mode {
+            AppMode::DeviceSearch { query, .. } => {
+                *query = new_query;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// DeviceSearch -> Normal, keeping the device the search jumped to.
+    pub fn finish_device_search(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::DeviceSearch { .. }))
+    }
+
+    /// Normal -> Command with an empty input.
+    pub fn start_command(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Command { input: String::new() };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the input of the open command line.
+    pub fn set_command_input(&mut self, new_input: String) -> bool {
+        match &mut self.mode {
+            AppMode::Command { input } => {
+                *input = new_input;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> Rename, with the prompt prefilled with the entry's current name.
+    pub fn start_rename(&mut self, path: String, name: String) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Rename { path, input: name };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the input of the rename prompt.
+    pub fn set_rename_input(&mut self, new_input: String) -> bool {
+        match &mut self.mode {
+            AppMode::Rename { input, .. } => {
+                *input = new_input;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Rename -> Normal after renaming.
+    pub fn finish_rename(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::Rename { .. }))
+    }
+
+    /// Normal -> Archive, with the prompt prefilled with a default destination.
+    pub fn start_archive(&mut self, path: String, destination: String) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Archive { path, input: destination };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the destination in the archive prompt.
+    pub fn set_archive_input(&mut self, new_input: String) -> bool {
+        match &mut self.mode {
+            AppMode::Archive { input, .. } => {
+                *input = new_input;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Archive -> Normal after queueing the archive.
+    pub fn finish_archive(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::Archive { .. }))
+    }
+
+    /// Normal -> Extract, with the prompt prefilled with a default destination.
+    pub fn start_extract(&mut self, path: String, destination: String) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Extract { path, input: destination };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the destination in the extract prompt.
+    pub fn set_extract_input(&mut self, new_input: String) -> bool {
+        match &mut self.mode {
+            AppMode::Extract { input, .. } => {
+                *input = new_input;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Extract -> Normal after queueing the extraction.
+    pub fn finish_extract(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::Extract { .. }))
+    }
+
+    /// Command -> Normal after running (or clearing) the command.
+    pub fn finish_command(&mut self) -> bool {
+        self.return_to_normal_from(|mode| matches!(mode, AppMode::Command { .. }))
+    }
+
+    /// Normal/Command -> History, scrolled to the top.
+    pub fn open_history(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal | AppMode::Command { .. } => {
+                self.mode = AppMode::History { offset: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scroll the history overlay.
+    pub fn scroll_history(&mut self, new_offset: usize) -> bool {
+        match &mut self.mode {
+            AppMode::History { offset } => {
+                *offset = new_offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal/Command -> ScanErrors, scrolled to the top.
+    pub fn open_scan_errors(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal | AppMode::Command { .. } => {
+                self.mode = AppMode::ScanErrors { offset: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scroll the scan errors overlay.
+    pub fn scroll_scan_errors(&mut self, new_offset: usize) -> bool {
+        match &mut self.mode {
+            AppMode::ScanErrors { offset } => {
+                *offset = new_offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working/Normal -> StaleFiles, scrolled to the top, once the search for them is done.
+    pub fn open_stale_files(&mut self) -> bool {
+        match self.mode {
+            AppMode::Working(_) | AppMode::Normal => {
+                self.mode = AppMode::StaleFiles { offset: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scroll the stale files overlay.
+    pub fn scroll_stale_files(&mut self, new_offset: usize) -> bool {
+        match &mut self.mode {
+            AppMode::StaleFiles { offset } => {
+                *offset = new_offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working/Normal -> ScanChanges, scrolled to the top, once the scans are compared.
+    pub fn open_scan_changes(&mut self) -> bool {
+        match self.mode {
+            AppMode::Working(_) | AppMode::Normal => {
+                self.mode = AppMode::ScanChanges { offset: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scroll the scan changes overlay.
+    pub fn scroll_scan_changes(&mut self, new_offset: usize) -> bool {
+        match &mut self.mode {
+            AppMode::ScanChanges { offset } => {
+                *offset = new_offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working/Normal -> BrokenLinks, scrolled to the top, once the search for them is done.
+    pub fn open_broken_links(&mut self) -> bool {
+        match self.mode {
+            AppMode::Working(_) | AppMode::Normal => {
+                self.mode = AppMode::BrokenLinks { offset: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scroll the broken links overlay.
+    pub fn scroll_broken_links(&mut self, new_offset: usize) -> bool {
+        match &mut self.mode {
+            AppMode::BrokenLinks { offset } => {
+                *offset = new_offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// BrokenLinks -> ConfirmBrokenLinkDelete.
+    pub fn request_broken_link_delete(&mut self) -> bool {
+        match self.mode {
+            AppMode::BrokenLinks { .. } => {
+                self.mode = AppMode::ConfirmBrokenLinkDelete;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working/Normal -> Duplicates, with the first file highlighted, once they are found.
+    pub fn open_duplicates(&mut self) -> bool {
+        match self.mode {
+            AppMode::Working(_) | AppMode::Normal => {
+                self.mode = AppMode::Duplicates { selected: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another file in the duplicates view.
+    pub fn select_duplicate(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::Duplicates { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Duplicates -> ConfirmDuplicateResolve, once a removal is planned.
+    pub fn request_duplicate_resolve(&mut self) -> bool {
+        match self.mode {
+            AppMode::Duplicates { selected } => {
+                self.mode = AppMode::ConfirmDuplicateResolve { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ConfirmDuplicateResolve -> Duplicates, to pick another resolution.
+    pub fn review_duplicates(&mut self) -> bool {
+        match self.mode {
+            AppMode::ConfirmDuplicateResolve { selected } => {
+                self.mode = AppMode::Duplicates { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working/Normal -> Goal, with the first candidate highlighted, once they are found.
+    pub fn open_goal(&mut self) -> bool {
+        match self.mode {
+            AppMode::Working(_) | AppMode::Normal => {
+                self.mode = AppMode::Goal { selected: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another candidate of the goal.
+    pub fn select_goal_candidate(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::Goal { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Goal -> ConfirmGoal.
+    pub fn request_goal_run(&mut self) -> bool {
+        match self.mode {
+            AppMode::Goal { selected } => {
+                self.mode = AppMode::ConfirmGoal { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ConfirmGoal -> Goal, to change what is accepted.
+    pub fn review_goal(&mut self) -> bool {
+        match self.mode {
+            AppMode::ConfirmGoal { selected } => {
+                self.mode = AppMode::Goal { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal/Command -> LargestDirs, with the largest directory highlighted.
+    pub fn open_largest_dirs(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal | AppMode::Command { .. } => {
+                self.mode = AppMode::LargestDirs { selected: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another directory in the leaderboard of the largest directories.
+    pub fn select_largest_dir(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::LargestDirs { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> Operations, with the first operation highlighted.
+    pub fn open_operations(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Operations { selected: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> JunkSuggestions, with the first suggestion highlighted.
+    pub fn open_junk_suggestions(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::JunkSuggestions { selected: 0 };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another suggestion in the junk suggestions panel.
+    pub fn select_junk_suggestion(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::JunkSuggestions { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> Breadcrumbs, with the segment at `selected` highlighted.
+    pub fn open_breadcrumbs(&mut self, selected: usize) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::Breadcrumbs { selected };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another segment of the breadcrumb path bar.
+    pub fn select_breadcrumb(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::Breadcrumbs { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal -> ConfirmUndo.
+    pub fn request_undo(&mut self) -> bool {
+        match self.mode {
+            AppMode::Normal => {
+                self.mode = AppMode::ConfirmUndo;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Highlight another operation in the operations panel.
+    pub fn select_operation(&mut self, index: usize) -> bool {
+        match &mut self.mode {
+            AppMode::Operations { selected } => {
+                *selected = index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Normal or the confirmation that started it -> Working, while a background task runs.
+    pub fn start_task(&mut self, description: impl Into<String>) -> bool {
+        match self.mode {
+            AppMode::Normal
+            | AppMode::ConfirmEject(_)
+            | AppMode::ConfirmEjectAll
+            | AppMode::ConfirmEmptyTrash(_)
+            | AppMode::EjectFailed { .. }
+            | AppMode::ConfirmClean
+            | AppMode::ConfirmBrokenLinkDelete
+            | AppMode::ConfirmDuplicateResolve { .. }
+            | AppMode::ConfirmGoal { .. }
+            | AppMode::ConfirmDockerPrune { .. }
+            | AppMode::ConfirmBrowserCacheClear { .. } => {
+                self.mode = AppMode::Working(description.into());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Working -> result popup once the task is done. Returns false if the popup was closed
+    /// meanwhile, so the result is shown some other way.
+    pub fn finish_task(&mut self, message: impl Into<String>) -> bool {
+        match self.mode {
+            AppMode::Working(_) => {
+                self.mode = AppMode::Ejected(message.into());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Any mode -> result popup. Used for eject/file operation outcomes and scan failures.
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.mode = AppMode::Ejected(message.into());
+    }
+
+    /// Result popup -> Normal on any key press.
+    pub fn dismiss(&mut self) -> bool {
+        match self.mode {
+            AppMode::Ejected(_) => {
+                self.mode = AppMode::Normal;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Abort a confirmation dialog or a running listing/scan and return to Normal.
+    pub fn cancel(&mut self) -> bool {
+        match self.mode {
+            AppMode::ConfirmEject(_)
+            | AppMode::ConfirmEjectAll
+            | AppMode::ConfirmBatteryScan { .. }
+            | AppMode::ConfirmEmptyTrash(_)
+            | AppMode::EjectFailed { .. }
+            | AppMode::ConfirmFileOp { .. }
+            | AppMode::DeviceSearch { .. }
+            | AppMode::Command { .. }
+            | AppMode::Rename { .. }
+            | AppMode::Archive { .. }
+            | AppMode::Extract { .. }
+            | AppMode::History { .. }
+            | AppMode::ScanErrors { .. }
+            | AppMode::StaleFiles { .. }
+            | AppMode::ScanChanges { .. }
+            | AppMode::BrokenLinks { .. }
+            | AppMode::ConfirmBrokenLinkDelete
+            | AppMode::Duplicates { .. }
+            | AppMode::ConfirmDuplicateResolve { .. }
+            | AppMode::Goal { .. }
+            | AppMode::ConfirmGoal { .. }
+            | AppMode::LargestDirs { .. }
+            | AppMode::Operations { .. }
+            | AppMode::JunkSuggestions { .. }
+            | AppMode::Breadcrumbs { .. }
+            | AppMode::ConfirmUndo
+            | AppMode::ConfirmClean
+            | AppMode::ConfirmFolderDelete { .. }
+            | AppMode::ConfirmDockerPrune { .. }
+            | AppMode::ConfirmBrowserCacheClear { .. }
+            | AppMode::Scanning { .. }
+            | AppMode::FullScan { .. }
+            | AppMode::Working(_) => {
+                self.mode = AppMode::Normal;
+                true
+            }
+            AppMode::Normal | AppMode::Ejected(_) => false,
+        }
+    }
+
+    fn return_to_normal_from(&mut self, is_source: impl Fn(&AppMode) -> bool) -> bool {
+        if is_source(&self.mode) {
+            self.mode = AppMode::Normal;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One representative of every `AppMode` variant.
+    fn all_modes() -> Vec<AppMode> {
+        vec![
+            AppMode::Normal,
+            AppMode::ConfirmEject(1),
+            AppMode::ConfirmEjectAll,
+            AppMode::ConfirmBatteryScan { incremental: false },
+            AppMode::ConfirmEmptyTrash(1),
+            AppMode::EjectFailed {
+                device_index: 1,
+                error: "busy".to_string(),
+                holders: Some(vec!["Finder (42)".to_string()]),
+            },
+            AppMode::Ejected("done".to_string()),
+            AppMode::Working("Ejecting USB".to_string()),
+            AppMode::Scanning { device_index: 0, spinner_index: 3 },
+            AppMode::FullScan { device_index: 0, spinner_index: 3 },
+            AppMode::ConfirmFileOp {
+                op_type: FileOperation::Delete,
+                file_index: 2,
+                target_path: None,
+            },
+            AppMode::DeviceSearch { query: "mac".to_string(), previous: 1 },
+            AppMode::Command { input: "hist".to_string() },
+            AppMode::Rename { path: "/tmp/a.txt".to_string(), input: "b.txt".to_string() },
+            AppMode::Archive { path: "/tmp/a".to_string(), input: "/tmp/a.zip".to_string() },
+            AppMode::Extract { path: "/tmp/a.zip".to_string(), input: "/tmp/a".to_string() },
+            AppMode::History { offset: 2 },
+            AppMode::ScanErrors { offset: 3 },
+            AppMode::StaleFiles { offset: 1 },
+            AppMode::ScanChanges { offset: 4 },
+            AppMode::BrokenLinks { offset: 2 },
+            AppMode::ConfirmBrokenLinkDelete,
+            AppMode::Duplicates { selected: 3 },
+            AppMode::ConfirmDuplicateResolve { selected: 1 },
+            AppMode::Goal { selected: 4 },
+            AppMode::ConfirmGoal { selected: 2 },
+            AppMode::LargestDirs { selected: 2 },
+            AppMode::Operations { selected: 1 },
+            AppMode::JunkSuggestions { selected: 2 },
+            AppMode::Breadcrumbs { selected: 1 },
+            AppMode::ConfirmUndo,
+            AppMode::ConfirmClean,
+            AppMode::ConfirmFolderDelete { folder_index: 4 },
+            AppMode::ConfirmDockerPrune { selected: vec![DockerResource::Images] },
+            AppMode::ConfirmBrowserCacheClear { selected: vec![0] },
+        ]
+    }
+
+    fn machine_in(mode: AppMode) -> ModeMachine {
+        ModeMachine { mode }
+    }
+
+    /// Applies `transition` from every mode and checks it succeeds exactly where `allowed`
+    /// says so, ends in `expected` when it does, and leaves the mode untouched otherwise.
+    fn check_transition(
+        transition: impl Fn(&mut ModeMachine) -> bool,
+        allowed: impl Fn(&AppMode) -> bool,
+        expected: AppMode,
+    ) {
+        for start in all_modes() {
+            let mut machine = machine_in(start.clone());
+            let applied = transition(&mut machine);
+            if allowed(&start) {
+                assert!(applied, "transition should apply from {:?}", start);
+                assert_eq!(machine.mode(), &expected, "wrong target from {:?}", start);
+            } else {
+                assert!(!applied, "transition should be rejected from {:?}", start);
+                assert_eq!(machine.mode(), &start, "mode changed from {:?}", start);
+            }
+        }
+    }
+
+    #[test]
+    fn starts_in_normal() {
+        let machine = ModeMachine::new();
+        assert!(machine.is_normal());
+        assert!(!machine.is_busy());
+    }
+
+    #[test]
+    fn start_listing_from_normal_or_scanning() {
+        check_transition(
+            |m| m.start_listing(4),
+            |mode| matches!(mode, AppMode::Normal | AppMode::Scanning { .. }),
+            AppMode::Scanning { device_index: 4, spinner_index: 0 },
+        );
+    }
+
+    #[test]
+    fn finish_listing_only_from_scanning() {
+        check_transition(
+            |m| m.finish_listing(),
+            |mode| matches!(mode, AppMode::Scanning { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn start_full_scan_only_from_normal() {
+        check_transition(
+            |m| m.start_full_scan(2),
+            |mode| *mode == AppMode::Normal,
+            AppMode::FullScan { device_index: 2, spinner_index: 0 },
+        );
+    }
+
+    #[test]
+    fn finish_full_scan_only_from_full_scan() {
+        check_transition(
+            |m| m.finish_full_scan(),
+            |mode| matches!(mode, AppMode::FullScan { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn request_eject_only_from_normal() {
+        check_transition(
+            |m| m.request_eject(3),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmEject(3),
+        );
+    }
+
+    #[test]
+    fn eject_failed_only_while_ejecting() {
+        check_transition(
+            |m| m.eject_failed(1, "in use".to_string(), None),
+            |mode| matches!(mode, AppMode::ConfirmEject(_) | AppMode::EjectFailed { .. } | AppMode::Working(_)),
+            AppMode::EjectFailed { device_index: 1, error: "in use".to_string(), holders: None },
+        );
+    }
+
+    #[test]
+    fn request_file_op_only_from_normal() {
+        check_transition(
+            |m| m.request_file_op(FileOperation::Copy, 5, Some("/tmp/x".to_string())),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmFileOp {
+                op_type: FileOperation::Copy,
+                file_index: 5,
+                target_path: Some("/tmp/x".to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn request_eject_all_from_normal_or_command() {
+        check_transition(
+            |m| m.request_eject_all(),
+            |mode| matches!(mode, AppMode::Normal | AppMode::Command { .. }),
+            AppMode::ConfirmEjectAll,
+        );
+    }
+
+    #[test]
+    fn request_battery_scan_only_from_normal() {
+        check_transition(
+            |m| m.request_battery_scan(true),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmBatteryScan { incremental: true },
+        );
+    }
+
+    #[test]
+    fn request_empty_trash_only_from_normal() {
+        check_transition(
+            |m| m.request_empty_trash(2),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmEmptyTrash(2),
+        );
+    }
+
+    #[test]
+    fn request_folder_delete_only_from_normal() {
+        check_transition(
+            |m| m.request_folder_delete(2),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmFolderDelete { folder_index: 2 },
+        );
+    }
+
+    #[test]
+    fn start_folder_delete_only_after_confirming() {
+        check_transition(
+            |m| m.start_folder_delete(1),
+            |mode| matches!(mode, AppMode::ConfirmFolderDelete { .. }),
+            AppMode::FullScan { device_index: 1, spinner_index: 0 },
+        );
+    }
+
+    #[test]
+    fn queue_file_op_only_after_confirming() {
+        check_transition(
+            |m| m.queue_file_op(),
+            |mode| matches!(mode, AppMode::ConfirmFileOp { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn request_clean_only_from_normal() {
+        check_transition(
+            |m| m.request_clean(),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmClean,
+        );
+    }
+
+    #[test]
+    fn request_docker_prune_only_from_normal() {
+        check_transition(
+            |m| m.request_docker_prune(vec![DockerResource::BuildCache]),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmDockerPrune { selected: vec![DockerResource::BuildCache] },
+        );
+    }
+
+    #[test]
+    fn toggle_docker_resource_only_in_prune_dialog() {
+        check_transition(
+            |m| m.toggle_docker_resource(DockerResource::Volumes),
+            |mode| matches!(mode, AppMode::ConfirmDockerPrune { .. }),
+            AppMode::ConfirmDockerPrune { selected: vec![DockerResource::Images, DockerResource::Volumes] },
+        );
+        let mut machine = machine_in(AppMode::ConfirmDockerPrune { selected: vec![DockerResource::Images] });
+        assert!(machine.toggle_docker_resource(DockerResource::Images));
+        assert_eq!(machine.mode(), &AppMode::ConfirmDockerPrune { selected: Vec::new() });
+    }
+
+    #[test]
+    fn request_browser_cache_clear_only_from_normal() {
+        check_transition(
+            |m| m.request_browser_cache_clear(vec![1]),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmBrowserCacheClear { selected: vec![1] },
+        );
+    }
+
+    #[test]
+    fn toggle_browser_profile_only_in_cache_dialog() {
+        check_transition(
+            |m| m.toggle_browser_profile(2),
+            |mode| matches!(mode, AppMode::ConfirmBrowserCacheClear { .. }),
+            AppMode::ConfirmBrowserCacheClear { selected: vec![0, 2] },
+        );
+        let mut machine = machine_in(AppMode::ConfirmBrowserCacheClear { selected: vec![0] });
+        assert!(machine.toggle_browser_profile(0));
+        assert_eq!(machine.mode(), &AppMode::ConfirmBrowserCacheClear { selected: Vec::new() });
+    }
+
+    #[test]
+    fn start_device_search_only_from_normal() {
+        check_transition(
+            |m| m.start_device_search(2),
+            |mode| *mode == AppMode::Normal,
+            AppMode::DeviceSearch { query: String::new(), previous: 2 },
+        );
+    }
+
+    #[test]
+    fn set_search_query_only_while_searching() {
+        check_transition(
+            |m| m.set_search_query("usb".to_string()),
+            |mode| matches!(mode, AppMode::DeviceSearch { .. }),
+            AppMode::DeviceSearch { query: "usb".to_string(), previous: 1 },
+        );
+    }
+
+    #[test]
+    fn finish_device_search_only_while_searching() {
+        check_transition(
+            |m| m.finish_device_search(),
+            |mode| matches!(mode, AppMode::DeviceSearch { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn start_command_only_from_normal() {
+        check_transition(
+            |m| m.start_command(),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Command { input: String::new() },
+        );
+    }
+
+    #[test]
+    fn start_rename_only_from_normal() {
+        check_transition(
+            |m| m.start_rename("/tmp/a.txt".to_string(), "a.txt".to_string()),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Rename { path: "/tmp/a.txt".to_string(), input: "a.txt".to_string() },
+        );
+    }
+
+    #[test]
+    fn set_rename_input_only_in_rename() {
+        check_transition(
+            |m| m.set_rename_input("c.txt".to_string()),
+            |mode| matches!(mode, AppMode::Rename { .. }),
+            AppMode::Rename { path: "/tmp/a.txt".to_string(), input: "c.txt".to_string() },
+        );
+    }
+
+    #[test]
+    fn finish_rename_only_from_rename() {
+        check_transition(
+            |m| m.finish_rename(),
+            |mode| matches!(mode, AppMode::Rename { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn start_archive_only_from_normal() {
+        check_transition(
+            |m| m.start_archive("/tmp/a".to_string(), "/tmp/a.zip".to_string()),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Archive { path: "/tmp/a".to_string(), input: "/tmp/a.zip".to_string() },
+        );
+    }
+
+    #[test]
+    fn set_archive_input_only_in_archive() {
+        check_transition(
+            |m| m.set_archive_input("/tmp/a.tar.gz".to_string()),
+            |mode| matches!(mode, AppMode::Archive { .. }),
+            AppMode::Archive { path: "/tmp/a".to_string(), input: "/tmp/a.tar.gz".to_string() },
+        );
+    }
+
+    #[test]
+    fn finish_archive_only_from_archive() {
+        check_transition(
+            |m| m.finish_archive(),
+            |mode| matches!(mode, AppMode::Archive { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn start_extract_only_from_normal() {
+        check_transition(
+            |m| m.start_extract("/tmp/a.zip".to_string(), "/tmp/a".to_string()),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Extract { path: "/tmp/a.zip".to_string(), input: "/tmp/a".to_string() },
+        );
+    }
+
+    #[test]
+    fn set_extract_input_only_in_extract() {
+        check_transition(
+            |m| m.set_extract_input("/tmp/b".to_string()),
+            |mode| matches!(mode, AppMode::Extract { .. }),
+            AppMode::Extract { path: "/tmp/a.zip".to_string(), input: "/tmp/b".to_string() },
+        );
+    }
+
+    #[test]
+    fn finish_extract_only_from_extract() {
+        check_transition(
+            |m| m.finish_extract(),
+            |mode| matches!(mode, AppMode::Extract { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn set_command_input_only_in_command() {
+        check_transition(
+            |m| m.set_command_input("history".to_string()),
+            |mode| matches!(mode, AppMode::Command { .. }),
+            AppMode::Command { input: "history".to_string() },
+        );
+    }
+
+    #[test]
+    fn finish_command_only_in_command() {
+        check_transition(
+            |m| m.finish_command(),
+            |mode| matches!(mode, AppMode::Command { .. }),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn open_history_from_normal_or_command() {
+        check_transition(
+            |m| m.open_history(),
+            |mode| matches!(mode, AppMode::Normal | AppMode::Command { .. }),
+            AppMode::History { offset: 0 },
+        );
+    }
+
+    #[test]
+    fn scroll_history_only_in_history() {
+        check_transition(
+            |m| m.scroll_history(7),
+            |mode| matches!(mode, AppMode::History { .. }),
+            AppMode::History { offset: 7 },
+        );
+    }
+
+    #[test]
+    fn open_scan_errors_from_normal_or_command() {
+        check_transition(
+            |m| m.open_scan_errors(),
+            |mode| matches!(mode, AppMode::Normal | AppMode::Command { .. }),
+            AppMode::ScanErrors { offset: 0 },
+        );
+    }
+
+    #[test]
+    fn scroll_scan_errors_only_in_scan_errors() {
+        check_transition(
+            |m| m.scroll_scan_errors(4),
+            |mode| matches!(mode, AppMode::ScanErrors { .. }),
+            AppMode::ScanErrors { offset: 4 },
+        );
+    }
+
+    #[test]
+    fn open_stale_files_from_working_or_normal() {
+        check_transition(
+            |m| m.open_stale_files(),
+            |mode| matches!(mode, AppMode::Working(_) | AppMode::Normal),
+            AppMode::StaleFiles { offset: 0 },
+        );
+    }
+
+    #[test]
+    fn scroll_stale_files_only_in_stale_files() {
+        check_transition(
+            |m| m.scroll_stale_files(6),
+            |mode| matches!(mode, AppMode::StaleFiles { .. }),
+            AppMode::StaleFiles { offset: 6 },
+        );
+    }
+
+    #[test]
+    fn open_scan_changes_from_working_or_normal() {
+        check_transition(
+            |m| m.open_scan_changes(),
+            |mode| matches!(mode, AppMode::Working(_) | AppMode::Normal),
+            AppMode::ScanChanges { offset: 0 },
+        );
+    }
+
+    #[test]
+    fn scroll_scan_changes_only_in_scan_changes() {
+        check_transition(
+            |m| m.scroll_scan_changes(5),
+            |mode| matches!(mode, AppMode::ScanChanges { .. }),
+            AppMode::ScanChanges { offset: 5 },
+        );
+    }
+
+    #[test]
+    fn open_broken_links_from_working_or_normal() {
+        check_transition(
+            |m| m.open_broken_links(),
+            |mode| matches!(mode, AppMode::Working(_) | AppMode::Normal),
+            AppMode::BrokenLinks { offset: 0 },
+        );
+    }
+
+    #[test]
+    fn scroll_broken_links_only_in_broken_links() {
+        check_transition(
+            |m| m.scroll_broken_links(3),
+            |mode| matches!(mode, AppMode::BrokenLinks { .. }),
+            AppMode::BrokenLinks { offset: 3 },
+        );
+    }
+
+    #[test]
+    fn request_broken_link_delete_from_broken_links() {
+        check_transition(
+            |m| m.request_broken_link_delete(),
+            |mode| matches!(mode, AppMode::BrokenLinks { .. }),
+            AppMode::ConfirmBrokenLinkDelete,
+        );
+    }
+
+    #[test]
+    fn open_duplicates_from_working_or_normal() {
+        check_transition(
+            |m| m.open_duplicates(),
+            |mode| matches!(mode, AppMode::Working(_) | AppMode::Normal),
+            AppMode::Duplicates { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn select_duplicate_only_in_duplicates() {
+        check_transition(
+            |m| m.select_duplicate(5),
+            |mode| matches!(mode, AppMode::Duplicates { .. }),
+            AppMode::Duplicates { selected: 5 },
+        );
+    }
+
+    #[test]
+    fn request_duplicate_resolve_from_duplicates() {
+        check_transition(
+            |m| m.request_duplicate_resolve(),
+            |mode| matches!(mode, AppMode::Duplicates { .. }),
+            AppMode::ConfirmDuplicateResolve { selected: 3 },
+        );
+    }
+
+    #[test]
+    fn review_duplicates_from_their_confirmation() {
+        check_transition(
+            |m| m.review_duplicates(),
+            |mode| matches!(mode, AppMode::ConfirmDuplicateResolve { .. }),
+            AppMode::Duplicates { selected: 1 },
+        );
+    }
+
+    #[test]
+    fn open_goal_from_working_or_normal() {
+        check_transition(
+            |m| m.open_goal(),
+            |mode| matches!(mode, AppMode::Working(_) | AppMode::Normal),
+            AppMode::Goal { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn select_goal_candidate_only_in_goal() {
+        check_transition(
+            |m| m.select_goal_candidate(6),
+            |mode| matches!(mode, AppMode::Goal { .. }),
+            AppMode::Goal { selected: 6 },
+        );
+    }
+
+    #[test]
+    fn request_goal_run_from_goal() {
+        check_transition(
+            |m| m.request_goal_run(),
+            |mode| matches!(mode, AppMode::Goal { .. }),
+            AppMode::ConfirmGoal { selected: 4 },
+        );
+    }
+
+    #[test]
+    fn review_goal_from_its_confirmation() {
+        check_transition(
+            |m| m.review_goal(),
+            |mode| matches!(mode, AppMode::ConfirmGoal { .. }),
+            AppMode::Goal { selected: 2 },
+        );
+    }
+
+    #[test]
+    fn open_largest_dirs_from_normal_or_command() {
+        check_transition(
+            |m| m.open_largest_dirs(),
+            |mode| matches!(mode, AppMode::Normal | AppMode::Command { .. }),
+            AppMode::LargestDirs { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn select_largest_dir_only_in_largest_dirs() {
+        check_transition(
+            |m| m.select_largest_dir(5),
+            |mode| matches!(mode, AppMode::LargestDirs { .. }),
+            AppMode::LargestDirs { selected: 5 },
+        );
+    }
+
+    #[test]
+    fn open_operations_only_from_normal() {
+        check_transition(
+            |m| m.open_operations(),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Operations { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn request_undo_only_from_normal() {
+        check_transition(
+            |m| m.request_undo(),
+            |mode| *mode == AppMode::Normal,
+            AppMode::ConfirmUndo,
+        );
+    }
+
+    #[test]
+    fn select_operation_only_in_operations() {
+        check_transition(
+            |m| m.select_operation(3),
+            |mode| matches!(mode, AppMode::Operations { .. }),
+            AppMode::Operations { selected: 3 },
+        );
+    }
+
+    #[test]
+    fn open_junk_suggestions_only_from_normal() {
+        check_transition(
+            |m| m.open_junk_suggestions(),
+            |mode| *mode == AppMode::Normal,
+            AppMode::JunkSuggestions { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn select_junk_suggestion_only_in_junk_suggestions() {
+        check_transition(
+            |m| m.select_junk_suggestion(3),
+            |mode| matches!(mode, AppMode::JunkSuggestions { .. }),
+            AppMode::JunkSuggestions { selected: 3 },
+        );
+    }
+
+    #[test]
+    fn open_breadcrumbs_only_from_normal() {
+        check_transition(
+            |m| m.open_breadcrumbs(2),
+            |mode| *mode == AppMode::Normal,
+            AppMode::Breadcrumbs { selected: 2 },
+        );
+    }
+
+    #[test]
+    fn select_breadcrumb_only_in_breadcrumbs() {
+        check_transition(
+            |m| m.select_breadcrumb(0),
+            |mode| matches!(mode, AppMode::Breadcrumbs { .. }),
+            AppMode::Breadcrumbs { selected: 0 },
+        );
+    }
+
+    #[test]
+    fn start_task_from_normal_or_its_confirmation() {
+        check_transition(
+            |m| m.start_task("Emptying the trash"),
+            |mode| {
+                matches!(
+                    mode,
+                    AppMode::Normal
+                        | AppMode::ConfirmEject(_)
+                        | AppMode::ConfirmEjectAll
+                        | AppMode::ConfirmEmptyTrash(_)
+                        | AppMode::EjectFailed { .. }
+                        | AppMode::ConfirmClean
+                        | AppMode::ConfirmBrokenLinkDelete
+                        | AppMode::ConfirmDuplicateResolve { .. }
+                        | AppMode::ConfirmGoal { .. }
+                        | AppMode::ConfirmDockerPrune { .. }
+                        | AppMode::ConfirmBrowserCacheClear { .. }
+                )
+            },
+            AppMode::Working("Emptying the trash".to_string()),
+        );
+    }
+
+    #[test]
+    fn finish_task_only_while_working() {
+        check_transition(
+            |m| m.finish_task("Emptied the trash"),
+            |mode| matches!(mode, AppMode::Working(_)),
+            AppMode::Ejected("Emptied the trash".to_string()),
+        );
+    }
+
+    #[test]
+    fn dismiss_only_from_message() {
+        check_transition(
+            |m| m.dismiss(),
+            |mode| matches!(mode, AppMode::Ejected(_)),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn cancel_from_dialogs_and_busy_modes() {
+        check_transition(
+            |m| m.cancel(),
+            |mode| !matches!(mode, AppMode::Normal | AppMode::Ejected(_)),
+            AppMode::Normal,
+        );
+    }
+
+    #[test]
+    fn show_message_from_every_mode() {
+        for start in all_modes() {
+            let mut machine = machine_in(start);
+            machine.show_message("hello");
+            assert_eq!(machine.mode(), &AppMode::Ejected("hello".to_string()));
+        }
+    }
+
+    #[test]
+    fn tick_spinner_wraps_and_ignores_idle_modes() {
+        for start in all_modes() {
+            let mut machine = machine_in(start.clone());
+            machine.tick_spinner(4);
+            match start {
+                AppMode::Scanning { device_index, .. } => {
+                    assert_eq!(machine.mode(), &AppMode::Scanning { device_index, spinner_index: 0 });
+                }
+                AppMode::FullScan { device_index, .. } => {
+                    assert_eq!(machine.mode(), &AppMode::FullScan { device_index, spinner_index: 0 });
+                }
+                other => assert_eq!(machine.mode(), &other),
+            }
+        }
+    }
+
+    #[test]
+    fn is_busy_only_while_scanning() {
+        for start in all_modes() {
+            let busy = matches!(start, AppMode::Scanning { .. } | AppMode::FullScan { .. });
+            assert_eq!(machine_in(start).is_busy(), busy);
+        }
+    }
+
+    #[test]
+    fn full_eject_flow() {
+        let mut machine = ModeMachine::new();
+        assert!(machine.request_eject(0));
+        assert!(!machine.start_full_scan(0));
+        assert!(machine.start_task("Ejecting USB"));
+        assert!(machine.finish_task("Ejected Device: USB successfully"));
+        assert!(!machine.cancel());
+        assert!(machine.dismiss());
+        assert!(machine.is_normal());
+    }
+
+    #[test]
+    fn hidden_task_flow() {
+        let mut machine = ModeMachine::new();
+        assert!(machine.request_empty_trash(0));
+        assert!(machine.start_task("Emptying the trash of USB"));
+        // Hiding the popup leaves the task running; its result no longer opens a popup
+        assert!(machine.cancel());
+        assert!(!machine.finish_task("Emptied the trash of USB"));
+        assert!(machine.is_normal());
+    }
+
+    #[test]
+    fn failed_eject_escalation_flow() {
+        let mut machine = ModeMachine::new();
+        assert!(!machine.eject_failed(0, "busy".to_string(), None));
+        assert!(machine.request_eject(0));
+        assert!(machine.eject_failed(0, "busy".to_string(), None));
+        // A failed retry updates the dialog in place
+        assert!(machine.eject_failed(0, "still busy".to_string(), Some(Vec::new())));
+        assert_eq!(
+            machine.mode(),
+            &AppMode::EjectFailed { device_index: 0, error: "still busy".to_string(), holders: Some(Vec::new()) }
+        );
+        assert!(machine.cancel());
+        assert!(machine.is_normal());
+    }
+}
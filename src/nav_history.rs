@@ -0,0 +1,89 @@
+/// Directories visited beyond this many steps back are forgotten.
+const MAX_STEPS: usize = 100;
+
+/// Directories listed on one device, to go back and forward between like in a browser.
+/// `None` stands for the device root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NavHistory {
+    back: Vec<Option<String>>,
+    forward: Vec<Option<String>>,
+}
+
+impl NavHistory {
+    /// Record leaving `from` for another directory, which drops the directories ahead.
+    pub fn visit(&mut self, from: Option<String>) {
+        if self.back.len() == MAX_STEPS {
+            self.back.remove(0);
+        }
+        self.back.push(from);
+        self.forward.clear();
+    }
+
+    /// The directory before `current`, which becomes the next one forward.
+    pub fn go_back(&mut self, current: Option<String>) -> Option<Option<String>> {
+        let dir = self.back.pop()?;
+        self.forward.push(current);
+        Some(dir)
+    }
+
+    /// The directory left by going back from it, with `current` back in the history.
+    pub fn go_forward(&mut self, current: Option<String>) -> Option<Option<String>> {
+        let dir = self.forward.pop()?;
+        self.back.push(current);
+        Some(dir)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(path: &str) -> Option<String> {
+        Some(path.to_string())
+    }
+
+    #[test]
+    fn goes_back_and_forward_over_several_steps() {
+        let mut history = NavHistory::default();
+        assert_eq!(history.go_back(None), None);
+
+        // root -> /v/a -> /v/a/b -> /v/a/b/c
+        history.visit(None);
+        history.visit(dir("/v/a"));
+        history.visit(dir("/v/a/b"));
+        assert_eq!(history.go_back(dir("/v/a/b/c")), Some(dir("/v/a/b")));
+        assert_eq!(history.go_back(dir("/v/a/b")), Some(dir("/v/a")));
+        assert_eq!(history.go_forward(dir("/v/a")), Some(dir("/v/a/b")));
+        assert!(history.can_go_forward());
+
+        // A new visit drops what was ahead
+        history.visit(dir("/v/a/b"));
+        assert!(!history.can_go_forward());
+        assert_eq!(history.go_forward(dir("/v/x")), None);
+        assert_eq!(history.go_back(dir("/v/x")), Some(dir("/v/a/b")));
+        assert_eq!(history.go_back(dir("/v/a/b")), Some(dir("/v/a")));
+        assert_eq!(history.go_back(dir("/v/a")), Some(None));
+        assert!(!history.can_go_back());
+    }
+
+    #[test]
+    fn forgets_the_oldest_steps() {
+        let mut history = NavHistory::default();
+        for step in 0..=MAX_STEPS {
+            history.visit(dir(&step.to_string()));
+        }
+        let mut oldest = None;
+        while let Some(dir) = history.go_back(None) {
+            oldest = dir;
+        }
+        assert_eq!(oldest, dir("1"));
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+use lazysmg::filesystem::{FileSystem, LocalFileSystem};
+use crate::{perform_file_operation, FileOperation};
+use crate::config::{ConfirmConfig, ConfirmPolicy};
+use crate::platform::trash;
+use crate::safety::Safety;
+use crate::storage::file_ops::{self, FileOpJob};
+
+/// How many copies, moves and deletes run at the same time; the others wait their turn.
+const MAX_RUNNING: usize = 2;
+
+/// Finished operations kept in the list, newest last.
+const MAX_FINISHED: usize = 20;
+
+/// Operations remembered for undo, newest last.
+const MAX_JOURNAL: usize = 50;
+
+/// Deletes in or of these always ask for confirmation, whatever `confirm.delete` says.
+const SYSTEM_PATHS: [&str; 12] = ["/System", "/Library", "/Applications", "/usr", "/bin", "/sbin", "/etc", "/var", "/private", "/opt", "/boot", "/lib"];
+
+/// `path` with `.` and `..` resolved without looking at the disk, so `/Users/a/../../etc`
+/// counts as `/etc`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::CurDir => {}
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+/// Whether `path` is part of the system: below one of `SYSTEM_PATHS`, a top-level directory,
+/// or the home directory or its `Library` itself.
+fn is_system_path(path: &Path) -> bool {
+    let path = normalize(path);
+    let path = path.as_path();
+    if SYSTEM_PATHS.iter().any(|system| path.starts_with(system)) || path.parent().is_none_or(|parent| parent == Path::new("/")) {
+        return true;
+    }
+    expanduser::expanduser("~").is_ok_and(|home| path == home || path == home.join("Library"))
+}
+
+/// Whether deleting `path`, of `size` bytes (None if it isn't known, as for a directory that
+/// wasn't scanned), has to be confirmed first.
+pub fn delete_needs_confirmation(confirm: &ConfirmConfig, path: &str, size: Option<u64>) -> bool {
+    if is_system_path(Path::new(path)) {
+        return true;
+    }
+    match confirm.delete {
+        ConfirmPolicy::Always => true,
+        ConfirmPolicy::LargeOnly => size.is_none_or(|size| size >= confirm.large_delete_mb * 1024 * 1024),
+        ConfirmPolicy::Never => false,
+    }
+}
+
+/// Where `path` is if `op` on it has to be confirmed by typing its name: deletes and moves of
+/// anything that isn't the user's own data, as scan results sorted by size easily put system
+/// files and app data on top.
+pub fn strong_confirmation(op: &FileOperation, path: &str) -> Option<Safety> {
+    let safety = Safety::classify(path);
+    (matches!(op, FileOperation::Delete | FileOperation::SecureDelete { .. } | FileOperation::Move) && safety != Safety::UserData).then_some(safety)
+}
+
+/// What has to be typed to confirm an operation on `path`: its file name.
+pub fn confirmation_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(|| path.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+/// How to revert a finished delete or move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revert {
+    /// Move an item deleted into the trash back to where it was
+    Restore { trashed: String, original: String },
+    /// Move a moved item back to where it came from
+    MoveBack { moved_to: String, original: String },
+}
+
+impl Revert {
+    /// What undoing does, for the confirmation.
+    pub fn description(&self) -> String {
+        match self {
+            Revert::Restore { original, .. } => format!("Restore {} from the trash", original),
+            Revert::MoveBack { moved_to, original } => format!("Move {} back to {}", moved_to, original),
+        }
+    }
+
+    /// Where the item is now, and where undoing puts it.
+    fn paths(&self) -> (&str, &str) {
+        match self {
+            Revert::Restore { trashed, original } => (trashed, original),
+            Revert::MoveBack { moved_to, original } => (moved_to, original),
+        }
+    }
+
+    fn apply(&self, job: &FileOpJob) -> Result<String, Box<dyn Error>> {
+        let (from, to) = self.paths();
+        let _on_devices = job.run_on(&[from, to])?;
+        match self {
+            Revert::Restore { trashed, original } => {
+                trash::restore_from_trash(Path::new(trashed), Path::new(original))?;
+                Ok(format!("Restored {} from the trash", original))
+            }
+            Revert::MoveBack { moved_to, original } => {
+                if fs::symlink_metadata(original).is_ok() {
+                    return Err(format!("{} exists again; not overwriting it", original).into());
+                }
+                file_ops::move_with_progress(Path::new(moved_to), Path::new(original), job)?;
+                Ok(format!("Moved {} back to {}", moved_to, original))
+            }
+        }
+    }
+}
+
+/// What a successful operation did, and how to revert it if it can be.
+#[derive(Debug)]
+pub struct FileOpOutcome {
+    pub summary: String,
+    pub revert: Option<Revert>,
+}
+
+/// Where an operation is in its life.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationState {
+    Pending,
+    Running,
+    Done(String),
+    Failed(String),
+    Cancelled,
+}
+
+impl OperationState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, OperationState::Pending | OperationState::Running)
+    }
+}
+
+/// A copy, move or delete the user confirmed.
+#[derive(Debug)]
+pub struct Operation {
+    pub id: u64,
+    pub op_type: FileOperation,
+    pub source: String,
+    pub target: Option<String>,
+    pub state: OperationState,
+    /// Progress of the operation, once it runs
+    pub job: Option<Arc<FileOpJob>>,
+    /// Set when the operation undoes an earlier one, which it reverts
+    pub undoing: Option<Revert>,
+}
+
+impl Operation {
+    /// "Copying", "Moving", "Deleting", "Archiving", "Extracting", "Erasing" or "Undoing"
+    pub fn label(&self) -> &'static str {
+        if self.undoing.is_some() {
+            return "Undoing";
+        }
+        match self.op_type {
+            FileOperation::Copy => "Copying",
+            FileOperation::Move => "Moving",
+            FileOperation::Delete => "Deleting",
+            FileOperation::Archive => "Archiving",
+            FileOperation::Extract => "Extracting",
+            FileOperation::SecureDelete { .. } => "Erasing",
+        }
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.source.as_str()).chain(self.target.as_deref()).map(Path::new)
+    }
+
+    /// Whether the two operations touch the same files, e.g. a delete of a folder that is
+    /// still being copied. Such an operation waits until the other one finished.
+    fn overlaps(&self, other: &Operation) -> bool {
+        self.paths().any(|a| other.paths().any(|b| a.starts_with(b) || b.starts_with(a)))
+    }
+}
+
+/// An operation that finished since the last update, to refresh the listings with.
+#[derive(Debug)]
+pub struct FinishedOperation {
+    pub summary: String,
+    /// The source path, if it no longer exists (it was moved or deleted)
+    pub removed: Option<String>,
+    /// For a delete that went through, whether it went to the trash rather than being deleted
+    /// permanently
+    pub trashed: Option<bool>,
+}
+
+/// Copies, moves and deletes waiting for, or running in, the background, plus the last
+/// finished ones.
+#[derive(Debug)]
+pub struct OperationQueue {
+    operations: Vec<Operation>,
+    next_id: u64,
+    /// Finished operations, and whether their source is gone since
+    done_tx: mpsc::Sender<(u64, Result<FileOpOutcome, String>, bool)>,
+    done_rx: mpsc::Receiver<(u64, Result<FileOpOutcome, String>, bool)>,
+    /// Deletes and moves that can be undone, newest last
+    journal: Vec<Revert>,
+    /// What operations look files up through: what a delete removes, and whether an operation's
+    /// source is gone once it finished. Deletes themselves go to the trash.
+    fs: Arc<dyn FileSystem>,
+}
+
+impl Default for OperationQueue {
+    fn default() -> Self {
+        OperationQueue::with_file_system(Arc::new(LocalFileSystem))
+    }
+}
+
+impl OperationQueue {
+    /// A queue whose operations look files up through `fs`.
+    pub fn with_file_system(fs: Arc<dyn FileSystem>) -> Self {
+        let (done_tx, done_rx) = mpsc::channel();
+        OperationQueue { operations: Vec::new(), next_id: 1, done_tx, done_rx, journal: Vec::new(), fs }
+    }
+
+    /// Queue an operation; it starts on the next update if fewer than `MAX_RUNNING` run.
+    pub fn enqueue(&mut self, op_type: FileOperation, source: String, target: Option<String>) {
+        self.operations.push(Operation {
+            id: self.next_id,
+            op_type,
+            source,
+            target,
+            state: OperationState::Pending,
+            job: None,
+            undoing: None,
+        });
+        self.next_id += 1;
+    }
+
+    /// Queue deleting `path` without a confirmation, if `confirm` lets it skip one (see
+    /// `delete_needs_confirmation`). Returns false, queueing nothing, if it has to be confirmed.
+    pub fn delete_unconfirmed(&mut self, confirm: &ConfirmConfig, path: &str, size: Option<u64>) -> bool {
+        if delete_needs_confirmation(confirm, path, size) {
+            return false;
+        }
+        self.enqueue(FileOperation::Delete, path.to_string(), None);
+        true
+    }
+
+    /// The most recent delete or move that can still be undone.
+    pub fn last_revert(&self) -> Option<&Revert> {
+        self.journal.last()
+    }
+
+    /// Queue undoing the most recent delete or move. Returns whether there was one.
+    pub fn undo_last(&mut self) -> bool {
+        let Some(revert) = self.journal.pop() else {
+            return false;
+        };
+        let (from, to) = revert.paths();
+        self.operations.push(Operation {
+            id: self.next_id,
+            op_type: FileOperation::Move,
+            source: from.to_string(),
+            target: Some(to.to_string()),
+            state: OperationState::Pending,
+            job: None,
+            undoing: Some(revert),
+        });
+        self.next_id += 1;
+        true
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Number of running and of pending operations.
+    pub fn counts(&self) -> (usize, usize) {
+        let count = |state: OperationState| self.operations.iter().filter(|op| op.state == state).count();
+        (count(OperationState::Running), count(OperationState::Pending))
+    }
+
+    /// Whether any operation is running or waiting to run.
+    pub fn is_active(&self) -> bool {
+        self.operations.iter().any(|op| !op.state.is_finished())
+    }
+
+    /// Cancel the operation at `index`: a pending one never starts, a running one
+    /// is asked to stop and reports how far it got. Returns whether there was one to cancel.
+    pub fn cancel(&mut self, index: usize) -> bool {
+        let Some(op) = self.operations.get_mut(index) else {
+            return false;
+        };
+        match (&op.state, &op.job) {
+            (OperationState::Pending, _) => op.state = OperationState::Cancelled,
+            (OperationState::Running, Some(job)) => job.stop(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Collect the operations that finished and start pending ones while fewer than
+    /// `MAX_RUNNING` run. Returns the operations that finished since the last update.
+    pub fn update(&mut self) -> Vec<FinishedOperation> {
+        let mut finished = Vec::new();
+        while let Ok((id, result, source_gone)) = self.done_rx.try_recv() {
+            let Some(op) = self.operations.iter_mut().find(|op| op.id == id) else {
+                continue;
+            };
+            let stopped = op.job.as_ref().is_some_and(|job| job.stopped());
+            let summary = match &result {
+                Ok(outcome) => format!("File operation result: {}", outcome.summary),
+                Err(_) if stopped => match op.job.as_ref().and_then(|job| job.stopped_by()) {
+                    Some(busy) => format!("Stopped {} {}: {}", op.label().to_lowercase(), op.source, busy),
+                    None => format!("Stopped {} {}", op.label().to_lowercase(), op.source),
+                },
+                Err(err) => format!("Operation failed: {}", err),
+            };
+            match (&result, op.undoing.clone()) {
+                (Ok(outcome), None) => self.journal.extend(outcome.revert.clone()),
+                // An undo that didn't go through can be tried again
+                (Err(_), Some(revert)) => self.journal.push(revert),
+                _ => {}
+            }
+            // Exactly the deletes that went to the trash can be undone
+            let trashed = match &result {
+                Ok(outcome) if matches!(op.op_type, FileOperation::Delete | FileOperation::SecureDelete { .. }) && op.undoing.is_none() => {
+                    Some(outcome.revert.is_some())
+                }
+                _ => None,
+            };
+            if self.journal.len() > MAX_JOURNAL {
+                self.journal.remove(0);
+            }
+            op.state = match result {
+                Ok(outcome) => OperationState::Done(outcome.summary),
+                Err(_) if stopped => OperationState::Cancelled,
+                Err(err) => OperationState::Failed(err),
+            };
+            let removed = source_gone.then(|| op.source.clone());
+            finished.push(FinishedOperation { summary, removed, trashed });
+        }
+
+        for index in 0..self.operations.len() {
+            if self.counts().0 >= MAX_RUNNING {
+                break;
+            }
+            // Operations start in the order they were queued, except that one waits while an
+            // earlier operation on the same files is unfinished
+            let blocked = self.operations[..index]
+                .iter()
+                .any(|earlier| !earlier.state.is_finished() && earlier.overlaps(&self.operations[index]));
+            let op = &mut self.operations[index];
+            if op.state != OperationState::Pending || blocked {
+                continue;
+            }
+            let job = Arc::new(FileOpJob::new(op.label(), op.source.clone()));
+            op.job = Some(job.clone());
+            op.state = OperationState::Running;
+
+            let (id, op_type, source, target) = (op.id, op.op_type.clone(), op.source.clone(), op.target.clone());
+            let undoing = op.undoing.clone();
+            let done_tx = self.done_tx.clone();
+            let fs = self.fs.clone();
+            tokio::task::spawn_blocking(move || {
+                let result = match &undoing {
+                    Some(revert) => revert.apply(&job).map(|summary| FileOpOutcome { summary, revert: None }),
+                    None => perform_file_operation(&op_type, &source, target.as_deref(), &job, fs.as_ref()),
+                };
+                let result = result.map_err(|e| e.to_string());
+                // Looked up here, as the volume may be slow to answer
+                let source_gone = fs.file_info(Path::new(&source)).is_err();
+                // Ignore errors - the app may have been closed
+                let _ = done_tx.send((id, result, source_gone));
+            });
+        }
+
+        // Keep only the newest finished operations
+        let finished_count = self.operations.iter().filter(|op| op.state.is_finished()).count();
+        let mut excess = finished_count.saturating_sub(MAX_FINISHED);
+        self.operations.retain(|op| {
+            let drop = excess > 0 && op.state.is_finished();
+            excess -= drop as usize;
+            !drop
+        });
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_paths_are_recognised() {
+        let home = expanduser::expanduser("~").unwrap();
+        let home = home.to_string_lossy();
+        let cases = [
+            ("/", true),
+            ("/System", true),
+            ("/System/Library/Kernels/kernel", true),
+            ("/usr", true),
+            ("/usr/", true),
+            ("/usr/bin/python3", true),
+            ("/etc/hosts", true),
+            ("/data", true),
+            (home.as_ref(), true),
+            (&format!("{}/", home), true),
+            (&format!("{}/Library", home), true),
+            (&format!("{}/Documents/../../../etc/passwd", home), true),
+            (&format!("{}/Documents/./..", home), true),
+            ("/Volumes/USB/../../usr/lib", true),
+            (&format!("{}/Documents/report.pdf", home), false),
+            (&format!("{}/Library/../Movies/trip.mov", home), false),
+            ("/Volumes/USB/photos/a.jpg", false),
+            ("/usrdata/a.jpg", false),
+        ];
+        for (path, system) in cases {
+            assert_eq!(is_system_path(Path::new(path)), system, "{}", path);
+        }
+    }
+
+    #[test]
+    fn deletes_ask_as_configured_except_for_system_paths() {
+        let ask = |delete, path, size| delete_needs_confirmation(&ConfirmConfig { delete, large_delete_mb: 100 }, path, size);
+        let mb = 1024 * 1024;
+        assert!(ask(ConfirmPolicy::Always, "/Volumes/USB/a.jpg", Some(1)));
+        assert!(!ask(ConfirmPolicy::LargeOnly, "/Volumes/USB/a.jpg", Some(99 * mb)));
+        assert!(ask(ConfirmPolicy::LargeOnly, "/Volumes/USB/a.jpg", Some(100 * mb)));
+        // A directory no scan measured
+        assert!(ask(ConfirmPolicy::LargeOnly, "/Volumes/USB/photos", None));
+        assert!(!ask(ConfirmPolicy::Never, "/Volumes/USB/a.jpg", None));
+        assert!(ask(ConfirmPolicy::Never, "/usr/bin/python3", Some(1)));
+        assert!(ask(ConfirmPolicy::Never, "/", None));
+        assert!(ask(ConfirmPolicy::Never, "/Volumes/USB/../../etc/hosts", Some(1)));
+        assert!(ask(ConfirmPolicy::LargeOnly, "/usr/", Some(1)));
+    }
+
+    #[test]
+    fn undoing_a_move_puts_it_back() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-operations-undo-{}", std::process::id()));
+        fs::create_dir_all(dir.join("from")).unwrap();
+        fs::write(dir.join("from/notes.txt"), b"notes").unwrap();
+        let (original, moved_to) = (dir.join("from/notes.txt"), dir.join("to/notes.txt"));
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+
+        let job = FileOpJob::new("Moving", path(&original));
+        let outcome = perform_file_operation(&FileOperation::Move, &path(&original), Some(&path(&moved_to)), &job, &LocalFileSystem).unwrap();
+        let revert = outcome.revert.expect("a move can be undone");
+        assert_eq!(revert, Revert::MoveBack { moved_to: path(&moved_to), original: path(&original) });
+        assert!(!original.exists() && moved_to.exists());
+
+        revert.apply(&FileOpJob::new("Undoing", path(&moved_to))).unwrap();
+        assert_eq!(fs::read(&original).unwrap(), b"notes");
+        assert!(!moved_to.exists());
+
+        // An undo never overwrites what has taken the original's place since
+        let outcome = perform_file_operation(&FileOperation::Move, &path(&original), Some(&path(&moved_to)), &job, &LocalFileSystem).unwrap();
+        fs::write(&original, b"new notes").unwrap();
+        assert!(outcome.revert.unwrap().apply(&job).is_err());
+        assert_eq!(fs::read(&original).unwrap(), b"new notes");
+        assert_eq!(fs::read(&moved_to).unwrap(), b"notes");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+use expanduser::expanduser;
+use jwalk::WalkDir;
+use serde::{Deserialize, Serialize};
+use sysinfo::{ProcessExt, System, SystemExt};
+
+/// Browsers whose profile layouts lazysmg understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Browser {
+    Safari,
+    Chrome,
+    Firefox,
+}
+
+impl Browser {
+    pub fn label(self) -> &'static str {
+        match self {
+            Browser::Safari => "Safari",
+            Browser::Chrome => "Chrome",
+            Browser::Firefox => "Firefox",
+        }
+    }
+
+    /// Process names of the running browser, compared case-insensitively.
+    fn process_names(self) -> &'static [&'static str] {
+        match self {
+            Browser::Safari => &["Safari"],
+            Browser::Chrome => &["Google Chrome", "chrome"],
+            Browser::Firefox => &["firefox", "firefox-bin"],
+        }
+    }
+}
+
+/// Where a browser keeps its profiles and what the entries of a profile are.
+struct BrowserLayout {
+    browser: Browser,
+    /// Directory holding one directory per profile, or the only profile if `profile_marker` is None
+    profiles_dir: &'static str,
+    /// File every profile directory contains, to tell profiles apart from other directories
+    profile_marker: Option<&'static str>,
+    /// Directories outside the profile holding its cache, in a subdirectory named like the
+    /// profile (or, for single-profile browsers, the whole directory)
+    cache_roots: &'static [&'static str],
+    /// Cache entries inside the profile directory
+    cache: &'static [&'static str],
+    history: &'static [&'static str],
+    extensions: &'static [&'static str],
+}
+
+const CHROME_CACHE: &[&str] = &[
+    "Cache",
+    "Code Cache",
+    "GPUCache",
+    "DawnCache",
+    "DawnWebGPUCache",
+    "DawnGraphiteCache",
+    "Service Worker/CacheStorage",
+    "Service Worker/ScriptCache",
+];
+const CHROME_HISTORY: &[&str] = &[
+    "History",
+    "History-journal",
+    "Visited Links",
+    "Top Sites",
+    "Top Sites-journal",
+    "Favicons",
+    "Favicons-journal",
+    "Shortcuts",
+    "Shortcuts-journal",
+];
+const CHROME_EXTENSIONS: &[&str] = &[
+    "Extensions",
+    "Local Extension Settings",
+    "Extension State",
+    "Extension Rules",
+    "Extension Scripts",
+];
+const FIREFOX_CACHE: &[&str] = &["cache2", "startupCache", "shader-cache"];
+const FIREFOX_HISTORY: &[&str] = &[
+    "places.sqlite",
+    "places.sqlite-wal",
+    "favicons.sqlite",
+    "favicons.sqlite-wal",
+    "sessionstore.jsonlz4",
+    "sessionstore-backups",
+];
+const FIREFOX_EXTENSIONS: &[&str] = &["extensions", "extensions.json", "browser-extension-data"];
+
+#[cfg(target_os = "macos")]
+const LAYOUTS: &[BrowserLayout] = &[
+    BrowserLayout {
+        browser: Browser::Safari,
+        profiles_dir: "~/Library/Safari",
+        profile_marker: None,
+        cache_roots: &[
+            "~/Library/Caches/com.apple.Safari",
+            "~/Library/Containers/com.apple.Safari/Data/Library/Caches",
+        ],
+        cache: &[],
+        history: &["History.db", "History.db-wal", "History.db-shm", "HistoryIndex.sk"],
+        extensions: &["Extensions"],
+    },
+    BrowserLayout {
+        browser: Browser::Chrome,
+        profiles_dir: "~/Library/Application Support/Google/Chrome",
+        profile_marker: Some("Preferences"),
+        cache_roots: &["~/Library/Caches/Google/Chrome"],
+        cache: CHROME_CACHE,
+        history: CHROME_HISTORY,
+        extensions: CHROME_EXTENSIONS,
+    },
+    BrowserLayout {
+        browser: Browser::Firefox,
+        profiles_dir: "~/Library/Application Support/Firefox/Profiles",
+        profile_marker: Some("prefs.js"),
+        cache_roots: &["~/Library/Caches/Firefox/Profiles"],
+        cache: FIREFOX_CACHE,
+        history: FIREFOX_HISTORY,
+        extensions: FIREFOX_EXTENSIONS,
+    },
+];
+
+#[cfg(not(target_os = "macos"))]
+const LAYOUTS: &[BrowserLayout] = &[
+    BrowserLayout {
+        browser: Browser::Chrome,
+        profiles_dir: "~/.config/google-chrome",
+        profile_marker: Some("Preferences"),
+        cache_roots: &["~/.cache/google-chrome"],
+        cache: CHROME_CACHE,
+        history: CHROME_HISTORY,
+        extensions: CHROME_EXTENSIONS,
+    },
+    BrowserLayout {
+        browser: Browser::Firefox,
+        profiles_dir: "~/.mozilla/firefox",
+        profile_marker: Some("prefs.js"),
+        cache_roots: &["~/.cache/mozilla/firefox"],
+        cache: FIREFOX_CACHE,
+        history: FIREFOX_HISTORY,
+        extensions: FIREFOX_EXTENSIONS,
+    },
+];
+
+/// One browser profile, with its size split by what the data is for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrowserProfile {
+    pub browser: Browser,
+    /// Profile directory name, e.g. "Default" or "abcd1234.default-release"
+    pub name: String,
+    pub path: String,
+    /// Existing cache directories, inside the profile or in the system cache folder
+    pub cache_dirs: Vec<String>,
+    pub cache_size: u64,
+    pub history_size: u64,
+    pub extensions_size: u64,
+    /// Everything else in the profile: settings, bookmarks, cookies, site data, ...
+    pub other_size: u64,
+}
+
+impl BrowserProfile {
+    pub fn total_size(&self) -> u64 {
+        self.cache_size + self.history_size + self.extensions_size + self.other_size
+    }
+}
+
+/// Size of a file, or of all files below a directory. Symlinks aren't followed.
+fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    WalkDir::new(path)
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn expand(path: &str) -> Option<PathBuf> {
+    expanduser(path).ok()
+}
+
+/// Profile directories of a browser, paired with their names.
+fn profile_dirs(layout: &BrowserLayout) -> Vec<(String, PathBuf)> {
+    let Some(root) = expand(layout.profiles_dir) else {
+        return Vec::new();
+    };
+    let Some(marker) = layout.profile_marker else {
+        return if root.is_dir() { vec![("Default".to_string(), root)] } else { Vec::new() };
+    };
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<(String, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join(marker).is_file())
+        .map(|path| (path.file_name().unwrap_or_default().to_string_lossy().into_owned(), path))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn measure_profile(layout: &BrowserLayout, name: String, path: &Path) -> BrowserProfile {
+    let entries_size = |names: &[&str]| -> u64 { names.iter().map(|entry| path_size(&path.join(entry))).sum() };
+
+    let mut cache_dirs: Vec<PathBuf> = layout.cache.iter().map(|entry| path.join(entry)).collect();
+    for root in layout.cache_roots.iter().filter_map(|root| expand(root)) {
+        cache_dirs.push(if layout.profile_marker.is_some() { root.join(&name) } else { root });
+    }
+    cache_dirs.retain(|dir| fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_dir()));
+
+    let cache_size: u64 = cache_dirs.iter().map(|dir| path_size(dir)).sum();
+    // Only the cache inside the profile is part of the profile directory's size
+    let inner_cache_size: u64 = cache_dirs.iter().filter(|dir| dir.starts_with(path)).map(|dir| path_size(dir)).sum();
+    let history_size = entries_size(layout.history);
+    let extensions_size = entries_size(layout.extensions);
+    let other_size = path_size(path).saturating_sub(inner_cache_size + history_size + extensions_size);
+
+    BrowserProfile {
+        browser: layout.browser,
+        name,
+        path: path.to_string_lossy().into_owned(),
+        cache_dirs: cache_dirs.iter().map(|dir| dir.to_string_lossy().into_owned()).collect(),
+        cache_size,
+        history_size,
+        extensions_size,
+        other_size,
+    }
+}
+
+/// Find and measure the profiles of every installed browser, largest cache first.
+// Called by the junk scan only
+#[cfg_attr(not(feature = "junk"), allow(dead_code))]
+pub fn find_profiles() -> Vec<BrowserProfile> {
+    let mut profiles: Vec<BrowserProfile> = LAYOUTS
+        .iter()
+        .flat_map(|layout| {
+            profile_dirs(layout)
+                .into_iter()
+                .map(move |(name, path)| measure_profile(layout, name, &path))
+        })
+        .collect();
+    profiles.sort_by_key(|profile| std::cmp::Reverse(profile.cache_size));
+    profiles
+}
+
+/// Browsers that are running right now. Their caches are in use and are not cleared.
+pub fn running_browsers() -> Vec<Browser> {
+    let mut system = System::new();
+    system.refresh_processes();
+    [Browser::Safari, Browser::Chrome, Browser::Firefox]
+        .into_iter()
+        .filter(|browser| {
+            system.processes().values().any(|process| {
+                browser.process_names().iter().any(|name| process.name().eq_ignore_ascii_case(name))
+            })
+        })
+        .collect()
+}
+
+/// Clear the cache of a profile: everything inside its cache directories, which are kept so
+/// the browser finds them where it expects. History, extensions and settings are not touched.
+/// Refuses while the browser is running. Returns the number of bytes freed.
+pub fn clear_cache(profile: &BrowserProfile) -> Result<u64, Box<dyn Error>> {
+    if running_browsers().contains(&profile.browser) {
+        return Err(format!("{} is running; quit it before clearing its cache", profile.browser.label()).into());
+    }
+    let mut freed = 0;
+    for dir in &profile.cache_dirs {
+        // The directory may have been replaced since the scan; never follow a symlink out of it
+        if !fs::symlink_metadata(dir)?.is_dir() {
+            return Err(format!("{} is not a directory", dir).into());
+        }
+        for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let size = path_size(&path);
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            freed += size;
+        }
+    }
+    Ok(freed)
+}
@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+use jwalk::WalkDirGeneric;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use crate::scanner::{measure_dir_with_progress, ScanOptions, ScanProgressMessage};
+
+/// A kind of build artifact directory, recognized by a project file next to it.
+#[derive(Debug)]
+pub struct ArtifactKind {
+    /// Directory name, e.g. "node_modules"
+    pub dir_name: &'static str,
+    /// At least one of these must sit next to the directory for it to count as an artifact
+    pub project_files: &'static [&'static str],
+}
+
+/// Artifact directories lazysmg looks for. A `target` or `build` directory without a matching
+/// project file next to it may hold anything, so it is left alone.
+pub const ARTIFACT_KINDS: &[ArtifactKind] = &[
+    ArtifactKind { dir_name: "node_modules", project_files: &["package.json"] },
+    ArtifactKind { dir_name: "target", project_files: &["Cargo.toml"] },
+    ArtifactKind { dir_name: ".venv", project_files: &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"] },
+    ArtifactKind { dir_name: "Pods", project_files: &["Podfile"] },
+    ArtifactKind { dir_name: "build", project_files: &["build.gradle", "build.gradle.kts", "CMakeLists.txt"] },
+    ArtifactKind { dir_name: ".gradle", project_files: &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"] },
+];
+
+/// The artifact kind of a directory named `dir_name`, if it has one.
+fn artifact_kind(dir_name: &str) -> Option<&'static ArtifactKind> {
+    ARTIFACT_KINDS.iter().find(|kind| kind.dir_name == dir_name)
+}
+
+/// One artifact directory and its size on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevArtifact {
+    /// Directory name, e.g. "node_modules"
+    pub kind: String,
+    pub path: String,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+/// A project directory with the artifact directories found in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevProject {
+    pub path: String,
+    pub artifacts: Vec<DevArtifact>,
+}
+
+impl DevProject {
+    pub fn total_size(&self) -> u64 {
+        self.artifacts.iter().map(|artifact| artifact.size).sum()
+    }
+
+    /// Artifact kinds in this project, e.g. "node_modules, target".
+    pub fn kinds(&self) -> String {
+        self.artifacts.iter().map(|artifact| artifact.kind.as_str()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Find artifact directories below `root`. Hidden directories are searched too (for `.venv` and
+/// `.gradle`), but artifact directories are not descended into (so packages inside
+/// `node_modules` aren't reported again), and neither are `.git` directories.
+pub fn find_artifacts(root: &str, options: ScanOptions) -> Vec<String> {
+    // Each directory entry's client state marks whether it is an artifact directory
+    WalkDirGeneric::<((), bool)>::new(root)
+        .parallelism(options.jwalk_parallelism())
+        .skip_hidden(false)
+        .process_read_dir(|_, _, _, children| {
+            let files: Vec<String> = children
+                .iter()
+                .flatten()
+                .filter(|child| child.file_type().is_file())
+                .map(|child| child.file_name().to_string_lossy().into_owned())
+                .collect();
+            for child in children.iter_mut().flatten() {
+                if !child.file_type().is_dir() {
+                    continue;
+                }
+                let name = child.file_name().to_string_lossy();
+                if name == ".git" {
+                    child.read_children_path = None;
+                } else if let Some(kind) = artifact_kind(&name)
+                    && kind.project_files.iter().any(|project_file| files.iter().any(|file| file == project_file))
+                {
+                    child.client_state = true;
+                    child.read_children_path = None;
+                }
+            }
+        })
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.client_state)
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Finds the build artifacts below `start_path`, measures them and groups them by project,
+/// reporting each measured file via the progress channel. Projects are sent largest first.
+pub fn scan_dev_artifacts_with_progress(
+    start_path: &str,
+    progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+) -> Result<(), Box<dyn Error + Send + 'static>> {
+    let mut projects: BTreeMap<String, Vec<DevArtifact>> = BTreeMap::new();
+    let mut files_processed = 0;
+
+    for artifact_path in find_artifacts(start_path, options) {
+        let path = Path::new(&artifact_path);
+        let (Some(project), Some(kind)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        // If measuring stops early, the application has likely closed
+        let Some((size, file_count)) = measure_dir_with_progress(path, &progress_tx, options) else {
+            return Ok(());
+        };
+        let artifact = DevArtifact {
+            kind: kind.to_string_lossy().into_owned(),
+            path: artifact_path.clone(),
+            size,
+            file_count,
+        };
+
+        files_processed += artifact.file_count;
+        projects.entry(project.to_string_lossy().into_owned()).or_default().push(artifact);
+    }
+
+    let mut projects: Vec<DevProject> = projects
+        .into_iter()
+        .map(|(path, artifacts)| DevProject { path, artifacts })
+        .collect();
+    projects.sort_by_key(|project| std::cmp::Reverse(project.total_size()));
+
+    // Ignore errors - the app may have been closed
+    let _ = progress_tx.blocking_send(ScanProgressMessage::DevArtifactsComplete {
+        root: start_path.to_string(),
+        projects,
+        files_processed,
+    });
+    Ok(())
+}
+
+/// Remove an artifact directory. The directory is checked again first: it must still be a real
+/// directory (not a symlink) of a known artifact kind with one of its project files next to it.
+pub fn clean_artifact(artifact: &DevArtifact) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(&artifact.path);
+    let kind = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(artifact_kind)
+        .ok_or_else(|| format!("{} is not a build artifact directory", artifact.path))?;
+    let project = path.parent().ok_or_else(|| format!("{} has no project directory", artifact.path))?;
+
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Err(format!("{} is not a directory", artifact.path).into());
+    }
+    if !kind.project_files.iter().any(|project_file| project.join(project_file).is_file()) {
+        return Err(format!("{} no longer has a {} next to it", artifact.path, kind.project_files.join("/")).into());
+    }
+    fs::remove_dir_all(path)?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::{error::Error, process::Command};
+use serde::{Deserialize, Serialize};
+
+/// Kinds of Docker data reported by `docker system df`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DockerResource {
+    Images,
+    Containers,
+    Volumes,
+    BuildCache,
+}
+
+impl DockerResource {
+    /// Every resource, in the order `docker system df` lists them.
+    pub const ALL: [DockerResource; 4] = [
+        DockerResource::Images,
+        DockerResource::Containers,
+        DockerResource::Volumes,
+        DockerResource::BuildCache,
+    ];
+
+    /// Resources a prune includes unless deselected. Volumes hold data, so they must be chosen explicitly.
+    pub const DEFAULT_PRUNE: [DockerResource; 3] = [
+        DockerResource::Images,
+        DockerResource::Containers,
+        DockerResource::BuildCache,
+    ];
+
+    /// The "Type" column of `docker system df`.
+    fn from_df_type(kind: &str) -> Option<Self> {
+        match kind {
+            "Images" => Some(DockerResource::Images),
+            "Containers" => Some(DockerResource::Containers),
+            "Local Volumes" => Some(DockerResource::Volumes),
+            "Build Cache" => Some(DockerResource::BuildCache),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DockerResource::Images => "Docker images",
+            DockerResource::Containers => "Docker containers",
+            DockerResource::Volumes => "Docker volumes",
+            DockerResource::BuildCache => "Docker build cache",
+        }
+    }
+
+    /// Key that toggles this resource in the prune dialog.
+    pub fn key(self) -> char {
+        match self {
+            DockerResource::Images => 'i',
+            DockerResource::Containers => 'c',
+            DockerResource::Volumes => 'v',
+            DockerResource::BuildCache => 'b',
+        }
+    }
+
+    /// What pruning removes, shown in the prune dialog.
+    pub fn prune_description(self) -> &'static str {
+        match self {
+            DockerResource::Images => "Images not used by any container (pulled or rebuilt again when needed)",
+            DockerResource::Containers => "Stopped containers, including their changes to the file system",
+            DockerResource::Volumes => "Volumes not used by any container (only anonymous ones since Docker 23). Their data is lost!",
+            DockerResource::BuildCache => "Build cache (later builds are slower until it is filled again)",
+        }
+    }
+
+    fn prune_args(self) -> &'static [&'static str] {
+        match self {
+            DockerResource::Images => &["image", "prune", "--all", "--force"],
+            DockerResource::Containers => &["container", "prune", "--force"],
+            DockerResource::Volumes => &["volume", "prune", "--force"],
+            DockerResource::BuildCache => &["builder", "prune", "--force"],
+        }
+    }
+}
+
+/// Disk usage of one kind of Docker data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DockerUsage {
+    pub resource: DockerResource,
+    pub total_count: u64,
+    pub active: u64,
+    pub size: u64,
+    /// Space a prune of this resource would free
+    pub reclaimable: u64,
+}
+
+/// One line of `docker system df --format '{{json .}}'`. Docker reports every field as a string.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DfLine {
+    #[serde(rename = "Type")]
+    kind: String,
+    total_count: String,
+    active: String,
+    size: String,
+    /// e.g. "1.2GB (50%)"
+    reclaimable: String,
+}
+
+/// Parse a size as printed by Docker ("1.2GB", "512kB", "0B"). Docker uses decimal units.
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "B" | "" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0_f64.powi(2),
+        "GiB" => 1024.0_f64.powi(3),
+        "TiB" => 1024.0_f64.powi(4),
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+fn parse_df(output: &str) -> Vec<DockerUsage> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<DfLine>(line).ok())
+        .filter_map(|line| {
+            Some(DockerUsage {
+                resource: DockerResource::from_df_type(&line.kind)?,
+                total_count: line.total_count.parse().unwrap_or(0),
+                active: line.active.parse().unwrap_or(0),
+                size: parse_size(&line.size).unwrap_or(0),
+                reclaimable: line.reclaimable.split_whitespace().next().and_then(parse_size).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Docker's disk usage per resource, or None when Docker isn't installed or its daemon isn't running.
+pub fn disk_usage() -> Option<Vec<DockerUsage>> {
+    let output = Command::new("docker")
+        .args(["system", "df", "--format", "{{json .}}"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_df(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Prune one kind of Docker data. Returns Docker's summary, e.g. "Total reclaimed space: 1.2GB".
+pub fn prune(resource: DockerResource) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("docker").args(resource.prune_args()).output()?;
+    if !output.status.success() {
+        return Err(format!("docker error: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("done")
+        .trim()
+        .to_string())
+}
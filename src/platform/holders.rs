@@ -0,0 +1,123 @@
+use std::{path::Path, process::Command};
+
+// Which processes keep a volume or file busy, for explaining a failed eject or delete. `lsof`
+// answers that on macOS and most Linux systems; where it is missing Linux still tells through
+// `/proc`.
+
+/// Processes with open files on the volume mounted at `mount_point`, as "name (pid)".
+/// Uses `lsof`, or reads `/proc` where that's missing. Returns None when neither is available.
+pub fn volume_holders(mount_point: &str) -> Option<Vec<String>> {
+    // "+f --" makes lsof treat the path as a file system and report every open file on it
+    lsof_holders(&["+f", "--", mount_point]).or_else(|| proc_holders(mount_point))
+}
+
+/// Processes that have the file at `path` open, or for a directory anything below it, as
+/// "name (pid)". Found like `volume_holders`; None when that isn't possible.
+pub fn path_holders(path: &str) -> Option<Vec<String>> {
+    // "+D" looks through the whole directory tree
+    let scope = if Path::new(path).is_dir() { "+D" } else { "--" };
+    lsof_holders(&[scope, path]).or_else(|| proc_holders(path))
+}
+
+/// Processes `lsof` reports for `args`, or None if it can't be run.
+fn lsof_holders(args: &[&str]) -> Option<Vec<String>> {
+    let output = Command::new("lsof").args(["-F", "pc"]).args(args).output().ok()?;
+    Some(parse_lsof(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// `lsof -F pc` output: a `p<pid>` line per process followed by its `c<command>` line, and
+/// lines for each of its files that only repeat the process.
+fn parse_lsof(output: &str) -> Vec<String> {
+    let mut holders = Vec::new();
+    let mut pid = None;
+    for line in output.lines() {
+        if let Some(value) = line.strip_prefix('p') {
+            pid = Some(value.to_string());
+        } else if let (Some(command), Some(pid)) = (line.strip_prefix('c'), pid.as_ref()) {
+            let holder = format!("{} ({})", command, pid);
+            if !holders.contains(&holder) {
+                holders.push(holder);
+            }
+        }
+    }
+    holders
+}
+
+#[cfg(target_os = "linux")]
+fn proc_holders(path: &str) -> Option<Vec<String>> {
+    holders_in(Path::new("/proc"), Path::new(path))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn proc_holders(_path: &str) -> Option<Vec<String>> {
+    None
+}
+
+/// Processes whose open files, working directory or executable are `path` or below it,
+/// found through the `<pid>` directories of `proc`. Other users' processes are only
+/// visible to root.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn holders_in(proc: &Path, path: &Path) -> Option<Vec<String>> {
+    let mut holders = Vec::new();
+    for entry in std::fs::read_dir(proc).ok()?.filter_map(|e| e.ok()) {
+        let pid = entry.file_name().to_string_lossy().into_owned();
+        if !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let dir = entry.path();
+        let fds = std::fs::read_dir(dir.join("fd")).into_iter().flatten().filter_map(|e| e.ok()).map(|fd| fd.path());
+        let uses_path = [dir.join("cwd"), dir.join("exe")]
+            .into_iter()
+            .chain(fds)
+            .filter_map(|link| std::fs::read_link(link).ok())
+            .any(|target| target.starts_with(path));
+        if uses_path {
+            let name = std::fs::read_to_string(dir.join("comm")).unwrap_or_default();
+            holders.push(format!("{} ({})", name.trim(), pid));
+        }
+    }
+    Some(holders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lsof_output_lists_each_process_once() {
+        let output = "p412\ncFinder\nf12\nn/Volumes/USB/a.txt\nf13\nn/Volumes/USB/b.txt\np977\ncmds\nf4\n";
+        assert_eq!(parse_lsof(output), ["Finder (412)", "mds (977)"]);
+        assert!(parse_lsof("").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn proc_shows_processes_using_the_path() {
+        use std::{fs, os::unix::fs::symlink};
+
+        let proc = std::env::temp_dir().join(format!("lazysmg-proc-{}", std::process::id()));
+        let process = |pid: &str, comm: &str, cwd: &str, fds: &[&str]| {
+            let dir = proc.join(pid);
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+            symlink(cwd, dir.join("cwd")).unwrap();
+            symlink("/usr/bin/true", dir.join("exe")).unwrap();
+            for (fd, target) in fds.iter().enumerate() {
+                symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+            }
+        };
+        process("101", "vim", "/home/ana", &["/dev/pts/0", "/media/usb/notes.txt"]);
+        process("202", "bash", "/media/usb/photos", &["/dev/pts/1"]);
+        process("303", "sleep", "/home/ana", &["/media/usb2/other.txt", "socket:[4711]"]);
+        // Not a process, even though it points at the volume
+        fs::create_dir_all(proc.join("self")).unwrap();
+        symlink("/media/usb", proc.join("self/cwd")).unwrap();
+
+        let mut holders = holders_in(&proc, Path::new("/media/usb")).unwrap();
+        holders.sort();
+        assert_eq!(holders, ["bash (202)", "vim (101)"]);
+        assert!(holders_in(&proc, Path::new("/mnt")).unwrap().is_empty());
+        assert_eq!(holders_in(&proc.join("missing"), Path::new("/media/usb")), None);
+        fs::remove_dir_all(&proc).unwrap();
+    }
+}
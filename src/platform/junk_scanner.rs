@@ -1,208 +1,418 @@
 use std::{
-    collections::HashMap,
     error::Error,
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 use expanduser::expanduser;
-use jwalk::{WalkDir, Parallelism};
+use jwalk::WalkDir;
 use serde::Deserialize;
 use tokio::sync::mpsc::Sender;
-use crate::scanner::{FileEntry, ScanProgressMessage};
-
-#[derive(Debug, Deserialize)]
+use crate::scanner::{self, FileEntry, JunkCategorySummary, ScanOptions, ScanProgressMessage};
+use crate::analysis::{AgeFilter, JunkScanResults};
+use crate::clock::{Clock, SystemClock};
+use crate::filesystem::{FileSystem, LocalFileSystem};
+use crate::storage::profile;
+use super::{browser_profiles, docker};
+
+/// Built-in junk locations, embedded so installed binaries don't depend on the source tree.
+const DEFAULT_JUNK_PATHS: &str = include_str!("junk_paths.toml");
+
+/// Junk paths per OS. Used both for the built-in defaults and for the user override file,
+/// where every section and field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[allow(dead_code)] // only the section for the current OS is read
 pub struct JunkPathsConfig {
+    /// Categories junk paths are grouped into, in display order
+    categories: Vec<JunkCategory>,
     macos: JunkPathsSection,
-    // linux: JunkPathsSection,
-    // windows: JunkPathsSection,
+    linux: JunkPathsSection,
+    windows: JunkPathsSection,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 struct JunkPathsSection {
-    paths: Vec<String>,
+    /// Paths to scan (in the user file: paths added to the defaults, or defaults given a new age)
+    paths: Vec<JunkPathEntry>,
+    /// Default paths to stop scanning (user file only)
+    remove: Vec<String>,
+    /// Minimum file age for every path in this section that doesn't set its own
+    min_age_days: Option<u64>,
 }
 
-/// Results of a junk scan, grouped by directory
-#[derive(Debug, Clone)]
-pub struct JunkScanResults {
-    pub folders: HashMap<String, FolderSummary>,
-    pub total_size: u64,
-    pub total_files: usize,
+/// A named group of junk paths, e.g. "Browser caches".
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct JunkCategory {
+    pub id: String,
+    pub name: String,
 }
 
-/// Summary information for a folder with junk files
-#[derive(Debug, Clone)]
-pub struct FolderSummary {
-    // pub path: String,
-    pub files: Vec<FileEntry>,
-    pub total_size: u64,
+/// Category for junk paths that don't name one.
+const OTHER_CATEGORY: &str = "other";
+
+/// A junk path, either as a plain string or as
+/// `{ path = "...", min_age_days = 30, category = "app_caches" }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum JunkPathEntry {
+    Path(String),
+    Detailed {
+        path: String,
+        min_age_days: Option<u64>,
+        category: Option<String>,
+    },
 }
 
-impl JunkScanResults {
-    pub fn new() -> Self {
-        JunkScanResults {
-            folders: HashMap::new(),
-            total_size: 0,
-            total_files: 0,
+impl JunkPathEntry {
+    fn path(&self) -> &str {
+        match self {
+            JunkPathEntry::Path(path) | JunkPathEntry::Detailed { path, .. } => path,
+        }
+    }
+
+    fn min_age_days(&self) -> Option<u64> {
+        match self {
+            JunkPathEntry::Path(_) => None,
+            JunkPathEntry::Detailed { min_age_days, .. } => *min_age_days,
         }
     }
 
-    /// Add a file to the results, grouping by its parent folder
-    pub fn add_file(&mut self, file: FileEntry) {
-        // Extract parent folder path
-        let path = PathBuf::from(&file.path);
-        let parent_path = if let Some(parent) = path.parent() {
-            parent.to_string_lossy().to_string()
-        } else {
-            // If no parent, use the path itself (unlikely)
-            file.path.clone()
-        };
-
-        // Add file size to total
-        self.total_size += file.size;
-        self.total_files += 1;
-
-        // Add or update folder summary
-        let folder_summary = self.folders.entry(parent_path.clone()).or_insert_with(|| FolderSummary {
-            //path: parent_path,
-            files: Vec::new(),
-            total_size: 0,
-        });
-
-        folder_summary.total_size += file.size;
-        folder_summary.files.push(file);
-    }
-
-    /// Sort folder summaries by size (largest first)
-    pub fn sort_by_size(&mut self) {
-        // Sort files within each folder
-        for folder_summary in self.folders.values_mut() {
-            folder_summary.files.sort_by(|a, b| b.size.cmp(&a.size));
+    fn category(&self) -> Option<&str> {
+        match self {
+            JunkPathEntry::Path(_) => None,
+            JunkPathEntry::Detailed { category, .. } => category.as_deref(),
         }
     }
+}
 
-    /// Convert results to a flat list of file entries sorted by size
-    pub fn to_file_entries(&self) -> Vec<FileEntry> {
-        let mut result = Vec::new();
+/// Paths compare equal regardless of a trailing separator.
+fn normalize_path(path: &str) -> String {
+    path.trim_end_matches(['/', '\\']).to_string()
+}
 
-        for folder in self.folders.values() {
-            for file in &folder.files {
-                result.push(file.clone());
+impl JunkPathsSection {
+    /// Apply a user override: drop the removed paths, then add new ones. A user entry for a
+    /// path that is already listed replaces it, so the user can change its minimum age.
+    fn merge(&mut self, user: JunkPathsSection) {
+        let removed: Vec<String> = user.remove.iter().map(|path| normalize_path(path)).collect();
+        self.paths.retain(|entry| !removed.contains(&normalize_path(entry.path())));
+        for entry in user.paths {
+            let key = normalize_path(entry.path());
+            match self.paths.iter_mut().find(|existing| normalize_path(existing.path()) == key) {
+                Some(existing) => *existing = entry,
+                None => self.paths.push(entry),
             }
         }
+        if user.min_age_days.is_some() {
+            self.min_age_days = user.min_age_days;
+        }
+    }
 
-        result.sort_by(|a, b| b.size.cmp(&a.size));
-        result
+    /// Paths with `~` expanded and their effective minimum age.
+    fn junk_paths(&self) -> Vec<JunkPath> {
+        self.paths
+            .iter()
+            .filter_map(|entry| match expanduser(entry.path()) {
+                Ok(expanded) => Some(JunkPath {
+                    path: expanded.to_string_lossy().to_string(),
+                    category: entry.category().unwrap_or(OTHER_CATEGORY).to_string(),
+                    min_age: entry
+                        .min_age_days()
+                        .or(self.min_age_days)
+                        .filter(|days| *days > 0)
+                        .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                }),
+                Err(_) => {
+                    eprintln!("Failed to expand path: {}", entry.path());
+                    None
+                }
+            })
+            .collect()
     }
 }
 
-/// Load junk paths from the built-in TOML configuration file
-pub fn load_junk_paths_config() -> Result<JunkPathsConfig, Box<dyn Error>> {
-    let config_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("src")
-        .join("platform")
-        .join("junk_paths.toml");
+/// A location to scan for junk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JunkPath {
+    pub path: String,
+    /// Id of the category this path belongs to
+    pub category: String,
+    /// Only files not modified for at least this long are reported
+    pub min_age: Option<Duration>,
+}
 
-    let content = fs::read_to_string(config_path)?;
-    let config: JunkPathsConfig = toml::from_str(&content)?;
+impl JunkPathsConfig {
+    /// User categories with a known id rename it; new ones are appended.
+    fn merge(&mut self, user: JunkPathsConfig) {
+        for category in user.categories {
+            match self.categories.iter_mut().find(|existing| existing.id == category.id) {
+                Some(existing) => *existing = category,
+                None => self.categories.push(category),
+            }
+        }
+        self.macos.merge(user.macos);
+        self.linux.merge(user.linux);
+        self.windows.merge(user.windows);
+    }
 
-    Ok(config)
-}
+    fn current_os_section(&self) -> &JunkPathsSection {
+        #[cfg(target_os = "macos")]
+        let section = &self.macos;
 
-/// Get junk paths for the current OS, with expanded home directories
-pub fn get_junk_paths_for_current_os() -> Result<Vec<String>, Box<dyn Error>> {
-    let config = load_junk_paths_config()?;
+        #[cfg(target_os = "linux")]
+        let section = &self.linux;
 
-    // Get paths for the current OS
-    #[cfg(target_os = "macos")]
-    let paths = config.macos.paths;
+        #[cfg(target_os = "windows")]
+        let section = &self.windows;
 
-    #[cfg(target_os = "linux")]
-    let paths = config.linux.paths;
+        section
+    }
 
-    #[cfg(target_os = "windows")]
-    let paths = config.windows.paths;
+    /// Display name of a category, falling back to its id for categories that aren't defined.
+    fn category_name(&self, id: &str) -> String {
+        match self.categories.iter().find(|category| category.id == id) {
+            Some(category) => category.name.clone(),
+            None if id == OTHER_CATEGORY => "Other".to_string(),
+            None => id.to_string(),
+        }
+    }
 
-    // Expand paths (~ and environment variables)
-    let expanded_paths = paths.iter()
-        .filter_map(|path| {
-            match expanduser(path) {
-                Ok(expanded) => Some(expanded.to_string_lossy().to_string()),
-                Err(_) => {
-                    eprintln!("Failed to expand path: {}", path);
-                    None
-                }
+    /// Categories used by the current OS's paths, in display order (undefined ones last).
+    fn current_os_categories(&self, paths: &[JunkPath]) -> Vec<JunkCategorySummary> {
+        let mut ids: Vec<&str> = self
+            .categories
+            .iter()
+            .map(|category| category.id.as_str())
+            .filter(|id| paths.iter().any(|path| path.category == *id))
+            .collect();
+        for path in paths {
+            if !ids.contains(&path.category.as_str()) {
+                ids.push(&path.category);
             }
-        })
-        .collect();
+        }
+        ids.into_iter()
+            .map(|id| JunkCategorySummary {
+                id: id.to_string(),
+                name: self.category_name(id),
+                roots: paths
+                    .iter()
+                    .filter(|path| path.category == id)
+                    .map(|path| path.path.clone())
+                    .collect(),
+                total_size: 0,
+                file_count: 0,
+            })
+            .collect()
+    }
+}
+
+/// Location of the user's junk paths override file, per profile.
+pub fn user_junk_paths_file() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(profile::dir("~/.config/lazysmg")?.join("junk_paths.toml"))
+}
+
+/// Load the built-in junk paths merged with the user's `~/.config/lazysmg/junk_paths.toml`, if present.
+pub fn load_junk_paths_config() -> Result<JunkPathsConfig, Box<dyn Error>> {
+    let mut config: JunkPathsConfig = toml::from_str(DEFAULT_JUNK_PATHS)?;
+
+    let user_path = user_junk_paths_file()?;
+    if user_path.exists() {
+        let content = fs::read_to_string(&user_path)?;
+        let user: JunkPathsConfig = toml::from_str(&content)
+            .map_err(|e| format!("Invalid junk paths file {}: {}", user_path.display(), e))?;
+        config.merge(user);
+    }
+
+    Ok(config)
+}
+
+/// The junk locations of this OS, built-in and the user's, with `~` expanded. Empty if the
+/// user's junk paths file can't be read.
+pub fn junk_roots() -> Vec<String> {
+    load_junk_paths_config()
+        .map(|config| config.current_os_section().junk_paths().into_iter().map(|junk| junk.path).collect())
+        .unwrap_or_default()
+}
 
-    Ok(expanded_paths)
+/// Minimum age of the files reported in a junk location added from a suggestion, since it is
+/// only a guess that they are disposable.
+const SUGGESTED_MIN_AGE_DAYS: i64 = 30;
+
+/// Category of a suggested junk location, by its name.
+fn suggested_category(path: &str) -> Option<&'static str> {
+    let name = Path::new(path).file_name()?.to_string_lossy().to_lowercase();
+    if name.contains("cache") {
+        Some("app_caches")
+    } else if name.contains("log") {
+        Some("logs")
+    } else if matches!(name.trim_start_matches('.'), "tmp" | "temp") {
+        Some("temp")
+    } else {
+        None
+    }
+}
+
+/// Add `path` to the junk locations of this OS in the user's junk paths file, creating it if
+/// needed. Only files older than `SUGGESTED_MIN_AGE_DAYS` are reported there. The rest of the
+/// file, comments included, is left as it is.
+pub fn add_user_junk_path(path: &str) -> Result<(), Box<dyn Error>> {
+    let os = if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "windows") {
+        "windows"
+    } else {
+        "linux"
+    };
+    let file = user_junk_paths_file()?;
+    let content = match fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut document: toml_edit::Document = content
+        .parse()
+        .map_err(|e| format!("Invalid junk paths file {}: {}", file.display(), e))?;
+
+    // Written like the built-in paths, with the home directory as `~`
+    let home = expanduser("~")?;
+    let written = match Path::new(path).strip_prefix(&home) {
+        Ok(rest) => format!("~/{}/", rest.display()),
+        Err(_) => format!("{}/", path.trim_end_matches('/')),
+    };
+    let mut entry = toml_edit::InlineTable::new();
+    entry.insert("path", written.into());
+    entry.insert("min_age_days", SUGGESTED_MIN_AGE_DAYS.into());
+    if let Some(category) = suggested_category(path) {
+        entry.insert("category", category.into());
+    }
+
+    if !document.contains_table(os) {
+        document[os] = toml_edit::table();
+    }
+    let section = &mut document[os];
+    if section.get("paths").is_none() {
+        section["paths"] = toml_edit::value(toml_edit::Array::new());
+    }
+    match &mut section["paths"] {
+        toml_edit::Item::Value(toml_edit::Value::Array(paths)) => paths.push(entry),
+        toml_edit::Item::ArrayOfTables(paths) => paths.push(entry.into_table()),
+        _ => return Err(format!("{}: [{}] paths isn't a list", file.display(), os).into()),
+    }
+    crate::storage::persist::write_atomic(&file, document.to_string().as_bytes())?;
+    Ok(())
 }
 
 /// Scan system junk, using the junk_paths.toml configuration
 /// Sends progress updates through the provided channel and returns the final results
 pub async fn scan_system_junk(
     progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+) -> Result<JunkScanResults, Box<dyn Error>> {
+    scan_system_junk_with(progress_tx, options, &SystemClock, &LocalFileSystem).await
+}
+
+/// `scan_system_junk`, judging the age of files by `clock` and reading their size and
+/// modification time through `fs`.
+pub async fn scan_system_junk_with(
+    progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+    clock: &dyn Clock,
+    fs: &dyn FileSystem,
 ) -> Result<JunkScanResults, Box<dyn Error>> {
-    let junk_paths = get_junk_paths_for_current_os()?;
+    let config = load_junk_paths_config()?;
+    let junk_paths = config.current_os_section().junk_paths();
+    let mut categories = config.current_os_categories(&junk_paths);
     let mut results = JunkScanResults::new();
+    let mut errors = Vec::new();
 
     // Scan each junk path
-    for base_path in junk_paths {
+    for JunkPath { path: base_path, category, min_age } in &junk_paths {
         // Skip if path doesn't exist
-        if !PathBuf::from(&base_path).exists() {
+        if !PathBuf::from(base_path).exists() {
             continue;
         }
-
-        // Walk directory
-        for entry in WalkDir::new(&base_path)
-            .parallelism(Parallelism::RayonDefaultPool {
-                busy_timeout: Duration::from_millis(100),
+        let age_filter = AgeFilter::new(*min_age, clock);
+        // Files under a more specific junk path (e.g. a browser cache inside the app caches)
+        // are counted there instead, so nothing is reported twice.
+        let nested: Vec<&str> = junk_paths
+            .iter()
+            .map(|other| other.path.as_str())
+            .filter(|other| {
+                Path::new(other).starts_with(base_path) && Path::new(other) != Path::new(base_path)
             })
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
+            .collect();
+        let summary = categories.iter_mut().find(|summary| summary.id == *category);
+        let mut category_size = 0;
+        let mut category_files = 0;
+
+        // Walk directory; what can't be read is reported with the results
+        for result in WalkDir::new(base_path).parallelism(options.jwalk_parallelism()) {
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(error) => {
+                    errors.push(scanner::jwalk_error(&error, Path::new(base_path)));
+                    continue;
+                }
+            };
             let ft = entry.file_type();
-            if ft.is_file() {
-                if let Ok(metadata) = entry.metadata() {
-                    let path = entry.path();
-                    let size = metadata.len();
-                    let name = path
-                        .file_name()
-                        .map(|os_str| os_str.to_string_lossy().into_owned())
-                        .unwrap_or_else(|| path.to_string_lossy().into_owned());
-
-                    // Create file entry
-                    let file_entry = FileEntry {
-                        name,
-                        path: path.to_string_lossy().into_owned(),
-                        size,
-                    };
-
-                    // Add file to results
-                    results.add_file(file_entry.clone());
-
-                    // Send progress update
-                    let progress_msg = ScanProgressMessage::FileScanned {
-                        size,
-                        path: path.to_string_lossy().into_owned(),
-                    };
-
-                    // Only log errors in debug mode
-                    if let Err(_) = progress_tx.send(progress_msg).await {
-                        // Channel closed, likely because the app is shutting down
-                        // Return early to avoid more errors
-                        return Ok(results);
-                    }
+            if ft.is_file()
+                && let Ok(info) = fs.file_info(&entry.path())
+                && age_filter.admits(info.modified)
+                && !nested.iter().any(|other| entry.path().starts_with(other))
+            {
+                let path = entry.path();
+                let size = info.size;
+                let name = path
+                    .file_name()
+                    .map(|os_str| os_str.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.to_string_lossy().into_owned());
+
+                // Create file entry
+                let file_entry = FileEntry {
+                    name,
+                    path: path.to_string_lossy().into_owned(),
+                    size,
+                    link_target: None,
+                    allocated_size: info.allocated_size,
+                };
+
+                // Add file to results
+                results.add_file(file_entry.clone());
+                category_size += size;
+                category_files += 1;
+
+                // Send progress update
+                let progress_msg = ScanProgressMessage::FileScanned {
+                    size,
+                    path: path.to_string_lossy().into_owned(),
+                };
+
+                // Only log errors in debug mode
+                if progress_tx.send(progress_msg).await.is_err() {
+                    // Channel closed, likely because the app is shutting down
+                    // Return early to avoid more errors
+                    return Ok(results);
                 }
             }
         }
+
+        if let Some(summary) = summary {
+            summary.total_size += category_size;
+            summary.file_count += category_files;
+        }
     }
 
     // Sort results
     results.sort_by_size();
 
+    // Docker keeps its data in a VM disk image (macOS) or under /var/lib/docker, which the
+    // junk paths can't attribute, so ask Docker itself
+    let docker = tokio::task::spawn_blocking(docker::disk_usage).await.ok().flatten();
+    // Browser profiles are split into cache, history and extensions, which one junk path can't do
+    let browsers = tokio::task::spawn_blocking(browser_profiles::find_profiles).await.unwrap_or_default();
+
     // Send completion message
     let completion_msg = ScanProgressMessage::JunkScanComplete {
         results: results.to_file_entries(),
@@ -210,10 +420,14 @@ pub async fn scan_system_junk(
         folder_summaries: results.folders.iter()
             .map(|(path, summary)| (path.clone(), summary.total_size, summary.files.len()))
             .collect(),
+        errors,
+        categories,
+        docker,
+        browsers,
     };
 
     // Ignore errors - the app may have been closed
     let _ = progress_tx.send(completion_msg).await;
 
     Ok(results)
-}
\ No newline at end of file
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    time::SystemTime,
+};
+use chrono::DateTime;
+
+/// Paths handed to one `mdls` call, to stay well below the argument length limit.
+#[cfg(target_os = "macos")]
+const MDLS_BATCH: usize = 200;
+
+/// When each of `paths` was last opened, for those where that is known. On macOS Spotlight
+/// records it (`kMDItemLastUsedDate`) whenever an app opens a file. Elsewhere, and for files
+/// Spotlight doesn't index, the access time is used, but only on volumes mounted without
+/// `noatime`, where it tells when the file was last read.
+pub fn last_opened(paths: &[String]) -> HashMap<String, SystemTime> {
+    let mut found = spotlight_last_used(paths);
+    let mounts = mount_table();
+    for path in paths {
+        if found.contains_key(path) || !atime_reliable(&mounts, path) {
+            continue;
+        }
+        if let Ok(accessed) = fs::symlink_metadata(path).and_then(|metadata| metadata.accessed()) {
+            found.insert(path.clone(), accessed);
+        }
+    }
+    found
+}
+
+#[cfg(target_os = "macos")]
+fn spotlight_last_used(paths: &[String]) -> HashMap<String, SystemTime> {
+    let mut found = HashMap::new();
+    for batch in paths.chunks(MDLS_BATCH) {
+        let Ok(output) = std::process::Command::new("mdls").args(["-raw", "-name", "kMDItemLastUsedDate"]).args(batch).output() else {
+            return found;
+        };
+        let dates = parse_mdls_dates(&String::from_utf8_lossy(&output.stdout));
+        // The output only lines up with the paths if mdls could read all of them
+        if dates.len() == batch.len() {
+            found.extend(batch.iter().zip(dates).filter_map(|(path, date)| Some((path.clone(), date?))));
+        }
+    }
+    found
+}
+
+#[cfg(not(target_os = "macos"))]
+fn spotlight_last_used(_paths: &[String]) -> HashMap<String, SystemTime> {
+    HashMap::new()
+}
+
+/// The values `mdls -raw` prints for several files, separated by NUL: a date such as
+/// `2024-03-01 09:12:44 +0000`, or `(null)` for files without one.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_mdls_dates(output: &str) -> Vec<Option<SystemTime>> {
+    output
+        .split('\0')
+        .map(|value| DateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S %z").ok().map(SystemTime::from))
+        .collect()
+}
+
+/// Mount points with whether they are mounted with `noatime`.
+fn mount_table() -> Vec<(String, bool)> {
+    #[cfg(target_os = "linux")]
+    {
+        fs::read_to_string("/proc/self/mounts").map(|table| parse_proc_mounts(&table)).unwrap_or_default()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        std::process::Command::new("mount")
+            .output()
+            .map(|output| parse_mount_output(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default()
+    }
+}
+
+/// `/proc/self/mounts`: device, mount point (with spaces written as `\040`), type and options.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_mounts(table: &str) -> Vec<(String, bool)> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let options = fields.nth(1)?;
+            Some((mount_point, options.split(',').any(|option| option == "noatime")))
+        })
+        .collect()
+}
+
+/// Output of `mount` on macOS and the BSDs: `/dev/disk3s1 on /Volumes/USB (apfs, local, noatime)`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn parse_mount_output(output: &str) -> Vec<(String, bool)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            Some((mount_point.to_string(), options.trim_end_matches(')').split(", ").any(|option| option == "noatime")))
+        })
+        .collect()
+}
+
+/// Whether reading the file at `path` updates its access time: the volume it is on, the
+/// innermost mount point above it, isn't mounted with `noatime`. Unknown volumes count as
+/// reliable, like the system defaults.
+fn atime_reliable(mounts: &[(String, bool)], path: &str) -> bool {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| Path::new(path).starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .is_none_or(|(_, noatime)| !noatime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_spotlight_dates() {
+        let dates = parse_mdls_dates("2024-03-01 09:12:44 +0000\x00(null)\x002024-03-01 10:12:44 +0100");
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_284_364);
+        assert_eq!(dates, [Some(expected), None, Some(expected)]);
+    }
+
+    #[test]
+    fn trusts_atime_unless_the_volume_is_noatime() {
+        let linux = parse_proc_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n/dev/sdb1 /mnt/My\\040Disk ext4 rw,noatime 0 0\n/dev/sdb2 /mnt/My\\040Disk/inner xfs rw 0 0\n",
+        );
+        assert!(atime_reliable(&linux, "/home/a/movie.mkv"));
+        assert!(!atime_reliable(&linux, "/mnt/My Disk/movie.mkv"));
+        assert!(atime_reliable(&linux, "/mnt/My Disk/inner/movie.mkv"));
+        // A sibling that only shares a prefix of the name
+        assert!(atime_reliable(&linux, "/mnt/My Diskette/movie.mkv"));
+
+        let macos = parse_mount_output(
+            "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n/dev/disk4s1 on /Volumes/Backup (hfs, local, nodev, nosuid, noatime, journaled)\n",
+        );
+        assert!(atime_reliable(&macos, "/Users/a/movie.mov"));
+        assert!(!atime_reliable(&macos, "/Volumes/Backup/movie.mov"));
+        assert!(atime_reliable(&[], "/anywhere"));
+    }
+}
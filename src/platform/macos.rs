@@ -1,7 +1,21 @@
-use std::process::Command;
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
 use sysinfo::{DiskExt, System, SystemExt};
+use super::smart::{self, SmartInfo};
+use super::trash;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StorageDevice {
     pub name: String,
     pub total_space: u64,
@@ -9,92 +23,616 @@ pub struct StorageDevice {
     pub mount_point: String,
     pub ejectable: bool,
     pub vendor_info: Option<String>,
+    pub smart: Option<SmartInfo>,
+    pub uuid: Option<String>,
+    /// APFS container the volume belongs to, e.g. "disk3"
+    pub container: Option<String>,
+    /// Whether the volume is encrypted (None when diskutil didn't say)
+    pub encrypted: Option<bool>,
+    /// Size of the current user's trash on the volume (None when it has none)
+    pub trash_size: Option<u64>,
+    /// Whether the home directory is on this volume, so its trash is the home trash
+    #[serde(default)]
+    pub home_volume: bool,
+    /// Whether the volume is mounted. Volumes that are attached but not mounted are listed too,
+    /// so they can be mounted; they have no mount point and no free space.
+    #[serde(default = "mounted_by_default")]
+    pub mounted: bool,
+    /// Device node, e.g. "/dev/disk4s1", which mounting an unmounted volume needs
+    #[serde(default)]
+    pub device_node: Option<String>,
+    /// File system ID (`st_dev`) of the mount point; mounts with the same one show the same data
+    #[serde(default)]
+    pub fs_id: Option<u64>,
+    /// Mount point of another listed device that exposes the same data (a bind mount, a
+    /// duplicate mount, or the firmlinked `/System/Volumes/Data` of `/`)
+    #[serde(default)]
+    pub alias_of: Option<String>,
+}
+
+fn mounted_by_default() -> bool {
+    true
+}
+
+impl StorageDevice {
+    /// An attached volume that isn't mounted.
+    fn unmounted(name: String, device_node: String, size: u64) -> Self {
+        StorageDevice {
+            name,
+            total_space: size,
+            available_space: 0,
+            mount_point: String::new(),
+            ejectable: false,
+            vendor_info: None,
+            smart: None,
+            uuid: None,
+            container: None,
+            encrypted: None,
+            trash_size: None,
+            home_volume: false,
+            mounted: false,
+            device_node: Some(device_node),
+            fs_id: None,
+            alias_of: None,
+        }
+    }
+
+    /// Whether `spec`, as written in the config file, names this device: its name, mount point
+    /// or volume UUID.
+    pub fn matches(&self, spec: &str) -> bool {
+        self.name == spec || self.mount_point == spec || self.uuid.as_deref() == Some(spec)
+    }
+
+    /// What identifies the device across refreshes: the mount point, or the device node of an
+    /// unmounted volume.
+    pub fn key(&self) -> &str {
+        match &self.device_node {
+            Some(node) if !self.mounted => node,
+            _ => &self.mount_point,
+        }
+    }
+}
+
+/// The fields of `diskutil info -plist <mount_point>` that lazysmg uses.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DiskutilInfo {
+    /// File system personality, e.g. "APFS"
+    #[serde(rename = "FilesystemName")]
+    pub filesystem: Option<String>,
+    /// Device / media name, usually the manufacturer and model
+    #[serde(rename = "MediaName")]
+    pub media_name: Option<String>,
+    #[serde(rename = "BusProtocol")]
+    pub protocol: Option<String>,
+    /// Whole disk the volume is part of, e.g. "disk3"
+    #[serde(rename = "ParentWholeDisk")]
+    pub parent_whole_disk: Option<String>,
+    /// "Verified", "Failing" or "Not Supported"
+    #[serde(rename = "SMARTStatus")]
+    pub smart_status: Option<String>,
+    #[serde(rename = "VolumeUUID")]
+    pub volume_uuid: Option<String>,
+    #[serde(rename = "APFSContainerReference")]
+    pub container: Option<String>,
+    #[serde(rename = "Ejectable")]
+    pub ejectable: Option<bool>,
+    #[serde(rename = "RemovableMedia")]
+    pub removable_media: Option<bool>,
+    /// APFS volume encryption
+    #[serde(rename = "Encryption")]
+    pub encryption: Option<bool>,
+    #[serde(rename = "FileVault")]
+    pub file_vault: Option<bool>,
+    /// Core Storage encryption, reported by macOS versions before APFS
+    #[serde(rename = "CoreStorageEncrypted")]
+    pub core_storage_encrypted: Option<bool>,
+    /// e.g. "/dev/disk4s1"
+    #[serde(rename = "DeviceNode")]
+    pub device_node: Option<String>,
+    #[serde(rename = "SolidState")]
+    pub solid_state: Option<bool>,
+}
+
+impl DiskutilInfo {
+    /// Summary shown in the device details, e.g. "FS: APFS, Manufacturer: ..., Protocol: USB".
+    fn vendor_info(&self) -> Option<String> {
+        let info_vec: Vec<String> = [
+            ("FS", &self.filesystem),
+            ("Manufacturer", &self.media_name),
+            ("Protocol", &self.protocol),
+        ]
+        .iter()
+        .filter_map(|(label, value)| value.as_ref().map(|value| format!("{}: {}", label, value)))
+        .collect();
+        if info_vec.is_empty() {
+            None
+        } else {
+            Some(info_vec.join(", "))
+        }
+    }
+
+    /// Whether the volume can be ejected, or None when diskutil reported neither flag.
+    fn is_ejectable(&self) -> Option<bool> {
+        match (self.ejectable, self.removable_media) {
+            (None, None) => None,
+            (ejectable, removable) => Some(ejectable.unwrap_or(false) || removable.unwrap_or(false)),
+        }
+    }
+
+    /// Whether any form of encryption is on, or None when diskutil reported none of them.
+    fn is_encrypted(&self) -> Option<bool> {
+        [self.encryption, self.file_vault, self.core_storage_encrypted]
+            .into_iter()
+            .flatten()
+            .reduce(|a, b| a || b)
+    }
+
+    /// SMART data from `smartctl` for the whole disk, falling back to diskutil's own status.
+    fn smart(&self) -> Option<SmartInfo> {
+        self.parent_whole_disk
+            .as_ref()
+            .and_then(|disk| smart::query_smart(&format!("/dev/{}", disk)))
+            .or_else(|| match self.smart_status.as_deref() {
+                Some(status @ ("Verified" | "Failing")) => Some(SmartInfo {
+                    passed: Some(status == "Verified"),
+                    power_on_hours: None,
+                    wear_percent: None,
+                    reallocated_sectors: None,
+                }),
+                _ => None,
+            })
+    }
+}
+
+/// How long the `diskutil info` of a volume is reused before diskutil is run again. Devices are
+/// detected twice a second, while what diskutil reports only changes with the volume; a volume
+/// that is mounted anew is queried right away.
+const DEVICE_INFO_TTL: Duration = Duration::from_secs(60);
+
+/// Last query time and result per mount point.
+type DeviceInfoCache = HashMap<String, (Instant, Option<DiskutilInfo>)>;
+
+static DEVICE_INFO_CACHE: LazyLock<Mutex<DeviceInfoCache>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// `diskutil_info`, cached per mount point for `DEVICE_INFO_TTL`.
+fn cached_diskutil_info(mount_point: &str) -> Option<DiskutilInfo> {
+    if let Ok(cache) = DEVICE_INFO_CACHE.lock()
+        && let Some((at, info)) = cache.get(mount_point)
+        && at.elapsed() < DEVICE_INFO_TTL
+    {
+        return info.clone();
+    }
+    let info = diskutil_info(mount_point);
+    if let Ok(mut cache) = DEVICE_INFO_CACHE.lock() {
+        cache.insert(mount_point.to_string(), (Instant::now(), info.clone()));
+    }
+    info
+}
+
+/// Drop the cached diskutil and SMART data of `device`, so the next device detection queries
+/// them again instead of waiting for the caches to expire.
+pub fn refresh_device_info(device: &StorageDevice) {
+    let cached = DEVICE_INFO_CACHE.lock().ok().and_then(|mut cache| cache.remove(&device.mount_point));
+    if let Some(disk) = cached.and_then(|(_, info)| info).and_then(|info| info.parent_whole_disk) {
+        smart::forget(&format!("/dev/{}", disk));
+    }
+}
+
+/// Run `diskutil info -plist` for a mount point and parse its output.
+pub fn diskutil_info(mount_point: &str) -> Option<DiskutilInfo> {
+    let output = Command::new("diskutil")
+        .arg("info")
+        .arg("-plist")
+        .arg(mount_point)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    plist::from_bytes(&output.stdout).ok()
+}
+
+/// A disk, partition or APFS volume in `diskutil list -plist`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiskutilListEntry {
+    #[serde(rename = "DeviceIdentifier")]
+    identifier: String,
+    #[serde(rename = "VolumeName")]
+    volume_name: Option<String>,
+    #[serde(rename = "MountPoint")]
+    mount_point: Option<String>,
+    #[serde(rename = "Size")]
+    size: u64,
+    /// Partition type, e.g. "EFI" or "Apple_HFS"
+    #[serde(rename = "Content")]
+    content: Option<String>,
+    #[serde(rename = "Partitions")]
+    partitions: Vec<DiskutilListEntry>,
+    #[serde(rename = "APFSVolumes")]
+    apfs_volumes: Vec<DiskutilListEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiskutilList {
+    #[serde(rename = "AllDisksAndPartitions")]
+    disks: Vec<DiskutilListEntry>,
+}
+
+impl DiskutilListEntry {
+    /// This entry and the ones below it that are named volumes without a mount point.
+    fn collect_unmounted(self, volumes: &mut Vec<StorageDevice>) {
+        let is_efi = self.content.as_deref() == Some("EFI");
+        if let Some(name) = self.volume_name.filter(|name| !name.is_empty() && name != "EFI")
+            && self.mount_point.is_none()
+            && !is_efi
+        {
+            volumes.push(StorageDevice::unmounted(name, format!("/dev/{}", self.identifier), self.size));
+        }
+        for entry in self.partitions.into_iter().chain(self.apfs_volumes) {
+            entry.collect_unmounted(volumes);
+        }
+    }
+}
+
+/// A block device in `lsblk --json`. Older versions print numbers and flags as strings.
+#[derive(Debug, Deserialize)]
+struct LsblkDevice {
+    path: Option<String>,
+    label: Option<String>,
+    #[serde(default)]
+    size: serde_json::Value,
+    mountpoint: Option<String>,
+    fstype: Option<String>,
+    #[serde(default)]
+    hotplug: serde_json::Value,
+    #[serde(default)]
+    rm: serde_json::Value,
+    #[serde(default)]
+    children: Vec<LsblkDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LsblkOutput {
+    blockdevices: Vec<LsblkDevice>,
+}
+
+fn json_u64(value: &serde_json::Value) -> u64 {
+    match value {
+        serde_json::Value::Number(number) => number.as_u64().unwrap_or(0),
+        serde_json::Value::String(text) => text.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn json_flag(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(flag) => *flag,
+        serde_json::Value::String(text) => text == "1" || text == "true",
+        other => json_u64(other) == 1,
+    }
+}
+
+/// File systems of block devices that aren't volumes one would mount.
+const NOT_MOUNTABLE: [&str; 4] = ["swap", "crypto_LUKS", "LVM2_member", "linux_raid_member"];
+
+impl LsblkDevice {
+    /// This device and its partitions that hold a mountable file system but aren't mounted,
+    /// on removable (or hot-pluggable) disks only.
+    fn collect_unmounted(self, removable: bool, volumes: &mut Vec<StorageDevice>) {
+        let removable = removable || json_flag(&self.hotplug) || json_flag(&self.rm);
+        if let (Some(path), Some(fstype)) = (&self.path, &self.fstype)
+            && removable
+            && self.mountpoint.is_none()
+            && !NOT_MOUNTABLE.contains(&fstype.as_str())
+        {
+            let name = self
+                .label
+                .clone()
+                .filter(|label| !label.is_empty())
+                .unwrap_or_else(|| path.trim_start_matches("/dev/").to_string());
+            volumes.push(StorageDevice::unmounted(name, path.clone(), json_u64(&self.size)));
+        }
+        for child in self.children {
+            child.collect_unmounted(removable, volumes);
+        }
+    }
+}
+
+/// Volumes that are attached but not mounted: those of external disks in `diskutil list` on
+/// macOS, and those of removable disks in `lsblk` elsewhere. Empty when the tool isn't there.
+fn unmounted_volumes() -> Vec<StorageDevice> {
+    let mut volumes = Vec::new();
+    if cfg!(target_os = "macos") {
+        let Ok(output) = Command::new("diskutil").args(["list", "-plist", "external"]).output() else {
+            return volumes;
+        };
+        let list: DiskutilList = plist::from_bytes(&output.stdout).unwrap_or_default();
+        for disk in list.disks {
+            disk.collect_unmounted(&mut volumes);
+        }
+    } else {
+        let Ok(output) = Command::new("lsblk")
+            .args(["--json", "--bytes", "-o", "PATH,LABEL,SIZE,MOUNTPOINT,FSTYPE,HOTPLUG,RM"])
+            .output()
+        else {
+            return volumes;
+        };
+        if let Ok(list) = serde_json::from_slice::<LsblkOutput>(&output.stdout) {
+            for device in list.blockdevices {
+                device.collect_unmounted(false, &mut volumes);
+            }
+        }
+    }
+    volumes
+}
+
+/// Upper bound on concurrent `diskutil`/`smartctl` invocations while enumerating devices.
+const MAX_ENUMERATION_WORKERS: usize = 8;
+
+/// Map `f` over `items` on a small pool of scoped threads, preserving order.
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_ENUMERATION_WORKERS)
+        .min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
 }
 
 /// Detects storage devices (local and mounted) on macOS using the sysinfo crate.
-/// For each disk, we additionally run "diskutil info <mount_point>" and attempt to extract:
-/// - File System Personality (FS type)
-/// - Device / Media Name (Manufacturer)
-/// - Protocol
+/// Each disk is then enriched from `diskutil info -plist <mount_point>` with its file system,
+/// manufacturer, protocol, volume UUID, APFS container, encryption status and whether it can be
+/// ejected (falling back to "mounted under /Volumes/" when diskutil is unavailable). The diskutil (and smartctl)
+/// calls run in parallel, since running them one disk at a time takes seconds with many volumes.
+///
+/// SMART health is read with `smartctl` for the whole disk the volume belongs to, falling back
+/// to diskutil's SMART status when smartctl is not installed.
+///
+/// Volumes that are attached but not mounted follow the mounted ones, see `unmounted_volumes`.
 pub fn detect_storage_devices() -> Vec<StorageDevice> {
     let mut sys = System::new_all();
     sys.refresh_disks_list();
     sys.refresh_disks();
 
-    sys.disks()
+    let disks: Vec<(String, String, u64, u64)> = sys
+        .disks()
         .iter()
         .map(|disk| {
-            let mount_str = disk.mount_point().to_string_lossy().to_string();
-            // Consider device ejectable if mount point starts with "/Volumes/"
-            let ejectable = mount_str.starts_with("/Volumes/");
-
-            // Try to gather extra info using "diskutil info"
-            let vendor_info = {
-                let output = Command::new("diskutil")
-                    .arg("info")
-                    .arg(&mount_str)
-                    .output();
-
-                if let Ok(output) = output {
-                    let info_str = String::from_utf8_lossy(&output.stdout);
-                    let mut media = None;
-                    let mut protocol = None;
-                    let mut fs_type = None;
-                    for line in info_str.lines() {
-                        if line.contains("Device / Media Name:") {
-                            media = line.split(':').nth(1).map(|s| s.trim().to_string());
-                        } else if line.contains("Protocol:") {
-                            protocol = line.split(':').nth(1).map(|s| s.trim().to_string());
-                        } else if line.contains("File System Personality:") {
-                            fs_type = line.split(':').nth(1).map(|s| s.trim().to_string());
-                        }
-                    }
-                    let mut info_vec = Vec::new();
-                    if let Some(fs) = fs_type {
-                        info_vec.push(format!("FS: {}", fs));
-                    }
-                    if let Some(manu) = media {
-                        info_vec.push(format!("Manufacturer: {}", manu));
-                    }
-                    if let Some(proto) = protocol {
-                        info_vec.push(format!("Protocol: {}", proto));
-                    }
-                    if !info_vec.is_empty() {
-                        Some(info_vec.join(", "))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            };
-
-            StorageDevice {
-                name: disk.name().to_string_lossy().to_string(),
-                total_space: disk.total_space(),
-                available_space: disk.available_space(),
-                mount_point: mount_str,
-                ejectable,
-                vendor_info,
-            }
+            (
+                disk.name().to_string_lossy().to_string(),
+                disk.mount_point().to_string_lossy().to_string(),
+                disk.total_space(),
+                disk.available_space(),
+            )
         })
-        .collect()
+        .collect();
+    let mount_points: Vec<&str> = disks.iter().map(|(_, mount, _, _)| mount.as_str()).collect();
+    let home_mount = trash::home_mount_point(&mount_points);
+
+    let mut devices = parallel_map(&disks, |(name, mount_str, total_space, available_space)| {
+        let info = cached_diskutil_info(mount_str).unwrap_or_default();
+        let home_volume = home_mount.as_ref() == Some(mount_str);
+        StorageDevice {
+            name: name.clone(),
+            total_space: *total_space,
+            available_space: *available_space,
+            mount_point: mount_str.clone(),
+            ejectable: info.is_ejectable().unwrap_or_else(|| mount_str.starts_with("/Volumes/")),
+            vendor_info: info.vendor_info(),
+            smart: info.smart(),
+            encrypted: info.is_encrypted(),
+            uuid: info.volume_uuid,
+            container: info.container,
+            trash_size: trash::trash_size(mount_str, home_volume),
+            home_volume,
+            mounted: true,
+            device_node: info.device_node,
+            fs_id: filesystem_id(mount_str),
+            alias_of: None,
+        }
+    });
+    mark_aliases(&mut devices);
+    devices.extend(unmounted_volumes());
+    devices
+}
+
+/// Free space of the volume mounted at `mount_point`, as sysinfo reports it for the device
+/// list; None if no such volume is mounted.
+pub fn available_space(mount_point: &str) -> Option<u64> {
+    let mut sys = System::new();
+    sys.refresh_disks_list();
+    sys.disks()
+        .iter()
+        .find(|disk| disk.mount_point() == Path::new(mount_point))
+        .map(|disk| disk.available_space())
+}
+
+/// Where macOS mounts the data volume that is firmlinked into `/`.
+const FIRMLINKED_DATA_VOLUME: &str = "/System/Volumes/Data";
+
+#[cfg(unix)]
+fn filesystem_id(mount_point: &str) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(mount_point).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn filesystem_id(_mount_point: &str) -> Option<u64> {
+    None
+}
+
+/// Set `alias_of` on every mounted device that shows the same data as another one: mounts of
+/// the same file system (the shortest mount point counts as the original), and the firmlinked
+/// data volume, which belongs to `/` when both are in the same APFS container.
+fn mark_aliases(devices: &mut [StorageDevice]) {
+    for index in 0..devices.len() {
+        let device = &devices[index];
+        let same_fs = devices
+            .iter()
+            .filter(|other| other.mount_point != device.mount_point && device.fs_id.is_some() && other.fs_id == device.fs_id)
+            .filter(|other| (other.mount_point.len(), &other.mount_point) < (device.mount_point.len(), &device.mount_point))
+            .min_by_key(|other| (other.mount_point.len(), &other.mount_point));
+        let firmlinked_root = devices.iter().find(|other| {
+            device.mount_point == FIRMLINKED_DATA_VOLUME
+                && other.mount_point == "/"
+                && device.container.is_some()
+                && other.container == device.container
+        });
+        let alias_of = same_fs.or(firmlinked_root).map(|original| original.mount_point.clone());
+        devices[index].alias_of = alias_of;
+    }
+}
+
+/// Combined (total, available) space of `devices`, counting data shown by several of them once:
+/// aliases are skipped, and APFS volumes of one container, which share its space, count once.
+/// Unmounted volumes aren't included.
+pub fn distinct_space<'a>(devices: impl IntoIterator<Item = &'a StorageDevice>) -> (u64, u64) {
+    let mut containers: Vec<&str> = Vec::new();
+    let mut total = (0, 0);
+    for device in devices.into_iter().filter(|device| device.mounted && device.alias_of.is_none()) {
+        if let Some(container) = device.container.as_deref() {
+            if containers.contains(&container) {
+                continue;
+            }
+            containers.push(container);
+        }
+        total.0 += device.total_space;
+        total.1 += device.available_space;
+    }
+    total
 }
 
-/// Ejects a storage device on macOS by invoking "diskutil eject <mount_point>".
+/// Ejects a storage device on macOS by invoking "diskutil eject <mount_point>", which unmounts
+/// every volume of the disk and detaches it.
 /// Returns Ok(()) if the command succeeds; otherwise returns an error.
 pub fn eject_device(device: &StorageDevice) -> Result<(), Box<dyn std::error::Error>> {
-    use std::process::Command;
-    let output = Command::new("diskutil")
-        .arg("eject")
-        .arg(&device.mount_point)
-        .output()?;
+    run_tool("diskutil", &["eject", &device.mount_point])
+}
+
+/// Unmounts just this volume, leaving the disk attached so the volume can be mounted again.
+/// Uses `diskutil unmount` on macOS and `umount` elsewhere.
+pub fn unmount_device(device: &StorageDevice) -> Result<(), Box<dyn std::error::Error>> {
+    if cfg!(target_os = "macos") {
+        run_tool("diskutil", &["unmount", &device.mount_point])
+    } else {
+        run_tool("umount", &[&device.mount_point])
+    }
+}
+
+/// Mounts an attached volume that isn't mounted, with `diskutil mount` on macOS and
+/// `udisksctl mount` elsewhere.
+pub fn mount_device(device: &StorageDevice) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(node) = device.device_node.as_deref() else {
+        return Err(format!("{} has no device node", device.name).into());
+    };
+    if cfg!(target_os = "macos") {
+        run_tool("diskutil", &["mount", node])
+    } else {
+        run_tool("udisksctl", &["mount", "-b", node])
+    }
+}
+
+/// What to try after a normal eject failed, from least to most disruptive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EjectEscalation {
+    /// Try a normal eject again, e.g. after closing the app that held the volume
+    Retry,
+    /// Unmount the volume but leave the disk attached
+    UnmountOnly,
+    /// Unmount even though files are open, then eject
+    Force,
+}
+
+impl EjectEscalation {
+    /// Every escalation, in the order the dialog offers them.
+    pub const ALL: [EjectEscalation; 3] = [EjectEscalation::Retry, EjectEscalation::UnmountOnly, EjectEscalation::Force];
+
+    /// Key that picks this escalation in the dialog.
+    pub fn key(self) -> char {
+        match self {
+            EjectEscalation::Retry => 'r',
+            EjectEscalation::UnmountOnly => 'u',
+            EjectEscalation::Force => 'f',
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EjectEscalation::Retry => "Retry",
+            EjectEscalation::UnmountOnly => "Unmount only",
+            EjectEscalation::Force => "Force eject",
+        }
+    }
+
+    /// What can go wrong, shown next to the label.
+    pub fn risk(self) -> &'static str {
+        match self {
+            EjectEscalation::Retry => "Safe. Close the apps using the volume first.",
+            EjectEscalation::UnmountOnly => "Safe. The volume disappears but the disk stays attached and can be remounted.",
+            EjectEscalation::Force => "Risky! Apps with open files lose unsaved changes and files being written may be corrupted.",
+        }
+    }
+}
+
+/// Eject (or unmount) a device after a normal eject failed. Uses `diskutil` on macOS and
+/// `umount` elsewhere.
+pub fn escalate_eject(device: &StorageDevice, escalation: EjectEscalation) -> Result<(), Box<dyn std::error::Error>> {
+    let mount = device.mount_point.as_str();
+    match escalation {
+        EjectEscalation::Retry => eject_device(device),
+        EjectEscalation::UnmountOnly => unmount_device(device),
+        EjectEscalation::Force if cfg!(target_os = "macos") => {
+            // Look up the whole disk first: once unmounted, the mount point no longer identifies it
+            match diskutil_info(mount).and_then(|info| info.parent_whole_disk) {
+                Some(disk) => {
+                    run_tool("diskutil", &["unmountDisk", "force", &disk])?;
+                    run_tool("diskutil", &["eject", &disk])
+                }
+                None => run_tool("diskutil", &["unmount", "force", mount]),
+            }
+        }
+        EjectEscalation::Force => run_tool("umount", &["-f", mount]),
+    }
+}
+
+/// Run an external tool, turning a non-zero exit into an error carrying its stderr.
+fn run_tool(program: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new(program).args(args).output()?;
     if output.status.success() {
         Ok(())
     } else {
         Err(format!(
-            "diskutil error: {}",
-            String::from_utf8_lossy(&output.stderr)
+            "{} error: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
         )
         .into())
     }
@@ -1,2 +1,16 @@
 pub mod macos;
+#[cfg(feature = "junk")]
 pub mod junk_scanner;
+pub mod dev_artifacts;
+pub mod xcode_cleaner;
+pub mod docker;
+pub mod browser_profiles;
+pub mod trash;
+pub mod holders;
+pub mod last_used;
+pub mod open;
+pub mod power;
+pub mod smart;
+pub mod system_roots;
+#[cfg(feature = "net")]
+pub mod update_check;
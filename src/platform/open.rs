@@ -0,0 +1,111 @@
+use std::{
+    error::Error,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Name of the OS file manager, for messages.
+pub fn file_manager_name() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Finder"
+    } else if cfg!(target_os = "windows") {
+        "Explorer"
+    } else {
+        "the file manager"
+    }
+}
+
+/// Show `path` selected in the OS file manager: `open -R` on macOS, `explorer /select,` on
+/// Windows, and elsewhere the desktop's file manager over D-Bus, falling back to opening the
+/// containing folder with `xdg-open`.
+pub fn reveal(path: &Path) -> Result<(), Box<dyn Error>> {
+    check_exists(path)?;
+    if cfg!(target_os = "macos") {
+        spawn("open", &["-R".as_ref(), path.as_os_str()])
+    } else if cfg!(target_os = "windows") {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        spawn("explorer", &[select.as_os_str()])
+    } else {
+        let uri = format!("array:string:{}", file_uri(path)?);
+        let shown = Command::new("dbus-send")
+            .args(["--session", "--dest=org.freedesktop.FileManager1", "--type=method_call", "--print-reply", "--reply-timeout=2000"])
+            .args(["/org/freedesktop/FileManager1", "org.freedesktop.FileManager1.ShowItems", &uri, "string:"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if shown {
+            return Ok(());
+        }
+        let folder = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(path);
+        spawn("xdg-open", &[folder.as_os_str()])
+    }
+}
+
+/// Open `path` with its default app: `open` on macOS, `start` on Windows and `xdg-open`
+/// elsewhere.
+pub fn open_with_default_app(path: &Path) -> Result<(), Box<dyn Error>> {
+    check_exists(path)?;
+    if cfg!(target_os = "macos") {
+        spawn("open", &[path.as_os_str()])
+    } else if cfg!(target_os = "windows") {
+        spawn("cmd", &["/C".as_ref(), "start".as_ref(), "".as_ref(), path.as_os_str()])
+    } else {
+        spawn("xdg-open", &[path.as_os_str()])
+    }
+}
+
+/// Open `path` with a handler's open command (program and arguments, the path filled in).
+pub fn open_with_command(path: &Path, command: &[String]) -> Result<(), Box<dyn Error>> {
+    check_exists(path)?;
+    let (program, args) = command.split_first().ok_or("empty open command")?;
+    let args: Vec<&std::ffi::OsStr> = args.iter().map(|arg| arg.as_ref()).collect();
+    spawn(program, &args)
+}
+
+/// Show a Quick Look preview of `path` (`qlmanage -p`), macOS only. The preview window stays
+/// open until it is closed.
+pub fn quick_look(path: &Path) -> Result<(), Box<dyn Error>> {
+    if !cfg!(target_os = "macos") {
+        return Err("Quick Look is only available on macOS".into());
+    }
+    check_exists(path)?;
+    spawn("qlmanage", &["-p".as_ref(), path.as_os_str()])
+}
+
+fn check_exists(path: &Path) -> Result<(), Box<dyn Error>> {
+    if std::fs::symlink_metadata(path).is_err() {
+        return Err(format!("{} no longer exists", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Start `program` detached from the terminal, so its output can't mess up the TUI, and reap
+/// it in the background. Only failing to start it is reported.
+fn spawn(program: &str, args: &[&std::ffi::OsStr]) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("couldn't run {}: {}", program, e))?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// `file://` URI of `path`, made absolute, with everything but unreserved characters and `/`
+/// percent-encoded.
+fn file_uri(path: &Path) -> Result<String, Box<dyn Error>> {
+    let path = std::path::absolute(path)?;
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    Ok(uri)
+}
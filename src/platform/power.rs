@@ -0,0 +1,101 @@
+use std::{fs, path::Path, process::Command};
+
+/// Whether the machine is running on battery right now. Machines without a battery, or whose
+/// power source can't be determined, count as on AC power.
+pub fn on_battery() -> bool {
+    if cfg!(target_os = "macos") {
+        pmset_on_battery()
+    } else {
+        sysfs_on_battery(Path::new("/sys/class/power_supply"))
+    }
+}
+
+/// `pmset -g batt` starts with "Now drawing from 'Battery Power'" (or 'AC Power').
+fn pmset_on_battery() -> bool {
+    Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+/// On battery when a battery is discharging, or when there are power adapters and none of
+/// them is online.
+fn sysfs_on_battery(power_supply_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(power_supply_dir) else {
+        return false;
+    };
+    let mut has_battery = false;
+    let mut discharging = false;
+    let mut has_adapter = false;
+    let mut adapter_online = false;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let read = |name: &str| fs::read_to_string(path.join(name)).map(|text| text.trim().to_string()).ok();
+        match read("type").as_deref() {
+            Some("Battery") => {
+                has_battery = true;
+                discharging |= read("status").as_deref() == Some("Discharging");
+            }
+            Some("Mains" | "USB") => {
+                has_adapter = true;
+                adapter_online |= read("online").as_deref() == Some("1");
+            }
+            _ => {}
+        }
+    }
+    has_battery && (discharging || (has_adapter && !adapter_online))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a power supply with the given sysfs attributes below `dir`.
+    fn supply(dir: &Path, name: &str, attributes: &[(&str, &str)]) {
+        let supply = dir.join(name);
+        fs::create_dir_all(&supply).unwrap();
+        for (attribute, value) in attributes {
+            fs::write(supply.join(attribute), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_the_power_supplies() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-power-supply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // No power supply class, or nothing in it: a desktop
+        assert!(!sysfs_on_battery(&dir));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(!sysfs_on_battery(&dir));
+
+        // A laptop charging on AC
+        supply(&dir, "BAT0", &[("type", "Battery"), ("status", "Charging")]);
+        supply(&dir, "AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(!sysfs_on_battery(&dir));
+        // Full batteries report neither charging nor discharging
+        supply(&dir, "BAT0", &[("status", "Not charging")]);
+        assert!(!sysfs_on_battery(&dir));
+
+        // Unplugged, even before the battery says it is discharging
+        supply(&dir, "AC", &[("online", "0")]);
+        assert!(sysfs_on_battery(&dir));
+        supply(&dir, "BAT0", &[("status", "Discharging")]);
+        assert!(sysfs_on_battery(&dir));
+        // Charging over USB-C counts as plugged in
+        supply(&dir, "ucsi-source-psy-USBC000:001", &[("type", "USB"), ("online", "1")]);
+        supply(&dir, "BAT0", &[("status", "Charging")]);
+        assert!(!sysfs_on_battery(&dir));
+
+        // A battery discharging with the adapter online still runs on battery
+        supply(&dir, "AC", &[("online", "1")]);
+        supply(&dir, "BAT0", &[("status", "Discharging")]);
+        assert!(sysfs_on_battery(&dir));
+
+        // A desktop listing its mains supply, but without a battery
+        fs::remove_dir_all(dir.join("BAT0")).unwrap();
+        supply(&dir, "AC", &[("online", "0")]);
+        assert!(!sysfs_on_battery(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How long a SMART query result is reused before `smartctl` is run again.
+/// The device listener polls twice a second, far more often than SMART data changes.
+const SMART_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// SMART health summary for a physical disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartInfo {
+    /// Overall self-assessment: `Some(true)` passed, `Some(false)` failing, `None` unknown
+    pub passed: Option<bool>,
+    pub power_on_hours: Option<u64>,
+    /// Percentage of rated endurance used (SSDs only)
+    pub wear_percent: Option<u64>,
+    pub reallocated_sectors: Option<u64>,
+}
+
+impl SmartInfo {
+    /// A drive is failing when its self-assessment failed, which takes the drive's own
+    /// thresholds for its attributes into account.
+    pub fn is_failing(&self) -> bool {
+        self.passed == Some(false)
+    }
+
+    /// Why a drive that isn't failing yet is worth watching. A few remapped sectors are
+    /// common on older drives, but a growing count often comes before a failure.
+    pub fn warning(&self) -> Option<String> {
+        match self.reallocated_sectors {
+            Some(sectors) if sectors > 0 && !self.is_failing() => {
+                Some(format!("{} reallocated sector{} - keep a backup", sectors, if sectors == 1 { "" } else { "s" }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Last query time and result per device node.
+type SmartCache = HashMap<String, (Instant, Option<SmartInfo>)>;
+
+static SMART_CACHE: LazyLock<Mutex<SmartCache>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Query SMART data for a whole-disk device node (e.g. "/dev/disk0") using `smartctl --json`.
+/// Results are cached per device for `SMART_CACHE_TTL`. Returns `None` if smartctl is not
+/// installed or the device does not report SMART data (e.g. most USB enclosures).
+pub fn query_smart(device_node: &str) -> Option<SmartInfo> {
+    if let Ok(cache) = SMART_CACHE.lock()
+        && let Some((at, info)) = cache.get(device_node)
+        && at.elapsed() < SMART_CACHE_TTL
+    {
+        return info.clone();
+    }
+
+    let info = Command::new("smartctl")
+        .args(["--json", "-a", device_node])
+        .output()
+        .ok()
+        .and_then(|output| parse_smartctl_json(&String::from_utf8_lossy(&output.stdout)));
+
+    if let Ok(mut cache) = SMART_CACHE.lock() {
+        cache.insert(device_node.to_string(), (Instant::now(), info.clone()));
+    }
+    info
+}
+
+/// Drop the cached SMART data of `device_node`, so the next query runs smartctl again.
+pub fn forget(device_node: &str) {
+    if let Ok(mut cache) = SMART_CACHE.lock() {
+        cache.remove(device_node);
+    }
+}
+
+/// Extract health, power-on hours, wear level and reallocated sectors from `smartctl --json -a`
+/// output. Handles both NVMe (health log) and ATA (attribute table) drives.
+pub fn parse_smartctl_json(json: &str) -> Option<SmartInfo> {
+    let root: Value = serde_json::from_str(json).ok()?;
+
+    let passed = root["smart_status"]["passed"].as_bool();
+    let power_on_hours = root["power_on_time"]["hours"].as_u64();
+
+    // NVMe drives report wear directly; ATA SSDs expose it as a normalized attribute
+    let nvme_log = &root["nvme_smart_health_information_log"];
+    let ata_attributes = root["ata_smart_attributes"]["table"].as_array();
+    let ata_attribute = |id: u64| {
+        ata_attributes.and_then(|table| table.iter().find(|attr| attr["id"].as_u64() == Some(id)))
+    };
+
+    let wear_percent = nvme_log["percentage_used"].as_u64().or_else(|| {
+        // 177: Wear_Leveling_Count, 231: SSD_Life_Left, 233: Media_Wearout_Indicator (100 = new)
+        [177, 231, 233]
+            .iter()
+            .find_map(|id| ata_attribute(*id))
+            .and_then(|attr| attr["value"].as_u64())
+            .map(|remaining| 100u64.saturating_sub(remaining))
+    });
+
+    // 5: Reallocated_Sector_Ct
+    let reallocated_sectors = ata_attribute(5).and_then(|attr| attr["raw"]["value"].as_u64());
+
+    if passed.is_none() && power_on_hours.is_none() && wear_percent.is_none() && reallocated_sectors.is_none() {
+        return None;
+    }
+
+    Some(SmartInfo {
+        passed,
+        power_on_hours,
+        wear_percent,
+        reallocated_sectors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed `smartctl --json -a` output of a SATA SSD.
+    const SATA: &str = r#"{
+        "device": {"name": "/dev/sda", "protocol": "ATA"},
+        "smart_status": {"passed": true},
+        "power_on_time": {"hours": 12034},
+        "ata_smart_attributes": {"revision": 1, "table": [
+            {"id": 5, "name": "Reallocated_Sector_Ct", "value": 100, "worst": 100, "thresh": 10, "raw": {"value": 3, "string": "3"}},
+            {"id": 9, "name": "Power_On_Hours", "value": 97, "worst": 97, "thresh": 0, "raw": {"value": 12034, "string": "12034"}},
+            {"id": 177, "name": "Wear_Leveling_Count", "value": 92, "worst": 92, "thresh": 0, "raw": {"value": 81, "string": "81"}}
+        ]}
+    }"#;
+
+    /// Trimmed `smartctl --json -a` output of an NVMe drive.
+    const NVME: &str = r#"{
+        "device": {"name": "/dev/nvme0", "protocol": "NVMe"},
+        "smart_status": {"passed": false},
+        "power_on_time": {"hours": 873},
+        "nvme_smart_health_information_log": {"critical_warning": 4, "percentage_used": 7, "media_errors": 0}
+    }"#;
+
+    #[test]
+    fn parses_sata_drives() {
+        let info = parse_smartctl_json(SATA).unwrap();
+        assert_eq!(
+            info,
+            SmartInfo { passed: Some(true), power_on_hours: Some(12034), wear_percent: Some(8), reallocated_sectors: Some(3) }
+        );
+        // Remapped sectors alone only call for attention
+        assert!(!info.is_failing());
+        assert_eq!(info.warning().as_deref(), Some("3 reallocated sectors - keep a backup"));
+    }
+
+    #[test]
+    fn parses_nvme_drives() {
+        let info = parse_smartctl_json(NVME).unwrap();
+        assert_eq!(info, SmartInfo { passed: Some(false), power_on_hours: Some(873), wear_percent: Some(7), reallocated_sectors: None });
+        assert!(info.is_failing());
+        assert_eq!(info.warning(), None);
+    }
+
+    #[test]
+    fn keeps_what_is_reported() {
+        // USB enclosures often pass on only part of the data
+        let info = parse_smartctl_json(r#"{"power_on_time": {"hours": 5}, "ata_smart_attributes": {"table": []}}"#).unwrap();
+        assert_eq!(info, SmartInfo { passed: None, power_on_hours: Some(5), wear_percent: None, reallocated_sectors: None });
+        assert!(!info.is_failing());
+        let healthy = SmartInfo { passed: Some(true), power_on_hours: None, wear_percent: None, reallocated_sectors: Some(0) };
+        assert_eq!(healthy.warning(), None);
+
+        // No SMART data at all, or not smartctl's JSON
+        let unsupported = r#"{"smartctl": {"exit_status": 1, "messages": [{"string": "Unknown USB bridge", "severity": "error"}]}}"#;
+        assert_eq!(parse_smartctl_json(unsupported), None);
+        assert_eq!(parse_smartctl_json(""), None);
+        assert_eq!(parse_smartctl_json("smartctl: command not found"), None);
+    }
+}
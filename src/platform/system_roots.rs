@@ -0,0 +1,48 @@
+use std::path::Path;
+use expanduser::expanduser;
+
+/// Directories no scan walks into: virtual file systems, other volumes mounted below the system
+/// volume and network automounts, which would be slow, endless or counted twice.
+#[cfg(target_os = "macos")]
+const PRUNED: &[&str] = &["/dev", "/Volumes", "/System/Volumes", "/Network", "/net", "/home"];
+#[cfg(not(target_os = "macos"))]
+const PRUNED: &[&str] = &["/proc", "/sys", "/dev", "/run", "/net", "/misc", "/media", "/mnt", "/snap"];
+
+/// Where apps, packages and services keep their data on the system volume, besides the home
+/// directory.
+#[cfg(target_os = "macos")]
+const DATA_LOCATIONS: &[&str] = &["/Applications", "/Library", "/opt", "/usr/local"];
+#[cfg(not(target_os = "macos"))]
+const DATA_LOCATIONS: &[&str] = &["/opt", "/usr/local", "/var/lib", "/var/log", "/var/cache", "/srv"];
+
+/// Whether `path` is one of the directories scans leave out.
+pub fn is_pruned(path: &Path) -> bool {
+    PRUNED.iter().any(|pruned| path == Path::new(pruned))
+}
+
+/// Whether any of the directories scans leave out is directly inside `dir`, so its entries
+/// need checking.
+pub fn holds_pruned(dir: &Path) -> bool {
+    PRUNED.iter().any(|pruned| Path::new(pruned).parent() == Some(dir))
+}
+
+/// Whether a scan of `path` walks the whole system volume.
+pub fn is_system_root(path: &str) -> bool {
+    path == "/"
+}
+
+/// What a full scan of the system volume walks unless `whole_system` is set in the `[scan]`
+/// section: the home directory and the data locations that exist, none inside another.
+pub fn system_scan_roots() -> Vec<String> {
+    let home = expanduser("~").ok().map(|home| home.to_string_lossy().into_owned());
+    let mut roots: Vec<String> = home
+        .into_iter()
+        .chain(DATA_LOCATIONS.iter().map(|location| location.to_string()))
+        .filter(|root| Path::new(root).is_dir())
+        .collect();
+    roots.sort();
+    roots.dedup();
+    let all = roots.clone();
+    roots.retain(|root| !all.iter().any(|other| other != root && Path::new(root).starts_with(other)));
+    roots
+}
@@ -0,0 +1,320 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use chrono::Local;
+use expanduser::expanduser;
+use jwalk::WalkDir;
+use crate::storage::persist;
+
+/// How long a measured trash size is reused. Device detection runs every half second, far too
+/// often to walk every trash each time.
+const SIZE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Measured trash sizes per mount point, with when they were measured.
+static SIZE_CACHE: Mutex<Vec<(String, Instant, u64)>> = Mutex::new(Vec::new());
+
+fn home_dir() -> Option<PathBuf> {
+    expanduser("~").ok()
+}
+
+/// User id of the current user (the owner of the home directory), which names the per-user
+/// trash folders on external volumes.
+#[cfg(unix)]
+fn user_id() -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(home_dir()?).ok().map(|metadata| metadata.uid())
+}
+
+#[cfg(not(unix))]
+fn user_id() -> Option<u32> {
+    None
+}
+
+/// The home directory's trash, which lives on the volume holding the home directory.
+fn home_trash(home: &Path) -> PathBuf {
+    if cfg!(target_os = "macos") {
+        home.join(".Trash")
+    } else {
+        home.join(".local/share/Trash")
+    }
+}
+
+/// Trash folders of the current user on the volume mounted at `mount_point`. `home_volume` says
+/// whether the home directory is on it, in which case its trash is the home trash.
+fn trash_dirs(mount_point: &str, home_volume: bool) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if home_volume && let Some(home) = home_dir() {
+        dirs.push(home_trash(&home));
+    }
+    // Other volumes keep a trash per user id: .Trashes/<uid> on macOS, .Trash-<uid> (or
+    // .Trash/<uid>) elsewhere
+    if mount_point != "/" && let Some(uid) = user_id() {
+        let mount = Path::new(mount_point);
+        dirs.push(mount.join(".Trashes").join(uid.to_string()));
+        dirs.push(mount.join(format!(".Trash-{}", uid)));
+        dirs.push(mount.join(".Trash").join(uid.to_string()));
+    }
+    dirs.retain(|dir| fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_dir()));
+    dirs
+}
+
+/// Size of all files below `path`, or of `path` itself if it is a file. Symlinks aren't followed.
+fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    WalkDir::new(path)
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// The mount point, among `mount_points`, of the volume holding the home directory.
+pub fn home_mount_point(mount_points: &[&str]) -> Option<String> {
+    let home = home_dir()?;
+    mount_points
+        .iter()
+        .filter(|mount| home.starts_with(mount))
+        .max_by_key(|mount| Path::new(mount).components().count())
+        .map(|mount| mount.to_string())
+}
+
+/// Size of the current user's trash on the volume, or None if it has none. Measurements are
+/// cached for a while, see `SIZE_CACHE_TTL`.
+pub fn trash_size(mount_point: &str, home_volume: bool) -> Option<u64> {
+    let dirs = trash_dirs(mount_point, home_volume);
+    if dirs.is_empty() {
+        return None;
+    }
+    let cached = SIZE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(mount, measured, _)| mount == mount_point && measured.elapsed() < SIZE_CACHE_TTL)
+        .map(|(_, _, size)| *size);
+    if cached.is_some() {
+        return cached;
+    }
+    let size = dirs.iter().map(|dir| path_size(dir)).sum();
+    let mut cache = SIZE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(mount, _, _)| mount != mount_point);
+    cache.push((mount_point.to_string(), Instant::now(), size));
+    Some(size)
+}
+
+/// Permanently delete everything in the current user's trash on the volume. The trash folders
+/// themselves are kept. Returns the number of bytes freed and what couldn't be deleted.
+pub fn empty_trash(mount_point: &str, home_volume: bool) -> (u64, Vec<String>) {
+    let mut freed = 0;
+    let mut failures = Vec::new();
+    for dir in trash_dirs(mount_point, home_volume) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                // On macOS reading ~/.Trash needs Full Disk Access for the terminal
+                failures.push(format!("{}: {}", dir.display(), err));
+                continue;
+            }
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let size = path_size(&path);
+            let removed = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => fs::remove_dir_all(&path),
+                _ => fs::remove_file(&path),
+            };
+            match removed {
+                Ok(()) => freed += size,
+                Err(err) => failures.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+    }
+    // Measure again next time, whatever is left
+    SIZE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).retain(|(mount, _, _)| mount != mount_point);
+    (freed, failures)
+}
+
+/// Device id of the file system holding `path` (`path` itself if it is a symlink).
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Root of the volume holding `path`: its topmost ancestor on the same device.
+fn volume_root(path: &Path) -> Option<PathBuf> {
+    let device = device_id(path)?;
+    let mut root = path.to_path_buf();
+    while let Some(parent) = root.parent() {
+        if device_id(parent) != Some(device) {
+            break;
+        }
+        root = parent.to_path_buf();
+    }
+    Some(root)
+}
+
+/// Where `path` goes when it is moved to the trash: the folder for the item itself and,
+/// outside macOS, the folder for its `.trashinfo` file. Always on the volume holding `path`,
+/// so moving it there is a rename.
+fn trash_destination(path: &Path) -> Option<(PathBuf, Option<PathBuf>)> {
+    let home = home_dir()?;
+    let base = if device_id(&home).is_some() && device_id(&home) == device_id(path) {
+        home_trash(&home)
+    } else {
+        let root = volume_root(path)?;
+        let uid = user_id()?;
+        if cfg!(target_os = "macos") {
+            root.join(".Trashes").join(uid.to_string())
+        } else {
+            root.join(format!(".Trash-{}", uid))
+        }
+    };
+    if cfg!(target_os = "macos") {
+        Some((base, None))
+    } else {
+        Some((base.join("files"), Some(base.join("info"))))
+    }
+}
+
+/// Escape a path for the `Path=` line of a `.trashinfo` file, which holds a URL path.
+fn escape_trash_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Move `path` (a file or directory) into the current user's trash on its volume, so it can be
+/// restored later. Outside macOS a `.trashinfo` file records where it came from, as file
+/// managers expect. Returns where the item now is.
+pub fn move_to_trash(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let name = path.file_name().ok_or_else(|| format!("{} can't be moved to the trash", path.display()))?;
+    // Resolve symlinked parents (e.g. /tmp on macOS) so the volume is found correctly
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let path = fs::canonicalize(parent)?.join(name);
+    let (files_dir, info_dir) =
+        trash_destination(&path).ok_or_else(|| format!("no trash available for {}", path.display()))?;
+    move_into_trash(&path, &files_dir, info_dir.as_deref())
+}
+
+/// Move `path`, with its parents resolved, into the trash folder `files_dir`, recording where
+/// it came from in a `.trashinfo` file in `info_dir` if there is one.
+fn move_into_trash(path: &Path, files_dir: &Path, info_dir: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+    let name = path.file_name().ok_or_else(|| format!("{} can't be moved to the trash", path.display()))?;
+    fs::create_dir_all(files_dir)?;
+    if let Some(info_dir) = info_dir {
+        fs::create_dir_all(info_dir)?;
+    }
+
+    // Number the item if the trash already holds one with the same name
+    let name = name.to_string_lossy();
+    let mut trashed_name = name.to_string();
+    let mut number = 2;
+    while fs::symlink_metadata(files_dir.join(&trashed_name)).is_ok()
+        || info_dir.as_ref().is_some_and(|info| info.join(format!("{}.trashinfo", trashed_name)).exists())
+    {
+        trashed_name = format!("{} {}", name, number);
+        number += 1;
+    }
+    let trashed = files_dir.join(&trashed_name);
+
+    let info_file = info_dir.map(|info| info.join(format!("{}.trashinfo", trashed_name)));
+    if let Some(info_file) = &info_file {
+        persist::write_atomic(
+            info_file,
+            format!(
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                escape_trash_path(path),
+                Local::now().format("%Y-%m-%dT%H:%M:%S")
+            )
+            .as_bytes(),
+        )?;
+    }
+    if let Err(err) = fs::rename(path, &trashed) {
+        if let Some(info_file) = &info_file {
+            let _ = fs::remove_file(info_file);
+        }
+        return Err(err.into());
+    }
+    // The trash grew; measure it again next time
+    SIZE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    Ok(trashed)
+}
+
+/// Move an item that `move_to_trash` put at `trashed` back to `original`, and drop its
+/// `.trashinfo`. Refuses if something else is at `original` by now.
+pub fn restore_from_trash(trashed: &Path, original: &Path) -> Result<(), Box<dyn Error>> {
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(format!("{} exists again; not overwriting it", original.display()).into());
+    }
+    fs::symlink_metadata(trashed).map_err(|_| format!("{} is no longer in the trash", trashed.display()))?;
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(trashed, original)?;
+    if let (Some(files_dir), Some(name)) = (trashed.parent(), trashed.file_name())
+        && files_dir.file_name().is_some_and(|dir| dir == "files")
+    {
+        let _ = fs::remove_file(files_dir.with_file_name("info").join(format!("{}.trashinfo", name.to_string_lossy())));
+    }
+    SIZE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trashed_items_are_recorded_numbered_and_restored() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-trash-{}", std::process::id()));
+        let (files_dir, info_dir) = (dir.join("Trash/files"), dir.join("Trash/info"));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        let original = fs::canonicalize(dir.join("docs")).unwrap().join("my notes.txt");
+        fs::write(&original, b"first").unwrap();
+
+        let first = move_into_trash(&original, &files_dir, Some(&info_dir)).unwrap();
+        assert_eq!(first, files_dir.join("my notes.txt"));
+        assert!(!original.exists());
+        let info = fs::read_to_string(info_dir.join("my notes.txt.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\n"), "{}", info);
+        assert!(info.contains(&format!("Path={}\n", escape_trash_path(&original))), "{}", info);
+        assert!(info.contains("my%20notes.txt"), "{}", info);
+        assert!(info.contains("DeletionDate="), "{}", info);
+
+        // A second item of the same name is numbered
+        fs::write(&original, b"second").unwrap();
+        let second = move_into_trash(&original, &files_dir, Some(&info_dir)).unwrap();
+        assert_eq!(second, files_dir.join("my notes.txt 2"));
+        assert!(info_dir.join("my notes.txt 2.trashinfo").exists());
+
+        restore_from_trash(&first, &original).unwrap();
+        assert_eq!(fs::read(&original).unwrap(), b"first");
+        assert!(!info_dir.join("my notes.txt.trashinfo").exists());
+        // Not over what is back in its place
+        assert!(restore_from_trash(&second, &original).is_err());
+        assert_eq!(fs::read(&second).unwrap(), b"second");
+        assert!(restore_from_trash(&first, &original).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    error::Error,
+    path::PathBuf,
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use serde::{Deserialize, Serialize};
+use crate::storage::{persist, profile};
+
+/// GitHub API endpoint for the newest published release.
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/donjordano/lazysmg/releases/latest";
+
+/// How long a looked-up release is trusted before GitHub is asked again, so a startup check
+/// costs one request a day at most.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Upgrades of the cached release, one per schema version (raised only for incompatible changes).
+const MIGRATIONS: &[persist::Migration] = &[persist::from_unversioned];
+
+/// Schema version of the cached release.
+const CACHE_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Version of the running build.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The newest release on GitHub, as cached between runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    /// Git tag, e.g. "v0.2.0"
+    pub tag: String,
+    /// Release page
+    pub url: String,
+    /// When it was looked up, in seconds since the Unix epoch
+    pub checked_at: u64,
+}
+
+impl Release {
+    /// Version of the release, without the "v" of the tag.
+    pub fn version(&self) -> &str {
+        self.tag.trim_start_matches('v')
+    }
+
+    /// Whether the release is newer than the running build.
+    pub fn is_newer(&self) -> bool {
+        is_newer(self.version(), CURRENT_VERSION)
+    }
+
+    /// How to upgrade, first line a short notice.
+    pub fn upgrade_instructions(&self) -> String {
+        format!(
+            "lazysmg {} is available (you have {})\n\nUpgrade with:\n  cargo install --git https://github.com/donjordano/lazysmg --tag {} --locked\nor download it from {}",
+            self.version(),
+            CURRENT_VERSION,
+            self.tag,
+            self.url
+        )
+    }
+}
+
+/// The numeric parts of a version like "1.2.3" (a pre-release or build suffix is ignored).
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Whether `candidate` is a higher version than `current`.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let (mut candidate, mut current) = (version_parts(candidate), version_parts(current));
+    let len = candidate.len().max(current.len());
+    candidate.resize(len, 0);
+    current.resize(len, 0);
+    candidate > current
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Cache location of the last looked-up release, per profile.
+fn cache_file() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(profile::dir("~/.cache/lazysmg")?.join("latest_release.json"))
+}
+
+fn load_cached() -> Option<Release> {
+    let release: Release = persist::load_json(&cache_file().ok()?, MIGRATIONS)?;
+    let age = Duration::from_secs(now().saturating_sub(release.checked_at));
+    (age < CACHE_TTL).then_some(release)
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+/// Ask the GitHub releases API (through `curl`) for the newest release.
+fn fetch_latest() -> Result<Release, Box<dyn Error + Send + Sync>> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--max-time", "10"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args(["--user-agent", concat!("lazysmg/", env!("CARGO_PKG_VERSION"))])
+        .arg(LATEST_RELEASE_URL)
+        .output()
+        .map_err(|e| format!("couldn't run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("couldn't reach GitHub: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    let release: GithubRelease = serde_json::from_slice(&output.stdout)?;
+    Ok(Release { tag: release.tag_name, url: release.html_url, checked_at: now() })
+}
+
+/// The newest release, from the cache if it was looked up within the last day, unless `force`.
+/// Blocks for up to ten seconds while asking GitHub, so run it off the UI thread.
+pub fn latest_release(force: bool) -> Result<Release, Box<dyn Error + Send + Sync>> {
+    if !force && let Some(release) = load_cached() {
+        return Ok(release);
+    }
+    let release = fetch_latest()?;
+    // Failing to cache only means asking again next time
+    if let Ok(path) = cache_file() {
+        let _ = persist::save_json(&path, CACHE_VERSION, &release);
+    }
+    Ok(release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_by_their_numbers() {
+        assert_eq!(version_parts("1.2.3"), vec![1, 2, 3]);
+        assert_eq!(version_parts("0.10"), vec![0, 10]);
+        assert_eq!(version_parts("1.2.0-beta.1+build.5"), vec![1, 2, 0]);
+        assert_eq!(version_parts("1.x.3"), vec![1, 0, 3]);
+
+        assert!(is_newer("0.10.0", "0.9.9"));
+        assert!(is_newer("1.0.0", "0.99.99"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0", "0.2.0"));
+        // Missing parts count as 0
+        assert!(!is_newer("1.2", "1.2.0"));
+        assert!(!is_newer("1.2.0", "1.2"));
+        assert!(is_newer("1.2.1", "1.2"));
+        assert!(!is_newer("1.2", "1.2.1"));
+        // A pre-release is no newer than the release of the same version, and the other way
+        // around
+        assert!(!is_newer("0.2.0-rc.1", "0.2.0"));
+        assert!(!is_newer("0.2.0", "0.2.0-rc.1"));
+        assert!(is_newer("0.3.0-rc.1", "0.2.0"));
+
+        let release = Release { tag: "v0.2.0".to_string(), url: String::new(), checked_at: 0 };
+        assert_eq!(release.version(), "0.2.0");
+    }
+}
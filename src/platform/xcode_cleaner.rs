@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use expanduser::expanduser;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use crate::scanner::{measure_dir_with_progress, ScanOptions, ScanProgressMessage};
+
+/// Device support folders Xcode keeps per connected device OS, one per platform.
+const DEVICE_SUPPORT_DIRS: [&str; 5] = [
+    "iOS DeviceSupport",
+    "watchOS DeviceSupport",
+    "tvOS DeviceSupport",
+    "macOS DeviceSupport",
+    "visionOS DeviceSupport",
+];
+
+/// What kind of Xcode data an item is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum XcodeItemKind {
+    /// Build products and indexes of one project, rebuilt on the next build
+    DerivedData,
+    /// Debug symbols copied from a device OS version, copied again when such a device connects
+    DeviceSupport,
+    /// Simulator runtime (an iOS/watchOS/... version for the simulator)
+    SimulatorRuntime,
+    /// App archive, needed to symbolicate crash reports of that build
+    Archive,
+}
+
+impl XcodeItemKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            XcodeItemKind::DerivedData => "DerivedData",
+            XcodeItemKind::DeviceSupport => "Device support",
+            XcodeItemKind::SimulatorRuntime => "Simulator runtime",
+            XcodeItemKind::Archive => "Archive",
+        }
+    }
+}
+
+/// One removable piece of Xcode data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XcodeItem {
+    pub kind: XcodeItemKind,
+    /// Project, OS version, runtime or archive name
+    pub name: String,
+    /// Directory on disk (for simulator runtimes: the runtime bundle)
+    pub path: String,
+    /// `simctl` identifier of a simulator runtime
+    pub runtime_id: Option<String>,
+    pub size: u64,
+    pub file_count: usize,
+    /// Preselected for cleaning: DerivedData, device support for all but the newest version of
+    /// each OS, and runtimes no simulator uses. Archives are never preselected.
+    pub suggested: bool,
+}
+
+impl XcodeItem {
+    fn new(kind: XcodeItemKind, name: String, path: &Path, suggested: bool) -> Self {
+        XcodeItem {
+            kind,
+            name,
+            path: path.to_string_lossy().into_owned(),
+            runtime_id: None,
+            size: 0,
+            file_count: 0,
+            suggested,
+        }
+    }
+}
+
+/// `~/Library/Developer`, where Xcode keeps everything this module cleans (except runtimes).
+fn developer_dir() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(expanduser("~/Library/Developer")?)
+}
+
+/// Subdirectories of `dir`, sorted by name. A missing directory has none.
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// DerivedData folders are named "<Project>-<hash>"; show just the project.
+fn derived_data_project(folder: &str) -> &str {
+    match folder.rsplit_once('-') {
+        Some((project, hash)) if !project.is_empty() && hash.chars().all(|c| c.is_ascii_alphanumeric()) => project,
+        _ => folder,
+    }
+}
+
+/// Numeric OS version of a device support folder such as "17.2 (21C62)" or
+/// "iPhone15,2 17.2 (21C62)", for finding the newest one.
+fn device_support_version(folder: &str) -> Vec<u32> {
+    folder
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|version| version.split('.').map_while(|part| part.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn derived_data_items(developer: &Path) -> Vec<XcodeItem> {
+    subdirs(&developer.join("Xcode/DerivedData"))
+        .into_iter()
+        .map(|dir| {
+            let folder = file_name(&dir);
+            XcodeItem::new(XcodeItemKind::DerivedData, derived_data_project(&folder).to_string(), &dir, true)
+        })
+        .collect()
+}
+
+fn device_support_items(developer: &Path) -> Vec<XcodeItem> {
+    let mut items = Vec::new();
+    for platform_dir in DEVICE_SUPPORT_DIRS {
+        let platform = platform_dir.trim_end_matches(" DeviceSupport");
+        let mut versions: Vec<(Vec<u32>, PathBuf)> = subdirs(&developer.join("Xcode").join(platform_dir))
+            .into_iter()
+            .map(|dir| (device_support_version(&file_name(&dir)), dir))
+            .collect();
+        versions.sort();
+        // Keep the newest version of each OS: it's the one a connected device most likely runs
+        let newest = versions.len().saturating_sub(1);
+        for (i, (_, dir)) in versions.iter().enumerate() {
+            items.push(XcodeItem::new(
+                XcodeItemKind::DeviceSupport,
+                format!("{} {}", platform, file_name(dir)),
+                dir,
+                i < newest,
+            ));
+        }
+    }
+    items
+}
+
+fn archive_items(developer: &Path) -> Vec<XcodeItem> {
+    // Archives/<date>/<name>.xcarchive
+    subdirs(&developer.join("Xcode/Archives"))
+        .into_iter()
+        .flat_map(|day| {
+            let date = file_name(&day);
+            subdirs(&day).into_iter().map(move |archive| {
+                let name = format!("{} ({})", file_name(&archive).trim_end_matches(".xcarchive"), date);
+                XcodeItem::new(XcodeItemKind::Archive, name, &archive, false)
+            })
+        })
+        .collect()
+}
+
+/// The parts of `xcrun simctl list -j runtimes devices` used to find unused runtimes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SimctlList {
+    runtimes: Vec<SimctlRuntime>,
+    /// Simulators, keyed by the identifier of the runtime they use
+    devices: HashMap<String, Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimctlRuntime {
+    identifier: String,
+    name: String,
+    #[serde(default)]
+    bundle_path: Option<String>,
+}
+
+fn simulator_runtime_items() -> Vec<XcodeItem> {
+    let Ok(output) = Command::new("xcrun").args(["simctl", "list", "-j", "runtimes", "devices"]).output() else {
+        return Vec::new();
+    };
+    let Ok(list) = serde_json::from_slice::<SimctlList>(&output.stdout) else {
+        return Vec::new();
+    };
+    list.runtimes
+        .into_iter()
+        .map(|runtime| {
+            let used = list.devices.get(&runtime.identifier).is_some_and(|devices| !devices.is_empty());
+            let path = runtime.bundle_path.unwrap_or_default();
+            let name = if used { runtime.name } else { format!("{} (no simulators)", runtime.name) };
+            XcodeItem {
+                runtime_id: Some(runtime.identifier),
+                ..XcodeItem::new(XcodeItemKind::SimulatorRuntime, name, Path::new(&path), !used)
+            }
+        })
+        .collect()
+}
+
+/// Find Xcode's DerivedData, device support, simulator runtimes and archives, measure them
+/// and send them via the progress channel, largest first within each kind.
+pub fn scan_xcode_with_progress(
+    progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+) -> Result<(), Box<dyn Error + Send + 'static>> {
+    let developer = developer_dir().map_err(|e| e as Box<dyn Error + Send>)?;
+    let mut items = derived_data_items(&developer);
+    items.extend(device_support_items(&developer));
+    items.extend(simulator_runtime_items());
+    items.extend(archive_items(&developer));
+
+    let mut files_processed = 0;
+    for item in &mut items {
+        if item.path.is_empty() {
+            continue;
+        }
+        // If measuring stops early, the application has likely closed
+        let Some((size, file_count)) = measure_dir_with_progress(Path::new(&item.path), &progress_tx, options) else {
+            return Ok(());
+        };
+        item.size = size;
+        item.file_count = file_count;
+        files_processed += file_count;
+    }
+    // Stable, so kinds stay in the order above
+    items.sort_by_key(|item| (item.kind as u8, std::cmp::Reverse(item.size)));
+
+    // Ignore errors - the app may have been closed
+    let _ = progress_tx.blocking_send(ScanProgressMessage::XcodeScanComplete { items, files_processed });
+    Ok(())
+}
+
+/// Remove one item: simulator runtimes through `simctl`, everything else by deleting its
+/// directory, which must still be a real directory inside `~/Library/Developer`.
+pub fn remove_item(item: &XcodeItem) -> Result<(), Box<dyn Error>> {
+    if let Some(runtime_id) = &item.runtime_id {
+        let output = Command::new("xcrun").args(["simctl", "runtime", "delete", runtime_id]).output()?;
+        return if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!("simctl error: {}", String::from_utf8_lossy(&output.stderr).trim()).into())
+        };
+    }
+
+    let path = Path::new(&item.path);
+    let developer = developer_dir().map_err(|e| e.to_string())?;
+    if !path.starts_with(&developer) || path == developer {
+        return Err(format!("{} is not inside {}", item.path, developer.display()).into());
+    }
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Err(format!("{} is not a directory", item.path).into());
+    }
+    fs::remove_dir_all(path)?;
+    Ok(())
+}
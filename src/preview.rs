@@ -0,0 +1,450 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use crate::config::Renderer;
+use crate::handlers::PreviewHandler;
+
+/// Bytes read from the start of a file to preview it; enough for the EXIF block of a photo.
+const HEAD_BYTES: u64 = 256 * 1024;
+/// Lines of a text file shown.
+const MAX_LINES: usize = 200;
+/// Characters of a text line shown.
+const MAX_LINE_CHARS: usize = 400;
+/// Rows of 16 bytes in the hex dump of a binary file.
+const HEX_ROWS: usize = 64;
+/// Entries of a directory listed.
+const MAX_DIR_ENTRIES: usize = 200;
+/// Preview commands still running after this are stopped.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Language of a text file, by its extension, for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Rust,
+    CLike,
+    Python,
+    Shell,
+    Json,
+    Toml,
+    Yaml,
+    Plain,
+}
+
+impl Syntax {
+    fn from_path(path: &str) -> Syntax {
+        let extension = Path::new(path).extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "rs" => Syntax::Rust,
+            "c" | "h" | "cc" | "cpp" | "hpp" | "m" | "swift" | "go" | "java" | "kt" | "js" | "jsx" | "ts" | "tsx" => Syntax::CLike,
+            "py" | "rb" => Syntax::Python,
+            "sh" | "bash" | "zsh" | "fish" => Syntax::Shell,
+            "json" => Syntax::Json,
+            "toml" | "ini" | "cfg" | "conf" => Syntax::Toml,
+            "yml" | "yaml" => Syntax::Yaml,
+            _ => Syntax::Plain,
+        }
+    }
+
+    fn line_comment(self) -> Option<&'static str> {
+        match self {
+            Syntax::Rust | Syntax::CLike => Some("//"),
+            Syntax::Python | Syntax::Shell | Syntax::Toml | Syntax::Yaml => Some("#"),
+            Syntax::Json | Syntax::Plain => None,
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Syntax::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false", "fn", "for", "if", "impl", "in",
+                "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+                "true", "type", "unsafe", "use", "where", "while",
+            ],
+            Syntax::CLike => &[
+                "break", "case", "class", "const", "continue", "default", "do", "else", "enum", "export", "extends", "false", "for", "func",
+                "function", "if", "import", "interface", "let", "new", "null", "package", "private", "public", "return", "static", "struct",
+                "switch", "this", "true", "type", "var", "void", "while",
+            ],
+            Syntax::Python => &[
+                "and", "as", "class", "def", "elif", "else", "end", "except", "False", "finally", "for", "from", "if", "import", "in", "is",
+                "lambda", "None", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+            ],
+            Syntax::Shell => &["case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local", "then", "while"],
+            Syntax::Json | Syntax::Toml | Syntax::Yaml => &["true", "false", "null"],
+            Syntax::Plain => &[],
+        }
+    }
+}
+
+/// What a highlighted piece of a line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+/// Split a line into highlighted pieces: line comments, quoted strings, numbers and keywords.
+/// Strings and comments spanning several lines aren't recognized.
+pub fn highlight(line: &str, syntax: Syntax) -> Vec<(Token, &str)> {
+    if syntax == Syntax::Plain {
+        return vec![(Token::Plain, line)];
+    }
+    let quotes: &[char] = if syntax == Syntax::Rust { &['"'] } else { &['"', '\''] };
+    let mut pieces = Vec::new();
+    // Start of the plain text not added yet
+    let mut plain = 0;
+    let mut at = 0;
+    while let Some(c) = line[at..].chars().next() {
+        let rest = &line[at..];
+        let (token, len) = if syntax.line_comment().is_some_and(|marker| rest.starts_with(marker)) {
+            (Token::Comment, rest.len())
+        } else if quotes.contains(&c) {
+            // Up to the closing quote that isn't escaped, or the end of the line
+            let mut escaped = false;
+            let close = rest.char_indices().skip(1).find(|&(_, ch)| {
+                let closes = ch == c && !escaped;
+                escaped = ch == '\\' && !escaped;
+                closes
+            });
+            (Token::String, close.map_or(rest.len(), |(index, ch)| index + ch.len_utf8()))
+        } else if c.is_alphanumeric() || c == '_' {
+            let number = c.is_ascii_digit();
+            let len = rest.find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || (number && ch == '.'))).unwrap_or(rest.len());
+            let token = if number {
+                Token::Number
+            } else if syntax.keywords().contains(&&rest[..len]) {
+                Token::Keyword
+            } else {
+                Token::Plain
+            };
+            (token, len)
+        } else {
+            (Token::Plain, c.len_utf8())
+        };
+        if token != Token::Plain {
+            if plain < at {
+                pieces.push((Token::Plain, &line[plain..at]));
+            }
+            pieces.push((token, &rest[..len]));
+            plain = at + len;
+        }
+        at += len;
+    }
+    if plain < line.len() {
+        pieces.push((Token::Plain, &line[plain..]));
+    }
+    pieces
+}
+
+/// What the preview of an entry shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewContent {
+    /// The first lines of a text file
+    Text { syntax: Syntax, lines: Vec<String>, truncated: bool },
+    /// Dimensions of an image, plus its EXIF summary (camera, date, ...) if it has one
+    Image { format: &'static str, width: u32, height: u32, exif: Vec<(&'static str, String)> },
+    /// Hex dump of the start of a binary file
+    Binary { lines: Vec<String> },
+    /// The entries of a directory, the first `MAX_DIR_ENTRIES` of `total`
+    Directory { entries: Vec<String>, total: usize },
+    /// The first lines a handler's preview command printed
+    Output { command: String, lines: Vec<String>, truncated: bool },
+    /// Why there is nothing to show
+    Unavailable(String),
+}
+
+/// Preview of one entry, loaded in the background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preview {
+    pub path: String,
+    pub content: PreviewContent,
+}
+
+/// Load the preview of the file or directory at `path`, files as `handler` says. Reads at most
+/// `HEAD_BYTES` of a file.
+pub fn load(path: &str, handler: &PreviewHandler) -> Preview {
+    let content = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => directory(path),
+        Ok(_) => {
+            let mut head = Vec::new();
+            match File::open(path).and_then(|file| file.take(HEAD_BYTES).read_to_end(&mut head)) {
+                Ok(_) => match handler {
+                    // Fall back to the built-in preview if the program isn't installed
+                    PreviewHandler::Command(command) => command_output(command).unwrap_or_else(|| file(path, &head, Renderer::Auto)),
+                    PreviewHandler::Builtin(renderer) => file(path, &head, *renderer),
+                },
+                Err(err) => PreviewContent::Unavailable(format!("Can't read it: {}", err)),
+            }
+        }
+        Err(err) => PreviewContent::Unavailable(format!("Can't read it: {}", err)),
+    };
+    Preview { path: path.to_string(), content }
+}
+
+fn directory(path: &str) -> PreviewContent {
+    let Ok(read_dir) = fs::read_dir(path) else {
+        return PreviewContent::Unavailable("Can't list the directory".to_string());
+    };
+    let mut entries: Vec<String> = read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) { format!("{}/", name) } else { name }
+        })
+        .collect();
+    entries.sort();
+    let total = entries.len();
+    entries.truncate(MAX_DIR_ENTRIES);
+    PreviewContent::Directory { entries, total }
+}
+
+fn file(path: &str, head: &[u8], renderer: Renderer) -> PreviewContent {
+    match renderer {
+        Renderer::Hex => return PreviewContent::Binary { lines: hex_dump(head) },
+        Renderer::Text => {
+            let (lines, truncated) = text_lines(&String::from_utf8_lossy(head), head.len() as u64 == HEAD_BYTES);
+            return PreviewContent::Text { syntax: Syntax::from_path(path), lines, truncated };
+        }
+        Renderer::Auto => {}
+    }
+    if let Some(content) = image(head) {
+        return content;
+    }
+    // Text if it has no NUL bytes and is UTF-8, apart from a character cut off at the end
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        Err(err) if err.error_len().is_none() => std::str::from_utf8(&head[..err.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    match text.filter(|text| !text.contains('\0')) {
+        Some(text) => {
+            let (lines, truncated) = text_lines(text, head.len() as u64 == HEAD_BYTES);
+            PreviewContent::Text { syntax: Syntax::from_path(path), lines, truncated }
+        }
+        None => PreviewContent::Binary { lines: hex_dump(head) },
+    }
+}
+
+/// The first `MAX_LINES` lines of `text`, tabs expanded and long lines cut, and whether there is
+/// more (also if `cut_off`, i.e. the text is only the head of something longer).
+fn text_lines(text: &str, cut_off: bool) -> (Vec<String>, bool) {
+    let mut lines: Vec<String> = text
+        .lines()
+        .take(MAX_LINES + 1)
+        .map(|line| line.replace('\t', "    ").chars().take(MAX_LINE_CHARS).collect())
+        .collect();
+    let truncated = lines.len() > MAX_LINES || cut_off;
+    lines.truncate(MAX_LINES);
+    (lines, truncated)
+}
+
+/// Run a handler's preview command and show what it prints, at most `HEAD_BYTES` of it. It is
+/// stopped after `COMMAND_TIMEOUT`. None if it couldn't be started.
+fn command_output(command: &[String]) -> Option<PreviewContent> {
+    let (program, args) = command.split_first()?;
+    let mut child = Command::new(program).args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().ok()?;
+    // Read both pipes to the end so the command never blocks on a full one
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut kept = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.by_ref().take(HEAD_BYTES).read_to_end(&mut kept);
+                let _ = io::copy(&mut pipe, &mut io::sink());
+            }
+            kept
+        })
+    };
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+    let output = stdout.join().unwrap_or_default();
+    let errors = stderr.join().unwrap_or_default();
+
+    let shown = command.join(" ");
+    let failed = match status {
+        None => Some(format!("{} took longer than {} s", program, COMMAND_TIMEOUT.as_secs())),
+        Some(status) if !status.success() => {
+            let reason = String::from_utf8_lossy(&errors).lines().next().map_or(status.to_string(), str::to_string);
+            Some(format!("{} failed: {}", program, reason))
+        }
+        Some(_) => None,
+    };
+    if let Some(failed) = failed.as_ref().filter(|_| output.is_empty()) {
+        return Some(PreviewContent::Unavailable(failed.clone()));
+    }
+    let (lines, truncated) = text_lines(&String::from_utf8_lossy(&output), output.len() as u64 == HEAD_BYTES || failed.is_some());
+    Some(PreviewContent::Output { command: shown, lines, truncated })
+}
+
+/// Offset, bytes in hex and printable ASCII, 16 bytes per line.
+fn hex_dump(head: &[u8]) -> Vec<String> {
+    head.chunks(16)
+        .take(HEX_ROWS)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let ascii: String = bytes.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+            format!("{:08x}  {:<47}  |{}|", row * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+fn be16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Format and dimensions of a PNG, JPEG, GIF, BMP or WebP image, read from its header.
+fn image(head: &[u8]) -> Option<PreviewContent> {
+    let image = |format, width, height, exif| Some(PreviewContent::Image { format, width, height, exif });
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        image("PNG", be32(head, 16)?, be32(head, 20)?, Vec::new())
+    } else if head.starts_with(b"GIF8") {
+        image("GIF", le16(head, 6)?, le16(head, 8)?, Vec::new())
+    } else if head.starts_with(b"BM") {
+        let height = le32(head, 22)? as i32;
+        image("BMP", le32(head, 18)?, height.unsigned_abs(), Vec::new())
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        // Extended and lossless images store the dimensions minus one
+        let (width, height) = match head.get(12..16)? {
+            b"VP8X" => ((le32(head, 24)? & 0xff_ffff) + 1, (le32(head, 27)? & 0xff_ffff) + 1),
+            b"VP8 " => (le16(head, 26)? & 0x3fff, le16(head, 28)? & 0x3fff),
+            b"VP8L" => {
+                let bits = le32(head, 21)?;
+                ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)
+            }
+            _ => return None,
+        };
+        image("WebP", width, height, Vec::new())
+    } else if head.starts_with(&[0xff, 0xd8]) {
+        jpeg(head)
+    } else {
+        None
+    }
+}
+
+/// Walk the segments of a JPEG up to its frame header, picking up the EXIF block on the way.
+fn jpeg(head: &[u8]) -> Option<PreviewContent> {
+    let mut exif = Vec::new();
+    let mut at = 2;
+    while head.get(at) == Some(&0xff) {
+        let marker = *head.get(at + 1)?;
+        let length = be16(head, at + 2)? as usize;
+        let segment = head.get(at + 4..at + 2 + length);
+        match marker {
+            // Start of frame, except DHT, JPG and DAC which share the range
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                let height = be16(head, at + 5)?;
+                let width = be16(head, at + 7)?;
+                return Some(PreviewContent::Image { format: "JPEG", width, height, exif });
+            }
+            0xe1 => {
+                if let Some(tiff) = segment.and_then(|segment| segment.strip_prefix(b"Exif\0\0")) {
+                    exif = exif_summary(tiff);
+                }
+            }
+            _ => {}
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+/// Camera, date, orientation and software from the TIFF structure of an EXIF block.
+fn exif_summary(tiff: &[u8]) -> Vec<(&'static str, String)> {
+    let little_endian = match tiff.get(..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Vec::new(),
+    };
+    let u16_at = |at: usize| if little_endian { le16(tiff, at) } else { be16(tiff, at) };
+    let u32_at = |at: usize| if little_endian { le32(tiff, at) } else { be32(tiff, at) };
+    // (tag, type, count, offset of the value or the value itself) of each entry of an IFD
+    let entries = |ifd: usize| -> Vec<(u32, u32, u32, usize)> {
+        let count = u16_at(ifd).unwrap_or(0) as usize;
+        (0..count)
+            .filter_map(|index| {
+                let entry = ifd + 2 + index * 12;
+                Some((u16_at(entry)?, u16_at(entry + 2)?, u32_at(entry + 4)?, entry + 8))
+            })
+            .collect()
+    };
+    let ascii = |count: u32, value_at: usize| -> Option<String> {
+        let at = if count <= 4 { value_at } else { u32_at(value_at)? as usize };
+        let bytes = tiff.get(at..at + count as usize)?;
+        let text = String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string();
+        (!text.is_empty()).then_some(text)
+    };
+
+    let mut summary = Vec::new();
+    let Some(ifd0) = u32_at(4) else {
+        return summary;
+    };
+    let mut exif_ifd = None;
+    for (tag, kind, count, value_at) in entries(ifd0 as usize) {
+        let label = match tag {
+            0x010f => "Make",
+            0x0110 => "Model",
+            0x0131 => "Software",
+            0x0132 => "Modified",
+            0x0112 if kind == 3 => {
+                if let Some(orientation) = u16_at(value_at) {
+                    summary.push(("Orientation", orientation.to_string()));
+                }
+                continue;
+            }
+            0x8769 => {
+                exif_ifd = u32_at(value_at);
+                continue;
+            }
+            _ => continue,
+        };
+        if kind == 2
+            && let Some(text) = ascii(count, value_at)
+        {
+            summary.push((label, text));
+        }
+    }
+    if let Some(exif_ifd) = exif_ifd {
+        for (tag, kind, count, value_at) in entries(exif_ifd as usize) {
+            if tag == 0x9003
+                && kind == 2
+                && let Some(text) = ascii(count, value_at)
+            {
+                summary.push(("Taken", text));
+            }
+        }
+    }
+    summary
+}
@@ -0,0 +1,177 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use crate::platform::macos::StorageDevice;
+use crate::scanner::{FileEntry, ScanProgressMessage};
+use crate::{App, AppMode, ScanMode};
+
+/// A message received by the main loop from one of the background producers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// Device list update from the device listener
+    Devices(Vec<StorageDevice>),
+    /// Result of a directory listing (errors are kept as their message)
+    Listing(Result<Vec<FileEntry>, String>),
+    /// Progress or completion message from a full/junk scan
+    Progress(ScanProgressMessage),
+    /// The app entered `AppMode::Scanning` (directory listing started)
+    ListingStarted { device_index: usize },
+    /// The app entered `AppMode::FullScan`
+    FullScanStarted {
+        device_index: usize,
+        total_bytes: u64,
+        junk: bool,
+        #[serde(default)]
+        dev_artifacts: bool,
+        #[serde(default)]
+        xcode: bool,
+    },
+    /// Lines of the log that couldn't be read so far; sent by the replay, never recorded
+    #[serde(skip)]
+    MalformedLines(usize),
+}
+
+/// One line of a replay log: the event and when it arrived, relative to app start.
+#[derive(Debug, Serialize, Deserialize)]
+struct LogLine {
+    at_ms: u64,
+    event: RecordedEvent,
+}
+
+/// Appends every incoming message to a JSON-lines log so a session can be replayed later.
+pub struct Recorder {
+    started: Instant,
+    writer: BufWriter<File>,
+    in_listing: bool,
+    in_full_scan: bool,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Recorder {
+            started: Instant::now(),
+            writer: BufWriter::new(File::create(path)?),
+            in_listing: false,
+            in_full_scan: false,
+        })
+    }
+
+    /// Record the start of a listing or full scan, so the replay can put the app
+    /// into the mode that consumes the messages that follow.
+    pub fn observe_mode(&mut self, mode: &AppMode, app: &App) {
+        let event = match *mode {
+            AppMode::Scanning { device_index, .. } if !self.in_listing => {
+                Some(RecordedEvent::ListingStarted { device_index })
+            }
+            AppMode::FullScan { device_index, .. } if !self.in_full_scan => {
+                Some(RecordedEvent::FullScanStarted {
+                    device_index,
+                    total_bytes: app.scan_progress.total_bytes,
+                    junk: app.scan_mode == ScanMode::JunkScan,
+                    dev_artifacts: app.scan_mode == ScanMode::DevArtifacts,
+                    xcode: app.scan_mode == ScanMode::Xcode,
+                })
+            }
+            _ => None,
+        };
+        self.in_listing = matches!(mode, AppMode::Scanning { .. });
+        self.in_full_scan = matches!(mode, AppMode::FullScan { .. });
+        if let Some(event) = event {
+            self.record(event);
+        }
+    }
+
+    /// Write one event. Recording is best effort: a failed write never interrupts the app.
+    pub fn record(&mut self, event: RecordedEvent) {
+        let line = LogLine {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        if let Ok(json) = serde_json::to_string(&line) {
+            let _ = writeln!(self.writer, "{}", json);
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+/// Reads a replay log and feeds its events into the app's channels at their original timing.
+/// Mode changes (`ListingStarted`/`FullScanStarted`) go to `control_tx` for the main loop to apply,
+/// as does the running count of malformed lines, which are skipped.
+/// Runs on its own thread and stops at the end of the file or when the app has shut down.
+pub fn start_replay(
+    path: &str,
+    control_tx: mpsc::Sender<RecordedEvent>,
+    device_tx: mpsc::Sender<Vec<StorageDevice>>,
+    scan_tx: Sender<Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>>>,
+    progress_tx: Sender<ScanProgressMessage>,
+) -> Result<(), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    thread::spawn(move || {
+        let started = Instant::now();
+        let mut malformed = 0;
+        for line in reader.lines().map_while(Result::ok) {
+            let line: LogLine = match serde_json::from_str(&line) {
+                Ok(line) => line,
+                Err(_) => {
+                    malformed += 1;
+                    if control_tx.send(RecordedEvent::MalformedLines(malformed)).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            // Wait until the event's original arrival time
+            let due = Duration::from_millis(line.at_ms);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+
+            let delivered = match line.event {
+                RecordedEvent::Devices(devices) => device_tx.send(devices).is_ok(),
+                RecordedEvent::Listing(result) => scan_tx
+                    .blocking_send(result.map_err(|e| {
+                        Box::<dyn Error + Send + Sync>::from(e) as Box<dyn Error + Send + 'static>
+                    }))
+                    .is_ok(),
+                RecordedEvent::Progress(msg) => progress_tx.blocking_send(msg).is_ok(),
+                control => control_tx.send(control).is_ok(),
+            };
+            if !delivered {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_lines_are_counted_not_printed() {
+        let path = std::env::temp_dir().join(format!("lazysmg-replay-{}.jsonl", std::process::id()));
+        std::fs::write(&path, "{\"at_ms\":0,\"event\":{\"Devices\":[]}}\nnot json\n{\"at_ms\":0}\n").unwrap();
+        let (control_tx, control_rx) = mpsc::channel();
+        let (device_tx, device_rx) = mpsc::channel();
+        let (scan_tx, _scan_rx) = tokio::sync::mpsc::channel(1);
+        let (progress_tx, _progress_rx) = tokio::sync::mpsc::channel(1);
+        start_replay(&path.to_string_lossy(), control_tx, device_tx, scan_tx, progress_tx).unwrap();
+
+        assert!(device_rx.recv().unwrap().is_empty());
+        let counts: Vec<_> = control_rx.iter().map(|event| match event {
+            RecordedEvent::MalformedLines(lines) => lines,
+            other => panic!("unexpected {:?}", other),
+        }).collect();
+        assert_eq!(counts, [1, 2]);
+        let _ = std::fs::remove_file(path);
+    }
+}
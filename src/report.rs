@@ -0,0 +1,178 @@
+use std::{
+    error::Error,
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+use expanduser::expanduser;
+use crate::analysis::JunkCategorySummary;
+use crate::config::ReportConfig;
+use crate::format::{format_count, format_duration, format_size, SizeUnits};
+use crate::scanner::FileEntry;
+use crate::storage::persist;
+use crate::storage::scan_history::ScanRecord;
+
+// Plain-text report of a junk scan run on a schedule (`lazysmg junk-scan` from cron or
+// launchd), meant to be read in a mail: totals, what changed since the previous run and what
+// takes up the most.
+
+/// What one junk scan found, for its report.
+#[derive(Debug, Clone)]
+pub struct JunkScanReport {
+    pub record: ScanRecord,
+    /// The previous junk scan of the same disk, to show what changed since
+    pub previous: Option<ScanRecord>,
+    pub categories: Vec<JunkCategorySummary>,
+    /// Folders holding junk as (path, total size, file count)
+    pub folders: Vec<(String, u64, usize)>,
+    pub files: Vec<FileEntry>,
+}
+
+impl JunkScanReport {
+    /// The report as text, listing the `top` largest categories, folders and files.
+    pub fn render(&self, top: usize, units: SizeUnits) -> String {
+        let record = &self.record;
+        let mut lines = vec![
+            format!("lazysmg junk report for {} ({})", record.device, record.mount_point),
+            format!("Scanned {} in {}", record.finished_at.format("%Y-%m-%d %H:%M"), format_duration(Duration::from_millis(record.duration_ms))),
+            String::new(),
+            format!("Junk: {} in {} files", format_size(record.total_size, units), format_count(record.file_count as u64)),
+        ];
+        match &self.previous {
+            Some(previous) => lines.push(format!(
+                "Since {}: {} ({}{} files)",
+                previous.finished_at.format("%Y-%m-%d %H:%M"),
+                size_change(previous.total_size, record.total_size, units),
+                if record.file_count < previous.file_count { '-' } else { '+' },
+                format_count(record.file_count.abs_diff(previous.file_count) as u64)
+            )),
+            None => lines.push("No earlier junk scan to compare with".to_string()),
+        }
+
+        let mut categories: Vec<_> = self.categories.iter().filter(|category| category.total_size > 0).collect();
+        categories.sort_by_key(|category| std::cmp::Reverse(category.total_size));
+        push_section(&mut lines, "By category", categories.iter().take(top).map(|category| {
+            format!("{:>10}  {} ({} files)", format_size(category.total_size, units), category.name, format_count(category.file_count as u64))
+        }));
+
+        let mut folders: Vec<_> = self.folders.iter().collect();
+        folders.sort_by_key(|(_, size, _)| std::cmp::Reverse(*size));
+        push_section(&mut lines, "Largest folders", folders.iter().take(top).map(|(path, size, count)| {
+            format!("{:>10}  {} ({} files)", format_size(*size, units), path, format_count(*count as u64))
+        }));
+
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by_key(|file| std::cmp::Reverse(file.size));
+        push_section(&mut lines, "Largest files", files.iter().take(top).map(|file| {
+            format!("{:>10}  {}", format_size(file.size, units), file.path)
+        }));
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Add a titled list to the report, unless it has no lines.
+fn push_section(lines: &mut Vec<String>, title: &str, section: impl Iterator<Item = String>) {
+    let section: Vec<String> = section.map(|line| format!("  {}", line)).collect();
+    if section.is_empty() {
+        return;
+    }
+    lines.push(String::new());
+    lines.push(format!("{}:", title));
+    lines.extend(section);
+}
+
+/// How much `after` differs from `before`, e.g. "+1.20 GB" or "-300.00 MB".
+fn size_change(before: u64, after: u64, units: SizeUnits) -> String {
+    if after >= before {
+        format!("+{}", format_size(after - before, units))
+    } else {
+        format!("-{}", format_size(before - after, units))
+    }
+}
+
+/// Write `report` to the configured file and pipe it to the configured command, or print it if
+/// neither is set.
+pub fn deliver(report: &str, config: &ReportConfig) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &config.path {
+        let path = expanduser(path)?;
+        persist::write_atomic(&path, report.as_bytes()).map_err(|e| format!("Couldn't write the report to {}: {}", path.display(), e))?;
+    }
+    if let Some(command) = &config.command {
+        pipe_to(command, report)?;
+    }
+    if config.path.is_none() && config.command.is_none() {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/// Run `command` with the shell, feeding it `report`, and wait for it to finish.
+fn pipe_to(command: &str, report: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't run {}: {}", command, e))?;
+    // Closed when dropped, so the command sees the end of the report
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(report.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("{} failed ({})", command, status).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> FileEntry {
+        FileEntry { name: path.rsplit('/').next().unwrap_or(path).to_string(), path: path.to_string(), size, link_target: None, allocated_size: None }
+    }
+
+    fn category(name: &str, total_size: u64) -> JunkCategorySummary {
+        JunkCategorySummary { id: name.to_lowercase(), name: name.to_string(), roots: Vec::new(), total_size, file_count: 1 }
+    }
+
+    #[test]
+    fn render_shows_change_and_largest() {
+        let files = vec![file("/Users/ä/Library/Caches/small", 1024), file("/Users/ä/Library/Logs/big.log", 3 * 1024 * 1024)];
+        let mut previous = ScanRecord::new("Macintosh HD", "/", true, 1000, &files[..1]);
+        previous.finished_at -= chrono::Duration::days(7);
+        let report = JunkScanReport {
+            record: ScanRecord::new("Macintosh HD", "/", true, 2500, &files),
+            previous: Some(previous),
+            categories: vec![category("Logs", 3 * 1024 * 1024), category("Caches", 1024), category("Empty", 0)],
+            folders: vec![("/Users/ä/Library/Caches".to_string(), 1024, 1), ("/Users/ä/Library/Logs".to_string(), 3 * 1024 * 1024, 1)],
+            files,
+        };
+
+        let text = report.render(1, SizeUnits::Binary);
+        assert!(text.starts_with("lazysmg junk report for Macintosh HD (/)\n"), "{}", text);
+        assert!(text.contains("Junk: 3.00 MB in 2 files\n"), "{}", text);
+        assert!(text.contains(": +3.00 MB (+1 files)\n"), "{}", text);
+        assert!(text.contains("By category:\n     3.00 MB  Logs (1 files)\n\n"), "{}", text);
+        assert!(text.contains("Largest folders:\n     3.00 MB  /Users/ä/Library/Logs (1 files)\n\n"), "{}", text);
+        assert!(text.contains("Largest files:\n     3.00 MB  /Users/ä/Library/Logs/big.log\n"), "{}", text);
+        assert!(!text.contains("Caches"), "{}", text);
+    }
+
+    #[test]
+    fn render_without_earlier_scan_or_junk() {
+        let report = JunkScanReport {
+            record: ScanRecord::new("Macintosh HD", "/", true, 0, &[]),
+            previous: None,
+            categories: vec![category("Empty", 0)],
+            folders: Vec::new(),
+            files: Vec::new(),
+        };
+        let text = report.render(10, SizeUnits::Binary);
+        assert!(text.contains("No earlier junk scan to compare with"), "{}", text);
+        assert!(!text.contains("By category"), "{}", text);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// How safe it is to act on a path, judged by where it is: the operating system's own files,
+/// data apps keep for themselves, or the user's documents and media.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Safety {
+    System,
+    AppData,
+    UserData,
+}
+
+/// Locations outside the home directory, with what they hold. The longest one a path is below
+/// decides, so `/usr/local` overrides `/usr`.
+#[cfg(target_os = "macos")]
+const LOCATIONS: &[(&str, Safety)] = &[
+    ("/System", Safety::System),
+    ("/usr", Safety::System),
+    ("/bin", Safety::System),
+    ("/sbin", Safety::System),
+    ("/etc", Safety::System),
+    ("/var", Safety::System),
+    ("/private", Safety::System),
+    ("/cores", Safety::System),
+    ("/dev", Safety::System),
+    ("/Library/Apple", Safety::System),
+    ("/Library", Safety::AppData),
+    ("/Applications", Safety::AppData),
+    ("/opt", Safety::AppData),
+    ("/usr/local", Safety::AppData),
+    ("/private/var/folders", Safety::AppData),
+    ("/Users", Safety::UserData),
+    ("/Volumes", Safety::UserData),
+];
+#[cfg(not(target_os = "macos"))]
+const LOCATIONS: &[(&str, Safety)] = &[
+    ("/usr", Safety::System),
+    ("/bin", Safety::System),
+    ("/sbin", Safety::System),
+    ("/lib", Safety::System),
+    ("/lib32", Safety::System),
+    ("/lib64", Safety::System),
+    ("/etc", Safety::System),
+    ("/boot", Safety::System),
+    ("/var", Safety::System),
+    ("/proc", Safety::System),
+    ("/sys", Safety::System),
+    ("/dev", Safety::System),
+    ("/run", Safety::System),
+    ("/opt", Safety::AppData),
+    ("/usr/local", Safety::AppData),
+    ("/var/lib", Safety::AppData),
+    ("/var/cache", Safety::AppData),
+    ("/var/log", Safety::AppData),
+    ("/var/tmp", Safety::AppData),
+    ("/srv", Safety::AppData),
+    ("/snap", Safety::AppData),
+    ("/tmp", Safety::AppData),
+    ("/home", Safety::UserData),
+    ("/media", Safety::UserData),
+    ("/mnt", Safety::UserData),
+    ("/run/media", Safety::UserData),
+];
+
+/// Directories of the home directory where apps keep their data, besides hidden ones.
+const HOME_APP_DATA: [&str; 3] = ["Library", "Applications", "snap"];
+
+impl Safety {
+    /// Where `path` is, with `home` the user's home directory. In the home directory, hidden
+    /// entries and `HOME_APP_DATA` are app data and everything else user data; the home
+    /// directory itself and the top-level directories count as system.
+    pub fn of(path: &Path, home: Option<&Path>) -> Safety {
+        if let Some(home) = home
+            && let Ok(relative) = path.strip_prefix(home)
+        {
+            return match relative.components().next().map(|first| first.as_os_str().to_string_lossy()) {
+                None => Safety::System,
+                Some(first) if first.starts_with('.') || HOME_APP_DATA.contains(&first.as_ref()) => Safety::AppData,
+                Some(_) => Safety::UserData,
+            };
+        }
+        if path.parent().is_none_or(|parent| parent == Path::new("/")) {
+            return Safety::System;
+        }
+        LOCATIONS
+            .iter()
+            .filter(|(location, _)| path.starts_with(location))
+            .max_by_key(|(location, _)| location.len())
+            // Volumes mounted elsewhere and other locations of one's own
+            .map_or(Safety::UserData, |(_, safety)| *safety)
+    }
+
+    /// Where `path` is, for the home directory of the user running lazysmg.
+    pub fn classify(path: &str) -> Safety {
+        static HOME: OnceLock<Option<PathBuf>> = OnceLock::new();
+        let home = HOME.get_or_init(|| expanduser::expanduser("~").ok());
+        Safety::of(Path::new(path), home.as_deref())
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Safety::System => "system files",
+            Safety::AppData => "app data",
+            Safety::UserData => "user data",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_location() {
+        let home = Some(Path::new("/home/ana"));
+        let safety = |path: &str| Safety::of(Path::new(path), home);
+        assert_eq!(safety("/home/ana/Movies/trip.mov"), Safety::UserData);
+        assert_eq!(safety("/home/ana/.cache/pip/wheel.whl"), Safety::AppData);
+        assert_eq!(safety("/home/ana/Library/Caches/x"), Safety::AppData);
+        assert_eq!(safety("/home/ana"), Safety::System);
+        assert_eq!(safety("/usr/bin/python3"), Safety::System);
+        assert_eq!(safety("/usr/local/lib/big.so"), Safety::AppData);
+        assert_eq!(safety("/opt/tool/data.bin"), Safety::AppData);
+        assert_eq!(safety("/etc"), Safety::System);
+        assert_eq!(safety("/data"), Safety::System);
+        assert_eq!(safety("/data/photos/a.jpg"), Safety::UserData);
+        // Only whole names count
+        assert_eq!(safety("/usrdata/a.jpg"), Safety::UserData);
+        assert_eq!(Safety::of(Path::new("/home/ana/Movies/trip.mov"), None), Safety::UserData);
+    }
+}
@@ -1,59 +1,313 @@
-use std::{error::Error, path::Path, io, sync::Arc, time::Duration};
+use std::{cell::RefCell, cmp::{Ordering, Reverse}, collections::BinaryHeap, error::Error, fs, iter, path::{Path, PathBuf}, io, rc::Rc, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use ignore::WalkBuilder;
 use jwalk::{WalkDir, Parallelism};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
+pub use crate::analysis::{DirTotals, FileEntry, JunkCategorySummary, ScanError};
+use crate::filesystem::allocated_size;
+use crate::platform::browser_profiles::BrowserProfile;
+use crate::platform::dev_artifacts::DevProject;
+use crate::platform::docker::DockerUsage;
+use crate::platform::system_roots;
+use crate::platform::xcode_cleaner::XcodeItem;
+use crate::storage::device_guard::{self, Operation};
+use crate::storage::scan_cache::{self, ScanCache};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FileEntry {
-    pub name: String,
-    pub path: String,
-    pub size: u64,
+/// Tuning knobs for recursive scans.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Number of traversal threads; 0 uses one per CPU core.
+    pub parallelism: usize,
+    /// Leave out what `.gitignore` files (and the global git excludes) exclude, so only content
+    /// that git tracks or could track is counted.
+    pub respect_gitignore: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Levels of directories walked below the scanned directory, for a quick overview of a huge
+    /// tree; files further down aren't counted. `None` walks the whole tree. Followed symlinks
+    /// are walked as deep from their target.
+    pub max_depth: Option<usize>,
+    /// Files a full scan of a device holds in memory, the largest ones; the sizes of all
+    /// directories are still totalled. `None` keeps every file.
+    pub max_files: Option<usize>,
+}
+
+/// What scans and listings do with symbolic links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Leave them out
+    Skip,
+    /// List them as links, with their target and their own (tiny) size
+    #[default]
+    Show,
+    /// Count what they point to, under the link's path. Every file is counted once: targets in
+    /// the scanned directory or in a target followed already are left out, which also ends loops.
+    Follow,
+}
+
+impl ScanOptions {
+    /// Whether a full scan with these options can be saved to the scan cache and rescanned
+    /// incrementally, which only knows the plain directory tree.
+    fn cacheable(&self) -> bool {
+        !self.respect_gitignore && self.symlinks != SymlinkPolicy::Follow && self.max_depth.is_none() && self.max_files.is_none()
+    }
+
+    /// Options for walking a directory one level below the scanned one, None if the depth
+    /// limit leaves nothing of it to walk.
+    fn below(self) -> Option<ScanOptions> {
+        match self.max_depth {
+            Some(0) => None,
+            max_depth => Some(ScanOptions { max_depth: max_depth.map(|depth| depth - 1), ..self }),
+        }
+    }
+
+    pub fn jwalk_parallelism(&self) -> Parallelism {
+        match self.parallelism {
+            0 => Parallelism::RayonDefaultPool {
+                busy_timeout: Duration::from_millis(100),
+            },
+            1 => Parallelism::Serial,
+            n => Parallelism::RayonNewPool(n),
+        }
+    }
+}
+
+/// A file, directory or symlink met while walking, with its metadata (a symlink's own) if it
+/// could be read.
+struct WalkEntry {
+    path: PathBuf,
+    file_type: fs::FileType,
+    /// Its metadata, or why it couldn't be read
+    metadata: Result<fs::Metadata, String>,
+}
+
+impl WalkEntry {
+    /// The entry as a file of the results, for files and symlinks; None for directories and
+    /// the like, and if its metadata couldn't be read.
+    fn to_file(&self) -> Option<FileEntry> {
+        if !self.file_type.is_file() && !self.file_type.is_symlink() {
+            return None;
+        }
+        let Ok(metadata) = &self.metadata else {
+            return None;
+        };
+        let name = self
+            .path
+            .file_name()
+            .map(|os_str| os_str.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.to_string_lossy().into_owned());
+        Some(FileEntry {
+            name,
+            path: self.path.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            link_target: link_target(&self.path, self.file_type),
+            allocated_size: allocated_size(metadata),
+        })
+    }
+
+    /// Why a file or symlink couldn't be counted, if its metadata couldn't be read.
+    fn error(&self) -> Option<ScanError> {
+        let counted = self.file_type.is_file() || self.file_type.is_symlink();
+        self.metadata.as_ref().err().filter(|_| counted).map(|message| scan_error(&self.path, message))
+    }
+}
+
+/// Errors met by the walks of one scan, shared like `LinkTargets`.
+type ErrorLog = Rc<RefCell<Vec<ScanError>>>;
+
+fn scan_error(path: &Path, message: impl std::fmt::Display) -> ScanError {
+    ScanError { path: path.to_string_lossy().into_owned(), message: message.to_string() }
+}
+
+/// A jwalk error met walking `dir`, which names the entry that couldn't be read if it knows.
+pub fn jwalk_error(error: &jwalk::Error, dir: &Path) -> ScanError {
+    let path = error.path().unwrap_or(dir);
+    match error.io_error() {
+        Some(io_error) => scan_error(path, io_error),
+        None => scan_error(path, error),
+    }
+}
+
+/// An error of the ignore crate's walker met walking `dir`.
+fn ignore_error(error: &ignore::Error, dir: &Path) -> ScanError {
+    fn path_of(error: &ignore::Error) -> Option<&Path> {
+        match error {
+            ignore::Error::WithPath { path, .. } => Some(path),
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => path_of(err),
+            ignore::Error::Partial(errors) => errors.first().and_then(path_of),
+            _ => None,
+        }
+    }
+    let path = path_of(error).unwrap_or(dir);
+    match error.io_error() {
+        Some(io_error) => scan_error(path, io_error),
+        None => scan_error(path, error),
+    }
+}
+
+/// Where the symlink at `path` points, as written in the link; None if it isn't one.
+fn link_target(path: &Path, file_type: fs::FileType) -> Option<String> {
+    file_type.is_symlink().then(|| fs::read_link(path).map_or("?".to_string(), |target| target.to_string_lossy().into_owned()))
+}
+
+/// Targets followed by one scan with `SymlinkPolicy::Follow`, so none is counted twice.
+struct LinkTargets {
+    /// The scanned directory, resolved
+    root: PathBuf,
+    followed: Vec<PathBuf>,
+}
+
+impl LinkTargets {
+    fn new(root: impl AsRef<Path>) -> Rc<RefCell<LinkTargets>> {
+        let root = fs::canonicalize(&root).unwrap_or_else(|_| root.as_ref().to_path_buf());
+        Rc::new(RefCell::new(LinkTargets { root, followed: Vec::new() }))
+    }
+
+    /// Resolve `link` and take its target to be walked. None for broken links and for targets
+    /// inside the scanned directory or a target taken before, which are counted already.
+    fn take(&mut self, link: &Path) -> Option<PathBuf> {
+        let target = fs::canonicalize(link).ok()?;
+        if target.starts_with(&self.root) || self.followed.iter().any(|followed| target.starts_with(followed)) {
+            return None;
+        }
+        self.followed.push(target.clone());
+        Some(target)
+    }
+
+    /// Whether `path`, met while walking `target`, is counted by another walk: it is in the
+    /// scanned directory or in another target inside `target` (which contains them if it is a
+    /// link to `..`, say).
+    fn counted_elsewhere(&self, path: &Path, target: &Path) -> bool {
+        path.starts_with(&self.root)
+            || self.followed.iter().any(|followed| followed != target && followed.starts_with(target) && path.starts_with(followed))
+    }
+}
+
+/// Entries at and below `dir`, hidden ones left out and symlinks handled as `options.symlinks`
+/// says; links followed by one scan share `links`. What can't be read is logged in `errors`.
+fn walk_entries(dir: impl AsRef<Path>, options: ScanOptions, links: &Rc<RefCell<LinkTargets>>, errors: &ErrorLog) -> Box<dyn Iterator<Item = WalkEntry>> {
+    with_links(walk_tree(dir.as_ref(), options, errors), options, links.clone(), errors.clone())
+}
+
+/// `entries` with their symlinks left out, kept, or replaced by what they point to, which is
+/// walked in turn and listed under the link's path.
+fn with_links(entries: Box<dyn Iterator<Item = WalkEntry>>, options: ScanOptions, links: Rc<RefCell<LinkTargets>>, errors: ErrorLog) -> Box<dyn Iterator<Item = WalkEntry>> {
+    Box::new(entries.flat_map(move |entry| -> Box<dyn Iterator<Item = WalkEntry>> {
+        if !entry.file_type.is_symlink() {
+            return Box::new(iter::once(entry));
+        }
+        match options.symlinks {
+            SymlinkPolicy::Skip => Box::new(iter::empty()),
+            SymlinkPolicy::Show => Box::new(iter::once(entry)),
+            SymlinkPolicy::Follow => {
+                let Some(target) = links.borrow_mut().take(&entry.path) else {
+                    return Box::new(iter::empty());
+                };
+                let (link, counted, walked) = (entry.path, links.clone(), target.clone());
+                let followed = walk_tree(&target, options, &errors)
+                    .filter(move |followed| !counted.borrow().counted_elsewhere(&followed.path, &walked))
+                    .map(move |mut followed| {
+                        // Paths below the target are shown below the link
+                        if let Ok(relative) = followed.path.strip_prefix(&target) {
+                            followed.path = if relative.as_os_str().is_empty() { link.clone() } else { link.join(relative) };
+                        }
+                        followed
+                    });
+                with_links(Box::new(followed), options, links.clone(), errors.clone())
+            }
+        }
+    }))
+}
+
+/// Entries at and below `dir` (down to `options.max_depth`), hidden ones and virtual file systems
+/// left out and symlinks not followed: walked in parallel by jwalk, or with `respect_gitignore`
+/// by the ignore crate's walker, which also leaves out what `.gitignore` files exclude (those of
+/// `dir`'s parents included) and walks on one thread. Directories that can't be read are logged
+/// in `errors`.
+fn walk_tree(dir: &Path, options: ScanOptions, errors: &ErrorLog) -> Box<dyn Iterator<Item = WalkEntry>> {
+    // The entries of the deepest directories walked are one level further down
+    let max_depth = options.max_depth.map_or(usize::MAX, |depth| depth + 1);
+    let (walked, errors) = (dir.to_path_buf(), errors.clone());
+    if options.respect_gitignore {
+        let mut walker = gitignore_walker(dir);
+        walker.max_depth(Some(max_depth)).filter_entry(|entry| !system_roots::is_pruned(entry.path()));
+        Box::new(walker.build().filter_map(move |result| {
+            let entry = result.map_err(|error| errors.borrow_mut().push(ignore_error(&error, &walked))).ok()?;
+            Some(WalkEntry { file_type: entry.file_type()?, metadata: entry.metadata().map_err(|e| e.to_string()), path: entry.into_path() })
+        }))
+    } else {
+        let walker = WalkDir::new(dir).parallelism(options.jwalk_parallelism()).max_depth(max_depth).process_read_dir(|_, dir, _, children| {
+            if system_roots::holds_pruned(dir) {
+                children.retain(|child| child.as_ref().map_or(true, |child| !system_roots::is_pruned(&child.path())));
+            }
+        });
+        Box::new(walker.into_iter().filter_map(move |result| {
+            let entry = result.map_err(|error| errors.borrow_mut().push(jwalk_error(&error, &walked))).ok()?;
+            Some(WalkEntry { file_type: entry.file_type(), metadata: entry.metadata().map_err(|e| e.to_string()), path: entry.path() })
+        }))
+    }
+}
+
+/// The ignore crate's walker for `dir`, set up to follow git's rules only: `.gitignore` files
+/// count even outside a repository, `.ignore` files don't.
+fn gitignore_walker(dir: &Path) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(dir);
+    builder.ignore(false).require_git(false);
+    builder
 }
 
 /// Scans for files under the given `start_path` using jwalk for parallel directory traversal.
 /// This implementation iterates recursively over directories in parallel, skips over errors gracefully,
 /// obtains file metadata, and returns a vector of FileEntry items sorted in descending order by file size.
-/// Errors are wrapped to satisfy `Send + 'static` and are returned only if the traversal itself fails catastrophically.
-pub fn scan_files(start_path: &str) -> Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>> {
+/// Errors are wrapped to satisfy `Send + 'static` and are returned only if the traversal itself fails catastrophically,
+/// or if the device holding `start_path` is being ejected.
+pub fn scan_files(start_path: &str, options: ScanOptions) -> Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>> {
+    let operation = device_guard::start_operation(start_path).map_err(boxed)?;
     let mut files = Vec::new();
 
-    // Use automatic parallelism based on CPU cores
-    for entry in WalkDir::new(start_path)
-        .parallelism(Parallelism::RayonDefaultPool {
-            busy_timeout: Duration::from_millis(100),
-        })
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        // Check if it's a file
-        let ft = entry.file_type();
-        if ft.is_file() {
-            if let Ok(metadata) = entry.metadata() {
-                let path = entry.path();
-                let size = metadata.len();
-                let name = path
-                    .file_name()
-                    .map(|os_str| os_str.to_string_lossy().into_owned())
-                    .unwrap_or_else(|| path.to_string_lossy().into_owned());
-                
-                files.push(FileEntry {
-                    name,
-                    path: path.to_string_lossy().into_owned(),
-                    size,
-                });
-            } else {
-                // If metadata access fails, log and continue
-                eprintln!("Failed to read metadata for {:?}", entry.path());
-                continue;
-            }
-        }
+    for entry in walk_entries(start_path, options, &LinkTargets::new(start_path), &ErrorLog::default()) {
+        operation.cancelled().map_err(boxed)?;
+        files.extend(entry.to_file());
     }
 
-    files.sort_by(|a, b| b.size.cmp(&a.size));
+    files.sort_by_key(|f| std::cmp::Reverse(f.size));
     Ok(files)
 }
 
-/// Lists the contents of the directory at `start_path` (non-recursively) using jwalk.
-pub fn list_directory(start_path: &str) -> Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>> {
+/// A symbolic link whose target doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub path: String,
+    /// What it points to, as stored in the link
+    pub target: String,
+}
+
+/// Symbolic links at and below each of `roots` whose targets no longer exist, as left behind
+/// by projects moved to another drive, sorted by path. Walked like full scans, leaving out
+/// hidden entries and virtual file systems; directories that can't be read are skipped.
+pub fn find_broken_links(roots: &[String], options: ScanOptions) -> Result<Vec<BrokenLink>, Box<dyn Error + Send + 'static>> {
+    let mut links = Vec::new();
+    for root in roots {
+        let operation = device_guard::start_operation(root).map_err(boxed)?;
+        let options = ScanOptions { respect_gitignore: false, ..options };
+        for entry in walk_tree(Path::new(root), options, &ErrorLog::default()) {
+            operation.cancelled().map_err(boxed)?;
+            // The metadata of a link is its own; fs::metadata follows it
+            if entry.file_type.is_symlink() && fs::metadata(&entry.path).is_err_and(|e| e.kind() == io::ErrorKind::NotFound) {
+                let target = fs::read_link(&entry.path).map_or_else(|_| String::new(), |target| target.to_string_lossy().into_owned());
+                links.push(BrokenLink { path: entry.path.to_string_lossy().into_owned(), target });
+            }
+        }
+    }
+    links.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(links)
+}
+
+/// Lists the contents of the directory at `start_path` (non-recursively) using jwalk. Symlinks
+/// are left out or listed with their target as `symlinks` says; followed, they have the size
+/// of what they point to.
+pub fn list_directory(start_path: &str, symlinks: SymlinkPolicy) -> Result<Vec<FileEntry>, Box<dyn Error + Send + 'static>> {
+    device_guard::check(start_path).map_err(boxed)?;
     let mut entries = Vec::new();
     
     // Use WalkDir with max_depth = 1 to list only immediate children.
@@ -73,9 +327,13 @@ pub fn list_directory(start_path: &str) -> Result<Vec<FileEntry>, Box<dyn Error
             let ft = entry.file_type();
             let is_file = ft.is_file();
             let is_dir = ft.is_dir();
+            let is_link = ft.is_symlink() && symlinks != SymlinkPolicy::Skip;
             
-            if is_file || is_dir {
-                let size = metadata.len();
+            if is_file || is_dir || is_link {
+                let (size, allocated) = match fs::metadata(entry.path()) {
+                    Ok(target) if is_link && symlinks == SymlinkPolicy::Follow => (target.len(), allocated_size(&target)),
+                    _ => (metadata.len(), allocated_size(&metadata)),
+                };
                 let name = entry
                     .path()
                     .file_name()
@@ -86,12 +344,13 @@ pub fn list_directory(start_path: &str) -> Result<Vec<FileEntry>, Box<dyn Error
                     name,
                     path: entry.path().to_string_lossy().into_owned(),
                     size,
+                    link_target: link_target(&entry.path(), ft),
+                    allocated_size: allocated,
                 });
             }
         } else {
             // Handle metadata access failure
-            return Err(Box::new(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(Box::new(io::Error::other(
                 format!("Failed to get metadata for {:?}", entry.path()),
             )) as Box<dyn Error + Send + 'static>);
         }
@@ -103,7 +362,7 @@ pub fn list_directory(start_path: &str) -> Result<Vec<FileEntry>, Box<dyn Error
 }
 
 /// Message types for progress reporting during a full storage scan
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ScanProgressMessage {
     FileScanned {
         size: u64,
@@ -112,80 +371,649 @@ pub enum ScanProgressMessage {
     ScanComplete {
         results: Vec<FileEntry>,
         files_processed: usize,
+        /// Totals of every directory, when `max_files` left files out of `results`
+        #[serde(default)]
+        dir_totals: Option<DirTotals>,
+        /// What couldn't be read, and so isn't counted
+        #[serde(default)]
+        errors: Vec<ScanError>,
     },
     JunkScanComplete {
         results: Vec<FileEntry>,
         files_processed: usize,
         folder_summaries: Vec<(String, u64, usize)>, // path, size, file count
+        /// What couldn't be read, and so isn't counted
+        #[serde(default)]
+        errors: Vec<ScanError>,
+        #[serde(default)]
+        categories: Vec<JunkCategorySummary>,
+        /// Docker disk usage, if Docker is running
+        #[serde(default)]
+        docker: Option<Vec<DockerUsage>>,
+        /// Profiles of installed browsers, with cache, history and extension sizes
+        #[serde(default)]
+        browsers: Vec<BrowserProfile>,
+    },
+    DevArtifactsComplete {
+        /// Directory the projects were searched under
+        root: String,
+        /// Projects with build artifacts, largest first
+        projects: Vec<DevProject>,
+        files_processed: usize,
+    },
+    XcodeScanComplete {
+        items: Vec<XcodeItem>,
+        files_processed: usize,
+    },
+    /// A full scan finished walking a top-level directory; `size` is the size of its files
+    DirectoryScanned {
+        path: String,
+        size: u64,
+    },
+    /// The largest files a running full scan has met so far, largest first
+    LargestFiles {
+        files: Vec<FileEntry>,
+    },
+    /// Junk files were removed while deleting a folder's junk; `path` is the last of them
+    FilesDeleted {
+        files: u64,
+        size: u64,
+        path: String,
+    },
+    FolderDeleteComplete {
+        folder: String,
+        /// Paths of the files that were removed
+        deleted: Vec<String>,
+        freed: u64,
+        /// Files that could not be removed, with the reason
+        failures: Vec<String>,
+        /// Whether the deletion was stopped before all files were removed
+        stopped: bool,
     },
 }
 
+/// Total size and number of files below `path` (hidden files included), reporting each file via
+/// the progress channel. Returns None if the channel was closed, i.e. the app has gone away, or
+/// if the device holding `path` is being ejected.
+pub fn measure_dir_with_progress(
+    path: &Path,
+    progress_tx: &Sender<ScanProgressMessage>,
+    options: ScanOptions,
+) -> Option<(u64, usize)> {
+    let mut size = 0;
+    let mut file_count = 0;
+    for entry in WalkDir::new(path)
+        .parallelism(options.jwalk_parallelism())
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        device_guard::check(&path.to_string_lossy()).ok()?;
+        if entry.file_type().is_file() && let Ok(metadata) = entry.metadata() {
+            size += metadata.len();
+            file_count += 1;
+            let progress_msg = ScanProgressMessage::FileScanned {
+                size: metadata.len(),
+                path: entry.path().to_string_lossy().into_owned(),
+            };
+            progress_tx.blocking_send(progress_msg).ok()?;
+        }
+    }
+    Some((size, file_count))
+}
+
+/// Wrap an error for the `Send` results of background scans.
+fn boxed(error: impl Error + Send + 'static) -> Box<dyn Error + Send + 'static> {
+    Box::new(error)
+}
+
+/// Directory the user has highlighted while a full scan runs. The scan walks the top-level
+/// directories one at a time and takes this one next if it hasn't been walked yet.
+#[derive(Debug, Clone, Default)]
+pub struct ScanPriority(Arc<Mutex<Option<String>>>);
+
+impl ScanPriority {
+    pub fn set(&self, path: Option<String>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+
+    fn get(&self) -> Option<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Files a running full scan reports as the largest so far.
+const LARGEST_FILES: usize = 100;
+
+/// How often a running full scan reports its largest files, if they changed.
+const LARGEST_FILES_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A file ordered by size, for the heap of the largest files.
+struct BySize(FileEntry);
+
+impl PartialEq for BySize {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BySize {}
+
+impl PartialOrd for BySize {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySize {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.size.cmp(&other.0.size).then_with(|| self.0.path.cmp(&other.0.path))
+    }
+}
+
+/// The `limit` largest of the files added.
+struct TopFiles {
+    /// Smallest on top, to be replaced by a larger file
+    heap: BinaryHeap<Reverse<BySize>>,
+    limit: usize,
+}
+
+impl TopFiles {
+    fn new(limit: usize) -> TopFiles {
+        TopFiles { heap: BinaryHeap::new(), limit }
+    }
+
+    /// Whether a file of `size` would be kept.
+    fn admits(&self, size: u64) -> bool {
+        self.limit > 0 && (self.heap.len() < self.limit || self.heap.peek().is_some_and(|Reverse(smallest)| smallest.0.size < size))
+    }
+
+    fn add(&mut self, file: FileEntry) {
+        if !self.admits(file.size) {
+            return;
+        }
+        self.heap.push(Reverse(BySize(file)));
+        if self.heap.len() > self.limit {
+            self.heap.pop();
+        }
+    }
+
+    fn into_files(self) -> Vec<FileEntry> {
+        self.heap.into_iter().map(|Reverse(file)| file.0).collect()
+    }
+}
+
+/// The `LARGEST_FILES` largest files met by a running scan, sent to the app every
+/// `LARGEST_FILES_INTERVAL` so it can show them before the scan completes.
+struct LargestFiles {
+    top: TopFiles,
+    changed: bool,
+    sent_at: Instant,
+}
+
+impl LargestFiles {
+    fn new() -> LargestFiles {
+        LargestFiles { top: TopFiles::new(LARGEST_FILES), changed: false, sent_at: Instant::now() }
+    }
+
+    fn add(&mut self, file: &FileEntry) {
+        if self.top.admits(file.size) {
+            self.top.add(file.clone());
+            self.changed = true;
+        }
+    }
+
+    /// Send the largest files if they changed and the last ones were sent long enough ago.
+    /// Returns false if the application has closed.
+    fn send_due(&mut self, progress_tx: &Sender<ScanProgressMessage>) -> bool {
+        if !self.changed || self.sent_at.elapsed() < LARGEST_FILES_INTERVAL {
+            return true;
+        }
+        self.changed = false;
+        self.sent_at = Instant::now();
+        let mut files: Vec<FileEntry> = self.top.heap.iter().map(|Reverse(file)| file.0.clone()).collect();
+        files.sort_by_key(|file| Reverse(file.size));
+        progress_tx.blocking_send(ScanProgressMessage::LargestFiles { files }).is_ok()
+    }
+}
+
+/// Files a full scan holds on to.
+enum ScannedFiles {
+    All(Vec<FileEntry>),
+    /// With `max_files`: the largest files, and the totals of every directory
+    Largest(TopFiles, DirTotals),
+}
+
+/// Files and directory tree collected by a full scan.
+struct FullScan {
+    root: PathBuf,
+    files: ScannedFiles,
+    files_processed: usize,
+    /// Directory tree for incremental rescans, None if the scan won't be saved
+    cache: Option<ScanCache>,
+    progress_tx: Sender<ScanProgressMessage>,
+    links: Rc<RefCell<LinkTargets>>,
+    errors: ErrorLog,
+    largest: LargestFiles,
+}
+
+impl FullScan {
+    /// A scan of `start_path`, building a scan cache if `save_cache` is set.
+    fn new(start_path: &str, progress_tx: Sender<ScanProgressMessage>, options: ScanOptions, save_cache: bool) -> FullScan {
+        let files = match options.max_files {
+            Some(limit) => ScannedFiles::Largest(TopFiles::new(limit), DirTotals::default()),
+            None => ScannedFiles::All(Vec::new()),
+        };
+        FullScan {
+            root: PathBuf::from(start_path),
+            files,
+            files_processed: 0,
+            cache: save_cache.then(|| ScanCache::new(start_path)),
+            progress_tx,
+            links: LinkTargets::new(start_path),
+            errors: ErrorLog::default(),
+            largest: LargestFiles::new(),
+        }
+    }
+
+    /// Record a file and report it. Returns false if the application has closed.
+    fn add_file(&mut self, file: FileEntry, parent: &str) -> bool {
+        let progress_msg = ScanProgressMessage::FileScanned {
+            size: file.size,
+            path: file.path.clone(),
+        };
+        // If sending fails, the application has likely closed
+        if self.progress_tx.blocking_send(progress_msg).is_err() {
+            return false;
+        }
+        self.largest.add(&file);
+        if !self.largest.send_due(&self.progress_tx) {
+            return false;
+        }
+        self.files_processed += 1;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.add_file(parent, file.clone());
+        }
+        match &mut self.files {
+            ScannedFiles::All(files) => files.push(file),
+            ScannedFiles::Largest(top, totals) => {
+                totals.add_file(&self.root, Path::new(&file.path), file.size);
+                top.add(file);
+            }
+        }
+        true
+    }
+
+    /// Record walked entries. Returns the size of their files, or None if the application has
+    /// closed.
+    fn add_entries(&mut self, entries: impl Iterator<Item = WalkEntry>, operation: &Operation) -> Result<Option<u64>, Box<dyn Error + Send + 'static>> {
+        let mut size = 0;
+        for entry in entries {
+            operation.cancelled().map_err(boxed)?;
+            let parent = entry.path.parent().unwrap_or(&entry.path).to_string_lossy().into_owned();
+            if entry.file_type.is_dir() {
+                // Remember each directory's mtime and its place in the tree for incremental rescans
+                if let Some(cache) = self.cache.as_mut() {
+                    let dir_path = entry.path.to_string_lossy().into_owned();
+                    let mtime = entry.metadata.as_ref().map_or(0, scan_cache::mtime_millis);
+                    cache.add_dir(&dir_path, mtime);
+                    cache.add_subdir(&parent, &dir_path);
+                }
+            } else if let Some(file) = entry.to_file() {
+                size += file.size;
+                if !self.add_file(file, &parent) {
+                    return Ok(None);
+                }
+            } else if let Some(error) = entry.error() {
+                self.errors.borrow_mut().push(error);
+            }
+        }
+        Ok(Some(size))
+    }
+
+    /// Walk one top-level directory. Returns the size of its files, or None if the
+    /// application has closed.
+    fn walk_dir(&mut self, dir: &str, operation: &Operation, options: ScanOptions) -> Result<Option<u64>, Box<dyn Error + Send + 'static>> {
+        let entries = walk_entries(dir, options, &self.links, &self.errors);
+        self.add_entries(entries, operation)
+    }
+
+    /// Walk directories one by one with the options given for each, the one set in `priority`
+    /// first, reporting each finished one with `DirectoryScanned`. Returns false if the
+    /// application has closed.
+    fn walk_pending(&mut self, mut pending_dirs: Vec<(String, ScanOptions)>, operation: &Operation, priority: &ScanPriority) -> Result<bool, Box<dyn Error + Send + 'static>> {
+        while !pending_dirs.is_empty() {
+            let next = priority
+                .get()
+                .and_then(|wanted| pending_dirs.iter().position(|(dir, _)| *dir == wanted))
+                .unwrap_or(0);
+            let (dir, options) = pending_dirs.remove(next);
+            let Some(size) = self.walk_dir(&dir, operation, options)? else {
+                return Ok(false);
+            };
+            let _ = self.progress_tx.blocking_send(ScanProgressMessage::DirectoryScanned { path: dir, size });
+        }
+        Ok(true)
+    }
+}
+
+/// The entries directly in `dir`, hidden ones left out, and with `respect_gitignore` also those
+/// that `.gitignore` files exclude. If `dir` can't be read, that is logged in `errors`.
+fn top_level_entries(dir: &str, options: ScanOptions, errors: &ErrorLog) -> Vec<WalkEntry> {
+    if options.respect_gitignore {
+        let mut walker = gitignore_walker(Path::new(dir));
+        walker.max_depth(Some(1));
+        return walker
+            .build()
+            .filter_map(|result| result.map_err(|error| errors.borrow_mut().push(ignore_error(&error, Path::new(dir)))).ok())
+            .filter(|entry| entry.depth() == 1)
+            .filter_map(|entry| Some(WalkEntry { file_type: entry.file_type()?, metadata: entry.metadata().map_err(|e| e.to_string()), path: entry.into_path() }))
+            .collect();
+    }
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(error) => {
+            errors.borrow_mut().push(scan_error(Path::new(dir), error));
+            return Vec::new();
+        }
+    };
+    read_dir
+        .filter_map(|e| e.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.') && !system_roots::is_pruned(&entry.path()))
+        .filter_map(|entry| Some(WalkEntry { file_type: entry.file_type().ok()?, metadata: entry.metadata().map_err(|e| e.to_string()), path: entry.path() }))
+        .collect()
+}
+
 /// Performs a full scan of the storage device, reporting progress via the progress channel.
 /// This function is designed to be run in a background thread and will send progress updates
-/// through the provided channel.
+/// through the provided channel. The directory tree is persisted to the scan cache so a later
+/// `incremental_scan_with_progress` can skip unchanged directories. The scan stops with an
+/// error if the device is being ejected.
+///
+/// Files in `start_path` itself come first, then the top-level directories one by one (each
+/// walked in parallel), the one set in `priority` first. Each finished directory is reported
+/// with `DirectoryScanned`, so its size is accurate before the rest of the disk is done.
 pub fn full_scan_with_progress(
     start_path: &str,
     _total_size: u64, // Not used directly but kept for API consistency
     progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+    priority: &ScanPriority,
 ) -> Result<(), Box<dyn Error + Send + 'static>> {
-    let mut files = Vec::new();
-    let progress_tx = Arc::new(progress_tx);
+    let operation = device_guard::start_operation(start_path).map_err(boxed)?;
+    // A scan that left out ignored files or followed links isn't saved, or later rescans
+    // would do so too
+    let mut scan = FullScan::new(start_path, progress_tx, options, options.cacheable());
+    if let Some(cache) = scan.cache.as_mut() {
+        let root_mtime = fs::metadata(start_path).map(|m| scan_cache::mtime_millis(&m)).unwrap_or(0);
+        cache.add_dir(start_path, root_mtime);
+    }
 
-    for entry in WalkDir::new(start_path)
-        .parallelism(Parallelism::RayonDefaultPool {
-            busy_timeout: Duration::from_millis(100),
-        })
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let ft = entry.file_type();
-        if ft.is_file() {
-            if let Ok(metadata) = entry.metadata() {
-                let path = entry.path();
-                let size = metadata.len();
-                let name = path
-                    .file_name()
-                    .map(|os_str| os_str.to_string_lossy().into_owned())
-                    .unwrap_or_else(|| path.to_string_lossy().into_owned());
-                
-                // Send progress update with file path
-                let tx = Arc::clone(&progress_tx);
-                let file_path = path.to_string_lossy().into_owned();
-                let progress_msg = ScanProgressMessage::FileScanned { 
-                    size,
-                    path: file_path.clone()
-                };
-                // If sending fails, the application has likely closed
-                if let Err(_) = tx.blocking_send(progress_msg) {
-                    // Return early to avoid more errors
-                    return Ok(());
-                }
-                
-                files.push(FileEntry {
-                    name,
-                    path: path.to_string_lossy().into_owned(),
-                    size,
-                });
-            } else {
-                // Log metadata access failure
-                eprintln!("Failed to read metadata for {:?}", entry.path());
-                continue;
+    // Hidden entries are skipped, like jwalk does by default
+    let mut pending_dirs = Vec::new();
+    for entry in top_level_entries(start_path, options, &scan.errors) {
+        if entry.file_type.is_dir() {
+            pending_dirs.push(entry.path.to_string_lossy().into_owned());
+        } else {
+            // Files, and symlinks as `options.symlinks` says
+            let entries = with_links(Box::new(iter::once(entry)), options, scan.links.clone(), scan.errors.clone());
+            if scan.add_entries(entries, &operation)?.is_none() {
+                return Ok(());
             }
         }
     }
+    pending_dirs.sort();
+    // A scan limited to the files of `start_path` itself leaves its directories out
+    let pending_dirs = match options.below() {
+        Some(dir_options) => pending_dirs.into_iter().map(|dir| (dir, dir_options)).collect(),
+        None => Vec::new(),
+    };
+    if !scan.walk_pending(pending_dirs, &operation, priority)? {
+        return Ok(());
+    }
+    finish_full_scan(scan)
+}
+
+/// Performs a full scan of the system volume mounted at `start_path` that only walks `roots`
+/// (the home directory and the data locations of `system_roots::system_scan_roots`), the
+/// one set in `priority` first, reporting progress like `full_scan_with_progress`. A depth
+/// limit counts from `start_path`. Such a scan leaves most of the volume out, so it is never
+/// saved to the scan cache.
+pub fn system_scan_with_progress(
+    start_path: &str,
+    roots: Vec<String>,
+    progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+    priority: &ScanPriority,
+) -> Result<(), Box<dyn Error + Send + 'static>> {
+    let operation = device_guard::start_operation(start_path).map_err(boxed)?;
+    let mut scan = FullScan::new(start_path, progress_tx, options, false);
+    let pending_dirs = roots
+        .into_iter()
+        .filter_map(|root| {
+            let levels = Path::new(&root).strip_prefix(start_path).ok()?.components().count();
+            let dir_options = (0..levels).try_fold(options, |options, _| options.below())?;
+            Some((root, dir_options))
+        })
+        .collect();
+    if !scan.walk_pending(pending_dirs, &operation, priority)? {
+        return Ok(());
+    }
+    finish_full_scan(scan)
+}
+
+/// Save the directory tree of a finished full scan for incremental rescans, if it built one,
+/// and send its results.
+fn finish_full_scan(scan: FullScan) -> Result<(), Box<dyn Error + Send + 'static>> {
+    // A failed cache write only costs the next rescan its speed-up
+    if let Some(cache) = &scan.cache {
+        let _ = scan_cache::save(cache);
+    }
 
     // Sort files by size (largest first)
-    files.sort_by(|a, b| b.size.cmp(&a.size));
+    let (mut files, dir_totals) = match scan.files {
+        ScannedFiles::All(files) => (files, None),
+        ScannedFiles::Largest(top, totals) => (top.into_files(), Some(totals)),
+    };
+    files.sort_by_key(|f| std::cmp::Reverse(f.size));
     
     // Send completion message with results and file count
-    let files_processed = files.len();
     let complete_msg = ScanProgressMessage::ScanComplete { 
         results: files,
-        files_processed 
+        files_processed: scan.files_processed,
+        dir_totals,
+        errors: scan.errors.take(),
     };
     
     // Ignore errors - the app may have been closed
-    let _ = progress_tx.blocking_send(complete_msg);
+    let _ = scan.progress_tx.blocking_send(complete_msg);
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Rescans a storage device using the persisted scan cache from a previous full scan.
+/// Directories whose mtime matches the cached value reuse their cached list of files without
+/// being re-read, though each file's size is; only new or changed directories are listed again. Subdirectories are still visited,
+/// since a change deep in the tree does not update the mtime of its ancestors.
+/// Falls back to `full_scan_with_progress` (with `priority`) when no cache exists for `start_path`,
+/// and when `respect_gitignore` is set, symlinks are followed or `max_files` is set, since the
+/// cache only knows the plain directory tree with every file.
+pub fn incremental_scan_with_progress(
+    start_path: &str,
+    total_size: u64,
+    progress_tx: Sender<ScanProgressMessage>,
+    options: ScanOptions,
+    priority: &ScanPriority,
+) -> Result<(), Box<dyn Error + Send + 'static>> {
+    let old_cache = match scan_cache::load(start_path).filter(|_| options.cacheable()) {
+        Some(cache) => cache,
+        None => return full_scan_with_progress(start_path, total_size, progress_tx, options, priority),
+    };
+    let operation = device_guard::start_operation(start_path).map_err(boxed)?;
+    let mut cache = ScanCache::new(start_path);
+    let mut largest = LargestFiles::new();
+    let mut errors = Vec::new();
+
+    // Each pending directory is paired with its parent so the tree can be rebuilt as we go
+    let mut pending: Vec<(String, Option<String>)> = vec![(start_path.to_string(), None)];
+    while let Some((dir_path, parent)) = pending.pop() {
+        operation.cancelled().map_err(boxed)?;
+        let metadata = match fs::metadata(&dir_path) {
+            Ok(metadata) if metadata.is_dir() => metadata,
+            // Directory was removed (or replaced) since the last scan
+            _ => continue,
+        };
+        let mtime = scan_cache::mtime_millis(&metadata);
+        cache.add_dir(&dir_path, mtime);
+        if let Some(parent) = parent {
+            cache.add_subdir(&parent, &dir_path);
+        }
+
+        let cached = old_cache
+            .dirs
+            .get(&dir_path)
+            .filter(|cached| mtime != 0 && cached.mtime == mtime);
+
+        let (files, subdirs) = if let Some(cached) = cached {
+            // Unchanged directory: reuse its files and walk the known subdirectories. Writing to a
+            // file leaves its directory's mtime alone, so the sizes are looked up again.
+            let mut files = Vec::new();
+            for file in cached.files.iter().filter(|file| file.link_target.is_none() || options.symlinks == SymlinkPolicy::Show) {
+                match fs::symlink_metadata(&file.path) {
+                    Ok(metadata) => files.push(FileEntry { size: metadata.len(), allocated_size: allocated_size(&metadata), ..file.clone() }),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => errors.push(scan_error(Path::new(&file.path), e)),
+                }
+            }
+            (files, cached.subdirs.clone())
+        } else {
+            // New or changed directory: list it again
+            let mut files = Vec::new();
+            let mut subdirs = Vec::new();
+            let read_dir = match fs::read_dir(&dir_path) {
+                Ok(read_dir) => read_dir,
+                Err(e) => {
+                    errors.push(scan_error(Path::new(&dir_path), e));
+                    continue;
+                }
+            };
+            // Hidden entries and virtual file systems are left out, like the full scan does
+            let entries = read_dir
+                .filter_map(|e| e.ok())
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.') && !system_roots::is_pruned(&entry.path()));
+            for entry in entries {
+                let ft = match entry.file_type() {
+                    Ok(ft) => ft,
+                    Err(_) => continue,
+                };
+                let path = entry.path();
+                if ft.is_dir() {
+                    subdirs.push(path.to_string_lossy().into_owned());
+                } else if ft.is_file() || (ft.is_symlink() && options.symlinks == SymlinkPolicy::Show) {
+                    match entry.metadata() {
+                        Ok(metadata) => files.push(FileEntry {
+                            name: entry.file_name().to_string_lossy().into_owned(),
+                            path: path.to_string_lossy().into_owned(),
+                            size: metadata.len(),
+                            link_target: link_target(&path, ft),
+                            allocated_size: allocated_size(&metadata),
+                        }),
+                        Err(e) => errors.push(scan_error(&path, e)),
+                    }
+                }
+            }
+            (files, subdirs)
+        };
+
+        for file in files {
+            let progress_msg = ScanProgressMessage::FileScanned {
+                size: file.size,
+                path: file.path.clone(),
+            };
+            // If sending fails, the application has likely closed
+            if progress_tx.blocking_send(progress_msg).is_err() {
+                return Ok(());
+            }
+            largest.add(&file);
+            if !largest.send_due(&progress_tx) {
+                return Ok(());
+            }
+            cache.add_file(&dir_path, file);
+        }
+        // Caches written before virtual file systems were left out may still list them
+        let subdirs = subdirs.into_iter().filter(|subdir| !system_roots::is_pruned(Path::new(subdir)));
+        pending.extend(subdirs.map(|subdir| (subdir, Some(dir_path.clone()))));
+    }
+
+    let _ = scan_cache::save(&cache);
+
+    let files = cache.to_file_entries();
+    let files_processed = files.len();
+    let _ = progress_tx.blocking_send(ScanProgressMessage::ScanComplete {
+        results: files,
+        files_processed,
+        dir_totals: None,
+        errors,
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, thread, time::Duration};
+
+    /// Paths and sizes of the files a scan run by `scan` completes with, sorted by path.
+    fn results(scan: impl FnOnce(Sender<ScanProgressMessage>) -> Result<(), Box<dyn Error + Send>> + Send + 'static) -> Vec<(String, u64)> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1024);
+        let scanning = thread::spawn(move || scan(tx));
+        let mut files = None;
+        while let Some(message) = rx.blocking_recv() {
+            if let ScanProgressMessage::ScanComplete { results, .. } = message {
+                files = Some(results);
+            }
+        }
+        scanning.join().unwrap().unwrap();
+        let mut files: Vec<(String, u64)> = files.expect("no results").into_iter().map(|file| (file.path, file.size)).collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn rescan_matches_a_full_scan_with_hidden_files() {
+        let dir = std::env::temp_dir().join(format!("lazysmg-rescan-{}", std::process::id()));
+        let root = dir.to_string_lossy().into_owned();
+        fs::create_dir_all(dir.join("project/.git")).unwrap();
+        fs::write(dir.join("project/main.rs"), b"fn main() {}").unwrap();
+        fs::write(dir.join("project/.env"), b"SECRET=1").unwrap();
+        fs::write(dir.join("project/.git/config"), b"[core]").unwrap();
+        fs::write(dir.join(".hidden"), b"hidden").unwrap();
+        fs::write(dir.join("top.txt"), b"top").unwrap();
+        let full = |root: String| results(move |tx| full_scan_with_progress(&root, 0, tx, ScanOptions::default(), &ScanPriority::default()));
+        let rescan = |root: String| results(move |tx| incremental_scan_with_progress(&root, 0, tx, ScanOptions::default(), &ScanPriority::default()));
+
+        let scanned = full(root.clone());
+        assert_eq!(scanned.len(), 2, "{:?}", scanned);
+        assert_eq!(rescan(root.clone()), scanned);
+
+        // A changed directory is listed again, and its hidden entries stay out
+        thread::sleep(Duration::from_millis(20));
+        fs::write(dir.join("project/lib.rs"), b"pub fn lib() {}").unwrap();
+        let rescanned = rescan(root.clone());
+        assert_eq!(rescanned.len(), 3, "{:?}", rescanned);
+        assert_eq!(rescanned, full(root.clone()));
+
+        // A file that grew, in a directory that didn't change, counts at its new size
+        thread::sleep(Duration::from_millis(20));
+        fs::OpenOptions::new().append(true).open(dir.join("top.txt")).unwrap().write_all(b" grown").unwrap();
+        let rescanned = rescan(root.clone());
+        assert!(rescanned.contains(&(dir.join("top.txt").to_string_lossy().into_owned(), 9)), "{:?}", rescanned);
+        assert_eq!(rescanned, full(root.clone()));
+
+        for previous in [false, true] {
+            let _ = fs::remove_file(scan_cache::cache_file(&root, previous).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+use crate::format::{format_count, format_duration, format_size, SizeUnits};
+use crate::storage::scan_history::ScanRecord;
+
+/// What a session did, printed when lazysmg quits so it stays in the scrollback.
+#[derive(Debug, Clone, Default)]
+pub struct SessionSummary {
+    /// Scans completed this session, oldest first
+    pub scans: Vec<ScanRecord>,
+    /// Cleanups as (what was cleaned, bytes freed): build artifacts, Xcode data, junk, caches,
+    /// emptied trashes and permanent deletes
+    pub freed: Vec<(String, u64)>,
+    /// Deletes that went to the trash, whose space is freed once it is emptied: how many and
+    /// their size, where a preview or full scan knew it
+    pub trashed: (usize, u64),
+}
+
+impl SessionSummary {
+    /// Whether nothing was scanned or cleaned, which leaves nothing to report.
+    pub fn is_empty(&self) -> bool {
+        self.scans.is_empty() && self.freed.is_empty() && self.trashed.0 == 0
+    }
+
+    pub fn add_freed(&mut self, what: impl Into<String>, bytes: u64) {
+        self.freed.push((what.into(), bytes));
+    }
+
+    /// The summary as plain lines: each scan with its largest file, then what was freed.
+    pub fn lines(&self, units: SizeUnits) -> Vec<String> {
+        let mut lines = Vec::new();
+        for scan in &self.scans {
+            lines.push(format!(
+                "Scanned {} ({}) in {}: {} in {} {}",
+                scan.device,
+                scan.mount_point,
+                format_duration(Duration::from_millis(scan.duration_ms)),
+                format_size(scan.total_size, units),
+                format_count(scan.file_count as u64),
+                if scan.junk { "junk files" } else { "files" }
+            ));
+            if let Some(top) = &scan.top_entry {
+                lines.push(format!("  Largest: {} ({})", top.path, format_size(top.size, units)));
+            }
+        }
+        if !self.freed.is_empty() {
+            let total: u64 = self.freed.iter().map(|(_, bytes)| bytes).sum();
+            lines.push(format!("Freed {}", format_size(total, units)));
+            lines.extend(self.freed.iter().map(|(what, bytes)| format!("  {}: {}", what, format_size(*bytes, units))));
+        }
+        let (count, bytes) = self.trashed;
+        if count > 0 {
+            lines.push(format!("Moved {} to the trash ({} deletes), freed once it is emptied", format_size(bytes, units), count));
+        }
+        lines
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fs, path::Path};
+use crate::analysis::{size_under, FileEntry};
+use crate::scanner::{scan_files, ScanOptions};
+
+/// A directory that would shrink if the deletion goes ahead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AncestorImpact {
+    pub path: String,
+    /// Current size, known only when a full scan of the device is available
+    pub before: Option<u64>,
+}
+
+impl AncestorImpact {
+    /// Size after deleting `freed` bytes below this directory.
+    pub fn after(&self, freed: u64) -> Option<u64> {
+        self.before.map(|before| before.saturating_sub(freed))
+    }
+}
+
+/// Preview of a deletion: how much space it frees and which directories shrink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionImpact {
+    pub target: String,
+    pub freed: u64,
+    /// Ancestors of the target, nearest first, up to and including the device root
+    pub ancestors: Vec<AncestorImpact>,
+}
+
+/// Ancestors of `target`, nearest first, stopping at `root` (or at the filesystem root
+/// when `target` is not below `root`).
+fn ancestors_of(target: &str, root: &str) -> Vec<String> {
+    let root = Path::new(root);
+    let mut ancestors = Vec::new();
+    for ancestor in Path::new(target).ancestors().skip(1) {
+        if ancestor.as_os_str().is_empty() {
+            break;
+        }
+        ancestors.push(ancestor.to_string_lossy().into_owned());
+        if ancestor == root {
+            break;
+        }
+    }
+    ancestors
+}
+
+/// Whether the scan results cover `target`, so `from_scan_results` gives real numbers.
+pub fn covers(entries: &[FileEntry], target: &str) -> bool {
+    let target = Path::new(target);
+    entries.iter().any(|entry| Path::new(&entry.path).starts_with(target))
+}
+
+/// Compute the impact from full scan results, which give the size of every ancestor.
+pub fn from_scan_results(target: &str, root: &str, entries: &[FileEntry]) -> DeletionImpact {
+    DeletionImpact {
+        target: target.to_string(),
+        freed: size_under(entries, Path::new(target)),
+        ancestors: ancestors_of(target, root)
+            .into_iter()
+            .map(|path| {
+                let before = size_under(entries, Path::new(&path));
+                AncestorImpact { path, before: Some(before) }
+            })
+            .collect(),
+    }
+}
+
+/// Compute the impact by measuring the target on disk. Ancestor sizes stay unknown, since
+/// measuring them would mean scanning the whole device. Ignored files are counted too, since
+/// they are deleted along with the rest.
+pub fn from_disk(target: &str, root: &str, options: ScanOptions) -> DeletionImpact {
+    let path = Path::new(target);
+    let freed = if path.is_dir() {
+        scan_files(target, ScanOptions { respect_gitignore: false, ..options })
+            .map(|files| files.iter().map(|file| file.size).sum())
+            .unwrap_or(0)
+    } else {
+        fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+    };
+    DeletionImpact {
+        target: target.to_string(),
+        freed,
+        ancestors: ancestors_of(target, root)
+            .into_iter()
+            .map(|path| AncestorImpact { path, before: None })
+            .collect(),
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    error::Error,
+    fmt,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// State of a device that operations must stay away from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceState {
+    /// The user confirmed ejecting it; the eject command is about to run or running
+    Ejecting,
+    /// Ejected (or unmounted), but possibly still listed until the device list refreshes
+    Ejected,
+}
+
+/// Error for an operation refused (or stopped) because its device is being ejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBusy {
+    pub mount_point: String,
+    pub state: DeviceState,
+}
+
+impl fmt::Display for DeviceBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state {
+            DeviceState::Ejecting => write!(f, "{} is being ejected", self.mount_point),
+            DeviceState::Ejected => write!(f, "{} has been ejected", self.mount_point),
+        }
+    }
+}
+
+impl Error for DeviceBusy {}
+
+#[derive(Debug)]
+struct Registry {
+    /// Mount points that are being or have been ejected
+    states: Vec<(String, DeviceState)>,
+    /// Paths of running operations, once per operation
+    running: Vec<String>,
+}
+
+/// Shared by the UI thread, every background scan and every file operation.
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry { states: Vec::new(), running: Vec::new() });
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Registry {
+    fn blocking(&self, path: &str) -> Option<DeviceBusy> {
+        self.states
+            .iter()
+            .find(|(mount, _)| Path::new(path).starts_with(mount))
+            .map(|(mount, state)| DeviceBusy { mount_point: mount.clone(), state: *state })
+    }
+
+    fn set_state(&mut self, mount_point: &str, state: Option<DeviceState>) {
+        self.states.retain(|(mount, _)| mount != mount_point);
+        if let Some(state) = state {
+            self.states.push((mount_point.to_string(), state));
+        }
+    }
+}
+
+/// Refuse to touch `path` if it is on a device that is being or has been ejected.
+pub fn check(path: &str) -> Result<(), DeviceBusy> {
+    match registry().blocking(path) {
+        Some(busy) => Err(busy),
+        None => Ok(()),
+    }
+}
+
+/// A running operation on a path, registered until dropped so an eject can wait for it.
+#[derive(Debug)]
+pub struct Operation {
+    path: String,
+}
+
+impl Operation {
+    /// Whether the operation should stop because its device is being ejected.
+    pub fn cancelled(&self) -> Result<(), DeviceBusy> {
+        check(&self.path)
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let mut registry = registry();
+        if let Some(index) = registry.running.iter().position(|path| *path == self.path) {
+            registry.running.remove(index);
+        }
+    }
+}
+
+/// Register a long-running operation (a scan, or a copy, move or other file operation) on
+/// `path`, unless its device is being ejected.
+pub fn start_operation(path: &str) -> Result<Operation, DeviceBusy> {
+    let mut registry = registry();
+    if let Some(busy) = registry.blocking(path) {
+        return Err(busy);
+    }
+    registry.running.push(path.to_string());
+    Ok(Operation { path: path.to_string() })
+}
+
+/// Mark `mount_point` as ejecting, so new operations on it are refused and running ones stop,
+/// then wait up to `timeout` for the running ones to finish. Returns whether they all did.
+pub fn begin_eject(mount_point: &str, timeout: Duration) -> bool {
+    registry().set_state(mount_point, Some(DeviceState::Ejecting));
+    let deadline = Instant::now() + timeout;
+    loop {
+        let busy = registry().running.iter().any(|path| Path::new(path).starts_with(mount_point));
+        if !busy {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Run `eject` on `mount_point` once the operations running on it have stopped, waiting up to
+/// `timeout` for them (see `begin_eject`). If they don't stop in time the device isn't ejected.
+pub fn eject_when_idle<E: From<String>>(mount_point: &str, timeout: Duration, eject: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+    if !begin_eject(mount_point, timeout) {
+        finish_eject(mount_point, false);
+        return Err(E::from(format!("operations on {} didn't stop in time, so it wasn't ejected", mount_point)));
+    }
+    let result = eject();
+    finish_eject(mount_point, result.is_ok());
+    result
+}
+
+/// Record the outcome of an eject: once ejected the mount stays off-limits until it leaves
+/// the device list; after a failed eject operations are allowed again.
+pub fn finish_eject(mount_point: &str, ejected: bool) {
+    registry().set_state(mount_point, ejected.then_some(DeviceState::Ejected));
+}
+
+/// A volume was mounted at `mount_point` again, so operations on it are allowed even if the
+/// device list never showed it gone.
+pub fn remounted(mount_point: &str) {
+    registry().set_state(mount_point, None);
+}
+
+/// Forget ejected mount points that are no longer in the device list, so a device mounted
+/// there later starts out usable.
+pub fn forget_missing(present_mounts: &[String]) {
+    registry()
+        .states
+        .retain(|(mount, state)| *state == DeviceState::Ejecting || present_mounts.contains(mount));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ejects_wait_for_operations_and_stop_them() {
+        // The registry is shared by every test, so these mount points are this test's own
+        let mount = format!("/lazysmg-guard-{}", std::process::id());
+        let file = format!("{}/a.bin", mount);
+        let operation = start_operation(&file).unwrap();
+        assert_eq!(operation.cancelled(), Ok(()));
+
+        // An operation that doesn't stop in time calls the eject off
+        let mut ejected = false;
+        let result: Result<(), String> = eject_when_idle(&mount, Duration::from_millis(50), || {
+            ejected = true;
+            Ok(())
+        });
+        assert!(result.unwrap_err().contains("didn't stop in time"));
+        assert!(!ejected);
+        assert_eq!(check(&file), Ok(()));
+
+        // While ejecting, running operations are told to stop and new ones are refused
+        let busy = DeviceBusy { mount_point: mount.clone(), state: DeviceState::Ejecting };
+        let stopping = thread::spawn(move || {
+            while operation.cancelled().is_ok() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            operation.cancelled()
+        });
+        let result: Result<(), String> = eject_when_idle(&mount, Duration::from_secs(10), || {
+            assert_eq!(start_operation(&file).unwrap_err(), busy);
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(stopping.join().unwrap(), Err(busy));
+
+        // Once ejected the device stays off-limits until it is mounted again
+        assert_eq!(check(&file).unwrap_err().state, DeviceState::Ejected);
+        assert!(check(&format!("{}-other/a.bin", mount)).is_ok());
+        remounted(&mount);
+        assert_eq!(check(&file), Ok(()));
+
+        // A failed eject allows operations again
+        let result: Result<(), String> = eject_when_idle(&mount, Duration::from_secs(1), || Err("busy".to_string()));
+        assert_eq!(result, Err("busy".to_string()));
+        assert!(start_operation(&file).is_ok());
+    }
+}
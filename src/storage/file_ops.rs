@@ -0,0 +1,1250 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc::Sender;
+use crate::scanner::{FileEntry, ScanProgressMessage};
+use crate::storage::device_guard;
+
+/// Bytes read and written at a time while copying.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A copy, move or delete running in the background, shared with the UI so it can show the
+/// progress and stop it.
+#[derive(Debug)]
+pub struct FileOpJob {
+    /// "Copying", "Moving" or "Deleting"
+    pub label: &'static str,
+    pub source: String,
+    pub started: Instant,
+    stop: AtomicBool,
+    /// Bytes copied (or deleted) so far, and in total
+    done: AtomicU64,
+    total: AtomicU64,
+    current_file: Mutex<Option<String>>,
+    /// Registered with the device guard while the job runs, one per path it works on
+    on_devices: Mutex<Vec<device_guard::Operation>>,
+    /// The eject that stopped the job, if one did
+    stopped_by: Mutex<Option<device_guard::DeviceBusy>>,
+}
+
+impl FileOpJob {
+    pub fn new(label: &'static str, source: String) -> Self {
+        FileOpJob {
+            label,
+            source,
+            started: Instant::now(),
+            stop: AtomicBool::new(false),
+            done: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            current_file: Mutex::new(None),
+            on_devices: Mutex::new(Vec::new()),
+            stopped_by: Mutex::new(None),
+        }
+    }
+
+    /// Count the job as running on the devices of `paths` until the returned guard is dropped,
+    /// so ejecting one of them waits for the job and stops it. Fails if one is being ejected.
+    pub fn run_on(&self, paths: &[&str]) -> Result<OnDevices<'_>, device_guard::DeviceBusy> {
+        let operations = paths.iter().map(|path| device_guard::start_operation(path)).collect::<Result<Vec<_>, _>>()?;
+        *self.on_devices.lock().unwrap_or_else(|e| e.into_inner()) = operations;
+        Ok(OnDevices(self))
+    }
+
+    /// The eject that stopped the job, if it was stopped for one.
+    pub fn stopped_by(&self) -> Option<device_guard::DeviceBusy> {
+        self.stopped_by.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Bytes done so far and in total. The total is 0 until it has been measured.
+    pub fn progress(&self) -> (u64, u64) {
+        (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed))
+    }
+
+    /// Bytes per second since the job started.
+    pub fn speed(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 { self.progress().0 as f64 / elapsed } else { 0.0 }
+    }
+
+    /// The file being copied or deleted right now.
+    pub fn current_file(&self) -> Option<String> {
+        self.current_file.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_current_file(&self, path: &Path) {
+        *self.current_file.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_string_lossy().into_owned());
+    }
+
+    fn add_done(&self, bytes: u64) {
+        self.done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn start_measured(&self, source: &Path) {
+        self.start(tree_size(source));
+    }
+
+    fn start(&self, total: u64) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Ask the operation to stop after the current chunk. A partly copied file is removed.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the job should stop: the user asked it to, or one of its devices is being
+    /// ejected.
+    pub fn stopped(&self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        let ejecting = self.on_devices.lock().unwrap_or_else(|e| e.into_inner()).iter().find_map(|operation| operation.cancelled().err());
+        let Some(busy) = ejecting else {
+            return false;
+        };
+        *self.stopped_by.lock().unwrap_or_else(|e| e.into_inner()) = Some(busy);
+        self.stop();
+        true
+    }
+}
+
+/// A job running on the devices of its paths, see `FileOpJob::run_on`.
+#[derive(Debug)]
+pub struct OnDevices<'a>(&'a FileOpJob);
+
+impl Drop for OnDevices<'_> {
+    fn drop(&mut self) {
+        self.0.on_devices.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// Temporary files and directories named so far, to keep their names apart.
+static TEMP_NAMES: AtomicU64 = AtomicU64::new(0);
+
+/// A hidden path next to `path` for something that becomes `path` once it is complete:
+/// `.<name>.<pid>-<n>.<suffix>`, unique within this process.
+fn temp_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map_or_else(|| "lazysmg".into(), |name| name.to_string_lossy());
+    let n = TEMP_NAMES.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.{}", name, std::process::id(), n, suffix))
+}
+
+/// Copies files chunk by chunk, counting the bytes copied in the job.
+struct Copier<'a> {
+    job: &'a FileOpJob,
+    copied: u64,
+}
+
+impl Copier<'_> {
+    /// Copy a file. It is written next to `target` and renamed over it once complete, so a
+    /// stopped or failed copy leaves a file it would replace as it was.
+    fn copy_file(&mut self, source: &Path, target: &Path) -> Result<(), Box<dyn Error>> {
+        self.job.set_current_file(source);
+        let temp = temp_path(target, "part");
+        let writer = fs::OpenOptions::new().write(true).create_new(true).open(&temp)?;
+        let copied = self.copy_contents(source, writer, &temp).and_then(|()| Ok(fs::rename(&temp, target)?));
+        if copied.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        copied
+    }
+
+    fn copy_contents(&mut self, source: &Path, mut writer: File, written: &Path) -> Result<(), Box<dyn Error>> {
+        let mut reader = File::open(source)?;
+        let mut buffer = vec![0; COPY_CHUNK_SIZE];
+        loop {
+            if self.job.stopped() {
+                return Err("stopped".into());
+            }
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            self.copied += read as u64;
+            self.job.add_done(read as u64);
+        }
+        writer.flush()?;
+        fs::set_permissions(written, fs::metadata(source)?.permissions())?;
+        Ok(())
+    }
+
+    /// Copy a file, or a directory with everything in it. Symlinks are recreated, not followed.
+    fn copy_tree(&mut self, source: &Path, target: &Path) -> Result<(), Box<dyn Error>> {
+        let metadata = fs::symlink_metadata(source)?;
+        if metadata.is_dir() {
+            fs::create_dir_all(target)?;
+            for entry in fs::read_dir(source)? {
+                let entry = entry?;
+                self.copy_tree(&entry.path(), &target.join(entry.file_name()))?;
+            }
+            Ok(())
+        } else if metadata.file_type().is_symlink() {
+            copy_symlink(source, target)?;
+            // Counted in the total by its own size
+            self.job.add_done(metadata.len());
+            Ok(())
+        } else {
+            self.copy_file(source, target)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> Result<(), Box<dyn Error>> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, target)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, _target: &Path) -> Result<(), Box<dyn Error>> {
+    Err(format!("{} is a symlink, which can't be copied here", source.display()).into())
+}
+
+/// Bytes of all files below `path` (or of `path` itself). Symlinks aren't followed.
+fn tree_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|entry| tree_size(&entry.path()))
+        .sum()
+}
+
+/// Copy `source` (a file or directory) to `target`, keeping the progress in `job`. Returns
+/// the number of bytes copied.
+pub fn copy_with_progress(source: &Path, target: &Path, job: &FileOpJob) -> Result<u64, Box<dyn Error>> {
+    if target.starts_with(source) {
+        return Err(format!("can't copy {} into itself", source.display()).into());
+    }
+    job.start_measured(source);
+    let mut copier = Copier { job, copied: 0 };
+    copier.copy_tree(source, target)?;
+    Ok(copier.copied)
+}
+
+/// Move `source` to `target`: a rename when both are on the same volume, otherwise a copy
+/// with progress followed by removing the source. Returns whether it had to copy.
+pub fn move_with_progress(source: &Path, target: &Path, job: &FileOpJob) -> Result<bool, Box<dyn Error>> {
+    move_with_rename(source, target, job, |source, target| fs::rename(source, target))
+}
+
+/// `move_with_progress` trying `rename` first, so tests can have it cross volumes.
+fn move_with_rename(
+    source: &Path,
+    target: &Path,
+    job: &FileOpJob,
+    rename: impl FnOnce(&Path, &Path) -> std::io::Result<()>,
+) -> Result<bool, Box<dyn Error>> {
+    match rename(source, target) {
+        Ok(()) => return Ok(false),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {}
+        Err(err) => return Err(err.into()),
+    }
+    copy_with_progress(source, target, job)?;
+    if fs::symlink_metadata(source)?.is_dir() {
+        fs::remove_dir_all(source)?;
+    } else {
+        fs::remove_file(source)?;
+    }
+    Ok(true)
+}
+
+/// Fill `buffer` with the bytes of pass `pass` of `passes`: zeros and ones in turn, and
+/// pseudo-random bytes in the last pass, from `state`.
+fn fill_pass(buffer: &mut [u8], pass: u32, passes: u32, state: &mut u64) {
+    if pass + 1 < passes {
+        buffer.fill(if pass.is_multiple_of(2) { 0x00 } else { 0xff });
+        return;
+    }
+    for chunk in buffer.chunks_mut(8) {
+        // xorshift64: not for secrets, only so the last pass isn't a pattern
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+    }
+}
+
+/// Overwrite the file at `path` `passes` times, syncing each pass to the disk, then remove it.
+fn erase_file(path: &Path, passes: u32, job: &FileOpJob) -> Result<(), Box<dyn Error>> {
+    job.set_current_file(path);
+    let metadata = fs::metadata(path)?;
+    // Other names of the file would keep pointing at the overwritten contents
+    #[cfg(unix)]
+    if std::os::unix::fs::MetadataExt::nlink(&metadata) > 1 {
+        return Err(format!("{} has other hard links, which would be overwritten too", path.display()).into());
+    }
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    let mut state = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64) | 1;
+    for pass in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut left = metadata.len();
+        while left > 0 {
+            if job.stopped() {
+                return Err("stopped".into());
+            }
+            let chunk = left.min(buffer.len() as u64) as usize;
+            fill_pass(&mut buffer[..chunk], pass, passes, &mut state);
+            file.write_all(&buffer[..chunk])?;
+            left -= chunk as u64;
+            job.add_done(chunk as u64);
+        }
+        // Each pass has to reach the disk, not only the cache
+        file.sync_all()?;
+    }
+    file.set_len(0)?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn erase_tree(path: &Path, passes: u32, job: &FileOpJob) -> Result<(), Box<dyn Error>> {
+    if job.stopped() {
+        return Err("stopped".into());
+    }
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            erase_tree(&entry?.path(), passes, job)?;
+        }
+        fs::remove_dir(path)?;
+    } else if metadata.file_type().is_symlink() {
+        fs::remove_file(path)?;
+    } else {
+        erase_file(path, passes, job)?;
+    }
+    Ok(())
+}
+
+/// Securely delete a file, or a directory with everything in it: overwrite every file
+/// `passes` times (at least once) before removing it. Symlinks are removed, not followed. A
+/// stopped erase leaves the file being overwritten in place, with its contents partly gone.
+pub fn secure_delete_with_progress(path: &Path, passes: u32, job: &FileOpJob) -> Result<(), Box<dyn Error>> {
+    let passes = passes.max(1);
+    job.start(tree_size(path) * passes as u64);
+    erase_tree(path, passes, job)
+}
+
+/// Archive formats, told apart by the file's extension. Zip and tar.gz archives can be
+/// written and extracted, 7z archives only extracted (with the `7zz`/`7z` command).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    const EXTENSIONS: [(&'static str, ArchiveFormat); 4] = [
+        (".zip", ArchiveFormat::Zip),
+        (".tar.gz", ArchiveFormat::TarGz),
+        (".tgz", ArchiveFormat::TarGz),
+        (".7z", ArchiveFormat::SevenZip),
+    ];
+
+    /// The format `path` is (or asks for): `.zip`, `.tar.gz`/`.tgz` or `.7z`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let lower = path.to_lowercase();
+        Self::EXTENSIONS.iter().find(|(extension, _)| lower.ends_with(extension)).map(|(_, format)| *format)
+    }
+
+    /// Whether archives of this format can be written, not just extracted.
+    pub fn writable(self) -> bool {
+        self != ArchiveFormat::SevenZip
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::SevenZip => ".7z",
+        }
+    }
+
+    /// The other writable format, to switch between them in the destination prompt.
+    pub fn toggled(self) -> Self {
+        match self {
+            ArchiveFormat::Zip => ArchiveFormat::TarGz,
+            ArchiveFormat::TarGz | ArchiveFormat::SevenZip => ArchiveFormat::Zip,
+        }
+    }
+
+    /// `path` without its archive extension, if it has one.
+    pub fn strip_extension(path: &str) -> &str {
+        let lower = path.to_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(extension, _)| lower.ends_with(extension))
+            .map_or(path, |(extension, _)| &path[..path.len() - extension.len()])
+    }
+
+    /// `path` with its archive extension (if any) replaced by this format's.
+    pub fn apply_to(self, path: &str) -> String {
+        format!("{}{}", Self::strip_extension(path), self.extension())
+    }
+}
+
+/// Reads a file into an archive, counting the bytes in the job and failing once it is stopped.
+#[cfg(feature = "archive")]
+struct JobReader<'a> {
+    file: File,
+    job: &'a FileOpJob,
+}
+
+#[cfg(feature = "archive")]
+impl Read for JobReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.job.stopped() {
+            return Err(std::io::Error::other("stopped"));
+        }
+        let read = self.file.read(buf)?;
+        self.job.add_done(read as u64);
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "archive")]
+enum ArchiveWriter {
+    Zip(zip::ZipWriter<File>),
+    TarGz(tar::Builder<flate2::write::GzEncoder<File>>),
+}
+
+#[cfg(feature = "archive")]
+impl ArchiveWriter {
+    fn new(file: File, format: ArchiveFormat) -> Result<Self, Box<dyn Error>> {
+        match format {
+            ArchiveFormat::Zip => Ok(ArchiveWriter::Zip(zip::ZipWriter::new(file))),
+            ArchiveFormat::TarGz => {
+                Ok(ArchiveWriter::TarGz(tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()))))
+            }
+            ArchiveFormat::SevenZip => Err("7z archives can only be extracted".into()),
+        }
+    }
+
+    fn zip_options(metadata: &fs::Metadata) -> zip::write::SimpleFileOptions {
+        use chrono::{Datelike, Timelike};
+
+        let mut options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(metadata.len() >= u32::MAX as u64);
+        #[cfg(unix)]
+        {
+            options = options.unix_permissions(std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()));
+        }
+        // Zip stores the local time; without it entries would date from 1980
+        let modified = metadata.modified().ok().map(chrono::DateTime::<chrono::Local>::from).and_then(|time| {
+            let year = u16::try_from(time.year()).ok()?;
+            let (month, day) = (time.month() as u8, time.day() as u8);
+            zip::DateTime::from_date_and_time(year, month, day, time.hour() as u8, time.minute() as u8, time.second() as u8).ok()
+        });
+        if let Some(modified) = modified {
+            options = options.last_modified_time(modified);
+        }
+        options
+    }
+
+    /// Add a file, a symlink (stored as a link) or a directory with everything in it as `name`.
+    fn add_tree(&mut self, path: &Path, name: &str, job: &FileOpJob) -> Result<(), Box<dyn Error>> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_dir() {
+            match self {
+                ArchiveWriter::Zip(zip) => zip.add_directory(name, Self::zip_options(&metadata))?,
+                ArchiveWriter::TarGz(tar) => tar.append_dir(name, path)?,
+            }
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                self.add_tree(&entry.path(), &format!("{}/{}", name, entry.file_name().to_string_lossy()), job)?;
+            }
+        } else if metadata.file_type().is_symlink() {
+            let link = fs::read_link(path)?;
+            match self {
+                ArchiveWriter::Zip(zip) => zip.add_symlink(name, link.to_string_lossy(), Self::zip_options(&metadata))?,
+                ArchiveWriter::TarGz(tar) => {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_metadata(&metadata);
+                    tar.append_link(&mut header, name, link)?;
+                }
+            }
+        } else {
+            job.set_current_file(path);
+            let mut reader = JobReader { file: File::open(path)?, job };
+            match self {
+                ArchiveWriter::Zip(zip) => {
+                    zip.start_file(name, Self::zip_options(&metadata))?;
+                    std::io::copy(&mut reader, zip)?;
+                }
+                ArchiveWriter::TarGz(tar) => {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_metadata(&metadata);
+                    tar.append_data(&mut header, name, reader)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                zip.finish()?;
+            }
+            ArchiveWriter::TarGz(tar) => {
+                tar.into_inner()?.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pack `source` (a file or directory) into a new archive at `target`, keeping the progress
+/// in `job`. Symlinks are stored as links, not followed. A stopped or failed archive is
+/// removed again. Returns the size of the archive.
+#[cfg(feature = "archive")]
+pub fn archive_with_progress(source: &Path, target: &Path, format: ArchiveFormat, job: &FileOpJob) -> Result<u64, Box<dyn Error>> {
+    if target.starts_with(source) {
+        return Err(format!("can't archive {} into itself", source.display()).into());
+    }
+    if fs::symlink_metadata(target).is_ok() {
+        return Err(format!("{} already exists", target.display()).into());
+    }
+    let name = source.file_name().map_or("archive".into(), |name| name.to_string_lossy());
+    job.start_measured(source);
+    let result = ArchiveWriter::new(File::create(target)?, format)
+        .and_then(|mut writer| writer.add_tree(source, &name, job).and_then(|()| writer.finish()));
+    match result {
+        Ok(()) => Ok(fs::metadata(target)?.len()),
+        Err(err) => {
+            let _ = fs::remove_file(target);
+            Err(err)
+        }
+    }
+}
+
+/// Writes an extracted file, failing once the job is stopped and, if `counts`, adding the
+/// bytes written to its progress.
+#[cfg(feature = "archive")]
+struct JobWriter<'a> {
+    file: File,
+    job: &'a FileOpJob,
+    counts: bool,
+}
+
+#[cfg(feature = "archive")]
+impl Write for JobWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.job.stopped() {
+            return Err(std::io::Error::other("stopped"));
+        }
+        let written = self.file.write(buf)?;
+        if self.counts {
+            self.job.add_done(written as u64);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Creates the files of an extracted archive below `dest`, remembering them so a stopped or
+/// failed extraction can be undone. Nothing that exists is overwritten.
+#[cfg(feature = "archive")]
+struct Extractor<'a> {
+    dest: &'a Path,
+    job: &'a FileOpJob,
+    /// Whether progress counts the bytes written, rather than those read from the archive
+    counts_written: bool,
+    /// Everything created so far, in order
+    created: Vec<std::path::PathBuf>,
+}
+
+#[cfg(feature = "archive")]
+impl Extractor<'_> {
+    /// Where the archive entry `name` goes. Entries that would end up outside `dest` (absolute,
+    /// with "..", or through a symlink extracted earlier) are refused.
+    fn target(&self, name: &Path) -> Result<std::path::PathBuf, Box<dyn Error>> {
+        use std::path::Component;
+        let mut target = self.dest.to_path_buf();
+        for component in name.components() {
+            match component {
+                Component::Normal(part) => {
+                    if fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+                        return Err(format!("{} would be extracted through a symlink", name.display()).into());
+                    }
+                    target.push(part);
+                }
+                Component::CurDir => {}
+                _ => return Err(format!("{} would be extracted outside the destination", name.display()).into()),
+            }
+        }
+        Ok(target)
+    }
+
+    fn create_dir(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if path.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        fs::create_dir(path)?;
+        self.created.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn create_file(&mut self, path: &Path, contents: &mut impl Read, mode: Option<u32>) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        self.job.set_current_file(path);
+        let file = File::options().write(true).create_new(true).open(path)?;
+        self.created.push(path.to_path_buf());
+        std::io::copy(contents, &mut JobWriter { file, job: self.job, counts: self.counts_written })?;
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(mode & 0o7777))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        Ok(())
+    }
+
+    fn create_symlink(&mut self, path: &Path, link: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        copy_symlink_to(link, path)?;
+        self.created.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn extract_zip(&mut self, archive: &Path) -> Result<(), Box<dyn Error>> {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?)?;
+        let mut total = 0;
+        for index in 0..zip.len() {
+            total += zip.by_index_raw(index)?.size();
+        }
+        self.job.start(total);
+        self.counts_written = true;
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index)?;
+            let name = entry.enclosed_name().ok_or_else(|| format!("{} would be extracted outside the destination", entry.name()))?;
+            let target = self.target(&name)?;
+            if entry.is_dir() {
+                self.create_dir(&target)?;
+            } else if entry.is_symlink() {
+                let mut link = String::new();
+                entry.read_to_string(&mut link)?;
+                self.create_symlink(&target, Path::new(&link))?;
+                self.job.add_done(link.len() as u64);
+            } else {
+                let mode = entry.unix_mode();
+                self.create_file(&target, &mut entry, mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_tar_gz(&mut self, archive: &Path) -> Result<(), Box<dyn Error>> {
+        // Progress counts the compressed bytes read, as the unpacked size isn't known up front
+        self.job.start_measured(archive);
+        let reader = JobReader { file: File::open(archive)?, job: self.job };
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let target = self.target(&entry.path()?)?;
+            let mode = entry.header().mode().ok();
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => self.create_dir(&target)?,
+                tar::EntryType::Symlink => {
+                    let link = entry.link_name()?.ok_or("symlink without a target")?.into_owned();
+                    self.create_symlink(&target, &link)?;
+                }
+                tar::EntryType::Regular | tar::EntryType::Continuous => self.create_file(&target, &mut entry, mode)?,
+                // Hard links, devices and the like aren't recreated
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Extract with the `7zz` (or `7z`/`7za`) command into a new hidden directory next to
+    /// `dest`, then move what it unpacked into place.
+    fn extract_7z(&mut self, archive: &Path) -> Result<(), Box<dyn Error>> {
+        use std::process::{Command, Stdio};
+
+        // A new directory of its own, so whatever is there already is left alone
+        let staging = temp_path(self.dest, "extracting");
+        fs::create_dir(&staging).map_err(|err| format!("couldn't create {}: {}", staging.display(), err))?;
+        self.job.start_measured(archive);
+
+        let mut spawned: Result<_, Box<dyn Error>> = Err("extracting 7z archives needs the 7zz or 7z command (7-Zip)".into());
+        for tool in ["7zz", "7z", "7za"] {
+            match Command::new(tool)
+                .args(["x", "-y", "-bsp1", "-bso0"])
+                .arg(format!("-o{}", staging.display()))
+                .arg(archive)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+            {
+                Ok(child) => {
+                    spawned = Ok(child);
+                    break;
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    spawned = Err(err.into());
+                    break;
+                }
+            }
+        }
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => {
+                let _ = fs::remove_dir(&staging);
+                return Err(err);
+            }
+        };
+
+        // 7-Zip reports its progress as percentages on stdout
+        let total = self.job.progress().1;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let status = std::thread::scope(|scope| {
+            let job = self.job;
+            scope.spawn(move || {
+                let Some(mut stdout) = stdout else {
+                    return;
+                };
+                let mut buffer = [0; 256];
+                let mut number = String::new();
+                while let Ok(read) = stdout.read(&mut buffer) {
+                    if read == 0 {
+                        break;
+                    }
+                    for &byte in &buffer[..read] {
+                        match byte {
+                            b'0'..=b'9' => number.push(byte as char),
+                            b'%' => {
+                                if let Ok(percent) = number.parse::<u64>() {
+                                    job.done.store(total * percent.min(100) / 100, Ordering::Relaxed);
+                                }
+                                number.clear();
+                            }
+                            _ => number.clear(),
+                        }
+                    }
+                }
+            });
+            let errors = scope.spawn(move || {
+                let mut errors = String::new();
+                if let Some(mut stderr) = stderr {
+                    let _ = stderr.read_to_string(&mut errors);
+                }
+                errors
+            });
+            loop {
+                if self.job.stopped() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err("stopped".to_string());
+                }
+                match child.try_wait() {
+                    Ok(Some(status)) if status.success() => return Ok(()),
+                    Ok(Some(_)) => {
+                        let errors = errors.join().unwrap_or_default();
+                        return Err(format!("7-Zip failed: {}", errors.trim()));
+                    }
+                    Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+                    Err(err) => return Err(err.to_string()),
+                }
+            }
+        });
+
+        let moved = status.map_err(Box::<dyn Error>::from).and_then(|()| {
+            self.create_dir(self.dest)?;
+            for entry in fs::read_dir(&staging)? {
+                let entry = entry?;
+                let target = self.dest.join(entry.file_name());
+                if fs::symlink_metadata(&target).is_ok() {
+                    return Err(format!("{} already exists", target.display()).into());
+                }
+                fs::rename(entry.path(), &target)?;
+                self.created.push(target);
+            }
+            Ok(())
+        });
+        let _ = fs::remove_dir_all(&staging);
+        moved
+    }
+
+    /// Remove everything created, newest first.
+    fn roll_back(&self) {
+        for path in self.created.iter().rev() {
+            let removed = match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                Ok(_) => fs::remove_file(path),
+                Err(_) => continue,
+            };
+            let _ = removed;
+        }
+    }
+}
+
+#[cfg(all(feature = "archive", unix))]
+fn copy_symlink_to(link: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    std::os::unix::fs::symlink(link, path)?;
+    Ok(())
+}
+
+#[cfg(all(feature = "archive", not(unix)))]
+fn copy_symlink_to(_link: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    Err(format!("{} is a symlink, which can't be created here", path.display()).into())
+}
+
+/// Unpack the zip, tar.gz or 7z archive at `archive` into the directory `dest` (created if
+/// needed), keeping the progress in `job`. Existing files are never overwritten, and entries
+/// that would land outside `dest` are refused. A stopped or failed extraction removes what it
+/// unpacked.
+#[cfg(feature = "archive")]
+pub fn extract_with_progress(archive: &Path, dest: &Path, job: &FileOpJob) -> Result<(), Box<dyn Error>> {
+    let format = ArchiveFormat::from_path(&archive.to_string_lossy()).ok_or("not a .zip, .tar.gz, .tgz or .7z archive")?;
+    if dest.starts_with(archive) {
+        return Err(format!("can't extract {} into itself", archive.display()).into());
+    }
+    let mut extractor = Extractor { dest, job, counts_written: false, created: Vec::new() };
+    let result = extractor.create_dir(dest).and_then(|()| match format {
+        ArchiveFormat::Zip => extractor.extract_zip(archive),
+        ArchiveFormat::TarGz => extractor.extract_tar_gz(archive),
+        ArchiveFormat::SevenZip => extractor.extract_7z(archive),
+    });
+    if result.is_err() {
+        extractor.roll_back();
+    }
+    result
+}
+
+/// How often a folder deletion reports its progress. Reporting every file would make the
+/// deletion wait for the UI, which only drains the progress channel a few times a second.
+const DELETE_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A running junk folder deletion, shared by the UI (which pauses or stops it) and the worker.
+#[derive(Debug)]
+pub struct FolderDeleteJob {
+    pub folder: String,
+    pub total_files: usize,
+    stop: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl FolderDeleteJob {
+    pub fn new(folder: String, total_files: usize) -> Self {
+        FolderDeleteJob {
+            folder,
+            total_files,
+            stop: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Stop after the file being deleted; what was deleted so far is still reported.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Pause or resume. Returns whether the job is now paused.
+    pub fn toggle_pause(&self) -> bool {
+        !self.paused.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Wait while paused. Returns false once the job should stop.
+    fn proceed(&self) -> bool {
+        while self.is_paused() && !self.stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        !self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// Delete the junk files of one folder, reporting the progress via the progress channel.
+/// Only the given files are removed (the folder itself and anything that wasn't reported as
+/// junk stay), and never through a symlinked directory. Stops early when the job is stopped or
+/// the device is being ejected; the completion message lists what was removed until then.
+pub fn delete_folder_junk(
+    job: Arc<FolderDeleteJob>,
+    files: Vec<FileEntry>,
+    progress_tx: Sender<ScanProgressMessage>,
+) {
+    let folder = job.folder.clone();
+    let mut deleted = Vec::new();
+    let mut freed = 0;
+    let mut failures = Vec::new();
+    let mut stopped = false;
+    let operation = device_guard::start_operation(&folder);
+    // Progress not reported yet: files, bytes and the last deleted path
+    let mut pending = (0, 0, String::new());
+    let mut last_report = Instant::now();
+
+    for file in files {
+        if !job.proceed() {
+            stopped = true;
+            break;
+        }
+        let busy = match &operation {
+            Ok(operation) => operation.cancelled().err(),
+            Err(busy) => Some(busy.clone()),
+        };
+        if let Some(busy) = busy {
+            failures.push(busy.to_string());
+            stopped = true;
+            break;
+        }
+        // The file must still be a regular file directly inside the folder
+        let path = Path::new(&file.path);
+        let result = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() && path.parent() == Some(Path::new(&folder)) => fs::remove_file(path),
+            Ok(_) => Err(std::io::Error::other("no longer a regular file in the folder")),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => {
+                freed += file.size;
+                pending = (pending.0 + 1, pending.1 + file.size, file.path.clone());
+                deleted.push(file.path);
+            }
+            Err(err) => failures.push(format!("{}: {}", file.path, err)),
+        }
+        if pending.0 > 0 && (last_report.elapsed() >= DELETE_PROGRESS_INTERVAL || job.is_paused()) {
+            let (files, size, path) = std::mem::take(&mut pending);
+            if progress_tx.blocking_send(ScanProgressMessage::FilesDeleted { files, size, path }).is_err() {
+                // The app has gone away
+                return;
+            }
+            last_report = Instant::now();
+        }
+    }
+    if pending.0 > 0 {
+        let (files, size, path) = pending;
+        let _ = progress_tx.blocking_send(ScanProgressMessage::FilesDeleted { files, size, path });
+    }
+
+    // Ignore errors - the app may have been closed
+    let _ = progress_tx.blocking_send(ScanProgressMessage::FolderDeleteComplete { folder, deleted, freed, failures, stopped });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for one test, removed again by `Drop`.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("lazysmg-file-ops-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn ejecting_the_device_stops_a_copy() {
+        let dir = TestDir::new("eject-copy");
+        fs::write(dir.0.join("a.bin"), vec![1; 3 * COPY_CHUNK_SIZE]).unwrap();
+        let source = dir.0.join("a.bin").to_string_lossy().into_owned();
+        let job = FileOpJob::new("Copying", source.clone());
+        let on_devices = job.run_on(&[&source]).unwrap();
+        assert!(!job.stopped());
+
+        let mount = dir.0.to_string_lossy().into_owned();
+        let ejecting = std::thread::spawn(move || device_guard::eject_when_idle(&mount, Duration::from_secs(10), || Err::<(), _>("kept for the test".to_string())));
+        while !job.stopped() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(copy_with_progress(&dir.0.join("a.bin"), &dir.0.join("b.bin"), &job).is_err());
+        assert!(!dir.0.join("b.bin").exists());
+        drop(on_devices);
+        assert_eq!(ejecting.join().unwrap(), Err("kept for the test".to_string()));
+        assert_eq!(job.stopped_by().map(|busy| busy.state), Some(device_guard::DeviceState::Ejecting));
+    }
+
+    #[test]
+    fn copy_over_an_existing_file_replaces_it_only_when_complete() {
+        let dir = TestDir::new("overwrite");
+        let (source, target) = (dir.0.join("new.txt"), dir.0.join("old.txt"));
+        fs::write(&source, b"new contents").unwrap();
+        fs::write(&target, b"old contents").unwrap();
+
+        let stopped = FileOpJob::new("Copying", source.to_string_lossy().into_owned());
+        stopped.stop();
+        assert!(copy_with_progress(&source, &target, &stopped).is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"old contents");
+        assert_eq!(entries(&dir.0), ["new.txt", "old.txt"]);
+
+        let job = FileOpJob::new("Copying", source.to_string_lossy().into_owned());
+        assert_eq!(copy_with_progress(&source, &target, &job).unwrap(), 12);
+        assert_eq!(fs::read(&target).unwrap(), b"new contents");
+        assert_eq!(entries(&dir.0), ["new.txt", "old.txt"]);
+    }
+
+    /// Symlinks of a test archive, by name and target, and its files, by name and contents.
+    #[cfg(feature = "archive")]
+    type Links<'a> = &'a [(&'a str, &'a str)];
+    #[cfg(feature = "archive")]
+    type Files<'a> = &'a [(&'a str, &'a [u8])];
+
+    /// Writes a test archive: `zip_archive` or `tar_gz_archive`.
+    #[cfg(feature = "archive")]
+    type BuildArchive = fn(&Path, Links, Files);
+
+    /// A zip archive at `path` with `files` (name, contents) and `links` (name, target), the
+    /// links first. Names are written as they are, however they escape.
+    #[cfg(feature = "archive")]
+    fn zip_archive(path: &Path, links: Links, files: Files) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, target) in links {
+            zip.add_symlink(*name, *target, options).unwrap();
+        }
+        for (name, contents) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// A tar.gz archive at `path` like `zip_archive`. The header fields are filled in by hand, as
+    /// the tar crate refuses to write names that escape.
+    #[cfg(feature = "archive")]
+    fn tar_gz_archive(path: &Path, links: Links, files: Files) {
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default()));
+        let mut append = |name: &str, link: Option<&str>, contents: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            if let Some(link) = link {
+                header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+                header.set_entry_type(tar::EntryType::Symlink);
+            }
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append(&header, contents).unwrap();
+        };
+        for (name, target) in links {
+            append(name, Some(target), b"");
+        }
+        for (name, contents) in files {
+            append(name, None, contents);
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn extraction_writes_nothing_outside_the_destination() {
+        let dir = TestDir::new("extract-escape");
+        let outside = dir.0.join("outside");
+        fs::create_dir(&outside).unwrap();
+        let absolute = outside.join("absolute.txt").to_string_lossy().into_owned();
+        let outside_link = outside.to_string_lossy().into_owned();
+        let cases: [(Links, Files); 4] = [
+            (&[], &[("ok.txt", b"fine"), ("../outside/parent.txt", b"evil")]),
+            (&[], &[("ok.txt", b"fine"), ("sub/../../outside/nested.txt", b"evil")]),
+            (&[], &[("ok.txt", b"fine"), (&absolute, b"evil")]),
+            (&[("link", &outside_link)], &[("link/through.txt", b"evil")]),
+        ];
+        for (build, extension) in [(zip_archive as BuildArchive, "zip"), (tar_gz_archive, "tar.gz")] {
+            for (index, (links, files)) in cases.iter().enumerate() {
+                let archive = dir.0.join(format!("case{}.{}", index, extension));
+                build(&archive, links, files);
+                let dest = dir.0.join("dest");
+                let job = FileOpJob::new("Extracting", archive.to_string_lossy().into_owned());
+                assert!(extract_with_progress(&archive, &dest, &job).is_err(), "case {} of {} was extracted", index, extension);
+                // Rolled back, and nothing got out
+                assert!(!dest.exists(), "case {} of {} left {:?}", index, extension, dest);
+                assert!(entries(&outside).is_empty(), "case {} of {} wrote {:?}", index, extension, entries(&outside));
+            }
+        }
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn extraction_never_overwrites() {
+        let dir = TestDir::new("extract-existing");
+        let dest = dir.0.join("dest");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("keep.txt"), b"mine").unwrap();
+        for (build, extension) in [(zip_archive as BuildArchive, "zip"), (tar_gz_archive, "tar.gz")] {
+            let archive = dir.0.join(format!("archive.{}", extension));
+            build(&archive, &[], &[("new.txt", b"new"), ("keep.txt", b"theirs")]);
+            let job = FileOpJob::new("Extracting", archive.to_string_lossy().into_owned());
+            assert!(extract_with_progress(&archive, &dest, &job).is_err());
+            // The existing file is kept and what was extracted before it removed again
+            assert_eq!(fs::read(dest.join("keep.txt")).unwrap(), b"mine");
+            assert_eq!(entries(&dest), ["keep.txt"]);
+        }
+    }
+
+    /// Everything below `dir` by relative path: files with their contents, directories, and
+    /// symlinks with their target.
+    #[cfg(feature = "archive")]
+    fn tree(dir: &Path) -> Vec<(String, String)> {
+        let mut found = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(path) = pending.pop() {
+            for entry in fs::read_dir(&path).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(dir).unwrap().to_string_lossy().into_owned();
+                let metadata = fs::symlink_metadata(&path).unwrap();
+                let what = if metadata.file_type().is_symlink() {
+                    format!("-> {}", fs::read_link(&path).unwrap().display())
+                } else if metadata.is_dir() {
+                    pending.push(path);
+                    "dir".to_string()
+                } else {
+                    String::from_utf8(fs::read(&path).unwrap()).unwrap()
+                };
+                found.push((relative, what));
+            }
+        }
+        found.sort();
+        found
+    }
+
+    #[cfg(all(feature = "archive", unix))]
+    #[test]
+    fn archives_extract_to_the_same_tree() {
+        let dir = TestDir::new("archive-round-trip");
+        let source = dir.0.join("project");
+        fs::create_dir_all(source.join("src/nested")).unwrap();
+        fs::create_dir_all(source.join("empty")).unwrap();
+        fs::write(source.join("README"), b"read me").unwrap();
+        fs::write(source.join("src/nested/deep.rs"), b"fn deep() {}").unwrap();
+        std::os::unix::fs::symlink("src/nested/deep.rs", source.join("link")).unwrap();
+        let expected = tree(&source);
+        assert_eq!(expected.len(), 6);
+
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let archive = dir.0.join(format!("project{}", format.extension()));
+            let job = FileOpJob::new("Archiving", source.to_string_lossy().into_owned());
+            assert!(archive_with_progress(&source, &archive, format, &job).unwrap() > 0);
+            let dest = dir.0.join(format!("out{}", format.extension()));
+            let job = FileOpJob::new("Extracting", archive.to_string_lossy().into_owned());
+            extract_with_progress(&archive, &dest, &job).unwrap();
+            assert_eq!(tree(&dest.join("project")), expected, "{:?}", format);
+        }
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn stopped_archive_is_removed() {
+        let dir = TestDir::new("archive-stopped");
+        let source = dir.0.join("project");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("big.bin"), vec![1; 4096]).unwrap();
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let archive = dir.0.join(format!("project{}", format.extension()));
+            let job = FileOpJob::new("Archiving", source.to_string_lossy().into_owned());
+            job.stop();
+            assert!(archive_with_progress(&source, &archive, format, &job).is_err());
+            assert!(!archive.exists(), "{:?}", format);
+        }
+        assert_eq!(entries(&dir.0), ["project"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_a_tree_with_its_links_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("copy-tree");
+        let source = dir.0.join("source");
+        fs::create_dir_all(source.join("a/b")).unwrap();
+        fs::create_dir_all(source.join("empty")).unwrap();
+        fs::write(source.join("top.txt"), b"top").unwrap();
+        fs::write(source.join("a/b/run.sh"), b"#!/bin/sh").unwrap();
+        fs::set_permissions(source.join("a/b/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("a/b/run.sh", source.join("link")).unwrap();
+
+        let target = dir.0.join("target");
+        let job = FileOpJob::new("Copying", source.to_string_lossy().into_owned());
+        assert_eq!(copy_with_progress(&source, &target, &job).unwrap(), 12);
+        // The link counts by the length of its target
+        assert_eq!(job.progress(), (22, 22));
+        assert_eq!(entries(&target), ["a", "empty", "link", "top.txt"]);
+        assert_eq!(fs::read(target.join("a/b/run.sh")).unwrap(), b"#!/bin/sh");
+        assert_eq!(fs::metadata(target.join("a/b/run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::read_link(target.join("link")).unwrap(), Path::new("a/b/run.sh"));
+        // The source is left as it was
+        assert_eq!(entries(&source), ["a", "empty", "link", "top.txt"]);
+
+        let job = FileOpJob::new("Copying", source.to_string_lossy().into_owned());
+        assert!(copy_with_progress(&source, &source.join("a/inside"), &job).is_err());
+        assert!(!source.join("a/inside").exists());
+    }
+
+    #[test]
+    fn move_across_volumes_copies_and_removes_the_source() {
+        let dir = TestDir::new("move");
+        let source = dir.0.join("source");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("sub/data.bin"), vec![3; 5000]).unwrap();
+
+        let target = dir.0.join("target");
+        let job = FileOpJob::new("Moving", source.to_string_lossy().into_owned());
+        let crosses = |_: &Path, _: &Path| Err(std::io::Error::from(ErrorKind::CrossesDevices));
+        assert!(move_with_rename(&source, &target, &job, crosses).unwrap());
+        assert!(!source.exists());
+        assert_eq!(fs::read(target.join("sub/data.bin")).unwrap(), vec![3; 5000]);
+        assert_eq!(job.progress(), (5000, 5000));
+
+        // Within a volume it is a rename
+        let job = FileOpJob::new("Moving", target.to_string_lossy().into_owned());
+        assert!(!move_with_progress(&target, &source, &job).unwrap());
+        assert_eq!(entries(&dir.0), ["source"]);
+
+        // Other failures of the rename are reported, and nothing is copied
+        let job = FileOpJob::new("Moving", source.to_string_lossy().into_owned());
+        let denied = |_: &Path, _: &Path| Err(std::io::Error::from(ErrorKind::PermissionDenied));
+        assert!(move_with_rename(&source, &target, &job, denied).is_err());
+        assert_eq!(entries(&dir.0), ["source"]);
+    }
+}
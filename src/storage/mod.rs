@@ -1,2 +1,11 @@
 pub mod hdd;
 pub mod ssd;
+pub mod scan_cache;
+pub mod scan_history;
+pub mod deletion_impact;
+pub mod device_guard;
+pub mod file_ops;
+pub mod persist;
+pub mod profile;
+pub mod secure_erase;
+pub mod space_check;
@@ -0,0 +1,324 @@
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+// Everything lazysmg persists goes through here, so a crash or power loss mid-save leaves
+// either the old or the new file, never a truncated one.
+//
+// JSON documents are stored as `{"version": N, "data": ...}`. Readers ignore fields they don't
+// know and fill in missing ones with defaults (`#[serde(default)]`), so adding a field needs no
+// new version and older builds keep reading newer files. The version is only raised for
+// incompatible changes; a build then skips files written with a newer version than its own.
+//
+// Each kind of file keeps a list of migrations, one per version: the one at index `i` upgrades
+// data of version `i` to `i + 1`, so the current version is the length of the list. Version 0
+// is the bare JSON written before versioning. Older files are upgraded when loaded and written
+// back in place, after the original is saved next to them as `<name>.v<old version>.bak`. A
+// document that can't be read at all is kept as `<name>.corrupt.bak` before anything replaces it.
+
+/// Temporary files written so far, to keep the names of concurrent writes apart.
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// Write `contents` to `path` atomically: into a temporary file next to it, flushed to disk,
+/// then renamed over `path`. Missing parent directories are created.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let file_name = path.file_name().ok_or_else(|| io::Error::other("no file name"))?;
+    let n = TEMP_FILES.fetch_add(1, Ordering::Relaxed);
+    let temp = parent.join(format!(".{}.{}-{}.tmp", file_name.to_string_lossy(), process::id(), n));
+
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    // Make the rename itself durable; not every platform can sync a directory
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Append one line to a log file and flush it to disk. If an earlier append was cut off by a
+/// crash, the torn line is ended first so it can't swallow this one; readers skip it.
+pub fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+    let mut record = String::with_capacity(line.len() + 2);
+    if file.metadata()?.len() > 0 {
+        let mut last = [0; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            record.push('\n');
+        }
+    }
+    record.push_str(line);
+    record.push('\n');
+    // One write, so concurrent appends don't interleave within a line
+    file.write_all(record.as_bytes())?;
+    file.sync_data()
+}
+
+/// Upgrades the data of a document, or a log record, by one version.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// Migration from the bare JSON written before versioning, which needs no changes to the data.
+pub fn from_unversioned(data: Value) -> Result<Value, String> {
+    Ok(data)
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+/// Save `value` as a versioned JSON document, atomically.
+pub fn save_json<T: Serialize>(path: &Path, version: u32, value: &T) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let json = serde_json::to_vec(&Envelope { version, data: value })?;
+    write_atomic(path, &json)?;
+    Ok(())
+}
+
+/// Apply the migrations from `version` on. `None` if `version` is newer than all of them.
+fn migrate(mut data: Value, version: u32, migrations: &[Migration]) -> Option<Result<Value, String>> {
+    let pending = migrations.get(version as usize..)?;
+    for migration in pending {
+        data = match migration(data) {
+            Ok(data) => data,
+            Err(err) => return Some(Err(err)),
+        };
+    }
+    Some(Ok(data))
+}
+
+/// Keep a copy of a file about to be upgraded from `version`.
+fn back_up(path: &Path, version: u32, contents: &[u8]) -> io::Result<()> {
+    back_up_as(path, &format!("v{}", version), contents)
+}
+
+/// Keep a copy of `path` as `<path>.<label>.bak`.
+fn back_up_as(path: &Path, label: &str, contents: &[u8]) -> io::Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", label));
+    write_atomic(Path::new(&backup), contents)
+}
+
+/// Load a document written by `save_json`, upgrading it with `migrations` (whose length is the
+/// current version) if it is older, and writing the upgraded document back. `None` if there is
+/// none, it can't be parsed or upgraded, or it was written with a newer, incompatible version.
+/// A document that can't be parsed or upgraded is backed up first, as the caller will likely
+/// write a new one over it.
+pub fn load_json<T: DeserializeOwned>(path: &Path, migrations: &[Migration]) -> Option<T> {
+    let content = fs::read(path).ok()?;
+    let corrupt = || {
+        let _ = back_up_as(path, "corrupt", &content);
+        None
+    };
+    let Ok(document) = serde_json::from_slice::<Value>(&content) else {
+        return corrupt();
+    };
+    // Recognised by its numeric version, so fields added to the envelope later don't make it
+    // look like data from before versioning
+    let (version, data) = match document {
+        Value::Object(mut envelope) if envelope.get("version").is_some_and(Value::is_u64) && envelope.contains_key("data") => {
+            let version = envelope.get("version").and_then(Value::as_u64)?;
+            (u32::try_from(version).ok()?, envelope.remove("data")?)
+        }
+        legacy => (0, legacy),
+    };
+    let current = migrations.len() as u32;
+    let Ok(data) = migrate(data, version, migrations)? else {
+        return corrupt();
+    };
+    let Ok(value) = serde_json::from_value(data.clone()) else {
+        return corrupt();
+    };
+    // Failing to store the upgrade only means upgrading again next time
+    if version < current && back_up(path, version, &content).is_ok() {
+        let _ = save_json(path, current, &data);
+    }
+    Some(value)
+}
+
+/// Load a log written with `append_line`, one JSON record per line, each carrying its version
+/// in a `version` field (0 if it has none). Older records are upgraded with `migrations` and, if
+/// there were any, the log is rewritten with them upgraded. Unreadable lines (such as one cut off
+/// by a crash) and records with a newer version are skipped, but kept in the log.
+pub fn load_lines<T: DeserializeOwned>(path: &Path, migrations: &[Migration]) -> Vec<T> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let current = migrations.len() as u32;
+    let mut records = Vec::new();
+    let mut lines = Vec::new();
+    // Oldest version upgraded, if any
+    let mut upgraded: Option<u32> = None;
+    for line in content.lines() {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let version = record.get("version").and_then(Value::as_u64).map_or(0, |version| version as u32);
+        let Some(Ok(mut record)) = migrate(record, version, migrations) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        if version < current {
+            if let Value::Object(fields) = &mut record {
+                fields.insert("version".to_string(), Value::from(current));
+            }
+            upgraded = Some(upgraded.map_or(version, |oldest| oldest.min(version)));
+            lines.push(record.to_string());
+        } else {
+            lines.push(line.to_string());
+        }
+        records.extend(serde_json::from_value(record).ok());
+    }
+    if let Some(oldest) = upgraded && back_up(path, oldest, content.as_bytes()).is_ok() {
+        let mut rewritten = lines.join("\n");
+        rewritten.push('\n');
+        let _ = write_atomic(path, rewritten.as_bytes());
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    /// A directory of its own for each test, removed afterwards.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("lazysmg-persist-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Settings {
+        width: u32,
+    }
+
+    /// Version 0 called the field `size`.
+    fn rename_size(mut data: Value) -> Result<Value, String> {
+        let size = data.get("size").cloned().ok_or("no size")?;
+        data.as_object_mut().ok_or("not an object")?.insert("width".to_string(), size);
+        Ok(data)
+    }
+
+    const MIGRATIONS: &[Migration] = &[rename_size];
+
+    #[test]
+    fn writes_replace_the_file_whole_from_any_thread() {
+        let dir = TestDir::new("atomic");
+        let path = dir.0.join("sub/state.json");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || write_atomic(&path, format!("writer {}", i).repeat(1000).as_bytes()))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        let content = fs::read_to_string(&path).unwrap();
+        assert!((0..8).any(|i| content == format!("writer {}", i).repeat(1000)));
+        // No temporary files are left behind
+        assert_eq!(names(&dir.0.join("sub")), ["state.json"]);
+    }
+
+    #[test]
+    fn documents_are_upgraded_and_unreadable_ones_kept() {
+        let dir = TestDir::new("json");
+        let path = dir.0.join("settings.json");
+        fs::create_dir_all(&dir.0).unwrap();
+
+        // Bare JSON from before versioning is upgraded, backed up and written back
+        fs::write(&path, r#"{"size": 80}"#).unwrap();
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), Some(Settings { width: 80 }));
+        assert_eq!(fs::read_to_string(dir.0.join("settings.json.v0.bak")).unwrap(), r#"{"size": 80}"#);
+        let upgraded: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(upgraded["version"], 1);
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), Some(Settings { width: 80 }));
+
+        // Fields added to the envelope later don't hide the version
+        fs::write(&path, r#"{"version": 1, "data": {"width": 100}, "written_by": "lazysmg 9"}"#).unwrap();
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), Some(Settings { width: 100 }));
+
+        // A newer version is skipped but left alone
+        fs::write(&path, r#"{"version": 2, "data": {"columns": 100}}"#).unwrap();
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), None);
+        assert!(!dir.0.join("settings.json.corrupt.bak").exists());
+
+        // A truncated file is kept before it can be replaced
+        fs::write(&path, r#"{"version": 1, "data": {"wid"#).unwrap();
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), None);
+        assert_eq!(fs::read_to_string(dir.0.join("settings.json.corrupt.bak")).unwrap(), r#"{"version": 1, "data": {"wid"#);
+
+        save_json(&path, 1, &Settings { width: 120 }).unwrap();
+        assert_eq!(load_json::<Settings>(&path, MIGRATIONS), Some(Settings { width: 120 }));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        width: u32,
+    }
+
+    #[test]
+    fn logs_survive_torn_lines_and_are_upgraded() {
+        let dir = TestDir::new("lines");
+        let path = dir.0.join("history.jsonl");
+        fs::create_dir_all(&dir.0).unwrap();
+        assert!(load_lines::<Record>(&path, MIGRATIONS).is_empty());
+
+        // An old record, a newer one and an append cut off by a crash
+        fs::write(&path, "{\"size\": 1}\n{\"version\": 2, \"columns\": 2}\n{\"version\": 1, \"wid").unwrap();
+        append_line(&path, r#"{"version": 1, "width": 3}"#).unwrap();
+        assert_eq!(load_lines::<Record>(&path, MIGRATIONS), [Record { width: 1 }, Record { width: 3 }]);
+
+        // The old record was upgraded in place; the others are kept as they were
+        let lines: Vec<String> = fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert!(lines[0].contains(r#""version":1"#) && lines[0].contains(r#""width":1"#), "{}", lines[0]);
+        assert_eq!(lines[1], r#"{"version": 2, "columns": 2}"#);
+        assert_eq!(lines[2], r#"{"version": 1, "wid"#);
+        assert!(dir.0.join("history.jsonl.v0.bak").exists());
+        assert_eq!(load_lines::<Record>(&path, MIGRATIONS), [Record { width: 1 }, Record { width: 3 }]);
+    }
+}
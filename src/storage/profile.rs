@@ -0,0 +1,33 @@
+use std::{io, path::PathBuf, sync::OnceLock};
+use expanduser::expanduser;
+
+// Named profiles (`--profile work`) keep their own config, junk paths, scan cache and scan
+// history, e.g. one for a personal laptop and one for the servers someone administers. A
+// profile's files live in `profiles/<name>` below the usual directories; without a profile the
+// usual directories are used.
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Use the profile `name` for the rest of the run; called before anything is loaded. Names are
+/// letters, digits, `-` and `_`, so each stays one directory.
+pub fn select(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid profile name {:?}: use letters, digits, - and _", name));
+    }
+    PROFILE.set(name.to_string()).map_err(|_| "A profile was already selected".to_string())
+}
+
+/// The profile selected with `--profile`, if any.
+pub fn selected() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
+/// `base`, a directory such as `~/.config/lazysmg`, with `~` expanded, or the directory of the
+/// selected profile below it.
+pub fn dir(base: &str) -> io::Result<PathBuf> {
+    let base = expanduser(base)?;
+    Ok(match selected() {
+        Some(name) => base.join("profiles").join(name),
+        None => base,
+    })
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use serde::{Deserialize, Serialize};
+use crate::scanner::FileEntry;
+use super::persist;
+
+/// Upgrades of the cache files, one per schema version (raised only for incompatible changes).
+const MIGRATIONS: &[persist::Migration] = &[persist::from_unversioned];
+
+/// Schema version of the cache files.
+const CACHE_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Files and subdirectories found directly inside one directory during a scan,
+/// together with the directory's modification time at that moment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CachedDir {
+    pub mtime: u64,
+    pub files: Vec<FileEntry>,
+    pub subdirs: Vec<String>,
+}
+
+/// Persisted result of a full scan, keyed by directory path so an incremental
+/// rescan can reuse every directory whose mtime has not changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanCache {
+    pub root: String,
+    pub dirs: HashMap<String, CachedDir>,
+}
+
+impl ScanCache {
+    pub fn new(root: &str) -> Self {
+        ScanCache {
+            root: root.to_string(),
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Record a directory and its mtime, keeping any children already added.
+    pub fn add_dir(&mut self, path: &str, mtime: u64) {
+        self.dirs.entry(path.to_string()).or_default().mtime = mtime;
+    }
+
+    /// Register `child` as a subdirectory of `parent`.
+    pub fn add_subdir(&mut self, parent: &str, child: &str) {
+        self.dirs
+            .entry(parent.to_string())
+            .or_default()
+            .subdirs
+            .push(child.to_string());
+    }
+
+    /// Add a file to the directory it lives in.
+    pub fn add_file(&mut self, parent: &str, file: FileEntry) {
+        self.dirs.entry(parent.to_string()).or_default().files.push(file);
+    }
+
+    /// Flatten the cache into a list of files sorted by size (largest first).
+    pub fn to_file_entries(&self) -> Vec<FileEntry> {
+        let mut files: Vec<FileEntry> = self
+            .dirs
+            .values()
+            .flat_map(|dir| dir.files.iter().cloned())
+            .collect();
+        files.sort_by_key(|f| std::cmp::Reverse(f.size));
+        files
+    }
+}
+
+/// Modification time in milliseconds since the Unix epoch, or 0 if unavailable.
+pub fn mtime_millis(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Directory holding one cache file per scanned root, per profile.
+#[cfg(not(test))]
+fn cache_dir() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(super::profile::dir("~/.cache/lazysmg")?.join("scans"))
+}
+
+/// Tests keep their caches in a temporary directory, away from the user's.
+#[cfg(test)]
+fn cache_dir() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(std::env::temp_dir().join(format!("lazysmg-scans-{}", std::process::id())))
+}
+
+/// Cache file location for a scan root (e.g. "/Volumes/Backup" -> "_Volumes_Backup.json"), or
+/// with `previous` the one of the scan before (e.g. "_Volumes_Backup.previous.json").
+pub(crate) fn cache_file(root: &str, previous: bool) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let file_name: String = root
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let suffix = if previous { ".previous" } else { "" };
+    Ok(cache_dir()?.join(format!("{}{}.json", file_name, suffix)))
+}
+
+/// Load the cached scan for `root`, returning `None` if there is no usable cache.
+pub fn load(root: &str) -> Option<ScanCache> {
+    load_file(root, false)
+}
+
+/// Load the scan of `root` before the cached one, to see what changed since.
+pub fn load_previous(root: &str) -> Option<ScanCache> {
+    load_file(root, true)
+}
+
+fn load_file(root: &str, previous: bool) -> Option<ScanCache> {
+    let path = cache_file(root, previous).ok()?;
+    // A save cut off between its renames leaves the newest cache staged
+    let path = if !previous && !path.exists() { staged_file(&path) } else { path };
+    let cache: ScanCache = persist::load_json(&path, MIGRATIONS)?;
+    if cache.root == root {
+        Some(cache)
+    } else {
+        None
+    }
+}
+
+/// Persist a scan so the next rescan of the same root can be incremental. The scan it replaces
+/// is kept as the previous one. The new cache is written in full before the old ones are
+/// rotated, so a failed write leaves them as they were.
+pub fn save(cache: &ScanCache) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = cache_file(&cache.root, false)?;
+    let staged = staged_file(&path);
+    persist::save_json(&staged, CACHE_VERSION, cache)?;
+    if path.exists() {
+        fs::rename(&path, cache_file(&cache.root, true)?)?;
+    }
+    fs::rename(&staged, &path)?;
+    Ok(())
+}
+
+/// Where `save` writes a cache before it replaces `path` (".new.json"; scan roots never put a
+/// dot in their file names).
+fn staged_file(path: &Path) -> PathBuf {
+    path.with_extension("new.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(root: &str, size: u64) -> ScanCache {
+        let mut cache = ScanCache::new(root);
+        cache.add_dir(root, 1);
+        cache.add_file(root, FileEntry { name: "a".to_string(), path: format!("{}/a", root), size, link_target: None, allocated_size: None });
+        cache
+    }
+
+    fn size(cache: Option<ScanCache>) -> Option<u64> {
+        cache.map(|cache| cache.to_file_entries()[0].size)
+    }
+
+    #[test]
+    fn saves_rotate_only_once_the_new_cache_is_written() {
+        let root = format!("/lazysmg-cache-test-{}", std::process::id());
+        save(&cache(&root, 1)).unwrap();
+        save(&cache(&root, 2)).unwrap();
+        assert_eq!((size(load(&root)), size(load_previous(&root))), (Some(2), Some(1)));
+
+        // A write that fails leaves both caches as they were
+        let staged = staged_file(&cache_file(&root, false).unwrap());
+        fs::create_dir_all(&staged).unwrap();
+        assert!(save(&cache(&root, 3)).is_err());
+        assert_eq!((size(load(&root)), size(load_previous(&root))), (Some(2), Some(1)));
+        fs::remove_dir(&staged).unwrap();
+
+        // A save cut off after rotating still finds the new cache
+        persist::save_json(&staged, CACHE_VERSION, &cache(&root, 4)).unwrap();
+        fs::rename(cache_file(&root, false).unwrap(), cache_file(&root, true).unwrap()).unwrap();
+        assert_eq!((size(load(&root)), size(load_previous(&root))), (Some(4), Some(2)));
+
+        for path in [staged, cache_file(&root, false).unwrap(), cache_file(&root, true).unwrap()] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{error::Error, path::PathBuf};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::scanner::FileEntry;
+use super::{persist, profile};
+
+/// Upgrades of the history records, one per schema version (raised only for incompatible changes).
+const MIGRATIONS: &[persist::Migration] = &[persist::from_unversioned];
+
+/// Schema version of the history records.
+const HISTORY_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Summary of one completed scan, appended to the history log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanRecord {
+    /// Schema version the record was written with; 0 for records from before versioning
+    #[serde(default)]
+    pub version: u32,
+    pub device: String,
+    pub mount_point: String,
+    /// Whether this was a junk scan rather than a full device scan
+    pub junk: bool,
+    pub finished_at: DateTime<Local>,
+    pub duration_ms: u64,
+    pub total_size: u64,
+    pub file_count: usize,
+    /// Largest file found, if any
+    pub top_entry: Option<FileEntry>,
+}
+
+impl ScanRecord {
+    /// Build a record from scan results; `results` need not be sorted.
+    pub fn new(device: &str, mount_point: &str, junk: bool, duration_ms: u64, results: &[FileEntry]) -> Self {
+        ScanRecord {
+            version: HISTORY_VERSION,
+            device: device.to_string(),
+            mount_point: mount_point.to_string(),
+            junk,
+            finished_at: Local::now(),
+            duration_ms,
+            total_size: results.iter().map(|f| f.size).sum(),
+            file_count: results.len(),
+            top_entry: results.iter().max_by_key(|f| f.size).cloned(),
+        }
+    }
+
+    /// Whether two records describe the same kind of scan of the same place, so their sizes compare.
+    pub fn same_target(&self, other: &ScanRecord) -> bool {
+        self.mount_point == other.mount_point && self.junk == other.junk
+    }
+}
+
+/// History log location, per profile: one JSON record per line.
+fn history_file() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(profile::dir("~/.local/share/lazysmg")?.join("scan_history.jsonl"))
+}
+
+/// Append a completed scan to the history log.
+pub fn append(record: &ScanRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
+    persist::append_line(&history_file()?, &serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Load all recorded scans, oldest first, upgrading records of older schemas in the log.
+/// Unreadable lines (such as one cut off by a crash) and records written with a newer,
+/// incompatible schema are skipped.
+pub fn load() -> Vec<ScanRecord> {
+    match history_file() {
+        Ok(path) => persist::load_lines(&path, MIGRATIONS),
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+// Warnings for the secure delete (`[delete] secure_erase`), which overwrites files before
+// deleting them. That only reaches the old contents on spinning disks and simple flash media
+// with a file system that writes in place: SSDs put the overwrites in other cells, and
+// copy-on-write file systems such as APFS write them to new blocks, so the old data survives
+// until the space happens to be reused.
+
+/// File systems that never overwrite file contents in place.
+const COPY_ON_WRITE: [&str; 4] = ["apfs", "btrfs", "zfs", "bcachefs"];
+
+/// What is known about the disk and file system a path is on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Medium {
+    /// File system type, e.g. "ext4" or "apfs"
+    pub fs_type: Option<String>,
+    /// Whether the disk is an SSD, if the system says
+    pub solid_state: Option<bool>,
+}
+
+impl Medium {
+    /// The medium of the volume `path` is on, the innermost mount point above it.
+    #[cfg(target_os = "linux")]
+    pub fn of(path: &Path) -> Medium {
+        let Some((device, fs_type)) = std::fs::read_to_string("/proc/self/mounts").ok().and_then(|table| mount_of(&parse_proc_mounts(&table), path)) else {
+            return Medium::default();
+        };
+        Medium { solid_state: rotational(&device).map(|rotational| !rotational), fs_type: Some(fs_type) }
+    }
+
+    /// The medium of the volume `path` is on, the innermost mount point above it.
+    #[cfg(not(target_os = "linux"))]
+    pub fn of(path: &Path) -> Medium {
+        let mounts = std::process::Command::new("mount")
+            .output()
+            .map(|output| parse_mount_output(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default();
+        let Some((mount_point, _, fs_type)) = innermost(&mounts, path) else {
+            return Medium::default();
+        };
+        let solid_state = if cfg!(target_os = "macos") {
+            crate::platform::macos::diskutil_info(mount_point).and_then(|info| info.solid_state)
+        } else {
+            None
+        };
+        Medium { fs_type: Some(fs_type.clone()), solid_state }
+    }
+
+    /// Why overwriting may not reach the old contents on this medium, or None if it should.
+    pub fn warning(&self) -> Option<String> {
+        let mut reasons = Vec::new();
+        if let Some(fs_type) = self.fs_type.as_deref().filter(|fs_type| COPY_ON_WRITE.contains(&fs_type.to_lowercase().as_str())) {
+            reasons.push(format!("The volume is {}, a copy-on-write file system: the overwrites go to new blocks and the old contents stay on the disk.", fs_type));
+        }
+        if self.solid_state == Some(true) {
+            reasons.push("The disk is an SSD: its firmware puts the overwrites in other cells, so the old contents may survive. Its own secure erase or full-disk encryption protects them instead.".to_string());
+        }
+        if reasons.is_empty() && (self.fs_type.is_none() || self.solid_state.is_none()) {
+            reasons.push("The kind of disk couldn't be told; overwriting only helps on spinning disks and simple flash media.".to_string());
+        }
+        (!reasons.is_empty()).then(|| reasons.join(" "))
+    }
+}
+
+/// Why a secure delete of `path` may not work, for its confirmation. Looks up the disk, so
+/// call it in the background.
+pub fn warning(path: &str) -> Option<String> {
+    Medium::of(Path::new(path)).warning()
+}
+
+/// Mounts as (mount point, device, file system type).
+type Mount = (String, String, String);
+
+/// The mount `path` is on: the innermost mount point above it.
+fn innermost<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts.iter().filter(|(mount_point, _, _)| path.starts_with(mount_point)).max_by_key(|(mount_point, _, _)| mount_point.len())
+}
+
+/// Device and file system type of the volume `path` is on.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_of(mounts: &[Mount], path: &Path) -> Option<(String, String)> {
+    innermost(mounts, path).map(|(_, device, fs_type)| (device.clone(), fs_type.clone()))
+}
+
+/// `/proc/self/mounts`: device, mount point (with spaces written as `\040`), type and options.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_mounts(table: &str) -> Vec<Mount> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?.to_string();
+            let mount_point = fields.next()?.replace("\\040", " ");
+            Some((mount_point, device, fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Output of `mount` on macOS and the BSDs: `/dev/disk3s1 on /Volumes/USB (apfs, local, noatime)`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn parse_mount_output(output: &str) -> Vec<Mount> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (device, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            let fs_type = options.trim_end_matches(')').split(", ").next()?;
+            Some((mount_point.to_string(), device.to_string(), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Whether the block device `device` (e.g. /dev/sda1 or /dev/mapper/home) spins, from
+/// `/sys/class/block`. Partitions have no queue of their own; their disk's counts.
+#[cfg(target_os = "linux")]
+fn rotational(device: &str) -> Option<bool> {
+    let name = std::fs::canonicalize(device).ok()?.file_name()?.to_string_lossy().into_owned();
+    let block = std::fs::canonicalize(Path::new("/sys/class/block").join(name)).ok()?;
+    let flag = std::fs::read_to_string(block.join("queue/rotational"))
+        .ok()
+        .or_else(|| std::fs::read_to_string(block.parent()?.join("queue/rotational")).ok())?;
+    match flag.trim() {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_about_ssds_and_copy_on_write() {
+        let medium = |fs_type: Option<&str>, solid_state| Medium { fs_type: fs_type.map(str::to_string), solid_state };
+        assert_eq!(medium(Some("ext4"), Some(false)).warning(), None);
+        assert!(medium(Some("ext4"), Some(true)).warning().unwrap().contains("SSD"));
+        let apfs = medium(Some("apfs"), Some(true)).warning().unwrap();
+        assert!(apfs.contains("apfs, a copy-on-write") && apfs.contains("SSD"), "{}", apfs);
+        assert!(medium(Some("btrfs"), Some(false)).warning().unwrap().contains("copy-on-write"));
+        assert!(medium(Some("vfat"), None).warning().unwrap().contains("couldn't be told"));
+    }
+
+    #[test]
+    fn finds_the_innermost_mount() {
+        let mounts = parse_proc_mounts("/dev/nvme0n1p2 / ext4 rw 0 0\n/dev/sdb1 /media/USB\\040Stick vfat rw 0 0\n");
+        assert_eq!(mount_of(&mounts, Path::new("/media/USB Stick/a.txt")), Some(("/dev/sdb1".to_string(), "vfat".to_string())));
+        assert_eq!(mount_of(&mounts, Path::new("/home/ana/a.txt")), Some(("/dev/nvme0n1p2".to_string(), "ext4".to_string())));
+        let mounts = parse_mount_output("/dev/disk3s1 on / (apfs, local, journaled)\n/dev/disk4s1 on /Volumes/Old Disk (hfs, local, nodev)\n");
+        assert_eq!(mount_of(&mounts, Path::new("/Volumes/Old Disk/x")), Some(("/dev/disk4s1".to_string(), "hfs".to_string())));
+    }
+
+    #[test]
+    fn secure_delete_overwrites_and_removes() {
+        use crate::storage::file_ops::{self, FileOpJob};
+        use std::fs;
+
+        let dir = std::env::temp_dir().join(format!("lazysmg-secure-erase-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.bin"), vec![7; 3000]).unwrap();
+        fs::write(dir.join("sub/b.bin"), b"secret").unwrap();
+        let job = FileOpJob::new("Erasing", dir.to_string_lossy().into_owned());
+        file_ops::secure_delete_with_progress(&dir, 3, &job).unwrap();
+        assert!(!dir.exists());
+        assert_eq!(job.progress(), (3 * 3006, 3 * 3006));
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fs, process::Command, thread, time::Duration};
+use crate::format::{format_size, SizeUnits};
+use crate::platform::macos;
+
+/// Deletes expected to free less than this aren't checked; other writes to the volume would
+/// drown them out.
+const MIN_CHECKED_SIZE: u64 = 10 * 1024 * 1024;
+/// Share of the expected space a delete has to free to count as having freed it.
+const MIN_FREED_SHARE: f64 = 0.5;
+/// Time the file system gets to release the space (APFS frees it lazily) before it is measured.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Free space of a volume just before a delete, to tell afterwards whether the delete freed the
+/// space it should have.
+#[derive(Debug, Clone)]
+pub struct SpaceCheck {
+    pub path: String,
+    pub mount_point: String,
+    pub available_before: u64,
+    /// What the delete should free
+    pub expected: u64,
+    /// Processes that had the entry open when the delete was confirmed
+    pub holders: Vec<String>,
+}
+
+/// Why a delete didn't free its space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpaceNote {
+    /// It went to the trash and nothing else holds on to it, so emptying the trash frees it
+    InTrash(String),
+    /// The likely causes and what to do about them
+    Explained(String),
+}
+
+impl SpaceCheck {
+    /// Measure the free space before deleting `path`, which should free `expected` bytes on the
+    /// volume at `mount_point`. None if the delete is too small to tell, or the free space can't
+    /// be measured.
+    pub fn before_delete(path: &str, mount_point: &str, expected: u64, holders: Vec<String>) -> Option<SpaceCheck> {
+        if expected < MIN_CHECKED_SIZE {
+            return None;
+        }
+        Some(SpaceCheck {
+            path: path.to_string(),
+            mount_point: mount_point.to_string(),
+            available_before: macos::available_space(mount_point)?,
+            expected,
+            holders,
+        })
+    }
+
+    /// Once the delete went through (into the trash if `trashed`), wait for the space to be
+    /// released and measure it again. Blocks for `SETTLE_TIME`. None if the space was freed.
+    pub fn explain(&self, trashed: bool, units: SizeUnits, empty_trash_key: &str) -> Option<SpaceNote> {
+        thread::sleep(SETTLE_TIME);
+        let available = macos::available_space(&self.mount_point)?;
+        let freed = available.saturating_sub(self.available_before);
+        if freed as f64 >= self.expected as f64 * MIN_FREED_SHARE {
+            return None;
+        }
+
+        let snapshots = snapshot_hint(&self.mount_point, self.expected);
+        if trashed && self.holders.is_empty() && snapshots.is_none() {
+            return Some(SpaceNote::InTrash(format!(
+                "{} of {} are freed once the trash is emptied ({} = empty trash)",
+                format_size(self.expected, units),
+                self.path,
+                empty_trash_key
+            )));
+        }
+
+        let mut causes = Vec::new();
+        if trashed {
+            causes.push(format!("It is in the trash, which keeps its space: press '{}' to empty the trash of this volume.", empty_trash_key));
+        }
+        if !self.holders.is_empty() {
+            causes.push(format!(
+                "It was open in {}. A deleted file's space is only freed once every process closes it: quit or restart them.",
+                self.holders.join(", ")
+            ));
+        }
+        causes.extend(snapshots);
+        causes.push("Other hard links to a file keep its data, and other programs may have written to the volume meanwhile.".to_string());
+
+        let mut text = format!(
+            "Deleting {} freed {} of the expected {} on {}. Likely causes:\n",
+            self.path,
+            format_size(freed, units),
+            format_size(self.expected, units),
+            self.mount_point
+        );
+        for cause in causes {
+            text.push_str(&format!("\n- {}", cause));
+        }
+        Some(SpaceNote::Explained(text))
+    }
+}
+
+/// Snapshots of the volume that may keep deleted data, with how to get rid of them: local Time
+/// Machine snapshots on macOS, and btrfs or ZFS snapshots elsewhere.
+fn snapshot_hint(mount_point: &str, expected: u64) -> Option<String> {
+    if cfg!(target_os = "macos") {
+        let output = Command::new("tmutil").args(["listlocalsnapshots", mount_point]).output().ok()?;
+        let count = String::from_utf8_lossy(&output.stdout).lines().filter(|line| line.contains("com.apple.TimeMachine")).count();
+        (count > 0).then(|| {
+            format!(
+                "{} local Time Machine snapshots of the volume keep deleted data until they expire; `tmutil thinlocalsnapshots {} {} 4` thins them.",
+                count, mount_point, expected
+            )
+        })
+    } else {
+        // /proc/mounts: device, mount point, file system type, ...
+        let mounts = fs::read_to_string("/proc/mounts").ok()?;
+        let fs_type = mounts.lines().find_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_, mount, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            (mount == mount_point).then(|| fs_type.to_string())
+        })?;
+        matches!(fs_type.as_str(), "btrfs" | "zfs").then(|| {
+            format!("The volume is {}: snapshots taken before the delete keep its data until they are removed.", fs_type)
+        })
+    }
+}
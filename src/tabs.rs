@@ -0,0 +1,183 @@
+use std::{mem, path::Path};
+use crate::platform::macos::StorageDevice;
+use crate::dir_view::DirView;
+use crate::type_view::TypeBreakdown;
+use crate::analysis::SizeDeltas;
+use crate::scanner::{DirTotals, FileEntry};
+use crate::{App, FolderSummary, PanelFocus, ScanMode};
+
+/// Tabs hold at most this many entries; the number keys switch to the first nine.
+pub const MAX_TABS: usize = 9;
+
+/// What a tab of the file panel remembers while another tab is shown: its device, the
+/// directory listed and how far it is scrolled, and its scan results.
+#[derive(Debug, Clone, Default)]
+pub struct Tab {
+    /// `StorageDevice::key` of the device, which stays the same when the device list changes
+    pub device: Option<String>,
+    pub browse_dir: Option<String>,
+    pub file_entries: Option<Vec<FileEntry>>,
+    pub full_scan_results: Option<Vec<FileEntry>>,
+    pub full_scan_totals: Option<DirTotals>,
+    pub dir_view: Option<DirView>,
+    pub type_breakdown: Option<TypeBreakdown>,
+    pub size_deltas: Option<SizeDeltas>,
+    pub scan_mode: ScanMode,
+    pub selected_file_index: usize,
+    pub file_list_offset: usize,
+    pub folder_summaries: Option<Vec<FolderSummary>>,
+    pub selected_folder_index: usize,
+    pub folder_view_mode: bool,
+    pub category_view_mode: bool,
+    pub focus: PanelFocus,
+}
+
+impl Tab {
+    /// A copy of what the file panel shows now, for a new tab.
+    pub fn capture(app: &App) -> Tab {
+        Tab {
+            device: app.devices.get(app.selected).map(|device| device.key().to_string()),
+            browse_dir: app.browse_dir.clone(),
+            file_entries: app.file_entries.clone(),
+            full_scan_results: app.full_scan_results.clone(),
+            full_scan_totals: app.full_scan_totals.clone(),
+            dir_view: app.dir_view.clone(),
+            type_breakdown: app.type_breakdown.clone(),
+            size_deltas: app.size_deltas.clone(),
+            scan_mode: app.scan_mode.clone(),
+            selected_file_index: app.selected_file_index,
+            file_list_offset: app.file_list_offset,
+            folder_summaries: app.folder_summaries.clone(),
+            selected_folder_index: app.selected_folder_index,
+            folder_view_mode: app.folder_view_mode,
+            category_view_mode: app.category_view_mode,
+            focus: app.focus.clone(),
+        }
+    }
+
+    /// Move what the file panel shows out of `app`, when switching away from its tab.
+    pub fn take(app: &mut App) -> Tab {
+        Tab {
+            device: app.devices.get(app.selected).map(|device| device.key().to_string()),
+            browse_dir: app.browse_dir.take(),
+            file_entries: app.file_entries.take(),
+            full_scan_results: app.full_scan_results.take(),
+            full_scan_totals: app.full_scan_totals.take(),
+            dir_view: app.dir_view.take(),
+            type_breakdown: app.type_breakdown.take(),
+            size_deltas: app.size_deltas.take(),
+            scan_mode: app.scan_mode.clone(),
+            selected_file_index: app.selected_file_index,
+            file_list_offset: app.file_list_offset,
+            folder_summaries: app.folder_summaries.take(),
+            selected_folder_index: app.selected_folder_index,
+            folder_view_mode: app.folder_view_mode,
+            category_view_mode: app.category_view_mode,
+            focus: app.focus.clone(),
+        }
+    }
+
+    /// Show this tab in the file panel. If its device is gone, the selected device is kept with
+    /// nothing listed, to be listed again from its root.
+    pub fn restore(self, app: &mut App) {
+        let index = self.device.as_deref().and_then(|key| app.devices.iter().position(|device| device.key() == key));
+        if let Some(index) = index {
+            app.selected = index;
+            app.browse_dir = self.browse_dir;
+            app.file_entries = self.file_entries;
+            app.full_scan_results = self.full_scan_results;
+            app.full_scan_totals = self.full_scan_totals;
+            app.dir_view = self.dir_view;
+            app.type_breakdown = self.type_breakdown;
+            app.size_deltas = self.size_deltas;
+            app.folder_summaries = self.folder_summaries;
+        } else {
+            app.browse_dir = None;
+            app.file_entries = None;
+            app.full_scan_results = None;
+            app.full_scan_totals = None;
+            app.dir_view = None;
+            app.type_breakdown = None;
+            app.size_deltas = None;
+            app.folder_summaries = None;
+        }
+        app.scan_mode = self.scan_mode;
+        app.selected_file_index = self.selected_file_index;
+        app.file_list_offset = self.file_list_offset;
+        app.selected_folder_index = self.selected_folder_index;
+        app.folder_view_mode = self.folder_view_mode;
+        app.category_view_mode = self.category_view_mode;
+        app.focus = self.focus;
+    }
+}
+
+/// Name shown in the tab bar: the device, and the directory listed below its root.
+fn label(device: Option<&StorageDevice>, browse_dir: Option<&str>) -> String {
+    let name = device.map_or("(gone)", |device| device.name.as_str());
+    match browse_dir.and_then(|dir| Path::new(dir).file_name()) {
+        Some(dir) => format!("{} › {}", name, dir.to_string_lossy()),
+        None => name.to_string(),
+    }
+}
+
+impl App {
+    /// Names of the tabs for the tab bar; the current one is named after what is shown now.
+    pub fn tab_labels(&self) -> Vec<String> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(index, tab)| {
+                if index == self.active_tab {
+                    label(self.devices.get(self.selected), self.browse_dir.as_deref())
+                } else {
+                    let device = tab.device.as_deref().and_then(|key| self.devices.iter().find(|device| device.key() == key));
+                    label(device, tab.browse_dir.as_deref())
+                }
+            })
+            .collect()
+    }
+
+    /// Open a tab on the device and directory shown, right after the current tab, and switch to
+    /// it. Returns false if `MAX_TABS` are open already.
+    pub fn open_tab(&mut self) -> bool {
+        if self.tabs.len() >= MAX_TABS {
+            return false;
+        }
+        self.tabs[self.active_tab] = Tab::capture(self);
+        self.tabs.insert(self.active_tab + 1, Tab::capture(self));
+        self.active_tab += 1;
+        true
+    }
+
+    /// Switch to the tab at `index`, keeping what the current one shows. Returns whether the
+    /// tab changed.
+    pub fn switch_tab(&mut self, index: usize) -> bool {
+        if index == self.active_tab || index >= self.tabs.len() {
+            return false;
+        }
+        self.tabs[self.active_tab] = Tab::take(self);
+        mem::take(&mut self.tabs[index]).restore(self);
+        self.active_tab = index;
+        self.tab_switched = true;
+        true
+    }
+
+    /// Switch to the next tab (`step` 1) or the previous one (-1), wrapping around.
+    pub fn cycle_tab(&mut self, step: isize) -> bool {
+        let count = self.tabs.len() as isize;
+        self.switch_tab((self.active_tab as isize + step).rem_euclid(count) as usize)
+    }
+
+    /// Close the current tab and show the one after it (or before it, for the last tab).
+    /// Returns false for the only tab.
+    pub fn close_tab(&mut self) -> bool {
+        if self.tabs.len() < 2 {
+            return false;
+        }
+        self.tabs.remove(self.active_tab);
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+        mem::take(&mut self.tabs[self.active_tab]).restore(self);
+        self.tab_switched = true;
+        true
+    }
+}
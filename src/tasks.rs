@@ -0,0 +1,68 @@
+use std::{fmt, sync::mpsc, thread};
+use crate::{App, ModeMachine};
+
+/// What a finished task changes in the app, run on the main loop.
+pub type Apply = Box<dyn FnOnce(&mut App, &mut ModeMachine) + Send>;
+
+/// Actions that touch the file systems or devices (ejects, cleaning, renames, opening files)
+/// run here, each on its own thread, so a slow disk or network mount never holds up the keys.
+/// Each hands back what to change in the app once it is done.
+pub struct Tasks {
+    tx: mpsc::Sender<Apply>,
+    rx: mpsc::Receiver<Apply>,
+    running: usize,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Tasks { tx, rx, running: 0 }
+    }
+}
+
+impl fmt::Debug for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tasks").field("running", &self.running).finish()
+    }
+}
+
+impl Tasks {
+    /// Run `work` in the background; what it returns is applied by `apply_finished`.
+    pub fn spawn(&mut self, work: impl FnOnce() -> Apply + Send + 'static) {
+        self.running += 1;
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            // Ignore errors - the app may have been closed
+            let _ = tx.send(work());
+        });
+    }
+
+    /// Number of tasks that haven't finished yet.
+    pub fn running(&self) -> usize {
+        self.running
+    }
+
+    /// Apply what the tasks that finished since the last call changed. Returns whether any did.
+    pub fn apply_finished(app: &mut App, mode: &mut ModeMachine) -> bool {
+        let mut applied = false;
+        while let Ok(apply) = app.tasks.rx.try_recv() {
+            app.tasks.running -= 1;
+            apply(app, mode);
+            applied = true;
+        }
+        applied
+    }
+}
+
+/// Show the result of a task: in the popup of a task the user waits for, in a new popup if
+/// nothing else is open, or else in the status line, so it doesn't interrupt a dialog.
+pub fn report(app: &mut App, mode: &mut ModeMachine, message: String) {
+    if mode.finish_task(message.clone()) {
+        return;
+    }
+    if mode.is_normal() {
+        mode.show_message(message);
+    } else {
+        app.notification = Some(message.replace('\n', "; "));
+    }
+}
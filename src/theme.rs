@@ -0,0 +1,340 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+
+// The colors of the UI come from a theme: one of the built-in palettes, with the colors set in
+// the `[colors]` section of the config file on top, then limited to what the terminal can show.
+
+/// Built-in palette a theme starts from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    /// For dark terminal backgrounds
+    #[default]
+    Dark,
+    /// For light terminal backgrounds
+    Light,
+    /// Bright colors only, for readability
+    HighContrast,
+}
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorSupport {
+    /// Worked out from `NO_COLOR`, `COLORTERM`, `TERM` and the terminal program
+    #[default]
+    Auto,
+    /// 24-bit RGB
+    TrueColor,
+    /// The 256-color palette
+    #[serde(rename = "256")]
+    Indexed,
+    /// The 16 ANSI colors
+    #[serde(rename = "16")]
+    Basic,
+    /// No colors; focus and selection still show in bold
+    None,
+}
+
+impl ColorSupport {
+    /// What the terminal supports, for `Auto`.
+    fn detect() -> ColorSupport {
+        ColorSupport::detect_from(|name| std::env::var(name).unwrap_or_default())
+    }
+
+    /// What the terminal supports, judged by the environment variables `var` returns. Over SSH
+    /// usually only `TERM` comes along, so it is also checked for truecolor and monochrome
+    /// terminal types.
+    fn detect_from(var: impl Fn(&str) -> String) -> ColorSupport {
+        let term = var("TERM");
+        let monochrome = term == "dumb" || term.ends_with("-mono") || term.ends_with("-m") || matches!(term.as_str(), "vt52" | "vt100" | "vt102" | "vt220");
+        if !var("NO_COLOR").is_empty() || monochrome {
+            ColorSupport::None
+        } else if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit")
+            || term.ends_with("-direct")
+            || term.contains("truecolor")
+            || term.contains("24bit")
+            || TRUECOLOR_TERMINALS.contains(&var("TERM_PROGRAM").as_str())
+            || !var("WT_SESSION").is_empty()
+        {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Indexed
+        } else {
+            ColorSupport::Basic
+        }
+    }
+}
+
+/// Terminal programs (`TERM_PROGRAM`) that show 24-bit colors without saying so in `COLORTERM`.
+const TRUECOLOR_TERMINALS: [&str; 5] = ["iTerm.app", "WezTerm", "vscode", "Hyper", "ghostty"];
+
+/// The colors used throughout the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Border of the focused panel
+    pub focus: Color,
+    /// Selected row in lists and tables
+    pub highlight: Color,
+    /// Table header row
+    pub header: Color,
+    /// Device usage gauge
+    pub usage_gauge: Color,
+    /// Full scan progress gauge
+    pub scan_gauge: Color,
+    /// Unfilled part of the gauges
+    pub gauge_bg: Color,
+    /// Help overlay border, matched characters during a device search and keywords in previews
+    pub accent: Color,
+    /// Popup and help overlay background
+    pub popup_bg: Color,
+    /// Popup and help overlay text
+    pub popup_fg: Color,
+    /// Legend text
+    pub legend: Color,
+    /// Healthy drives
+    pub good: Color,
+    /// Failing drives
+    pub bad: Color,
+    /// Strings in previews
+    pub string: Color,
+    /// Numbers in previews
+    pub number: Color,
+    /// Directories in file listings
+    pub directory: Color,
+    /// Images in file listings
+    pub image: Color,
+    /// Videos in file listings
+    pub video: Color,
+    /// Audio files in file listings
+    pub audio: Color,
+    /// Archives and disk images in file listings
+    pub archive: Color,
+    /// Source code and config files in file listings
+    pub code: Color,
+    /// Text files and documents in file listings
+    pub document: Color,
+    /// Paths of system files in scan results
+    pub system_data: Color,
+    /// Paths of data apps keep for themselves in scan results
+    pub app_data: Color,
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Theme {
+        match name {
+            ThemeName::Dark => Theme {
+                focus: Color::Magenta,
+                highlight: Color::Yellow,
+                header: Color::LightBlue,
+                usage_gauge: Color::Magenta,
+                scan_gauge: Color::Cyan,
+                gauge_bg: Color::Black,
+                accent: Color::Cyan,
+                popup_bg: Color::DarkGray,
+                popup_fg: Color::White,
+                legend: Color::White,
+                good: Color::Green,
+                bad: Color::Red,
+                string: Color::Green,
+                number: Color::Yellow,
+                directory: Color::LightBlue,
+                image: Color::Magenta,
+                video: Color::LightMagenta,
+                audio: Color::Cyan,
+                archive: Color::LightRed,
+                code: Color::LightGreen,
+                document: Color::Gray,
+                system_data: Color::LightRed,
+                app_data: Color::Yellow,
+            },
+            ThemeName::Light => Theme {
+                focus: Color::Blue,
+                highlight: Color::Magenta,
+                header: Color::Blue,
+                usage_gauge: Color::Blue,
+                scan_gauge: Color::Cyan,
+                gauge_bg: Color::Gray,
+                accent: Color::Magenta,
+                popup_bg: Color::Gray,
+                popup_fg: Color::Black,
+                legend: Color::Black,
+                good: Color::Green,
+                bad: Color::Red,
+                string: Color::Green,
+                number: Color::Red,
+                directory: Color::Blue,
+                image: Color::Magenta,
+                video: Color::Magenta,
+                audio: Color::Cyan,
+                archive: Color::Red,
+                code: Color::Green,
+                document: Color::DarkGray,
+                system_data: Color::Red,
+                app_data: Color::Magenta,
+            },
+            ThemeName::HighContrast => Theme {
+                focus: Color::White,
+                highlight: Color::LightYellow,
+                header: Color::LightCyan,
+                usage_gauge: Color::LightGreen,
+                scan_gauge: Color::LightCyan,
+                gauge_bg: Color::Black,
+                accent: Color::LightMagenta,
+                popup_bg: Color::Black,
+                popup_fg: Color::White,
+                legend: Color::White,
+                good: Color::LightGreen,
+                bad: Color::LightRed,
+                string: Color::LightGreen,
+                number: Color::LightYellow,
+                directory: Color::LightBlue,
+                image: Color::LightMagenta,
+                video: Color::LightMagenta,
+                audio: Color::LightCyan,
+                archive: Color::LightRed,
+                code: Color::LightGreen,
+                document: Color::White,
+                system_data: Color::LightRed,
+                app_data: Color::LightYellow,
+            },
+        }
+    }
+
+    /// The theme with every color replaced by the closest one the terminal can show.
+    pub fn limited_to(self, support: ColorSupport) -> Theme {
+        let support = match support {
+            ColorSupport::Auto => ColorSupport::detect(),
+            support => support,
+        };
+        let limit = |color| limit_color(color, support);
+        Theme {
+            focus: limit(self.focus),
+            highlight: limit(self.highlight),
+            header: limit(self.header),
+            usage_gauge: limit(self.usage_gauge),
+            scan_gauge: limit(self.scan_gauge),
+            gauge_bg: limit(self.gauge_bg),
+            accent: limit(self.accent),
+            popup_bg: limit(self.popup_bg),
+            popup_fg: limit(self.popup_fg),
+            legend: limit(self.legend),
+            good: limit(self.good),
+            bad: limit(self.bad),
+            string: limit(self.string),
+            number: limit(self.number),
+            directory: limit(self.directory),
+            image: limit(self.image),
+            video: limit(self.video),
+            audio: limit(self.audio),
+            archive: limit(self.archive),
+            code: limit(self.code),
+            document: limit(self.document),
+            system_data: limit(self.system_data),
+            app_data: limit(self.app_data),
+        }
+    }
+}
+
+/// The 16 ANSI colors with their usual RGB values.
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6×6×6 color cube of the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// RGB value of an entry of the 256-color palette.
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC[index as usize].1,
+        16..=231 => {
+            let cube = index - 16;
+            (CUBE_LEVELS[(cube / 36) as usize], CUBE_LEVELS[(cube / 6 % 6) as usize], CUBE_LEVELS[(cube % 6) as usize])
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// How different two colors look, by the "redmean" approximation: the channels are weighted by
+/// how well the eye tells them apart, green the most and blue the least.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let red_mean = (r1 as i32 + r2 as i32) / 2;
+    let (dr, dg, db) = (r1 as i32 - r2 as i32, g1 as i32 - g2 as i32, b1 as i32 - b2 as i32);
+    ((((512 + red_mean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - red_mean) * db * db) >> 8)) as u32
+}
+
+/// Closest entry of the 256-color palette, from the color cube or the gray ramp.
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255).min_by_key(|&index| distance(rgb, indexed_rgb(index))).unwrap_or(16)
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> Color {
+    BASIC.iter().min_by_key(|(_, basic)| distance(rgb, *basic)).map_or(Color::Reset, |(color, _)| *color)
+}
+
+fn limit_color(color: Color, support: ColorSupport) -> Color {
+    match (support, color) {
+        (ColorSupport::None, _) => Color::Reset,
+        (ColorSupport::Indexed, Color::Rgb(r, g, b)) => Color::Indexed(nearest_indexed((r, g, b))),
+        (ColorSupport::Basic, Color::Rgb(r, g, b)) => nearest_basic((r, g, b)),
+        (ColorSupport::Basic, Color::Indexed(index)) => nearest_basic(indexed_rgb(index)),
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> ColorSupport {
+        ColorSupport::detect_from(|name| vars.iter().find(|(var, _)| *var == name).map_or(String::new(), |(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn detects_support_from_environment() {
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]), ColorSupport::TrueColor);
+        assert_eq!(detect(&[("TERM", "xterm-direct")]), ColorSupport::TrueColor);
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]), ColorSupport::TrueColor);
+        // Over SSH, where COLORTERM doesn't come along
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ColorSupport::Indexed);
+        assert_eq!(detect(&[("TERM", "tmux-256color")]), ColorSupport::Indexed);
+        assert_eq!(detect(&[("TERM", "xterm")]), ColorSupport::Basic);
+        assert_eq!(detect(&[("TERM", "linux")]), ColorSupport::Basic);
+        assert_eq!(detect(&[("TERM", "vt100")]), ColorSupport::None);
+        assert_eq!(detect(&[("TERM", "xterm-mono")]), ColorSupport::None);
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("NO_COLOR", "1")]), ColorSupport::None);
+    }
+
+    #[test]
+    fn colors_map_to_closest_supported() {
+        let orange = Color::Rgb(255, 136, 0);
+        assert_eq!(limit_color(orange, ColorSupport::TrueColor), orange);
+        assert_eq!(limit_color(orange, ColorSupport::Indexed), Color::Indexed(208));
+        assert_eq!(limit_color(orange, ColorSupport::Basic), Color::Yellow);
+        assert_eq!(limit_color(Color::Indexed(208), ColorSupport::Basic), Color::Yellow);
+        assert_eq!(limit_color(Color::Rgb(30, 30, 30), ColorSupport::Basic), Color::Black);
+        assert_eq!(limit_color(Color::Rgb(250, 250, 250), ColorSupport::Indexed), Color::Indexed(231));
+        // Named colors are shown by every terminal with colors
+        assert_eq!(limit_color(Color::LightBlue, ColorSupport::Basic), Color::LightBlue);
+        assert_eq!(limit_color(Color::LightBlue, ColorSupport::None), Color::Reset);
+    }
+}
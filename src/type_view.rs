@@ -0,0 +1,57 @@
+use crate::analysis::{self, FileEntry, TypeUsage};
+use crate::file_kind::{self, FileKind};
+use crate::App;
+
+/// Extensions the file type breakdown keeps, the largest ones.
+const TOP_EXTENSIONS: usize = 20;
+
+/// Space the files of a full scan take up by type, shown in the bottom panel: per kind of
+/// file (videos, images, archives, ...) and for the largest extensions.
+#[derive(Debug, Clone, Default)]
+pub struct TypeBreakdown {
+    pub kinds: Vec<TypeUsage<FileKind>>,
+    /// The largest extensions, `None` standing for files without one
+    pub extensions: Vec<TypeUsage<Option<String>>>,
+    /// Total size of the files
+    pub total: u64,
+}
+
+impl TypeBreakdown {
+    pub fn new(files: &[FileEntry]) -> TypeBreakdown {
+        let kinds = analysis::usage_by(files, |file| {
+            Some(if file.link_target.is_some() { FileKind::Symlink } else { FileKind::of(&file.path, false) })
+        });
+        let mut extensions = analysis::usage_by(files, |file| Some(file_kind::extension(&file.path)));
+        extensions.truncate(TOP_EXTENSIONS);
+        let total = kinds.iter().map(|kind| kind.size).sum();
+        TypeBreakdown { kinds, extensions, total }
+    }
+}
+
+impl App {
+    /// Whether the bottom panel shows the file type breakdown of the full scan.
+    pub fn type_breakdown_shown(&self) -> bool {
+        self.type_breakdown.is_some() && self.full_scan_shown()
+    }
+
+    /// Show or hide the file type breakdown. Returns false if there is no finished full scan
+    /// to break down.
+    pub fn toggle_type_breakdown(&mut self) -> bool {
+        if self.type_breakdown_shown() {
+            self.type_breakdown = None;
+            return true;
+        }
+        let Some(files) = self.full_scan_results.as_deref().filter(|_| self.full_scan_shown()) else {
+            return false;
+        };
+        self.type_breakdown = Some(TypeBreakdown::new(files));
+        true
+    }
+
+    /// Break down the files again, after the scan results changed.
+    pub fn refresh_type_breakdown(&mut self) {
+        if let (Some(breakdown), Some(files)) = (self.type_breakdown.as_mut(), self.full_scan_results.as_deref()) {
+            *breakdown = TypeBreakdown::new(files);
+        }
+    }
+}
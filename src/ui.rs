@@ -3,10 +3,22 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Row, Table, Clear},
-    Terminal,
+    widgets::{Block, Borders, Cell, Gauge, List, ListItem, ListState, Paragraph, Row, Table, Clear},
+    Frame, Terminal,
 };
 use crate::{App, AppMode};
+use crate::config::{Action, BatteryPolicy, KeyBindings, LayoutConfig};
+use crate::file_kind::FileKind;
+use crate::help;
+use crate::analysis::{self, ChangeKind, CostModel};
+use crate::format::{format_cost, format_count, format_depth, format_duration, format_size};
+use crate::operations::{confirmation_name, strong_confirmation, Operation, OperationState};
+use crate::safety::Safety;
+use crate::platform::macos::{distinct_space, StorageDevice};
+use crate::storage::file_ops::FileOpJob;
+use crate::storage::profile;
+use crate::theme::Theme;
+use crate::type_view::TypeBreakdown;
 
 /// Compute a centered rectangle for popup overlays.
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -34,99 +46,911 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
+/// Where the panels of the main screen are.
+pub struct ScreenLayout {
+    /// Whether this is the compact layout of a narrow terminal
+    pub compact: bool,
+    pub header: Rect,
+    /// Only has a height with more than one tab open
+    pub tab_bar: Rect,
+    pub devices: Rect,
+    pub details: Rect,
+    pub gauge: Rect,
+    pub file_list: Rect,
+    /// Only there with the preview pane on
+    pub preview: Option<Rect>,
+    pub progress: Rect,
+    pub legend: Rect,
+}
+
+/// Terminals narrower than this get the compact layout.
+const COMPACT_WIDTH: u16 = 100;
+
+/// Split the screen into its panels. Also used to find what a mouse click hits.
+///
+/// Terminals narrower than `COMPACT_WIDTH` get a compact layout instead: the left panel is
+/// stacked above the right one, with the device list next to the details, and the legend is a
+/// single line.
+pub fn screen_layout(size: Rect, app: &App) -> ScreenLayout {
+    let LayoutConfig { device_panel, progress_panel } = app.layout;
+    let compact = size.width < COMPACT_WIDTH;
+    // Outer layout: overview header, tab bar, main area and bottom legend.
+    let tab_rows = if app.tabs.len() > 1 { 1 } else { 0 };
+    let outer_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [Constraint::Length(1), Constraint::Length(tab_rows), Constraint::Min(0), Constraint::Length(if compact { 1 } else { 3 })].as_ref(),
+        )
+        .split(size);
+    // Main area: left panel (30% by default) and right panel, side by side or stacked.
+    let main_chunks = Layout::default()
+        .direction(if compact { Direction::Vertical } else { Direction::Horizontal })
+        .constraints([Constraint::Percentage(device_panel), Constraint::Percentage(100 - device_panel)].as_ref())
+        .split(outer_chunks[2]);
+
+    // Split right panel into top (file listing) and bottom (scan progress, 30% by default)
+    let right_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(100 - progress_panel), Constraint::Percentage(progress_panel)].as_ref())
+        .split(main_chunks[1]);
+    // With the preview pane on, the file listing shares the top of the right panel with it
+    let (file_list, preview) = if app.preview_pane {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(right_chunks[0]);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (right_chunks[0], None)
+    };
+    // Left panel: split into two parts, stacked (side by side when compact).
+    // First: device list; second: split further into device details (80%) and progress bar (20%).
+    // When compact, the bar gets just its three rows, and only if that leaves room for details.
+    let left_chunks = Layout::default()
+        .direction(if compact { Direction::Horizontal } else { Direction::Vertical })
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(main_chunks[0]);
+    let gauge_height = match compact {
+        true if left_chunks[1].height >= 9 => Constraint::Length(3),
+        true => Constraint::Length(0),
+        false => Constraint::Percentage(20),
+    };
+    let details_and_gauge = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), gauge_height].as_ref())
+        .split(left_chunks[1]);
+
+    ScreenLayout {
+        compact,
+        header: outer_chunks[0],
+        tab_bar: outer_chunks[1],
+        devices: left_chunks[0],
+        details: details_and_gauge[0],
+        gauge: details_and_gauge[1],
+        file_list,
+        preview,
+        progress: right_chunks[1],
+        legend: outer_chunks[3],
+    }
+}
+
+/// Path of `entry` relative to the device it is on, for the compact layout, which has no path
+/// column.
+fn relative_name(entry: &crate::scanner::FileEntry, mount_point: &str) -> String {
+    match std::path::Path::new(&entry.path).strip_prefix(mount_point) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_string_lossy().into_owned(),
+        _ => entry.name.clone(),
+    }
+}
+
+/// Color of an entry of a file listing, by its kind; `None` leaves other files uncolored.
+fn kind_color(colors: &Theme, kind: FileKind) -> Option<Color> {
+    match kind {
+        FileKind::Directory => Some(colors.directory),
+        FileKind::Symlink => Some(colors.accent),
+        FileKind::Image => Some(colors.image),
+        FileKind::Video => Some(colors.video),
+        FileKind::Audio => Some(colors.audio),
+        FileKind::Archive => Some(colors.archive),
+        FileKind::Code => Some(colors.code),
+        FileKind::Document => Some(colors.document),
+        FileKind::Other => None,
+    }
+}
+
+/// Name cell of a file listing: the name with the icon of its kind in front if icons are on,
+/// in the color of its kind unless the row is highlighted.
+fn name_cell(app: &App, name: String, kind: FileKind, row_style: Style, highlighted: bool) -> Span<'static> {
+    let text = if app.config.display.icons { format!("{} {}", kind.icon(), name) } else { name };
+    let style = match kind_color(&app.theme, kind) {
+        Some(color) if !highlighted => row_style.fg(color),
+        _ => row_style,
+    };
+    Span::styled(text, style)
+}
+
+/// Size of a scanned entry, followed by a badge of how much it changed since the results the
+/// last rescan replaced, e.g. `1.20 GB ▲ +300.00 MB`. Growth takes the `bad` color.
+fn size_cell(app: &App, path: &str, size: String, style: Style) -> Cell<'static> {
+    let mut spans = vec![Span::styled(size, style)];
+    if let Some(delta) = app.size_deltas.as_ref().and_then(|deltas| deltas.get(path)) {
+        let units = app.config.display.size_units;
+        let (badge, color) = if delta > 0 {
+            (format!(" ▲ +{}", format_size(delta as u64, units)), app.theme.bad)
+        } else {
+            (format!(" ▼ -{}", format_size(delta.unsigned_abs(), units)), app.theme.good)
+        };
+        spans.push(Span::styled(badge, style.fg(color)));
+    }
+    Cell::from(Spans::from(spans))
+}
+
+/// Share of `total` that `size` makes up, as a bar and percentage: `[####      ]  41.7%`.
+fn usage_bar(size: u64, total: u64) -> String {
+    let share = if total == 0 { 0.0 } else { size as f64 / total as f64 };
+    let filled = ((share * 10.0).round() as usize).min(10);
+    format!("[{}{}] {:>5.1}%", "#".repeat(filled), " ".repeat(10 - filled), share * 100.0)
+}
+
+/// Separator between the segments of the breadcrumb path bar.
+const BREADCRUMB_SEPARATOR: &str = " › ";
+
+/// Labels of the segments of the breadcrumb path bar: the device's mount point, then the name
+/// of each directory below it.
+fn breadcrumb_labels(app: &App) -> Vec<String> {
+    app.breadcrumbs()
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| match std::path::Path::new(&path).file_name() {
+            Some(name) if i > 0 => name.to_string_lossy().into_owned(),
+            _ => path,
+        })
+        .collect()
+}
+
+/// Start of the file listing's title once it is scrolled down.
+fn scrolled_indicator(app: &App) -> &'static str {
+    if app.file_list_offset > 0 { "↟ " } else { "" }
+}
+
+/// The breadcrumb path bar as the title of the file listing, with the segment at `highlighted`
+/// picked out. Empty unless a subdirectory is listed.
+fn breadcrumb_spans(app: &App, highlighted: Option<usize>) -> Vec<Span<'static>> {
+    let labels = breadcrumb_labels(app);
+    if labels.is_empty() {
+        return Vec::new();
+    }
+    let mut spans = vec![Span::raw("[ ")];
+    for (i, label) in labels.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(BREADCRUMB_SEPARATOR));
+        }
+        let style = if highlighted == Some(i) {
+            Style::default().fg(app.theme.highlight).add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::styled(label, style));
+    }
+    spans.push(Span::raw(" ]"));
+    spans
+}
+
+/// The segment of the breadcrumb path bar at `column` of the top border of the file listing
+/// in `area`, if any.
+pub fn breadcrumb_at(app: &App, area: Rect, column: u16) -> Option<usize> {
+    // The title starts after the corner of the border
+    let mut start = area.x + 1 + Span::raw(scrolled_indicator(app)).width() as u16;
+    let spans = breadcrumb_spans(app, None);
+    for (i, span) in spans.iter().enumerate() {
+        let end = start + span.width() as u16;
+        // Segments alternate with separators between the brackets
+        if i % 2 == 1 && (start..end).contains(&column) {
+            return Some(i / 2);
+        }
+        start = end;
+    }
+    None
+}
+
+/// The legend as one abbreviated line, for the compact layout: the prompt being typed, or the
+/// main keys followed by the status the full legend shows in its title.
+fn compact_legend(app: &App, mode: &AppMode, keys: &KeyBindings) -> String {
+    // The first key of each action, with Ctrl written as ^
+    let key = |action| keys.keys(action).first().map_or(String::new(), |binding| binding.to_string().replace("Ctrl-", "^"));
+    match mode {
+        AppMode::Command { input } => format!(":{}█", input),
+        AppMode::Rename { input, .. } => format!("Rename to: {}█ (Enter/Esc)", input),
+        AppMode::Archive { input, .. } => format!("Archive into: {}█ (Tab/Enter/Esc)", input),
+        AppMode::Extract { input, .. } => format!("Extract into: {}█ (Enter/Esc)", input),
+        AppMode::Breadcrumbs { .. } => format!("Jump to: ←/→ choose  {} go  Esc cancel", key(Action::OpenFolder)),
+        _ => {
+            let mut line = format!(
+                "{}/{} move  {}/{} panels  {} help  {} quit",
+                key(Action::Down),
+                key(Action::Up),
+                key(Action::FocusLeft),
+                key(Action::FocusRight),
+                key(Action::Help),
+                key(Action::Quit)
+            );
+            if app.on_battery {
+                line.push_str(" | battery");
+            }
+            let (running, pending) = app.operations.counts();
+            if running + pending > 0 {
+                line.push_str(&format!(" | ops {} running, {} queued", running, pending));
+            }
+            if let Some(notification) = &app.notification {
+                line.push_str(" | ");
+                line.push_str(notification);
+            }
+            line
+        }
+    }
+}
+
+/// Help overlay text, grouped by section and listing the currently bound keys.
+fn help_text(keys: &KeyBindings) -> String {
+    let sections: [(&str, &[Action]); 5] = [
+        ("Navigation", &[
+            Action::Down,
+            Action::Up,
+            Action::FocusLeft,
+            Action::FocusRight,
+            Action::WidenDevicePanel,
+            Action::NarrowDevicePanel,
+            Action::GrowProgressPanel,
+            Action::ShrinkProgressPanel,
+            Action::Help,
+        ]),
+        ("Tabs", &[Action::NewTab, Action::CloseTab, Action::NextTab, Action::PreviousTab]),
+        ("Device Operations", &[Action::Refresh, Action::SearchDevices, Action::Eject, Action::EjectAll, Action::Unmount, Action::Mount, Action::EmptyTrash]),
+        ("File Operations (when right panel is focused)", &[
+            Action::QuickScan,
+            Action::FullScan,
+            Action::IncrementalScan,
+            Action::ToggleGitignore,
+            Action::Delete,
+            Action::SecureDelete,
+            Action::Copy,
+            Action::Move,
+            Action::Rename,
+            Action::Archive,
+            Action::Extract,
+            Action::Reveal,
+            Action::OpenFile,
+            Action::QuickLook,
+            Action::TogglePreview,
+            Action::ToggleCost,
+            Action::ToggleAllocated,
+            Action::Operations,
+            Action::Undo,
+            Action::ToggleFolderView,
+            Action::OpenFolder,
+            Action::ParentDir,
+            Action::Back,
+            Action::Forward,
+            Action::Breadcrumbs,
+            Action::ToggleCategoryView,
+            Action::ToggleCategory,
+            Action::DevScan,
+            Action::ToggleProject,
+            Action::CleanArtifacts,
+            Action::DockerPrune,
+            Action::ClearBrowserCache,
+            Action::JunkSuggestions,
+            Action::AcceptSuggestion,
+        ]),
+        ("General", &[Action::Pause, Action::Cancel, Action::Quit]),
+    ];
+
+    let mut text = String::from("\n            LAZYSMG KEYBOARD SHORTCUTS\n");
+    for (title, actions) in sections {
+        text.push_str(&format!("\n{}:\n{}\n", title, "-".repeat(title.len() + 1)));
+        for action in actions {
+            text.push_str(&format!("{:<14}: {}\n", keys.label(*action), action.description()));
+        }
+    }
+    text
+}
+
+/// Help lines matching a search typed into the help overlay.
+fn help_search_text(keys: &KeyBindings, query: &str) -> String {
+    let entries = help::search(keys, query);
+    if entries.is_empty() {
+        return format!("\nNothing matches \"{}\"", query.trim());
+    }
+    let mut text = String::from("\n");
+    for entry in entries {
+        text.push_str(&format!("{:<8} {:<24}: {}\n", entry.kind.label(), entry.name, entry.description));
+    }
+    text
+}
+
+/// Percentage of a copy, move or delete done so far.
+fn job_percent(job: &FileOpJob) -> u16 {
+    let (done, total) = job.progress();
+    (done * 100).checked_div(total).unwrap_or(0).min(100) as u16
+}
+
+/// Time a copy, move or delete still needs at its speed so far, or "-" if unknown.
+fn job_eta(job: &FileOpJob) -> String {
+    let (done, total) = job.progress();
+    let speed = job.speed();
+    if speed > 0.0 && total > done {
+        format_duration(std::time::Duration::from_secs_f64((total - done) as f64 / speed))
+    } else {
+        "-".to_string()
+    }
+}
+
+/// Most operations shown with a gauge each; with more, one gauge sums them all up.
+const MAX_OPERATION_GAUGES: usize = 3;
+
+/// One row of the operation gauges. `bar` is the percentage and its label, or None while the
+/// operation waits in the queue.
+struct GaugeRow {
+    label: String,
+    bar: Option<(u16, String)>,
+    stats: String,
+}
+
+/// Progress of the running and queued file operations: a compact row per operation (what it
+/// does, a bar, its speed and time left), or a single summary row when there are many.
+fn draw_operation_gauges<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App, active: &[&Operation]) {
+    let colors = &app.theme;
+    let keys = &app.config.keys;
+    let units = app.config.display.size_units;
+
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "[ Operations ] {} = list, then {} = cancel",
+        keys.label(Action::Operations),
+        keys.label(Action::Cancel)
+    ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let jobs: Vec<&FileOpJob> = active.iter().filter_map(|op| op.job.as_deref()).collect();
+    // One row per operation, or a single summary row
+    let rows: Vec<GaugeRow> = if active.len() > MAX_OPERATION_GAUGES {
+        let (done, total) = jobs.iter().fold((0, 0), |(done, total), job| {
+            let (job_done, job_total) = job.progress();
+            (done + job_done, total + job_total)
+        });
+        let speed: f64 = jobs.iter().map(|job| job.speed()).sum();
+        let percent = (done * 100).checked_div(total).unwrap_or(0).min(100) as u16;
+        let (running, pending) = app.operations.counts();
+        vec![GaugeRow {
+            label: format!("{} running, {} queued", running, pending),
+            bar: Some((percent, format!("{} / {}", format_size(done, units), format_size(total, units)))),
+            stats: format!("{}/s", format_size(speed as u64, units)),
+        }]
+    } else {
+        active
+            .iter()
+            .map(|op| {
+                let name = std::path::Path::new(&op.source)
+                    .file_name()
+                    .map_or_else(|| op.source.clone(), |name| name.to_string_lossy().into_owned());
+                let label = format!("#{} {} {}", op.id, op.label(), name);
+                match (&op.state, &op.job) {
+                    (OperationState::Running, Some(job)) => GaugeRow {
+                        label,
+                        bar: Some((job_percent(job), format!("{}%", job_percent(job)))),
+                        stats: format!("{}/s, ETA {}", format_size(job.speed() as u64, units), job_eta(job)),
+                    },
+                    _ => GaugeRow { label, bar: None, stats: "queued".to_string() },
+                }
+            })
+            .collect()
+    };
+
+    let row_areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(rows.iter().map(|_| Constraint::Length(1)).chain([Constraint::Min(0)]).collect::<Vec<_>>())
+        .split(inner);
+    for (row, row_area) in rows.into_iter().zip(row_areas.iter()) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(40), Constraint::Percentage(25)].as_ref())
+            .split(*row_area);
+        f.render_widget(Paragraph::new(row.label), columns[0]);
+        match row.bar {
+            Some((percent, gauge_label)) => {
+                let gauge = Gauge::default()
+                    .gauge_style(Style::default().fg(colors.scan_gauge).bg(colors.gauge_bg))
+                    .percent(percent)
+                    .label(Span::raw(gauge_label));
+                f.render_widget(gauge, columns[1]);
+            }
+            None => f.render_widget(Paragraph::new("waiting").style(Style::default().add_modifier(Modifier::DIM)), columns[1]),
+        }
+        f.render_widget(Paragraph::new(format!(" {}", row.stats)), columns[2]);
+    }
+}
+
+/// What a bulk clean of build artifacts (or Xcode data) would remove.
+fn clean_preview_text(app: &App) -> String {
+    let units = app.config.display.size_units;
+    if app.xcode_view_active() {
+        let items = app.selected_xcode_items();
+        let freed: u64 = items.iter().map(|item| item.size).sum();
+        let mut text = format!("Remove {} Xcode items?\n\nFrees: {}\n\n", items.len(), format_size(freed, units));
+        for item in &items {
+            text.push_str(&format!("  {}: {} ({})\n", item.kind.label(), item.name, format_size(item.size, units)));
+        }
+        text.push_str("\nDerivedData and device support are recreated by Xcode when needed; archives are gone for good.\nThis action cannot be undone!\n\nPress Y to confirm, N to cancel.");
+        return text;
+    }
+
+    let projects = app.selected_projects();
+    let freed: u64 = projects.iter().map(|project| project.total_size()).sum();
+
+    let mut text = format!(
+        "Remove the build artifacts of {} projects?\n\nFrees: {}\n\n",
+        projects.len(),
+        format_size(freed, units)
+    );
+    for project in &projects {
+        text.push_str(&format!("  {}  {} ({})\n", project.path, project.kinds(), format_size(project.total_size(), units)));
+    }
+    text.push_str("\nA directory is only removed if its project file (package.json, Cargo.toml, ...) is still next to it.\nThis action cannot be undone!\n\nPress Y to confirm, N to cancel.");
+    text
+}
+
+/// Diff lines shown in the overwrite confirmation; the rest are counted.
+const MAX_DIFF_LINES_SHOWN: usize = 30;
+
+/// Confirmation text for a copy or move of `source` onto the existing `target`: both versions'
+/// size and modification time, and how the text changes, so the user can pick which to keep.
+fn overwrite_text(app: &App, source: &str, target: &str) -> Option<String> {
+    use crate::diff::{is_newer, DiffLine};
+
+    let units = app.config.display.size_units;
+    let preview = app.overwrite_preview.as_ref().filter(|preview| preview.target == target)?;
+    let version = |label: &str, version: &crate::diff::FileVersion, other: &crate::diff::FileVersion| {
+        format!(
+            "{}: {}, modified {}{}\n",
+            label,
+            format_size(version.size, units),
+            version.modified_label(),
+            if is_newer(version, other) { " (newer)" } else { "" }
+        )
+    };
+    let mut text = format!("{}\nwould replace {}, which already exists.\n\n", source, target);
+    text.push_str(&version("Existing", &preview.existing, &preview.incoming));
+    text.push_str(&version("Incoming", &preview.incoming, &preview.existing));
+    text.push('\n');
+    match &preview.diff {
+        Ok(_) if preview.identical() => text.push_str("The contents are identical.\n"),
+        Ok(lines) => {
+            text.push_str("Changes (- existing, + incoming):\n");
+            for line in lines.iter().take(MAX_DIFF_LINES_SHOWN) {
+                match line {
+                    DiffLine::Same(line) => text.push_str(&format!("  {}\n", line)),
+                    DiffLine::Removed(line) => text.push_str(&format!("- {}\n", line)),
+                    DiffLine::Added(line) => text.push_str(&format!("+ {}\n", line)),
+                    DiffLine::Skipped(count) => text.push_str(&format!("  ... {} unchanged lines\n", count)),
+                }
+            }
+            if lines.len() > MAX_DIFF_LINES_SHOWN {
+                text.push_str(&format!("  ... {} more lines\n", lines.len() - MAX_DIFF_LINES_SHOWN));
+            }
+        }
+        Err(reason) => text.push_str(&format!("{}.\n", reason)),
+    }
+    text.push_str("\nPress Y to replace it, N to keep the existing file.");
+    Some(text)
+}
+
+/// Space freed by a pending delete and how each enclosing directory would shrink.
+fn deletion_preview_text(app: &App, target: &str) -> String {
+    let units = app.config.display.size_units;
+    let Some(impact) = app.delete_preview.as_ref().filter(|impact| impact.target == target) else {
+        return "\nCalculating size...\n".to_string();
+    };
+
+    let mut text = format!(
+        "Frees: {} (once the trash is emptied)\n\nDirectories that would shrink:\n",
+        format_size(impact.freed, units)
+    );
+    for ancestor in &impact.ancestors {
+        match (ancestor.before, ancestor.after(impact.freed)) {
+            (Some(before), Some(after)) => text.push_str(&format!(
+                "  {}  {} -> {} (-{})\n",
+                ancestor.path,
+                format_size(before, units),
+                format_size(after, units),
+                format_size(impact.freed, units)
+            )),
+            _ => text.push_str(&format!("  {}  -{}\n", ancestor.path, format_size(impact.freed, units))),
+        }
+    }
+    text
+}
+
+/// Warning about processes that have `target` open, for its delete confirmation. Empty if none
+/// do, or if that can't be found out.
+fn open_files_text(app: &App, target: &str) -> String {
+    match app.delete_holders.as_ref().filter(|(path, _)| path == target) {
+        Some((_, Some(holders))) if !holders.is_empty() => format!(
+            "\nWarning: open in {}. Its space is only freed once they close it.\n",
+            holders.join(", ")
+        ),
+        Some(_) => String::new(),
+        None if app.delete_holders_rx.is_some() => "\nChecking whether any process has it open...\n".to_string(),
+        None => String::new(),
+    }
+}
+
+/// File type breakdown of the full scan: the space each kind of file takes up next to the
+/// largest extensions, with their share of the scanned files as bars.
+fn draw_type_breakdown<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App, breakdown: &TypeBreakdown) {
+    let colors = &app.theme;
+    let units = app.config.display.size_units;
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    let widths = [Constraint::Percentage(35), Constraint::Length(18), Constraint::Percentage(25)];
+
+    let files: usize = breakdown.kinds.iter().map(|kind| kind.file_count).sum();
+    let mut title = format!("[ Space by Type ] {} in {} files", format_size(breakdown.total, units), format_count(files as u64));
+    if app.full_scan_totals.is_some() {
+        // Scans bounded by max_files only kept their largest files
+        title.push_str(", largest only");
+    }
+    let rows: Vec<Row> = breakdown.kinds.iter().map(|kind| {
+        let style = kind_color(colors, kind.kind).map_or_else(Style::default, |color| Style::default().fg(color));
+        Row::new(vec![
+            Span::styled(kind.kind.label(), style),
+            Span::raw(usage_bar(kind.size, breakdown.total)),
+            Span::raw(format_size(kind.size, units)),
+        ])
+    }).collect();
+    let table = Table::new(rows)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&widths);
+    f.render_widget(table, halves[0]);
+
+    let rows: Vec<Row> = breakdown.extensions.iter().map(|extension| {
+        let label = extension.kind.as_ref().map_or_else(|| "(none)".to_string(), |ext| format!(".{}", ext));
+        Row::new(vec![
+            Span::raw(label),
+            Span::raw(usage_bar(extension.size, breakdown.total)),
+            Span::raw(format_size(extension.size, units)),
+        ])
+    }).collect();
+    let table = Table::new(rows)
+        .block(Block::default().borders(Borders::ALL).title("[ Top Extensions ]"))
+        .widths(&widths);
+    f.render_widget(table, halves[1]);
+}
+
+/// Preview pane: the head of a text file with basic highlighting, an image's dimensions and
+/// EXIF summary, a hex dump of a binary file, the entries of a directory or what a handler's
+/// preview command printed.
+#[cfg(feature = "preview")]
+fn draw_preview<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    use crate::preview::{highlight, PreviewContent, Token};
+
+    let colors = &app.theme;
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let selected = app.previewed_path();
+    let preview = app.preview.as_ref().filter(|preview| selected.as_ref() == Some(&preview.path));
+    let mut title = "[ Preview ]".to_string();
+    let lines: Vec<Spans> = match (selected.as_ref(), preview) {
+        (None, _) => vec![Spans::from(Span::styled("Nothing to preview", dim))],
+        (Some(_), None) => vec![Spans::from(Span::styled("Loading...", dim))],
+        (Some(path), Some(preview)) => {
+            let name = std::path::Path::new(path).file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+            title = format!("[ Preview: {} ]", name);
+            match &preview.content {
+                PreviewContent::Text { syntax, lines, truncated } => {
+                    let mut text: Vec<Spans> = lines
+                        .iter()
+                        .map(|line| {
+                            let spans: Vec<Span> = highlight(line, *syntax)
+                                .into_iter()
+                                .map(|(token, piece)| {
+                                    let style = match token {
+                                        Token::Plain => Style::default(),
+                                        Token::Keyword => Style::default().fg(colors.accent),
+                                        Token::String => Style::default().fg(colors.string),
+                                        Token::Number => Style::default().fg(colors.number),
+                                        Token::Comment => dim,
+                                    };
+                                    Span::styled(piece.to_string(), style)
+                                })
+                                .collect();
+                            Spans::from(spans)
+                        })
+                        .collect();
+                    if *truncated {
+                        text.push(Spans::from(Span::styled("...", dim)));
+                    }
+                    text
+                }
+                PreviewContent::Image { format, width, height, exif } => {
+                    let mut text = vec![Spans::from(format!("{} image, {} × {} pixels", format, width, height))];
+                    if !exif.is_empty() {
+                        text.push(Spans::from(""));
+                    }
+                    for (label, value) in exif {
+                        text.push(Spans::from(vec![Span::styled(format!("{}: ", label), Style::default().fg(colors.header)), Span::raw(value.clone())]));
+                    }
+                    text
+                }
+                PreviewContent::Binary { lines } => std::iter::once(Spans::from(Span::styled("Binary file", dim)))
+                    .chain(lines.iter().map(|line| Spans::from(line.clone())))
+                    .collect(),
+                PreviewContent::Directory { entries, total } => std::iter::once(Spans::from(Span::styled(format!("{} entries", total), dim)))
+                    .chain(entries.iter().map(|entry| Spans::from(entry.clone())))
+                    .collect(),
+                PreviewContent::Output { command, lines, truncated } => {
+                    let mut text: Vec<Spans> = std::iter::once(Spans::from(Span::styled(format!("$ {}", command), dim)))
+                        .chain(lines.iter().map(|line| Spans::from(line.clone())))
+                        .collect();
+                    if *truncated {
+                        text.push(Spans::from(Span::styled("...", dim)));
+                    }
+                    text
+                }
+                PreviewContent::Unavailable(reason) => vec![Spans::from(Span::styled(reason.clone(), dim))],
+            }
+        }
+    };
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(paragraph, area);
+}
+
+/// One-line summary of the internal storage for the header: capacity, used and free space (data
+/// shown by several mounts counted once), junk found by the last junk scan and the trash.
+fn storage_overview(app: &App) -> Spans<'static> {
+    let units = app.config.display.size_units;
+    let internal: Vec<&StorageDevice> = app.devices.iter().filter(|device| device.mounted && !device.ejectable).collect();
+    let (total, free) = distinct_space(internal.iter().copied());
+    let used = total.saturating_sub(free);
+    let trash: u64 = internal.iter().filter(|device| device.alias_of.is_none()).filter_map(|device| device.trash_size).sum();
+    let junk = app.junk_categories.as_ref().map(|categories| {
+        categories
+            .iter()
+            .filter(|category| !app.excluded_categories.contains(&category.id))
+            .map(|category| category.total_size)
+            .sum::<u64>()
+    });
+    let label = Style::default().fg(app.theme.header);
+    let percent = (used * 100).checked_div(total).unwrap_or(0);
+    Spans::from(vec![
+        Span::styled(" Internal storage ", label.add_modifier(Modifier::BOLD)),
+        Span::raw(format!("{} total", format_size(total, units))),
+        Span::styled("  Used ", label),
+        Span::raw(format!("{} ({}%)", format_size(used, units), percent)),
+        Span::styled("  Free ", label),
+        Span::raw(format_size(free, units)),
+        Span::styled("  Junk ", label),
+        Span::raw(junk.map_or("not scanned".to_string(), |junk| format!("{} reclaimable", format_size(junk, units)))),
+        Span::styled("  Trash ", label),
+        Span::raw(format_size(trash, units)),
+    ])
+}
+
+/// What is left in the scrollback when lazysmg quits: the storage overview of the header, then
+/// what the session scanned and freed, or the selected device if it did neither.
+pub fn exit_summary(app: &App) -> String {
+    let units = app.config.display.size_units;
+    let overview: String = storage_overview(app).0.iter().map(|span| span.content.as_ref()).collect();
+    let mut lines = vec![format!("lazysmg:{}", overview)];
+    if !app.session.is_empty() {
+        lines.extend(app.session.lines(units));
+    } else if let Some(device) = app.selected_device().filter(|device| device.mounted) {
+        lines.push(format!(
+            "{} ({}): {} used of {}, {} free",
+            device.name,
+            device.mount_point,
+            format_size(device.total_space.saturating_sub(device.available_space), units),
+            format_size(device.total_space, units),
+            format_size(device.available_space, units)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// One line with the open tabs, numbered for the keys that switch to them; the current one is
+/// highlighted.
+fn tab_bar(app: &App) -> Spans<'static> {
+    let highlight = Style::default().fg(app.theme.highlight).add_modifier(Modifier::BOLD | Modifier::REVERSED);
+    let spans: Vec<Span> = app
+        .tab_labels()
+        .into_iter()
+        .enumerate()
+        .map(|(index, label)| {
+            let text = format!(" {} {} ", index + 1, label);
+            if index == app.active_tab {
+                Span::styled(text, highlight)
+            } else {
+                Span::styled(text, Style::default().add_modifier(Modifier::DIM))
+            }
+        })
+        .collect();
+    Spans::from(spans)
+}
+
 pub fn draw_app<B: Backend>(
     terminal: &mut Terminal<B>,
     app: &App,
     mode: &AppMode,
     spinner_chars: &[&str],
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let colors = &app.theme;
+    let keys = &app.config.keys;
+    let units = app.config.display.size_units;
+    let cost_model = app.cost_model();
+    let currency = &app.config.cost.currency;
     terminal.draw(|f| {
         let size = f.size();
-        // Outer layout: main area and bottom legend.
-        let outer_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
-            .split(size);
-        // Main area: left panel (30%) and right panel (70%).
-        let main_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
-            .split(outer_chunks[0]);
-
-        // Split right panel into top (file listing) and bottom (scan progress)
-        let right_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
-            .split(main_chunks[1]);
-        // Left panel: split vertically into two parts.
-        // Top: device list; Bottom: split further into device details (70%) and progress bar (30%).
-        let left_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-            .split(main_chunks[0]);
-        let details_and_gauge = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
-            .split(left_chunks[1]);
-
-        // Left panel: Device list.
+        let layout = screen_layout(size, app);
+        f.render_widget(Paragraph::new(storage_overview(app)), layout.header);
+        if app.tabs.len() > 1 {
+            f.render_widget(Paragraph::new(tab_bar(app)), layout.tab_bar);
+        }
+        let (list_area, preview_area) = (layout.file_list, layout.preview);
+
+        // Left panel: Device list. During a type-ahead search, matched characters are
+        // highlighted and devices that don't match are dimmed.
+        let search_query = match mode {
+            AppMode::DeviceSearch { query, .. } if !query.is_empty() => Some(query.as_str()),
+            _ => None,
+        };
         let items: Vec<ListItem> = app
             .devices
             .iter()
-            .enumerate()
-            .map(|(_i, dev)| {
-                let mut text = dev.name.clone();
+            .map(|dev| {
+                let mut spans: Vec<Span> = match search_query.map(|query| crate::fuzzy::fuzzy_match(query, &dev.name)) {
+                    Some(Some((_, positions))) => dev
+                        .name
+                        .chars()
+                        .enumerate()
+                        .map(|(i, c)| {
+                            if positions.contains(&i) {
+                                Span::styled(c.to_string(), Style::default().fg(colors.accent).add_modifier(Modifier::UNDERLINED))
+                            } else {
+                                Span::raw(c.to_string())
+                            }
+                        })
+                        .collect(),
+                    Some(None) => vec![Span::styled(dev.name.clone(), Style::default().add_modifier(Modifier::DIM))],
+                    None => vec![Span::raw(dev.name.clone())],
+                };
+                // Attached volumes that aren't mounted are dimmed as well
+                if !dev.mounted {
+                    for span in &mut spans {
+                        span.style = span.style.add_modifier(Modifier::DIM);
+                    }
+                    spans.push(Span::styled(" (not mounted)", Style::default().add_modifier(Modifier::DIM)));
+                }
+                // Mounts showing the same data as another device
+                if let Some(original) = &dev.alias_of {
+                    spans.push(Span::styled(format!(" (alias of {})", original), Style::default().add_modifier(Modifier::DIM)));
+                }
                 if dev.ejectable {
-                    text = format!("{} ⏏", dev.name);
+                    spans.push(Span::raw(" ⏏"));
                 }
-                ListItem::new(Spans::from(text))
+                ListItem::new(Spans::from(spans))
             })
             .collect();
 
         // Set different block style based on focus
         let devices_block_style = if app.focus == crate::PanelFocus::Left {
-            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+            Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
         } else {
             Style::default()
         };
 
+        let devices_title = match mode {
+            AppMode::DeviceSearch { query, .. } => format!("[ Devices ] /{}", query),
+            _ => "[ Devices ]".to_string(),
+        };
         let list = List::new(items)
             .block(Block::default()
                 .borders(Borders::ALL)
-                .title("[ Devices ]")
+                .title(devices_title)
                 .border_style(devices_block_style))
-            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            .highlight_style(Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD))
             .highlight_symbol(">> ");
         let mut list_state = ListState::default();
         list_state.select(Some(app.selected));
-        f.render_stateful_widget(list, left_chunks[0], &mut list_state);
+        f.render_stateful_widget(list, layout.devices, &mut list_state);
 
         // Left panel: Device details.
-        let device_details = if !app.devices.is_empty() {
-            let device = &app.devices[app.selected];
+        let device_details = if let Some(device) = app.selected_device() {
             let total_gb = device.total_space as f64 / 1024_f64.powi(3);
             let free_gb = device.available_space as f64 / 1024_f64.powi(3);
-            let mut info = format!(
-                "Name: {}\nMount: {}\nTotal: {:.2} GB\nFree: {:.2} GB",
-                device.name, device.mount_point, total_gb, free_gb
-            );
+            let mut info = if device.mounted {
+                format!(
+                    "Name: {}\nMount: {}\nTotal: {:.2} GB\nFree: {:.2} GB",
+                    device.name, device.mount_point, total_gb, free_gb
+                )
+            } else {
+                format!(
+                    "Name: {}\nMount: not mounted ({} = mount)\nDevice: {}\nSize: {:.2} GB",
+                    device.name,
+                    keys.label(Action::Mount),
+                    device.device_node.as_deref().unwrap_or("?"),
+                    total_gb
+                )
+            };
+            if let Some(container) = &device.container {
+                info.push_str(&format!("\nContainer: {}", container));
+            }
+            if let Some(original) = &device.alias_of {
+                info.push_str(&format!("\nSame data as: {}", original));
+            }
+            if let Some(encrypted) = device.encrypted {
+                info.push_str(&format!("\nEncrypted: {}", if encrypted { "Yes" } else { "No" }));
+            }
+            if let Some(trash_size) = device.trash_size {
+                info.push_str(&format!("\nTrash: {}", format_size(trash_size, units)));
+            }
+            if let Some(model) = cost_model.filter(|_| device.mounted) {
+                let used = device.total_space.saturating_sub(device.available_space);
+                info.push_str(&format!("\nCost of used space: {}", format_cost(model.monthly_cost(used), currency)));
+            }
             if let Some(extra) = &device.vendor_info {
                 info.push_str("\nInfo:");
                 for part in extra.split(',') {
                     info.push_str(&format!("\n       - {}", part.trim()));
                 }
             }
-            info
+            let mut lines: Vec<Spans> = info.lines().map(|line| Spans::from(line.to_string())).collect();
+            if let Some(smart) = &device.smart {
+                // SMART health, highlighted in red when the drive is failing or worth watching
+                let warning = smart.warning();
+                let health_style = if smart.is_failing() {
+                    Style::default().fg(colors.bad).add_modifier(Modifier::BOLD)
+                } else if warning.is_some() {
+                    Style::default().fg(colors.bad)
+                } else {
+                    Style::default().fg(colors.good)
+                };
+                let health = match (smart.passed, smart.is_failing(), warning) {
+                    (_, true, _) => "FAILING - back up this drive now!".to_string(),
+                    (_, false, Some(warning)) => format!("Watch - {}", warning),
+                    (Some(true), false, None) => "OK".to_string(),
+                    _ => "Unknown".to_string(),
+                };
+                lines.push(Spans::from(vec![
+                    Span::raw("Health: "),
+                    Span::styled(health, health_style),
+                ]));
+                if let Some(hours) = smart.power_on_hours {
+                    lines.push(Spans::from(format!("       - Power-on: {} h", hours)));
+                }
+                if let Some(wear) = smart.wear_percent {
+                    lines.push(Spans::from(format!("       - Wear: {}%", wear)));
+                }
+                if let Some(sectors) = smart.reallocated_sectors {
+                    lines.push(Spans::from(vec![
+                        Span::raw("       - Reallocated sectors: "),
+                        Span::styled(sectors.to_string(), if sectors > 0 { health_style } else { Style::default() }),
+                    ]));
+                }
+            }
+            lines
         } else {
-            "No devices found.".to_string()
+            vec![Spans::from("No devices found.")]
         };
         let details_paragraph = Paragraph::new(device_details)
             .block(Block::default().borders(Borders::ALL).title("[ Device Details ]"));
-        f.render_widget(details_paragraph, details_and_gauge[0]);
+        f.render_widget(details_paragraph, layout.details);
 
         // Left panel: Progress Bar gauge.
-        if !app.devices.is_empty() {
-            let device = &app.devices[app.selected];
+        if app.selected_device().is_some_and(|device| !device.mounted) {
+            let placeholder = Paragraph::new("Not mounted")
+                .block(Block::default().borders(Borders::ALL).title("[ Usage ]"));
+            f.render_widget(placeholder, layout.gauge);
+        } else if let Some(device) = app.selected_device() {
             let total = device.total_space as f64;
             let free = device.available_space as f64;
             let used = total - free;
@@ -138,98 +962,77 @@ pub fn draw_app<B: Backend>(
             let label = format!("Used: {}%", percent);
             let gauge = Gauge::default()
                 .block(Block::default().borders(Borders::ALL).title("[ Usage ]"))
-                .gauge_style(Style::default().fg(Color::Magenta).bg(Color::Black))
+                .gauge_style(Style::default().fg(colors.usage_gauge).bg(colors.gauge_bg))
                 .percent(percent)
                 .label(Span::raw(label));
-            f.render_widget(gauge, details_and_gauge[1]);
+            f.render_widget(gauge, layout.gauge);
         } else {
             // If no devices, display a placeholder.
             let placeholder = Paragraph::new("No device available")
                 .block(Block::default().borders(Borders::ALL).title("[ Usage ]"));
-            f.render_widget(placeholder, details_and_gauge[1]);
+            f.render_widget(placeholder, layout.gauge);
         }
 
         // Right top panel - file listing
-        let right_content = if app.devices.is_empty() {
-            "No storage devices detected."
+        let right_content = if app.selected_device().is_none() {
+            "No storage devices detected.".to_string()
+        } else if app.selected_device().is_some_and(|device| !device.mounted) {
+            format!("This volume is not mounted. Press {} to mount it.", keys.label(Action::Mount))
         } else if app.scanning {
-            "Scanning in progress..."
+            "Scanning in progress...".to_string()
         } else if let Some(ref entries) = app.file_entries {
             if entries.is_empty() {
-                "No files/folders found on this device."
+                "No files/folders found on this device.".to_string()
             } else {
-                "" // Table view below.
+                String::new() // Table view below.
             }
         } else {
-            "Loading files..."
+            "Loading files...".to_string()
         };
 
         // Determine which files to display (regular listing, full scan, or folder view)
         let display_full_scan = app.full_scan_results.is_some() && !app.scan_progress.in_progress;
         let display_folder_view = app.folder_summaries.is_some() && app.folder_view_mode;
 
-        // Right top panel - Folder summaries (for junk scan)
-        if display_folder_view && app.folder_summaries.is_some() {
-            let folder_summaries = app.folder_summaries.as_ref().unwrap();
-            
-            let title = "[ Junk Files by Folder ]";
-            
-            // Apply scrolling by showing a window of folders
-            let visible_folders: Vec<(usize, &crate::FolderSummary)> = folder_summaries.iter()
-                .enumerate()
-                .skip(app.file_list_offset)
-                .take(20) // Show ~20 folders at a time
-                .collect();
-            
-            // Show scroll indicators and count in the title
-            let mut title = title.to_string();
-            title = format!("{} [{}/{}]", title, app.selected_folder_index + 1, folder_summaries.len());
-            
-            // Add up/down scroll indicators
-            if app.file_list_offset > 0 {
-                title = format!("↟ {} ", title);
-            }
-            if app.file_list_offset + 20 < folder_summaries.len() {
-                title = format!("{} ↡", title);
-            }
-            
-            let rows: Vec<Row> = visible_folders.iter().map(|(idx, folder)| {
-                // Format folder size in a more readable way (KB, MB, GB)
-                let size_str = if folder.total_size < 1024 {
-                    format!("{} B", folder.total_size)
-                } else if folder.total_size < 1024 * 1024 {
-                    format!("{:.2} KB", folder.total_size as f64 / 1024.0)
-                } else if folder.total_size < 1024 * 1024 * 1024 {
-                    format!("{:.2} MB", folder.total_size as f64 / (1024.0 * 1024.0))
-                } else {
-                    format!("{:.2} GB", folder.total_size as f64 / (1024.0 * 1024.0 * 1024.0))
-                };
-                
-                // Highlight the selected folder
-                let style = if *idx == app.selected_folder_index && app.focus == crate::PanelFocus::Right {
-                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        // Right top panel - Xcode data and whether a clean includes it
+        if let Some(items) = app.xcode_items.as_ref().filter(|_| app.xcode_view_active()) {
+            let selected_size: u64 = app.selected_xcode_items().iter().map(|item| item.size).sum();
+            let title = format!(
+                "[ Xcode Data ] Selected: {} ({} = select, {} = clean)",
+                format_size(selected_size, units),
+                keys.label(Action::ToggleProject),
+                keys.label(Action::CleanArtifacts)
+            );
+
+            let offset = app.selected_xcode_index.saturating_sub(19);
+            let rows: Vec<Row> = items.iter().enumerate().skip(offset).take(20).map(|(idx, item)| {
+                let selected = app.xcode_selected.contains(&item.path);
+                let style = if idx == app.selected_xcode_index && app.focus == crate::PanelFocus::Right {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else if !selected {
+                    Style::default().add_modifier(Modifier::DIM)
                 } else {
                     Style::default()
                 };
-                
+
                 Row::new(vec![
-                    Span::styled(folder.path.clone(), style),
-                    Span::styled(size_str, style),
-                    Span::styled(format!("{}", folder.file_count), style)
+                    Span::styled(if selected { "[x]" } else { "[ ]" }, style),
+                    Span::styled(item.kind.label(), style),
+                    Span::styled(item.name.clone(), style),
+                    Span::styled(format_size(item.size, units), style),
                 ])
             }).collect();
-            
-            // Set different block style based on focus
+
             let right_block_style = if app.focus == crate::PanelFocus::Right {
-                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
-            
+
             let table = Table::new(rows)
                 .header(
-                    Row::new(vec!["Folder Path", "Total Size", "Files"])
-                        .style(Style::default().fg(Color::LightBlue))
+                    Row::new(vec!["", "Type", "Name", "Size"])
+                        .style(Style::default().fg(colors.header))
                         .bottom_margin(1),
                 )
                 .block(Block::default()
@@ -237,82 +1040,144 @@ pub fn draw_app<B: Backend>(
                     .title(title)
                     .border_style(right_block_style))
                 .widths(&[
-                    Constraint::Percentage(70),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(10),
+                    Constraint::Length(4),
+                    Constraint::Length(18),
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(15),
                 ]);
-            f.render_widget(table, right_chunks[0]);
+            f.render_widget(table, list_area);
         }
-        // Right top panel - File listing (normal or full scan)
-        else if (app.file_entries.is_some() && !app.scanning && !app.file_entries.as_ref().unwrap().is_empty()) || display_full_scan {
-            let entries = if display_full_scan {
-                app.full_scan_results.as_ref().unwrap()
-            } else {
-                app.file_entries.as_ref().unwrap()
-            };
-
-            let title = if display_full_scan {
-                "[ Files By Size (Descending) ]"
-            } else {
-                "[ Files & Folders ]"
-            };
+        // Right top panel - Projects with build artifacts and whether a clean includes them
+        else if let Some(projects) = app.dev_projects.as_ref().filter(|_| app.dev_view_active()) {
+            let selected_size: u64 = app.selected_projects().iter().map(|project| project.total_size()).sum();
+            let title = format!(
+                "[ Build Artifacts in {} ] Selected: {} ({} = select, {} = clean)",
+                app.dev_scan_root.as_deref().unwrap_or_default(),
+                format_size(selected_size, units),
+                keys.label(Action::ToggleProject),
+                keys.label(Action::CleanArtifacts)
+            );
 
-            // Apply scrolling by showing a window of entries
-            let visible_entries: Vec<(usize, &crate::scanner::FileEntry)> = entries.iter()
-                .enumerate()
-                .skip(app.file_list_offset)
-                .take(20) // Show ~20 entries at a time
-                .collect();
+            // Keep the selected project visible, as in the file listing
+            let offset = app.selected_project_index.saturating_sub(19);
+            let rows: Vec<Row> = projects.iter().enumerate().skip(offset).take(20).map(|(idx, project)| {
+                let excluded = app.excluded_projects.contains(&project.path);
+                let style = if idx == app.selected_project_index && app.focus == crate::PanelFocus::Right {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else if excluded {
+                    Style::default().add_modifier(Modifier::DIM)
+                } else {
+                    Style::default()
+                };
 
-            // Show scroll indicators and count in the title
-            let mut title = title.to_string();
-            title = format!("{} [{}/{}]", title, app.selected_file_index + 1, entries.len());
+                Row::new(vec![
+                    Span::styled(if excluded { "[ ]" } else { "[x]" }, style),
+                    Span::styled(project.path.clone(), style),
+                    Span::styled(project.kinds(), style),
+                    Span::styled(format_size(project.total_size(), units), style),
+                ])
+            }).collect();
 
-            // Add up/down scroll indicators with more visible characters
-            if app.file_list_offset > 0 {
-                title = format!("↟ {} ", title);
-            }
-            if app.file_list_offset + 20 < entries.len() {
-                title = format!("{} ↡", title);
-            }
+            let right_block_style = if app.focus == crate::PanelFocus::Right {
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
 
-            let rows: Vec<Row> = visible_entries.iter().map(|(idx, entry)| {
-                // Format file size in a more readable way (KB, MB, GB)
-                let size_str = if entry.size < 1024 {
-                    format!("{} B", entry.size)
-                } else if entry.size < 1024 * 1024 {
-                    format!("{:.2} KB", entry.size as f64 / 1024.0)
-                } else if entry.size < 1024 * 1024 * 1024 {
-                    format!("{:.2} MB", entry.size as f64 / (1024.0 * 1024.0))
-                } else {
-                    format!("{:.2} GB", entry.size as f64 / (1024.0 * 1024.0 * 1024.0))
-                };
+            let table = Table::new(rows)
+                .header(
+                    Row::new(vec!["", "Project", "Artifacts", "Size"])
+                        .style(Style::default().fg(colors.header))
+                        .bottom_margin(1),
+                )
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(right_block_style))
+                .widths(&[
+                    Constraint::Length(4),
+                    Constraint::Percentage(55),
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(15),
+                ]);
+            f.render_widget(table, list_area);
+        }
+        // Right top panel - Junk categories with their totals and include/exclude state
+        else if let Some(categories) = app.junk_categories.as_ref().filter(|_| app.category_view_mode) {
+            let included_size: u64 = categories
+                .iter()
+                .filter(|category| !app.excluded_categories.contains(&category.id))
+                .map(|category| category.total_size)
+                .sum();
+            let title = format!(
+                "[ Junk Categories ] Included: {} ({} = include/exclude)",
+                format_size(included_size, units),
+                keys.label(Action::ToggleCategory)
+            );
 
-                // Highlight the selected file
-                let style = if *idx == app.selected_file_index && app.focus == crate::PanelFocus::Right {
-                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            let mut rows: Vec<Row> = categories.iter().enumerate().map(|(idx, category)| {
+                let excluded = app.excluded_categories.contains(&category.id);
+                let style = if idx == app.selected_category_index && app.focus == crate::PanelFocus::Right {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else if excluded {
+                    Style::default().add_modifier(Modifier::DIM)
                 } else {
                     Style::default()
                 };
 
                 Row::new(vec![
-                    Span::styled(entry.name.clone(), style),
-                    Span::styled(entry.path.clone(), style),
-                    Span::styled(size_str, style)
+                    Span::styled(if excluded { "[ ]" } else { "[x]" }, style),
+                    Span::styled(category.name.clone(), style),
+                    Span::styled(format_size(category.total_size, units), style),
+                    Span::styled(format_count(category.file_count as u64), style),
                 ])
             }).collect();
 
-            // Set different block style based on focus
+            // Docker data isn't part of the junk files; it is pruned through Docker instead
+            for usage in app.docker_usage.iter().flatten() {
+                let style = Style::default().fg(colors.accent);
+                rows.push(Row::new(vec![
+                    Span::styled("", style),
+                    Span::styled(
+                        format!("{} ({} reclaimable)", usage.resource.label(), format_size(usage.reclaimable, units)),
+                        style,
+                    ),
+                    Span::styled(format_size(usage.size, units), style),
+                    Span::styled(format!("{} ({} active)", usage.total_count, usage.active), style),
+                ]));
+            }
+
+            // Browser profiles, split by what the data is for; only the cache is cleared
+            for profile in &app.browser_profiles {
+                let style = Style::default().fg(colors.accent);
+                rows.push(Row::new(vec![
+                    Span::styled("", style),
+                    Span::styled(
+                        format!(
+                            "{} {}: cache {}, history {}, extensions {}",
+                            profile.browser.label(),
+                            profile.name,
+                            format_size(profile.cache_size, units),
+                            format_size(profile.history_size, units),
+                            format_size(profile.extensions_size, units)
+                        ),
+                        style,
+                    ),
+                    Span::styled(format_size(profile.total_size(), units), style),
+                    Span::styled("", style),
+                ]));
+            }
+
             let right_block_style = if app.focus == crate::PanelFocus::Right {
-                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
 
             let table = Table::new(rows)
                 .header(
-                    Row::new(vec!["Name", "Path", "File Size"])
-                        .style(Style::default().fg(Color::LightBlue))
+                    Row::new(vec!["", "Category", "Total Size", "Files"])
+                        .style(Style::default().fg(colors.header))
                         .bottom_margin(1),
                 )
                 .block(Block::default()
@@ -320,58 +1185,433 @@ pub fn draw_app<B: Backend>(
                     .title(title)
                     .border_style(right_block_style))
                 .widths(&[
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(50),
+                    Constraint::Length(4),
+                    Constraint::Percentage(60),
                     Constraint::Percentage(20),
+                    Constraint::Percentage(15),
                 ]);
-            f.render_widget(table, right_chunks[0]);
-        } else {
-            // Set different block style based on focus
+            f.render_widget(table, list_area);
+        }
+        // Right top panel - Directory sizes of the full scan
+        else if let Some(view) = app.dir_view.as_ref().filter(|_| app.dir_view_active()) {
+            let mut title = format!("[ {} ] {} in {} entries", view.dir, format_size(view.total, units), view.children.len());
+            if view.offset > 0 {
+                title = format!("↟ {} ", title);
+            }
+            if view.offset + 20 < view.children.len() {
+                title = format!("{} ↡", title);
+            }
+
+            let rows: Vec<Row> = view.children.iter().enumerate().skip(view.offset).take(20).map(|(idx, child)| {
+                let highlighted = idx == view.selected && app.focus == crate::PanelFocus::Right;
+                let style = if highlighted {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let name = if child.is_dir { format!("{}/", child.name) } else { child.name.clone() };
+                let kind = FileKind::of(&child.path, child.is_dir);
+                Row::new(vec![
+                    Cell::from(name_cell(app, name, kind, style, highlighted)),
+                    size_cell(app, &child.path, format_size(child.size, units), style),
+                    Cell::from(Span::styled(usage_bar(child.size, view.total), style)),
+                    Cell::from(Span::styled(format_count(child.file_count as u64), style)),
+                ])
+            }).collect();
+
             let right_block_style = if app.focus == crate::PanelFocus::Right {
-                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
 
-            let right_panel = Paragraph::new(right_content)
-                .block(Block::default()
+            let table = Table::new(rows)
+                .header(
+                    Row::new(vec!["Name", "Size", "Share", "Files"])
+                        .style(Style::default().fg(colors.header))
+                        .bottom_margin(1),
+                )
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(right_block_style))
+                .widths(&[
+                    Constraint::Percentage(45),
+                    Constraint::Percentage(15),
+                    Constraint::Length(20),
+                    Constraint::Percentage(10),
+                ]);
+            f.render_widget(table, list_area);
+        }
+        // Right top panel - Folder summaries (for junk scan)
+        else if let Some(folder_summaries) = app.folder_summaries.as_ref().filter(|_| display_folder_view) {
+            
+            let title = "[ Junk Files by Folder ]";
+            
+            // Apply scrolling by showing a window of folders
+            let visible_folders: Vec<(usize, &crate::FolderSummary)> = folder_summaries.iter()
+                .enumerate()
+                .skip(app.file_list_offset)
+                .take(20) // Show ~20 folders at a time
+                .collect();
+            
+            // Show scroll indicators and count in the title
+            let mut title = title.to_string();
+            title = format!("{} [{}/{}]", title, app.selected_folder_index + 1, folder_summaries.len());
+            
+            // Add up/down scroll indicators
+            if app.file_list_offset > 0 {
+                title = format!("↟ {} ", title);
+            }
+            if app.file_list_offset + 20 < folder_summaries.len() {
+                title = format!("{} ↡", title);
+            }
+            
+            let rows: Vec<Row> = visible_folders.iter().map(|(idx, folder)| {
+                let size_str = format_size(folder.total_size, units);
+                
+                // Highlight the selected folder
+                let highlighted = *idx == app.selected_folder_index && app.focus == crate::PanelFocus::Right;
+                let style = if highlighted {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                
+                let mut cells = vec![
+                    name_cell(app, folder.path.clone(), FileKind::Directory, style, highlighted),
+                    Span::styled(size_str, style),
+                    Span::styled(format_count(folder.file_count as u64), style)
+                ];
+                if let Some(model) = cost_model {
+                    cells.push(Span::styled(format_cost(model.monthly_cost(folder.total_size), currency), style));
+                }
+                Row::new(cells)
+            }).collect();
+            
+            // Set different block style based on focus
+            let right_block_style = if app.focus == crate::PanelFocus::Right {
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            
+            // With costs shown, the path makes room for them
+            let (header, widths) = if cost_model.is_some() {
+                (
+                    vec!["Folder Path", "Total Size", "Files", "Cost"],
+                    vec![Constraint::Percentage(50), Constraint::Percentage(15), Constraint::Percentage(10), Constraint::Percentage(25)],
+                )
+            } else {
+                (
+                    vec!["Folder Path", "Total Size", "Files"],
+                    vec![Constraint::Percentage(70), Constraint::Percentage(20), Constraint::Percentage(10)],
+                )
+            };
+            let table = Table::new(rows)
+                .header(
+                    Row::new(header)
+                        .style(Style::default().fg(colors.header))
+                        .bottom_margin(1),
+                )
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(right_block_style))
+                .widths(&widths);
+            f.render_widget(table, list_area);
+        }
+        // Right top panel - Largest files of the running full scan
+        else if let Some(files) = app.largest_so_far.as_ref().filter(|_| app.largest_so_far_shown()) {
+            let title = format!("[ Largest Files So Far ] ({} = browse the listing)", keys.label(Action::ToggleFolderView));
+            let mount_point = app.devices.get(app.selected).map_or("", |device| device.mount_point.as_str());
+            let shown_size = |file: &crate::scanner::FileEntry| if app.show_allocated { file.disk_usage() } else { file.size };
+
+            // Only what fits; the list keeps changing while the scan runs
+            let rows: Vec<Row> = files.iter().take(list_area.height.saturating_sub(4) as usize).map(|file| {
+                let kind = if file.link_target.is_some() { FileKind::Symlink } else { FileKind::of(&file.path, false) };
+                let mut cells = if layout.compact {
+                    vec![name_cell(app, relative_name(file, mount_point), kind, Style::default(), false)]
+                } else {
+                    vec![name_cell(app, file.name.clone(), kind, Style::default(), false), Span::raw(file.path.clone())]
+                };
+                cells.push(Span::raw(format_size(shown_size(file), units)));
+                Row::new(cells)
+            }).collect();
+
+            let right_block_style = if app.focus == crate::PanelFocus::Right {
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let size_header = if app.show_allocated { "On Disk" } else { "File Size" };
+            let (header, widths) = if layout.compact {
+                (vec!["Name", size_header], vec![Constraint::Percentage(75), Constraint::Percentage(25)])
+            } else {
+                (
+                    vec!["Name", "Path", size_header],
+                    vec![Constraint::Percentage(30), Constraint::Percentage(50), Constraint::Percentage(20)],
+                )
+            };
+            let table = Table::new(rows)
+                .header(
+                    Row::new(header)
+                        .style(Style::default().fg(colors.header))
+                        .bottom_margin(1),
+                )
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(right_block_style))
+                .widths(&widths);
+            f.render_widget(table, list_area);
+        }
+        // Right top panel - File listing (normal or full scan)
+        else if let Some(entries) = app.listed_entries() {
+
+            // While a full scan runs, finished top-level directories show their scanned size
+            let scanning_device = app.scan_progress.in_progress && app.scan_mode == crate::ScanMode::FullScan && !display_full_scan;
+            let title = if display_full_scan {
+                let full_scan = app.scan_mode == crate::ScanMode::FullScan;
+                let mut title = match (app.full_scan_depth.filter(|_| full_scan), app.full_scan_roots.as_ref().filter(|_| full_scan)) {
+                    (Some(depth), _) => format!("[ Files By Size (Descending), {} deep", format_depth(depth)),
+                    (None, Some(_)) => "[ Files By Size (Descending), home and data locations".to_string(),
+                    (None, None) => "[ Files By Size (Descending)".to_string(),
+                };
+                // A scan that kept only its largest files lists fewer than it counted
+                let counted = app.full_scan_totals.as_ref().zip(app.devices.get(app.selected)).and_then(|(totals, device)| totals.get(&device.mount_point));
+                if let Some(total) = counted.filter(|_| full_scan) {
+                    title.push_str(&format!(", largest {} of {} files", format_count(entries.len() as u64), format_count(total.file_count as u64)));
+                }
+                title.push_str(" ]");
+                title
+            } else if scanning_device && app.largest_so_far.is_some() {
+                format!(
+                    "[ Files & Folders ] (✓ = scanned, highlighted folder is scanned next, {} = largest files so far)",
+                    keys.label(Action::ToggleFolderView)
+                )
+            } else if scanning_device {
+                "[ Files & Folders ] (✓ = scanned, highlighted folder is scanned next)".to_string()
+            } else {
+                "[ Files & Folders ]".to_string()
+            };
+
+            let mount_point = app.devices.get(app.selected).map_or("", |device| device.mount_point.as_str());
+            // Sparse and compressed files take up less on disk than their size
+            let shown_size = |entry: &crate::scanner::FileEntry| if app.show_allocated { entry.disk_usage() } else { entry.size };
+            let size_header = if app.show_allocated { "On Disk" } else { "File Size" };
+
+            // Apply scrolling by showing a window of entries
+            let visible_entries: Vec<(usize, &crate::scanner::FileEntry)> = entries.iter()
+                .enumerate()
+                .skip(app.file_list_offset)
+                .take(20) // Show ~20 entries at a time
+                .collect();
+
+            // Show scroll indicators and count in the title. A subdirectory being listed shows
+            // its path as breadcrumbs instead.
+            let highlighted_crumb = match mode {
+                AppMode::Breadcrumbs { selected } => Some(*selected),
+                _ => None,
+            };
+            let mut title_spans = vec![Span::raw(scrolled_indicator(app))];
+            let crumbs = breadcrumb_spans(app, highlighted_crumb);
+            if crumbs.is_empty() || display_full_scan {
+                title_spans.push(Span::raw(title));
+            } else {
+                title_spans.extend(crumbs);
+            }
+            title_spans.push(Span::raw(format!(" [{}/{}]", app.selected_file_index + 1, entries.len())));
+
+            // Add up/down scroll indicators with more visible characters
+            if app.file_list_offset > 0 {
+                title_spans.push(Span::raw(" "));
+            }
+            if app.file_list_offset + 20 < entries.len() {
+                title_spans.push(Span::raw(" ↡"));
+            }
+            let title = Spans::from(title_spans);
+
+            // When the large files of a full scan were last opened, once that is looked up
+            let last_opened_shown = display_full_scan && !layout.compact && !app.last_opened.is_empty();
+            let rows: Vec<Row> = visible_entries.iter().map(|(idx, entry)| {
+                let size_str = match app.scanned_dir_sizes.get(&entry.path).filter(|_| scanning_device) {
+                    Some(size) => format!("{} ✓", format_size(*size, units)),
+                    None => format_size(shown_size(entry), units),
+                };
+
+                // Highlight the selected file
+                let highlighted = *idx == app.selected_file_index && app.focus == crate::PanelFocus::Right;
+                let style = if highlighted {
+                    Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+
+                // Scan results only hold files; in a listing, only the visible rows are looked up
+                let is_dir = !display_full_scan && std::path::Path::new(&entry.path).is_dir();
+                let kind = if entry.link_target.is_some() { FileKind::Symlink } else { FileKind::of(&entry.path, is_dir) };
+                let name = if layout.compact { relative_name(entry, mount_point) } else { entry.name.clone() };
+                let name = match &entry.link_target {
+                    Some(target) => format!("{} → {}", name, target),
+                    None => name,
+                };
+                // Paths outside the user's own data stand out in the path column, or by their
+                // size in the compact layout
+                let safety_style = match Safety::classify(&entry.path) {
+                    _ if highlighted => style,
+                    Safety::System => style.fg(colors.system_data),
+                    Safety::AppData => style.fg(colors.app_data),
+                    Safety::UserData => style,
+                };
+                let mut cells: Vec<Cell> = if layout.compact {
+                    vec![name_cell(app, name, kind, style, highlighted).into()]
+                } else {
+                    vec![name_cell(app, name, kind, style, highlighted).into(), Span::styled(entry.path.clone(), safety_style).into()]
+                };
+                let size_style = if layout.compact { safety_style } else { style };
+                cells.push(if display_full_scan { size_cell(app, &entry.path, size_str, size_style) } else { Span::styled(size_str, size_style).into() });
+                if let Some(model) = cost_model {
+                    cells.push(Span::styled(format_cost(model.monthly_cost(shown_size(entry)), currency), style).into());
+                }
+                if last_opened_shown {
+                    let opened = app.last_opened.get(&entry.path).map(|opened| chrono::DateTime::<chrono::Local>::from(*opened).format("%Y-%m-%d").to_string());
+                    cells.push(Span::styled(opened.unwrap_or_default(), style).into());
+                }
+                Row::new(cells)
+            }).collect();
+
+            // Set different block style based on focus
+            let right_block_style = if app.focus == crate::PanelFocus::Right {
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            // With costs shown, the path makes room for them. The compact layout has no path
+            // column; names are relative to the device instead.
+            let (mut header, mut widths) = if layout.compact && cost_model.is_some() {
+                (
+                    vec!["Name", size_header, "Cost"],
+                    vec![Constraint::Percentage(55), Constraint::Percentage(20), Constraint::Percentage(25)],
+                )
+            } else if layout.compact {
+                (vec!["Name", size_header], vec![Constraint::Percentage(75), Constraint::Percentage(25)])
+            } else if cost_model.is_some() {
+                (
+                    vec!["Name", "Path", size_header, "Cost"],
+                    vec![Constraint::Percentage(25), Constraint::Percentage(35), Constraint::Percentage(18), Constraint::Percentage(22)],
+                )
+            } else {
+                (
+                    vec!["Name", "Path", size_header],
+                    vec![Constraint::Percentage(30), Constraint::Percentage(50), Constraint::Percentage(20)],
+                )
+            };
+            if last_opened_shown {
+                header.push("Last opened");
+                widths.push(Constraint::Length(11));
+            }
+            let table = Table::new(rows)
+                .header(
+                    Row::new(header)
+                        .style(Style::default().fg(colors.header))
+                        .bottom_margin(1),
+                )
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(right_block_style))
+                .widths(&widths);
+            f.render_widget(table, list_area);
+        } else {
+            // Set different block style based on focus
+            let right_block_style = if app.focus == crate::PanelFocus::Right {
+                Style::default().fg(colors.focus).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            let right_panel = Paragraph::new(right_content)
+                .block(Block::default()
                     .borders(Borders::ALL)
                     .title("[ Files & Folders ]")
                     .border_style(right_block_style));
-            f.render_widget(right_panel, right_chunks[0]);
+            f.render_widget(right_panel, list_area);
+        }
+
+        // Right top panel - preview of the selected entry
+        #[cfg(feature = "preview")]
+        if let Some(area) = preview_area {
+            draw_preview(f, area, app);
         }
+        #[cfg(not(feature = "preview"))]
+        let _ = preview_area;
 
-        // Right bottom panel - Only show scan progress when in scan mode
-        if app.scan_progress.in_progress || matches!(mode, AppMode::FullScan { .. }) {
+        // Right bottom panel - Only show scan progress when in scan mode (a junk folder
+        // deletion reports its progress the same way). Running file operations get a gauge
+        // each, below the scan progress if a scan runs as well.
+        let deleting = app.folder_delete.as_ref();
+        let active_operations: Vec<&Operation> = app.operations
+            .operations()
+            .iter()
+            .filter(|op| !op.state.is_finished())
+            .collect();
+        let scan_shown = matches!(mode, AppMode::FullScan { .. }) || app.scan_progress.in_progress;
+        let mut progress_area = layout.progress;
+        if !active_operations.is_empty() {
+            if scan_shown {
+                let halves = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                    .split(progress_area);
+                progress_area = halves[0];
+                draw_operation_gauges(f, halves[1], app, &active_operations);
+            } else {
+                draw_operation_gauges(f, progress_area, app, &active_operations);
+            }
+        }
+        if let (AppMode::FullScan { spinner_index, .. }, 0) = (mode, app.scan_progress.files_processed) {
+            // Full scan is initializing
+            let spinner = spinner_chars.get(*spinner_index).copied().unwrap_or_default();
+            let (text, title) = if deleting.is_some() {
+                (format!("{} Deleting junk files...", spinner), "[ Deleting Folder ]")
+            } else {
+                (format!("{} Preparing full scan...", spinner), "[ Full Scan ]")
+            };
+            let paragraph = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(title));
+            f.render_widget(paragraph, progress_area);
+        } else if app.scan_progress.in_progress || matches!(mode, AppMode::FullScan { .. }) {
             // Full scan in progress - show detailed progress
+            // Files can add up to more than the used space the total was taken from (hard links,
+            // files written meanwhile), which the gauge can't show
             let progress_percent = if app.scan_progress.total_bytes > 0 {
-                (app.scan_progress.scanned_bytes as f64 / app.scan_progress.total_bytes as f64 * 100.0) as u16
+                (app.scan_progress.scanned_bytes as f64 / app.scan_progress.total_bytes as f64 * 100.0).min(100.0) as u16
             } else {
                 0
             };
 
             // Format sizes in a readable way
-            let scanned_str = if app.scan_progress.scanned_bytes < 1024 * 1024 {
-                format!("{:.2} KB", app.scan_progress.scanned_bytes as f64 / 1024.0)
-            } else if app.scan_progress.scanned_bytes < 1024 * 1024 * 1024 {
-                format!("{:.2} MB", app.scan_progress.scanned_bytes as f64 / (1024.0 * 1024.0))
-            } else {
-                format!("{:.2} GB", app.scan_progress.scanned_bytes as f64 / (1024.0 * 1024.0 * 1024.0))
-            };
+            let scanned_str = format_size(app.scan_progress.scanned_bytes, units);
+            let total_str = format_size(app.scan_progress.total_bytes, units);
 
-            let total_str = if app.scan_progress.total_bytes < 1024 * 1024 {
-                format!("{:.2} KB", app.scan_progress.total_bytes as f64 / 1024.0)
-            } else if app.scan_progress.total_bytes < 1024 * 1024 * 1024 {
-                format!("{:.2} MB", app.scan_progress.total_bytes as f64 / (1024.0 * 1024.0))
-            } else {
-                format!("{:.2} GB", app.scan_progress.total_bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+            // Progress bar, with the recent throughput and the time left at that pace
+            let mut label = format!("{}: {} / {} ({}%)", if deleting.is_some() { "Deleted" } else { "Scanned" }, scanned_str, total_str, progress_percent);
+            if let Some((bytes_per_sec, files_per_sec)) = app.scan_progress.throughput() {
+                label.push_str(&format!("  {}/s, {:.0} files/s", format_size(bytes_per_sec as u64, units), files_per_sec));
+                if let Some(remaining) = app.scan_progress.remaining() {
+                    label.push_str(&format!(", ~{} left", format_duration(remaining)));
+                }
+            }
+            let gauge_title = match deleting {
+                Some(job) if job.is_paused() => "[ Deletion Paused ]",
+                Some(_) => "[ Deletion Progress ]",
+                None => "[ Full Scan Progress ]",
             };
-
-            // Progress bar
-            let label = format!("Scanned: {} / {} ({}%)", scanned_str, total_str, progress_percent);
             let gauge = Gauge::default()
-                .block(Block::default().borders(Borders::ALL).title("[ Full Scan Progress ]"))
-                .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+                .block(Block::default().borders(Borders::ALL).title(gauge_title))
+                .gauge_style(Style::default().fg(colors.scan_gauge).bg(colors.gauge_bg))
                 .percent(progress_percent)
                 .label(Span::raw(label));
 
@@ -379,10 +1619,11 @@ pub fn draw_app<B: Backend>(
             let current_file = if let Some(ref file_path) = app.scan_progress.current_file {
                 // Truncate the path if it's too long
                 let max_length = 60;
-                if file_path.len() > max_length {
-                    // Truncate the middle of the path
-                    let start = &file_path[0..30];
-                    let end = &file_path[file_path.len() - 30..];
+                let chars: Vec<char> = file_path.chars().collect();
+                if chars.len() > max_length {
+                    // Truncate the middle of the path, at character boundaries
+                    let start: String = chars[..30].iter().collect();
+                    let end: String = chars[chars.len() - 30..].iter().collect();
                     format!("{}...{}", start, end)
                 } else {
                     file_path.clone()
@@ -391,95 +1632,1026 @@ pub fn draw_app<B: Backend>(
                 "".to_string()
             };
 
-            let scan_stats = format!(
-                "Files processed: {}\nCurrent file: {}\nPress 'q' to quit or 'c' to cancel scan",
-                app.scan_progress.files_processed,
-                current_file
-            );
+            let scan_stats = match deleting {
+                Some(job) => format!(
+                    "Files deleted: {} / {}\nCurrent file: {}\nPress '{}' to {}, '{}' to stop deleting or '{}' to quit",
+                    format_count(app.scan_progress.files_processed),
+                    format_count(job.total_files as u64),
+                    current_file,
+                    keys.label(Action::Pause),
+                    if job.is_paused() { "resume" } else { "pause" },
+                    keys.label(Action::Cancel),
+                    keys.label(Action::Quit)
+                ),
+                None => format!(
+                    "Files processed: {}\nCurrent file: {}\nPress '{}' to quit or '{}' to cancel scan",
+                    format_count(app.scan_progress.files_processed),
+                    current_file,
+                    keys.label(Action::Quit),
+                    keys.label(Action::Cancel)
+                ),
+            };
 
             // Create a vertical layout for the gauge and stats text
             let progress_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(right_chunks[1]);
+                .split(progress_area);
 
             f.render_widget(gauge, progress_chunks[0]);
 
             let stats_paragraph = Paragraph::new(scan_stats)
                 .block(Block::default().borders(Borders::ALL).title("[ Scan Statistics ]"));
             f.render_widget(stats_paragraph, progress_chunks[1]);
-        } else if let AppMode::FullScan { spinner_index, .. } = mode {
-            // Full scan is initializing
-            let spinner = spinner_chars[*spinner_index];
-            let text = format!("{} Preparing full scan...", spinner);
-            let paragraph = Paragraph::new(text)
-                .block(Block::default().borders(Borders::ALL).title("[ Full Scan ]"));
-            f.render_widget(paragraph, right_chunks[1]);
-        } else if app.focus == crate::PanelFocus::Right {
-            if app.folder_summaries.is_some() && app.scan_mode == crate::ScanMode::JunkScan {
+        } else if let Some(breakdown) = app.type_breakdown.as_ref().filter(|_| app.type_breakdown_shown() && active_operations.is_empty()) {
+            draw_type_breakdown(f, layout.progress, app, breakdown);
+        } else if app.focus == crate::PanelFocus::Right && active_operations.is_empty() {
+            if app.xcode_view_active() {
+                let help_text = format!(
+                    "\n\n- Press '{}' to select/deselect the item\n- Press '{}' to remove the selected items\n- Enter ':xcode' to measure again",
+                    keys.label(Action::ToggleProject),
+                    keys.label(Action::CleanArtifacts)
+                );
+                let paragraph = Paragraph::new(help_text)
+                    .block(Block::default().borders(Borders::ALL).title("[ Xcode Cleanup ]"));
+                f.render_widget(paragraph, layout.progress);
+            } else if app.dev_view_active() {
+                let help_text = format!(
+                    "\n\n- Press '{}' to select/deselect the project\n- Press '{}' to remove the artifacts of the selected projects\n- Press '{}' to search again",
+                    keys.label(Action::ToggleProject),
+                    keys.label(Action::CleanArtifacts),
+                    keys.label(Action::DevScan)
+                );
+                let paragraph = Paragraph::new(help_text)
+                    .block(Block::default().borders(Borders::ALL).title("[ Build Artifact Operations ]"));
+                f.render_widget(paragraph, layout.progress);
+            } else if app.folder_summaries.is_some() && app.scan_mode == crate::ScanMode::JunkScan {
                 // Show junk scan help when folder summaries are displayed
-                let help_text = if app.folder_view_mode {
-                    "\n\n- Press 'Enter' to view files in this folder\n- Press 'Tab' to switch to file view\n- Press 'S' to rescan junk files"
-                } else {
-                    "\n\n- Press 'Tab' to switch to folder view\n- Press 'd' to delete file\n- Press 'S' to rescan junk files"
-                };
-                let title = if app.folder_view_mode {
-                    "[ Folder Operations ]"
+                let (help_text, title) = if app.category_view_mode {
+                    let mut help_text = format!(
+                        "\n\n- Press '{}' to include/exclude the selected category\n- Press '{}' to return to the file view\n- Press '{}' to switch to folder view",
+                        keys.label(Action::ToggleCategory),
+                        keys.label(Action::ToggleCategoryView),
+                        keys.label(Action::ToggleFolderView)
+                    );
+                    if app.docker_usage.is_some() {
+                        help_text.push_str(&format!("\n- Press '{}' to prune Docker data", keys.label(Action::DockerPrune)));
+                    }
+                    if !app.browser_profiles.is_empty() {
+                        help_text.push_str(&format!("\n- Press '{}' to clear browser caches", keys.label(Action::ClearBrowserCache)));
+                    }
+                    (help_text, "[ Category Operations ]")
+                } else if app.folder_view_mode {
+                    (
+                        format!(
+                            "\n\n- Press '{}' to view files in this folder\n- Press '{}' to delete all junk in this folder\n- Press '{}' to switch to file view\n- Press '{}' to rescan junk files",
+                            keys.label(Action::OpenFolder),
+                            keys.label(Action::Delete),
+                            keys.label(Action::ToggleFolderView),
+                            keys.label(Action::FullScan)
+                        ),
+                        "[ Folder Operations ]",
+                    )
                 } else {
-                    "[ File Operations ]"
+                    (
+                        format!(
+                            "\n\n- Press '{}' to switch to folder view\n- Press '{}' to show junk categories\n- Press '{}' to delete file\n- Press '{}' to rescan junk files",
+                            keys.label(Action::ToggleFolderView),
+                            keys.label(Action::ToggleCategoryView),
+                            keys.label(Action::Delete),
+                            keys.label(Action::FullScan)
+                        ),
+                        "[ File Operations ]",
+                    )
                 };
                 let paragraph = Paragraph::new(help_text)
                     .block(Block::default().borders(Borders::ALL).title(title));
-                f.render_widget(paragraph, right_chunks[1]);
+                f.render_widget(paragraph, layout.progress);
+            } else if app.dir_view_active() {
+                let help_text = format!(
+                    "\n\n- Press '{}' to open the selected directory\n- Press '{}' to go up a directory\n- Press '{}' to show the files of the scan\n- Press '{}' to show the space by file type",
+                    keys.label(Action::OpenFolder),
+                    keys.label(Action::ParentDir),
+                    keys.label(Action::ToggleFolderView),
+                    keys.label(Action::ToggleCategoryView)
+                );
+                let paragraph = Paragraph::new(help_text)
+                    .block(Block::default().borders(Borders::ALL).title("[ Directory Sizes ]"));
+                f.render_widget(paragraph, layout.progress);
             } else if app.file_entries.is_some() || app.full_scan_results.is_some() {
                 // Show file operations help when files are displayed and right panel is focused
-                let help_text = "\n\n- Press 'd' to delete file\n- Press 'c' to copy file\n- Press 'm' to move file\n- Press 'S' for full scan and size sorting";
+                let mut help_text = format!(
+                    "\n\n- Press '{}' to delete file\n- Press '{}' to copy file\n- Press '{}' to move file\n- Press '{}' for full scan and size sorting\n- Press '{}' for incremental rescan",
+                    keys.label(Action::Delete),
+                    keys.label(Action::Copy),
+                    keys.label(Action::Move),
+                    keys.label(Action::FullScan),
+                    keys.label(Action::IncrementalScan)
+                );
+                if app.full_scan_shown() {
+                    help_text.push_str(&format!("\n- Press '{}' to show the space by file type", keys.label(Action::ToggleCategoryView)));
+                }
                 let paragraph = Paragraph::new(help_text)
                     .block(Block::default().borders(Borders::ALL).title("[ File Operations ]"));
-                f.render_widget(paragraph, right_chunks[1]);
+                f.render_widget(paragraph, layout.progress);
             }
         }
         // No else condition - hide panel when not needed
 
-        let file_op_keys = if app.focus == crate::PanelFocus::Right && (app.file_entries.is_some() || app.full_scan_results.is_some()) {
-            "File operations: Up/Down = navigate, d = delete, c = copy, m = move"
+        let file_op_keys = if app.focus == crate::PanelFocus::Right && (app.file_entries.is_some() || app.full_scan_results.is_some()) && !app.dir_view_active() {
+            format!(
+                "File operations: {}/{} = navigate, {} = delete, {} = copy, {} = move",
+                keys.label(Action::Up),
+                keys.label(Action::Down),
+                keys.label(Action::Delete),
+                keys.label(Action::Copy),
+                keys.label(Action::Move)
+            )
         } else {
-            ""
+            String::new()
         };
 
-        let legend_text = format!(
-            "j/k = up/down | Ctrl-l/Ctrl-h = switch panels | q = quit | ? = Help ...\n{}",
-            file_op_keys
-        );
+        let legend_text = if let AppMode::Command { input } = mode {
+            format!(":{}█", input)
+        } else if let AppMode::Rename { path, input } = mode {
+            format!("Rename {}\nNew name: {}█  (Enter = rename, Esc = cancel)", path, input)
+        } else if let AppMode::Archive { path, input } = mode {
+            format!("Archive {}\nInto: {}█  (Tab = zip/tar.gz, Enter = start, Esc = cancel)", path, input)
+        } else if let AppMode::Extract { path, input } = mode {
+            format!("Extract {}\nInto: {}█  (Enter = extract, Esc = cancel)", path, input)
+        } else if let AppMode::Breadcrumbs { .. } = mode {
+            format!(
+                "Jump to a directory of the path: ←/→ or {}/{} = choose, {} = go there, Esc = cancel",
+                keys.label(Action::Up),
+                keys.label(Action::Down),
+                keys.label(Action::OpenFolder)
+            )
+        } else {
+            format!(
+                "{}/{} = down/up | {}/{} = switch panels | {} = quit | {} = Help ...\n{}",
+                keys.label(Action::Down),
+                keys.label(Action::Up),
+                keys.label(Action::FocusRight),
+                keys.label(Action::FocusLeft),
+                keys.label(Action::Quit),
+                keys.label(Action::Help),
+                file_op_keys
+            )
+        };
         // Use smaller text for the legend
         let legend_text_spans = Spans::from(vec![
-            Span::styled(legend_text, Style::default().add_modifier(Modifier::DIM).fg(Color::White))
+            Span::styled(legend_text, Style::default().add_modifier(Modifier::DIM).fg(colors.legend))
         ]);
 
-        let legend = Paragraph::new(legend_text_spans)
-            .block(Block::default().borders(Borders::ALL).title("[ Legend ]"));
-        f.render_widget(legend, outer_chunks[1]);
+        let mut legend_title = match &app.notification {
+            Some(notification) => format!("[ Legend ] {}", notification),
+            None => "[ Legend ]".to_string(),
+        };
+        if let Some(profile) = profile::selected() {
+            legend_title.push_str(&format!(" [Profile: {}]", profile));
+        }
+        if app.on_battery {
+            legend_title.push_str(match app.config.scan.on_battery {
+                BatteryPolicy::Throttle => " [On battery: scans throttled]",
+                BatteryPolicy::Confirm => " [On battery: scans throttled, full scans ask first]",
+                BatteryPolicy::Ignore => " [On battery]",
+            });
+        }
+        let (running, pending) = app.operations.counts();
+        if running + pending > 0 {
+            legend_title.push_str(&format!(
+                " [Operations: {} running, {} queued ({} = show)]",
+                running,
+                pending,
+                keys.label(Action::Operations)
+            ));
+        }
+        if layout.compact {
+            let legend = Paragraph::new(compact_legend(app, mode, keys)).style(Style::default().add_modifier(Modifier::DIM).fg(colors.legend));
+            f.render_widget(legend, layout.legend);
+        } else {
+            let legend = Paragraph::new(legend_text_spans)
+                .block(Block::default().borders(Borders::ALL).title(legend_title));
+            f.render_widget(legend, layout.legend);
+        }
 
         match mode {
             AppMode::ConfirmEject(index) => {
                 if let Some(device) = app.devices.get(*index) {
-                    let popup_area = centered_rect(60, 20, size);
+                    let popup_area = centered_rect(60, 30, size);
 
                     // First, render a clear background to make it fully opaque
                     f.render_widget(Clear, popup_area);
 
+                    let text = match device.trash_size.filter(|size| *size > 0) {
+                        // Trashed files stay on the volume, out of sight, until the trash is emptied
+                        Some(trash_size) => format!(
+                            "Are you sure you want to eject this device?\n(Device: {})\n\nThis volume still holds {} of trashed files.\n\nPress T to empty the trash and eject, Y to eject, N to cancel.",
+                            device.name,
+                            format_size(trash_size, units)
+                        ),
+                        None => format!(
+                            "Are you sure you want to eject this device?\n(Device: {})\nPress Y to confirm, N to cancel.",
+                            device.name
+                        ),
+                    };
+                    let block = Block::default()
+                        .borders(Borders::ALL)
+                        .title("[ Confirm Eject ]")
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                    let paragraph = Paragraph::new(text).block(block);
+                    f.render_widget(paragraph, popup_area);
+                }
+            },
+            AppMode::ConfirmBatteryScan { incremental } => {
+                let popup_area = centered_rect(60, 30, size);
+                f.render_widget(Clear, popup_area);
+
+                let options = app.config.scan.options(true);
+                let text = format!(
+                    "Running on battery. A{} scan reads the whole device\nand drains the battery faster.\n\nScan anyway with {} thread{}? (Y/N)",
+                    if *incremental { "n incremental" } else { " full" },
+                    options.parallelism,
+                    if options.parallelism == 1 { "" } else { "s" }
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ On Battery ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmEjectAll => {
+                let popup_area = centered_rect(60, 40, size);
+                f.render_widget(Clear, popup_area);
+
+                let names: Vec<&str> = app.devices.iter().filter(|device| device.ejectable).map(|device| device.name.as_str()).collect();
+                let text = if names.is_empty() {
+                    "There are no ejectable devices.\nPress N to close.".to_string()
+                } else {
+                    format!(
+                        "Eject these devices, one after another?\n\n  {}\n\nPress Y to confirm, N to cancel.",
+                        names.join("\n  ")
+                    )
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Eject All ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmEmptyTrash(index) => {
+                if let Some(device) = app.devices.get(*index) {
+                    let popup_area = centered_rect(60, 20, size);
+                    f.render_widget(Clear, popup_area);
+
                     let text = format!(
-                        "Are you sure you want to eject this device?\n(Device: {})\nPress Y to confirm, N to cancel.",
-                        device.name
+                        "Permanently delete everything in the trash of {}?\n({} in the trash)\nPress Y to confirm, N to cancel.",
+                        device.name,
+                        format_size(device.trash_size.unwrap_or(0), units)
                     );
                     let block = Block::default()
                         .borders(Borders::ALL)
-                        .title("[ Confirm Eject ]")
-                        .style(Style::default().fg(Color::White).bg(Color::DarkGray));
+                        .title("[ Empty Trash ]")
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
                     let paragraph = Paragraph::new(text).block(block);
                     f.render_widget(paragraph, popup_area);
                 }
             },
+            AppMode::EjectFailed { device_index, error, holders } => {
+                let popup_area = centered_rect(70, 50, size);
+                f.render_widget(Clear, popup_area);
+
+                let device_name = app.devices.get(*device_index).map_or("device", |device| device.name.as_str());
+                let mut text = format!("Could not eject {}:\n{}\n\n", device_name, error);
+                match holders {
+                    Some(holders) if !holders.is_empty() => {
+                        text.push_str(&format!("In use by: {}\n\n", holders.join(", ")));
+                    }
+                    Some(_) => text.push_str("No processes found with open files on the volume.\n\n"),
+                    None => {}
+                }
+                for escalation in crate::platform::macos::EjectEscalation::ALL {
+                    text.push_str(&format!("  {}  {:<13} {}\n", escalation.key(), escalation.label(), escalation.risk()));
+                }
+                text.push_str("\nPress N or Esc to cancel.");
+
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Eject Failed ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::History { offset } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                // Newest first; each size is compared with the previous scan of the same target
+                let rows: Vec<Row> = app.scan_history
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .skip(*offset)
+                    .map(|(i, record)| {
+                        let change = app.scan_history[..i]
+                            .iter()
+                            .rev()
+                            .find(|previous| previous.same_target(record))
+                            .map(|previous| {
+                                let (sign, delta) = if record.total_size >= previous.total_size {
+                                    ("+", record.total_size - previous.total_size)
+                                } else {
+                                    ("-", previous.total_size - record.total_size)
+                                };
+                                format!("{}{}", sign, format_size(delta, units))
+                            })
+                            .unwrap_or_default();
+                        let top_entry = record.top_entry
+                            .as_ref()
+                            .map(|entry| format!("{} ({})", entry.path, format_size(entry.size, units)))
+                            .unwrap_or_default();
+                        Row::new(vec![
+                            record.finished_at.format("%Y-%m-%d %H:%M").to_string(),
+                            format!("{}{}", record.device, if record.junk { " (junk)" } else { "" }),
+                            format!("{:.1}s", record.duration_ms as f64 / 1000.0),
+                            format_size(record.total_size, units),
+                            change,
+                            format_count(record.file_count as u64),
+                            top_entry,
+                        ])
+                    })
+                    .collect();
+
+                let title = if app.scan_history.is_empty() {
+                    "[ Scan History ] No scans recorded yet".to_string()
+                } else {
+                    format!("[ Scan History ] [{}/{}]", offset + 1, app.scan_history.len())
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Finished", "Device", "Took", "Total", "Change", "Files", "Largest File"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[
+                        Constraint::Length(16),
+                        Constraint::Percentage(15),
+                        Constraint::Length(8),
+                        Constraint::Length(11),
+                        Constraint::Length(12),
+                        Constraint::Length(8),
+                        Constraint::Percentage(40),
+                    ]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::ScanErrors { offset } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                // How many errors each top-level directory (or junk path) has, then the errors
+                let junk_roots: Vec<String> = match (&app.scan_mode, &app.junk_categories) {
+                    (crate::ScanMode::JunkScan, Some(categories)) => categories.iter().flat_map(|category| category.roots.iter().cloned()).collect(),
+                    _ => Vec::new(),
+                };
+                let mount_point = app.devices.get(app.selected).map_or("/", |device| device.mount_point.as_str());
+                let per_root = analysis::errors_by_root(&app.scan_errors, &junk_roots, mount_point);
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(per_root.len().min(5) as u16 + 3), Constraint::Min(3)])
+                    .split(popup_area);
+                let style = Style::default().fg(colors.popup_fg).bg(colors.popup_bg);
+
+                let root_rows: Vec<Row> = per_root
+                    .iter()
+                    .map(|(root, count)| Row::new(vec![root.clone(), count.to_string()]))
+                    .collect();
+                let roots_table = Table::new(root_rows)
+                    .header(Row::new(vec!["Where", "Errors"]).style(Style::default().fg(colors.header)))
+                    .block(Block::default().borders(Borders::ALL).title("[ Scan Errors ]").style(style))
+                    .widths(&[Constraint::Percentage(85), Constraint::Length(8)]);
+                f.render_widget(roots_table, chunks[0]);
+
+                let rows: Vec<Row> = app.scan_errors
+                    .iter()
+                    .skip(*offset)
+                    .map(|error| Row::new(vec![error.path.clone(), error.message.clone()]))
+                    .collect();
+                let title = if app.scan_errors.is_empty() {
+                    "[ Unreadable ] The last scan read everything".to_string()
+                } else {
+                    format!("[ Unreadable, not counted ] [{}/{}]", offset + 1, app.scan_errors.len())
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Path", "Error"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default().borders(Borders::ALL).title(title).style(style))
+                    .widths(&[Constraint::Percentage(60), Constraint::Percentage(40)]);
+                f.render_widget(table, chunks[1]);
+            },
+            AppMode::StaleFiles { offset } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                let stale = app.config.stale;
+                let rows: Vec<Row> = app.stale_files
+                    .iter()
+                    .skip(*offset)
+                    .map(|file| {
+                        let last_used = chrono::DateTime::<chrono::Local>::from(file.last_used);
+                        Row::new(vec![file.path.clone(), format_size(file.size, units), last_used.format("%Y-%m-%d").to_string()])
+                    })
+                    .collect();
+                let total: u64 = app.stale_files.iter().map(|file| file.size).sum();
+                let title = if app.stale_files.is_empty() {
+                    format!("[ Stale Files ] None of at least {} MB unused for {} days", stale.min_size_mb, stale.unused_days)
+                } else {
+                    format!(
+                        "[ Stale Files ] {} of at least {} MB unused for {} days, {} [{}/{}]",
+                        app.stale_files.len(),
+                        stale.min_size_mb,
+                        stale.unused_days,
+                        format_size(total, units),
+                        offset + 1,
+                        app.stale_files.len()
+                    )
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Path", "Size", "Last used"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[Constraint::Percentage(70), Constraint::Length(11), Constraint::Length(11)]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::ScanChanges { offset } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                let style = Style::default().fg(colors.popup_fg).bg(colors.popup_bg);
+                let rows: Vec<Row> = app.scan_changes
+                    .iter()
+                    .skip(*offset)
+                    .map(|change| {
+                        let what = match (change.kind, change.is_dir) {
+                            (ChangeKind::Added, _) => "New file",
+                            (ChangeKind::Removed, _) => "Deleted",
+                            (ChangeKind::Grown, true) => "Dir grew",
+                            (ChangeKind::Grown, false) => "File grew",
+                            (ChangeKind::Shrunk, true) => "Dir shrank",
+                            (ChangeKind::Shrunk, false) => "File shrank",
+                        };
+                        let (delta, color) = if change.delta > 0 {
+                            (format!("+{}", format_size(change.delta as u64, units)), colors.bad)
+                        } else {
+                            (format!("-{}", format_size(change.delta.unsigned_abs(), units)), colors.good)
+                        };
+                        Row::new(vec![
+                            Cell::from(what),
+                            Cell::from(change.path.clone()),
+                            Cell::from(Span::styled(delta, style.fg(color))),
+                        ])
+                    })
+                    .collect();
+                let grown: u64 = app.scan_changes.iter().filter(|change| !change.is_dir && change.delta > 0).map(|change| change.delta as u64).sum();
+                let freed: u64 = app.scan_changes.iter().filter(|change| !change.is_dir && change.delta < 0).map(|change| change.delta.unsigned_abs()).sum();
+                let title = if app.scan_changes.is_empty() {
+                    "[ Changes Since the Previous Scan ] Nothing changed".to_string()
+                } else {
+                    format!(
+                        "[ Changes Since the Previous Scan ] files +{} / -{} [{}/{}]",
+                        format_size(grown, units),
+                        format_size(freed, units),
+                        offset + 1,
+                        app.scan_changes.len()
+                    )
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Change", "Path", "Size change"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default().borders(Borders::ALL).title(title).style(style))
+                    .widths(&[Constraint::Length(12), Constraint::Percentage(70), Constraint::Length(13)]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::BrokenLinks { offset } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                let rows: Vec<Row> = app.broken_links
+                    .iter()
+                    .skip(*offset)
+                    .map(|link| Row::new(vec![link.path.clone(), link.target.clone()]))
+                    .collect();
+                let title = if app.broken_links.is_empty() {
+                    "[ Broken Links ] Every symlink points to something".to_string()
+                } else {
+                    format!(
+                        "[ Broken Links ] {} pointing nowhere, {} deletes them all [{}/{}]",
+                        app.broken_links.len(),
+                        keys.label(Action::Delete),
+                        offset + 1,
+                        app.broken_links.len()
+                    )
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Link", "Missing target"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[Constraint::Percentage(55), Constraint::Percentage(45)]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::ConfirmBrokenLinkDelete => {
+                let popup_area = centered_rect(70, 25, size);
+                f.render_widget(Clear, popup_area);
+
+                let text = format!(
+                    "Delete {} broken symlinks?\n\nOnly the links are removed; what they pointed to is gone already.\n\nPress Y to confirm, N to cancel.",
+                    app.broken_links.len()
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Delete Broken Links ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::Duplicates { selected } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                // A heading row for each group, then its files; the selection counts files only
+                let mut rows = Vec::new();
+                let mut selected_row = 0;
+                let mut file_index = 0;
+                for group in &app.duplicate_groups {
+                    rows.push(
+                        Row::new(vec![
+                            format!("{} copies of {}", group.files.len(), format_size(group.size, units)),
+                            format!("{} wasted", format_size(group.wasted(), units)),
+                        ])
+                        .style(Style::default().fg(colors.header)),
+                    );
+                    for file in &group.files {
+                        let style = if file_index == *selected {
+                            selected_row = rows.len();
+                            Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default()
+                        };
+                        let modified = app
+                            .duplicate_modified
+                            .get(&file.path)
+                            .map(|modified| chrono::DateTime::<chrono::Local>::from(*modified).format("%Y-%m-%d %H:%M").to_string());
+                        rows.push(Row::new(vec![format!("  {}", file.path), modified.unwrap_or_default()]).style(style));
+                        file_index += 1;
+                    }
+                }
+                // Keep the highlighted file and its heading in sight below the header
+                let visible = popup_area.height.saturating_sub(4).max(1) as usize;
+                let offset = selected_row.saturating_sub(visible - 1);
+                let rows: Vec<Row> = rows.into_iter().skip(offset).collect();
+
+                let title = if app.duplicate_groups.is_empty() {
+                    "[ Duplicates ] No two files hold the same content".to_string()
+                } else {
+                    let wasted: u64 = app.duplicate_groups.iter().map(|group| group.wasted()).sum();
+                    format!(
+                        "[ Duplicates ] {} groups, {} wasted. n = keep the newest of each, f = keep the copies in the highlighted file's folder",
+                        app.duplicate_groups.len(),
+                        format_size(wasted, units)
+                    )
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["File", "Modified"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[Constraint::Percentage(75), Constraint::Length(16)]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::ConfirmDuplicateResolve { .. } => {
+                let popup_area = centered_rect(80, 60, size);
+                f.render_widget(Clear, popup_area);
+
+                let mut lines = Vec::new();
+                if let Some(plan) = &app.duplicate_plan {
+                    lines.push(format!(
+                        "Dry run: {} copies would move to the trash, reclaiming {} once it is emptied.",
+                        plan.removals.len(),
+                        format_size(plan.freed, units)
+                    ));
+                    if plan.skipped > 0 {
+                        lines.push(format!("{} groups have no copy in that folder and stay as they are.", plan.skipped));
+                    }
+                    lines.push(String::new());
+                    // Leave room for the lines around the list
+                    let shown = (popup_area.height as usize).saturating_sub(lines.len() + 6).max(1);
+                    lines.extend(plan.removals.iter().take(shown).map(|removal| format!("- {}  (keeps {})", removal.remove.path, removal.keep)));
+                    if plan.removals.len() > shown {
+                        lines.push(format!("  and {} more", plan.removals.len() - shown));
+                    }
+                    lines.push(String::new());
+                }
+                lines.push("Each copy is compared byte by byte with the one kept first.".to_string());
+                lines.push("Press Y to delete them, N to pick another resolution.".to_string());
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Resolve Duplicates ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(lines.join("\n")).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::Goal { selected } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+                if let Some(goal) = &app.goal {
+                    let block = Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("[ Goal: {} free on {} ]", format_size(goal.target, units), goal.device))
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                    let inner = block.inner(popup_area);
+                    f.render_widget(block, popup_area);
+                    let parts = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(1)])
+                        .split(inner);
+
+                    // Free space now plus what the accepted candidates free, towards the target
+                    let accepted = goal.plan.accepted_size();
+                    let free = goal.available + accepted;
+                    let percent = (free as f64 * 100.0 / goal.target.max(1) as f64).min(100.0) as u16;
+                    let label = if goal.plan.is_met() {
+                        format!("{} free with the accepted candidates: goal reached", format_size(free, units))
+                    } else {
+                        format!("{} free with the accepted candidates, {} to go", format_size(free, units), format_size(goal.target - free, units))
+                    };
+                    let gauge = Gauge::default()
+                        .gauge_style(Style::default().fg(colors.usage_gauge).bg(colors.gauge_bg))
+                        .percent(percent)
+                        .label(Span::raw(label));
+                    f.render_widget(gauge, parts[0]);
+
+                    // Keep the highlighted candidate in sight below the header
+                    let visible = parts[2].height.saturating_sub(2).max(1) as usize;
+                    let offset = selected.saturating_sub(visible - 1);
+                    let rows: Vec<Row> = goal.plan.candidates
+                        .iter()
+                        .zip(&goal.plan.accepted)
+                        .enumerate()
+                        .skip(offset)
+                        .map(|(i, (candidate, accepted))| {
+                            let style = if i == *selected {
+                                Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                            } else {
+                                Style::default()
+                            };
+                            Row::new(vec![
+                                if *accepted { "[x]" } else { "[ ]" }.to_string(),
+                                candidate.source.label().to_string(),
+                                format_size(candidate.size(), units),
+                                candidate.label.clone(),
+                            ])
+                            .style(style)
+                        })
+                        .collect();
+                    let table = Table::new(rows)
+                        .header(
+                            Row::new(vec!["", "Source", "Size", "What"])
+                                .style(Style::default().fg(colors.header))
+                                .bottom_margin(1),
+                        )
+                        .widths(&[
+                            Constraint::Length(3),
+                            Constraint::Length(12),
+                            Constraint::Length(11),
+                            Constraint::Percentage(75),
+                        ]);
+                    f.render_widget(Paragraph::new("Space = accept, a = accept enough, Enter = run the plan, Esc = close"), parts[1]);
+                    f.render_widget(table, parts[2]);
+                }
+            },
+            AppMode::ConfirmGoal { .. } => {
+                let popup_area = centered_rect(70, 30, size);
+                f.render_widget(Clear, popup_area);
+
+                let mut lines = Vec::new();
+                if let Some(goal) = &app.goal {
+                    let (junk, rest): (Vec<_>, Vec<_>) = goal.plan.accepted().partition(|candidate| candidate.source == analysis::GoalSource::Junk);
+                    let size = |candidates: &[&analysis::GoalCandidate]| format_size(candidates.iter().map(|candidate| candidate.size()).sum(), units);
+                    let files = |candidates: &[&analysis::GoalCandidate]| candidates.iter().map(|candidate| candidate.files.len()).sum::<usize>();
+                    if !junk.is_empty() {
+                        lines.push(format!("Delete {} junk files for good, freeing {}.", files(&junk), size(&junk)));
+                    }
+                    if !rest.is_empty() {
+                        lines.push(format!("Move {} files to the trash, {} freed once it is emptied.", files(&rest), size(&rest)));
+                    }
+                    if !goal.plan.is_met() {
+                        lines.push(format!("This falls {} short of the goal.", format_size(goal.plan.needed - goal.plan.accepted_size(), units)));
+                    }
+                    lines.push(String::new());
+                }
+                lines.push("Press Y to run the plan, N to change it.".to_string());
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Free Up Space ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                f.render_widget(Paragraph::new(lines.join("\n")).block(block), popup_area);
+            },
+            AppMode::LargestDirs { selected } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                // Keep the highlighted directory in sight below the header
+                let visible = popup_area.height.saturating_sub(4).max(1) as usize;
+                let offset = selected.saturating_sub(visible - 1);
+                let rows: Vec<Row> = app.largest_dirs
+                    .iter()
+                    .enumerate()
+                    .skip(offset)
+                    .map(|(i, (dir, total))| {
+                        let style = if i == *selected {
+                            Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default()
+                        };
+                        Row::new(vec![
+                            format!("{}.", i + 1),
+                            dir.clone(),
+                            format_size(total.size, units),
+                            format_count(total.file_count as u64),
+                        ])
+                        .style(style)
+                    })
+                    .collect();
+
+                let title = format!(
+                    "[ Largest Directories ] {} = show in the directory view, Esc = close",
+                    keys.label(Action::OpenFolder)
+                );
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["#", "Directory", "Size", "Files"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[
+                        Constraint::Length(4),
+                        Constraint::Percentage(70),
+                        Constraint::Length(11),
+                        Constraint::Length(9),
+                    ]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::Operations { selected } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                let rows: Vec<Row> = app.operations
+                    .operations()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, op)| {
+                        let (state, progress) = match (&op.state, &op.job) {
+                            (OperationState::Running, Some(job)) => {
+                                let (done, total) = job.progress();
+                                (
+                                    format!("{} {}%", op.label(), job_percent(job)),
+                                    format!(
+                                        "{} / {}, {}/s, ETA {}",
+                                        format_size(done, units),
+                                        format_size(total, units),
+                                        format_size(job.speed() as u64, units),
+                                        job_eta(job)
+                                    ),
+                                )
+                            }
+                            (OperationState::Pending | OperationState::Running, _) => ("Queued".to_string(), String::new()),
+                            (OperationState::Done(result), _) => ("Done".to_string(), result.clone()),
+                            (OperationState::Failed(err), _) => ("Failed".to_string(), err.clone()),
+                            (OperationState::Cancelled, _) => ("Cancelled".to_string(), String::new()),
+                        };
+                        let path = match &op.target {
+                            Some(target) => format!("{} -> {}", op.source, target),
+                            None => op.source.clone(),
+                        };
+                        let style = if i == *selected {
+                            Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                        } else if op.state.is_finished() {
+                            Style::default().add_modifier(Modifier::DIM)
+                        } else {
+                            Style::default()
+                        };
+                        Row::new(vec![op.id.to_string(), state, path, progress]).style(style)
+                    })
+                    .collect();
+
+                let title = if app.operations.operations().is_empty() {
+                    "[ Operations ] No copies, moves or deletes yet".to_string()
+                } else {
+                    format!(
+                        "[ Operations ] {} = cancel highlighted, {} = close",
+                        keys.label(Action::Cancel),
+                        keys.label(Action::Operations)
+                    )
+                };
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["#", "State", "Path", "Progress"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[
+                        Constraint::Length(4),
+                        Constraint::Length(14),
+                        Constraint::Percentage(50),
+                        Constraint::Percentage(40),
+                    ]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::JunkSuggestions { selected } => {
+                let popup_area = centered_rect(90, 70, size);
+                f.render_widget(Clear, popup_area);
+
+                let rows: Vec<Row> = app.junk_suggestions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, suggestion)| {
+                        let why: Vec<&str> = suggestion.hints.iter().map(|hint| hint.label()).collect();
+                        let style = if i == *selected {
+                            Style::default().fg(colors.highlight).add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default()
+                        };
+                        Row::new(vec![
+                            suggestion.path.clone(),
+                            format_size(suggestion.size, units),
+                            format_count(suggestion.file_count as u64),
+                            why.join(", "),
+                        ])
+                        .style(style)
+                    })
+                    .collect();
+
+                let title = format!(
+                    "[ Junk suggestions ] {} = add highlighted to your junk paths, {} = close",
+                    keys.label(Action::AcceptSuggestion),
+                    keys.label(Action::JunkSuggestions)
+                );
+                let table = Table::new(rows)
+                    .header(
+                        Row::new(vec!["Path", "Size", "Files", "Why"])
+                            .style(Style::default().fg(colors.header))
+                            .bottom_margin(1),
+                    )
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg)))
+                    .widths(&[
+                        Constraint::Percentage(50),
+                        Constraint::Length(11),
+                        Constraint::Length(8),
+                        Constraint::Percentage(30),
+                    ]);
+                f.render_widget(table, popup_area);
+            },
+            AppMode::ConfirmUndo => {
+                let popup_area = centered_rect(70, 25, size);
+                f.render_widget(Clear, popup_area);
+
+                let text = match app.operations.last_revert() {
+                    Some(revert) => format!(
+                        "Undo the last operation?\n\n{}\n\nPress Y to confirm, N to cancel.",
+                        revert.description()
+                    ),
+                    None => "There is nothing to undo.\nPress N to close.".to_string(),
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Confirm Undo ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmFolderDelete { folder_index } => {
+                let popup_area = centered_rect(70, 30, size);
+                f.render_widget(Clear, popup_area);
+
+                let text = match app.folder_summaries.as_ref().and_then(|folders| folders.get(*folder_index)) {
+                    Some(folder) => format!(
+                        "Delete all junk files in\n{}?\n\n{} files, {}\n\nOnly the files found by the junk scan are removed; the folder itself stays.\n\nPress Y to confirm, N to cancel.",
+                        folder.path,
+                        format_count(folder.file_count as u64),
+                        format_size(folder.total_size, units)
+                    ),
+                    None => "The folder is no longer in the results.\n\nPress N to cancel.".to_string(),
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Delete Junk Folder ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmClean => {
+                let popup_area = centered_rect(70, 60, size);
+                f.render_widget(Clear, popup_area);
+
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Confirm Clean ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(clean_preview_text(app)).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmDockerPrune { selected } => {
+                let popup_area = centered_rect(80, 50, size);
+                f.render_widget(Clear, popup_area);
+
+                let mut text = String::from("Prune Docker data:\n\n");
+                for resource in crate::platform::docker::DockerResource::ALL {
+                    let reclaimable = app.docker_usage
+                        .iter()
+                        .flatten()
+                        .find(|usage| usage.resource == resource)
+                        .map_or(0, |usage| usage.reclaimable);
+                    text.push_str(&format!(
+                        "  {} {}  {:<19} frees {:>10}  {}\n",
+                        if selected.contains(&resource) { "[x]" } else { "[ ]" },
+                        resource.key(),
+                        resource.label(),
+                        format_size(reclaimable, units),
+                        resource.prune_description()
+                    ));
+                }
+                text.push_str("\nPress a letter to check/uncheck, Y to prune the checked data, N to cancel.");
+
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Docker Cleanup ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::ConfirmBrowserCacheClear { selected } => {
+                let popup_area = centered_rect(80, 50, size);
+                f.render_widget(Clear, popup_area);
+
+                let mut text = String::from("Clear browser caches (history, extensions and settings are kept):\n\n");
+                for (index, profile) in app.browser_profiles.iter().enumerate() {
+                    text.push_str(&format!(
+                        "  {} {}  {:<28} frees {:>10}{}\n",
+                        if selected.contains(&index) { "[x]" } else { "[ ]" },
+                        if index < 9 { (index + 1).to_string() } else { " ".to_string() },
+                        format!("{} {}", profile.browser.label(), profile.name),
+                        format_size(profile.cache_size, units),
+                        if app.running_browsers.contains(&profile.browser) { "  (running - quit it first)" } else { "" }
+                    ));
+                }
+                text.push_str("\nPress a number to check/uncheck, Y to clear the checked caches, N to cancel.");
+
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Browser Caches ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
             AppMode::Ejected(msg) => {
                 let popup_area = centered_rect(60, 20, size);
 
@@ -490,7 +2662,19 @@ pub fn draw_app<B: Backend>(
                 let block = Block::default()
                     .borders(Borders::ALL)
                     .title("[ Ejection Result ]")
-                    .style(Style::default().fg(Color::White).bg(Color::DarkGray));
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
+                let paragraph = Paragraph::new(text).block(block);
+                f.render_widget(paragraph, popup_area);
+            },
+            AppMode::Working(description) => {
+                let popup_area = centered_rect(60, 20, size);
+                f.render_widget(Clear, popup_area);
+
+                let text = format!("{}...\n\nEsc hides this; it keeps running and reports back when done.", description);
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Working ]")
+                    .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
                 let paragraph = Paragraph::new(text).block(block);
                 f.render_widget(paragraph, popup_area);
             },
@@ -513,12 +2697,21 @@ pub fn draw_app<B: Backend>(
                 };
 
                 if let Some(file) = file_option {
-                    let popup_area = centered_rect(70, 30, size);
+                    // Deletion previews list the directories that would shrink and overwrites show a
+                    // diff, so give them more room
+                    let overwriting = matches!(op_type, crate::FileOperation::Copy | crate::FileOperation::Move)
+                        && app.overwrite_preview.as_ref().is_some_and(|preview| target_path.as_ref() == Some(&preview.target));
+                    let popup_height = if matches!(op_type, crate::FileOperation::Delete | crate::FileOperation::SecureDelete { .. }) { 60 } else if overwriting { 80 } else { 30 };
+                    let popup_area = centered_rect(70, popup_height, size);
 
                     // Clear the background first
                     f.render_widget(Clear, popup_area);
 
                     let (title, message) = match op_type {
+                        crate::FileOperation::Copy | crate::FileOperation::Move if let Some(text) = target_path.as_ref().and_then(|target| overwrite_text(app, &file.path, target)) => (
+                            if *op_type == crate::FileOperation::Copy { "[ Confirm Copy: Replace Existing File? ]" } else { "[ Confirm Move: Replace Existing File? ]" },
+                            text,
+                        ),
                         crate::FileOperation::Copy => {
                             let default_dest = "destination".to_string();
                             let target = target_path.as_ref().unwrap_or(&default_dest);
@@ -544,16 +2737,76 @@ pub fn draw_app<B: Backend>(
                         crate::FileOperation::Delete => (
                             "[ Confirm Delete ]",
                             format!(
-                                "Are you sure you want to delete this file?\n\nFile: {}\n\nThis action cannot be undone!\n\nPress Y to confirm, N to cancel.",
-                                file.path
+                                "Are you sure you want to delete this {}?\n\nPath: {}\n{}{}\nIt is moved to the trash; '{}' undoes the delete.\n\nPress Y to confirm, N to cancel.",
+                                if std::path::Path::new(&file.path).is_dir() { "directory" } else { "file" },
+                                file.path,
+                                open_files_text(app, &file.path),
+                                deletion_preview_text(app, &file.path),
+                                keys.label(Action::Undo)
                             )
                         ),
+                        crate::FileOperation::SecureDelete { passes } => {
+                            let warning = match &app.erase_warning {
+                                Some((target, warning)) if *target == file.path => {
+                                    warning.as_ref().map_or_else(|| "The disk spins and the file system writes in place, so overwriting reaches the old contents.".to_string(), |warning| format!("⚠ {}", warning))
+                                }
+                                _ => "Checking the disk…".to_string(),
+                            };
+                            (
+                                "[ Confirm Secure Delete ]",
+                                format!(
+                                    "Are you sure you want to securely erase this {}?\n\nPath: {}\n\nEvery file is overwritten {} times, then deleted. It doesn't go to the trash and can't be undone.\n\n{}\nOn SSDs and copy-on-write file systems (APFS, btrfs, ZFS) overwriting is meaningless: use full-disk encryption or the disk's own secure erase there.\n\nPress Y to confirm, N to cancel.",
+                                    if std::path::Path::new(&file.path).is_dir() { "directory" } else { "file" },
+                                    file.path,
+                                    (*passes).max(1),
+                                    warning
+                                )
+                            )
+                        },
+                        crate::FileOperation::Archive => {
+                            let default_dest = "destination".to_string();
+                            let target = target_path.as_ref().unwrap_or(&default_dest);
+                            (
+                                "[ Confirm Archive ]",
+                                format!(
+                                    "Are you sure you want to archive this file?\n\nSource: {}\nArchive: {}\n\nPress Y to confirm, N to cancel.",
+                                    file.path, target
+                                )
+                            )
+                        },
+                        crate::FileOperation::Extract => {
+                            let default_dest = "destination".to_string();
+                            let target = target_path.as_ref().unwrap_or(&default_dest);
+                            (
+                                "[ Confirm Extract ]",
+                                format!(
+                                    "Are you sure you want to extract this archive?\n\nArchive: {}\nInto: {}\n\nPress Y to confirm, N to cancel.",
+                                    file.path, target
+                                )
+                            )
+                        },
                     };
 
+                    let message = match strong_confirmation(op_type, &file.path) {
+                        Some(safety) => {
+                            let name = confirmation_name(&file.path);
+                            let prompt = format!(
+                                "⚠ This is {}, not your own data. Type its name, {}, and press Enter to confirm; Esc cancels.\n> {}█",
+                                safety.label(),
+                                name,
+                                app.confirm_input
+                            );
+                            match message.strip_suffix("Press Y to confirm, N to cancel.") {
+                                Some(rest) => format!("{}{}", rest, prompt),
+                                None => format!("{}\n\n{}", message, prompt),
+                            }
+                        }
+                        None => message,
+                    };
                     let block = Block::default()
                         .borders(Borders::ALL)
                         .title(title)
-                        .style(Style::default().fg(Color::White).bg(Color::DarkGray));
+                        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg));
                     let paragraph = Paragraph::new(message).block(block);
                     f.render_widget(paragraph, popup_area);
                 }
@@ -568,45 +2821,249 @@ pub fn draw_app<B: Backend>(
             // Clear the background first
             f.render_widget(Clear, help_area);
 
-            let help_text = "
-            LAZYSMG KEYBOARD SHORTCUTS
-
-Navigation:
------------
-j, Down       : Move down in current panel
-k, Up         : Move up in current panel
-Ctrl+h        : Focus left panel (devices)
-Ctrl+l        : Focus right panel (files)
-?             : Show/hide this help screen
-
-Device Operations:
------------------
-r             : Refresh device list
-e             : Eject selected device (if ejectable)
-
-File Operations (when right panel is focused):
---------------------------------------------
-s             : Scan current directory (non-recursive)
-S             : Full device scan with progress bar
-d             : Delete selected file (requires confirmation)
-c             : Copy selected file (requires confirmation)
-m             : Move selected file (requires confirmation)
-
-General:
--------
-q             : Quit application
-            ";
+            let help_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+                .split(help_area);
+
+            let search_paragraph = Paragraph::new(format!("{}█", app.help_query))
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title("[ Search keys, commands and config options ]")
+                    .border_style(Style::default().fg(colors.accent))
+                    .style(Style::default().bg(colors.popup_bg)))
+                .style(Style::default().fg(colors.popup_fg));
+            f.render_widget(search_paragraph, help_chunks[0]);
+
+            let help_text = if app.help_query.trim().is_empty() {
+                help_text(keys)
+            } else {
+                help_search_text(keys, &app.help_query)
+            };
 
             let help_paragraph = Paragraph::new(help_text)
                 .block(Block::default()
                     .borders(Borders::ALL)
-                    .title("[ Help (press ? to close) ]")
-                    .border_style(Style::default().fg(Color::Cyan))
-                    .style(Style::default().bg(Color::DarkGray)))
-                .style(Style::default().fg(Color::White));
+                    .title(format!("[ Help (press {} to close) ]", keys.label(Action::Help)))
+                    .border_style(Style::default().fg(colors.accent))
+                    .style(Style::default().bg(colors.popup_bg)))
+                .style(Style::default().fg(colors.popup_fg));
 
-            f.render_widget(help_paragraph, help_area);
+            f.render_widget(help_paragraph, help_chunks[1]);
         }
     })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::FileOperation;
+    use crate::scanner::FileEntry;
+    use crate::{FolderSummary, PanelFocus, ScanMode};
+    use ratatui::backend::TestBackend;
+
+    fn device(name: &str, mount: &str, mounted: bool) -> StorageDevice {
+        serde_json::from_value(serde_json::json!({
+            "name": name, "total_space": 1_000_000, "available_space": 400_000, "mount_point": mount,
+            "ejectable": true, "vendor_info": null, "smart": null, "uuid": null, "container": null,
+            "encrypted": null, "trash_size": 1000, "mounted": mounted,
+        }))
+        .unwrap()
+    }
+
+    fn entries(count: usize) -> Vec<FileEntry> {
+        (0..count)
+            .map(|i| FileEntry { name: format!("fïle-{}.rs", i), path: format!("/mnt/dïsk/ünïcödé/{}/fïle-{}.rs", "ß".repeat(i % 40), i), size: i as u64 * 1000, link_target: None, allocated_size: None })
+            .collect()
+    }
+
+    /// Small deterministic generator, so a failing run can be replayed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn random_mode(rng: &mut Rng) -> AppMode {
+        let index = rng.below(50);
+        match rng.below(31) {
+            0 => AppMode::Normal,
+            1 => AppMode::ConfirmEject(index),
+            2 => AppMode::ConfirmEmptyTrash(index),
+            3 => AppMode::EjectFailed { device_index: index, error: "busy".to_string(), holders: Some(vec!["Finder".to_string()]) },
+            4 => AppMode::Scanning { device_index: index, spinner_index: index },
+            5 => AppMode::FullScan { device_index: index, spinner_index: index },
+            6 => AppMode::ConfirmFileOp { op_type: FileOperation::Delete, file_index: index, target_path: None },
+            7 => AppMode::ConfirmFileOp { op_type: FileOperation::Copy, file_index: index, target_path: Some("/tmp/x".to_string()) },
+            8 => AppMode::DeviceSearch { query: "dï".to_string(), previous: index },
+            9 => AppMode::Command { input: "history".to_string() },
+            10 => AppMode::History { offset: index },
+            11 => AppMode::Operations { selected: index },
+            12 => AppMode::JunkSuggestions { selected: index },
+            13 => AppMode::Breadcrumbs { selected: index },
+            14 => AppMode::ConfirmFolderDelete { folder_index: index },
+            15 => AppMode::ConfirmBrowserCacheClear { selected: vec![index] },
+            16 => AppMode::Rename { path: "/mnt/dïsk/a".to_string(), input: "b".to_string() },
+            17 => AppMode::ConfirmClean,
+            18 => AppMode::Ejected("done".to_string()),
+            19 => AppMode::Working("Ejecting Dïsk".to_string()),
+            20 => AppMode::ScanErrors { offset: index },
+            21 => AppMode::LargestDirs { selected: index },
+            22 => AppMode::StaleFiles { offset: index },
+            23 => AppMode::BrokenLinks { offset: index },
+            24 => AppMode::ConfirmBrokenLinkDelete,
+            25 => AppMode::ScanChanges { offset: index },
+            26 => AppMode::Duplicates { selected: index },
+            27 => AppMode::ConfirmDuplicateResolve { selected: index },
+            28 => AppMode::Goal { selected: index },
+            29 => AppMode::ConfirmGoal { selected: index },
+            _ => AppMode::ConfirmUndo,
+        }
+    }
+
+    /// Change the state the UI reads in ways a device vanishing or a scan finishing between two
+    /// frames could.
+    fn mutate(app: &mut App, rng: &mut Rng) {
+        match rng.below(15) {
+            0 => {
+                if !app.devices.is_empty() {
+                    let index = rng.below(app.devices.len());
+                    app.devices.remove(index);
+                }
+            }
+            1 => app.devices.push(device("Nëw", "/mnt/nëw", rng.below(2) == 0)),
+            2 => app.selected = rng.below(6),
+            3 => app.file_entries = [None, Some(Vec::new()), Some(entries(rng.below(60)))][rng.below(3)].clone(),
+            4 => {
+                app.full_scan_results = [None, Some(Vec::new()), Some(entries(rng.below(60)))][rng.below(3)].clone();
+                if let Some(results) = app.full_scan_results.as_ref().filter(|_| rng.below(2) == 0) {
+                    app.size_deltas = Some(analysis::SizeDeltas::between(std::path::Path::new("/mnt/dïsk"), &entries(rng.below(60)), results));
+                }
+                app.set_sort_by_delta(rng.below(2) == 0);
+                if rng.below(2) == 0 {
+                    app.toggle_dir_view();
+                }
+                if rng.below(2) == 0 {
+                    app.toggle_type_breakdown();
+                }
+                if rng.below(2) == 0 {
+                    app.rank_largest_dirs();
+                }
+            }
+            5 => app.selected_file_index = rng.below(80),
+            6 => app.file_list_offset = rng.below(80),
+            7 => {
+                app.folder_summaries = Some((0..rng.below(5)).map(|i| FolderSummary { path: format!("/mnt/dïsk/{}", i), total_size: 10, file_count: 1 }).collect());
+                app.folder_view_mode = rng.below(2) == 0;
+                app.selected_folder_index = rng.below(10);
+            }
+            8 => {
+                app.scan_mode = [ScanMode::FullScan, ScanMode::JunkScan, ScanMode::DevArtifacts, ScanMode::Xcode][rng.below(4)].clone();
+                app.scan_progress.in_progress = rng.below(2) == 0;
+                app.scan_progress.total_bytes = rng.below(3) as u64 * 1000;
+                app.scan_progress.scanned_bytes = rng.below(5000) as u64;
+                app.scan_progress.files_processed = rng.below(3) as u64;
+                app.scan_progress.current_file = Some(format!("/mnt/{}/fïle", "é".repeat(rng.below(60))));
+            }
+            9 => app.browse_dir = [None, Some("/mnt/dïsk/ünïcödé".to_string()), Some("/elsewhere".to_string())][rng.below(3)].clone(),
+            10 => app.focus = if rng.below(2) == 0 { PanelFocus::Left } else { PanelFocus::Right },
+            13 => {
+                app.stale_files = (0..rng.below(30))
+                    .map(|i| analysis::StaleFile { path: format!("/mnt/dïsk/öld{}.iso", i), size: 1 << 30, last_used: std::time::SystemTime::UNIX_EPOCH })
+                    .collect();
+                app.scan_errors = (0..rng.below(30))
+                    .map(|i| crate::scanner::ScanError { path: format!("/mnt/dïsk/lö{}/ckéd", i % 4), message: "Permission denied (os error 13)".to_string() })
+                    .collect();
+                app.scan_changes = (0..rng.below(30))
+                    .map(|i| analysis::PathChange { path: format!("/mnt/dïsk/chänged{}", i), is_dir: i % 2 == 0, kind: analysis::ChangeKind::Grown, delta: 1 << 20 })
+                    .collect();
+                app.broken_links = (0..rng.below(30))
+                    .map(|i| crate::scanner::BrokenLink { path: format!("/mnt/dïsk/lïnk{}", i), target: format!("/Volumes/göne/{}", i) })
+                    .collect();
+                app.last_opened = entries(rng.below(60)).into_iter().step_by(2).map(|file| (file.path, std::time::SystemTime::UNIX_EPOCH)).collect();
+                app.duplicate_groups = entries(rng.below(60))
+                    .chunks(3)
+                    .filter(|files| files.len() > 1)
+                    .map(|files| analysis::DuplicateGroup { size: files[0].size, files: files.to_vec() })
+                    .collect();
+                app.duplicate_modified = app.duplicate_groups.iter().flat_map(|group| &group.files).step_by(2).map(|file| (file.path.clone(), std::time::SystemTime::UNIX_EPOCH)).collect();
+                app.duplicate_plan = (rng.below(2) == 0).then(|| {
+                    analysis::plan_duplicate_removal(&app.duplicate_groups, &analysis::KeepRule::Under("/mnt/dïsk".into()), |_| None)
+                });
+                app.goal = (rng.below(2) == 0).then(|| {
+                    let candidates = entries(rng.below(40))
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, file)| analysis::GoalCandidate { source: [analysis::GoalSource::Junk, analysis::GoalSource::ColdFile][i % 2], label: file.path.clone(), files: vec![file], keep: None })
+                        .collect();
+                    let mut plan = analysis::GoalPlan::new(rng.below(1 << 20) as u64, candidates);
+                    if rng.below(2) == 0 {
+                        plan.accept_until_met();
+                    }
+                    crate::goal::Goal { device: "Dïsk".to_string(), target: 1 << 30, available: rng.below(1 << 20) as u64, plan }
+                });
+            }
+            11 => {
+                app.scanning = rng.below(2) == 0;
+                app.largest_so_far = [None, Some(entries(rng.below(120)))][rng.below(2)].clone();
+                app.show_largest_so_far = rng.below(3) > 0;
+            }
+            12 => {
+                app.preview_pane = !app.preview_pane;
+                app.show_help = rng.below(4) == 0;
+                app.help_query = ["", "stale", "zzz"][rng.below(3)].to_string();
+                app.confirm_input = ["", "fïle-1.rs", "ünïcödé"][rng.below(3)].to_string();
+            }
+            _ => {
+                if rng.below(2) == 0 {
+                    app.open_tab();
+                } else {
+                    app.close_tab();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exit_summary_lists_scans_and_cleanups() {
+        let mut app = App::new(vec![device("Dïsk", "/mnt/dïsk", true)], crate::config::Config::default());
+        let summary = exit_summary(&app);
+        assert!(summary.starts_with("lazysmg: Internal storage "), "{}", summary);
+        assert!(summary.ends_with("\nDïsk (/mnt/dïsk): 585.94 KB used of 976.56 KB, 390.62 KB free"), "{}", summary);
+
+        let files = entries(3);
+        app.session.scans.push(crate::storage::scan_history::ScanRecord::new("Dïsk", "/mnt/dïsk", false, 1500, &files));
+        app.session.add_freed("2 build artifact directories", 3 * 1024 * 1024);
+        app.session.add_freed("Trash of Dïsk", 1024 * 1024);
+        app.session.trashed = (2, 2048);
+        let summary = exit_summary(&app);
+        assert!(!summary.contains("used of"), "{}", summary);
+        assert!(summary.contains("\nScanned Dïsk (/mnt/dïsk) in 1s: 2.93 KB in 3 files\n"), "{}", summary);
+        assert!(summary.contains(&format!("\n  Largest: {} (1.95 KB)\n", files[2].path)), "{}", summary);
+        assert!(summary.contains("\nFreed 4.00 MB\n  2 build artifact directories: 3.00 MB\n  Trash of Dïsk: 1.00 MB\n"), "{}", summary);
+        assert!(summary.ends_with("\nMoved 2.00 KB to the trash (2 deletes), freed once it is emptied"), "{}", summary);
+    }
+
+    #[test]
+    fn rendering_survives_state_changes_between_frames() {
+        let sizes = [(120, 40), (80, 24), (30, 8), (3, 2)];
+        for seed in 1..=25u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let devices = vec![device("Dïsk", "/mnt/dïsk", true), device("Other", "/mnt/other", false)];
+            let mut app = App::new(devices, crate::config::Config::default());
+            for frame in 0..50 {
+                mutate(&mut app, &mut rng);
+                let mode = random_mode(&mut rng);
+                let (width, height) = sizes[frame % sizes.len()];
+                let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+                draw_app(&mut terminal, &app, &mode, &["|", "/", "-", "\\"]).unwrap_or_else(|e| panic!("seed {} frame {}: {}", seed, frame, e));
+            }
+        }
+    }
+}